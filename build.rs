@@ -0,0 +1,197 @@
+use std::{env, fs, path::Path};
+
+// Generate the C header of the C API, `include/rustracer.h`, from `src/ffi.rs`: an opaque type for every public
+// struct, a typedef for every function pointer type and a prototype for every exported function, each with the
+// comment above it up to its safety requirements. The file is only written when it changes.
+fn main() {
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=build.rs");
+    let root = env::var("CARGO_MANIFEST_DIR").expect("cargo sets the manifest directory");
+    let source = fs::read_to_string(Path::new(&root).join("src/ffi.rs")).expect("src/ffi.rs is readable");
+    let header = header(&source);
+    let path = Path::new(&root).join("include/rustracer.h");
+    if fs::read_to_string(&path).ok().as_deref() != Some(header.as_str()) {
+        fs::create_dir_all(path.parent().unwrap()).expect("include/ can be created");
+        fs::write(&path, header).expect("include/rustracer.h can be written");
+    }
+}
+
+const PREAMBLE: &str = "\
+/*
+ * C API of rustracer, generated by `build.rs` from `src/ffi.rs`. Don't edit it, change the functions there.
+ *
+ * Build the library with `cargo rustc --lib --release --crate-type cdylib` (or `staticlib`) and link
+ * `target/release/librustracer.so` (or `.a`, which also needs `-lpthread -ldl -lm`).
+ */
+
+#ifndef RUSTRACER_H
+#define RUSTRACER_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern \"C\" {
+#endif
+";
+
+const POSTAMBLE: &str = "
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* RUSTRACER_H */
+";
+
+const MAX_WIDTH: usize = 120;
+
+fn header(source: &str) -> String {
+    let mut declarations = vec![];
+    let mut comment: Vec<&str> = vec![];
+    let mut lines = source.lines();
+    while let Some(line) = lines.next() {
+        if let Some(text) = line.strip_prefix("//") {
+            comment.push(text.strip_prefix(' ').unwrap_or(text));
+            continue;
+        }
+        if line.starts_with("#[") {
+            continue;
+        }
+        let declaration = if let Some(rest) = line.strip_prefix("pub struct ") {
+            let name = rest.split([' ', '{', ';']).next().unwrap_or(rest);
+            Some(format!("typedef struct {name} {name};"))
+        } else if let Some(rest) = line.strip_prefix("pub type ") {
+            let (name, signature) = rest.split_once(" = extern \"C\" fn").expect("function pointer type");
+            let (parameters, result) = c_signature(signature.trim_end_matches(';'));
+            Some(format!("typedef {result} (*{})({parameters});", c_name(name)))
+        } else if line.starts_with("pub extern \"C\" fn ") || line.starts_with("pub unsafe extern \"C\" fn ") {
+            // The signature runs up to the brace that opens the body
+            let mut text = line.to_string();
+            while !text.trim_end().ends_with('{') {
+                text.push_str(lines.next().expect("the body of the function").trim());
+            }
+            let text = text.trim_end().trim_end_matches('{').trim();
+            let (_, rest) = text.split_once(" fn ").expect("a function");
+            let (name, signature) = rest.split_at(rest.find('(').expect("parameters"));
+            let (parameters, result) = c_signature(signature);
+            let start = match result.ends_with('*') {
+                true => format!("{result}{name}("),
+                false => format!("{result} {name}("),
+            };
+            Some(prototype(&start, &parameters))
+        } else {
+            None
+        };
+        if let Some(declaration) = declaration {
+            declarations.push(format!("{}{declaration}", c_comment(&comment)));
+        }
+        comment.clear();
+    }
+    format!("{PREAMBLE}\n{}\n{POSTAMBLE}", declarations.join("\n\n"))
+}
+
+// C parameters and result type of a Rust signature `(name: Type, ...) -> Type`.
+fn c_signature(signature: &str) -> (String, String) {
+    let close = signature.rfind(')').expect("closing parenthesis");
+    let parameters: Vec<String> = signature[1..close]
+        .split(',')
+        .map(str::trim)
+        .filter(|parameter| !parameter.is_empty())
+        .map(|parameter| {
+            let (name, rust) = parameter.split_once(':').expect("name: type");
+            let c = c_type(rust.trim());
+            match c.ends_with('*') {
+                true => format!("{c}{}", name.trim()),
+                false => format!("{c} {}", name.trim()),
+            }
+        })
+        .collect();
+    let result = match signature[close + 1..].trim().strip_prefix("->") {
+        Some(rust) => c_type(rust.trim()),
+        None => "void".to_string(),
+    };
+    let parameters = match parameters.is_empty() {
+        true => "void".to_string(),
+        false => parameters.join(", "),
+    };
+    (parameters, result.trim_end().to_string())
+}
+
+fn c_type(rust: &str) -> String {
+    if let Some(pointee) = rust.strip_prefix("*const ") {
+        return format!("const {} *", c_type(pointee));
+    }
+    if let Some(pointee) = rust.strip_prefix("*mut ") {
+        return format!("{} *", c_type(pointee));
+    }
+    if let Some(inner) = rust.strip_prefix("Option<").and_then(|rest| rest.strip_suffix('>')) {
+        // Nullable function pointers
+        return c_type(inner);
+    }
+    match rust {
+        "c_char" => "char",
+        "c_void" => "void",
+        "f32" => "float",
+        "f64" => "double",
+        "i32" => "int32_t",
+        "u8" => "uint8_t",
+        "u32" => "uint32_t",
+        "u64" => "uint64_t",
+        "usize" => "size_t",
+        name => return c_name(name),
+    }
+    .to_string()
+}
+
+// Types of the API are prefixed with the name of the library in C.
+fn c_name(name: &str) -> String {
+    match name.starts_with("Rustracer") {
+        true => name.to_string(),
+        false => format!("Rustracer{name}"),
+    }
+}
+
+// Prototype from `start`, e.g. `int32_t name(`, and the parameters, which are wrapped to new lines aligned after
+// the parenthesis when they don't fit.
+fn prototype(start: &str, parameters: &str) -> String {
+    let mut text = start.to_string();
+    let mut width = start.len();
+    let parameters: Vec<&str> = parameters.split(", ").collect();
+    for (index, parameter) in parameters.iter().enumerate() {
+        let end = if index + 1 == parameters.len() { ");" } else { "," };
+        if index > 0 {
+            if width + 1 + parameter.len() + end.len() > MAX_WIDTH {
+                text.push('\n');
+                text.push_str(&" ".repeat(start.len()));
+                width = start.len();
+            } else {
+                text.push(' ');
+                width += 1;
+            }
+        }
+        text.push_str(parameter);
+        text.push_str(end);
+        width += parameter.len() + end.len();
+    }
+    text
+}
+
+// C comment of the lines of a Rust comment, without the safety requirements, which are about calling from Rust. The
+// words are wrapped anew to fit the `/* ` and ` */` of C comments.
+fn c_comment(lines: &[&str]) -> String {
+    let end = lines.iter().position(|line| *line == "# Safety").unwrap_or(lines.len());
+    let mut rows: Vec<String> = vec![];
+    for word in lines[..end].iter().flat_map(|line| line.split_whitespace()) {
+        match rows.last_mut() {
+            Some(row) if 3 + row.len() + 1 + word.len() + 3 <= MAX_WIDTH => {
+                row.push(' ');
+                row.push_str(word);
+            }
+            _ => rows.push(word.to_string()),
+        }
+    }
+    match rows.is_empty() {
+        true => String::new(),
+        false => format!("/* {} */\n", rows.join("\n * ")),
+    }
+}
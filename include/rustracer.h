@@ -0,0 +1,67 @@
+/*
+ * C API of rustracer, generated by `build.rs` from `src/ffi.rs`. Don't edit it, change the functions there.
+ *
+ * Build the library with `cargo rustc --lib --release --crate-type cdylib` (or `staticlib`) and link
+ * `target/release/librustracer.so` (or `.a`, which also needs `-lpthread -ldl -lm`).
+ */
+
+#ifndef RUSTRACER_H
+#define RUSTRACER_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Scene built in code, created with `rustracer_scene_new` and released with `rustracer_scene_free`. */
+typedef struct RustracerScene RustracerScene;
+
+/* Called with the user data, the scanlines done and the scanlines of the whole render, on the thread calling
+ * `rustracer_scene_render`. */
+typedef void (*RustracerProgressCallback)(void *user_data, uint64_t done, uint64_t total);
+
+/* Message of the last call that failed on this thread, empty if none did. It stays valid until the next call fails. */
+const char *rustracer_last_error(void);
+
+/* Empty scene with the default camera, released with `rustracer_scene_free`. Returns NULL if it can't be created. */
+RustracerScene *rustracer_scene_new(void);
+
+void rustracer_scene_free(RustracerScene *scene);
+
+/* Replace the camera by one with the parameters of a camera directive, e.g. `image_width=640 look_from=0,1,5`.
+ * Returns 0, or -1 if they are invalid. */
+int32_t rustracer_scene_set_camera(RustracerScene *scene, const char *parameters);
+
+/* Add a material with the parameters of a material directive, e.g. `type=metal albedo=0.8,0.6,0.2 fuzz=0.1`. Returns
+ * its index for the objects to refer to, or -1 if they are invalid. */
+int32_t rustracer_scene_add_material(RustracerScene *scene, const char *parameters);
+
+/* Add a sphere of the material with the index `material`. Returns 0, or -1 if there is no such material. */
+int32_t rustracer_scene_add_sphere(RustracerScene *scene, double x, double y, double z, double radius,
+                                   int32_t material);
+
+/* Add a triangle mesh of the material with the index `material`: `vertices` holds the x, y and z coordinates of
+ * `vertex_count` vertices and `indices` three vertex indices for each of the `triangle_count` triangles,
+ * counter-clockwise when looking at the front. The data is copied. Returns 0, or -1 if an index is out of range. */
+int32_t rustracer_scene_add_mesh(RustracerScene *scene, const float *vertices, size_t vertex_count,
+                                 const uint32_t *indices, size_t triangle_count, int32_t material);
+
+/* Width of the image the camera renders, 0 if the camera is invalid. */
+uint32_t rustracer_scene_width(const RustracerScene *scene);
+
+/* Height of the image the camera renders, 0 if the camera is invalid. */
+uint32_t rustracer_scene_height(const RustracerScene *scene);
+
+/* Render the scene into `rgba`, `length` bytes for the opaque 8-bit RGBA pixels of the image row by row from the top
+ * left, at least 4 * width * height. `progress` is called with `user_data` as scanlines finish, it can be null.
+ * Returns 0, or -1 if the buffer is too small. */
+int32_t rustracer_scene_render(const RustracerScene *scene, uint8_t *rgba, size_t length,
+                               RustracerProgressCallback progress, void *user_data);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* RUSTRACER_H */
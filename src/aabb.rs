@@ -0,0 +1,93 @@
+use std::ops::Range;
+
+use crate::{
+    float::Float,
+    ray::Ray,
+    vec3::{Point, Vec3},
+};
+
+// Axis-aligned bounding box.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Aabb {
+    pub min: Point,
+    pub max: Point,
+}
+
+impl Aabb {
+    // Box containing nothing, growing it by anything results in the other box.
+    pub const EMPTY: Aabb = Aabb {
+        min: Point::new(Float::INFINITY, Float::INFINITY, Float::INFINITY),
+        max: Point::new(Float::NEG_INFINITY, Float::NEG_INFINITY, Float::NEG_INFINITY),
+    };
+
+    pub fn new(min: Point, max: Point) -> Aabb {
+        Aabb { min, max }
+    }
+
+    pub fn from_points(points: impl IntoIterator<Item = Point>) -> Aabb {
+        points.into_iter().fold(Aabb::EMPTY, Aabb::grow)
+    }
+
+    pub fn grow(self, point: Point) -> Aabb {
+        Aabb {
+            min: self.min.min(point),
+            max: self.max.max(point),
+        }
+    }
+
+    pub fn union(self, other: Aabb) -> Aabb {
+        Aabb {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn centroid(&self) -> Point {
+        Point::midpoint(self.min, self.max)
+    }
+
+    pub fn extent(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub fn surface_area(&self) -> Float {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let d = self.extent();
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    // Slab test, `inv_direction` is the component-wise inverse of the ray direction. Returns the distance at which
+    // the ray enters the box, so that traversal can visit the nearer boxes first.
+    pub fn hit(&self, ray: Ray, inv_direction: Vec3, t_range: &Range<Float>) -> Option<Float> {
+        self.clip(ray, inv_direction, t_range).map(|t| t.start)
+    }
+
+    // Part of `t_range` in which the ray is inside the box.
+    pub fn clip(&self, ray: Ray, inv_direction: Vec3, t_range: &Range<Float>) -> Option<Range<Float>> {
+        let mut t_min = t_range.start;
+        let mut t_max = t_range.end;
+        for axis in 0..3 {
+            let inv = inv_direction[axis];
+            let origin = ray.origin[axis];
+            let mut t0 = (self.min[axis] - origin) * inv;
+            let mut t1 = (self.max[axis] - origin) * inv;
+            if inv < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // `max`/`min` drop the NaN that appears when the ray lies in the plane of a slab
+            t_min = t0.max(t_min);
+            t_max = t1.min(t_max);
+            if t_max < t_min {
+                return None;
+            }
+        }
+        Some(t_min..t_max)
+    }
+}
@@ -0,0 +1,308 @@
+use std::{ops::Range, str::FromStr};
+
+#[cfg(feature = "embree")]
+use crate::embree::EmbreeIndex;
+use crate::{
+    aabb::Aabb,
+    bvh::{Bvh, BvhSettings},
+    float::Float,
+    hittable::{Hit, Hittable, HittableList},
+    kdtree::KdTree,
+    packet::{RayPacket, PACKET_SIZE},
+    ray::Ray,
+    simd::Mask,
+};
+
+// Acceleration structure used for the objects of a scene and the triangles of its meshes.
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Accelerator {
+    Bvh(BvhSettings),
+    KdTree,
+    #[cfg(feature = "embree")]
+    Embree,
+}
+
+impl Default for Accelerator {
+    fn default() -> Accelerator {
+        Accelerator::Bvh(BvhSettings::default())
+    }
+}
+
+impl FromStr for Accelerator {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Accelerator, String> {
+        match s {
+            "bvh" => Ok(Accelerator::default()),
+            "kdtree" => Ok(Accelerator::KdTree),
+            #[cfg(feature = "embree")]
+            "embree" => Ok(Accelerator::Embree),
+            #[cfg(not(feature = "embree"))]
+            "embree" => Err("the embree accelerator needs the 'embree' feature".to_string()),
+            _ => Err(format!("unknown accelerator '{s}', expected 'bvh' or 'kdtree'")),
+        }
+    }
+}
+
+impl Accelerator {
+    pub fn name(self) -> &'static str {
+        match self {
+            Accelerator::Bvh(_) => "bvh",
+            Accelerator::KdTree => "kdtree",
+            #[cfg(feature = "embree")]
+            Accelerator::Embree => "embree",
+        }
+    }
+}
+
+// Spatial index over primitives identified by their index.
+pub enum SpatialIndex {
+    Bvh(Bvh),
+    KdTree(KdTree),
+    #[cfg(feature = "embree")]
+    Embree(EmbreeIndex),
+}
+
+impl SpatialIndex {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "index_build",
+            level = "debug",
+            skip_all,
+            fields(primitives = bounds.len(), ?accelerator)
+        )
+    )]
+    pub fn new(bounds: &[Aabb], accelerator: Accelerator) -> SpatialIndex {
+        match accelerator {
+            Accelerator::Bvh(settings) => SpatialIndex::Bvh(Bvh::new(bounds, settings)),
+            Accelerator::KdTree => SpatialIndex::KdTree(KdTree::new(bounds)),
+            #[cfg(feature = "embree")]
+            Accelerator::Embree => SpatialIndex::Embree(EmbreeIndex::new(bounds)),
+        }
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        match self {
+            SpatialIndex::Bvh(bvh) => bvh.bounds(),
+            SpatialIndex::KdTree(tree) => tree.bounds(),
+            #[cfg(feature = "embree")]
+            SpatialIndex::Embree(index) => index.bounds(),
+        }
+    }
+
+    // Adapt the index to primitives that moved. A BVH is refitted, a kd-tree can't move its split planes and is
+    // rebuilt, like the Embree scene.
+    pub fn update(&mut self, bounds: &[Aabb]) {
+        match self {
+            SpatialIndex::Bvh(bvh) => bvh.refit(bounds),
+            SpatialIndex::KdTree(_) => *self = SpatialIndex::KdTree(KdTree::new(bounds)),
+            #[cfg(feature = "embree")]
+            SpatialIndex::Embree(_) => *self = SpatialIndex::Embree(EmbreeIndex::new(bounds)),
+        }
+    }
+
+    // Call `hit_primitive` for the primitives the ray may hit, see `Bvh::traverse`.
+    pub fn traverse(
+        &self,
+        ray: Ray,
+        t_range: Range<Float>,
+        hit_primitive: impl FnMut(usize, Range<Float>) -> Option<Float>,
+    ) {
+        match self {
+            SpatialIndex::Bvh(bvh) => bvh.traverse(ray, t_range, hit_primitive),
+            SpatialIndex::KdTree(tree) => tree.traverse(ray, t_range, hit_primitive),
+            #[cfg(feature = "embree")]
+            SpatialIndex::Embree(index) => index.traverse(ray, t_range, hit_primitive),
+        }
+    }
+
+    // Whether `hit_primitive` reports a hit for any primitive the ray may hit. The traversal stops at the first one,
+    // whichever it is.
+    pub fn traverse_any(
+        &self,
+        ray: Ray,
+        t_range: Range<Float>,
+        mut hit_primitive: impl FnMut(usize, Range<Float>) -> bool,
+    ) -> bool {
+        let mut found = false;
+        self.traverse(ray, t_range, |primitive, t_range| {
+            found = found || hit_primitive(primitive, t_range);
+            // A hit at minus infinity leaves an empty range, which no node or cell can overlap
+            found.then_some(Float::NEG_INFINITY)
+        });
+        found
+    }
+
+    // Call `hit_primitive` for the primitives the rays of the packet may hit, see `Bvh::traverse_packet`. The other
+    // indices have no packet traversal and trace the rays one by one.
+    pub fn traverse_packet<const N: usize>(
+        &self,
+        packet: &RayPacket<N>,
+        t_start: Float,
+        t_ends: &mut [Float; N],
+        mut hit_primitive: impl FnMut(usize, Mask<N>, &mut [Float; N]),
+    ) {
+        if let SpatialIndex::Bvh(bvh) = self {
+            return bvh.traverse_packet(packet, t_start, t_ends, hit_primitive);
+        }
+        for lane in packet.active.lanes() {
+            self.traverse(packet.rays[lane], t_start..t_ends[lane], |primitive, _| {
+                let t_end = t_ends[lane];
+                hit_primitive(primitive, Mask::single(lane), t_ends);
+                (t_ends[lane] < t_end).then_some(t_ends[lane])
+            });
+        }
+    }
+}
+
+// Object list that finds the objects a ray can hit through a spatial index over their bounding boxes.
+pub struct AcceleratedList {
+    objects: HittableList,
+    index: SpatialIndex,
+}
+
+impl AcceleratedList {
+    pub fn new(objects: HittableList, accelerator: Accelerator) -> AcceleratedList {
+        let bounds: Vec<Aabb> = objects.iter().map(|object| object.bounding_box()).collect();
+        AcceleratedList {
+            index: SpatialIndex::new(&bounds, accelerator),
+            objects,
+        }
+    }
+
+    // Objects to modify between the frames of an animation, e.g. to replace instances by ones with a new
+    // transformation. Their number and order must stay the same, `refit` updates the index afterwards.
+    pub fn objects_mut(&mut self) -> &mut HittableList {
+        &mut self.objects
+    }
+
+    // Update the index after objects moved, which for a BVH is much faster than building a new list.
+    pub fn refit(&mut self) {
+        let bounds: Vec<Aabb> = self.objects.iter().map(|object| object.bounding_box()).collect();
+        self.index.update(&bounds);
+    }
+}
+
+impl Hittable for AcceleratedList {
+    fn hit(&self, ray: Ray, t_range: Range<Float>) -> Option<Hit<'_>> {
+        // A single object, like a list of spheres, has its own index already
+        if let [object] = &self.objects[..] {
+            return object.hit(ray, t_range);
+        }
+        let mut closest = None;
+        self.index.traverse(ray, t_range, |index, t_range| {
+            let hit = self.objects[index].hit(ray, t_range)?;
+            closest = Some(hit);
+            Some(hit.t)
+        });
+        closest
+    }
+
+    fn hit_any(&self, ray: Ray, t_range: Range<Float>) -> bool {
+        self.index
+            .traverse_any(ray, t_range, |index, t_range| self.objects[index].hit_any(ray, t_range))
+    }
+
+    fn fit_to(&mut self, shutter: Range<Float>) -> bool {
+        let changed = self.objects.fit_to(shutter);
+        if changed {
+            self.refit();
+        }
+        changed
+    }
+
+    fn hit_packet<'a>(&'a self, rays: &[Ray], t_range: Range<Float>, hits: &mut [Option<Hit<'a>>]) {
+        for (rays, hits) in rays.chunks(PACKET_SIZE).zip(hits.chunks_mut(PACKET_SIZE)) {
+            let packet = RayPacket::<PACKET_SIZE>::new(rays);
+            let mut t_ends = [t_range.end; PACKET_SIZE];
+            for (t_end, hit) in t_ends.iter_mut().zip(hits.iter()) {
+                *t_end = hit.map_or(*t_end, |hit| hit.t);
+            }
+            self.index
+                .traverse_packet(&packet, t_range.start, &mut t_ends, |index, lanes, t_ends| {
+                    // The object traces the lanes that reached it as a packet of its own
+                    let mut object_lanes = [0; PACKET_SIZE];
+                    let mut object_rays = packet.rays;
+                    let mut object_hits = [None; PACKET_SIZE];
+                    let mut len = 0;
+                    for lane in lanes.lanes() {
+                        object_lanes[len] = lane;
+                        object_rays[len] = packet.rays[lane];
+                        object_hits[len] = hits[lane];
+                        len += 1;
+                    }
+                    let object_range = t_range.start..t_range.end;
+                    self.objects[index].hit_packet(&object_rays[..len], object_range, &mut object_hits[..len]);
+                    for (&lane, hit) in object_lanes[..len].iter().zip(object_hits) {
+                        if let Some(hit) = hit.filter(|hit| hit.t < t_ends[lane]) {
+                            t_ends[lane] = hit.t;
+                            hits[lane] = Some(hit);
+                        }
+                    }
+                });
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.index.bounds()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        animation::{Animated, Keyframes, Placement},
+        color::Color,
+        material::{Lambertian, Material},
+        sphere::Sphere,
+        transform::Transform,
+        vec3::{Point, Vec3},
+    };
+
+    fn sphere(x: Float, y: Float) -> Box<dyn Hittable> {
+        let material: Arc<dyn Material> = Arc::new(Lambertian::new(Color::WHITE));
+        Box::new(Sphere::new(Point::new(x, y, -5.0), 1.0, material))
+    }
+
+    // Ray from the origin at `time` through the point at `x`, `y` five units in front of it.
+    fn ray(x: Float, y: Float, time: Float) -> Ray {
+        Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(x, y, -5.0), time)
+    }
+
+    #[test]
+    fn moved_objects_are_hit_after_refit() {
+        let mut list = AcceleratedList::new(vec![sphere(0.0, 0.0), sphere(10.0, 0.0)], Accelerator::default());
+        list.objects_mut()[0] = sphere(0.0, 5.0);
+        // The index still has the sphere where it was
+        assert!(list.hit(ray(0.0, 5.0, 0.0), 0.001..Float::INFINITY).is_none());
+        list.refit();
+        assert!(list.hit(ray(0.0, 5.0, 0.0), 0.001..Float::INFINITY).is_some());
+        assert!(list.hit(ray(0.0, 0.0, 0.0), 0.001..Float::INFINITY).is_none());
+    }
+
+    #[test]
+    fn animated_objects_fit_the_shutter() {
+        let placement = |x: Float| Placement {
+            scale: Vec3::new(1.0, 1.0, 1.0),
+            rotate: Vec3::new(0.0, 0.0, 0.0),
+            translate: Vec3::new(x, 0.0, 0.0),
+        };
+        let moving = Animated::new(
+            Arc::from(sphere(0.0, 0.0)),
+            Keyframes::new(vec![(0.0, placement(0.0)), (10.0, placement(10.0))]),
+            Transform::IDENTITY,
+        );
+        let mut list = AcceleratedList::new(vec![Box::new(moving), sphere(-10.0, 0.0)], Accelerator::default());
+        assert!(list.hit(ray(0.0, 0.0, 0.0), 0.001..Float::INFINITY).is_some());
+        assert!(list.fit_to(4.5..5.5));
+        assert!(list.bounding_box().max.x <= 6.5);
+        assert!(list.hit(ray(5.0, 0.0, 5.0), 0.001..Float::INFINITY).is_some());
+        // Where the sphere was at the start is outside of the bounds of the frame now
+        assert!(list.hit(ray(0.0, 0.0, 0.0), 0.001..Float::INFINITY).is_none());
+    }
+}
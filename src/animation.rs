@@ -0,0 +1,209 @@
+use std::{ops::Range, sync::Arc};
+
+use crate::{
+    aabb::Aabb,
+    camera::CameraSettings,
+    float::Float,
+    hittable::{Hit, Hittable},
+    instance::{hit_placed, object_ray},
+    ray::Ray,
+    transform::Transform,
+    vec3::{Point, Vec3},
+};
+
+// Values that can be blended between two keyframes, `t` goes from 0 at `self` to 1 at `other`.
+pub trait Interpolate: Copy {
+    fn interpolate(self, other: Self, t: Float) -> Self;
+}
+
+impl Interpolate for Float {
+    fn interpolate(self, other: Float, t: Float) -> Float {
+        self + t * (other - self)
+    }
+}
+
+impl Interpolate for Vec3 {
+    fn interpolate(self, other: Vec3, t: Float) -> Vec3 {
+        self + t * (other - self)
+    }
+}
+
+impl Interpolate for Point {
+    fn interpolate(self, other: Point, t: Float) -> Point {
+        self + t * (other - self)
+    }
+}
+
+// The camera moves and zooms, the settings of the image are the same in all keyframes.
+impl Interpolate for CameraSettings {
+    fn interpolate(self, other: CameraSettings, t: Float) -> CameraSettings {
+        CameraSettings {
+            vfov: self.vfov.interpolate(other.vfov, t),
+            look_from: self.look_from.interpolate(other.look_from, t),
+            look_at: self.look_at.interpolate(other.look_at, t),
+            vup: self.vup.interpolate(other.vup, t),
+            defocus_angle: self.defocus_angle.interpolate(other.defocus_angle, t),
+            focus_dist: self.focus_dist.interpolate(other.focus_dist, t),
+            ..self
+        }
+    }
+}
+
+// Values at given frames, e.g. the placements an object moves through. Between two keyframes the value changes
+// linearly, before the first and after the last one it holds still.
+#[derive(Clone)]
+pub struct Keyframes<T> {
+    keys: Vec<(Float, T)>, // frames and values, sorted by frame
+}
+
+impl<T> Default for Keyframes<T> {
+    fn default() -> Keyframes<T> {
+        Keyframes { keys: vec![] }
+    }
+}
+
+impl<T: Interpolate> Keyframes<T> {
+    pub fn new(mut keys: Vec<(Float, T)>) -> Keyframes<T> {
+        keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Keyframes { keys }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    // Value at any moment, None without keyframes.
+    pub fn at(&self, frame: Float) -> Option<T> {
+        let next = self.keys.partition_point(|(key, _)| *key <= frame);
+        match (next.checked_sub(1).map(|index| self.keys[index]), self.keys.get(next)) {
+            (Some((from, a)), Some(&(to, b))) => Some(a.interpolate(b, (frame - from) / (to - from))),
+            (Some((_, value)), None) | (None, Some(&(_, value))) => Some(value),
+            (None, None) => None,
+        }
+    }
+
+    pub fn try_map<U, E>(self, mut f: impl FnMut(T) -> Result<U, E>) -> Result<Keyframes<U>, E> {
+        let keys = self
+            .keys
+            .into_iter()
+            .map(|(frame, value)| Ok((frame, f(value)?)))
+            .collect::<Result<_, E>>()?;
+        Ok(Keyframes { keys })
+    }
+}
+
+// Scale, rotation (degrees around X, then Y, then Z) and translation of an object, applied in this order. The parts
+// are interpolated on their own, so objects turn around their own origin while they move.
+#[derive(Copy, Clone)]
+pub struct Placement {
+    pub scale: Vec3,
+    pub rotate: Vec3,
+    pub translate: Vec3,
+}
+
+impl Placement {
+    pub fn transform(&self) -> Transform {
+        Transform::scaling(self.scale)
+            .then(Transform::rotation(0, self.rotate.x))
+            .then(Transform::rotation(1, self.rotate.y))
+            .then(Transform::rotation(2, self.rotate.z))
+            .then(Transform::translation(self.translate))
+    }
+}
+
+impl Interpolate for Placement {
+    fn interpolate(self, other: Placement, t: Float) -> Placement {
+        Placement {
+            scale: self.scale.interpolate(other.scale, t),
+            rotate: self.rotate.interpolate(other.rotate, t),
+            translate: self.translate.interpolate(other.translate, t),
+        }
+    }
+}
+
+// Largest angle in degrees an object turns between two of the placements its bounds are computed from.
+const BOUNDS_STEP: Float = 2.0;
+
+// Object moving through keyframes of its placement. Like an `Instance`, rays are moved into the space of the object,
+// with the placement at the time of the ray, so an object moving while the shutter is open is blurred along its path.
+// Its bounds enclose the whole animation, or the part of it `fit_to` was given.
+pub struct Animated {
+    object: Arc<dyn Hittable>,
+    keyframes: Keyframes<Placement>,
+    transform: Transform, // applied after the placement, e.g. of the file including the object
+    bounds: Aabb,
+}
+
+impl Animated {
+    pub fn new(object: Arc<dyn Hittable>, keyframes: Keyframes<Placement>, transform: Transform) -> Animated {
+        assert!(!keyframes.is_empty(), "animated objects need keyframes");
+        let (first, last) = (keyframes.keys[0].0, keyframes.keys[keyframes.keys.len() - 1].0);
+        let mut animated = Animated {
+            object,
+            keyframes,
+            transform,
+            bounds: Aabb::EMPTY,
+        };
+        animated.bounds = animated.bounds_during(first..last);
+        animated
+    }
+
+    fn transform_at(&self, time: Float) -> Transform {
+        let placement = self.keyframes.at(time).expect("checked in new");
+        placement.transform().then(self.transform)
+    }
+
+    // Bounds of the object while it moves during `times`, from placements close enough to each other that the object
+    // doesn't turn out of the boxes around them.
+    fn bounds_during(&self, times: Range<Float>) -> Aabb {
+        let keys = self.keyframes.keys.iter().map(|&(key, _)| key);
+        let times: Vec<Float> = [times.start]
+            .into_iter()
+            .chain(keys.filter(|&key| key > times.start && key < times.end))
+            .chain([times.end])
+            .collect();
+        let mut bounds = Aabb::EMPTY;
+        for pair in times.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            let [a, b] = [from, to].map(|time| self.keyframes.at(time).expect("checked in new"));
+            let turn = (b.rotate - a.rotate).abs();
+            let steps = (turn.x.max(turn.y).max(turn.z) / BOUNDS_STEP).ceil().max(1.0) as usize;
+            for step in 0..=steps {
+                let t = step as Float / steps as Float;
+                let placement = self.keyframes.at(from + t * (to - from)).expect("checked in new");
+                let transform = placement.transform().then(self.transform);
+                bounds = bounds.union(transform.bounds(self.object.bounding_box()));
+            }
+        }
+        bounds
+    }
+}
+
+impl Hittable for Animated {
+    fn hit(&self, ray: Ray, t_range: Range<Float>) -> Option<Hit<'_>> {
+        let transform = self.transform_at(ray.time);
+        let hit = hit_placed(self.object.as_ref(), &transform, ray, t_range)?;
+        // Where the point of the object that was hit is a frame later
+        let moved = self
+            .transform_at(ray.time + 1.0)
+            .point(transform.inverse().point(hit.point));
+        Some(Hit {
+            motion: hit.motion + (moved - hit.point),
+            ..hit
+        })
+    }
+
+    fn hit_any(&self, ray: Ray, t_range: Range<Float>) -> bool {
+        let to_object = self.transform_at(ray.time).inverse();
+        self.object.hit_any(object_ray(&to_object, ray), t_range)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounds
+    }
+
+    fn fit_to(&mut self, shutter: Range<Float>) -> bool {
+        self.bounds = self.bounds_during(shutter);
+        true
+    }
+}
@@ -0,0 +1,97 @@
+use std::{fs::File, path::Path, sync::Arc};
+
+use crate::{
+    error::{Error, Result},
+    float::{consts::PI, Float},
+    output::read_ppm,
+    util::random_double,
+    vec3::Vec3,
+};
+
+// Shape of the aperture of the thin lens, which is the shape out-of-focus highlights (bokeh) take. A real aperture is
+// closed by a number of straight blades into a regular polygon, e.g. the hexagons of a lens with 6 blades, turned by
+// a rotation in degrees. A mask is an image of the aperture, the brightness of each pixel how much light passes
+// there, for hearts, stars or the cat's eyes of a lens cut off at the sides. Samples lie within the unit square, the
+// polygon fits the unit circle and the mask fills the square along its longer side.
+#[derive(Clone, Default)]
+pub enum Aperture {
+    #[default]
+    Round,
+    Polygon {
+        blades: u32,
+        rotation: Float, // radians, the first corner points up without it
+    },
+    Mask(Arc<ApertureMask>),
+}
+
+impl Aperture {
+    // The aperture closed by `blades` blades turned by `rotation` degrees, round with less than 3.
+    pub fn polygon(blades: u32, rotation: Float) -> Aperture {
+        if blades < 3 {
+            return Aperture::Round;
+        }
+        Aperture::Polygon {
+            blades,
+            rotation: rotation.to_radians(),
+        }
+    }
+
+    // Returns a random point of the aperture, to the right and up of its center.
+    pub fn sample(&self) -> [Float; 2] {
+        match self {
+            Aperture::Round => {
+                let p = Vec3::random_in_unit_disk();
+                [p.x, p.y]
+            }
+            Aperture::Polygon { blades, rotation } => {
+                // Pick one of the triangles between the center and the sides, then a point of it
+                let side = ((random_double() * *blades as Float) as u32).min(blades - 1);
+                let corner = |k: u32| {
+                    let angle = rotation + PI / 2.0 + 2.0 * PI * k as Float / *blades as Float;
+                    [angle.cos(), angle.sin()]
+                };
+                let ([x0, y0], [x1, y1]) = (corner(side), corner(side + 1));
+                let (radius, along) = (random_double().sqrt(), random_double());
+                [radius * (x0 + along * (x1 - x0)), radius * (y0 + along * (y1 - y0))]
+            }
+            Aperture::Mask(mask) => mask.sample(),
+        }
+    }
+}
+
+// Image of an aperture, sampled in proportion to the brightness of its pixels.
+pub struct ApertureMask {
+    width: usize,
+    height: usize,
+    cdf: Vec<Float>, // running sum of the brightness of the pixels, row by row
+}
+
+// Reads an aperture mask from a plain text PPM image (see `output::read_ppm`).
+pub fn load(path: &Path) -> Result<ApertureMask> {
+    let (width, height, pixels) = read_ppm(&mut File::open(path)?)?;
+    let mut total = 0.0;
+    let cdf = pixels
+        .iter()
+        .map(|pixel| {
+            total += pixel.iter().map(|value| *value as Float).sum::<Float>() / (3.0 * 255.0);
+            total
+        })
+        .collect();
+    if total == 0.0 {
+        return Err(Error::parse(0, "the aperture mask lets no light through"));
+    }
+    Ok(ApertureMask { width, height, cdf })
+}
+
+impl ApertureMask {
+    fn sample(&self) -> [Float; 2] {
+        let target = random_double() * self.cdf[self.cdf.len() - 1];
+        let index = self.cdf.partition_point(|sum| *sum <= target).min(self.cdf.len() - 1);
+        let (x, y) = (index % self.width, index / self.width);
+        let scale = 2.0 / self.width.max(self.height) as Float;
+        [
+            (x as Float + random_double() - self.width as Float / 2.0) * scale,
+            (self.height as Float / 2.0 - y as Float - random_double()) * scale,
+        ]
+    }
+}
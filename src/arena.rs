@@ -0,0 +1,191 @@
+use std::{collections::HashMap, ops::Range, sync::Arc};
+
+use crate::{
+    aabb::Aabb,
+    accel::{Accelerator, SpatialIndex},
+    float::Float,
+    hittable::{Hit, Hittable, HittableList},
+    material::Material,
+    mesh::{hit_triangle, triangle_normal, triangle_uv},
+    ray::Ray,
+    sphere::{hit_sphere, sphere_bounds, sphere_hit},
+    transform::Transform,
+    vec3::Point,
+};
+
+// Reference to a primitive of the arena, an index into the array of its kind.
+#[derive(Copy, Clone)]
+enum Primitive {
+    Sphere(u32),
+    Triangle(u32),
+    Object(u32),
+}
+
+// Flat storage of a whole scene. Instead of a graph of boxed objects, the arena keeps every kind of primitive in
+// arrays of plain values (structure of arrays) that refer to vertices and materials by index, with a single spatial
+// index over all of them. Mesh instances are baked into world space, so a traversal never leaves the index to follow
+// a pointer or a transformation, at the cost of storing instanced geometry once per instance. Apart from the material
+// table the arrays hold nothing but numbers and could be written out as they are.
+pub struct Arena {
+    materials: Vec<Arc<dyn Material>>, // material table
+    material_ids: HashMap<usize, u32>, // table index of every material by address, shared materials are stored once
+    sphere_centers: Vec<Point>,
+    sphere_radii: Vec<Float>,
+    sphere_materials: Vec<u32>,
+    vertices: Vec<Point>, // world space vertices of all triangles
+    triangles: Vec<[u32; 3]>,
+    triangle_materials: Vec<u32>,
+    objects: HittableList, // objects that can't be flattened, intersected through `dyn Hittable`
+    primitives: Vec<Primitive>,
+    index: SpatialIndex,
+}
+
+impl Arena {
+    // Flatten the objects of `world` into the arena, the ones that don't support it are kept as they are.
+    pub fn new(world: HittableList, accelerator: Accelerator) -> Arena {
+        let mut arena = Arena {
+            materials: vec![],
+            material_ids: HashMap::new(),
+            sphere_centers: vec![],
+            sphere_radii: vec![],
+            sphere_materials: vec![],
+            vertices: vec![],
+            triangles: vec![],
+            triangle_materials: vec![],
+            objects: vec![],
+            primitives: vec![],
+            index: SpatialIndex::new(&[], accelerator),
+        };
+        for object in world {
+            if !object.flatten(&Transform::IDENTITY, &mut arena) {
+                arena.objects.push(object);
+            }
+        }
+
+        let spheres = (0..arena.sphere_centers.len() as u32).map(Primitive::Sphere);
+        let triangles = (0..arena.triangles.len() as u32).map(Primitive::Triangle);
+        let objects = (0..arena.objects.len() as u32).map(Primitive::Object);
+        arena.primitives = spheres.chain(triangles).chain(objects).collect();
+        let bounds: Vec<Aabb> = arena
+            .primitives
+            .iter()
+            .map(|&primitive| arena.primitive_bounds(primitive))
+            .collect();
+        arena.index = SpatialIndex::new(&bounds, accelerator);
+        arena
+    }
+
+    pub fn add_sphere(&mut self, center: Point, radius: Float, material: &Arc<dyn Material>) {
+        let material = self.add_material(material);
+        self.sphere_centers.push(center);
+        self.sphere_radii.push(radius);
+        self.sphere_materials.push(material);
+    }
+
+    // Add the triangles of a mesh, `triangles` index into `vertices` and `material` gives the material of a face.
+    pub fn add_mesh<'a>(
+        &mut self,
+        vertices: impl IntoIterator<Item = Point>,
+        triangles: &[[usize; 3]],
+        material: impl Fn(usize) -> &'a Arc<dyn Material>,
+    ) {
+        let offset = self.vertices.len();
+        self.vertices.extend(vertices);
+        for (face, triangle) in triangles.iter().enumerate() {
+            let material = self.add_material(material(face));
+            self.triangles.push(triangle.map(|index| (offset + index) as u32));
+            self.triangle_materials.push(material);
+        }
+    }
+
+    fn add_material(&mut self, material: &Arc<dyn Material>) -> u32 {
+        let id = Arc::as_ptr(material) as *const () as usize;
+        *self.material_ids.entry(id).or_insert_with(|| {
+            self.materials.push(material.clone());
+            (self.materials.len() - 1) as u32
+        })
+    }
+
+    fn triangle(&self, index: u32) -> [Point; 3] {
+        self.triangles[index as usize].map(|vertex| self.vertices[vertex as usize])
+    }
+
+    fn primitive_bounds(&self, primitive: Primitive) -> Aabb {
+        match primitive {
+            Primitive::Sphere(i) => sphere_bounds(self.sphere_centers[i as usize], self.sphere_radii[i as usize]),
+            Primitive::Triangle(i) => Aabb::from_points(self.triangle(i)),
+            Primitive::Object(i) => self.objects[i as usize].bounding_box(),
+        }
+    }
+
+    // Distance to the primitive within `t_range`, objects are intersected in full and return their hit as well.
+    // `hit_sphere` and `hit_triangle` count their tests for the `stats` feature, the same as for the objects that
+    // aren't flattened, so `--arena` renders report the same counters.
+    fn hit_primitive(
+        &self,
+        primitive: Primitive,
+        ray: Ray,
+        t_range: &Range<Float>,
+    ) -> Option<(Float, Option<Hit<'_>>)> {
+        match primitive {
+            Primitive::Sphere(i) => {
+                let t = hit_sphere(
+                    self.sphere_centers[i as usize],
+                    self.sphere_radii[i as usize],
+                    ray,
+                    t_range,
+                )?;
+                Some((t, None))
+            }
+            Primitive::Triangle(i) => Some((hit_triangle(self.triangle(i), ray, t_range)?, None)),
+            Primitive::Object(i) => {
+                let hit = self.objects[i as usize].hit(ray, t_range.clone())?;
+                Some((hit.t, Some(hit)))
+            }
+        }
+    }
+}
+
+impl Hittable for Arena {
+    fn hit(&self, ray: Ray, t_range: Range<Float>) -> Option<Hit<'_>> {
+        let mut closest = None;
+        self.index.traverse(ray, t_range, |index, t_range| {
+            let (t, hit) = self.hit_primitive(self.primitives[index], ray, &t_range)?;
+            closest = Some((self.primitives[index], t, hit));
+            Some(t)
+        });
+
+        let material = |index: u32| self.materials[index as usize].as_ref();
+        match closest? {
+            (Primitive::Sphere(i), t, _) => {
+                let i = i as usize;
+                let radius = self.sphere_radii[i];
+                Some(sphere_hit(
+                    self.sphere_centers[i],
+                    radius,
+                    material(self.sphere_materials[i]),
+                    ray,
+                    t,
+                ))
+            }
+            (Primitive::Triangle(i), t, _) => {
+                let material = material(self.triangle_materials[i as usize]);
+                let vertices = self.triangle(i);
+                Some(Hit::new(ray, t, triangle_normal(vertices), material).with_uv(triangle_uv(vertices, ray.at(t))))
+            }
+            (Primitive::Object(_), _, hit) => hit,
+        }
+    }
+
+    fn hit_any(&self, ray: Ray, t_range: Range<Float>) -> bool {
+        self.index
+            .traverse_any(ray, t_range, |index, t_range| match self.primitives[index] {
+                Primitive::Object(i) => self.objects[i as usize].hit_any(ray, t_range),
+                primitive => self.hit_primitive(primitive, ray, &t_range).is_some(),
+            })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.index.bounds()
+    }
+}
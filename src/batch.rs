@@ -0,0 +1,350 @@
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use rustracer::{
+    accel::AcceleratedList,
+    bvh::{BvhBuilder, BvhSettings, BvhWidth},
+    camera::Camera,
+    error::{Error, Result},
+    output::{write_image, ImageFormat},
+    parser::{parse_directives, Directive},
+    progress::Progress,
+    scene::Scene,
+};
+
+// One entry of the manifest, a table of a TOML manifest (`jobs.toml`), e.g.
+//
+//   [[job]]
+//   scene = "scenes/three-spheres.scene"
+//   output = "out/preview.ppm"
+//   image_width = 400
+//   samples_per_pixel = 16
+//
+// or a line of a manifest in the format of the scenes:
+//
+//   job scene=scenes/three-spheres.scene output=out/preview.ppm image_width=400 samples_per_pixel=16
+//
+// Relative paths are resolved against the directory of the manifest. Settings that are not given are taken from the
+// camera of the scene, the BVH settings (`bvh`, `bvh_width` and `bvh_compact`) default to the command line ones.
+// `layer` renders the layer of a group of objects, see `Camera::with_layer`, so the layers of a scene can be jobs of
+// their own.
+struct Job {
+    scene: PathBuf,
+    output: PathBuf,
+    image_width: Option<i32>,
+    samples_per_pixel: Option<i32>,
+    max_depth: Option<i32>,
+    bvh: Option<BvhBuilder>,
+    bvh_width: Option<BvhWidth>,
+    bvh_compact: Option<bool>,
+    layer: Option<String>,
+}
+
+struct Report {
+    width: i32,
+    height: i32,
+    samples_per_pixel: i32,
+    elapsed: Duration,
+}
+
+fn parse_manifest(source: &str, base: &Path, toml: bool) -> Result<Vec<Job>> {
+    let directives = match toml {
+        true => parse_toml(source)?,
+        false => parse_directives(source)?,
+    };
+    let mut jobs = vec![];
+    for mut directive in directives {
+        if directive.keyword != "job" {
+            return Err(Error::parse(
+                directive.line,
+                format!("unknown directive '{}'", directive.keyword),
+            ));
+        }
+        jobs.push(Job {
+            scene: base.join(directive.take_required("scene")?),
+            output: base.join(directive.take_required("output")?),
+            image_width: directive.take_parsed("image_width")?,
+            samples_per_pixel: directive.take_parsed("samples_per_pixel")?,
+            max_depth: directive.take_parsed("max_depth")?,
+            bvh: directive.take_parsed("bvh")?,
+            bvh_width: directive.take_parsed("bvh_width")?,
+            bvh_compact: directive.take_parsed("bvh_compact")?,
+            layer: directive.take("layer"),
+        });
+        directive.finish()?;
+    }
+    Ok(jobs)
+}
+
+// The `[[job]]` tables of a TOML manifest as `job` directives. Only the part of TOML manifests need is read: tables
+// of keys with strings, integers, floats and booleans as values, and comments.
+fn parse_toml(source: &str) -> Result<Vec<Directive>> {
+    let mut jobs: Vec<Directive> = vec![];
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let text = toml_uncomment(text).trim();
+        if text.is_empty() {
+            continue;
+        }
+        if text.starts_with('[') {
+            if text != "[[job]]" {
+                return Err(Error::parse(line, format!("unknown table {text}, expected [[job]]")));
+            }
+            let mut job = parse_directives("job")?.remove(0);
+            job.line = line;
+            jobs.push(job);
+            continue;
+        }
+        let (key, value) = text
+            .split_once('=')
+            .ok_or_else(|| Error::parse(line, format!("expected key = value, found '{text}'")))?;
+        let (key, value) = (key.trim(), value.trim());
+        let value =
+            toml_value(value).ok_or_else(|| Error::parse(line, format!("invalid value {value} for '{key}'")))?;
+        let Some(job) = jobs.last_mut() else {
+            return Err(Error::parse(line, format!("'{key}' is outside of a [[job]] table")));
+        };
+        if job.get(key).is_some() {
+            return Err(Error::parse(line, format!("duplicate key '{key}'")));
+        }
+        job.set(key, &value);
+    }
+    Ok(jobs)
+}
+
+// The line up to its comment, a `#` outside of strings.
+fn toml_uncomment(text: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (index, c) in text.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(open), c) if c == open && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &text[..index],
+            _ => {}
+        }
+        escaped = false;
+    }
+    text
+}
+
+// Text of a string, or a number or boolean as it's written.
+fn toml_value(value: &str) -> Option<String> {
+    if let Some(literal) = value.strip_prefix('\'').and_then(|rest| rest.strip_suffix('\'')) {
+        return (!literal.contains('\'')).then(|| literal.to_string());
+    }
+    let Some(basic) = value.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')) else {
+        let number = value.replace('_', "");
+        let valid = matches!(value, "true" | "false") || number.parse::<i64>().is_ok() || number.parse::<f64>().is_ok();
+        return valid.then_some(number);
+    };
+    let mut text = String::new();
+    let mut chars = basic.chars();
+    while let Some(c) = chars.next() {
+        text.push(match c {
+            '\\' => match chars.next()? {
+                'n' => '\n',
+                't' => '\t',
+                c @ ('"' | '\\') => c,
+                _ => return None,
+            },
+            '"' => return None,
+            c => c,
+        });
+    }
+    Some(text)
+}
+
+// Render all jobs of the manifest, `parallel` of them at the same time, each on a thread of its own. A failing job
+// doesn't stop the batch: every job reports its status when it's done, and the failures are listed again in the
+// summary printed at the end. Returns false if any of the jobs failed.
+pub fn run(manifest: &Path, bvh: BvhSettings, parallel: usize) -> Result<bool> {
+    let base = manifest.parent().unwrap_or(Path::new(""));
+    let toml = manifest.extension().is_some_and(|extension| extension == "toml");
+    let jobs = parse_manifest(&fs::read_to_string(manifest)?, base, toml)?;
+
+    let progress = BatchProgress::new(jobs.len(), parallel);
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(vec![]);
+    thread::scope(|scope| {
+        for _ in 0..parallel.min(jobs.len()) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(job) = jobs.get(index) else {
+                    break;
+                };
+                let scanlines = progress.start_job(index, &job.scene);
+                let result = render_job(job, bvh, scanlines.as_ref());
+                progress.finish_job(index, &status(job, &result));
+                results.lock().unwrap().push((index, result));
+            });
+        }
+    });
+    progress.finish()?;
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(index, _)| *index);
+    let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+    eprintln!("Batch summary: {} succeeded, {failed} failed", results.len() - failed);
+    for (_, result) in &results {
+        if let Err(message) = result {
+            eprintln!("  [failed] {message}");
+        }
+    }
+    Ok(failed == 0)
+}
+
+// Status line of a finished job.
+fn status(job: &Job, result: &std::result::Result<Report, String>) -> String {
+    match result {
+        Ok(report) => format!(
+            "[ok]     {} -> {} ({}x{}, {} spp) in {:.1?}",
+            job.scene.display(),
+            job.output.display(),
+            report.width,
+            report.height,
+            report.samples_per_pixel,
+            report.elapsed,
+        ),
+        Err(message) => format!("[failed] {message}"),
+    }
+}
+
+fn render_job(job: &Job, bvh: BvhSettings, progress: &dyn Progress) -> std::result::Result<Report, String> {
+    let start = Instant::now();
+    let bvh = BvhSettings {
+        builder: job.bvh.unwrap_or(bvh.builder),
+        width: job.bvh_width.unwrap_or(bvh.width),
+        compact: job.bvh_compact.unwrap_or(bvh.compact),
+    };
+    let scene = Scene::load(&job.scene, bvh).map_err(|err| format!("{}: {err}", job.scene.display()))?;
+    if let Some(layer) = &job.layer {
+        scene
+            .check_group(layer)
+            .map_err(|err| format!("{}: {err}", job.scene.display()))?;
+    }
+
+    let mut settings = scene.camera;
+    settings.image_width = job.image_width.unwrap_or(settings.image_width);
+    settings.samples_per_pixel = job.samples_per_pixel.unwrap_or(settings.samples_per_pixel);
+    settings.max_depth = job.max_depth.unwrap_or(settings.max_depth);
+    let camera = Camera::new(settings)
+        .with_layer(job.layer.as_deref())
+        .with_light_links(scene.light_links)
+        .with_lens(scene.lens.as_deref())
+        .with_aperture_mask(scene.aperture_mask)
+        .with_lut(scene.lut);
+    let world = AcceleratedList::new(scene.world, scene.accelerator);
+
+    let write = || -> std::io::Result<()> {
+        if let Some(parent) = job.output.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let (image, _) = camera.render_image(&world, progress)?;
+        write_image(
+            &image,
+            ImageFormat::of_path(&job.output),
+            &mut File::create(&job.output)?,
+        )
+    };
+    write().map_err(|err| format!("{}: {err}", job.output.display()))?;
+
+    Ok(Report {
+        width: camera.image_width(),
+        height: camera.image_height(),
+        samples_per_pixel: settings.samples_per_pixel,
+        elapsed: start.elapsed(),
+    })
+}
+
+// Overall progress of the batch together with the progress of the jobs rendering.
+#[cfg(feature = "progress")]
+struct BatchProgress {
+    multi: indicatif::MultiProgress,
+    jobs: indicatif::ProgressBar,
+    scanlines: Mutex<Vec<(usize, indicatif::ProgressBar)>>, // bars of the jobs rendering
+    count: usize,
+}
+
+#[cfg(feature = "progress")]
+impl BatchProgress {
+    fn new(count: usize, _parallel: usize) -> BatchProgress {
+        use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+
+        let multi = MultiProgress::new();
+        let jobs = multi.add(ProgressBar::new(count as u64));
+        jobs.set_style(ProgressStyle::with_template("Jobs done: {pos}/{len}").unwrap());
+        BatchProgress {
+            multi,
+            jobs,
+            scanlines: Mutex::new(vec![]),
+            count,
+        }
+    }
+
+    fn start_job(&self, index: usize, scene: &Path) -> Box<dyn Progress + '_> {
+        let bar = self.multi.add(indicatif::ProgressBar::new(0));
+        let progress = rustracer::progress::BarProgress::new(bar.clone(), "");
+        bar.set_prefix(format!("Job {}/{} {}", index + 1, self.count, scene.display()));
+        self.scanlines.lock().unwrap().push((index, bar));
+        Box::new(progress)
+    }
+
+    fn finish_job(&self, index: usize, status: &str) {
+        let mut scanlines = self.scanlines.lock().unwrap();
+        if let Some(position) = scanlines.iter().position(|(job, _)| *job == index) {
+            let (_, bar) = scanlines.remove(position);
+            bar.finish_and_clear();
+            self.multi.remove(&bar);
+        }
+        self.jobs.inc(1);
+        self.multi
+            .suspend(|| eprintln!("Job {}/{} {status}", index + 1, self.count));
+    }
+
+    fn finish(&self) -> std::io::Result<()> {
+        self.multi.clear()
+    }
+}
+
+#[cfg(not(feature = "progress"))]
+struct BatchProgress {
+    count: usize,
+    parallel: usize,
+}
+
+#[cfg(not(feature = "progress"))]
+impl BatchProgress {
+    fn new(count: usize, parallel: usize) -> BatchProgress {
+        BatchProgress { count, parallel }
+    }
+
+    // The scanlines of jobs rendering side by side would overwrite each other's line, only single jobs show them.
+    fn start_job(&self, index: usize, scene: &Path) -> Box<dyn Progress + '_> {
+        rustracer::info!("job {}/{} {}", index + 1, self.count, scene.display());
+        match self.parallel {
+            1 => Box::new(rustracer::progress::TextProgress::new("Scanlines:")),
+            _ => Box::new(rustracer::progress::NoProgress),
+        }
+    }
+
+    fn finish_job(&self, index: usize, status: &str) {
+        eprintln!("Job {}/{} {status}", index + 1, self.count);
+    }
+
+    fn finish(&self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,535 @@
+use std::{ops::Range, str::FromStr};
+
+mod quantized;
+mod wide;
+
+use quantized::QuantizedNode;
+use wide::WideNode;
+
+use crate::{
+    aabb::Aabb,
+    float::Float,
+    packet::{self, RayPacket},
+    ray::Ray,
+    simd::{Floats, Mask},
+    stats::{self, Counter},
+    vec3::{Point, Vec3},
+};
+
+const BINS: usize = 16; // candidate split planes per axis are the boundaries between the bins
+const TRAVERSAL_COST: Float = 1.0; // cost of visiting a node relative to intersecting a primitive
+const MAX_LEAF_SIZE: usize = 4; // larger leaves are split even when the heuristic doesn't see a gain
+const MAX_DEPTH: usize = 48; // bounds the traversal stack, deeper nodes become leaves
+const STACK_SIZE: usize = MAX_DEPTH + 2;
+
+// How the hierarchy is built. The surface area heuristic gives the fastest traversal, the linear builder (LBVH)
+// sorts the primitives along a Morton curve and splits the sorted list, which builds an order of magnitude faster at
+// the cost of slower rays. It is meant for interactive and preview renders where build time matters more.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BvhBuilder {
+    #[default]
+    Sah,
+    Linear,
+}
+
+impl FromStr for BvhBuilder {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<BvhBuilder, String> {
+        match s {
+            "sah" => Ok(BvhBuilder::Sah),
+            "lbvh" | "linear" => Ok(BvhBuilder::Linear),
+            _ => Err(format!("unknown BVH builder '{s}', expected 'sah' or 'lbvh'")),
+        }
+    }
+}
+
+impl BvhBuilder {
+    pub fn name(self) -> &'static str {
+        match self {
+            BvhBuilder::Sah => "sah",
+            BvhBuilder::Linear => "lbvh",
+        }
+    }
+}
+
+// Number of children per node. Wide nodes test all their child boxes at once using SIMD and make the tree shallower,
+// the hierarchy is built as a binary tree first and collapsed afterwards.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BvhWidth {
+    Binary,
+    #[default]
+    Four,
+    Eight,
+}
+
+impl FromStr for BvhWidth {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<BvhWidth, String> {
+        match s {
+            "2" => Ok(BvhWidth::Binary),
+            "4" => Ok(BvhWidth::Four),
+            "8" => Ok(BvhWidth::Eight),
+            _ => Err(format!("unsupported BVH width '{s}', expected 2, 4 or 8")),
+        }
+    }
+}
+
+impl BvhWidth {
+    pub fn children(self) -> usize {
+        match self {
+            BvhWidth::Binary => 2,
+            BvhWidth::Four => 4,
+            BvhWidth::Eight => 8,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BvhSettings {
+    pub builder: BvhBuilder,
+    pub width: BvhWidth,
+    pub compact: bool, // quantized binary nodes of 32 bytes, halving the memory traffic; the width is ignored
+}
+
+// Bounding volume hierarchy over primitives identified by their index. It only knows the bounding boxes, the owner
+// (an object list or a triangle mesh) intersects the primitives of the leaves the ray reaches.
+//
+// Nodes are split using the surface area heuristic: the centroids are sorted into bins along every axis and the
+// boundary between bins that minimizes the expected cost of a ray hitting the node is chosen, which is the area of
+// every child (the probability of a random ray hitting it) times the number of primitives in it.
+pub struct Bvh {
+    nodes: Vec<Node>,    // binary hierarchy as built
+    layout: Layout,      // nodes used for traversal
+    indices: Vec<usize>, // primitive indices, every leaf refers to a contiguous range
+}
+
+enum Layout {
+    Binary,
+    Four(Vec<WideNode<4>>),
+    Eight(Vec<WideNode<8>>),
+    Quantized(Vec<QuantizedNode>),
+}
+
+#[derive(Copy, Clone)]
+struct Node {
+    bounds: Aabb,
+    start: usize, // first child of interior nodes (the second one follows it), first entry of `indices` for leaves
+    count: usize, // number of primitives in a leaf, 0 for interior nodes
+}
+
+// Split of a node between the bins of one axis.
+struct Split {
+    axis: usize,
+    bin: usize, // last bin that goes to the left child
+    cost: Float,
+}
+
+impl Bvh {
+    pub fn new(bounds: &[Aabb], settings: BvhSettings) -> Bvh {
+        let mut bvh = Bvh {
+            nodes: vec![],
+            layout: Layout::Binary,
+            indices: (0..bounds.len()).collect(),
+        };
+        if bounds.is_empty() {
+            return bvh;
+        }
+        let centroids: Vec<Point> = bounds.iter().map(Aabb::centroid).collect();
+        bvh.nodes.push(Node {
+            bounds: bounds.iter().fold(Aabb::EMPTY, |acc, b| acc.union(*b)),
+            start: 0,
+            count: bounds.len(),
+        });
+        match settings.builder {
+            BvhBuilder::Sah => bvh.subdivide(0, 0, bounds, &centroids),
+            BvhBuilder::Linear => {
+                let centroid_bounds = Aabb::from_points(centroids.iter().copied());
+                let keys = bvh
+                    .indices
+                    .iter()
+                    .map(|&i| (morton_code(centroids[i], &centroid_bounds), i));
+                let sorted = radix_sort(keys.collect());
+                bvh.indices = sorted.iter().map(|&(_, i)| i).collect();
+                let codes: Vec<u64> = sorted.iter().map(|&(code, _)| code).collect();
+                bvh.subdivide_linear(0, 0, bounds, &codes);
+            }
+        }
+        bvh.layout = match settings.width {
+            _ if settings.compact => Layout::Quantized(quantized::quantize(&bvh.nodes)),
+            BvhWidth::Binary => Layout::Binary,
+            BvhWidth::Four => Layout::Four(wide::collapse(&bvh.nodes)),
+            BvhWidth::Eight => Layout::Eight(wide::collapse(&bvh.nodes)),
+        };
+        bvh
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.nodes.first().map_or(Aabb::EMPTY, |node| node.bounds)
+    }
+
+    // Update the node bounds after the primitives moved, keeping the topology of the tree. This is a single pass over
+    // the nodes instead of a full build, but the tree gets less efficient the further primitives move from where
+    // they were when it was built. `bounds` must hold the same primitives as when building.
+    pub fn refit(&mut self, bounds: &[Aabb]) {
+        assert_eq!(
+            bounds.len(),
+            self.indices.len(),
+            "refit with a different number of primitives"
+        );
+        // Children are always stored after their parent
+        for index in (0..self.nodes.len()).rev() {
+            let Node { start, count, .. } = self.nodes[index];
+            self.nodes[index].bounds = if count > 0 {
+                self.indices[start..start + count]
+                    .iter()
+                    .fold(Aabb::EMPTY, |acc, &i| acc.union(bounds[i]))
+            } else {
+                self.nodes[start].bounds.union(self.nodes[start + 1].bounds)
+            };
+        }
+        self.layout = match self.layout {
+            Layout::Binary => Layout::Binary,
+            Layout::Four(_) => Layout::Four(wide::collapse(&self.nodes)),
+            Layout::Eight(_) => Layout::Eight(wide::collapse(&self.nodes)),
+            Layout::Quantized(_) => Layout::Quantized(quantized::quantize(&self.nodes)),
+        };
+    }
+
+    fn subdivide(&mut self, node: usize, depth: usize, bounds: &[Aabb], centroids: &[Point]) {
+        let Node { start, count, .. } = self.nodes[node];
+        if count <= 1 || depth >= MAX_DEPTH {
+            return;
+        }
+        let indices = &mut self.indices[start..start + count];
+        let centroid_bounds = Aabb::from_points(indices.iter().map(|&i| centroids[i]));
+        let Some(split) = find_split(indices, bounds, centroids, &centroid_bounds) else {
+            return;
+        };
+        // Leaf cost is one intersection per primitive, with the area of the node cancelled out of the comparison
+        if split.cost >= count as Float && count <= MAX_LEAF_SIZE {
+            return;
+        }
+
+        let bin = |i: usize| bin_index(centroids[i], split.axis, &centroid_bounds);
+        let mid = partition(indices, |i| bin(i) <= split.bin);
+        let left_bounds = indices[..mid].iter().fold(Aabb::EMPTY, |acc, &i| acc.union(bounds[i]));
+        let right_bounds = indices[mid..].iter().fold(Aabb::EMPTY, |acc, &i| acc.union(bounds[i]));
+
+        let left = self.nodes.len();
+        self.nodes.push(Node {
+            bounds: left_bounds,
+            start,
+            count: mid,
+        });
+        self.nodes.push(Node {
+            bounds: right_bounds,
+            start: start + mid,
+            count: count - mid,
+        });
+        self.nodes[node].start = left;
+        self.nodes[node].count = 0;
+
+        self.subdivide(left, depth + 1, bounds, centroids);
+        self.subdivide(left + 1, depth + 1, bounds, centroids);
+    }
+
+    // Split the primitives, already sorted by their Morton codes, at the highest bit in which the codes of the node
+    // differ. Returns the bounds of the node, which are computed bottom-up.
+    fn subdivide_linear(&mut self, node: usize, depth: usize, bounds: &[Aabb], codes: &[u64]) -> Aabb {
+        let Node { start, count, .. } = self.nodes[node];
+        if count <= MAX_LEAF_SIZE || depth >= MAX_DEPTH {
+            let leaf_bounds = self.indices[start..start + count]
+                .iter()
+                .fold(Aabb::EMPTY, |acc, &i| acc.union(bounds[i]));
+            self.nodes[node].bounds = leaf_bounds;
+            return leaf_bounds;
+        }
+
+        let codes_of_node = &codes[start..start + count];
+        let (first, last) = (codes_of_node[0], codes_of_node[count - 1]);
+        let mid = if first == last {
+            // Primitives in the same Morton cell can't be told apart, halve the list instead
+            count / 2
+        } else {
+            // All codes of the node share the bits above the highest differing one, so the bit is sorted too
+            let bit = 63 - (first ^ last).leading_zeros();
+            codes_of_node.partition_point(|code| (code >> bit) & 1 == 0)
+        };
+
+        let left = self.nodes.len();
+        for (start, count) in [(start, mid), (start + mid, count - mid)] {
+            self.nodes.push(Node {
+                bounds: Aabb::EMPTY,
+                start,
+                count,
+            });
+        }
+        self.nodes[node].start = left;
+        self.nodes[node].count = 0;
+
+        let left_bounds = self.subdivide_linear(left, depth + 1, bounds, codes);
+        let right_bounds = self.subdivide_linear(left + 1, depth + 1, bounds, codes);
+        let node_bounds = left_bounds.union(right_bounds);
+        self.nodes[node].bounds = node_bounds;
+        node_bounds
+    }
+
+    // Visit the leaves the ray passes through, nearest first. `hit_primitive` intersects a primitive within the given
+    // range and returns the distance of the hit, which limits the rest of the search to closer primitives.
+    pub fn traverse(
+        &self,
+        ray: Ray,
+        t_range: Range<Float>,
+        mut hit_primitive: impl FnMut(usize, Range<Float>) -> Option<Float>,
+    ) {
+        match &self.layout {
+            Layout::Binary => self.traverse_binary(ray, t_range, &mut hit_primitive),
+            Layout::Four(nodes) => {
+                wide::traverse::<4, { wide::STACK_SIZE_4 }>(nodes, &self.indices, ray, t_range, &mut hit_primitive)
+            }
+            Layout::Eight(nodes) => {
+                wide::traverse::<8, { wide::STACK_SIZE_8 }>(nodes, &self.indices, ray, t_range, &mut hit_primitive)
+            }
+            Layout::Quantized(nodes) => {
+                quantized::traverse(nodes, &self.bounds(), &self.indices, ray, t_range, &mut hit_primitive)
+            }
+        }
+    }
+
+    fn traverse_binary(
+        &self,
+        ray: Ray,
+        t_range: Range<Float>,
+        hit_primitive: &mut impl FnMut(usize, Range<Float>) -> Option<Float>,
+    ) {
+        let Some(root) = self.nodes.first() else {
+            return;
+        };
+        let inv_direction = Vec3::new(1.0 / ray.direction.x, 1.0 / ray.direction.y, 1.0 / ray.direction.z);
+        let mut t_range = t_range;
+        let Some(t_root) = root.bounds.hit(ray, inv_direction, &t_range) else {
+            return;
+        };
+
+        // Nodes still to visit with the distance at which the ray enters them
+        let mut stack = [(0, 0.0); STACK_SIZE];
+        stack[0] = (0, t_root);
+        let mut len = 1;
+        while len > 0 {
+            len -= 1;
+            let (index, t_enter) = stack[len];
+            // A hit found since the node was pushed can make it irrelevant
+            if t_enter > t_range.end {
+                continue;
+            }
+            let node = &self.nodes[index];
+            stats::count(Counter::NodeVisits, 1);
+            if node.count > 0 {
+                for &primitive in &self.indices[node.start..node.start + node.count] {
+                    if let Some(t) = hit_primitive(primitive, t_range.clone()) {
+                        t_range.end = t;
+                    }
+                }
+                continue;
+            }
+
+            let left = self.nodes[node.start].bounds.hit(ray, inv_direction, &t_range);
+            let right = self.nodes[node.start + 1].bounds.hit(ray, inv_direction, &t_range);
+            // Push the farther child first, so the nearer one is visited next
+            let mut push = |child, t_enter| {
+                stack[len] = (child, t_enter);
+                len += 1;
+            };
+            match (left, right) {
+                (Some(t_left), Some(t_right)) if t_left <= t_right => {
+                    push(node.start + 1, t_right);
+                    push(node.start, t_left);
+                }
+                (Some(t_left), Some(t_right)) => {
+                    push(node.start, t_left);
+                    push(node.start + 1, t_right);
+                }
+                (Some(t_left), None) => push(node.start, t_left),
+                (None, Some(t_right)) => push(node.start + 1, t_right),
+                (None, None) => {}
+            }
+        }
+    }
+
+    // Same as `traverse` for all rays of the packet at once. A node is visited when any active ray hits it, in the
+    // order of the nearest of them. Every ray searches from `t_start` to its entry of `t_ends`, `hit_primitive`
+    // intersects a primitive with the lanes that reached it and lowers the ends of the lanes it hits. This always
+    // walks the binary nodes, which suit packets better than wide ones: the packet already fills the SIMD lanes.
+    pub fn traverse_packet<const N: usize>(
+        &self,
+        packet: &RayPacket<N>,
+        t_start: Float,
+        t_ends: &mut [Float; N],
+        mut hit_primitive: impl FnMut(usize, Mask<N>, &mut [Float; N]),
+    ) {
+        let Some(root) = self.nodes.first() else {
+            return;
+        };
+        let t_start = t_start as f32;
+        let mut t_end = Floats(t_ends.map(packet::t_end));
+        if !packet.hit(&root.bounds, t_start, t_end).0.any() {
+            return;
+        }
+
+        let mut stack = [0; STACK_SIZE];
+        let mut len = 1;
+        while len > 0 {
+            len -= 1;
+            let node = &self.nodes[stack[len]];
+            stats::count(Counter::NodeVisits, 1);
+            if node.count > 0 {
+                // The lanes are tested again, hits found since the node was pushed can rule some of them out
+                let (lanes, _) = packet.hit(&node.bounds, t_start, t_end);
+                if lanes.any() {
+                    for &primitive in &self.indices[node.start..node.start + node.count] {
+                        hit_primitive(primitive, lanes, t_ends);
+                    }
+                    t_end = Floats(t_ends.map(packet::t_end));
+                }
+                continue;
+            }
+
+            let (left_mask, t_left) = packet.hit(&self.nodes[node.start].bounds, t_start, t_end);
+            let (right_mask, t_right) = packet.hit(&self.nodes[node.start + 1].bounds, t_start, t_end);
+            let mut push = |child| {
+                stack[len] = child;
+                len += 1;
+            };
+            match (left_mask.any(), right_mask.any()) {
+                (true, true) if t_left.min_where(left_mask) <= t_right.min_where(right_mask) => {
+                    push(node.start + 1);
+                    push(node.start);
+                }
+                (true, true) => {
+                    push(node.start);
+                    push(node.start + 1);
+                }
+                (true, false) => push(node.start),
+                (false, true) => push(node.start + 1),
+                (false, false) => {}
+            }
+        }
+    }
+}
+
+fn bin_index(centroid: Point, axis: usize, centroid_bounds: &Aabb) -> usize {
+    let min = centroid_bounds.min[axis];
+    let extent = centroid_bounds.extent()[axis];
+    let bin = ((centroid[axis] - min) / extent * BINS as Float) as usize;
+    bin.min(BINS - 1)
+}
+
+// Cheapest split over all axes, None if all centroids coincide and the primitives can't be separated.
+fn find_split(indices: &[usize], bounds: &[Aabb], centroids: &[Point], centroid_bounds: &Aabb) -> Option<Split> {
+    let parent_area = indices
+        .iter()
+        .fold(Aabb::EMPTY, |acc, &i| acc.union(bounds[i]))
+        .surface_area();
+    let mut best: Option<Split> = None;
+    for axis in 0..3 {
+        if centroid_bounds.extent()[axis] <= 0.0 {
+            continue;
+        }
+        let mut bins = [(Aabb::EMPTY, 0usize); BINS];
+        for &i in indices {
+            let bin = &mut bins[bin_index(centroids[i], axis, centroid_bounds)];
+            bin.0 = bin.0.union(bounds[i]);
+            bin.1 += 1;
+        }
+
+        // Sweep from the right to get the cost of everything after each boundary, then from the left
+        let mut right_cost = [0.0; BINS];
+        let mut area = Aabb::EMPTY;
+        let mut count = 0;
+        for bin in (1..BINS).rev() {
+            area = area.union(bins[bin].0);
+            count += bins[bin].1;
+            right_cost[bin - 1] = area.surface_area() * count as Float;
+        }
+        let mut area = Aabb::EMPTY;
+        let mut count = 0;
+        for bin in 0..BINS - 1 {
+            area = area.union(bins[bin].0);
+            count += bins[bin].1;
+            let cost = TRAVERSAL_COST + (area.surface_area() * count as Float + right_cost[bin]) / parent_area;
+            if best.as_ref().is_none_or(|best| cost < best.cost) {
+                best = Some(Split { axis, bin, cost });
+            }
+        }
+    }
+    best
+}
+
+// Interleave the bits of the position quantized to 21 bits per axis within `bounds`, so that points close to each
+// other along the resulting Z-order curve are close in space as well. Points outside are clamped to the bounds.
+pub(crate) fn morton_code(point: Point, bounds: &Aabb) -> u64 {
+    const SCALE: Float = ((1 << 21) - 1) as Float;
+    let extent = bounds.extent();
+    let quantize = |axis: usize| {
+        let extent = extent[axis];
+        let offset = point[axis] - bounds.min[axis];
+        let normalized = if extent > 0.0 {
+            (offset / extent).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        spread_bits((normalized * SCALE) as u64)
+    };
+    quantize(0) << 2 | quantize(1) << 1 | quantize(2)
+}
+
+// Insert two zero bits between each of the lowest 21 bits.
+fn spread_bits(value: u64) -> u64 {
+    let mut x = value & 0x1f_ffff;
+    x = (x | x << 32) & 0x1f_0000_0000_ffff;
+    x = (x | x << 16) & 0x1f_0000_ff00_00ff;
+    x = (x | x << 8) & 0x100f_00f0_0f00_f00f;
+    x = (x | x << 4) & 0x10c3_0c30_c30c_30c3;
+    x = (x | x << 2) & 0x1249_2492_4924_9249;
+    x
+}
+
+// Least significant digit radix sort of (code, index) pairs, one byte per pass.
+pub(crate) fn radix_sort(mut keys: Vec<(u64, usize)>) -> Vec<(u64, usize)> {
+    let mut scratch = keys.clone();
+    for pass in 0..8 {
+        let digit = |code: u64| ((code >> (8 * pass)) & 0xff) as usize;
+        let mut offsets = [0; 256];
+        for &(code, _) in &keys {
+            offsets[digit(code)] += 1;
+        }
+        let mut sum = 0;
+        for offset in offsets.iter_mut() {
+            (*offset, sum) = (sum, sum + *offset);
+        }
+        for &(code, index) in &keys {
+            let offset = &mut offsets[digit(code)];
+            scratch[*offset] = (code, index);
+            *offset += 1;
+        }
+        std::mem::swap(&mut keys, &mut scratch);
+    }
+    keys
+}
+
+// Reorder `indices` so that the ones matching `left` come first, returns how many there are.
+fn partition(indices: &mut [usize], left: impl Fn(usize) -> bool) -> usize {
+    let mut mid = 0;
+    for i in 0..indices.len() {
+        if left(indices[i]) {
+            indices.swap(i, mid);
+            mid += 1;
+        }
+    }
+    mid
+}
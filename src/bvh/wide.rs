@@ -0,0 +1,181 @@
+use std::ops::Range;
+
+use super::{Node, MAX_DEPTH};
+use crate::{
+    float::Float,
+    ray::Ray,
+    stats::{self, Counter},
+};
+
+// Node of a BVH with up to N children whose boxes are stored component by component. Testing a ray against all of
+// them is the same few instructions for every lane, which the compiler turns into SIMD code (AVX for 4 lanes of
+// Float, two registers for 8) without needing intrinsics or nightly features.
+#[derive(Clone)]
+pub struct WideNode<const N: usize> {
+    min: [[Float; N]; 3], // lower corner of every child box, per axis
+    max: [[Float; N]; 3], // upper corner of every child box, per axis
+    start: [usize; N],    // wide node of interior children, first entry of the primitive indices for leaves
+    count: [usize; N],    // number of primitives in leaf children, 0 for interior ones
+    len: usize,           // number of used lanes
+}
+
+impl<const N: usize> WideNode<N> {
+    fn empty() -> WideNode<N> {
+        WideNode {
+            min: [[Float::INFINITY; N]; 3],
+            max: [[Float::NEG_INFINITY; N]; 3],
+            start: [0; N],
+            count: [0; N],
+            len: 0,
+        }
+    }
+
+    // Distance at which the ray enters every child box, infinity for the ones it misses.
+    fn hit(&self, origin: [Float; 3], inv_direction: [Float; 3], t_range: &Range<Float>) -> [Float; N] {
+        let mut t_min = [t_range.start; N];
+        let mut t_max = [t_range.end; N];
+        for axis in 0..3 {
+            for lane in 0..N {
+                let t0 = (self.min[axis][lane] - origin[axis]) * inv_direction[axis];
+                let t1 = (self.max[axis][lane] - origin[axis]) * inv_direction[axis];
+                t_min[lane] = t_min[lane].max(t0.min(t1));
+                t_max[lane] = t_max[lane].min(t0.max(t1));
+            }
+        }
+        let mut t_enter = [Float::INFINITY; N];
+        for lane in 0..self.len {
+            if t_min[lane] <= t_max[lane] {
+                t_enter[lane] = t_min[lane];
+            }
+        }
+        t_enter
+    }
+}
+
+// Collapse a binary hierarchy into one with N children per node. Every wide node takes the children of a binary node
+// and keeps replacing the interior child with the largest surface area by its own two children until it is full.
+pub fn collapse<const N: usize>(nodes: &[Node]) -> Vec<WideNode<N>> {
+    let mut wide = vec![];
+    if !nodes.is_empty() {
+        collapse_node(nodes, 0, &mut wide);
+    }
+    wide
+}
+
+fn collapse_node<const N: usize>(nodes: &[Node], index: usize, wide: &mut Vec<WideNode<N>>) -> usize {
+    let node = &nodes[index];
+    let mut children = if node.count > 0 {
+        vec![index]
+    } else {
+        vec![node.start, node.start + 1]
+    };
+    while children.len() < N {
+        let largest = children
+            .iter()
+            .enumerate()
+            .filter(|(_, &child)| nodes[child].count == 0)
+            .max_by(|(_, &a), (_, &b)| {
+                let area = |child: usize| nodes[child].bounds.surface_area();
+                area(a).total_cmp(&area(b))
+            })
+            .map(|(position, _)| position);
+        let Some(position) = largest else {
+            break;
+        };
+        let opened = children.swap_remove(position);
+        children.extend([nodes[opened].start, nodes[opened].start + 1]);
+    }
+
+    let wide_index = wide.len();
+    wide.push(WideNode::empty());
+    let mut wide_node = WideNode::empty();
+    wide_node.len = children.len();
+    for (lane, &child_index) in children.iter().enumerate() {
+        let child = &nodes[child_index];
+        for axis in 0..3 {
+            wide_node.min[axis][lane] = child.bounds.min[axis];
+            wide_node.max[axis][lane] = child.bounds.max[axis];
+        }
+        if child.count > 0 {
+            wide_node.start[lane] = child.start;
+            wide_node.count[lane] = child.count;
+        } else {
+            wide_node.start[lane] = collapse_node(nodes, child_index, wide);
+        }
+    }
+    wide[wide_index] = wide_node;
+    wide_index
+}
+
+// Entry of the traversal stack, a wide node (`count` is 0) or a leaf with the distance at which the ray enters it.
+#[derive(Copy, Clone)]
+struct Entry {
+    start: usize,
+    count: usize,
+    t_enter: Float,
+}
+
+// Every visited node pushes at most N - 1 more entries than it pops, so S = MAX_DEPTH * (N - 1) + 1 is enough.
+pub const STACK_SIZE_4: usize = MAX_DEPTH * 3 + 1;
+pub const STACK_SIZE_8: usize = MAX_DEPTH * 7 + 1;
+
+// Same as `Bvh::traverse` for the wide layout, children are visited nearest first.
+pub fn traverse<const N: usize, const S: usize>(
+    nodes: &[WideNode<N>],
+    indices: &[usize],
+    ray: Ray,
+    t_range: Range<Float>,
+    hit_primitive: &mut impl FnMut(usize, Range<Float>) -> Option<Float>,
+) {
+    if nodes.is_empty() {
+        return;
+    }
+    let origin = ray.origin.into();
+    let inv_direction = [1.0 / ray.direction.x, 1.0 / ray.direction.y, 1.0 / ray.direction.z];
+    let mut t_range = t_range;
+
+    let mut stack = [Entry {
+        start: 0,
+        count: 0,
+        t_enter: 0.0,
+    }; S];
+    stack[0].t_enter = t_range.start;
+    let mut len = 1;
+    while len > 0 {
+        len -= 1;
+        let entry = stack[len];
+        if entry.t_enter > t_range.end {
+            continue;
+        }
+        stats::count(Counter::NodeVisits, 1);
+        if entry.count > 0 {
+            for &primitive in &indices[entry.start..entry.start + entry.count] {
+                if let Some(t) = hit_primitive(primitive, t_range.clone()) {
+                    t_range.end = t;
+                }
+            }
+            continue;
+        }
+
+        let node = &nodes[entry.start];
+        let t_enter = node.hit(origin, inv_direction, &t_range);
+        // Sort the children that were hit by decreasing distance, so the nearest one ends up on top of the stack
+        let first = len;
+        for (lane, &t) in t_enter.iter().enumerate().take(node.len) {
+            if t == Float::INFINITY {
+                continue;
+            }
+            let mut position = len;
+            while position > first && stack[position - 1].t_enter < t {
+                stack[position] = stack[position - 1];
+                position -= 1;
+            }
+            stack[position] = Entry {
+                start: node.start[lane],
+                count: node.count[lane],
+                t_enter: t,
+            };
+            len += 1;
+        }
+    }
+}
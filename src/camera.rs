@@ -1,52 +1,1090 @@
-use std::cmp::max;
+mod anaglyph;
+mod autofocus;
+#[cfg(feature = "formats")]
+mod cryptomatte;
+mod debug;
+#[cfg(feature = "formats")]
+mod deep;
+mod wavefront;
 
-use indicatif::{ProgressBar, ProgressStyle};
+use std::{
+    cell::Cell,
+    cmp::max,
+    io::{self, Write},
+    ops::Range,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
+#[cfg(feature = "formats")]
+use crate::exr::LiveImage;
+#[cfg(feature = "network")]
+use crate::preview::PreviewServer;
 use crate::{
-    color::{write_color, Color},
-    hittable::Hittable,
+    animation::Interpolate,
+    aperture::{Aperture, ApertureMask},
+    checkpoint::{CheckpointSettings, Checkpoints},
+    color::Color,
+    color_space::ColorSpace,
+    exposure::DEFAULT_SENSOR_HEIGHT,
+    film::Film,
+    filter::Filter,
+    float::{consts::FRAC_PI_2, Float},
+    hittable::{Hit, Hittable},
+    id::group_id,
+    info,
+    integrator::Integrator,
+    lens::{Lens, LensSystem},
+    light_linking::LightLinks,
+    lut::Lut,
+    medium::MediumStack,
+    output::{write_ppm, Framebuffer},
+    packet::PACKET_SIZE,
+    post::{self, Bloom, FilmGrain, LensFlare, WhiteBalance},
+    progress::Progress,
+    projection::Projection,
     ray::Ray,
+    stats::{self, Counter, Stats},
+    stereo::Stereo,
     util::{degrees_to_radians, random_double},
     vec3::{Point, Vec3},
 };
 
+pub use autofocus::Focus;
+
+// Ignore hits that are very close to the calculated intersection point to solve the "shadow acne"
+const T_RANGE: Range<Float> = 0.001..Float::INFINITY;
+
+// How often a paused render looks whether it's resumed.
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+thread_local! {
+    // Rays the current thread traced since they were last reported to the progress
+    static RAYS: Cell<u64> = const { Cell::new(0) };
+}
+
+fn count_rays(count: usize, primary: bool) {
+    RAYS.with(|rays| rays.set(rays.get() + count as u64));
+    let counter = if primary {
+        Counter::PrimaryRays
+    } else {
+        Counter::SecondaryRays
+    };
+    stats::count(counter, count as u64);
+}
+
+// Count `count` occlusion queries, which only ask whether anything is in the way.
+fn count_shadow_rays(count: usize) {
+    RAYS.with(|rays| rays.set(rays.get() + count as u64));
+    stats::count(Counter::ShadowRays, count as u64);
+}
+
+// Whether anything is in the way of `ray`, counted as a shadow ray whatever the answer.
+fn occluded(world: &dyn Hittable, ray: Ray) -> bool {
+    count_shadow_rays(1);
+    world.hit_any(ray, T_RANGE)
+}
+
+// Report the rays traced by the current thread since the last report.
+fn report_rays(progress: &dyn Progress) {
+    progress.traced(RAYS.with(|rays| rays.replace(0)));
+}
+
+// Without the `formats` feature there is no live OpenEXR file to write, this stands in for it and can't be created.
+#[cfg(not(feature = "formats"))]
+enum LiveImage {}
+
+#[cfg(not(feature = "formats"))]
+impl LiveImage {
+    fn create(_path: &std::path::Path, _width: usize, _height: usize) -> io::Result<LiveImage> {
+        let message = "the live OpenEXR image needs the 'formats' feature";
+        Err(io::Error::new(io::ErrorKind::Unsupported, message))
+    }
+
+    fn write_scanline(&mut self, _y: usize, _colors: &[Color]) -> io::Result<()> {
+        match *self {}
+    }
+
+    fn finish(&mut self, _image: &Framebuffer) -> io::Result<()> {
+        match *self {}
+    }
+}
+
+#[derive(Clone)]
 pub struct Camera {
-    samples_per_pixel: i32, // Count of random samples for each pixel
-    max_depth: i32,         // Maximum number of ray bounces into scene
-    image_width: i32,       // Rendered image width in pixels
-    image_height: i32,      // Rendered image height in pixels
-    center: Point,          // Camera center
-    pixel00_loc: Point,     // Location of pixel 0, 0
-    pixel_delta_u: Vec3,    // Offset to pixel to the right
-    pixel_delta_v: Vec3,    // Offset to pixel below
-    defocus_angle: f64,     // Variation angle of rays through each pixel
-    defocus_disk_u: Vec3,   // Defocus disk horizontal radius
-    defocus_disk_v: Vec3,   // Defocus disk vertical radius
+    samples_per_pixel: i32,      // Count of random samples for each pixel
+    max_depth: i32,              // Maximum number of ray bounces into scene
+    image_width: i32,            // Rendered image width in pixels
+    image_height: i32,           // Rendered image height in pixels
+    eye_size: [i32; 2],          // Size of the view of each eye in pixels, the whole image without stereo
+    stereo: Stereo,              // How the views of the eyes are laid out on the image
+    interocular: Float,          // Distance between the eyes of a stereo camera
+    exposure: Float,             // Scale of the light reaching the image
+    vignetting: Float,           // Strength of the cosine falloff of the light towards the edges
+    chromatic_aberration: Float, // Magnification of the red image over the blue one
+    bloom: Bloom,                // Glow around the bright parts of the image
+    flare: LensFlare,            // Ghosts, halos and streaks of the lens around bright lights
+    grain: FilmGrain,            // Grain of photographic film over the image
+    white_balance: WhiteBalance, // Color balance for the light of the scene
+    color_space: ColorSpace,     // Color space the image is written out in
+    lut: Option<Arc<Lut>>,       // Color grade of the displayed image, see `lut.rs`
+    focus_dist: Float,           // Distance from the camera to the plane in focus
+    sensor_height: Float,        // Height of the sensor behind the lens
+    lens: Option<Arc<Lens>>,     // Lens system the camera rays are traced through, a thin lens if not set
+    view: View,                  // Where the camera is when the shutter opens
+    closing_view: View,          // Where the camera is when the shutter closes
+    next_view: View,             // Where the camera is a frame later, for the motion vectors
+    shutter: Range<Float>,       // Frames the shutter is open, the camera rays are spread over them
+    defocus_angle: Float,        // Variation angle of rays through each pixel
+    aperture: Aperture,          // Shape of the thin lens aperture, the shape of out-of-focus highlights
+    filter: Filter,              // Pixel reconstruction filter
+    packets: bool,               // Trace the camera rays of a pixel together as ray packets
+    wavefront: bool,             // Use the wavefront integrator
+    sort_rays: bool,             // Sort the bounced rays of the wavefront integrator for coherence
+    columns: Range<i32>,         // Columns of the pixels that are rendered, all of them unless the image is cropped
+    rows: Range<i32>,            // Rows of the pixels that are rendered
+    // Where to save the progress of the render
+    checkpoints: Option<CheckpointSettings>,
+    // OpenEXR file the image is written into while it renders
+    live_exr: Option<PathBuf>,
+    // Web page the image is shown on while it renders
+    #[cfg(feature = "network")]
+    preview: Option<Arc<PreviewServer>>,
+    // Set from outside (e.g. by a signal handler) to stop the render after the scanlines in progress
+    stop: Option<&'static AtomicBool>,
+    // Set from outside to pause the render between scanlines, and cleared to resume it
+    pause: Option<&'static AtomicBool>,
+    // Wall-clock time after which a progressive render stops
+    time_budget: Option<Duration>,
+    // Log every bounce of the samples, the image is cropped to the single pixel being debugged
+    debug_pixel: bool,
+    integrator: Integrator,  // What is computed for every sample
+    layer: Option<u32>,      // Group of the objects the camera sees, the others are held out
+    light_links: LightLinks, // Which lights light which objects
+    // Light of rays leaving the scene, a sky gradient if not set
+    background: Option<Color>,
+}
+
+// Position and orientation of the camera, what changes when it moves.
+#[derive(Copy, Clone)]
+struct View {
+    center: Point,        // Camera center
+    pixel00_loc: Point,   // Location of pixel 0, 0
+    pixel_delta_u: Vec3,  // Offset to pixel to the right
+    pixel_delta_v: Vec3,  // Offset to pixel below
+    defocus_disk_u: Vec3, // Defocus disk horizontal radius
+    defocus_disk_v: Vec3, // Defocus disk vertical radius
+    focus_plane: Vec3,    // Normal of the plane in focus over its distance from the camera center
+    projection: Projection,
+    fov: Float, // Vertical field of view in radians
+}
+
+// What a render got done, it may have been stopped before the end.
+#[derive(Copy, Clone, Debug)]
+pub struct RenderInfo {
+    pub samples: u64,             // camera samples traced, including the ones of a resumed checkpoint
+    pub samples_per_pixel: Float, // average over the rendered pixels
+    pub stopped: bool,            // the render was stopped before all samples were done
+    pub stats: Stats,             // work done, only counted with the `stats` feature
+}
+
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CameraSettings {
+    pub aspect_ratio: Float,      // Ratio of image width over height
+    pub image_width: i32,         // Rendered image width in pixels
+    pub samples_per_pixel: i32,   // Count of random samples for each pixel
+    pub max_depth: i32,           // Maximum number of ray bounces into scene
+    pub vfov: Float,              // Vertical view angle (field of view)
+    pub look_from: Point,         // Point camera is looking from
+    pub look_at: Point,           // Point camera is looking at
+    pub vup: Vec3,                // Camera-relative "up" direction
+    pub defocus_angle: Float,     // Variation angle of rays through each pixel
+    pub focus_dist: Float,        // Distance from camera look_from point to plane of perfect focus
+    pub filter: Filter,           // Pixel reconstruction filter
+    pub projection: Projection,   // How the lens maps directions onto the image, perspective or fisheye
+    pub shutter: Float,           // Fraction of a frame the shutter is open in animations, for motion blur
+    pub stereo: Stereo,           // Views for both eyes side by side or on top of each other, for VR headsets
+    pub interocular: Float,       // Distance between the eyes of a stereo camera
+    pub exposure: Float,          // Scale of the light reaching the image, from the photographic settings
+    pub sensor_height: Float,     // Height of the sensor behind the f-number and the lens of the camera
+    pub aperture_blades: u32,     // Count of blades closing the aperture into a polygon, round if 0
+    pub aperture_rotation: Float, // Rotation of the polygon of the aperture in degrees
+    pub shift_x: Float,           // Shift of the lens to the right, in image widths
+    pub shift_y: Float,           // Shift of the lens up, in image heights
+    pub tilt: Float,              // Tilt of the lens down in degrees, the plane of focus leans back from the camera
+    pub swing: Float,             // Swing of the lens to the left in degrees, the plane of focus turns to the right
+    pub vignetting: Float,        // Strength of the cosine falloff of the light towards the edges, from 0 to 1
+    // Magnification of the red image over the blue one, for color fringes towards the edges
+    pub chromatic_aberration: Float,
+    pub bloom: Bloom,                // Glow around the bright parts of the image
+    pub flare: LensFlare,            // Ghosts, halos and streaks of the lens around bright lights
+    pub grain: FilmGrain,            // Grain of photographic film over the image
+    pub white_balance: WhiteBalance, // Color balance for the light of the scene
+    pub color_space: ColorSpace,     // Color space the image is written out in
+    // Light arriving from where rays leave the scene, a sky gradient if not set
+    pub background: Option<Color>,
+}
+
+impl Default for CameraSettings {
+    fn default() -> CameraSettings {
+        CameraSettings {
+            aspect_ratio: 1.0,
+            image_width: 100,
+            samples_per_pixel: 10,
+            max_depth: 10,
+            vfov: 90.0,
+            look_from: Point::new(0.0, 0.0, 0.0),
+            look_at: Point::new(0.0, 0.0, -1.0),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            defocus_angle: 0.0,
+            focus_dist: 10.0,
+            filter: Filter::Box,
+            projection: Projection::Perspective,
+            shutter: 0.5,
+            stereo: Stereo::Mono,
+            interocular: 0.065,
+            exposure: 1.0,
+            sensor_height: DEFAULT_SENSOR_HEIGHT,
+            aperture_blades: 0,
+            aperture_rotation: 0.0,
+            shift_x: 0.0,
+            shift_y: 0.0,
+            tilt: 0.0,
+            swing: 0.0,
+            vignetting: 0.0,
+            chromatic_aberration: 0.0,
+            bloom: Bloom::default(),
+            flare: LensFlare::default(),
+            grain: FilmGrain::default(),
+            white_balance: WhiteBalance::default(),
+            color_space: ColorSpace::default(),
+            background: None,
+        }
+    }
 }
 
 impl Camera {
-    pub fn new(
-        aspect_ratio: f64,      // Ratio of image width over height
-        image_width: i32,       // Rendered image width in pixels
-        samples_per_pixel: i32, // Count of random samples for each pixel
-        max_depth: i32,         // Maximum number of ray bounces into scene
-        vfov: f64,              // Vertical view angle (field of view)
-        look_from: Point,       // Point camera is looking from
-        look_at: Point,         // Point camera is looking at
-        vup: Vec3,              // Camera-relative "up" direction
-        defocus_angle: f64,     // Variation angle of rays through each pixel
-        focus_dist: f64,        // Distance from camera look_from point to plane of perfect focus
-    ) -> Camera {
+    pub fn new(settings: CameraSettings) -> Camera {
+        let CameraSettings {
+            aspect_ratio,
+            image_width,
+            samples_per_pixel,
+            max_depth,
+            defocus_angle,
+            filter,
+            stereo,
+            interocular,
+            exposure,
+            focus_dist,
+            sensor_height,
+            aperture_blades,
+            aperture_rotation,
+            chromatic_aberration,
+            vignetting,
+            bloom,
+            flare,
+            grain,
+            white_balance,
+            color_space,
+            background,
+            ..
+        } = settings;
+
         // Ensure that height is bigger than 1
-        let image_height = max(1, (image_width as f64 / aspect_ratio) as i32);
+        let eye_height = max(1, (image_width as Float / aspect_ratio) as i32);
+        let view = View::new(&settings, eye_height);
+        let [image_width, image_height] = stereo.image_size(settings.image_width, eye_height);
+
+        Camera {
+            samples_per_pixel,
+            max_depth,
+            image_width,
+            image_height,
+            eye_size: [settings.image_width, eye_height],
+            view,
+            closing_view: view,
+            next_view: view,
+            shutter: 0.0..0.0,
+            defocus_angle,
+            aperture: Aperture::polygon(aperture_blades, aperture_rotation),
+            filter,
+            stereo,
+            interocular,
+            exposure,
+            vignetting,
+            chromatic_aberration,
+            bloom,
+            flare,
+            grain,
+            white_balance,
+            color_space,
+            lut: None,
+            focus_dist,
+            sensor_height,
+            lens: None,
+            background,
+            packets: false,
+            wavefront: false,
+            sort_rays: false,
+            columns: 0..image_width,
+            rows: 0..image_height,
+            checkpoints: None,
+            live_exr: None,
+            #[cfg(feature = "network")]
+            preview: None,
+            stop: None,
+            pause: None,
+            time_budget: None,
+            debug_pixel: false,
+            integrator: Integrator::Path,
+            layer: None,
+            light_links: LightLinks::default(),
+        }
+    }
+
+    // Spread the camera rays over the frames in `shutter`, so objects moving meanwhile are blurred. The camera itself
+    // moves from its own settings when the shutter opens to the `closing` ones, the settings of the image stay.
+    pub fn with_shutter(self, shutter: Range<Float>, closing: CameraSettings) -> Camera {
+        Camera {
+            shutter,
+            closing_view: View::new(&closing, self.eye_size[1]),
+            defocus_angle: self.defocus_angle.max(closing.defocus_angle),
+            ..self
+        }
+    }
+
+    // Where the camera is a frame after the one rendered, the motion integrator measures how far the points seen move
+    // on the image until then. Without it the camera stands still.
+    pub fn with_next_frame(self, next: CameraSettings) -> Camera {
+        Camera {
+            next_view: View::new(&next, self.eye_size[1]),
+            ..self
+        }
+    }
+
+    // Trace the samples of every pixel as packets of rays. They start at nearly the same point in nearly the same
+    // direction and visit the same BVH nodes, which pays off with large meshes. Scenes of many small objects and
+    // instances can get slower, the rays diverge soon and the packet tests waste work on the missing rays.
+    pub fn with_packets(self, packets: bool) -> Camera {
+        Camera { packets, ..self }
+    }
+
+    // Render with the wavefront integrator, which advances the samples of many scanlines a bounce at a time instead of
+    // following every sample through all its bounces, see `wavefront.rs`.
+    pub fn with_wavefront(self, wavefront: bool) -> Camera {
+        Camera { wavefront, ..self }
+    }
+
+    // Sort the rays of every bounce after the first by direction and origin before tracing them, which only applies to
+    // the wavefront integrator.
+    pub fn with_ray_sorting(self, sort_rays: bool) -> Camera {
+        Camera { sort_rays, ..self }
+    }
+
+    // Only render the pixels in the window from column x0 and row y0 up to, but excluding, column x1 and row y1.
+    // The rest of the image stays black, which saves the time for the whole frame when looking at a part of it.
+    pub fn with_crop(self, crop: Option<[i32; 4]>) -> Camera {
+        let Some([x0, y0, x1, y1]) = crop else {
+            return self;
+        };
+        let clamp = |value: i32, size: i32| value.clamp(0, size);
+        Camera {
+            columns: clamp(x0, self.image_width)..clamp(x1, self.image_width),
+            rows: clamp(y0, self.image_height)..clamp(y1, self.image_height),
+            ..self
+        }
+    }
+
+    // Periodically save the progress of the render, and pick it up again from an earlier checkpoint if the settings
+    // say so, see `checkpoint.rs`.
+    pub fn with_checkpoints(self, checkpoints: Option<CheckpointSettings>) -> Camera {
+        Camera { checkpoints, ..self }
+    }
+
+    // Write the image into an OpenEXR file while it renders, every scanline as soon as it's done and the finished
+    // image at the end, see `exr.rs`.
+    pub fn with_live_exr(self, live_exr: Option<PathBuf>) -> Camera {
+        Camera { live_exr, ..self }
+    }
+
+    // Stream the image to the browsers of a preview server while it renders, like the live OpenEXR file, see
+    // `preview.rs`.
+    #[cfg(feature = "network")]
+    pub fn with_preview(self, preview: Option<Arc<PreviewServer>>) -> Camera {
+        Camera { preview, ..self }
+    }
+
+    // Stop rendering once `stop` is set, at the end of the scanlines in progress. The image is then made of the
+    // scanlines that are done and black everywhere else.
+    pub fn with_stop(self, stop: Option<&'static AtomicBool>) -> Camera {
+        Camera { stop, ..self }
+    }
+
+    // Wait at the end of the scanlines in progress while `pause` is set, keeping the samples so far, e.g. to free the
+    // CPU for a while. Time spent paused doesn't count against the time budget, a stop ends the pause.
+    pub fn with_pause(self, pause: Option<&'static AtomicBool>) -> Camera {
+        Camera { pause, ..self }
+    }
+
+    // Render progressively: go over the image again and again with a few samples per pixel at a time, until either
+    // all samples per pixel are done or the time is up, and keep the image of the samples up to then. The
+    // whole image improves evenly, a pass cut short only leaves some scanlines with one pass less. Not available with
+    // the wavefront integrator, and renders with a time budget save no checkpoints.
+    pub fn with_time_budget(self, time_budget: Option<Duration>) -> Camera {
+        Camera { time_budget, ..self }
+    }
+
+    // Only render the pixel at column x and row y, with the camera rays traced one by one, and log every bounce of its
+    // samples: where it hit, the material, the attenuation and the throughput of the path, see `debug.rs`.
+    pub fn with_debug_pixel(self, pixel: Option<[i32; 2]>) -> Camera {
+        let Some([x, y]) = pixel else {
+            return self;
+        };
+        Camera {
+            packets: false,
+            wavefront: false,
+            debug_pixel: true,
+            ..self.with_crop(Some([x, y, x + 1, y + 1]))
+        }
+    }
+
+    // Compute the samples with a different integrator than the path tracer, see `integrator.rs`. The camera rays of
+    // the false-color integrators are traced one by one.
+    pub fn with_integrator(self, integrator: Integrator) -> Camera {
+        Camera {
+            packets: self.packets && integrator == Integrator::Path,
+            wavefront: self.wavefront && integrator == Integrator::Path,
+            integrator,
+            ..self
+        }
+    }
+
+    // Render the layer of a group of objects: camera rays that hit objects of other groups are held out, the pixels
+    // stay black where they cover them. The held out objects still cast shadows and show in reflections. The layer
+    // `default` has the objects without a group and the background, so the layers of all groups add up to the image.
+    pub fn with_layer(self, group: Option<&str>) -> Camera {
+        Camera {
+            layer: group.map(group_id),
+            ..self
+        }
+    }
+
+    // Let the lights only light the objects they are linked with, see `light_linking.rs`.
+    pub fn with_light_links(self, light_links: LightLinks) -> Camera {
+        Camera { light_links, ..self }
+    }
+
+    // Trace the camera rays through a lens system focused at the focus distance, see `lens.rs`.
+    pub fn with_lens(self, lens: Option<&LensSystem>) -> Camera {
+        let [width, height] = self.eye_size;
+        let film_size = [
+            self.sensor_height * width as Float / height as Float,
+            self.sensor_height,
+        ];
+        Camera {
+            lens: lens.map(|lens| Arc::new(Lens::new(lens, self.focus_dist, film_size))),
+            ..self
+        }
+    }
+
+    // Give the thin lens aperture the shape of an image, see `aperture.rs`.
+    pub fn with_aperture_mask(self, mask: Option<Arc<ApertureMask>>) -> Camera {
+        match mask {
+            Some(mask) => Camera {
+                aperture: Aperture::Mask(mask),
+                ..self
+            },
+            None => self,
+        }
+    }
+
+    // Grade the rendered image with a color lookup table, see `lut.rs`.
+    pub fn with_lut(self, lut: Option<Arc<Lut>>) -> Camera {
+        Camera { lut, ..self }
+    }
+
+    // Whether the camera ray that ended up with `hit` is held out of the layer rendered.
+    fn held_out(&self, hit: Option<&Hit>) -> bool {
+        self.layer.is_some_and(|layer| hit.map_or(0, |hit| hit.group) != layer)
+    }
+
+    // Whether to stop the render, asked between scanlines. Waits first while the render is paused.
+    fn stopped(&self) -> bool {
+        self.wait_while_paused();
+        self.stop.is_some_and(|stop| stop.load(Ordering::Relaxed))
+    }
+
+    // Wait until the render is resumed or stopped if it's paused, returns how long it waited.
+    fn wait_while_paused(&self) -> Duration {
+        let Some(pause) = self.pause.filter(|pause| pause.load(Ordering::Relaxed)) else {
+            return Duration::ZERO;
+        };
+        let start = Instant::now();
+        info!("render paused");
+        while pause.load(Ordering::Relaxed) && !self.stop.is_some_and(|stop| stop.load(Ordering::Relaxed)) {
+            thread::sleep(PAUSE_POLL_INTERVAL);
+        }
+        info!("render resumed after {:.1?}", start.elapsed());
+        start.elapsed()
+    }
+
+    pub fn image_width(&self) -> i32 {
+        self.image_width
+    }
+
+    pub fn image_height(&self) -> i32 {
+        self.image_height
+    }
+
+    pub fn samples_per_pixel(&self) -> i32 {
+        self.samples_per_pixel
+    }
+
+    // Scanlines the camera renders, all of the image unless it's cropped.
+    pub fn rows(&self) -> Range<i32> {
+        self.rows.clone()
+    }
+
+    // Render the world as a PPM image into `out`, advancing `progress` by one for every finished scanline.
+    pub fn render(&self, world: &dyn Hittable, out: &mut dyn Write, progress: &dyn Progress) -> io::Result<RenderInfo> {
+        let (image, info) = self.render_image(world, progress)?;
+        write_ppm(&image, out)?;
+        Ok(info)
+    }
+
+    // Render the world into a framebuffer holding the filtered average of the samples around every pixel. Fails only
+    // if a checkpoint can't be loaded or saved.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "render",
+            level = "debug",
+            skip_all,
+            fields(width = self.image_width, height = self.image_height)
+        )
+    )]
+    pub fn render_image(&self, world: &dyn Hittable, progress: &dyn Progress) -> io::Result<(Framebuffer, RenderInfo)> {
+        if self.stereo == Stereo::Anaglyph {
+            return self.render_anaglyph(world, progress);
+        }
+        let samples_per_pixel = self.samples_per_pixel.max(0) as usize;
+        let progressive = self.time_budget.is_some() && !self.wavefront;
+        // Samples per pixel of every pass over the image, a pass is the whole render unless it's progressive
+        let pass_samples = match progressive {
+            true if self.packets => PACKET_SIZE,
+            true => 1,
+            false => samples_per_pixel.max(1),
+        };
+        let passes = samples_per_pixel.div_ceil(pass_samples);
+        let mut deadline = self.time_budget.map(|budget| Instant::now() + budget);
+        progress.start((self.rows.len() * passes) as u64);
+        progress.samples_per_pixel(pass_samples.min(samples_per_pixel) as u64);
+        stats::take();
+
+        // TODO: Multithreading
+        let to_usize = |range: &Range<i32>| range.start as usize..range.end as usize;
+        let (columns, rows) = (to_usize(&self.columns), to_usize(&self.rows));
+        let mut film = Film::region(columns.clone(), rows, self.filter);
+        let mut checkpoints = Checkpoints::new(self.checkpoints.as_ref().filter(|_| self.time_budget.is_none()));
+        let start = match checkpoints.resume(&mut film)? {
+            Some(row) => row.clamp(self.rows.start, self.rows.end),
+            None => self.rows.start,
+        };
+        let mut live = match &self.live_exr {
+            Some(path) => Some(LiveImage::create(
+                path,
+                self.image_width as usize,
+                self.image_height as usize,
+            )?),
+            None => None,
+        };
+        #[cfg(feature = "network")]
+        if let Some(preview) = &self.preview {
+            preview.begin(self.image_width as usize, self.image_height as usize);
+        }
+        progress.advance((start - self.rows.start) as u64);
+        report_rays(progress);
+        let row_samples = (columns.len() * samples_per_pixel) as u64;
+        let mut samples = (start - self.rows.start) as u64 * row_samples;
+        let mut stopped = false;
+        if self.wavefront {
+            let row = self.render_wavefront(world, &mut film, start, progress, &mut checkpoints, &mut live)?;
+            samples += (row - start) as u64 * row_samples;
+            stopped = row < self.rows.end;
+        } else {
+            'passes: for pass in 0..passes {
+                let count = pass_samples.min(samples_per_pixel - pass * pass_samples);
+                let first_row = if pass == 0 { start } else { self.rows.start };
+                progress.samples_per_pixel((pass * pass_samples + count) as u64);
+                for j in first_row..self.rows.end {
+                    let paused = self.wait_while_paused();
+                    deadline = deadline.map(|deadline| deadline + paused);
+                    if self.stopped() {
+                        stopped = true;
+                        break 'passes;
+                    }
+                    if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                        break 'passes;
+                    }
+                    #[cfg(feature = "tracing")]
+                    let _span = tracing::trace_span!("scanline", row = j, pass).entered();
+                    // Every scanline is rendered into a tile of its own, the way a worker thread would
+                    let mut tile = film.tile(columns.clone(), j as usize..j as usize + 1);
+                    for i in self.columns.clone() {
+                        self.pixel_samples(i, j, count, world, &mut tile);
+                    }
+                    film.merge(&tile);
+                    samples += (columns.len() * count) as u64;
+                    checkpoints.update(&film, j + 1)?;
+                    self.write_live(&mut live, &film, j..j + 1)?;
+                    report_rays(progress);
+                    progress.advance(1);
+                }
+            }
+        }
+        if stopped {
+            let row = self.rows.start + (samples / row_samples.max(1)) as i32;
+            checkpoints.save(&film, row)?;
+        }
+
+        let image = self.develop(&film);
+        if let Some(live) = &mut live {
+            live.finish(&image)?;
+        }
+        #[cfg(feature = "network")]
+        if let Some(preview) = &self.preview {
+            preview.finish(&image);
+        }
+        let info = RenderInfo {
+            samples,
+            samples_per_pixel: samples as Float / (columns.len() * self.rows.len()).max(1) as Float,
+            stopped,
+            stats: stats::take(),
+        };
+        Ok((image, info))
+    }
+
+    // Empty film of the part of the image the camera renders, for merging tiles into.
+    pub fn film(&self) -> Film {
+        let to_usize = |range: &Range<i32>| range.start as usize..range.end as usize;
+        Film::region(to_usize(&self.columns), to_usize(&self.rows), self.filter)
+    }
+
+    // Splat all samples of the pixels of the scanlines `rows` into `tile`, e.g. on a worker of a distributed render.
+    pub fn render_rows(&self, world: &dyn Hittable, tile: &mut Film, rows: Range<i32>) {
+        let samples_per_pixel = self.samples_per_pixel.max(0) as usize;
+        for j in rows {
+            for i in self.columns.clone() {
+                self.pixel_samples(i, j, samples_per_pixel, world, tile);
+            }
+        }
+    }
+
+    // The image of the rendered film, with the exposure, the post effects and the color space of the camera.
+    pub fn develop(&self, film: &Film) -> Framebuffer {
+        let mut image = Framebuffer::new(self.image_width as usize, self.image_height as usize);
+        film.resolve_into(&mut image);
+        if self.integrator == Integrator::Path && (self.exposure != 1.0 || self.lens.is_some() || self.vignetting > 0.0)
+        {
+            let width = image.width();
+            for (i, color) in image.rows_mut(0, self.image_height as usize).iter_mut().enumerate() {
+                *color = self.exposure_at(i % width, i / width) * *color;
+            }
+        }
+        if self.integrator == Integrator::Path && !self.white_balance.is_neutral() {
+            post::white_balance(&mut image, self.white_balance);
+        }
+        if self.integrator == Integrator::Path && self.bloom.strength > 0.0 {
+            post::bloom(&mut image, self.eye_size.map(|size| size as usize), self.bloom);
+        }
+        if self.integrator == Integrator::Path && self.flare.strength > 0.0 {
+            post::lens_flare(&mut image, self.eye_size.map(|size| size as usize), self.flare);
+        }
+        if self.integrator == Integrator::Path && self.chromatic_aberration != 0.0 {
+            post::chromatic_aberration(
+                &mut image,
+                self.eye_size.map(|size| size as usize),
+                self.chromatic_aberration,
+            );
+        }
+        if self.integrator == Integrator::Path && self.grain.amount > 0.0 {
+            post::film_grain(&mut image, self.grain, self.frame());
+        }
+        if let Some(lut) = self.lut.as_ref().filter(|_| self.integrator == Integrator::Path) {
+            lut.grade(&mut image);
+        }
+        self.integrator.finish(&mut image, self.max_depth);
+        if self.view.projection.is_fisheye() {
+            mask_image_circle(&mut image, self.eye_size);
+        }
+        image.set_color_space(self.color_space);
+        image
+    }
+
+    // Splat `count` samples of a pixel.
+    fn pixel_samples(&self, i: i32, j: i32, count: usize, world: &dyn Hittable, film: &mut Film) {
+        if self.debug_pixel {
+            self.debug_samples(i, j, count, world, film);
+            return;
+        }
+        if self.integrator != Integrator::Path {
+            self.false_color_samples(i, j, count, world, film);
+            return;
+        }
+        if self.packets {
+            self.pixel_samples_packets(i, j, count, world, film);
+            return;
+        }
+        for _ in 0..count {
+            let (ray, position) = self.get_ray(i, j);
+            let color = self.ray_color(ray, self.max_depth, world, &MediumStack::default(), None);
+            film.splat(position, color);
+        }
+    }
+
+    // Splat `count` samples of a pixel, with the camera rays traced as packets.
+    fn pixel_samples_packets(&self, i: i32, j: i32, count: usize, world: &dyn Hittable, film: &mut Film) {
+        let mut remaining = count;
+        while remaining > 0 {
+            let count = remaining.min(PACKET_SIZE);
+            remaining -= count;
+            let mut rays = [Ray::new(Point::ORIGIN, Vec3::ZERO, 0.0); PACKET_SIZE];
+            let mut positions = [[0.0; 2]; PACKET_SIZE];
+            for (ray, position) in rays[..count].iter_mut().zip(&mut positions) {
+                (*ray, *position) = self.get_ray(i, j);
+            }
+            let mut hits = [None; PACKET_SIZE];
+            world.hit_packet(&rays[..count], T_RANGE, &mut hits[..count]);
+            count_rays(count, true);
+            for ((&ray, hit), position) in rays[..count].iter().zip(hits).zip(positions) {
+                film.splat(
+                    position,
+                    self.shade(ray, hit, self.max_depth, world, &MediumStack::default(), None),
+                );
+            }
+        }
+    }
+
+    // Get a randomly sampled camera ray for the pixel at location i,j originating from the camera defocus disk,
+    // together with the position of the sample on the image in pixels.
+    fn get_ray(&self, i: i32, j: i32) -> (Ray, [Float; 2]) {
+        let t = if self.shutter.is_empty() { 0.0 } else { random_double() };
+        self.get_ray_at(i, j, t)
+    }
+
+    // Like `get_ray`, at the moment `t` between the opening (0) and the closing (1) of the shutter.
+    fn get_ray_at(&self, i: i32, j: i32, t: Float) -> (Ray, [Float; 2]) {
+        let time = self.shutter.start + t * (self.shutter.end - self.shutter.start);
+        let view = self.view_at(t);
+        let [width, height] = self.eye_size;
+        let (eye, [x, y]) = self.stereo.eye([i, j], width, height);
+        let pixel_x = (x as Float) * view.pixel_delta_u;
+        let pixel_y = (y as Float) * view.pixel_delta_v;
+
+        let pixel_center = view.pixel00_loc + pixel_x + pixel_y;
+        let px = -0.5 + random_double();
+        let py = -0.5 + random_double();
+        let pixel_sample = pixel_center + (px * view.pixel_delta_u) + (py * view.pixel_delta_v);
+
+        let ray_origin = if self.defocus_angle <= 0.0 {
+            view.center
+        } else {
+            view.defocus_disk_sample(self.aperture.sample())
+        };
+        let ray_direction = view.focus_direction(ray_origin, pixel_sample);
+        // Through a lens system, the rays it blocks altogether are left to its vignetting
+        let film_point = [
+            (x as Float + 0.5 + px) / width as Float - 0.5,
+            (y as Float + 0.5 + py) / height as Float - 0.5,
+        ];
+        let (ray_origin, ray_direction) = match self.lens.as_ref().and_then(|lens| lens.ray(film_point)) {
+            Some((origin, direction)) => {
+                let [right, down, forward] = view.axes();
+                let to_world = |v: Vec3| v.x * right - v.y * down + v.z * forward;
+                (view.center + to_world(origin), to_world(direction))
+            }
+            None => (ray_origin, ray_direction),
+        };
+        let ray_origin = ray_origin + eye * self.interocular / 2.0 * view.side(ray_direction);
+        let position = [i as Float + 0.5 + px, j as Float + 0.5 + py];
+        (Ray::new(ray_origin, ray_direction, time), position)
+    }
+
+    // Write the scanlines of `rows` into the live image and the preview, and the ones above them as far as the filter
+    // reaches, which their samples splat onto as well. Only the exposure is applied, the effects over the whole image
+    // come with the finished image.
+    fn write_live(&self, live: &mut Option<LiveImage>, film: &Film, rows: Range<i32>) -> io::Result<()> {
+        #[cfg(feature = "network")]
+        let preview = self.preview.is_some();
+        #[cfg(not(feature = "network"))]
+        let preview = false;
+        if live.is_none() && !preview {
+            return Ok(());
+        }
+        let margin = self.filter.radius().ceil() as i32;
+        for y in (rows.start - margin).max(self.rows.start)..rows.end {
+            let colors: Vec<Color> = (0..self.image_width)
+                .map(|x| {
+                    if !self.columns.contains(&x) {
+                        return Color::BLACK;
+                    }
+                    let color = film.pixel((x - self.columns.start) as usize, (y - self.rows.start) as usize);
+                    match self.integrator {
+                        Integrator::Path => self.exposure_at(x as usize, y as usize) * color,
+                        _ => color,
+                    }
+                })
+                .collect();
+            if let Some(live) = live {
+                live.write_scanline(y as usize, &colors)?;
+            }
+            #[cfg(feature = "network")]
+            if let Some(preview) = &self.preview {
+                preview.scanline(y as usize, &colors, self.color_space);
+            }
+        }
+        Ok(())
+    }
+
+    // Scale of the light reaching the pixel at x, y: the exposure, the vignetting of the lens system and the cosine
+    // falloff of the light off the axis.
+    fn exposure_at(&self, x: usize, y: usize) -> Float {
+        if self.lens.is_none() && self.vignetting <= 0.0 {
+            return self.exposure;
+        }
+        let [width, height] = self.eye_size;
+        let (_, [x, y]) = self.stereo.eye([x as i32, y as i32], width, height);
+        let mut scale = self.exposure;
+        if let Some(lens) = &self.lens {
+            let film_point = [
+                (x as Float + 0.5) / width as Float - 0.5,
+                (y as Float + 0.5) / height as Float - 0.5,
+            ];
+            scale *= lens.vignetting(film_point);
+        }
+        if self.vignetting > 0.0 {
+            let view = &self.view;
+            let pixel_center = view.pixel00_loc + (x as Float) * view.pixel_delta_u + (y as Float) * view.pixel_delta_v;
+            let [_, _, forward] = view.axes();
+            let direction = (view.focus_point(pixel_center) - view.center).normalize();
+            scale *= post::vignetting(Vec3::dot(direction, forward).max(0.0), self.vignetting);
+        }
+        scale
+    }
+
+    // Frame of the animation being rendered, the one the shutter is open around.
+    fn frame(&self) -> i64 {
+        ((self.shutter.start + self.shutter.end) / 2.0).round() as i64
+    }
+
+    // Where the camera is at the moment `t` between the opening (0) and the closing (1) of the shutter.
+    fn view_at(&self, t: Float) -> View {
+        if self.shutter.is_empty() {
+            self.view
+        } else {
+            self.view.interpolate(&self.closing_view, t)
+        }
+    }
+
+    // Light arriving along `ray`, which leaves the surface of the object with the IDs `from` (see `Hit::ids`), None
+    // for camera rays.
+    fn ray_color(
+        &self,
+        ray: Ray,
+        depth: i32,
+        world: &dyn Hittable,
+        media: &MediumStack,
+        from: Option<[u32; 2]>,
+    ) -> Color {
+        if depth <= 0 {
+            self.log_note(depth, format_args!("out of bounces"));
+            return Color::BLACK;
+        }
+        count_rays(1, depth == self.max_depth);
+        let hit = world.hit(ray, T_RANGE);
+        let transmittance = media.transmittance(ray, hit.as_ref());
+        if transmittance == Color::BLACK {
+            self.log_note(depth, format_args!("absorbed by the medium"));
+            return Color::BLACK;
+        }
+        transmittance * self.shade(ray, hit, depth, world, media, from)
+    }
+
+    // Light arriving along `ray`, which was already traced to its closest `hit`.
+    fn shade(
+        &self,
+        ray: Ray,
+        hit: Option<Hit>,
+        depth: i32,
+        world: &dyn Hittable,
+        media: &MediumStack,
+        from: Option<[u32; 2]>,
+    ) -> Color {
+        if depth <= 0 || (depth == self.max_depth && self.held_out(hit.as_ref())) {
+            return Color::BLACK;
+        }
+        let Some(hit) = hit else {
+            let background = self.background(ray);
+            self.log_note(depth, format_args!("left the scene, background {background}"));
+            return background;
+        };
+
+        if hit.material.is_shadow_catcher() && depth == self.max_depth {
+            // Camera rays see through the shadow catcher, only the light blocked or reflected by other objects
+            // changes what is behind it. Compare the light arriving from a random direction with what would
+            // arrive if there were no objects at all.
+            let behind = self.ray_color(Ray::new(hit.point, ray.direction, ray.time), depth, world, media, from);
+            // Only the front face catches anything, closed catchers are see-through from the inside
+            if !hit.front_face {
+                return behind;
+            }
+            let scatter = hit.material.scatter(ray, hit);
+            let before = self.log_bounce(depth, &hit, scatter.as_ref());
+            let color = match scatter {
+                // Light that no object blocks or reflects arrives as it would without any objects, only an occlusion
+                // test is needed to tell
+                Some(scatter) if depth > 1 && !occluded(world, scatter.ray) => {
+                    self.log_note(depth - 1, format_args!("left the scene unoccluded"));
+                    behind * media.transmittance(scatter.ray, None)
+                }
+                Some(scatter) => {
+                    let received = self.ray_color(scatter.ray, depth - 1, world, media, Some(hit.ids()));
+                    let unoccluded = self.background(scatter.ray);
+                    behind * received / unoccluded
+                }
+                None => behind,
+            };
+            Camera::end_bounce(before);
+            return color;
+        }
+
+        let Some(medium) = hit.material.medium() else {
+            let emitted = self.emitted(&hit, from);
+            let scatter = hit.material.scatter(ray, hit);
+            let before = self.log_bounce(depth, &hit, scatter.as_ref());
+            let color = match scatter {
+                Some(scatter) => {
+                    let incoming = self.ray_color(scatter.ray, depth - 1, world, media, Some(hit.ids()));
+                    emitted + scatter.attenuation * incoming
+                }
+                None => emitted,
+            };
+            Camera::end_bounce(before);
+            return color;
+        };
+
+        // Boundary of a transparent medium, the indices of refraction on both sides depend on the media the
+        // ray is already in
+        let entering = hit.front_face;
+        let crossed = if entering {
+            media.entered(hit.material, medium)
+        } else {
+            media.exited(hit.material)
+        };
+        if media.is_false_interface(hit.material, medium, entering) {
+            self.log_note(
+                depth,
+                format_args!(
+                    "passed through a {} boundary inside the same medium",
+                    hit.material.name()
+                ),
+            );
+            let ray = Ray::new(hit.point, ray.direction, ray.time);
+            return self.ray_color(ray, depth - 1, world, &crossed, from);
+        }
+        let eta_from = if entering { media.ir() } else { medium.ir };
+        let scatter = hit.material.scatter_between(ray, hit, eta_from, crossed.ir());
+        let before = self.log_bounce(depth, &hit, scatter.as_ref());
+        let color = match scatter {
+            Some(scatter) => {
+                // The ray refracted if it continues on the other side of the surface
+                let refracted = Vec3::dot(scatter.ray.direction, hit.normal) < 0.0;
+                let media = if refracted { &crossed } else { media };
+                scatter.attenuation * self.ray_color(scatter.ray, depth - 1, world, media, Some(hit.ids()))
+            }
+            None => Color::BLACK,
+        };
+        Camera::end_bounce(before);
+        color
+    }
+
+    // Light given off by the surface hit, unless light linking leaves out the object the ray comes from.
+    fn emitted(&self, hit: &Hit, from: Option<[u32; 2]>) -> Color {
+        match from {
+            Some(from) if !self.light_links.lights(hit.ids(), from) => Color::BLACK,
+            _ => hit.material.emitted(hit),
+        }
+    }
+
+    fn background(&self, ray: Ray) -> Color {
+        if let Some(background) = self.background {
+            return background;
+        }
+        let unit_direction = ray.direction.normalize();
+        let a = 0.5 * (unit_direction.y + 1.0);
+        (1.0 - a) * Color::WHITE + a * Color::new(0.5, 0.7, 1.0)
+    }
+}
+
+// Black out what lies outside of the image circle of a fisheye lens in the views of `size` pixels the image is made
+// of, the pixels on its edge by the part outside.
+fn mask_image_circle(image: &mut Framebuffer, size: [i32; 2]) {
+    let [width, height] = size.map(|size| size as usize);
+    let radius = height as Float / 2.0;
+    for y in 0..image.height() {
+        for x in 0..image.width() {
+            let offset = Vec3::new(
+                (x % width) as Float + 0.5 - width as Float / 2.0,
+                (y % height) as Float + 0.5 - radius,
+                0.0,
+            );
+            let inside = (radius - offset.length() + 0.5).clamp(0.0, 1.0);
+            image.set(x, y, inside * image.get(x, y));
+        }
+    }
+}
+
+impl View {
+    fn new(settings: &CameraSettings, image_height: i32) -> View {
+        let CameraSettings {
+            image_width,
+            vfov,
+            look_from,
+            look_at,
+            vup,
+            defocus_angle,
+            focus_dist,
+            projection,
+            sensor_height,
+            shift_x,
+            shift_y,
+            tilt,
+            swing,
+            ..
+        } = *settings;
         let center = look_from;
 
-        // Determine viewport dimensions (assuming right-handed coordinates)
-        let theta = degrees_to_radians(vfov);
-        let h = f64::tan(theta / 2.0);
+        // Determine viewport dimensions (assuming right-handed coordinates). Other lenses than the perspective one
+        // bend the rays through a viewport 90° high, see `Projection::direction`.
+        let fov = degrees_to_radians(vfov);
+        let theta = if projection == Projection::Perspective {
+            fov
+        } else {
+            FRAC_PI_2
+        };
+        let h = Float::tan(theta / 2.0);
         let viewport_height = 2.0 * h * focus_dist;
         // We don't use aspect_ratio here because actual aspect ratio may be different due to integer image dimensions
-        let viewport_width = viewport_height * (image_width as f64 / image_height as f64);
+        let viewport_width = match projection {
+            Projection::Equirectangular => viewport_height,
+            _ => viewport_height * (image_width as Float / image_height as Float),
+        };
 
         // Calculate the u,v,w unit basis vectors for the camera coordinate frame.
         let w = (look_from - look_at).normalize();
@@ -58,12 +1096,13 @@ impl Camera {
         let viewport_v = viewport_height * -v;
 
         // Distances between pixel horizontally and vertically
-        let pixel_delta_u = viewport_u / (image_width as f64);
-        let pixel_delta_v = viewport_v / (image_height as f64);
+        let pixel_delta_u = viewport_u / (image_width as Float);
+        let pixel_delta_v = viewport_v / (image_height as Float);
 
-        // Location of the upper left pixel
+        // Location of the upper left pixel, a shifted lens moves the image across the viewport
         let viewport_distance = focus_dist * w;
-        let viewport_upper_left = center - viewport_distance - (viewport_u + viewport_v) / 2.0;
+        let viewport_upper_left =
+            center - viewport_distance - (viewport_u + viewport_v) / 2.0 + shift_x * viewport_u - shift_y * viewport_v;
         let pixel00_loc = viewport_upper_left + 0.5 * (pixel_delta_u + pixel_delta_v);
 
         // Calculate the camera defocus disk basis vectors
@@ -71,87 +1110,151 @@ impl Camera {
         let defocus_disk_u = u * defocus_radius;
         let defocus_disk_v = v * defocus_radius;
 
-        Camera {
-            samples_per_pixel,
-            max_depth,
-            image_width,
-            image_height,
+        // A tilted lens focuses on a plane at an angle to the sensor, which meets the plane of the lens at the hinge
+        // line f / sin(tilt) away from the center (the Scheimpflug principle)
+        let focal_length = sensor_height / 2.0 / Float::tan(fov / 2.0);
+        let lean = |angle: Float| focus_dist * degrees_to_radians(angle).sin() / focal_length;
+        let focus_plane = (-w - lean(tilt) * v - lean(swing) * u) / focus_dist;
+
+        View {
             center,
             pixel00_loc,
             pixel_delta_u,
             pixel_delta_v,
-            defocus_angle,
             defocus_disk_u,
             defocus_disk_v,
+            focus_plane,
+            projection,
+            fov,
         }
     }
 
-    pub fn render(&self, world: &dyn Hittable) {
-        let pb = ProgressBar::new(self.image_height as u64);
-        pb.set_prefix("Scanlines remaining:");
-        pb.set_style(ProgressStyle::with_template("{prefix} {wide_bar} {pos}/{len}").unwrap());
+    // The view in between, `t` goes from 0 at `self` to 1 at `other`. Moving the image plane straight is close enough
+    // to turning the camera over the short time the shutter is open.
+    fn interpolate(&self, other: &View, t: Float) -> View {
+        View {
+            center: self.center.interpolate(other.center, t),
+            pixel00_loc: self.pixel00_loc.interpolate(other.pixel00_loc, t),
+            pixel_delta_u: self.pixel_delta_u.interpolate(other.pixel_delta_u, t),
+            pixel_delta_v: self.pixel_delta_v.interpolate(other.pixel_delta_v, t),
+            defocus_disk_u: self.defocus_disk_u.interpolate(other.defocus_disk_u, t),
+            defocus_disk_v: self.defocus_disk_v.interpolate(other.defocus_disk_v, t),
+            focus_plane: self.focus_plane.interpolate(other.focus_plane, t),
+            ..*self
+        }
+    }
 
-        // TODO: Multithreading
-        println!("P3\n{} {}\n255", self.image_width, self.image_height);
-        for j in 0..self.image_height {
-            for i in 0..self.image_width {
-                let mut pixel_color = Vec3::ZERO;
-                for _ in 0..self.samples_per_pixel {
-                    let ray = self.get_ray(i, j);
-                    pixel_color += Camera::ray_color(ray, self.max_depth, world)
-                }
-                write_color(pixel_color, self.samples_per_pixel);
-            }
-            pb.inc(1);
+    // Direction from `origin` to the point in focus of the camera ray through a point on the viewport, as long as the
+    // ray from the center to the viewport point: the hit distances are in lengths of the direction, and points focused
+    // far off by a tilted lens would make it too long.
+    fn focus_direction(&self, origin: Point, viewport_point: Point) -> Vec3 {
+        let direction = self.focus_point(viewport_point) - origin;
+        direction * ((viewport_point - self.center).length() / direction.length())
+    }
+
+    // The point in focus the camera ray through a point on the viewport aims at, in the direction the lens bends the
+    // ray to. The perspective lens focuses on the plane in focus, the viewport unless the lens is tilted, rays that
+    // run away from it are focused far off.
+    fn focus_point(&self, viewport_point: Point) -> Point {
+        if self.projection == Projection::Perspective {
+            let offset = viewport_point - self.center;
+            return self.center + offset / Vec3::dot(self.focus_plane, offset).max(1e-6);
         }
-        pb.finish_and_clear();
+        let [right, down, forward] = self.axes();
+        let offset = viewport_point - self.center;
+        let distance = Vec3::dot(offset, forward);
+        let point = [Vec3::dot(offset, right) / distance, Vec3::dot(offset, down) / distance];
+        let direction = self.projection.direction(point, self.fov);
+        self.center + distance * (direction.x * right + direction.y * down + direction.z * forward)
     }
 
-    // Get a randomly sampled camera ray for the pixel at location i,j originating from the camera defocus disk.
-    fn get_ray(&self, i: i32, j: i32) -> Ray {
-        let pixel_x = (i as f64) * self.pixel_delta_u;
-        let pixel_y = (j as f64) * self.pixel_delta_v;
+    // Direction to the right of `direction` seen from the camera, shorter the closer `direction` is to straight up
+    // or down, where the eyes of a stereo camera come together.
+    fn side(&self, direction: Vec3) -> Vec3 {
+        let [_, down, _] = self.axes();
+        Vec3::cross(down, direction.normalize())
+    }
 
-        let pixel_center = self.pixel00_loc + pixel_x + pixel_y;
-        let pixel_sample = pixel_center + self.pixel_sample_square();
+    // Directions to the right and down along the pixels, and forward from the camera.
+    fn axes(&self) -> [Vec3; 3] {
+        [
+            self.pixel_delta_u.normalize(),
+            self.pixel_delta_v.normalize(),
+            Vec3::cross(self.pixel_delta_u, self.pixel_delta_v).normalize(),
+        ]
+    }
 
-        let ray_origin = if self.defocus_angle <= 0.0 {
-            self.center
+    // Position on the image in pixels of what the camera sees in `direction` from its center, None behind the camera.
+    fn project(&self, direction: Vec3) -> Option<[Float; 2]> {
+        let normal = Vec3::cross(self.pixel_delta_u, self.pixel_delta_v);
+        // Other lenses than the perspective one see the direction where the perspective view through their viewport
+        // sees another one
+        let direction = if self.projection == Projection::Perspective {
+            direction
         } else {
-            self.defocus_disk_sample()
+            let [right, down, forward] = self.axes();
+            let local = Vec3::new(
+                Vec3::dot(direction, right),
+                Vec3::dot(direction, down),
+                Vec3::dot(direction, forward),
+            );
+            let [x, y] = self.projection.viewport_point(local, self.fov)?;
+            x * right + y * down + forward
         };
-        let ray_direction = pixel_sample - ray_origin;
-        Ray::new(ray_origin, ray_direction)
+        // Where the direction meets the plane of the pixels
+        let distance = Vec3::dot(self.pixel00_loc - self.center, normal) / Vec3::dot(direction, normal);
+        if distance.is_nan() || distance <= 0.0 {
+            return None;
+        }
+        let offset = self.center + distance * direction - self.pixel00_loc;
+        let along = |delta: Vec3| Vec3::dot(offset, delta) / delta.length_squared() + 0.5;
+        Some([along(self.pixel_delta_u), along(self.pixel_delta_v)])
     }
 
-    // Returns a random point in the square surrounding a pixel at the origin.
-    fn pixel_sample_square(&self) -> Vec3 {
-        let px = -0.5 + random_double();
-        let py = -0.5 + random_double();
-        (px * self.pixel_delta_u) + (py * self.pixel_delta_v)
+    // Returns the point of the camera defocus disk at `x`, `y` of the aperture.
+    fn defocus_disk_sample(&self, [x, y]: [Float; 2]) -> Point {
+        self.center + (x * self.defocus_disk_u) + (y * self.defocus_disk_v)
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::{bvh::BvhSettings, progress::NoProgress, scene::Scene};
 
-    // Returns a random point in the camera defocus disk.
-    fn defocus_disk_sample(&self) -> Point {
-        let p = Vec3::random_in_unit_disk();
-        self.center + (p.x * self.defocus_disk_u) + (p.y * self.defocus_disk_v)
+    // Image of a shadow catcher seen from above in front of a plain background, with `objects` added to the scene,
+    // rendered by the wavefront integrator if `wavefront`.
+    fn render_catcher(objects: &str, wavefront: bool) -> Framebuffer {
+        let source = format!(
+            "camera aspect_ratio=1 image_width=4 samples_per_pixel=32 max_depth=4 look_from=0,0.5,0 look_at=0,0,0 \
+             vup=0,0,-1 background=0.5,0.6,0.7\n\
+             material catcher type=shadow_catcher albedo=0.6,0.6,0.6\n\
+             material black type=lambertian albedo=0,0,0\n\
+             sphere center=0,-10,0 radius=10 material=catcher\n{objects}"
+        );
+        let scene = Scene::parse(&source, Path::new(""), BvhSettings::default()).expect("valid scene");
+        let (image, _) = Camera::new(scene.camera)
+            .with_wavefront(wavefront)
+            .render_image(&scene.world, &NoProgress)
+            .expect("rendered");
+        image
     }
 
-    fn ray_color(ray: Ray, depth: i32, world: &dyn Hittable) -> Color {
-        if depth <= 0 {
-            return Vec3::ZERO;
-        }
-        // Ignore hits that are very close to the calculated intersection point to solve the "shadow acne"
-        let t_range = 0.001..f64::INFINITY;
-        if let Some(hit) = world.hit(ray, t_range) {
-            return match hit.material.scatter(ray, hit) {
-                Some(scatter) => scatter.attenuation * Camera::ray_color(scatter.ray, depth - 1, world),
-                None => Vec3::ZERO,
-            };
+    #[test]
+    fn shadow_catcher_shows_background_unless_occluded() {
+        for wavefront in [false, true] {
+            let clear = render_catcher("", wavefront);
+            let background = clear.get(0, 0);
+            // Equal up to rounding. In the f32 build the odd scattered ray that grazes the catcher also hits it
+            // again, which darkens its sample.
+            let tolerance = if cfg!(feature = "f32") { 0.05 } else { 1e-3 };
+            let close = |color: Color| (color - background).map(Float::abs).max_component() < tolerance;
+            assert!(clear.pixels().iter().all(|&color| close(color)));
+            // A black sphere above the camera, out of its view, shadows the middle of the catcher
+            let shadowed = render_catcher("sphere center=0,3,0 radius=2.2 material=black", wavefront);
+            assert!(shadowed.get(2, 2).luminance() < 0.8 * background.luminance());
         }
-        // Background
-        let unit_direction = ray.direction.normalize();
-        let a = 0.5 * (unit_direction.y + 1.0);
-        (1.0 - a) * Color::new(1.0, 1.0, 1.0) + a * Color::new(0.5, 0.7, 1.0)
     }
 }
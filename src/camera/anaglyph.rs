@@ -0,0 +1,82 @@
+use std::io;
+
+use super::{Camera, LiveImage, RenderInfo};
+use crate::{
+    checkpoint::CheckpointSettings,
+    color::Color,
+    hittable::Hittable,
+    integrator::Integrator,
+    output::Framebuffer,
+    post::{self, FilmGrain},
+    progress::Progress,
+    stereo::Stereo,
+};
+
+impl Camera {
+    // Render the views of the two eyes of an anaglyph one after the other, as the halves of a side-by-side image, and
+    // combine them. Each eye gets half the time budget and checkpoints of its own, the film grain and the color grade
+    // go over the combined image, which is the only one written into the live OpenEXR file and shown on the preview.
+    pub(super) fn render_anaglyph(
+        &self,
+        world: &dyn Hittable,
+        progress: &dyn Progress,
+    ) -> io::Result<(Framebuffer, RenderInfo)> {
+        let [width, height] = self.eye_size;
+        let render_eye = |eye: &str, offset: i32| {
+            let camera = Camera {
+                image_width: 2 * width,
+                stereo: Stereo::SideBySide,
+                columns: self.columns.start + offset..self.columns.end + offset,
+                checkpoints: self.checkpoints.as_ref().map(|checkpoints| CheckpointSettings {
+                    path: format!("{}.{eye}", checkpoints.path.display()).into(),
+                    ..checkpoints.clone()
+                }),
+                time_budget: self.time_budget.map(|budget| budget / 2),
+                grain: FilmGrain {
+                    amount: 0.0,
+                    ..self.grain
+                },
+                lut: None,
+                live_exr: None,
+                #[cfg(feature = "network")]
+                preview: None,
+                ..self.clone()
+            };
+            camera.render_image(world, progress)
+        };
+        let (left, left_info) = render_eye("left", 0)?;
+        let (right, right_info) = render_eye("right", width)?;
+
+        // Red from the left eye, green and blue from the right one
+        let mut image = Framebuffer::new(width as usize, height as usize);
+        for y in 0..height as usize {
+            for x in 0..width as usize {
+                let (left, right) = (left.get(x, y), right.get(x + width as usize, y));
+                image.set(x, y, Color::new(left.r, right.g, right.b));
+            }
+        }
+        if self.integrator == Integrator::Path && self.grain.amount > 0.0 {
+            post::film_grain(&mut image, self.grain, self.frame());
+        }
+        if let Some(lut) = self.lut.as_ref().filter(|_| self.integrator == Integrator::Path) {
+            lut.grade(&mut image);
+        }
+        image.set_color_space(self.color_space);
+        if let Some(path) = &self.live_exr {
+            LiveImage::create(path, image.width(), image.height())?.finish(&image)?;
+        }
+        #[cfg(feature = "network")]
+        if let Some(preview) = &self.preview {
+            preview.finish(&image);
+        }
+        let mut stats = left_info.stats;
+        stats += right_info.stats;
+        let info = RenderInfo {
+            samples: left_info.samples + right_info.samples,
+            samples_per_pixel: (left_info.samples_per_pixel + right_info.samples_per_pixel) / 2.0,
+            stopped: left_info.stopped || right_info.stopped,
+            stats,
+        };
+        Ok((image, info))
+    }
+}
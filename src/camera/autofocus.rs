@@ -0,0 +1,88 @@
+use super::{Camera, CameraSettings, T_RANGE};
+use crate::{
+    error::{Error, Result},
+    float::Float,
+    hittable::Hittable,
+    id::name_id,
+    ray::Ray,
+    util::degrees_to_radians,
+    vec3::Vec3,
+};
+
+// What the camera focuses on, instead of a focus distance measured by hand.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Focus {
+    Pixel([i32; 2]), // the surface seen through the center of the pixel at x, y
+    Object(String),  // the object or group of the name, where it shows in the middle of its part of the image
+}
+
+impl CameraSettings {
+    // Distance to the plane of what the camera focuses on in `world` at `time`, along its viewing direction.
+    pub fn focus_distance(&self, world: &dyn Hittable, focus: &Focus, time: Float) -> Result<Float> {
+        let camera = Camera::new(*self);
+        let [width, height] = [camera.image_width, camera.image_height];
+        let depth = |pixel: [i32; 2]| {
+            let ray = camera.center_ray(pixel, time);
+            let hit = world.hit(ray, T_RANGE)?;
+            let [_, _, forward] = camera.view.axes();
+            Some((hit.ids(), Vec3::dot(hit.point - camera.view.center, forward)))
+        };
+        match focus {
+            Focus::Pixel([x, y]) => {
+                if !(0..width).contains(x) || !(0..height).contains(y) {
+                    return Err(Error::parse(
+                        0,
+                        format!("pixel {x} {y} to focus on is outside of the {width}x{height} image"),
+                    ));
+                }
+                depth([*x, *y])
+                    .map(|(_, distance)| distance)
+                    .ok_or_else(|| Error::parse(0, format!("nothing to focus on at pixel {x} {y}")))
+            }
+            Focus::Object(name) => {
+                // The pixels that see the object, the one closest to their middle is focused on
+                let id = name_id(name);
+                let seen: Vec<_> = (0..height)
+                    .flat_map(|y| (0..width).map(move |x| [x, y]))
+                    .filter_map(|pixel| match depth(pixel) {
+                        Some((ids, distance)) if ids.contains(&id) => Some((pixel, distance)),
+                        _ => None,
+                    })
+                    .collect();
+                let count = seen.len() as Float;
+                let middle = |axis: usize| seen.iter().map(|(pixel, _)| pixel[axis] as Float).sum::<Float>() / count;
+                let [mx, my] = [middle(0), middle(1)];
+                seen.iter()
+                    .min_by(|(a, _), (b, _)| {
+                        let off = |[x, y]: [i32; 2]| Float::hypot(x as Float - mx, y as Float - my);
+                        off(*a).total_cmp(&off(*b))
+                    })
+                    .map(|(_, distance)| *distance)
+                    .ok_or_else(|| Error::parse(0, format!("'{name}' to focus on is out of view")))
+            }
+        }
+    }
+
+    // The settings focused at `focus_dist`, with the same aperture: the defocus angle changes with the distance.
+    pub fn with_focus_dist(self, focus_dist: Float) -> CameraSettings {
+        let aperture_radius = self.focus_dist * degrees_to_radians(self.defocus_angle / 2.0).tan();
+        CameraSettings {
+            focus_dist,
+            defocus_angle: 2.0 * (aperture_radius / focus_dist).atan().to_degrees(),
+            ..self
+        }
+    }
+}
+
+impl Camera {
+    // The ray through the center of the pixel at x, y from the center of the aperture.
+    fn center_ray(&self, [i, j]: [i32; 2], time: Float) -> Ray {
+        let [width, height] = self.eye_size;
+        let (eye, [x, y]) = self.stereo.eye([i, j], width, height);
+        let view = &self.view;
+        let pixel_center = view.pixel00_loc + (x as Float) * view.pixel_delta_u + (y as Float) * view.pixel_delta_v;
+        let direction = view.focus_direction(view.center, pixel_center);
+        let origin = view.center + eye * self.interocular / 2.0 * view.side(direction);
+        Ray::new(origin, direction, time)
+    }
+}
@@ -0,0 +1,36 @@
+use super::{count_rays, report_rays, Camera, T_RANGE};
+use crate::{cryptomatte::Cryptomatte, hittable::Hittable, progress::Progress};
+
+impl Camera {
+    // Render the Cryptomatte mattes of the world: the object and material IDs of the first hit of every camera
+    // sample, see `cryptomatte.rs`. The samples count for the pixel they are in, without the pixel filter. The crop
+    // and the stop apply.
+    pub fn render_cryptomatte(&self, world: &dyn Hittable, progress: &dyn Progress) -> Cryptomatte {
+        let samples_per_pixel = self.samples_per_pixel.max(0) as usize;
+        progress.start(self.rows.len() as u64);
+        progress.samples_per_pixel(samples_per_pixel as u64);
+
+        let mut mattes = Cryptomatte::new(self.image_width as usize, self.image_height as usize);
+        let mut samples = Vec::with_capacity(samples_per_pixel);
+        for j in self.rows.clone() {
+            if self.stopped() {
+                break;
+            }
+            for i in self.columns.clone() {
+                samples.clear();
+                for _ in 0..samples_per_pixel {
+                    let (ray, _) = self.get_ray(i, j);
+                    count_rays(1, true);
+                    let ids = world
+                        .hit(ray, T_RANGE)
+                        .map_or([0, 0], |hit| [hit.object, hit.material.id()]);
+                    samples.push(ids);
+                }
+                mattes.set(i as usize, j as usize, &samples);
+            }
+            report_rays(progress);
+            progress.advance(1);
+        }
+        mattes
+    }
+}
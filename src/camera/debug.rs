@@ -0,0 +1,175 @@
+use std::{cell::Cell, fmt::Arguments};
+
+use super::{count_rays, Camera, RAYS, T_RANGE};
+use crate::{
+    color::Color,
+    film::Film,
+    float::Float,
+    hittable::{Hit, Hittable},
+    id::id_color,
+    info,
+    integrator::{displayed, Integrator},
+    material::Scatter,
+    medium::MediumStack,
+    ray::Ray,
+    stats, warn,
+};
+
+thread_local! {
+    // Product of the attenuations along the path of the sample being debugged, up to the current bounce
+    static THROUGHPUT: Cell<Color> = const { Cell::new(Color::WHITE) };
+}
+
+// Debugging aids: the false-color integrators of `integrator.rs`, and logging of every bounce of the samples of a
+// single pixel for `Camera::with_debug_pixel`. The messages are info events, so they show up without `--verbose`. The
+// materials only report the attenuation of a bounce, which already includes the pdf of the direction they picked, so
+// that's what is logged along with the throughput of the path.
+impl Camera {
+    // Splat `count` samples of the pixel at i,j with a message for every bounce and a summary at the end, which counts
+    // the samples that came out NaN or infinite.
+    pub(super) fn debug_samples(&self, i: i32, j: i32, count: usize, world: &dyn Hittable, film: &mut Film) {
+        let mut sum = Color::BLACK;
+        let mut invalid = 0;
+        for sample in 0..count {
+            let (ray, position) = self.get_ray(i, j);
+            info!(
+                "pixel {i} {j} sample {sample}: camera ray from {} towards {}",
+                ray.origin, ray.direction
+            );
+            THROUGHPUT.with(|throughput| throughput.set(Color::WHITE));
+            let color = self.ray_color(ray, self.max_depth, world, &MediumStack::default(), None);
+            if color.is_finite() {
+                info!("pixel {i} {j} sample {sample}: radiance {color}");
+                sum += color;
+            } else {
+                warn!("pixel {i} {j} sample {sample}: radiance {color} is not finite");
+                invalid += 1;
+            }
+            film.splat(position, color);
+        }
+        let average = sum / (count - invalid).max(1) as Float;
+        info!("pixel {i} {j}: average of the finite samples {average}, {invalid} of {count} samples not finite");
+    }
+
+    // Splat `count` samples of the pixel at i,j computed by one of the false-color integrators. The heatmaps and the
+    // depth are splatted as gray, they are turned into colors once the whole image is done.
+    pub(super) fn false_color_samples(&self, i: i32, j: i32, count: usize, world: &dyn Hittable, film: &mut Film) {
+        for _ in 0..count {
+            // Motion vectors are measured from the frame itself, in the middle of the shutter
+            let (ray, position) = match self.integrator {
+                Integrator::Motion => self.get_ray_at(i, j, 0.5),
+                _ => self.get_ray(i, j),
+            };
+            let color = match self.integrator {
+                Integrator::Bounces => {
+                    let before = RAYS.with(|rays| rays.get());
+                    self.ray_color(ray, self.max_depth, world, &MediumStack::default(), None);
+                    Color::splat((RAYS.with(|rays| rays.get()) - before) as Float)
+                }
+                Integrator::NodeVisits | Integrator::PrimitiveTests => {
+                    let before = stats::current();
+                    count_rays(1, true);
+                    world.hit(ray, T_RANGE);
+                    let after = stats::current();
+                    let work = match self.integrator {
+                        Integrator::NodeVisits => after.node_visits - before.node_visits,
+                        _ => after.triangle_tests + after.sphere_tests - before.triangle_tests - before.sphere_tests,
+                    };
+                    Color::splat(work as Float)
+                }
+                Integrator::Motion => {
+                    count_rays(1, true);
+                    self.motion(ray, world.hit(ray, T_RANGE))
+                }
+                integrator => {
+                    count_rays(1, true);
+                    match world.hit(ray, T_RANGE) {
+                        Some(hit) => surface_color(integrator, hit),
+                        None => Color::BLACK,
+                    }
+                }
+            };
+            film.splat(position, color);
+        }
+    }
+
+    // How far what the camera ray sees moves on the image until the next frame, in pixels to the right and down as
+    // red and green. The sky is far away, only turning the camera moves it.
+    fn motion(&self, ray: Ray, hit: Option<Hit>) -> Color {
+        let view = self.view_at(0.5);
+        let (from, to) = match hit {
+            Some(hit) => (hit.point - view.center, hit.point + hit.motion - self.next_view.center),
+            None => (ray.direction, ray.direction),
+        };
+        match (view.project(from), self.next_view.project(to)) {
+            (Some([x0, y0]), Some([x1, y1])) => Color::new(x1 - x0, y1 - y0, 0.0),
+            _ => Color::BLACK,
+        }
+    }
+
+    // Log the bounce of a path of the pixel being debugged at `hit`, `depth` is the number of bounces left. Returns
+    // the throughput of the path before the bounce, which the caller restores once the bounce is done.
+    pub(super) fn log_bounce(&self, depth: i32, hit: &Hit, scatter: Option<&Scatter>) -> Option<Color> {
+        if !self.debug_pixel {
+            return None;
+        }
+        let bounce = self.max_depth - depth;
+        let face = if hit.front_face { "front" } else { "back" };
+        let before = THROUGHPUT.with(|throughput| throughput.get());
+        match scatter {
+            Some(scatter) => {
+                let throughput = before * scatter.attenuation;
+                THROUGHPUT.with(|cell| cell.set(throughput));
+                info!(
+                    "  bounce {bounce}: {} ({face} face) at {} with normal {}, t {}: attenuation {}, throughput {}, \
+                     scattered towards {}",
+                    hit.material.name(),
+                    hit.point,
+                    hit.normal,
+                    hit.t,
+                    scatter.attenuation,
+                    throughput,
+                    scatter.ray.direction
+                );
+            }
+            None => info!(
+                "  bounce {bounce}: {} ({face} face) at {} with normal {}, t {}: absorbed",
+                hit.material.name(),
+                hit.point,
+                hit.normal,
+                hit.t
+            ),
+        }
+        Some(before)
+    }
+
+    // Restore the throughput returned by `log_bounce`.
+    pub(super) fn end_bounce(before: Option<Color>) {
+        if let Some(before) = before {
+            THROUGHPUT.with(|throughput| throughput.set(before));
+        }
+    }
+
+    // Log what happened at a bounce of a path of the pixel being debugged other than scattering at a surface, e.g.
+    // the path leaving the scene or running out of bounces.
+    pub(super) fn log_note(&self, depth: i32, message: Arguments) {
+        if self.debug_pixel {
+            info!("  bounce {}: {message}", self.max_depth - depth);
+        }
+    }
+}
+
+// Color of the surface at `hit` for the integrators that only look at the closest hit of the camera rays.
+fn surface_color(integrator: Integrator, hit: Hit) -> Color {
+    match integrator {
+        Integrator::Normals => {
+            let normal = hit.normal;
+            displayed(Color::new(normal.x + 1.0, normal.y + 1.0, normal.z + 1.0) * 0.5)
+        }
+        Integrator::Depth => Color::splat(hit.t),
+        Integrator::Uv => displayed(Color::new(hit.uv[0], hit.uv[1], 0.0)),
+        Integrator::Object => displayed(id_color(hit.object)),
+        Integrator::Material => displayed(id_color(hit.material.id())),
+        _ => Color::BLACK,
+    }
+}
@@ -0,0 +1,56 @@
+use super::{count_rays, report_rays, Camera, RenderInfo, T_RANGE};
+use crate::{
+    deep::DeepImage, float::Float, hittable::Hittable, medium::MediumStack, progress::Progress, stats, vec3::Vec3,
+};
+
+// Deep rendering: the camera samples of every pixel are kept apart by the depth of what they hit first, see
+// `deep.rs`, rather than filtered into one color.
+impl Camera {
+    // Render the world into a deep image. The samples are traced one by one with the path tracer and count for the
+    // pixel they are in, without the pixel filter. The crop and the stop apply, checkpoints and time budgets don't.
+    pub fn render_deep(&self, world: &dyn Hittable, progress: &dyn Progress) -> (DeepImage, RenderInfo) {
+        let samples_per_pixel = self.samples_per_pixel.max(0) as usize;
+        progress.start(self.rows.len() as u64);
+        progress.samples_per_pixel(samples_per_pixel as u64);
+        stats::take();
+
+        // Depths are measured along the viewing direction, the plane of the pixels faces the camera
+        let forward = Vec3::cross(self.view.pixel_delta_u, self.view.pixel_delta_v).normalize();
+        let mut image = DeepImage::new(self.image_width as usize, self.image_height as usize);
+        let mut samples = Vec::with_capacity(samples_per_pixel);
+        let mut rendered = 0;
+        let mut stopped = false;
+        for j in self.rows.clone() {
+            if self.stopped() {
+                stopped = true;
+                break;
+            }
+            for i in self.columns.clone() {
+                samples.clear();
+                for _ in 0..samples_per_pixel {
+                    let (ray, _) = self.get_ray(i, j);
+                    count_rays(1, true);
+                    let hit = world.hit(ray, T_RANGE);
+                    let depth = hit.map(|hit| Vec3::dot(hit.point - ray.origin, forward));
+                    let color = self.exposure_at(i as usize, j as usize)
+                        * self.shade(ray, hit, self.max_depth, world, &MediumStack::default(), None);
+                    samples.push((depth, color));
+                }
+                image.set(i as usize, j as usize, &mut samples);
+            }
+            rendered += 1;
+            report_rays(progress);
+            progress.advance(1);
+        }
+
+        let pixels = self.columns.len() * rendered;
+        let info = RenderInfo {
+            samples: (pixels * samples_per_pixel) as u64,
+            samples_per_pixel: (pixels * samples_per_pixel) as Float
+                / (self.columns.len() * self.rows.len()).max(1) as Float,
+            stopped,
+            stats: stats::take(),
+        };
+        (image, info)
+    }
+}
@@ -0,0 +1,347 @@
+use std::io;
+
+use super::{count_rays, count_shadow_rays, report_rays, Camera, LiveImage, T_RANGE};
+use crate::{
+    aabb::Aabb,
+    bvh::{morton_code, radix_sort},
+    checkpoint::Checkpoints,
+    color::Color,
+    film::Film,
+    float::Float,
+    hittable::{Hit, Hittable},
+    medium::MediumStack,
+    progress::Progress,
+    ray::Ray,
+    vec3::Vec3,
+};
+
+const WAVE_SIZE: usize = 1 << 18; // camera rays started together, whole scanlines are rendered per wave
+
+// Path of a camera sample that is still being traced.
+struct Path {
+    ray: Ray,             // next ray to trace
+    throughput: Color,    // attenuation accumulated along the path so far
+    radiance: Color,      // light given off by the surfaces along the path so far, as seen by the camera
+    position: [Float; 2], // position of the camera sample on the image
+    depth: i32,           // remaining bounces
+    media: MediumStack,
+    from: Option<[u32; 2]>, // IDs of the object the ray leaves, None for camera rays
+    waiting: Option<usize>, // index of the shadow catcher path waiting for the light of this one, None for samples
+}
+
+impl Path {
+    // Path that passed a surface giving off light.
+    fn emit(self, emitted: Color) -> Path {
+        Path {
+            radiance: self.radiance + self.throughput * emitted,
+            ..self
+        }
+    }
+
+    // Path continuing with the next ray after a bounce.
+    fn bounce(self, ray: Ray, attenuation: Color) -> Path {
+        Path {
+            ray,
+            throughput: self.throughput * attenuation,
+            depth: self.depth - 1,
+            ..self
+        }
+    }
+
+    // Path leaving the surface it scattered off.
+    fn leaving(self, hit: &Hit) -> Path {
+        Path {
+            from: Some(hit.ids()),
+            ..self
+        }
+    }
+}
+
+// Camera path that hit the front of a shadow catcher, waiting for the occlusion test of the ray the catcher scattered.
+struct Catch {
+    behind: Path,   // path continuing through the catcher
+    ray: Ray,       // ray scattered off the catcher
+    from: [u32; 2], // IDs of the catcher
+}
+
+// Camera path behind a shadow catcher whose scattered ray was blocked, waiting for the light received along it.
+struct Caught {
+    behind: Path,
+    unoccluded: Color, // light that would arrive along the scattered ray if there were no objects at all
+}
+
+impl Caught {
+    // Path behind the catcher, darkened or tinted by the light the catcher received.
+    fn release(self, received: Color) -> Path {
+        Path {
+            throughput: self.behind.throughput * received / self.unoccluded,
+            ..self.behind
+        }
+    }
+}
+
+// Outcome of a path meeting the surface it hit.
+enum Step {
+    Continue(Path),
+    Done(Color), // light the path contributes to its sample
+    Catch(Catch),
+}
+
+// Wavefront (streaming) integrator. Instead of following one sample at a time through all its bounces, the samples of
+// many scanlines are advanced in lockstep: every stage runs over a whole buffer of paths before the next one starts,
+// first generating the camera rays, then intersecting all of them with the scene, then shading all hits, which
+// produces the buffer of rays for the next bounce, and last testing the rays scattered off shadow catchers for
+// occlusion. Every stage keeps its code and data hot in the caches for the whole buffer, and the buffers are what a
+// GPU or a SIMD backend would work on. Like the recursive integrator, it finds light only where paths happen to hit
+// emissive surfaces and doesn't sample the lights. The images of both are the same.
+impl Camera {
+    // Render the scanlines from `start` on. Checkpoints are saved between waves, when they are due, the live image is
+    // written after every wave and the render stops between waves if asked to. Returns the first scanline that wasn't
+    // rendered.
+    pub(super) fn render_wavefront(
+        &self,
+        world: &dyn Hittable,
+        film: &mut Film,
+        start: i32,
+        progress: &dyn Progress,
+        checkpoints: &mut Checkpoints,
+        live: &mut Option<LiveImage>,
+    ) -> io::Result<i32> {
+        let width = self.columns.len();
+        let samples_per_pixel = self.samples_per_pixel.max(0) as usize;
+        let rows_per_wave = (WAVE_SIZE / (width * samples_per_pixel).max(1)).max(1) as i32;
+        let bounds = world.bounding_box();
+
+        let columns = self.columns.start as usize..self.columns.end as usize;
+        let mut row = start;
+        while row < self.rows.end && !self.stopped() {
+            let rows = rows_per_wave.min(self.rows.end - row);
+            #[cfg(feature = "tracing")]
+            let _span = tracing::debug_span!("wave", row, rows).entered();
+            let mut tile = film.tile(columns.clone(), row as usize..(row + rows) as usize);
+            let mut paths = self.generate(row..row + rows);
+            let mut caught = vec![];
+            let mut primary = true;
+            while !paths.is_empty() {
+                // Only camera rays are coherent enough for packets
+                let hits = intersect(&paths, world, primary && self.packets, self.max_depth);
+                let catches;
+                (paths, catches) = self.advance(paths, hits, &mut tile, &mut caught);
+                paths.extend(self.occlude(catches, world, &mut caught));
+                if self.sort_rays {
+                    paths = sort(paths, &bounds);
+                }
+                primary = false;
+            }
+            film.merge(&tile);
+            checkpoints.update(film, row + rows)?;
+            self.write_live(live, film, row..row + rows)?;
+            report_rays(progress);
+            progress.advance(rows as u64);
+            row += rows;
+        }
+        Ok(row)
+    }
+
+    // Camera rays for all samples of the rendered pixels of the given scanlines.
+    fn generate(&self, rows: std::ops::Range<i32>) -> Vec<Path> {
+        if self.max_depth <= 0 {
+            return vec![];
+        }
+        let mut paths = vec![];
+        for j in rows {
+            for i in self.columns.clone() {
+                for _ in 0..self.samples_per_pixel {
+                    let (ray, position) = self.get_ray(i, j);
+                    paths.push(Path {
+                        ray,
+                        throughput: Color::WHITE,
+                        radiance: Color::BLACK,
+                        position,
+                        depth: self.max_depth,
+                        media: MediumStack::default(),
+                        from: None,
+                        waiting: None,
+                    });
+                }
+            }
+        }
+        paths
+    }
+
+    // Shade the hits, splatting the samples of finished paths and releasing the shadow catcher paths waiting for them.
+    // Returns the paths that continue and the shadow catcher hits to test for occlusion.
+    fn advance(
+        &self,
+        paths: Vec<Path>,
+        hits: Vec<Option<Hit>>,
+        film: &mut Film,
+        caught: &mut [Option<Caught>],
+    ) -> (Vec<Path>, Vec<Catch>) {
+        let mut next = Vec::with_capacity(paths.len());
+        let mut catches = vec![];
+        for (path, hit) in paths.into_iter().zip(hits) {
+            // Light is absorbed on the way through the medium the path is in
+            let throughput = path.throughput * path.media.transmittance(path.ray, hit.as_ref());
+            let path = Path { throughput, ..path };
+            let (position, waiting) = (path.position, path.waiting);
+            let (throughput, radiance) = (path.throughput, path.radiance);
+            let light = match self.step(path, hit) {
+                Step::Continue(path) if path.depth > 0 => {
+                    next.push(path);
+                    continue;
+                }
+                // Out of bounces, no more light reaches the sample but it still counts towards the average
+                Step::Continue(path) => path.radiance,
+                Step::Done(light) => radiance + throughput * light,
+                Step::Catch(catch) => {
+                    catches.push(catch);
+                    continue;
+                }
+            };
+            match waiting.and_then(|index| caught[index].take()) {
+                Some(caught) => next.push(caught.release(light)),
+                None => film.splat(position, light),
+            }
+        }
+        (next, catches)
+    }
+
+    // Test the rays scattered off shadow catchers for occlusion. Light that no object blocks or reflects arrives as it
+    // would without any objects, and the path behind the catcher continues right away. The light along the other rays
+    // is traced as a path of its own, which the path behind the catcher waits for in `caught`. Returns the paths that
+    // continue.
+    fn occlude(&self, catches: Vec<Catch>, world: &dyn Hittable, caught: &mut Vec<Option<Caught>>) -> Vec<Path> {
+        count_shadow_rays(catches.iter().filter(|catch| catch.behind.depth > 1).count());
+        let mut next = Vec::with_capacity(catches.len());
+        for Catch { behind, ray, from } in catches {
+            if behind.depth > 1 && !world.hit_any(ray, T_RANGE) {
+                let throughput = behind.throughput * behind.media.transmittance(ray, None);
+                next.push(Path { throughput, ..behind });
+                continue;
+            }
+            let waiting = Caught {
+                unoccluded: self.background(ray),
+                behind,
+            };
+            // Out of bounces, the catcher receives no light at all
+            if waiting.behind.depth <= 1 {
+                next.push(waiting.release(Color::BLACK));
+                continue;
+            }
+            next.push(Path {
+                ray,
+                throughput: Color::WHITE,
+                radiance: Color::BLACK,
+                position: waiting.behind.position,
+                depth: waiting.behind.depth - 1,
+                media: waiting.behind.media.clone(),
+                from: Some(from),
+                waiting: Some(caught.len()),
+            });
+            caught.push(Some(waiting));
+        }
+        next
+    }
+
+    // Same as one level of `Camera::shade`.
+    fn step(&self, path: Path, hit: Option<Hit>) -> Step {
+        if path.depth == self.max_depth && self.held_out(hit.as_ref()) {
+            return Step::Done(Color::BLACK);
+        }
+        let Some(hit) = hit else {
+            return Step::Done(self.background(path.ray));
+        };
+        if hit.material.is_shadow_catcher() && path.depth == self.max_depth {
+            // Camera rays see through the shadow catcher, the path continues behind it
+            let ray = path.ray;
+            let behind = Path {
+                ray: Ray::new(hit.point, ray.direction, ray.time),
+                ..path
+            };
+            // Only the front face catches anything, closed catchers are see-through from the inside
+            if !hit.front_face {
+                return Step::Continue(behind);
+            }
+            return match hit.material.scatter(ray, hit) {
+                Some(scatter) => Step::Catch(Catch {
+                    behind,
+                    ray: scatter.ray,
+                    from: hit.ids(),
+                }),
+                None => Step::Continue(behind),
+            };
+        }
+
+        let Some(medium) = hit.material.medium() else {
+            let emitted = self.emitted(&hit, path.from);
+            return match hit.material.scatter(path.ray, hit) {
+                Some(scatter) => Step::Continue(
+                    path.emit(emitted)
+                        .bounce(scatter.ray, scatter.attenuation)
+                        .leaving(&hit),
+                ),
+                None => Step::Done(emitted),
+            };
+        };
+
+        let entering = hit.front_face;
+        let crossed = if entering {
+            path.media.entered(hit.material, medium)
+        } else {
+            path.media.exited(hit.material)
+        };
+        if path.media.is_false_interface(hit.material, medium, entering) {
+            let ray = Ray::new(hit.point, path.ray.direction, path.ray.time);
+            return Step::Continue(Path {
+                media: crossed,
+                ..path.bounce(ray, Color::WHITE)
+            });
+        }
+        let eta_from = if entering { path.media.ir() } else { medium.ir };
+        match hit.material.scatter_between(path.ray, hit, eta_from, crossed.ir()) {
+            Some(scatter) => {
+                // The ray refracted if it continues on the other side of the surface
+                let refracted = Vec3::dot(scatter.ray.direction, hit.normal) < 0.0;
+                let path = path.bounce(scatter.ray, scatter.attenuation).leaving(&hit);
+                Step::Continue(if refracted {
+                    Path { media: crossed, ..path }
+                } else {
+                    path
+                })
+            }
+            None => Step::Done(Color::BLACK),
+        }
+    }
+}
+
+// Closest hit of every path, the paths with all `max_depth` bounces left count as camera rays. The camera rays of a
+// pixel are next to each other in the buffer, so they can be traced together as packets.
+fn intersect<'a>(paths: &[Path], world: &'a dyn Hittable, packets: bool, max_depth: i32) -> Vec<Option<Hit<'a>>> {
+    let primary = paths.iter().filter(|path| path.depth == max_depth).count();
+    count_rays(primary, true);
+    count_rays(paths.len() - primary, false);
+    if packets {
+        let rays: Vec<Ray> = paths.iter().map(|path| path.ray).collect();
+        let mut hits = vec![None; rays.len()];
+        world.hit_packet(&rays, T_RANGE, &mut hits);
+        hits
+    } else {
+        paths.iter().map(|path| world.hit(path.ray, T_RANGE)).collect()
+    }
+}
+
+// Reorder the bounced rays so that rays leaving in the same octant from nearby points follow each other. Diffuse
+// bounces scatter the rays of neighbouring pixels all over the scene, after sorting consecutive rays visit the same
+// BVH nodes and primitives again while they are still in the cache. The key is the octant of the direction in the top
+// bits followed by the Morton code of the origin within the scene bounds.
+fn sort(paths: Vec<Path>, bounds: &Aabb) -> Vec<Path> {
+    let keys = paths.iter().enumerate().map(|(index, path)| {
+        let Ray { origin, direction, .. } = path.ray;
+        let octant = (direction.x < 0.0) as u64 | ((direction.y < 0.0) as u64) << 1 | ((direction.z < 0.0) as u64) << 2;
+        (octant << 60 | morton_code(origin, bounds) >> 3, index)
+    });
+    let order = radix_sort(keys.collect());
+    let mut paths: Vec<Option<Path>> = paths.into_iter().map(Some).collect();
+    order.iter().filter_map(|&(_, index)| paths[index].take()).collect()
+}
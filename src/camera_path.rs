@@ -0,0 +1,385 @@
+use std::{fs, path::Path};
+
+use crate::{
+    animation::Keyframes,
+    camera::CameraSettings,
+    error::{Error, Result},
+    float::Float,
+    vec3::{Point, Vec3},
+};
+
+// Where the camera is at one frame of the path.
+struct Row {
+    line: usize,
+    frame: Option<Float>,
+    position: Option<Point>,
+    rotation: Option<[Float; 4]>, // quaternion w, x, y, z
+    look_at: Option<Point>,
+    vfov: Option<Float>,
+    focus_dist: Option<Float>,
+}
+
+impl Row {
+    fn new(line: usize) -> Row {
+        Row {
+            line,
+            frame: None,
+            position: None,
+            rotation: None,
+            look_at: None,
+            vfov: None,
+            focus_dist: None,
+        }
+    }
+}
+
+// Camera path of a flythrough, e.g. exported from Blender or recorded while moving through a scene: the position and
+// orientation of the camera at every frame, as CSV with a header row
+//
+//   frame,x,y,z,qw,qx,qy,qz,vfov
+//   1,0,1.5,8,1,0,0,0,40
+//
+// or as JSON, a list of frames (or an object with the list as "frames")
+//
+//   [{"frame": 1, "position": [0, 1.5, 8], "rotation": [1, 0, 0, 0], "vfov": 40}, ...]
+//
+// The orientation is either a rotation quaternion (w, x, y, z) of a camera looking down -Z with +Y up, like the
+// cameras of Blender and USD, or a point to look at (`look_at_x`, `look_at_y` and `look_at_z` columns, `look_at` in
+// JSON). Without either the camera keeps looking at the `look_at` of `camera`. Rows without a frame number follow
+// the one before, starting at 1. The path is in the coordinates of the scene, `camera` fills in everything it leaves
+// out.
+pub fn load(path: &Path, camera: CameraSettings) -> Result<Keyframes<CameraSettings>> {
+    let text = fs::read_to_string(path)?;
+    let json =
+        path.extension().is_some_and(|extension| extension == "json") || text.trim_start().starts_with(['[', '{']);
+    let rows = if json { parse_json(&text)? } else { parse_csv(&text)? };
+    if rows.is_empty() {
+        return Err(Error::parse(1, "the camera path has no frames"));
+    }
+
+    let mut keys: Vec<(Float, CameraSettings)> = vec![];
+    let mut frame = 0.0;
+    for row in rows {
+        frame = row.frame.unwrap_or(frame + 1.0);
+        if keys.iter().any(|(other, _)| *other == frame) {
+            return Err(Error::parse(row.line, format!("duplicate frame {frame}")));
+        }
+        let look_from = row
+            .position
+            .ok_or_else(|| Error::parse(row.line, "missing camera position"))?;
+        let mut settings = CameraSettings {
+            look_from,
+            vfov: row.vfov.unwrap_or(camera.vfov),
+            focus_dist: row.focus_dist.unwrap_or(camera.focus_dist),
+            ..camera
+        };
+        match (row.rotation, row.look_at) {
+            (Some(_), Some(_)) => {
+                return Err(Error::parse(row.line, "give either a rotation or a point to look at"));
+            }
+            (Some([w, x, y, z]), None) => {
+                let length = (w * w + x * x + y * y + z * z).sqrt();
+                if length == 0.0 || !length.is_finite() {
+                    return Err(Error::parse(row.line, "invalid rotation quaternion"));
+                }
+                let (w, axis) = (w / length, Vec3::new(x, y, z) / length);
+                // v' = v + 2w (u x v) + 2u x (u x v) for the unit quaternion (w, u)
+                let rotate = |v: Vec3| {
+                    let t = 2.0 * Vec3::cross(axis, v);
+                    v + w * t + Vec3::cross(axis, t)
+                };
+                settings.look_at = look_from + rotate(Vec3::new(0.0, 0.0, -1.0));
+                settings.vup = rotate(Vec3::new(0.0, 1.0, 0.0));
+            }
+            (None, Some(look_at)) => settings.look_at = look_at,
+            (None, None) => {}
+        }
+        keys.push((frame, settings));
+    }
+    Ok(Keyframes::new(keys))
+}
+
+fn parse_csv(text: &str) -> Result<Vec<Row>> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(index, text)| (index + 1, text.trim()))
+        .filter(|(_, text)| !text.is_empty() && !text.starts_with('#'));
+    let Some((header_line, header)) = lines.next() else {
+        return Ok(vec![]);
+    };
+    let columns: Vec<&str> = header.split(',').map(str::trim).collect();
+    let column = |name: &str| columns.iter().position(|column| *column == name);
+    // Columns that belong together, all or none of them
+    let group = |names: &[&str]| -> Result<Option<Vec<usize>>> {
+        let found: Vec<_> = names.iter().filter_map(|name| column(name)).collect();
+        match found.len() {
+            0 => Ok(None),
+            n if n == names.len() => Ok(Some(found)),
+            _ => Err(Error::parse(
+                header_line,
+                format!("camera paths need all of the columns {}", names.join(", ")),
+            )),
+        }
+    };
+    let position = group(&["x", "y", "z"])?.ok_or_else(|| Error::parse(header_line, "missing x, y and z columns"))?;
+    let rotation = group(&["qw", "qx", "qy", "qz"])?;
+    let look_at = group(&["look_at_x", "look_at_y", "look_at_z"])?;
+    let (frame, vfov, focus_dist) = (column("frame"), column("vfov"), column("focus_dist"));
+    for unknown in columns.iter().filter(|name| {
+        ![
+            "frame",
+            "x",
+            "y",
+            "z",
+            "qw",
+            "qx",
+            "qy",
+            "qz",
+            "look_at_x",
+            "look_at_y",
+            "look_at_z",
+            "vfov",
+            "focus_dist",
+        ]
+        .contains(name)
+    }) {
+        crate::warn!("camera path: ignoring the column '{unknown}'");
+    }
+
+    let mut rows = vec![];
+    for (line, text) in lines {
+        let values = text
+            .split(',')
+            .map(|value| {
+                let value = value.trim();
+                value
+                    .parse::<Float>()
+                    .map_err(|_| Error::parse(line, format!("invalid number '{value}'")))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if values.len() != columns.len() {
+            return Err(Error::parse(
+                line,
+                format!("expected {} values, found {}", columns.len(), values.len()),
+            ));
+        }
+        let vector = |indices: &Vec<usize>| Vec3::new(values[indices[0]], values[indices[1]], values[indices[2]]);
+        rows.push(Row {
+            frame: frame.map(|index| values[index]),
+            position: Some(vector(&position).to_point()),
+            rotation: rotation
+                .as_ref()
+                .map(|q| [values[q[0]], values[q[1]], values[q[2]], values[q[3]]]),
+            look_at: look_at.as_ref().map(|indices| vector(indices).to_point()),
+            vfov: vfov.map(|index| values[index]),
+            focus_dist: focus_dist.map(|index| values[index]),
+            ..Row::new(line)
+        });
+    }
+    Ok(rows)
+}
+
+fn parse_json(text: &str) -> Result<Vec<Row>> {
+    let mut parser = JsonParser {
+        text,
+        position: 0,
+        depth: 0,
+    };
+    let (_, root) = parser.value()?;
+    parser.skip_whitespace();
+    if parser.position < text.len() {
+        return Err(Error::parse(parser.line(), "unexpected text after the camera path"));
+    }
+    let frames = match root {
+        Json::Object(mut fields) => match fields.iter().position(|(key, _)| key == "frames") {
+            Some(index) => fields.swap_remove(index).1 .1,
+            None => return Err(Error::parse(1, "missing \"frames\"")),
+        },
+        root => root,
+    };
+    let Json::Array(frames) = frames else {
+        return Err(Error::parse(1, "expected a list of frames"));
+    };
+
+    let mut rows = vec![];
+    for (line, frame) in frames {
+        let Json::Object(fields) = frame else {
+            return Err(Error::parse(line, "expected an object for the frame"));
+        };
+        let mut row = Row::new(line);
+        for (key, (line, value)) in fields {
+            let invalid = || Error::parse(line, format!("invalid value for \"{key}\""));
+            let number = |value: &Json| match value {
+                Json::Number(number) => Ok(*number),
+                _ => Err(invalid()),
+            };
+            let numbers = |value: &Json, count: usize| match value {
+                Json::Array(items) if items.len() == count => items.iter().map(|(_, item)| number(item)).collect(),
+                _ => Err(invalid()),
+            };
+            match key.as_str() {
+                "frame" => row.frame = Some(number(&value)?),
+                "position" => row.position = Some(to_point(numbers(&value, 3)?)),
+                "look_at" => row.look_at = Some(to_point(numbers(&value, 3)?)),
+                "rotation" => {
+                    let q: Vec<Float> = numbers(&value, 4)?;
+                    row.rotation = Some([q[0], q[1], q[2], q[3]]);
+                }
+                "vfov" => row.vfov = Some(number(&value)?),
+                "focus_dist" => row.focus_dist = Some(number(&value)?),
+                _ => crate::warn!("camera path: ignoring \"{key}\""),
+            }
+        }
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+fn to_point(values: Vec<Float>) -> Point {
+    Point::new(values[0], values[1], values[2])
+}
+
+// JSON value, the items of arrays and objects come with the line they start on.
+enum Json {
+    Null,
+    Bool,
+    Number(Float),
+    String(String),
+    Array(Vec<(usize, Json)>),
+    Object(Vec<(String, (usize, Json))>),
+}
+
+// Most arrays and objects inside each other, camera paths need three. Deeper ones end the parse before they
+// overflow the stack.
+const MAX_DEPTH: usize = 32;
+
+struct JsonParser<'a> {
+    text: &'a str,
+    position: usize,
+    depth: usize, // arrays and objects the parser is inside of
+}
+
+impl JsonParser<'_> {
+    fn line(&self) -> usize {
+        self.text[..self.position].matches('\n').count() + 1
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.text[self.position..];
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.position).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<()> {
+        self.skip_whitespace();
+        if self.peek() != Some(byte) {
+            return Err(Error::parse(self.line(), format!("expected '{}'", byte as char)));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<(usize, Json)> {
+        self.skip_whitespace();
+        let line = self.line();
+        if matches!(self.peek(), Some(b'[' | b'{')) && self.depth == MAX_DEPTH {
+            return Err(Error::parse(
+                line,
+                format!("arrays and objects nested deeper than {MAX_DEPTH}"),
+            ));
+        }
+        let value = match self.peek() {
+            Some(b'[') => {
+                self.position += 1;
+                self.depth += 1;
+                let mut items = vec![];
+                self.skip_whitespace();
+                if self.peek() == Some(b']') {
+                    self.position += 1;
+                } else {
+                    loop {
+                        items.push(self.value()?);
+                        self.skip_whitespace();
+                        match self.peek() {
+                            Some(b',') => self.position += 1,
+                            _ => break self.expect(b']')?,
+                        }
+                    }
+                }
+                self.depth -= 1;
+                Json::Array(items)
+            }
+            Some(b'{') => {
+                self.position += 1;
+                self.depth += 1;
+                let mut fields = vec![];
+                self.skip_whitespace();
+                if self.peek() == Some(b'}') {
+                    self.position += 1;
+                } else {
+                    loop {
+                        self.skip_whitespace();
+                        let key = match self.value()? {
+                            (_, Json::String(key)) => key,
+                            (line, _) => return Err(Error::parse(line, "expected a string as key")),
+                        };
+                        self.expect(b':')?;
+                        fields.push((key, self.value()?));
+                        self.skip_whitespace();
+                        match self.peek() {
+                            Some(b',') => self.position += 1,
+                            _ => break self.expect(b'}')?,
+                        }
+                    }
+                }
+                self.depth -= 1;
+                Json::Object(fields)
+            }
+            Some(b'"') => {
+                self.position += 1;
+                let mut string = String::new();
+                let mut chars = self.text[self.position..].char_indices();
+                loop {
+                    let Some((offset, c)) = chars.next() else {
+                        return Err(Error::parse(line, "unterminated string"));
+                    };
+                    match c {
+                        '"' => {
+                            self.position += offset + 1;
+                            break;
+                        }
+                        // Escapes are kept as they are, keys and values of camera paths don't need them
+                        '\\' => {
+                            string.push(c);
+                            string.extend(chars.next().map(|(_, c)| c));
+                        }
+                        c => string.push(c),
+                    }
+                }
+                Json::String(string)
+            }
+            _ => {
+                let rest = &self.text[self.position..];
+                let length = rest
+                    .find(|c: char| c.is_whitespace() || matches!(c, ',' | ']' | '}'))
+                    .unwrap_or(rest.len());
+                let token = &rest[..length];
+                self.position += length;
+                match token {
+                    "null" => Json::Null,
+                    "true" | "false" => Json::Bool,
+                    token => Json::Number(
+                        token
+                            .parse()
+                            .map_err(|_| Error::parse(line, format!("unexpected '{token}'")))?,
+                    ),
+                }
+            }
+        };
+        Ok((line, value))
+    }
+}
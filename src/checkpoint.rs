@@ -0,0 +1,99 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use crate::{film::Film, rng::Rng, util::with_rng};
+
+const MAGIC: &[u8; 8] = b"RTCHKPT1";
+
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+
+// Where and how often the state of a render is saved.
+#[derive(Clone, Debug)]
+pub struct CheckpointSettings {
+    pub path: PathBuf,
+    pub interval: Duration, // minimum time between two checkpoints
+    pub resume: bool,       // continue from the checkpoint at `path` if there is one
+}
+
+// Saves the state of a render in progress: the sums of the film, the first scanline that isn't done yet and the state
+// of the random number generator, so a resumed render continues the same random sequence. The checkpoint is written
+// to a temporary file first and renamed, an interruption while saving leaves the previous checkpoint intact.
+//
+// The checkpoint only holds the progress, not the scene. Resuming with a different scene or camera settings continues
+// the render of whatever is loaded, only an image or crop window of a different size is refused.
+pub struct Checkpoints<'a> {
+    settings: Option<&'a CheckpointSettings>,
+    last_save: Instant,
+}
+
+impl<'a> Checkpoints<'a> {
+    // No checkpoints are written without settings.
+    pub fn new(settings: Option<&'a CheckpointSettings>) -> Checkpoints<'a> {
+        Checkpoints {
+            settings,
+            last_save: Instant::now(),
+        }
+    }
+
+    // Load the checkpoint into `film` if the render is resumed and a checkpoint was saved before. Returns the row to
+    // continue at, or `None` to start from the beginning.
+    pub fn resume(&self, film: &mut Film) -> io::Result<Option<i32>> {
+        let Some(settings) = self.settings.filter(|settings| settings.resume) else {
+            return Ok(None);
+        };
+        let file = match File::open(&settings.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let mut input = BufReader::new(file);
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a checkpoint file"));
+        }
+        let mut read_u64 = || -> io::Result<u64> {
+            let mut bytes = [0; 8];
+            input.read_exact(&mut bytes)?;
+            Ok(u64::from_le_bytes(bytes))
+        };
+        let row = read_u64()? as i32;
+        let state = [read_u64()?, read_u64()?, read_u64()?, read_u64()?];
+        film.read_sums(&mut input)?;
+        with_rng(|rng| *rng = Rng::from_state(state));
+        crate::info!("resuming from {} at scanline {row}", settings.path.display());
+        Ok(Some(row))
+    }
+
+    // Save a checkpoint if the interval has passed since the last one, `row` is the first scanline not yet in `film`.
+    pub fn update(&mut self, film: &Film, row: i32) -> io::Result<()> {
+        match self.settings {
+            Some(settings) if self.last_save.elapsed() >= settings.interval => self.save(film, row),
+            _ => Ok(()),
+        }
+    }
+
+    // Save a checkpoint right away, e.g. when the render is stopped.
+    pub fn save(&mut self, film: &Film, row: i32) -> io::Result<()> {
+        let Some(settings) = self.settings else {
+            return Ok(());
+        };
+        let temporary = settings.path.with_extension("tmp");
+        let mut out = BufWriter::new(File::create(&temporary)?);
+        out.write_all(MAGIC)?;
+        out.write_all(&(row as u64).to_le_bytes())?;
+        for word in with_rng(|rng| rng.state()) {
+            out.write_all(&word.to_le_bytes())?;
+        }
+        film.write_sums(&mut out)?;
+        out.into_inner()?.sync_all()?;
+        fs::rename(&temporary, &settings.path)?;
+        crate::debug!("saved checkpoint {} at scanline {row}", settings.path.display());
+        self.last_save = Instant::now();
+        Ok(())
+    }
+}
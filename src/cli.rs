@@ -0,0 +1,602 @@
+use std::{ops::RangeInclusive, path::PathBuf, time::Duration};
+
+use rustracer::{
+    bvh::BvhSettings, camera::Focus, examples, float::Float, generator::GeneratorSettings, integrator::Integrator,
+    log::Level, output::ImageFormat, scene::Override,
+};
+
+pub const USAGE: &str = "\
+Usage: rustracer [OPTIONS] [SCENE]
+       rustracer batch [OPTIONS] <MANIFEST>
+       rustracer worker --listen <ADDRESS>
+
+Renders SCENE (or the built-in demo scene) into the image file given with --output, or as a PPM image to stdout.
+The extension of image files picks their format: *.ppm, *.qoi, 16 bit *.png or *.tif(f), or linear *.exr or *.hdr.
+
+Options:
+  -o, --output <FILE> Write the image into FILE, in the format of its extension; '-' writes it to stdout, which is
+                      also where it goes without --output unless stdout is a terminal
+  --format <FORMAT>   Format of the image written to stdout: 'ppm' (default), 'qoi' (smaller and faster to write),
+                      'png' or 'tiff' with 16 bits per channel, or 'exr' or 'hdr' with the linear light values
+  --set <OBJECT.KEY=VALUE>
+                      Change a parameter of the scene file, e.g. camera.vfov=35, ball.radius=2 or
+                      ball.material.roughness=0.2; can be repeated
+  --scene <NAME>      Render the built-in scene NAME instead of a scene file, e.g. 'cornell' (see --list-scenes)
+  --generate <SPHERES> Render a random scene of SPHERES small spheres on a ground plane around three big ones
+  --boxes <N>         Add N boxes to the random scene of --generate
+  --mix <D,M,G>       Weights of diffuse, metal and glass materials in the random scene, 0.8,0.15,0.05 by default
+  --seed <N>          Seed of the random scene, the same seed gives the same scene (0 by default)
+  --batch <MANIFEST>  Render every job listed in MANIFEST (e.g. jobs.toml) into its own output file, the same as
+                      'rustracer batch'
+  --parallel <N>      Render N jobs of the batch at the same time, each on a core of its own (1 by default)
+  --frames <FIRST..LAST>
+                      Render the frames FIRST to LAST of the animation of the scene, with motion blur, each into a
+                      numbered PPM file; a single number renders one frame
+  --frame-range <FIRST..LAST>
+                      The same as --frames
+  --frame-step <N>    Only render every Nth frame of --frames, starting with the first, e.g. for a slice of the
+                      animation per render farm node
+  --skip-existing     Leave out the frames whose files exist, and claim the others with a FILE.part file while they
+                      render, so nodes rendering the same frames share them; exits with 3 if other nodes still render
+                      some of them
+  --frame-output <PATTERN>
+                      File name of the frames, the #s are replaced by the frame number (frame_####.ppm by default)
+  --video <FILE>      Encode the frames into the video FILE, e.g. shot.mp4 or shot.webm, with ffmpeg instead of writing
+                      them into files of their own (needs a build with the 'video' feature)
+  --fps <N>           Frames per second of the video, 24 by default
+  --camera-path <FILE> Move the camera along the path in FILE, a CSV or JSON file of camera positions and rotations
+                      per frame, e.g. exported from Blender
+  --watch <IMAGE>     Render SCENE into the image file IMAGE progressively, starting over whenever the scene file or a
+                      file it refers to changes, until Ctrl-C
+  --golden <DIR>      Render the scenes in DIR with a fixed seed and compare them with their reference images
+  --update-golden     Write the reference images of --golden instead of comparing with them
+  --tolerance <RMSE>  Highest root mean square error per channel (0 to 1) that --golden accepts, 0.01 by default
+  --bvh <BUILDER>     BVH construction: 'sah' (default, fastest rendering) or 'lbvh' (fastest build, for previews)
+  --bvh-width <N>     Children per BVH node tested together with SIMD: 2, 4 (default) or 8
+  --bvh-compact       Use quantized 32-byte binary BVH nodes to save memory bandwidth on huge scenes
+  --packets           Trace the camera rays of every pixel together as SIMD ray packets (faster on large meshes)
+  --wavefront         Render with the wavefront integrator, advancing all samples of many scanlines bounce by bounce
+  --sort-rays         Sort bounced rays by direction and origin before tracing them (needs --wavefront)
+  --integrator <NAME> What to compute for every pixel: 'path' (default) for the image, false colors of the 'normals',
+                      'depth' or 'uv' of the surfaces seen, their 'motion' vectors until the next frame, the IDs of
+                      the named 'object' or 'material' seen, a heatmap of the 'bounces' of the paths, or of the work
+                      per camera ray, 'nodes' for acceleration structure nodes visited or 'tests' for primitives tested
+  --layer <GROUP>     Only render the objects of GROUP (given with group=GROUP), the others are held out in black;
+                      'default' has the objects without a group and the background
+  --crop <X0 Y0 X1 Y1> Only render the pixels from X0, Y0 up to (excluding) X1, Y1, the rest stays black
+  --focus-at <X Y>    Focus the camera on the surface seen at the pixel X, Y instead of at the focus distance
+  --focus-on <NAME>   Focus the camera on the object or group NAME, in the middle of where it shows in the image
+  --debug-pixel <X Y> Only render the pixel at X, Y and log every bounce of its samples, for chasing NaNs and black
+                      pixels
+  --time <DURATION>   Render progressively until DURATION (e.g. 90s, 2m or 1h) is up, at most the scene's samples
+  --deep <FILE>       Also write the image as a deep OpenEXR file, with the samples of every pixel at their depths
+  --cryptomatte <FILE> Also write the image as an OpenEXR file with Cryptomatte mattes of the named objects and
+                      materials
+  --hdr <FILE>        Also write the image as a Radiance HDR file, with the light above what a PPM can hold
+  --exr <FILE>        Also write the image into the OpenEXR FILE while it renders, every scanline as soon as it's done,
+                      for watching the render in an image viewer; the finished image replaces it at the end
+  --serve <ADDRESS>   Serve a web page at ADDRESS, e.g. 0.0.0.0:8080, that shows the image in the browser while it
+                      renders, refined with every pass
+  --workers <ADDRESSES>
+                      Render bands of scanlines on the worker processes at the comma separated ADDRESSES (host:port),
+                      which need the scene at the same path, and write the image here; failed workers are left out
+  --listen <ADDRESS>  Address the worker waits for coordinators at, e.g. 0.0.0.0:7878, each connection renders on a
+                      core of its own
+  --stats-json <FILE> Write the settings, timings, counters and peak memory of the render to FILE as JSON
+  --checkpoint <FILE> Save the progress of the render to FILE every minute
+  --checkpoint-interval <SECONDS>
+                      Time between two checkpoints, 60 by default
+  --resume            Continue the render from the checkpoint FILE if there is one (needs --checkpoint)
+  --arena             Flatten the scene into arrays of spheres and triangles under a single acceleration structure
+  -v, --verbose       Log more details, e.g. the acceleration structure builds; twice for every scanline
+  -q, --quiet         Only print errors, without a progress bar
+  --list-presets      Print the material and index of refraction presets scene files can use
+  --list-scenes       Print the names of the built-in scenes
+  -h, --help          Print this help";
+
+#[derive(Default)]
+pub struct Options {
+    pub scene: Option<PathBuf>,  // scene file to render, the demo scene is used if missing
+    pub output: Option<PathBuf>, // image file to write, '-' for stdout
+    pub format: ImageFormat,     // format of the image written to stdout
+    // changes of scene parameters given with --set
+    pub overrides: Vec<Override>,
+    pub batch: Option<PathBuf>,  // manifest of jobs for the batch mode
+    pub parallel: Option<usize>, // jobs of the batch rendered at the same time
+    pub golden: Option<PathBuf>, // directory of the scenes and reference images of the golden image test
+    // image file that watch mode keeps rendering into
+    pub watch: Option<PathBuf>,
+    // frames of the animation to render, and the file name of their images
+    pub frames: Option<RangeInclusive<i32>>,
+    pub frame_output: Option<String>,
+    pub frame_step: Option<usize>, // render every Nth frame of the range
+    pub skip_existing: bool,       // leave out the frames that are done or claimed by other processes
+    // video file the frames are encoded into, and its frame rate
+    pub video: Option<PathBuf>,
+    pub fps: Option<Float>,
+    // file of camera positions per frame the camera moves along
+    pub camera_path: Option<PathBuf>,
+    // group of objects to render, the others are held out
+    pub layer: Option<String>,
+    // built-in scene to render
+    pub example: Option<String>,
+    // random scene to render
+    pub generator: Option<GeneratorSettings>,
+    pub update_golden: bool,
+    pub tolerance: Option<f64>,
+    pub bvh: BvhSettings,
+    pub packets: bool, // trace camera rays as packets
+    pub wavefront: bool,
+    pub sort_rays: bool,
+    pub arena: bool, // flatten the scene into an arena
+    pub integrator: Integrator,
+    pub crop: Option<[i32; 4]>, // window of the image to render, x0 y0 x1 y1
+    pub debug_pixel: Option<[i32; 2]>,
+    pub focus: Option<Focus>, // what the camera focuses on instead of its focus distance
+    pub checkpoint: Option<PathBuf>,
+    pub checkpoint_interval: Option<u64>, // seconds
+    pub resume: bool,
+    pub time: Option<Duration>, // time budget of a progressive render
+    pub stats_json: Option<PathBuf>,
+    // deep OpenEXR file to write besides the image
+    pub deep: Option<PathBuf>,
+    // OpenEXR file with the Cryptomatte layers to write besides the image
+    pub cryptomatte: Option<PathBuf>,
+    // Radiance HDR file to write besides the image
+    pub hdr: Option<PathBuf>,
+    // OpenEXR file the image is written into while it renders
+    pub exr: Option<PathBuf>,
+    // address of the web page showing the render
+    pub serve: Option<String>,
+    // addresses of the workers to render on, and the address a worker listens at
+    pub workers: Vec<String>,
+    pub listen: Option<String>,
+    pub worker: bool, // run as a worker of a distributed render
+    pub verbose: u8,  // number of times --verbose was given
+    pub quiet: bool,
+    pub list_presets: bool,
+    pub list_scenes: bool,
+    pub help: bool,
+}
+
+impl Options {
+    pub fn log_level(&self) -> Level {
+        match (self.quiet, self.verbose) {
+            (true, _) => Level::Error,
+            (false, 0) => Level::Info,
+            (false, 1) => Level::Debug,
+            (false, _) => Level::Trace,
+        }
+    }
+}
+
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut generator = GeneratorSettings::default();
+    let (mut generate, mut generator_options) = (false, false);
+    let mut args = args.into_iter().peekable();
+    options.worker = args.next_if(|arg| arg == "worker").is_some();
+    let batch = args.next_if(|arg| arg == "batch").is_some();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => options.help = true,
+            "--list-presets" => options.list_presets = true,
+            "--list-scenes" => options.list_scenes = true,
+            "-v" | "--verbose" => options.verbose += 1,
+            "-q" | "--quiet" => options.quiet = true,
+            "--bvh" => options.bvh.builder = value(&mut args, &arg)?.parse()?,
+            "--bvh-width" => options.bvh.width = value(&mut args, &arg)?.parse()?,
+            "--bvh-compact" => options.bvh.compact = true,
+            "--packets" => options.packets = true,
+            "--wavefront" => options.wavefront = true,
+            "--sort-rays" => options.sort_rays = true,
+            "--arena" => options.arena = true,
+            "--integrator" => options.integrator = value(&mut args, &arg)?.parse()?,
+            "--crop" => {
+                let mut crop = [0; 4];
+                for coordinate in &mut crop {
+                    let value = value(&mut args, &arg)?;
+                    *coordinate = value
+                        .parse()
+                        .map_err(|_| format!("invalid pixel coordinate '{value}' for '{arg}'"))?;
+                }
+                let [x0, y0, x1, y1] = crop;
+                if x0 >= x1 || y0 >= y1 {
+                    return Err(format!(
+                        "empty crop window {x0} {y0} {x1} {y1}, expected x0 < x1 and y0 < y1"
+                    ));
+                }
+                options.crop = Some(crop);
+            }
+            "--debug-pixel" => {
+                let mut pixel = [0; 2];
+                for coordinate in &mut pixel {
+                    let value = value(&mut args, &arg)?;
+                    *coordinate = value
+                        .parse()
+                        .map_err(|_| format!("invalid pixel coordinate '{value}' for '{arg}'"))?;
+                }
+                options.debug_pixel = Some(pixel);
+            }
+            "--focus-at" | "--focus-on" if options.focus.is_some() => {
+                return Err("--focus-at and --focus-on exclude each other".to_string())
+            }
+            "--focus-at" => {
+                let mut pixel = [0; 2];
+                for coordinate in &mut pixel {
+                    let value = value(&mut args, &arg)?;
+                    *coordinate = value
+                        .parse()
+                        .map_err(|_| format!("invalid pixel coordinate '{value}' for '{arg}'"))?;
+                }
+                options.focus = Some(Focus::Pixel(pixel));
+            }
+            "--focus-on" => options.focus = Some(Focus::Object(value(&mut args, &arg)?)),
+            "--checkpoint" => options.checkpoint = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--checkpoint-interval" => {
+                let seconds = value(&mut args, &arg)?;
+                let seconds = seconds
+                    .parse()
+                    .map_err(|_| format!("invalid number of seconds '{seconds}' for '{arg}'"))?;
+                options.checkpoint_interval = Some(seconds);
+            }
+            "--resume" => options.resume = true,
+            "--stats-json" => options.stats_json = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--deep" => options.deep = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--cryptomatte" => options.cryptomatte = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--hdr" => options.hdr = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--exr" => options.exr = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--workers" => {
+                let workers = value(&mut args, &arg)?;
+                options.workers = workers.split(',').map(str::to_string).collect();
+                if let Some(worker) = options.workers.iter().find(|worker| !worker.contains(':')) {
+                    return Err(format!("invalid worker '{worker}' for '{arg}', expected host:port"));
+                }
+            }
+            "--serve" => options.serve = Some(value(&mut args, &arg)?),
+            "--listen" => options.listen = Some(value(&mut args, &arg)?),
+            "--time" => options.time = Some(parse_duration(&value(&mut args, &arg)?)?),
+            "--set" => options.overrides.push(value(&mut args, &arg)?.parse()?),
+            "-o" | "--output" => options.output = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--format" => options.format = value(&mut args, &arg)?.parse()?,
+            "--scene" => {
+                let name = value(&mut args, &arg)?;
+                if !examples::names().any(|(example, _)| example == name) {
+                    return Err(format!("unknown scene '{name}', see --list-scenes"));
+                }
+                options.example = Some(name);
+            }
+            "--generate" => {
+                generator.spheres = count(&value(&mut args, &arg)?, &arg)?;
+                generate = true;
+            }
+            "--boxes" => {
+                generator.boxes = count(&value(&mut args, &arg)?, &arg)?;
+                generator_options = true;
+            }
+            "--mix" => {
+                let mix = value(&mut args, &arg)?;
+                let weights: Vec<f64> = mix.split(',').filter_map(|weight| weight.parse().ok()).collect();
+                match weights[..] {
+                    [d, m, g] if d >= 0.0 && m >= 0.0 && g >= 0.0 && d + m + g > 0.0 => {
+                        generator.mix = [d as Float, m as Float, g as Float];
+                    }
+                    _ => {
+                        return Err(format!(
+                            "invalid weights '{mix}' for '{arg}', expected three numbers like 0.8,0.15,0.05"
+                        ))
+                    }
+                }
+                generator_options = true;
+            }
+            "--seed" => {
+                let seed = value(&mut args, &arg)?;
+                generator.seed = seed.parse().map_err(|_| format!("invalid seed '{seed}' for '{arg}'"))?;
+                generator_options = true;
+            }
+            "--batch" => options.batch = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--parallel" => match count(&value(&mut args, &arg)?, &arg)? {
+                0 => return Err(format!("'{arg}' needs at least 1 job")),
+                jobs => options.parallel = Some(jobs),
+            },
+            "--watch" => options.watch = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--frames" | "--frame-range" => {
+                let frames = value(&mut args, &arg)?;
+                let (first, last) = frames.split_once("..").unwrap_or((&frames, &frames));
+                match (first.parse(), last.parse()) {
+                    (Ok(first), Ok(last)) if first <= last => options.frames = Some(first..=last),
+                    _ => {
+                        return Err(format!(
+                            "invalid frames '{frames}' for '{arg}', expected a range like 1..240 or a single frame"
+                        ))
+                    }
+                }
+            }
+            "--frame-step" => match count(&value(&mut args, &arg)?, &arg)? {
+                0 => return Err(format!("'{arg}' needs a step of at least 1 frame")),
+                step => options.frame_step = Some(step),
+            },
+            "--skip-existing" => options.skip_existing = true,
+            "--frame-output" => {
+                let pattern = value(&mut args, &arg)?;
+                if !pattern.contains('#') {
+                    return Err(format!(
+                        "'{pattern}' for '{arg}' needs #s where the frame number goes, e.g. frame_####.ppm"
+                    ));
+                }
+                options.frame_output = Some(pattern);
+            }
+            "--camera-path" => options.camera_path = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--layer" => options.layer = Some(value(&mut args, &arg)?),
+            "--video" => options.video = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--fps" => {
+                let fps = value(&mut args, &arg)?;
+                match fps.parse::<Float>() {
+                    Ok(fps) if fps > 0.0 && fps.is_finite() => options.fps = Some(fps),
+                    _ => return Err(format!("invalid frame rate '{fps}' for '{arg}'")),
+                }
+            }
+            "--golden" => options.golden = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--update-golden" => options.update_golden = true,
+            "--tolerance" => {
+                let tolerance = value(&mut args, &arg)?;
+                let tolerance = tolerance
+                    .parse()
+                    .map_err(|_| format!("invalid tolerance '{tolerance}' for '{arg}'"))?;
+                options.tolerance = Some(tolerance);
+            }
+            flag if flag.starts_with('-') => return Err(format!("unknown option '{flag}'")),
+            _ if options.scene.is_some() => return Err(format!("unexpected argument '{arg}'")),
+            _ => options.scene = Some(PathBuf::from(arg)),
+        }
+    }
+    // The argument of `rustracer batch` is the manifest
+    if batch {
+        if options.batch.is_some() {
+            return Err("'batch' takes the manifest as its argument, without --batch".to_string());
+        }
+        options.batch = Some(options.scene.take().ok_or("'batch' needs a manifest")?);
+    }
+    if options.parallel.is_some() && options.batch.is_none() {
+        return Err("--parallel is for the jobs of a batch".to_string());
+    }
+    if options.sort_rays && !options.wavefront {
+        return Err("--sort-rays only works with --wavefront".to_string());
+    }
+    if (options.resume || options.checkpoint_interval.is_some()) && options.checkpoint.is_none() {
+        return Err("--resume and --checkpoint-interval need a --checkpoint file".to_string());
+    }
+    if options.time.is_some() && (options.wavefront || options.checkpoint.is_some()) {
+        return Err("--time can't be combined with --wavefront or --checkpoint".to_string());
+    }
+    if options.debug_pixel.is_some() && (options.crop.is_some() || options.packets || options.wavefront) {
+        return Err("--debug-pixel can't be combined with --crop, --packets or --wavefront".to_string());
+    }
+    if options.integrator != Integrator::Path && (options.packets || options.wavefront || options.debug_pixel.is_some())
+    {
+        return Err("--integrator can't be combined with --packets, --wavefront or --debug-pixel".to_string());
+    }
+    if options.integrator.counts() && !cfg!(feature = "stats") {
+        return Err("the 'nodes' and 'tests' integrators need a build with the 'stats' feature".to_string());
+    }
+    if options.quiet && options.verbose > 0 {
+        return Err("--quiet and --verbose exclude each other".to_string());
+    }
+    if generator_options && !generate {
+        return Err("--boxes, --mix and --seed need --generate".to_string());
+    }
+    if generate {
+        options.generator = Some(generator);
+    }
+    let scenes = [
+        options.scene.is_some(),
+        options.example.is_some(),
+        options.generator.is_some(),
+    ];
+    if scenes.iter().filter(|&&scene| scene).count() > 1 {
+        return Err("only one of a scene file, --scene and --generate can be given".to_string());
+    }
+    if options.batch.is_some() && scenes.contains(&true) {
+        return Err("a scene cannot be combined with --batch".to_string());
+    }
+    if options.golden.is_some() && (options.batch.is_some() || scenes.contains(&true)) {
+        return Err("--golden cannot be combined with --batch or a scene".to_string());
+    }
+    if options.watch.is_some() && options.scene.is_none() {
+        return Err("--watch needs a scene file".to_string());
+    }
+    if options.watch.is_some()
+        && (options.time.is_some()
+            || options.checkpoint.is_some()
+            || options.debug_pixel.is_some()
+            || options.stats_json.is_some())
+    {
+        return Err("--watch can't be combined with --time, --checkpoint, --debug-pixel or --stats-json".to_string());
+    }
+    if options.frame_output.is_some() && options.frames.is_none() {
+        return Err("--frame-output needs --frames".to_string());
+    }
+    if options.video.is_some() && !cfg!(feature = "video") {
+        return Err("--video needs a build with the 'video' feature".to_string());
+    }
+    if (options.deep.is_some() || options.cryptomatte.is_some() || options.exr.is_some()) && !cfg!(feature = "formats")
+    {
+        return Err("--deep, --cryptomatte and --exr need a build with the 'formats' feature".to_string());
+    }
+    let extension = |path: &PathBuf| path.extension().map(|extension| extension.to_ascii_lowercase());
+    if options.output.iter().chain(&options.watch).any(|path| {
+        extension(path).is_some_and(|extension| ["png", "tif", "tiff", "exr"].iter().any(|name| extension == *name))
+    }) && !cfg!(feature = "formats")
+    {
+        return Err("PNG, TIFF and OpenEXR images need a build with the 'formats' feature".to_string());
+    }
+    if (options.serve.is_some() || !options.workers.is_empty() || options.worker) && !cfg!(feature = "network") {
+        return Err("--serve, --workers and 'worker' need a build with the 'network' feature".to_string());
+    }
+    if options.video.is_some() && (options.frames.is_none() || options.frame_output.is_some()) {
+        return Err("--video needs --frames and can't be combined with --frame-output".to_string());
+    }
+    if (options.frame_step.is_some() || options.skip_existing) && options.frames.is_none() {
+        return Err("--frame-step and --skip-existing need --frames".to_string());
+    }
+    if options.skip_existing && options.video.is_some() {
+        return Err("--skip-existing is for the files of the frames, not for --video".to_string());
+    }
+    if options.fps.is_some() && options.video.is_none() {
+        return Err("--fps needs --video".to_string());
+    }
+    if options.frames.is_some()
+        && (options.batch.is_some()
+            || options.golden.is_some()
+            || options.watch.is_some()
+            || options.checkpoint.is_some()
+            || options.debug_pixel.is_some()
+            || options.stats_json.is_some())
+    {
+        return Err(
+            "--frames can't be combined with --batch, --golden, --watch, --checkpoint, --debug-pixel or --stats-json"
+                .to_string(),
+        );
+    }
+    if options.deep.is_some()
+        && (options.batch.is_some()
+            || options.golden.is_some()
+            || options.watch.is_some()
+            || options.frames.is_some()
+            || options.time.is_some()
+            || options.checkpoint.is_some()
+            || options.debug_pixel.is_some()
+            || options.integrator != Integrator::Path)
+    {
+        return Err(
+            "--deep can't be combined with --batch, --golden, --watch, --frames, --time, --checkpoint, --debug-pixel \
+             or --integrator"
+                .to_string(),
+        );
+    }
+    if options.cryptomatte.is_some()
+        && (options.batch.is_some()
+            || options.golden.is_some()
+            || options.watch.is_some()
+            || options.frames.is_some()
+            || options.debug_pixel.is_some())
+    {
+        return Err(
+            "--cryptomatte can't be combined with --batch, --golden, --watch, --frames or --debug-pixel".to_string(),
+        );
+    }
+    if options.output.is_some()
+        && (options.batch.is_some() || options.golden.is_some() || options.watch.is_some() || options.frames.is_some())
+    {
+        return Err(
+            "--output can't be combined with --batch, --golden, --watch or --frames, they name their own files"
+                .to_string(),
+        );
+    }
+    if options.format != ImageFormat::Ppm
+        && (options.batch.is_some()
+            || options.golden.is_some()
+            || options.watch.is_some()
+            || options.frames.is_some()
+            || options.output.as_ref().is_some_and(|path| path.as_os_str() != "-"))
+    {
+        return Err(
+            "--format is for the image written to stdout, the files of --output, --batch, --watch and --frames go by \
+             their extension, --golden only uses PPM"
+                .to_string(),
+        );
+    }
+    if options.hdr.is_some()
+        && (options.batch.is_some() || options.golden.is_some() || options.watch.is_some() || options.frames.is_some())
+    {
+        return Err("--hdr can't be combined with --batch, --golden, --watch or --frames".to_string());
+    }
+    if options.exr.is_some()
+        && (options.batch.is_some() || options.golden.is_some() || options.watch.is_some() || options.frames.is_some())
+    {
+        return Err("--exr can't be combined with --batch, --golden, --watch or --frames".to_string());
+    }
+    if options.serve.is_some()
+        && (options.batch.is_some()
+            || options.golden.is_some()
+            || options.watch.is_some()
+            || options.frames.is_some()
+            || !options.workers.is_empty())
+    {
+        return Err("--serve can't be combined with --batch, --golden, --watch, --frames or --workers".to_string());
+    }
+    if !options.overrides.is_empty() && (options.batch.is_some() || options.golden.is_some()) {
+        return Err("--set cannot be combined with --batch or --golden".to_string());
+    }
+    if options.camera_path.is_some() && (options.batch.is_some() || options.golden.is_some()) {
+        return Err("--camera-path cannot be combined with --batch or --golden".to_string());
+    }
+    if options.focus.is_some() && (options.batch.is_some() || options.golden.is_some()) {
+        return Err("--focus-at and --focus-on cannot be combined with --batch or --golden".to_string());
+    }
+    if options.layer.is_some()
+        && (options.batch.is_some() || options.golden.is_some() || options.integrator != Integrator::Path)
+    {
+        return Err("--layer cannot be combined with --batch, --golden or --integrator".to_string());
+    }
+    if options.worker != options.listen.is_some() {
+        return Err("'worker' needs a --listen address, and --listen is only for 'worker'".to_string());
+    }
+    if options.worker && (scenes.contains(&true) || !options.workers.is_empty()) {
+        return Err("a worker renders the scene its coordinator sends, it takes no scene or --workers".to_string());
+    }
+    if !options.workers.is_empty()
+        && (options.batch.is_some()
+            || options.golden.is_some()
+            || options.watch.is_some()
+            || options.frames.is_some()
+            || options.time.is_some()
+            || options.checkpoint.is_some()
+            || options.debug_pixel.is_some()
+            || options.deep.is_some()
+            || options.exr.is_some()
+            || options.wavefront)
+    {
+        return Err(
+            "--workers can't be combined with --batch, --golden, --watch, --frames, --time, --checkpoint, \
+             --debug-pixel, --deep, --exr or --wavefront"
+                .to_string(),
+        );
+    }
+    if (options.update_golden || options.tolerance.is_some()) && options.golden.is_none() {
+        return Err("--update-golden and --tolerance need a --golden directory".to_string());
+    }
+    Ok(options)
+}
+
+// Number of objects for `flag`.
+fn count(text: &str, flag: &str) -> Result<usize, String> {
+    text.parse()
+        .map_err(|_| format!("invalid number '{text}' for '{flag}'"))
+}
+
+fn value(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<String, String> {
+    args.next().ok_or_else(|| format!("missing value for '{flag}'"))
+}
+
+// Duration with a unit, `90s`, `2m` or `1h`, plain numbers are seconds.
+fn parse_duration(text: &str) -> Result<Duration, String> {
+    let (number, unit) = match text.find(|c: char| c.is_ascii_alphabetic()) {
+        Some(index) => text.split_at(index),
+        None => (text, "s"),
+    };
+    let seconds_per_unit = match unit {
+        "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        _ => {
+            return Err(format!(
+                "unknown unit '{unit}' in duration '{text}', expected 's', 'm' or 'h'"
+            ))
+        }
+    };
+    match number.parse::<f64>() {
+        Ok(number) if number >= 0.0 && number.is_finite() => Ok(Duration::from_secs_f64(number * seconds_per_unit)),
+        _ => Err(format!("invalid duration '{text}'")),
+    }
+}
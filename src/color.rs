@@ -1,25 +1,179 @@
-use crate::vec3::Vec3;
+use std::{
+    fmt::{Display, Formatter, Result},
+    iter::Sum,
+    ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Sub, SubAssign},
+};
 
-pub type Color = Vec3;
+use crate::{
+    float::Float,
+    util::{random_double, random_double_ranged},
+};
 
-#[inline]
-fn linear_to_gamma(linear_component: f64) -> f64 {
-    linear_component.sqrt()
+// Linear RGB radiance or reflectance. A type of its own rather than a `Vec3`, so colors can't be mixed up with
+// positions and directions; there is no dot product or normalization here, and no arithmetic between the two.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Color {
+    pub r: Float,
+    pub g: Float,
+    pub b: Float,
 }
 
-pub fn write_color(color: Color, samples_per_pixel: i32) {
-    // Average the color by the number of samples
-    let color = color / samples_per_pixel as f64;
-    // Apply the linear to gamma correction
-    let color = Color::new(
-        linear_to_gamma(color.x),
-        linear_to_gamma(color.y),
-        linear_to_gamma(color.z),
-    );
-
-    let ir = (255.0 * color.x.clamp(0.0, 1.0)) as i32;
-    let ig = (255.0 * color.y.clamp(0.0, 1.0)) as i32;
-    let ib = (255.0 * color.z.clamp(0.0, 1.0)) as i32;
-
-    println!("{ir} {ig} {ib}")
+impl Display for Color {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{} {} {}", self.r, self.g, self.b)
+    }
+}
+
+impl Color {
+    pub const BLACK: Color = Color::splat(0.0);
+    pub const WHITE: Color = Color::splat(1.0);
+
+    pub const fn new(r: Float, g: Float, b: Float) -> Color {
+        Color { r, g, b }
+    }
+
+    // Gray with all channels equal to `value`.
+    pub const fn splat(value: Float) -> Color {
+        Color::new(value, value, value)
+    }
+
+    pub fn random() -> Color {
+        Color::new(random_double(), random_double(), random_double())
+    }
+
+    pub fn random_ranged(min: Float, max: Float) -> Color {
+        Color::new(
+            random_double_ranged(min, max),
+            random_double_ranged(min, max),
+            random_double_ranged(min, max),
+        )
+    }
+
+    pub fn map(self, f: impl Fn(Float) -> Float) -> Color {
+        Color::new(f(self.r), f(self.g), f(self.b))
+    }
+
+    pub fn to_array(self) -> [Float; 3] {
+        [self.r, self.g, self.b]
+    }
+
+    // Brightness as perceived by the eye, with the Rec. 709 (sRGB) weights of the channels.
+    pub fn luminance(self) -> Float {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
+    // Mean of the three channels.
+    pub fn average(self) -> Float {
+        (self.r + self.g + self.b) / 3.0
+    }
+
+    pub fn max_component(self) -> Float {
+        self.r.max(self.g).max(self.b)
+    }
+
+    pub fn clamp(self, min: Float, max: Float) -> Color {
+        self.map(|c| c.clamp(min, max))
+    }
+
+    // False if any channel is NaN or infinite, which usually means a division by a zero probability somewhere.
+    pub fn is_finite(self) -> bool {
+        self.r.is_finite() && self.g.is_finite() && self.b.is_finite()
+    }
+
+    // Encode with the sRGB transfer curve, for displaying or storing in 8 bit formats.
+    pub fn linear_to_srgb(self) -> Color {
+        self.map(|c| {
+            if c <= 0.0031308 {
+                12.92 * c
+            } else {
+                1.055 * c.powf(1.0 / 2.4) - 0.055
+            }
+        })
+    }
+
+    // Decode sRGB encoded values, e.g. colors picked in an image editor, to linear RGB.
+    pub fn srgb_to_linear(self) -> Color {
+        self.map(|c| {
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        })
+    }
+
+    // Color of a black body at `temperature` in Kelvin from Planck's law at one wavelength per channel, scaled so the
+    // brightest channel is 1 like PBRT normalizes its black body spectra.
+    pub fn blackbody(temperature: Float) -> Color {
+        let radiance = |wavelength: Float| {
+            let wavelength = wavelength * 1e-9;
+            let exponent = 1.4387769e-2 / (wavelength * temperature.max(1.0));
+            1.0 / (wavelength.powi(5) * exponent.exp_m1())
+        };
+        let color = Color::new(radiance(610.0), radiance(550.0), radiance(465.0));
+        let max = color.max_component();
+        if max > 0.0 && max.is_finite() {
+            color / max
+        } else {
+            Color::BLACK
+        }
+    }
+}
+
+// Channel-wise arithmetic between colors, and scaling by a factor.
+macro_rules! impl_color_operation {
+    ($Operation:ident $op_fn:ident $OperationAssign:ident $op_assign_fn:ident $op_symbol:tt) => {
+        impl $Operation for Color {
+            type Output = Color;
+
+            #[inline]
+            fn $op_fn(self, other: Color) -> Color {
+                Color::new(self.r $op_symbol other.r, self.g $op_symbol other.g, self.b $op_symbol other.b)
+            }
+        }
+
+        impl $Operation<Float> for Color {
+            type Output = Color;
+
+            #[inline]
+            fn $op_fn(self, factor: Float) -> Color {
+                Color::new(self.r $op_symbol factor, self.g $op_symbol factor, self.b $op_symbol factor)
+            }
+        }
+
+        impl $OperationAssign for Color {
+            #[inline]
+            fn $op_assign_fn(&mut self, other: Color) {
+                *self = *self $op_symbol other;
+            }
+        }
+
+        impl $OperationAssign<Float> for Color {
+            #[inline]
+            fn $op_assign_fn(&mut self, factor: Float) {
+                *self = *self $op_symbol factor;
+            }
+        }
+    };
+}
+
+impl_color_operation!(Add add AddAssign add_assign +);
+impl_color_operation!(Sub sub SubAssign sub_assign -);
+impl_color_operation!(Mul mul MulAssign mul_assign *);
+impl_color_operation!(Div div DivAssign div_assign /);
+
+impl Mul<Color> for Float {
+    type Output = Color;
+
+    #[inline]
+    fn mul(self, color: Color) -> Color {
+        color * self
+    }
+}
+
+impl Sum for Color {
+    fn sum<I: Iterator<Item = Color>>(iter: I) -> Color {
+        iter.fold(Color::BLACK, |acc, color| acc + color)
+    }
 }
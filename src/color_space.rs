@@ -0,0 +1,83 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+use crate::{color::Color, float::Float};
+
+// Color space the image is written out in. The renderer works in linear scene-referred values with the primaries of
+// sRGB (Rec.709) throughout and only converts the colors when they are written: sRGB keeps them and gamma corrects
+// them for display like the renderer always did, Rec.2020 maps them onto its wider primaries with its transfer
+// function for wide gamut displays, and ACEScg onto the primaries of the ACES working space, kept linear for
+// compositing. OCIO configurations are not read, the conversions are built in.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ColorSpace {
+    #[default]
+    Srgb,
+    Rec2020,
+    AcesCg,
+}
+
+impl FromStr for ColorSpace {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<ColorSpace, String> {
+        match s {
+            "srgb" => Ok(ColorSpace::Srgb),
+            "rec2020" => Ok(ColorSpace::Rec2020),
+            "acescg" => Ok(ColorSpace::AcesCg),
+            _ => Err(format!(
+                "unknown color space '{s}', expected 'srgb', 'rec2020' or 'acescg'"
+            )),
+        }
+    }
+}
+
+impl Display for ColorSpace {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ColorSpace::Srgb => "srgb",
+            ColorSpace::Rec2020 => "rec2020",
+            ColorSpace::AcesCg => "acescg",
+        })
+    }
+}
+
+// Linear Rec.709 to linear Rec.2020, both with the D65 white point (ITU-R BT.2087).
+const REC709_TO_REC2020: [[Float; 3]; 3] = [
+    [0.6274039, 0.329283, 0.0433131],
+    [0.0690973, 0.9195404, 0.0113623],
+    [0.0163914, 0.0880133, 0.8955953],
+];
+
+// Linear Rec.709 to ACEScg (AP1 primaries), adapted from D65 to the ACES white point with the Bradford transform.
+const REC709_TO_ACESCG: [[Float; 3]; 3] = [
+    [0.6130974, 0.3395231, 0.0473795],
+    [0.0701937, 0.9163539, 0.0134524],
+    [0.0206156, 0.1095698, 0.8698147],
+];
+
+impl ColorSpace {
+    // Values of the linear color in the color space, gamma encoded unless the space is linear. They are not clamped.
+    pub fn encode(self, color: Color) -> [Float; 3] {
+        let convert =
+            |matrix: [[Float; 3]; 3]| matrix.map(|row| row[0] * color.r + row[1] * color.g + row[2] * color.b);
+        match self {
+            ColorSpace::Srgb => color.to_array().map(|value| value.max(0.0).sqrt()),
+            ColorSpace::Rec2020 => convert(REC709_TO_REC2020).map(rec2020_transfer),
+            ColorSpace::AcesCg => convert(REC709_TO_ACESCG),
+        }
+    }
+}
+
+// Transfer function of Rec.2020 (ITU-R BT.2020), linear near black and a power of 0.45 above.
+fn rec2020_transfer(value: Float) -> Float {
+    const ALPHA: Float = 1.0992968;
+    const BETA: Float = 0.018054;
+    if value < BETA {
+        4.5 * value.max(0.0)
+    } else {
+        ALPHA * value.powf(0.45) - (ALPHA - 1.0)
+    }
+}
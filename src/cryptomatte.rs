@@ -0,0 +1,126 @@
+use std::io::{self, Write};
+
+use crate::{
+    exr,
+    float::Float,
+    id::{murmur3, name_id, Names},
+    output::Framebuffer,
+};
+
+// IDs kept per pixel and layer, the ones that cover most of it. Every EXR layer holds two of them.
+const RANKS: usize = 6;
+
+// Coverage of the IDs of the named objects and materials in every pixel, for the Cryptomatte mattes of compositing
+// packages: instead of a single ID per pixel, the ones that share it at the edges of objects keep their part of it.
+pub struct Cryptomatte {
+    width: usize,
+    height: usize,
+    objects: Vec<Vec<(u32, Float)>>, // IDs and coverage in scanline order, most covering first
+    materials: Vec<Vec<(u32, Float)>>,
+}
+
+impl Cryptomatte {
+    // Mattes of the given size that cover nothing.
+    pub fn new(width: usize, height: usize) -> Cryptomatte {
+        Cryptomatte {
+            width,
+            height,
+            objects: vec![vec![]; width * height],
+            materials: vec![vec![]; width * height],
+        }
+    }
+
+    // Set the pixel at x, y from the object and material IDs of the first hits of all its camera samples, 0 for
+    // samples that hit nothing or nothing named.
+    pub fn set(&mut self, x: usize, y: usize, samples: &[[u32; 2]]) {
+        let index = y * self.width + x;
+        self.objects[index] = coverage(samples.iter().map(|ids| ids[0]), samples.len());
+        self.materials[index] = coverage(samples.iter().map(|ids| ids[1]), samples.len());
+    }
+}
+
+// Share of the samples every ID other than 0 has, for the IDs with the largest shares.
+fn coverage(ids: impl Iterator<Item = u32>, count: usize) -> Vec<(u32, Float)> {
+    let mut counts: Vec<(u32, usize)> = vec![];
+    for id in ids.filter(|&id| id != 0) {
+        match counts.iter_mut().find(|(other, _)| *other == id) {
+            Some((_, n)) => *n += 1,
+            None => counts.push((id, 1)),
+        }
+    }
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    counts.truncate(RANKS);
+    counts
+        .into_iter()
+        .map(|(id, n)| (id, n as Float / count as Float))
+        .collect()
+}
+
+// Write the image and its mattes as an uncompressed OpenEXR file with Cryptomatte layers: R, G and B of the linear
+// image, then CryptoObject00 to 02 and CryptoMaterial00 to 02 with two ranks of ID and coverage each. The IDs are
+// stored as the bits of the floats, and the manifest in the metadata maps the names of the scene to them.
+pub fn write_cryptomatte_exr(
+    image: &Framebuffer,
+    mattes: &Cryptomatte,
+    names: &Names,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let (width, height) = (mattes.width, mattes.height);
+    let mut channels = vec![
+        (
+            "R".to_string(),
+            image.pixels().iter().map(|color| color.r as f32).collect(),
+        ),
+        (
+            "G".to_string(),
+            image.pixels().iter().map(|color| color.g as f32).collect(),
+        ),
+        (
+            "B".to_string(),
+            image.pixels().iter().map(|color| color.b as f32).collect(),
+        ),
+    ];
+    let mut attributes = vec![];
+    for (layer, pixels, names) in [
+        ("CryptoObject", &mattes.objects, &names.objects),
+        ("CryptoMaterial", &mattes.materials, &names.materials),
+    ] {
+        for rank in 0..RANKS {
+            let id = |pixel: &Vec<(u32, Float)>| pixel.get(rank).map_or(0.0, |&(id, _)| f32::from_bits(id));
+            let share = |pixel: &Vec<(u32, Float)>| pixel.get(rank).map_or(0.0, |&(_, share)| share as f32);
+            let [id_channel, share_channel] = if rank % 2 == 0 { ["R", "G"] } else { ["B", "A"] };
+            let prefix = format!("{layer}{:02}", rank / 2);
+            channels.push((format!("{prefix}.{id_channel}"), pixels.iter().map(id).collect()));
+            channels.push((format!("{prefix}.{share_channel}"), pixels.iter().map(share).collect()));
+        }
+        // Layers are told apart by the first 7 hex digits of the hash of their name
+        let key = &format!("{:08x}", murmur3(layer.as_bytes()))[..7];
+        let manifest = names
+            .iter()
+            .map(|name| format!("\"{}\":\"{:08x}\"", json_escape(name), name_id(name)))
+            .collect::<Vec<_>>()
+            .join(",");
+        for (field, value) in [
+            ("name", layer.to_string()),
+            ("hash", "MurmurHash3_32".to_string()),
+            ("conversion", "uint32_to_float32".to_string()),
+            ("manifest", format!("{{{manifest}}}")),
+        ] {
+            attributes.push((format!("cryptomatte/{key}/{field}"), "string", value.into_bytes()));
+        }
+    }
+    exr::write_image(width, height, channels, attributes, out)
+}
+
+fn json_escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,379 @@
+use std::{ops::Range, str::FromStr, sync::Arc};
+
+use crate::float::consts::PI;
+
+use crate::{
+    aabb::Aabb,
+    accel::{Accelerator, SpatialIndex},
+    float::Float,
+    hittable::{Hit, Hittable},
+    material::Material,
+    onb::Onb,
+    ray::Ray,
+    vec3::{Point, Vec3},
+};
+
+// Every cubic segment of a strand is split into this many pieces for the spatial index, so the boxes around them fit
+// the curve closely.
+const PIECES_PER_SEGMENT: usize = 8;
+
+// Deepest subdivision of a piece when intersecting it.
+const MAX_DEPTH: i32 = 10;
+
+// How a curve is drawn, both are flat ribbons that always face the ray.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CurveShape {
+    Flat,     // a ribbon shaded like a flat strip
+    Cylinder, // a ribbon shaded like a tube, with the normal turning around the curve across its width
+}
+
+impl FromStr for CurveShape {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<CurveShape, String> {
+        match s {
+            "flat" => Ok(CurveShape::Flat),
+            "cylinder" => Ok(CurveShape::Cylinder),
+            _ => Err(format!("unknown curve shape '{s}', expected 'flat' or 'cylinder'")),
+        }
+    }
+}
+
+// Strand of hair or fur, a chain of cubic Bézier segments: the points are the control points of the segments, each
+// segment starts at the last point of the one before, so a strand of n segments has 3n + 1 points.
+#[derive(Clone, Debug)]
+pub struct Strand {
+    pub points: Vec<Point>,
+    pub widths: [Float; 2], // width at the root (the first point) and at the tip, in between it narrows linearly
+}
+
+// Piece of a segment with its own control points.
+struct Piece {
+    points: [Point; 4],
+    widths: [Float; 2], // width at both ends
+    u: [Float; 2],      // u coordinate along the strand at both ends
+}
+
+// Strands of hair or fur, intersected as in PBRT: the curve is subdivided until its pieces are nearly straight, and
+// these are tested like thick lines in a space where the ray runs along the z axis.
+pub struct Curves {
+    pieces: Vec<Piece>,
+    shape: CurveShape,
+    material: Arc<dyn Material>,
+    index: SpatialIndex, // acceleration structure over the pieces
+}
+
+impl Curves {
+    pub fn new(
+        strands: Vec<Strand>,
+        shape: CurveShape,
+        material: Arc<dyn Material>,
+        accelerator: Accelerator,
+    ) -> Curves {
+        let mut pieces = vec![];
+        for strand in strands {
+            assert!(
+                strand.points.len() >= 4 && (strand.points.len() - 1) % 3 == 0,
+                "a strand has 3n + 1 control points"
+            );
+            let segments = (strand.points.len() - 1) / 3;
+            let total = (segments * PIECES_PER_SEGMENT) as Float;
+            for (segment, points) in strand.points.windows(4).step_by(3).enumerate() {
+                let points = [points[0], points[1], points[2], points[3]];
+                for piece in 0..PIECES_PER_SEGMENT {
+                    let range = [piece, piece + 1].map(|end| end as Float / PIECES_PER_SEGMENT as Float);
+                    let u = [piece, piece + 1].map(|end| (segment * PIECES_PER_SEGMENT + end) as Float / total);
+                    let [root, tip] = strand.widths;
+                    pieces.push(Piece {
+                        points: sub_curve(points, range),
+                        widths: u.map(|u| root + (tip - root) * u),
+                        u,
+                    });
+                }
+            }
+        }
+        let bounds: Vec<Aabb> = pieces.iter().map(Piece::bounds).collect();
+        Curves {
+            index: SpatialIndex::new(&bounds, accelerator),
+            pieces,
+            shape,
+            material,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pieces.len() / PIECES_PER_SEGMENT
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pieces.is_empty()
+    }
+
+    fn piece_hit(&self, index: usize, ray: Ray, found: PieceHit) -> Option<Hit<'_>> {
+        let piece = &self.pieces[index];
+        let (_, dpdu) = eval_bezier(piece.points.map(Point::to_vec), found.s);
+        // Across the curve, facing the ray
+        let frame = ray_frame(ray, piece.points);
+        let dpdu_plane = frame.world_to_local(dpdu);
+        let mut dpdv_plane = Vec3::new(-dpdu_plane.y, dpdu_plane.x, 0.0).normalize();
+        if self.shape == CurveShape::Cylinder {
+            // Turn the normal around the curve from one edge to the other
+            let theta = (found.v - 0.5) * PI;
+            dpdv_plane = rotate(dpdv_plane, dpdu_plane.normalize(), -theta);
+        }
+        let dpdv = frame.local(dpdv_plane);
+        let normal = Vec3::cross(dpdu, dpdv).normalize();
+        if !normal.length_squared().is_finite() || normal.near_zero() {
+            return None;
+        }
+        let hit = Hit::new(ray, found.t, normal, self.material.as_ref());
+        // Across the curve, v grows towards the cross product of the normal and the tangent, on whichever side the
+        // normal ends up
+        let v = if hit.front_face { found.v } else { 1.0 - found.v };
+        let u = piece.u[0] + (piece.u[1] - piece.u[0]) * found.s;
+        Some(hit.with_uv([u, v]).with_tangent(dpdu.normalize()))
+    }
+}
+
+impl Hittable for Curves {
+    fn hit(&self, ray: Ray, t_range: Range<Float>) -> Option<Hit<'_>> {
+        let mut closest: Option<(usize, PieceHit)> = None;
+        self.index.traverse(ray, t_range, |index, t_range| {
+            let found = self.pieces[index].intersect(ray, &t_range, false)?;
+            closest = Some((index, found));
+            Some(found.t)
+        });
+
+        let (index, found) = closest?;
+        self.piece_hit(index, ray, found)
+    }
+
+    fn hit_any(&self, ray: Ray, t_range: Range<Float>) -> bool {
+        self.index.traverse_any(ray, t_range, |index, t_range| {
+            self.pieces[index].intersect(ray, &t_range, true).is_some()
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.index.bounds()
+    }
+}
+
+// Where a ray hits a piece.
+#[derive(Copy, Clone)]
+struct PieceHit {
+    t: Float,
+    s: Float, // position along the piece, from 0 to 1
+    v: Float, // position across the curve, from 0 to 1
+}
+
+impl Piece {
+    fn bounds(&self) -> Aabb {
+        // The curve stays inside the hull of its control points
+        let half = 0.5 * self.widths[0].max(self.widths[1]);
+        let bounds = Aabb::from_points(self.points);
+        Aabb::new(
+            bounds.min - Vec3::new(half, half, half),
+            bounds.max + Vec3::new(half, half, half),
+        )
+    }
+
+    // Closest hit within `t_range`, or any hit if `any`.
+    fn intersect(&self, ray: Ray, t_range: &Range<Float>, any: bool) -> Option<PieceHit> {
+        let frame = ray_frame(ray, self.points);
+        let cp = self.points.map(|point| frame.world_to_local(point - ray.origin));
+        let length = ray.direction.length();
+        let z_max = t_range.end * length;
+        let half = 0.5 * self.widths[0].max(self.widths[1]);
+        if outside(cp, half, z_max) {
+            return None;
+        }
+
+        // Subdivide until the pieces are about as straight as a fraction of the width
+        let mut l0: Float = 0.0;
+        for i in 0..2 {
+            let second = cp[i] - 2.0 * cp[i + 1] + cp[i + 2];
+            l0 = l0.max(second.x.abs()).max(second.y.abs()).max(second.z.abs());
+        }
+        let eps = 0.05 * self.widths[0].max(self.widths[1]);
+        let depth = match eps > 0.0 {
+            true => ((2.0 as Float).sqrt() * 6.0 * l0 / (8.0 * eps)).log2() / 2.0,
+            false => 0.0,
+        };
+        let depth = (depth.round() as i32).clamp(0, MAX_DEPTH);
+        let mut search = Search {
+            widths: self.widths,
+            length,
+            t_range: t_range.clone(),
+            any,
+            found: None,
+        };
+        search.recurse(cp, 0.0, 1.0, depth);
+        search.found
+    }
+}
+
+// Recursive subdivision of a piece in ray space, keeping the closest hit.
+struct Search {
+    widths: [Float; 2],
+    length: Float, // length of the ray direction, distances in ray space are t times it
+    t_range: Range<Float>,
+    any: bool,
+    found: Option<PieceHit>,
+}
+
+impl Search {
+    fn recurse(&mut self, cp: [Vec3; 4], s0: Float, s1: Float, depth: i32) {
+        if self.any && self.found.is_some() {
+            return;
+        }
+        if depth > 0 {
+            let split = subdivide_bezier(cp);
+            let s = [s0, 0.5 * (s0 + s1), s1];
+            for half in 0..2 {
+                let cp = [
+                    split[3 * half],
+                    split[3 * half + 1],
+                    split[3 * half + 2],
+                    split[3 * half + 3],
+                ];
+                let width = self.width(s[half]).max(self.width(s[half + 1]));
+                if !outside(cp, 0.5 * width, self.t_range.end * self.length) {
+                    self.recurse(cp, s[half], s[half + 1], depth - 1);
+                }
+            }
+            return;
+        }
+
+        // Nearly straight: the ray (at the origin) must be beyond the line through each end, perpendicular to the
+        // end's tangent, towards the other end
+        let edge = (cp[1].y - cp[0].y) * -cp[0].y + cp[0].x * (cp[0].x - cp[1].x);
+        if edge < 0.0 {
+            return;
+        }
+        let edge = (cp[2].y - cp[3].y) * -cp[3].y + cp[3].x * (cp[3].x - cp[2].x);
+        if edge < 0.0 {
+            return;
+        }
+        // Closest point of the line between the ends
+        let direction = Vec3::new(cp[3].x - cp[0].x, cp[3].y - cp[0].y, 0.0);
+        let denominator = direction.length_squared();
+        if denominator == 0.0 {
+            return;
+        }
+        let w = Vec3::dot(-Vec3::new(cp[0].x, cp[0].y, 0.0), direction) / denominator;
+        let s = (s0 + (s1 - s0) * w).clamp(s0, s1);
+        let width = self.width(s);
+        let (point, tangent) = eval_bezier(cp, w.clamp(0.0, 1.0));
+        let distance2 = point.x * point.x + point.y * point.y;
+        if distance2 > 0.25 * width * width {
+            return;
+        }
+        let t = point.z / self.length;
+        let closest = self.found.map_or(self.t_range.end, |found| found.t);
+        if !(self.t_range.start < t && t < closest) {
+            return;
+        }
+        // Side of the curve the ray passes on
+        let edge = tangent.x * -point.y + point.x * tangent.y;
+        let offset = distance2.sqrt() / width;
+        let v = if edge > 0.0 { 0.5 + offset } else { 0.5 - offset };
+        self.found = Some(PieceHit { t, s, v });
+    }
+
+    fn width(&self, s: Float) -> Float {
+        self.widths[0] + (self.widths[1] - self.widths[0]) * s
+    }
+}
+
+// Whether the curve with the control points `cp` in ray space, thickened by `half` a width, misses the ray up to
+// `z_max`.
+fn outside(cp: [Vec3; 4], half: Float, z_max: Float) -> bool {
+    let min = cp
+        .iter()
+        .fold(Vec3::new(Float::MAX, Float::MAX, Float::MAX), |acc, &p| acc.min(p));
+    let max = cp
+        .iter()
+        .fold(-Vec3::new(Float::MAX, Float::MAX, Float::MAX), |acc, &p| acc.max(p));
+    max.x + half < 0.0
+        || min.x - half > 0.0
+        || max.y + half < 0.0
+        || min.y - half > 0.0
+        || max.z + half < 0.0
+        || min.z - half > z_max
+}
+
+// Frame with the ray direction as z, and x along the curve as far as possible so the boxes around its pieces are
+// thin.
+fn ray_frame(ray: Ray, points: [Point; 4]) -> Onb {
+    let direction = ray.direction.normalize();
+    let up = Vec3::cross(direction, points[3] - points[0]);
+    if up.length_squared() == 0.0 {
+        return Onb::new(direction);
+    }
+    let right = Vec3::cross(up.normalize(), direction).normalize();
+    Onb {
+        u: right,
+        v: Vec3::cross(direction, right),
+        w: direction,
+    }
+}
+
+// Point and derivative of a cubic Bézier curve at `s`.
+fn eval_bezier(cp: [Vec3; 4], s: Float) -> (Vec3, Vec3) {
+    let a = [0, 1, 2].map(|i| Vec3::lerp(cp[i], cp[i + 1], s));
+    let b = [0, 1].map(|i| Vec3::lerp(a[i], a[i + 1], s));
+    let derivative = match (b[1] - b[0]).length_squared() > 0.0 {
+        true => 3.0 * (b[1] - b[0]),
+        false => cp[3] - cp[0],
+    };
+    (Vec3::lerp(b[0], b[1], s), derivative)
+}
+
+// Control points of the two halves of a cubic Bézier curve, the middle one is shared.
+fn subdivide_bezier(cp: [Vec3; 4]) -> [Vec3; 7] {
+    [
+        cp[0],
+        (cp[0] + cp[1]) / 2.0,
+        (cp[0] + 2.0 * cp[1] + cp[2]) / 4.0,
+        (cp[0] + 3.0 * cp[1] + 3.0 * cp[2] + cp[3]) / 8.0,
+        (cp[1] + 2.0 * cp[2] + cp[3]) / 4.0,
+        (cp[2] + cp[3]) / 2.0,
+        cp[3],
+    ]
+}
+
+// Control points of the part of a cubic Bézier curve between `range[0]` and `range[1]`, by blossoming.
+fn sub_curve(points: [Point; 4], [s0, s1]: [Float; 2]) -> [Point; 4] {
+    let cp = points.map(Point::to_vec);
+    let blossom = |s: [Float; 3]| {
+        let a = [0, 1, 2].map(|i| Vec3::lerp(cp[i], cp[i + 1], s[0]));
+        let b = [0, 1].map(|i| Vec3::lerp(a[i], a[i + 1], s[1]));
+        Vec3::lerp(b[0], b[1], s[2]).to_point()
+    };
+    [
+        blossom([s0, s0, s0]),
+        blossom([s0, s0, s1]),
+        blossom([s0, s1, s1]),
+        blossom([s1, s1, s1]),
+    ]
+}
+
+// Rotation of `v` by `angle` radians around the unit vector `axis`.
+fn rotate(v: Vec3, axis: Vec3, angle: Float) -> Vec3 {
+    let (sin, cos) = angle.sin_cos();
+    v * cos + Vec3::cross(axis, v) * sin + axis * Vec3::dot(axis, v) * (1.0 - cos)
+}
+
+// Strands through the points of polylines, smoothed into Bézier segments with Catmull-Rom tangents.
+pub fn smooth_polyline(points: &[Point]) -> Vec<Point> {
+    let at = |i: isize| points[i.clamp(0, points.len() as isize - 1) as usize];
+    let mut control = vec![points[0]];
+    for i in 0..points.len() as isize - 1 {
+        let (before, start, end, after) = (at(i - 1), at(i), at(i + 1), at(i + 2));
+        control.push(start + (end - before) / 6.0);
+        control.push(end - (after - start) / 6.0);
+        control.push(end);
+    }
+    control
+}
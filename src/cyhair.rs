@@ -0,0 +1,106 @@
+use std::{fs, io, path::Path};
+
+use crate::{
+    curve::{smooth_polyline, Strand},
+    error::{Error, Result},
+    float::Float,
+    vec3::Point,
+};
+
+// Hair in the binary HAIR format of Cem Yuksel's cyHair, in which many groom and fur assets are published. The file
+// stores every strand as a polyline, which is smoothed into Bézier segments here. The thickness of the strands, if the
+// file has it, gives their widths at the root and at the tip; their transparency and colors are ignored.
+pub struct HairModel {
+    pub strands: Vec<Strand>,
+}
+
+// Flags of the arrays a file has, after the header in this order.
+const HAS_SEGMENTS: u32 = 1;
+const HAS_POINTS: u32 = 2;
+const HAS_THICKNESS: u32 = 4;
+const HAS_TRANSPARENCY: u32 = 8;
+const HAS_COLOR: u32 = 16;
+
+const HEADER_SIZE: usize = 128;
+
+impl HairModel {
+    pub fn load(path: &Path) -> Result<HairModel> {
+        HairModel::parse(&fs::read(path)?)
+    }
+
+    pub fn parse(data: &[u8]) -> Result<HairModel> {
+        let invalid = |message: &str| Error::Io(io::Error::new(io::ErrorKind::InvalidData, message.to_string()));
+        if data.len() < HEADER_SIZE || !data.starts_with(b"HAIR") {
+            return Err(invalid("not a HAIR file"));
+        }
+        let word = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().expect("4 bytes"));
+        let strand_count = word(4) as usize;
+        let point_count = word(8) as usize;
+        let flags = word(12);
+        let default_segments = word(16) as usize;
+        let default_thickness = f32::from_le_bytes(data[20..24].try_into().expect("4 bytes")) as Float;
+        if flags & HAS_POINTS == 0 {
+            return Err(invalid("HAIR file without points"));
+        }
+
+        let mut offset = HEADER_SIZE;
+        let mut array = |size: usize| -> Result<&[u8]> {
+            let array = offset
+                .checked_add(size)
+                .and_then(|end| data.get(offset..end))
+                .ok_or_else(|| invalid("truncated HAIR file"))?;
+            offset += size;
+            Ok(array)
+        };
+        let segments: Vec<usize> = match flags & HAS_SEGMENTS {
+            0 => vec![default_segments; strand_count],
+            _ => array(strand_count.saturating_mul(2))?
+                .chunks_exact(2)
+                .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+                .collect(),
+        };
+        let floats = |bytes: &[u8]| -> Vec<Float> {
+            bytes
+                .chunks_exact(4)
+                .map(|bytes| f32::from_le_bytes(bytes.try_into().expect("4 bytes")) as Float)
+                .collect()
+        };
+        let coordinates = floats(array(point_count.saturating_mul(12))?);
+        let thickness = match flags & HAS_THICKNESS {
+            0 => vec![],
+            _ => floats(array(point_count.saturating_mul(4))?),
+        };
+        // The arrays after these aren't needed, but must be there
+        if flags & HAS_TRANSPARENCY != 0 {
+            array(point_count.saturating_mul(4))?;
+        }
+        if flags & HAS_COLOR != 0 {
+            array(point_count.saturating_mul(12))?;
+        }
+
+        let mut strands = Vec::with_capacity(strand_count);
+        let mut first = 0;
+        for segments in segments {
+            let end = first + segments + 1;
+            if end > point_count {
+                return Err(invalid("HAIR file has fewer points than its strands"));
+            }
+            let points: Vec<Point> = (first..end)
+                .map(|i| Point::new(coordinates[3 * i], coordinates[3 * i + 1], coordinates[3 * i + 2]))
+                .collect();
+            let widths = match thickness.is_empty() {
+                true => [default_thickness; 2],
+                false => [thickness[first], thickness[end - 1]],
+            };
+            first = end;
+            // Strands of a single point have no length to draw
+            if segments > 0 {
+                strands.push(Strand {
+                    points: smooth_polyline(&points),
+                    widths,
+                });
+            }
+        }
+        Ok(HairModel { strands })
+    }
+}
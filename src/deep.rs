@@ -0,0 +1,150 @@
+use std::io::{self, Write};
+
+use crate::{color::Color, exr, float::Float, output::Framebuffer};
+
+// Closest relative difference of the depths of two samples that are kept apart, closer ones are merged into one.
+const DEPTH_TOLERANCE: Float = 1e-3;
+
+// Part of a deep pixel: what the camera sees at one depth, with the color premultiplied by the alpha.
+#[derive(Copy, Clone, Debug)]
+pub struct DeepSample {
+    pub depth: Float, // distance from the camera along its viewing direction
+    pub color: Color,
+    pub alpha: Float, // how much of what lies behind the sample it covers
+}
+
+// Image of deep pixels: every pixel keeps the surfaces (and the points in volumes) its camera samples hit, sorted from
+// front to back, instead of a single color. Compositing packages can put other renders in between or hold them out
+// by depth. Camera samples that hit nothing leave the pixel transparent.
+pub struct DeepImage {
+    width: usize,
+    height: usize,
+    pixels: Vec<Vec<DeepSample>>, // in scanline order from the top left
+}
+
+impl DeepImage {
+    // Transparent image of the given size.
+    pub fn new(width: usize, height: usize) -> DeepImage {
+        DeepImage {
+            width,
+            height,
+            pixels: vec![vec![]; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> &[DeepSample] {
+        &self.pixels[y * self.width + x]
+    }
+
+    // Set the pixel at x, y from all its camera samples, the depth of the first hit and the light along the path of
+    // every sample, None for the ones that hit nothing. Samples at nearly the same depth become one deep sample, with
+    // the coverage of their share of all the samples of the pixel. Laid over each other from back to front, the deep
+    // samples add up to the average of the camera samples.
+    pub fn set(&mut self, x: usize, y: usize, samples: &mut [(Option<Float>, Color)]) {
+        let count = samples.len();
+        samples.sort_by(|(a, _), (b, _)| a.unwrap_or(Float::INFINITY).total_cmp(&b.unwrap_or(Float::INFINITY)));
+        let mut pixel = vec![];
+        let mut behind = count; // samples not covered by the deep samples in front
+        let mut rest = &samples[..];
+        while let Some(&(Some(depth), _)) = rest.first() {
+            let length = rest
+                .iter()
+                .position(|(other, _)| other.is_none_or(|other| other - depth > DEPTH_TOLERANCE * depth.abs()))
+                .unwrap_or(rest.len());
+            let (group, others) = rest.split_at(length);
+            let sum = group.iter().fold(Color::BLACK, |sum, &(_, color)| sum + color);
+            pixel.push(DeepSample {
+                depth,
+                color: sum / behind as Float,
+                alpha: group.len() as Float / behind as Float,
+            });
+            behind -= group.len();
+            rest = others;
+        }
+        self.pixels[y * self.width + x] = pixel;
+    }
+
+    // The image the deep pixels make when they are laid over each other from back to front on black.
+    pub fn flatten(&self) -> Framebuffer {
+        let mut image = Framebuffer::new(self.width, self.height);
+        for (index, pixel) in self.pixels.iter().enumerate() {
+            let color = pixel.iter().rev().fold(Color::BLACK, |behind, sample| {
+                sample.color + (1.0 - sample.alpha) * behind
+            });
+            image.set(index % self.width, index / self.width, color);
+        }
+        image
+    }
+}
+
+// Write the image as an uncompressed deep scanline OpenEXR file, with the channels R, G, B and A of the premultiplied
+// colors and Z of the depths as 32 bit floats, one scanline per chunk.
+pub fn write_deep_exr(image: &DeepImage, out: &mut dyn Write) -> io::Result<()> {
+    let max_samples = image.pixels.iter().map(Vec::len).max().unwrap_or(0);
+    let attributes = vec![
+        ("type".to_string(), "string", b"deepscanline".to_vec()),
+        ("version".to_string(), "int", 1_i32.to_le_bytes().to_vec()),
+        (
+            "maxSamplesPerPixel".to_string(),
+            "int",
+            (max_samples as i32).to_le_bytes().to_vec(),
+        ),
+        (
+            "chunkCount".to_string(),
+            "int",
+            (image.height as i32).to_le_bytes().to_vec(),
+        ),
+    ];
+    // The channels are listed in alphabetical order, the sample data follows the same order
+    let header = exr::header(
+        exr::DEEP,
+        &["A", "B", "G", "R", "Z"],
+        image.width,
+        image.height,
+        attributes,
+    );
+    let chunks: Vec<Vec<u8>> = (0..image.height).map(|y| deep_scanline(image, y)).collect();
+    exr::write(&header, &chunks, out)
+}
+
+// Chunk of the scanline y: its number, the sizes of the table of sample counts and of the samples, the table with the
+// running total of the samples of the pixels, and the samples of every channel in turn.
+fn deep_scanline(image: &DeepImage, y: usize) -> Vec<u8> {
+    let pixels = &image.pixels[y * image.width..(y + 1) * image.width];
+    let mut table = vec![];
+    let mut total = 0;
+    for pixel in pixels {
+        total += pixel.len() as i32;
+        table.extend(total.to_le_bytes());
+    }
+    let mut samples = vec![];
+    let channels: [fn(&DeepSample) -> Float; 5] = [
+        |sample| sample.alpha,
+        |sample| sample.color.b,
+        |sample| sample.color.g,
+        |sample| sample.color.r,
+        |sample| sample.depth,
+    ];
+    for channel in channels {
+        for sample in pixels.iter().flatten() {
+            samples.extend((channel(sample) as f32).to_le_bytes());
+        }
+    }
+    let mut chunk = vec![];
+    chunk.extend((y as i32).to_le_bytes());
+    chunk.extend((table.len() as u64).to_le_bytes());
+    // Packed and unpacked size of the samples, the same without compression
+    chunk.extend((samples.len() as u64).to_le_bytes());
+    chunk.extend((samples.len() as u64).to_le_bytes());
+    chunk.extend(table);
+    chunk.extend(samples);
+    chunk
+}
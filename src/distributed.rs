@@ -0,0 +1,503 @@
+use std::{
+    collections::VecDeque,
+    env,
+    io::{self, BufReader, BufWriter, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    ops::Range,
+    path::PathBuf,
+    sync::{
+        atomic::Ordering,
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use rustracer::{
+    accel::AcceleratedList,
+    arena::Arena,
+    camera::{Camera, Focus, RenderInfo},
+    debug, examples,
+    float::Float,
+    generator,
+    hittable::Hittable,
+    info,
+    output::Framebuffer,
+    progress::Progress,
+    scene::Scene,
+    stats, warn,
+};
+
+use crate::{
+    cli::{self, Options},
+    interrupt::INTERRUPTED,
+};
+
+// Distributed rendering: a coordinator splits the image into bands of scanlines and hands them out to workers on
+// other machines, which render all samples of a band and send back its film, the sums of the samples around every
+// pixel. The coordinator merges the bands into one film and develops the image from it, as if it had rendered it
+// alone. Workers get the command line of the coordinator and load the scene themselves, from the same path, so the
+// scene and the files it refers to need to be shared or copied to every machine.
+//
+// The protocol is a handshake and then requests of bands, on a TCP connection of its own per worker:
+//
+//   coordinator: MAGIC, working directory, number of arguments, arguments (strings are a u32 length and UTF-8)
+//   worker:      0 when the scene loaded, or 1 and an error message
+//   coordinator: first and end row of a band as u32
+//   worker:      the film of the band, see `Film::write_sums`
+//   ...
+//
+// The connection closes when there are no bands left. A worker that fails or disconnects is dropped and its band
+// goes back to the others, once they all failed the coordinator renders what's left itself. Bands in progress are
+// also handed out to workers without work, so a slow worker doesn't hold up the image, and a worker that doesn't
+// answer within `HANDSHAKE_TIMEOUT` or `BAND_TIMEOUT` counts as failed. There is no
+// authentication: workers render whatever they are sent and read the files it names, keep them on a trusted network.
+
+// Start of the handshake, with the version of the protocol.
+const MAGIC: &[u8; 8] = b"RTDIST01";
+
+// Scanlines per band, small enough to spread the image over many workers and to lose little when one fails.
+const BAND_ROWS: i32 = 8;
+
+// How long to wait for a worker to accept the connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// How long to wait for a worker to load the scene, and then for each band it renders. A worker that takes longer is
+// taken to have hung and is dropped like one that disconnected.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(600);
+const BAND_TIMEOUT: Duration = Duration::from_secs(300);
+
+// How often the coordinator checks for Ctrl-C while it waits for bands.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Run as a worker listening at `address`, rendering for every coordinator that connects on a thread of its own.
+// Runs until the process is killed.
+pub fn serve(address: &str) -> io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    info!("waiting for coordinators at {}", listener.local_addr()?);
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                warn!("failed to accept a connection: {err}");
+                continue;
+            }
+        };
+        thread::spawn(move || {
+            let peer = stream
+                .peer_addr()
+                .map_or_else(|_| "unknown".to_string(), |peer| peer.to_string());
+            match work(stream) {
+                Ok(bands) => info!("rendered {bands} bands for {peer}"),
+                Err(err) => warn!("{peer}: {err}"),
+            }
+        });
+    }
+    Ok(())
+}
+
+// Serve the coordinator at the other end of `stream`, returns the number of bands rendered.
+fn work(stream: TcpStream) -> io::Result<usize> {
+    stream.set_nodelay(true)?;
+    let mut input = BufReader::new(stream.try_clone()?);
+    let mut out = BufWriter::new(stream);
+    let mut magic = [0; 8];
+    input.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("not a rustracer coordinator"));
+    }
+    let directory = PathBuf::from(read_string(&mut input)?);
+    let count = read_u32(&mut input)?;
+    let args = (0..count)
+        .map(|_| read_string(&mut input))
+        .collect::<io::Result<Vec<_>>>()?;
+    let loaded = cli::parse_args(args).and_then(|mut options| {
+        // Paths of the command line are relative to the directory of the coordinator
+        options.scene = options.scene.map(|path| directory.join(path));
+        options.camera_path = options.camera_path.map(|path| directory.join(path));
+        load(&options)
+    });
+    let (world, camera) = match loaded {
+        Ok(loaded) => loaded,
+        Err(message) => {
+            out.write_all(&[1])?;
+            write_string(&mut out, &message)?;
+            out.flush()?;
+            return Err(io::Error::other(message));
+        }
+    };
+    out.write_all(&[0])?;
+    write_u32(&mut out, camera.image_width() as u32)?;
+    write_u32(&mut out, camera.image_height() as u32)?;
+    out.flush()?;
+    let film = camera.film();
+    let mut bands = 0;
+    loop {
+        let start = match read_u32(&mut input) {
+            Ok(start) => start as i32,
+            // The coordinator is done
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(bands),
+            Err(err) => return Err(err),
+        };
+        let end = read_u32(&mut input)? as i32;
+        let rows = camera.rows();
+        if start >= end || start < rows.start || end > rows.end {
+            return Err(invalid_data(format!(
+                "band {start}..{end} is outside of the rows {rows:?}"
+            )));
+        }
+        let mut tile = film.band(start as usize..end as usize);
+        camera.render_rows(world.as_ref(), &mut tile, start..end);
+        tile.write_sums(&mut out)?;
+        out.flush()?;
+        bands += 1;
+    }
+}
+
+// The world and the camera of the render the options describe, the way the coordinator sets them up.
+fn load(options: &Options) -> Result<(Box<dyn Hittable>, Camera), String> {
+    let scene = match (&options.scene, &options.generator) {
+        (Some(path), _) => Scene::load_with_overrides(path, options.bvh, &options.overrides)
+            .map_err(|err| format!("{}: {err}", path.display()))?,
+        (None, generator) => {
+            let scene = match generator {
+                Some(settings) => generator::generate(settings, options.bvh),
+                None => {
+                    let name = options.example.as_deref().unwrap_or("demo");
+                    examples::load(name, options.bvh).expect("the scene names are checked when parsing the arguments")
+                }
+            };
+            scene
+                .with_overrides(&options.overrides)
+                .map_err(|err| err.to_string())?
+        }
+    };
+    let mut scene = match &options.camera_path {
+        Some(path) => scene
+            .with_camera_path(path)
+            .map_err(|err| format!("{}: {err}", path.display()))?,
+        None => scene,
+    };
+    if let Some(layer) = &options.layer {
+        scene.check_group(layer).map_err(|err| err.to_string())?;
+    }
+    if let Some(Focus::Object(name)) = &options.focus {
+        scene.check_object(name).map_err(|err| err.to_string())?;
+    }
+    let world: Box<dyn Hittable> = if options.arena {
+        Box::new(Arena::new(std::mem::take(&mut scene.world), scene.accelerator))
+    } else {
+        Box::new(AcceleratedList::new(
+            std::mem::take(&mut scene.world),
+            scene.accelerator,
+        ))
+    };
+    let settings = match &options.focus {
+        Some(focus) => {
+            let focus_dist = scene
+                .camera
+                .focus_distance(world.as_ref(), focus, 0.0)
+                .map_err(|err| err.to_string())?;
+            scene.camera.with_focus_dist(focus_dist)
+        }
+        None => scene.camera,
+    };
+    let camera = Camera::new(settings)
+        .with_next_frame(scene.camera_at(1.0))
+        .with_light_links(scene.light_links.clone())
+        .with_lens(scene.lens.as_deref())
+        .with_aperture_mask(scene.aperture_mask)
+        .with_lut(scene.lut)
+        .with_packets(options.packets)
+        .with_crop(options.crop)
+        .with_integrator(options.integrator)
+        .with_layer(options.layer.as_deref());
+    Ok((world, camera))
+}
+
+// Render the image with `camera` on the workers at `addresses`, or in `world` here once they all failed. The workers
+// get the command line of this process.
+pub fn render(
+    addresses: &[String],
+    camera: &Camera,
+    world: &dyn Hittable,
+    progress: &dyn Progress,
+) -> io::Result<(Framebuffer, RenderInfo)> {
+    let rows = camera.rows();
+    let bands: Arc<Vec<_>> = Arc::new(
+        rows.clone()
+            .step_by(BAND_ROWS as usize)
+            .map(|start| start..(start + BAND_ROWS).min(rows.end))
+            .collect(),
+    );
+    let work = Arc::new(Mutex::new(Work::new(bands.len())));
+    let handshake = Arc::new(handshake()?);
+    let mut film = camera.film();
+    let size = [camera.image_width(), camera.image_height()];
+    let max_pixels = film.width() * film.height();
+    let (sender, receiver) = mpsc::channel();
+    for address in addresses {
+        let (address, bands, work, handshake, sender) = (
+            address.clone(),
+            bands.clone(),
+            work.clone(),
+            handshake.clone(),
+            sender.clone(),
+        );
+        thread::spawn(move || {
+            let worker = Worker {
+                bands: &bands,
+                work: &work,
+                size,
+                max_pixels,
+            };
+            if let Err(err) = worker.run(&address, &handshake, &sender) {
+                warn!("worker {address} failed, rendering without it: {err}");
+            }
+        });
+    }
+    drop(sender);
+
+    progress.start(rows.len() as u64);
+    stats::take();
+    let mut remaining = bands.len();
+    // Merge the bands as they come in, until they are all done or every worker failed. Workers still on a band
+    // someone else finished are not waited for.
+    let mut stopped = false;
+    while remaining > 0 {
+        if INTERRUPTED.load(Ordering::Relaxed) {
+            stopped = true;
+            break;
+        }
+        match receiver.recv_timeout(POLL_INTERVAL) {
+            Ok((index, sums)) => {
+                let band = &bands[index];
+                let mut tile = film.band(band.start as usize..band.end as usize);
+                tile.read_sums(&mut &sums[..])?;
+                film.merge(&tile);
+                progress.advance(band.len() as u64);
+                remaining -= 1;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    let mut local = false;
+    while !stopped {
+        let next = work.lock().unwrap().next();
+        let Some(index) = next else {
+            break;
+        };
+        if !local {
+            warn!("no workers left, rendering the rest of the image here");
+            local = true;
+        }
+        let band = bands[index].clone();
+        let mut tile = film.band(band.start as usize..band.end as usize);
+        camera.render_rows(world, &mut tile, band.clone());
+        work.lock().unwrap().finish(index);
+        film.merge(&tile);
+        progress.advance(band.len() as u64);
+        stopped = INTERRUPTED.load(Ordering::Relaxed);
+    }
+
+    let done = work.lock().unwrap().done_rows(&bands);
+    let samples = (done * film.width()) as u64 * camera.samples_per_pixel() as u64;
+    let info = RenderInfo {
+        samples,
+        samples_per_pixel: samples as Float / max_pixels.max(1) as Float,
+        stopped,
+        stats: stats::take(),
+    };
+    Ok((camera.develop(&film), info))
+}
+
+// Bands of a distributed render, shared by the threads talking to the workers.
+struct Work {
+    queue: VecDeque<usize>, // bands nobody works on yet, in order
+    busy: Vec<usize>,       // number of workers on every band
+    done: Vec<bool>,
+}
+
+impl Work {
+    fn new(count: usize) -> Work {
+        Work {
+            queue: (0..count).collect(),
+            busy: vec![0; count],
+            done: vec![false; count],
+        }
+    }
+
+    // Band to render next: the first one nobody works on, or else the unfinished one the fewest work on.
+    fn next(&mut self) -> Option<usize> {
+        let index = match self.queue.pop_front() {
+            Some(index) => index,
+            None => (0..self.done.len())
+                .filter(|&index| !self.done[index])
+                .min_by_key(|&index| self.busy[index])?,
+        };
+        self.busy[index] += 1;
+        Some(index)
+    }
+
+    // Mark a band as rendered, returns whether it's the first time.
+    fn finish(&mut self, index: usize) -> bool {
+        self.busy[index] -= 1;
+        !std::mem::replace(&mut self.done[index], true)
+    }
+
+    // A band failed to render, it goes back to the front of the queue unless another worker is on it.
+    fn fail(&mut self, index: usize) {
+        self.busy[index] -= 1;
+        if !self.done[index] && self.busy[index] == 0 {
+            self.queue.push_front(index);
+        }
+    }
+
+    fn done_rows(&self, bands: &[Range<i32>]) -> usize {
+        bands
+            .iter()
+            .zip(&self.done)
+            .filter(|(_, &done)| done)
+            .map(|(band, _)| band.len())
+            .sum()
+    }
+}
+
+// Thread of the coordinator talking to one worker.
+struct Worker<'a> {
+    bands: &'a [Range<i32>],
+    work: &'a Mutex<Work>,
+    size: [i32; 2],    // width and height of the image
+    max_pixels: usize, // pixels of the whole film, no band is larger
+}
+
+impl Worker<'_> {
+    // Hand out bands to the worker at `address` and send their sums to `results`, until there are none left.
+    fn run(&self, address: &str, handshake: &[u8], results: &Sender<(usize, Vec<u8>)>) -> io::Result<()> {
+        let socket = address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| invalid_data(format!("no address for '{address}'")))?;
+        let stream = TcpStream::connect_timeout(&socket, CONNECT_TIMEOUT)?;
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        stream.set_write_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        let mut input = BufReader::new(stream.try_clone()?);
+        let mut out = BufWriter::new(stream);
+        out.write_all(handshake)?;
+        out.flush()?;
+        let mut status = [0];
+        input
+            .read_exact(&mut status)
+            .map_err(|err| timed_out(err, HANDSHAKE_TIMEOUT))?;
+        if status[0] != 0 {
+            return Err(io::Error::other(read_string(&mut input)?));
+        }
+        let size = [read_u32(&mut input)? as i32, read_u32(&mut input)? as i32];
+        if size != self.size {
+            return Err(invalid_data(format!(
+                "it renders a {}x{} image instead of {}x{}, is its scene the same?",
+                size[0], size[1], self.size[0], self.size[1]
+            )));
+        }
+        debug!("worker {address} is ready");
+        input.get_ref().set_read_timeout(Some(BAND_TIMEOUT))?;
+        input.get_ref().set_write_timeout(Some(BAND_TIMEOUT))?;
+        loop {
+            let next = self.work.lock().unwrap().next();
+            let Some(index) = next else {
+                return Ok(());
+            };
+            let sums = self.request(&mut input, &mut out, &self.bands[index]);
+            match sums.map_err(|err| timed_out(err, BAND_TIMEOUT)) {
+                Ok(sums) => {
+                    // The render is over once nobody receives
+                    if self.work.lock().unwrap().finish(index) && results.send((index, sums)).is_err() {
+                        return Ok(());
+                    }
+                }
+                Err(err) => {
+                    self.work.lock().unwrap().fail(index);
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    // Have the worker render `band`, returns its sums as `Film::write_sums` wrote them.
+    fn request(&self, input: &mut impl Read, out: &mut impl Write, band: &Range<i32>) -> io::Result<Vec<u8>> {
+        write_u32(out, band.start as u32)?;
+        write_u32(out, band.end as u32)?;
+        out.flush()?;
+        let mut sums = vec![0; 32];
+        input.read_exact(&mut sums)?;
+        let value = |index: usize| u64::from_le_bytes(sums[index * 8..index * 8 + 8].try_into().expect("8 bytes"));
+        let pixels = value(2).saturating_mul(value(3));
+        if pixels > self.max_pixels as u64 {
+            return Err(invalid_data(format!(
+                "band of {pixels} pixels is larger than the image"
+            )));
+        }
+        // Every pixel has its color and weight as four doubles
+        sums.resize(32 + pixels as usize * 32, 0);
+        input.read_exact(&mut sums[32..])?;
+        Ok(sums)
+    }
+}
+
+// Handshake of this process as a coordinator, with its working directory and its arguments.
+fn handshake() -> io::Result<Vec<u8>> {
+    let mut handshake = MAGIC.to_vec();
+    let directory = env::current_dir()?;
+    write_string(&mut handshake, &directory.to_string_lossy())?;
+    let args: Vec<_> = env::args().skip(1).collect();
+    write_u32(&mut handshake, args.len() as u32)?;
+    for arg in &args {
+        write_string(&mut handshake, arg)?;
+    }
+    Ok(handshake)
+}
+
+fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn write_u32(out: &mut impl Write, value: u32) -> io::Result<()> {
+    out.write_all(&value.to_le_bytes())
+}
+
+// Longest string accepted, arguments and messages are short.
+const MAX_STRING: u32 = 1 << 16;
+
+fn read_string(input: &mut impl Read) -> io::Result<String> {
+    let length = read_u32(input)?;
+    if length > MAX_STRING {
+        return Err(invalid_data(format!("string of {length} bytes is too long")));
+    }
+    let mut bytes = vec![0; length as usize];
+    input.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|_| invalid_data("string is not UTF-8"))
+}
+
+fn write_string(out: &mut impl Write, text: &str) -> io::Result<()> {
+    write_u32(out, text.len() as u32)?;
+    out.write_all(text.as_bytes())
+}
+
+// Error of a read or write that ran into the timeout of the socket, which reports it as `WouldBlock` on some systems.
+fn timed_out(err: io::Error, timeout: Duration) -> io::Error {
+    match err.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => {
+            io::Error::new(io::ErrorKind::TimedOut, format!("no answer within {timeout:?}"))
+        }
+        _ => err,
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
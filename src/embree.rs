@@ -0,0 +1,236 @@
+use std::{
+    cell::Cell,
+    ffi::{c_char, c_int, c_uint, c_void},
+    ops::Range,
+    ptr,
+};
+
+use crate::{aabb::Aabb, float::Float, ray::Ray};
+
+type RtcDevice = *mut c_void;
+type RtcScene = *mut c_void;
+type RtcGeometry = *mut c_void;
+
+const RTC_GEOMETRY_TYPE_USER: c_int = 120;
+const RTC_INVALID_GEOMETRY_ID: c_uint = u32::MAX;
+
+#[repr(C)]
+struct RtcBounds {
+    lower: [f32; 3],
+    align0: f32,
+    upper: [f32; 3],
+    align1: f32,
+}
+
+#[repr(C)]
+struct RtcBoundsFunctionArguments {
+    geometry_user_ptr: *mut c_void,
+    prim_id: c_uint,
+    time_step: c_uint,
+    bounds_o: *mut RtcBounds,
+}
+
+#[repr(C, align(16))]
+struct RtcRay {
+    org: [f32; 3],
+    tnear: f32,
+    dir: [f32; 3],
+    time: f32,
+    tfar: f32,
+    mask: c_uint,
+    id: c_uint,
+    flags: c_uint,
+}
+
+// Only the fields up to `geom_id` are used, the instance ids that follow depend on how Embree was configured
+#[repr(C, align(16))]
+struct RtcHit {
+    ng: [f32; 3],
+    u: f32,
+    v: f32,
+    prim_id: c_uint,
+    geom_id: c_uint,
+    inst_id: [c_uint; 8],
+}
+
+#[repr(C)]
+struct RtcRayHit {
+    ray: RtcRay,
+    hit: RtcHit,
+}
+
+#[repr(C)]
+struct RtcIntersectFunctionNArguments {
+    valid: *mut c_int,
+    geometry_user_ptr: *mut c_void,
+    prim_id: c_uint,
+    context: *mut c_void,
+    rayhit: *mut RtcRayHit,
+    n: c_uint,
+    geom_id: c_uint,
+}
+
+#[link(name = "embree4")]
+extern "C" {
+    fn rtcNewDevice(config: *const c_char) -> RtcDevice;
+    fn rtcReleaseDevice(device: RtcDevice);
+    fn rtcNewScene(device: RtcDevice) -> RtcScene;
+    fn rtcReleaseScene(scene: RtcScene);
+    fn rtcCommitScene(scene: RtcScene);
+    fn rtcNewGeometry(device: RtcDevice, geometry_type: c_int) -> RtcGeometry;
+    fn rtcReleaseGeometry(geometry: RtcGeometry);
+    fn rtcCommitGeometry(geometry: RtcGeometry);
+    fn rtcAttachGeometry(scene: RtcScene, geometry: RtcGeometry) -> c_uint;
+    fn rtcSetGeometryUserPrimitiveCount(geometry: RtcGeometry, count: c_uint);
+    fn rtcSetGeometryUserData(geometry: RtcGeometry, ptr: *mut c_void);
+    fn rtcSetGeometryBoundsFunction(
+        geometry: RtcGeometry,
+        bounds: extern "C" fn(*const RtcBoundsFunctionArguments),
+        user_ptr: *mut c_void,
+    );
+    fn rtcSetGeometryIntersectFunction(
+        geometry: RtcGeometry,
+        intersect: extern "C" fn(*const RtcIntersectFunctionNArguments),
+    );
+    fn rtcIntersect1(scene: RtcScene, rayhit: *mut RtcRayHit, args: *mut c_void);
+}
+
+// State of the ray being traced, the intersect callback has no other way to reach the closure.
+struct Query<'a> {
+    t_range: Range<Float>,
+    hit_primitive: &'a mut dyn FnMut(usize, Range<Float>) -> Option<Float>,
+}
+
+thread_local! {
+    // Innermost query of the thread, indices can be nested (e.g. a mesh inside an object list)
+    static QUERY: Cell<*mut c_void> = const { Cell::new(ptr::null_mut()) };
+}
+
+// Spatial index backed by Intel Embree 4 (https://www.embree.org), linked against the system library. Embree builds
+// and traverses its own BVH over the primitive bounds (as a user geometry) and calls back for every primitive the ray
+// may hit, so it serves objects and mesh triangles alike and gives a production-grade baseline for our own structures.
+pub struct EmbreeIndex {
+    device: RtcDevice,
+    scene: RtcScene,
+    bounds: Box<[Aabb]>, // read by Embree while committing the scene
+    scene_bounds: Aabb,
+}
+
+// Committed Embree scenes are safe to traverse from any thread
+unsafe impl Send for EmbreeIndex {}
+unsafe impl Sync for EmbreeIndex {}
+
+impl EmbreeIndex {
+    pub fn new(bounds: &[Aabb]) -> EmbreeIndex {
+        let mut index = EmbreeIndex {
+            device: ptr::null_mut(),
+            scene: ptr::null_mut(),
+            bounds: bounds.into(),
+            scene_bounds: bounds.iter().fold(Aabb::EMPTY, |acc, b| acc.union(*b)),
+        };
+        // SAFETY: the geometry only refers to `index.bounds`, which lives on the heap as long as the scene
+        unsafe {
+            index.device = rtcNewDevice(ptr::null());
+            assert!(!index.device.is_null(), "failed to create the Embree device");
+            index.scene = rtcNewScene(index.device);
+            let geometry = rtcNewGeometry(index.device, RTC_GEOMETRY_TYPE_USER);
+            rtcSetGeometryUserPrimitiveCount(geometry, bounds.len() as c_uint);
+            rtcSetGeometryUserData(geometry, index.bounds.as_mut_ptr() as *mut c_void);
+            rtcSetGeometryBoundsFunction(geometry, primitive_bounds, ptr::null_mut());
+            rtcSetGeometryIntersectFunction(geometry, intersect);
+            rtcCommitGeometry(geometry);
+            rtcAttachGeometry(index.scene, geometry);
+            rtcReleaseGeometry(geometry);
+            rtcCommitScene(index.scene);
+        }
+        index
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.scene_bounds
+    }
+
+    pub fn traverse(
+        &self,
+        ray: Ray,
+        t_range: Range<Float>,
+        mut hit_primitive: impl FnMut(usize, Range<Float>) -> Option<Float>,
+    ) {
+        fn f32s(v: impl Into<[Float; 3]>) -> [f32; 3] {
+            v.into().map(|c| c as f32)
+        }
+        let mut rayhit = RtcRayHit {
+            ray: RtcRay {
+                org: f32s(ray.origin),
+                tnear: t_range.start as f32,
+                dir: f32s(ray.direction),
+                time: 0.0,
+                tfar: (t_range.end as f32).next_up(),
+                mask: u32::MAX,
+                id: 0,
+                flags: 0,
+            },
+            hit: RtcHit {
+                ng: [0.0; 3],
+                u: 0.0,
+                v: 0.0,
+                prim_id: RTC_INVALID_GEOMETRY_ID,
+                geom_id: RTC_INVALID_GEOMETRY_ID,
+                inst_id: [RTC_INVALID_GEOMETRY_ID; 8],
+            },
+        };
+        let mut query = Query {
+            t_range,
+            hit_primitive: &mut hit_primitive,
+        };
+        let previous = QUERY.with(|current| current.replace(&mut query as *mut Query as *mut c_void));
+        // SAFETY: `query` outlives the call, the callbacks run on this thread before it returns
+        unsafe { rtcIntersect1(self.scene, &mut rayhit, ptr::null_mut()) };
+        QUERY.with(|current| current.set(previous));
+    }
+}
+
+impl Drop for EmbreeIndex {
+    fn drop(&mut self) {
+        // SAFETY: both were created in `new` and are not used afterwards
+        unsafe {
+            rtcReleaseScene(self.scene);
+            rtcReleaseDevice(self.device);
+        }
+    }
+}
+
+extern "C" fn primitive_bounds(args: *const RtcBoundsFunctionArguments) {
+    // SAFETY: Embree passes the user data set in `EmbreeIndex::new` and a primitive id below the count
+    unsafe {
+        let args = &*args;
+        let bounds = &*(args.geometry_user_ptr as *const Aabb).add(args.prim_id as usize);
+        // Round outwards, the box has to contain the primitive in double precision
+        let lower = |v: Float| (v as f32).next_down();
+        let upper = |v: Float| (v as f32).next_up();
+        *args.bounds_o = RtcBounds {
+            lower: [lower(bounds.min.x), lower(bounds.min.y), lower(bounds.min.z)],
+            align0: 0.0,
+            upper: [upper(bounds.max.x), upper(bounds.max.y), upper(bounds.max.z)],
+            align1: 0.0,
+        };
+    }
+}
+
+extern "C" fn intersect(args: *const RtcIntersectFunctionNArguments) {
+    // SAFETY: only `rtcIntersect1` is used, so there is a single valid ray, and `traverse` set the query
+    unsafe {
+        let args = &*args;
+        if *args.valid == 0 {
+            return;
+        }
+        let query = &mut *(QUERY.with(Cell::get) as *mut Query);
+        if let Some(t) = (query.hit_primitive)(args.prim_id as usize, query.t_range.clone()) {
+            query.t_range.end = t;
+            let rayhit = &mut *args.rayhit;
+            rayhit.ray.tfar = (t as f32).next_up();
+            rayhit.hit.prim_id = args.prim_id;
+            rayhit.hit.geom_id = args.geom_id;
+        }
+    }
+}
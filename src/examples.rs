@@ -0,0 +1,345 @@
+use std::{path::Path, sync::Arc};
+
+use crate::{
+    accel::Accelerator,
+    animation::Keyframes,
+    bvh::BvhSettings,
+    camera::CameraSettings,
+    color::Color,
+    color_space::ColorSpace,
+    exposure::DEFAULT_SENSOR_HEIGHT,
+    filter::Filter,
+    float::Float,
+    hittable::{Hittable, HittableList},
+    id::Names,
+    instance::Instance,
+    light_linking::LightLinks,
+    material::{Dielectric, DiffuseLight, Lambertian, Material, Metal, ThinFilm},
+    mesh::TriangleMesh,
+    post::{Bloom, FilmGrain, LensFlare, WhiteBalance},
+    projection::Projection,
+    scene::Scene,
+    sphere::{Sphere, SphereList},
+    stereo::Stereo,
+    transform::Transform,
+    util::{random_double, random_double_ranged},
+    vec3::{Point, Vec3},
+    volume::ConstantMedium,
+};
+
+// Scenes built into the renderer, so there is something to render without writing a scene file, and well known
+// images to compare with other renderers and to benchmark. The random spheres are placed anew on every run.
+const SCENES: &[(&str, &str)] = &[
+    (
+        "demo",
+        "final scene of \"Ray Tracing in One Weekend\" with oil slicks and soap bubbles (default)",
+    ),
+    ("rtiow", "final scene of \"Ray Tracing in One Weekend\" as in the book"),
+    ("three-spheres", "the three spheres from \"Ray Tracing in One Weekend\""),
+    ("cornell", "Cornell box with two white boxes lit by the ceiling light"),
+    ("smoke", "Cornell box with a black and a white box of smoke"),
+];
+
+// Names and descriptions of the built-in scenes.
+pub fn names() -> impl Iterator<Item = (&'static str, &'static str)> {
+    SCENES.iter().copied()
+}
+
+// Build the scene with the given name, its objects use the BVH built with `bvh`.
+pub fn load(name: &str, bvh: BvhSettings) -> Option<Scene> {
+    let accelerator = Accelerator::Bvh(bvh);
+    match name {
+        "demo" => Some(random_spheres(true, accelerator)),
+        "rtiow" => Some(random_spheres(false, accelerator)),
+        "three-spheres" => {
+            let scene = Scene::parse(include_str!("../scenes/three-spheres.scene"), Path::new(""), bvh);
+            Some(scene.expect("the built-in scene is valid"))
+        }
+        "cornell" => Some(cornell_box(false, accelerator)),
+        "smoke" => Some(cornell_box(true, accelerator)),
+        _ => None,
+    }
+}
+
+// Small spheres of random materials around three big ones. With `films` some of the metal spheres have an oil slick
+// and some of the glass spheres are soap bubbles.
+fn random_spheres(films: bool, accelerator: Accelerator) -> Scene {
+    let mut spheres = vec![];
+
+    let ground_material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    spheres.push(Sphere::new(Point::new(0.0, -1000.0, 0.0), 1000.0, ground_material));
+
+    let base_point = Point::new(4.0, 0.2, 0.0);
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_mat = random_double();
+            let center = Point::new(
+                a as Float + 0.9 * random_double(),
+                0.2,
+                b as Float + 0.9 * random_double(),
+            );
+
+            if (center - base_point).length() > 0.9 {
+                let material: Arc<dyn Material> = if choose_mat < 0.8 {
+                    // diffuse
+                    let albedo = Color::random() * Color::random();
+                    Arc::new(Lambertian::new(albedo))
+                } else if !films && choose_mat < 0.95 || films && choose_mat < 0.9 {
+                    // metal
+                    let albedo = Color::random_ranged(0.5, 1.0);
+                    let fuzz = random_double_ranged(0.0, 0.5);
+                    Arc::new(Metal::new(albedo, fuzz))
+                } else if films && choose_mat < 0.925 {
+                    // oil slick on metal
+                    let film = ThinFilm::new(random_double_ranged(300.0, 600.0), 1.45);
+                    Arc::new(Metal::new(Color::new(0.8, 0.8, 0.8), 0.0).with_film(film))
+                } else if !films || choose_mat < 0.975 {
+                    // glass
+                    Arc::new(Dielectric::new(1.5))
+                } else {
+                    // soap bubble
+                    let film = ThinFilm::new(random_double_ranged(250.0, 600.0), 1.33);
+                    Arc::new(Dielectric::new(1.0).with_film(film))
+                };
+                spheres.push(Sphere::new(center, 0.2, material));
+            }
+        }
+    }
+
+    let material1 = Arc::new(Dielectric::new(1.5));
+    spheres.push(Sphere::new(Point::new(0.0, 1.0, 0.0), 1.0, material1));
+
+    let material2 = Arc::new(Lambertian::new(Color::new(0.4, 0.2, 0.1)));
+    spheres.push(Sphere::new(Point::new(-4.0, 1.0, 0.0), 1.0, material2));
+
+    let material3 = Arc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0));
+    spheres.push(Sphere::new(Point::new(4.0, 1.0, 0.0), 1.0, material3));
+
+    //Camera
+    let camera = CameraSettings {
+        aspect_ratio: 16.0 / 9.0,
+        image_width: 1200,
+        samples_per_pixel: 500,
+        max_depth: 50,
+
+        vfov: 20.0,
+        look_from: Point::new(13.0, 2.0, 3.0),
+        look_at: Point::new(0.0, 0.0, 0.0),
+        vup: Vec3::new(0.0, 1.0, 0.0),
+
+        defocus_angle: 0.6,
+        focus_dist: 10.0,
+
+        filter: Filter::Box,
+        projection: Projection::Perspective,
+        shutter: 0.5,
+        stereo: Stereo::Mono,
+        interocular: 0.065,
+        exposure: 1.0,
+        sensor_height: DEFAULT_SENSOR_HEIGHT,
+        aperture_blades: 0,
+        aperture_rotation: 0.0,
+        shift_x: 0.0,
+        shift_y: 0.0,
+        tilt: 0.0,
+        swing: 0.0,
+        vignetting: 0.0,
+        chromatic_aberration: 0.0,
+        bloom: Bloom::default(),
+        flare: LensFlare::default(),
+        grain: FilmGrain::default(),
+        white_balance: WhiteBalance::default(),
+        color_space: ColorSpace::default(),
+        background: None,
+    };
+
+    // Spheres are kept in a list of their own, which intersects them without dynamic dispatch
+    let world: HittableList = vec![Box::new(SphereList::new(spheres, accelerator))];
+    Scene {
+        camera,
+        world,
+        accelerator,
+        files: vec![],
+        camera_keyframes: Keyframes::default(),
+        names: Names::default(),
+        light_links: LightLinks::default(),
+        lens: None,
+        aperture_mask: None,
+        lut: None,
+    }
+}
+
+// The Cornell box of "Ray Tracing: The Next Week", 555 units wide, with a red wall on the right and a green one on the
+// left. The light on the ceiling is the only light, the box is open towards the camera. With `smoke` the two boxes
+// inside are smoke, a black one and a white one, under a larger and dimmer light.
+fn cornell_box(smoke: bool, accelerator: Accelerator) -> Scene {
+    let red: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.65, 0.05, 0.05)));
+    let white: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
+    let green: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.12, 0.45, 0.15)));
+    let light: Arc<dyn Material> = if smoke {
+        Arc::new(DiffuseLight::new(Color::new(7.0, 7.0, 7.0)))
+    } else {
+        Arc::new(DiffuseLight::new(Color::new(15.0, 15.0, 15.0)))
+    };
+
+    let mut walls = Quads::default();
+    walls.add(
+        Point::new(555.0, 0.0, 0.0),
+        Vec3::new(0.0, 555.0, 0.0),
+        Vec3::new(0.0, 0.0, 555.0),
+        0,
+    );
+    walls.add(
+        Point::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 555.0, 0.0),
+        Vec3::new(0.0, 0.0, 555.0),
+        1,
+    );
+    walls.add(
+        Point::new(0.0, 0.0, 0.0),
+        Vec3::new(555.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 555.0),
+        2,
+    );
+    walls.add(
+        Point::new(555.0, 555.0, 555.0),
+        Vec3::new(-555.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, -555.0),
+        2,
+    );
+    walls.add(
+        Point::new(0.0, 0.0, 555.0),
+        Vec3::new(555.0, 0.0, 0.0),
+        Vec3::new(0.0, 555.0, 0.0),
+        2,
+    );
+    // The light faces down, it only emits from its front face
+    if smoke {
+        walls.add(
+            Point::new(113.0, 554.0, 127.0),
+            Vec3::new(330.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 305.0),
+            3,
+        );
+    } else {
+        walls.add(
+            Point::new(343.0, 554.0, 332.0),
+            Vec3::new(-130.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -105.0),
+            3,
+        );
+    }
+    let mut world: HittableList = vec![Box::new(
+        walls.mesh(vec![green, red, white.clone(), light], accelerator),
+    )];
+
+    let tall = Transform::rotation(1, 15.0).then(Transform::translation(Vec3::new(265.0, 0.0, 295.0)));
+    let short = Transform::rotation(1, -18.0).then(Transform::translation(Vec3::new(130.0, 0.0, 65.0)));
+    let tall = Instance::new(box_mesh(Vec3::new(165.0, 330.0, 165.0), &white, accelerator), tall);
+    let short = Instance::new(box_mesh(Vec3::new(165.0, 165.0, 165.0), &white, accelerator), short);
+    if smoke {
+        world.push(Box::new(ConstantMedium::new(Box::new(tall), 0.01, Color::BLACK)));
+        world.push(Box::new(ConstantMedium::new(Box::new(short), 0.01, Color::WHITE)));
+    } else {
+        world.push(Box::new(tall));
+        world.push(Box::new(short));
+    }
+
+    let camera = CameraSettings {
+        aspect_ratio: 1.0,
+        image_width: 600,
+        samples_per_pixel: 200,
+        max_depth: 50,
+
+        vfov: 40.0,
+        look_from: Point::new(278.0, 278.0, -800.0),
+        look_at: Point::new(278.0, 278.0, 0.0),
+        vup: Vec3::new(0.0, 1.0, 0.0),
+
+        defocus_angle: 0.0,
+        focus_dist: 10.0,
+
+        filter: Filter::Box,
+        projection: Projection::Perspective,
+        shutter: 0.5,
+        stereo: Stereo::Mono,
+        interocular: 0.065,
+        exposure: 1.0,
+        sensor_height: DEFAULT_SENSOR_HEIGHT,
+        aperture_blades: 0,
+        aperture_rotation: 0.0,
+        shift_x: 0.0,
+        shift_y: 0.0,
+        tilt: 0.0,
+        swing: 0.0,
+        vignetting: 0.0,
+        chromatic_aberration: 0.0,
+        bloom: Bloom::default(),
+        flare: LensFlare::default(),
+        grain: FilmGrain::default(),
+        white_balance: WhiteBalance::default(),
+        color_space: ColorSpace::default(),
+        background: Some(Color::BLACK),
+    };
+    Scene {
+        camera,
+        world,
+        accelerator,
+        files: vec![],
+        camera_keyframes: Keyframes::default(),
+        names: Names::default(),
+        light_links: LightLinks::default(),
+        lens: None,
+        aperture_mask: None,
+        lut: None,
+    }
+}
+
+// Box from the origin to `size`, with its faces pointing out.
+fn box_mesh(size: Vec3, material: &Arc<dyn Material>, accelerator: Accelerator) -> Arc<dyn Hittable> {
+    let mut faces = Quads::default();
+    faces.add_box(size, &Transform::IDENTITY, 0);
+    Arc::new(faces.mesh(vec![material.clone()], accelerator))
+}
+
+// Parallelograms collected into a triangle mesh.
+#[derive(Default)]
+pub(crate) struct Quads {
+    vertices: Vec<Point>,
+    triangles: Vec<[usize; 3]>,
+    face_materials: Vec<usize>,
+}
+
+impl Quads {
+    // Add the parallelogram with the corner `q` and the edges `u` and `v`, its front face is on the side of `u` x `v`.
+    pub(crate) fn add(&mut self, q: Point, u: Vec3, v: Vec3, material: usize) {
+        let first = self.vertices.len();
+        self.vertices.extend([q, q + u, q + u + v, q + v]);
+        self.triangles.push([first, first + 1, first + 2]);
+        self.triangles.push([first, first + 2, first + 3]);
+        self.face_materials.extend([material, material]);
+    }
+
+    // Add the box from the origin to `size` moved into place by `transform`, with its faces pointing out.
+    pub(crate) fn add_box(&mut self, size: Vec3, transform: &Transform, material: usize) {
+        let [x, y, z] = [
+            Vec3::new(size.x, 0.0, 0.0),
+            Vec3::new(0.0, size.y, 0.0),
+            Vec3::new(0.0, 0.0, size.z),
+        ]
+        .map(|edge| transform.vector(edge));
+        let origin = transform.point(Point::new(0.0, 0.0, 0.0));
+        self.add(origin, y, x, material);
+        self.add(origin + z, x, y, material);
+        self.add(origin, z, y, material);
+        self.add(origin + x, y, z, material);
+        self.add(origin, x, z, material);
+        self.add(origin + y, z, x, material);
+    }
+
+    // Mesh of the parallelograms, the materials they were added with are indices into `materials`.
+    pub(crate) fn mesh(self, materials: Vec<Arc<dyn Material>>, accelerator: Accelerator) -> TriangleMesh {
+        TriangleMesh::new(self.vertices, self.triangles, materials[0].clone(), accelerator)
+            .with_face_materials(materials, self.face_materials)
+    }
+}
@@ -0,0 +1,76 @@
+use std::str::FromStr;
+
+use crate::float::Float;
+
+// Height of the sensor of cameras with photographic settings, 35 mm full frame in meters.
+pub const DEFAULT_SENSOR_HEIGHT: Float = 0.024;
+
+// Frame rate shutter times are turned into fractions of a frame at.
+pub const DEFAULT_FPS: Float = 24.0;
+
+// Photographic settings of a camera: the ISO sensitivity, the time the shutter is open and the f-number of the lens.
+// They expose the image the way they would on a real camera, with the light reaching it proportional to the shutter
+// time and the sensitivity and inversely proportional to the square of the f-number: f/1, 1 s at ISO 100 (an
+// exposure value of 0) leaves the image as is, every stop less exposure halves it. The shutter time also sets how long
+// the shutter is open for motion blur, and the f-number the size of the aperture for depth of field.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Exposure {
+    pub iso: Float,
+    pub shutter_time: Float, // seconds
+    pub f_number: Float,
+}
+
+impl Default for Exposure {
+    fn default() -> Exposure {
+        Exposure {
+            iso: 100.0,
+            shutter_time: 1.0,
+            f_number: 1.0,
+        }
+    }
+}
+
+impl Exposure {
+    // Scale of the light reaching the image.
+    pub fn scale(&self) -> Float {
+        self.shutter_time * self.iso / 100.0 / (self.f_number * self.f_number)
+    }
+
+    // Defocus angle of a lens with the field of view `vfov` in degrees over a sensor `sensor_height` high, focused at
+    // `focus_dist`, in the units of the sensor height.
+    pub fn defocus_angle(&self, vfov: Float, sensor_height: Float, focus_dist: Float) -> Float {
+        let focal_length = sensor_height / 2.0 / (vfov.to_radians() / 2.0).tan();
+        let aperture_radius = focal_length / self.f_number / 2.0;
+        2.0 * (aperture_radius / focus_dist).atan().to_degrees()
+    }
+
+    // Fraction of a frame the shutter is open at `fps` frames per second, at most the whole frame.
+    pub fn shutter(&self, fps: Float) -> Float {
+        (self.shutter_time * fps).min(1.0)
+    }
+}
+
+// Shutter time in seconds, written as a number of seconds or as a fraction, e.g. "1/250".
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ShutterTime(pub Float);
+
+impl FromStr for ShutterTime {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<ShutterTime, String> {
+        let time = match s.split_once('/') {
+            Some((numerator, denominator)) => match (numerator.parse::<Float>(), denominator.parse::<Float>()) {
+                (Ok(numerator), Ok(denominator)) => numerator / denominator,
+                _ => Float::NAN,
+            },
+            None => s.parse().unwrap_or(Float::NAN),
+        };
+        if time > 0.0 && time.is_finite() {
+            Ok(ShutterTime(time))
+        } else {
+            Err(format!(
+                "invalid shutter time '{s}', expected seconds like '0.004' or '1/250'"
+            ))
+        }
+    }
+}
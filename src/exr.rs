@@ -0,0 +1,174 @@
+use std::{
+    fs::File,
+    io::{self, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use crate::{color::Color, output::Framebuffer};
+
+// Pieces of OpenEXR files as the deep, the Cryptomatte and the live output write them: single part, uncompressed,
+// one scanline per chunk and every channel in 32 bit floats.
+
+// OpenEXR pixel type of 32 bit floats.
+const FLOAT: i32 = 2;
+
+// Flag in the version field of files of deep pixels.
+pub(crate) const DEEP: i32 = 0x800;
+
+// Header of a file of `width` by `height` pixels with the given channels, which must be sorted by name, and further
+// attributes (name, type and value) besides the ones every file needs. `flags` go into the version field.
+pub(crate) fn header(
+    flags: i32,
+    channels: &[&str],
+    width: usize,
+    height: usize,
+    attributes: Vec<(String, &str, Vec<u8>)>,
+) -> Vec<u8> {
+    let mut header = vec![];
+    // Magic number, then version 2 and the flags
+    header.extend(20000630_i32.to_le_bytes());
+    header.extend((2 | flags).to_le_bytes());
+    let mut list = vec![];
+    for name in channels {
+        list.extend(name.as_bytes());
+        list.push(0);
+        list.extend(FLOAT.to_le_bytes());
+        list.extend([0; 4]); // linear flag and reserved bytes
+        list.extend(1_i32.to_le_bytes()); // sampling in x and y
+        list.extend(1_i32.to_le_bytes());
+    }
+    list.push(0);
+    let window: Vec<u8> = [0, 0, width as i32 - 1, height as i32 - 1]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    let standard = [
+        ("channels", "chlist", list),
+        ("compression", "compression", vec![0]),
+        ("dataWindow", "box2i", window.clone()),
+        ("displayWindow", "box2i", window),
+        ("lineOrder", "lineOrder", vec![0]),
+        ("pixelAspectRatio", "float", 1.0_f32.to_le_bytes().to_vec()),
+        ("screenWindowCenter", "v2f", [0; 8].to_vec()),
+        ("screenWindowWidth", "float", 1.0_f32.to_le_bytes().to_vec()),
+    ];
+    let standard = standard.map(|(name, kind, value)| (name.to_string(), kind, value));
+    for (name, kind, value) in standard.into_iter().chain(attributes) {
+        header.extend(name.as_bytes());
+        header.push(0);
+        header.extend(kind.as_bytes());
+        header.push(0);
+        header.extend((value.len() as i32).to_le_bytes());
+        header.extend(value);
+    }
+    header.push(0);
+    header
+}
+
+// Write the file: the header, the table of where the chunks start and the chunks.
+pub(crate) fn write(header: &[u8], chunks: &[Vec<u8>], out: &mut dyn Write) -> io::Result<()> {
+    let mut offset = (header.len() + 8 * chunks.len()) as u64;
+    let mut offsets = vec![];
+    for chunk in chunks {
+        offsets.extend(offset.to_le_bytes());
+        offset += chunk.len() as u64;
+    }
+    out.write_all(header)?;
+    out.write_all(&offsets)?;
+    for chunk in chunks {
+        out.write_all(chunk)?;
+    }
+    out.flush()
+}
+
+// Flat image with the given channels, every one a value per pixel in scanline order. The channels are sorted by name,
+// as OpenEXR files list them.
+pub(crate) fn write_image(
+    width: usize,
+    height: usize,
+    mut channels: Vec<(String, Vec<f32>)>,
+    attributes: Vec<(String, &str, Vec<u8>)>,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    channels.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let names: Vec<&str> = channels.iter().map(|(name, _)| name.as_str()).collect();
+    let header = header(0, &names, width, height, attributes);
+    // Chunks of a scanline: its number, the size of the data and the values of every channel in turn
+    let chunks: Vec<Vec<u8>> = (0..height)
+        .map(|y| {
+            let mut chunk = vec![];
+            chunk.extend((y as i32).to_le_bytes());
+            chunk.extend(((channels.len() * width * 4) as i32).to_le_bytes());
+            for (_, values) in &channels {
+                chunk.extend(values[y * width..(y + 1) * width].iter().flat_map(|v| v.to_le_bytes()));
+            }
+            chunk
+        })
+        .collect();
+    write(&header, &chunks, out)
+}
+
+// RGB image written into an OpenEXR file while it renders. The whole file is laid out up front, black, and every
+// scanline is written over its chunk as soon as it is done, so viewers that reload the file show the render so far and
+// a crash keeps everything finished before it.
+pub(crate) struct LiveImage {
+    file: File,
+    width: usize,
+    chunks: u64, // offset of the chunk of the first scanline
+}
+
+impl LiveImage {
+    pub(crate) fn create(path: &Path, width: usize, height: usize) -> io::Result<LiveImage> {
+        let create = || -> io::Result<LiveImage> {
+            let black = Framebuffer::new(width, height);
+            let header = header(0, &["B", "G", "R"], width, height, vec![]);
+            let mut file = File::create(path)?;
+            write(&header, &scanlines(&black), &mut file)?;
+            let chunks = (header.len() + 8 * height) as u64;
+            Ok(LiveImage { file, width, chunks })
+        };
+        create().map_err(|err| io::Error::new(err.kind(), format!("{}: {err}", path.display())))
+    }
+
+    // Write the colors of the scanline `y` over the ones in the file.
+    pub(crate) fn write_scanline(&mut self, y: usize, colors: &[Color]) -> io::Result<()> {
+        let offset = self.chunks + (y * (8 + 12 * self.width)) as u64;
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.write_all(&scanline(y, colors))
+    }
+
+    // Write the finished image over the whole file.
+    pub(crate) fn finish(&mut self, image: &Framebuffer) -> io::Result<()> {
+        for y in 0..image.height() {
+            let row = &image.pixels()[y * image.width()..(y + 1) * image.width()];
+            self.write_scanline(y, row)?;
+        }
+        self.file.sync_all()
+    }
+}
+
+// Chunks of every scanline of an RGB image.
+fn scanlines(image: &Framebuffer) -> Vec<Vec<u8>> {
+    image
+        .pixels()
+        .chunks(image.width().max(1))
+        .take(image.height())
+        .enumerate()
+        .map(|(y, row)| scanline(y, row))
+        .collect()
+}
+
+// Chunk of an RGB scanline: its number, the size of the data and the blue, green and red values, sorted by name.
+fn scanline(y: usize, colors: &[Color]) -> Vec<u8> {
+    let mut chunk = Vec::with_capacity(8 + 12 * colors.len());
+    chunk.extend((y as i32).to_le_bytes());
+    chunk.extend(((12 * colors.len()) as i32).to_le_bytes());
+    for channel in [
+        |color: &Color| color.b,
+        |color: &Color| color.g,
+        |color: &Color| color.r,
+    ] {
+        chunk.extend(colors.iter().flat_map(|color| (channel(color) as f32).to_le_bytes()));
+    }
+    chunk
+}
@@ -0,0 +1,377 @@
+use std::{
+    cell::RefCell,
+    ffi::{c_char, c_void, CStr, CString},
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    accel::{AcceleratedList, Accelerator},
+    camera::{Camera, CameraSettings},
+    float::Float,
+    hittable::{Hittable, HittableList},
+    material::Material,
+    mesh::TriangleMesh,
+    progress::{NoProgress, Progress},
+    scene::{parse_camera_parameters, parse_material_parameters},
+    sphere::Sphere,
+    vec3::Point,
+};
+
+// C API for embedding the renderer in other applications. A scene is built in code from materials, spheres and
+// triangle meshes, with the camera and the materials given as the parameters of their directives in scene files (see
+// `scene.rs`), and rendered into an RGBA buffer of the caller. Functions that can fail return -1 and leave a message
+// for `rustracer_last_error`, and so do all of them if the renderer panics, which never unwinds into the caller. The
+// library builds with `cargo rustc --lib --release --crate-type cdylib` (or `staticlib`).
+//
+// `build.rs` generates the declarations of `include/rustracer.h` from the functions and types here, with the comments
+// right above them up to their safety requirements.
+
+// Scene built in code, created with `rustracer_scene_new` and released with `rustracer_scene_free`.
+pub struct RustracerScene {
+    camera: CameraSettings,
+    materials: Vec<Arc<dyn Material>>,
+    primitives: Vec<Primitive>,
+}
+
+// Objects are kept as they were given, and the world is built for every render, so a scene can be added to and
+// rendered again.
+enum Primitive {
+    Sphere {
+        center: Point,
+        radius: Float,
+        material: Arc<dyn Material>,
+    },
+    Mesh {
+        vertices: Vec<Point>,
+        triangles: Vec<[usize; 3]>,
+        material: Arc<dyn Material>,
+    },
+}
+
+impl RustracerScene {
+    fn world(&self) -> Box<dyn Hittable> {
+        let accelerator = Accelerator::default();
+        let objects: HittableList = self
+            .primitives
+            .iter()
+            .map(|primitive| -> Box<dyn Hittable> {
+                match primitive {
+                    Primitive::Sphere {
+                        center,
+                        radius,
+                        material,
+                    } => Box::new(Sphere::new(*center, *radius, material.clone())),
+                    Primitive::Mesh {
+                        vertices,
+                        triangles,
+                        material,
+                    } => Box::new(TriangleMesh::new(
+                        vertices.clone(),
+                        triangles.clone(),
+                        material.clone(),
+                        accelerator,
+                    )),
+                }
+            })
+            .collect();
+        Box::new(AcceleratedList::new(objects, accelerator))
+    }
+
+    fn material(&self, index: i32) -> Result<Arc<dyn Material>, String> {
+        usize::try_from(index)
+            .ok()
+            .and_then(|index| self.materials.get(index))
+            .cloned()
+            .ok_or_else(|| format!("unknown material {index}"))
+    }
+}
+
+// Called with the user data, the scanlines done and the scanlines of the whole render, on the thread calling
+// `rustracer_scene_render`.
+pub type ProgressCallback = extern "C" fn(user_data: *mut c_void, done: u64, total: u64);
+
+// Progress reported to the callback of the caller.
+struct CallbackProgress {
+    callback: ProgressCallback,
+    user_data: *mut c_void,
+    counts: Mutex<(u64, u64)>, // scanlines done and in total
+}
+
+// The user data is only passed back to the callback, on the thread rendering.
+unsafe impl Sync for CallbackProgress {}
+
+impl Progress for CallbackProgress {
+    fn start(&self, total: u64) {
+        *self.counts.lock().unwrap() = (0, total);
+    }
+
+    fn advance(&self, delta: u64) {
+        let mut counts = self.counts.lock().unwrap();
+        counts.0 += delta;
+        (self.callback)(self.user_data, counts.0, counts.1);
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+// Record the error of a failed call for `rustracer_last_error`.
+fn fail(message: &str) {
+    let message = CString::new(message.replace('\0', " ")).expect("no nul bytes");
+    LAST_ERROR.with(|error| *error.borrow_mut() = message);
+}
+
+// Record the error of a failed call, and turn the result into the value returned to C.
+fn report<T>(result: Result<T, String>, ok: impl FnOnce(T) -> i32) -> i32 {
+    match result {
+        Ok(value) => ok(value),
+        Err(message) => {
+            fail(&message);
+            -1
+        }
+    }
+}
+
+// Run the body of a function called from C. Unwinding out of it would abort the process or worse, so a panic is
+// caught and recorded like an error, and the function returns `failed`.
+fn guard<T>(failed: T, body: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or_else(|payload| {
+        let message = match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+            (Some(message), _) => message,
+            (_, Some(message)) => message.as_str(),
+            _ => "unknown error",
+        };
+        fail(&format!("the renderer panicked: {message}"));
+        failed
+    })
+}
+
+// # Safety
+// `text` must be a nul-terminated string.
+unsafe fn text<'a>(text: *const c_char) -> Result<&'a str, String> {
+    match text.is_null() {
+        true => Ok(""),
+        false => CStr::from_ptr(text).to_str().map_err(|err| err.to_string()),
+    }
+}
+
+// Message of the last call that failed on this thread, empty if none did. It stays valid until the next call fails.
+#[no_mangle]
+pub extern "C" fn rustracer_last_error() -> *const c_char {
+    guard(ptr::null(), || LAST_ERROR.with(|error| error.borrow().as_ptr()))
+}
+
+// Empty scene with the default camera, released with `rustracer_scene_free`. Returns NULL if it can't be created.
+#[no_mangle]
+pub extern "C" fn rustracer_scene_new() -> *mut RustracerScene {
+    guard(ptr::null_mut(), || {
+        Box::into_raw(Box::new(RustracerScene {
+            camera: CameraSettings::default(),
+            materials: vec![],
+            primitives: vec![],
+        }))
+    })
+}
+
+// # Safety
+// `scene` must come from `rustracer_scene_new` or be null, and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn rustracer_scene_free(scene: *mut RustracerScene) {
+    guard((), || {
+        if !scene.is_null() {
+            drop(Box::from_raw(scene));
+        }
+    })
+}
+
+// Replace the camera by one with the parameters of a camera directive, e.g. `image_width=640 look_from=0,1,5`.
+// Returns 0, or -1 if they are invalid.
+//
+// # Safety
+// `scene` must be a live scene and `parameters` a nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rustracer_scene_set_camera(scene: *mut RustracerScene, parameters: *const c_char) -> i32 {
+    guard(-1, || {
+        let scene = &mut *scene;
+        let camera =
+            text(parameters).and_then(|parameters| parse_camera_parameters(parameters).map_err(|err| err.to_string()));
+        report(camera, |camera| {
+            scene.camera = camera;
+            0
+        })
+    })
+}
+
+// Add a material with the parameters of a material directive, e.g. `type=metal albedo=0.8,0.6,0.2 fuzz=0.1`.
+// Returns its index for the objects to refer to, or -1 if they are invalid.
+//
+// # Safety
+// `scene` must be a live scene and `parameters` a nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rustracer_scene_add_material(scene: *mut RustracerScene, parameters: *const c_char) -> i32 {
+    guard(-1, || {
+        let scene = &mut *scene;
+        let material = text(parameters)
+            .and_then(|parameters| parse_material_parameters(parameters).map_err(|err| err.to_string()));
+        report(material, |material| {
+            scene.materials.push(material);
+            scene.materials.len() as i32 - 1
+        })
+    })
+}
+
+// Add a sphere of the material with the index `material`. Returns 0, or -1 if there is no such material.
+//
+// # Safety
+// `scene` must be a live scene.
+#[no_mangle]
+pub unsafe extern "C" fn rustracer_scene_add_sphere(
+    scene: *mut RustracerScene,
+    x: f64,
+    y: f64,
+    z: f64,
+    radius: f64,
+    material: i32,
+) -> i32 {
+    guard(-1, || {
+        let scene = &mut *scene;
+        report(scene.material(material), |material| {
+            scene.primitives.push(Primitive::Sphere {
+                center: Point::new(x as Float, y as Float, z as Float),
+                radius: radius as Float,
+                material,
+            });
+            0
+        })
+    })
+}
+
+// Add a triangle mesh of the material with the index `material`: `vertices` holds the x, y and z coordinates of
+// `vertex_count` vertices and `indices` three vertex indices for each of the `triangle_count` triangles,
+// counter-clockwise when looking at the front. The data is copied. Returns 0, or -1 if an index is out of range.
+//
+// # Safety
+// `scene` must be a live scene, `vertices` must point to `3 * vertex_count` floats and `indices` to
+// `3 * triangle_count` integers.
+#[no_mangle]
+pub unsafe extern "C" fn rustracer_scene_add_mesh(
+    scene: *mut RustracerScene,
+    vertices: *const f32,
+    vertex_count: usize,
+    indices: *const u32,
+    triangle_count: usize,
+    material: i32,
+) -> i32 {
+    guard(-1, || {
+        let scene = &mut *scene;
+        let vertices: Vec<Point> = match vertex_count {
+            0 => vec![],
+            _ => slice::from_raw_parts(vertices, 3 * vertex_count)
+                .chunks_exact(3)
+                .map(|xyz| Point::new(xyz[0] as Float, xyz[1] as Float, xyz[2] as Float))
+                .collect(),
+        };
+        let triangles: Vec<[usize; 3]> = match triangle_count {
+            0 => vec![],
+            _ => slice::from_raw_parts(indices, 3 * triangle_count)
+                .chunks_exact(3)
+                .map(|triangle| [0, 1, 2].map(|corner| triangle[corner] as usize))
+                .collect(),
+        };
+        let mesh = scene.material(material).and_then(|material| {
+            match triangles.iter().flatten().find(|&&index| index >= vertices.len()) {
+                Some(index) => Err(format!(
+                    "vertex index {index} out of range, the mesh has {vertex_count} vertices"
+                )),
+                None => Ok(material),
+            }
+        });
+        report(mesh, |material| {
+            scene.primitives.push(Primitive::Mesh {
+                vertices,
+                triangles,
+                material,
+            });
+            0
+        })
+    })
+}
+
+// Width of the image the camera renders, 0 if the camera is invalid.
+//
+// # Safety
+// `scene` must be a live scene.
+#[no_mangle]
+pub unsafe extern "C" fn rustracer_scene_width(scene: *const RustracerScene) -> u32 {
+    guard(0, || Camera::new((*scene).camera).image_width() as u32)
+}
+
+// Height of the image the camera renders, 0 if the camera is invalid.
+//
+// # Safety
+// `scene` must be a live scene.
+#[no_mangle]
+pub unsafe extern "C" fn rustracer_scene_height(scene: *const RustracerScene) -> u32 {
+    guard(0, || Camera::new((*scene).camera).image_height() as u32)
+}
+
+// Render the scene into `rgba`, `length` bytes for the opaque 8-bit RGBA pixels of the image row by row from the top
+// left, at least 4 * width * height. `progress` is called with `user_data` as scanlines finish, it can be null.
+// Returns 0, or -1 if the buffer is too small.
+//
+// # Safety
+// `scene` must be a live scene and `rgba` must point to `length` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rustracer_scene_render(
+    scene: *const RustracerScene,
+    rgba: *mut u8,
+    length: usize,
+    progress: Option<ProgressCallback>,
+    user_data: *mut c_void,
+) -> i32 {
+    guard(-1, || {
+        let scene = &*scene;
+        let camera = Camera::new(scene.camera);
+        let needed = 4 * camera.image_width() as usize * camera.image_height() as usize;
+        if length < needed {
+            return report::<()>(
+                Err(format!(
+                    "the buffer of {length} bytes is too small for the {needed} of the image"
+                )),
+                |_| 0,
+            );
+        }
+        let callback = progress.map(|callback| CallbackProgress {
+            callback,
+            user_data,
+            counts: Mutex::new((0, 0)),
+        });
+        let progress: &dyn Progress = match &callback {
+            Some(callback) => callback,
+            None => &NoProgress,
+        };
+        let rendered = camera
+            .render_image(scene.world().as_ref(), progress)
+            .map_err(|err| err.to_string());
+        report(rendered, |(image, _)| {
+            image.write_rgba8(slice::from_raw_parts_mut(rgba, length));
+            0
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panics_fail_the_call() {
+        assert_eq!(guard(-1, || panic!("out of cheese")), -1);
+        let message = unsafe { CStr::from_ptr(rustracer_last_error()) };
+        assert_eq!(message.to_str(), Ok("the renderer panicked: out of cheese"));
+        assert_eq!(guard(-1, || 7), 7);
+    }
+}
@@ -0,0 +1,185 @@
+use std::{
+    io::{self, Read, Write},
+    ops::Range,
+};
+
+use crate::{color::Color, filter::Filter, float::Float, output::Framebuffer};
+
+// Sensor the camera exposes: the weighted sums of the samples splatted onto every pixel, in linear HDR color. A
+// sample adds its color, weighted by the reconstruction filter, to all pixels whose center is within the radius of
+// the filter, so wide filters pool the samples of neighbouring pixels and the weight of a pixel adds up from many
+// samples even at low sample counts.
+//
+// A film can also be a tile of a larger one, covering a rectangle of its pixels. Workers splat into tiles of their
+// own and the tiles are merged into the image afterwards; sample positions are in image coordinates either way.
+pub struct Film {
+    x0: usize, // column and row of the top left pixel in the image, zero unless this is a tile
+    y0: usize,
+    width: usize,
+    height: usize,
+    filter: Filter,
+    colors: Vec<Color>,  // sum of the weighted sample colors of every pixel
+    weights: Vec<Float>, // sum of the weights of the samples of every pixel
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize, filter: Filter) -> Film {
+        Film::region(0..width, 0..height, filter)
+    }
+
+    // Film for a window of the image only, covering the given columns and rows of its pixels.
+    pub fn region(columns: Range<usize>, rows: Range<usize>, filter: Filter) -> Film {
+        let (width, height) = (columns.len(), rows.len());
+        Film {
+            x0: columns.start,
+            y0: rows.start,
+            width,
+            height,
+            filter,
+            colors: vec![Color::BLACK; width * height],
+            weights: vec![0.0; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // Empty tile for the samples of the given pixels. It is larger than the rectangle by the radius of the filter, to
+    // take the splats of the samples near its border.
+    pub fn tile(&self, columns: Range<usize>, rows: Range<usize>) -> Film {
+        let margin = self.filter.radius().ceil() as usize;
+        let grow = |range: Range<usize>, start: usize, size: usize| {
+            range.start.saturating_sub(margin).max(start)..(range.end + margin).min(start + size)
+        };
+        Film::region(
+            grow(columns, self.x0, self.width),
+            grow(rows, self.y0, self.height),
+            self.filter,
+        )
+    }
+
+    // Empty tile for the samples of all pixels of the given rows.
+    pub fn band(&self, rows: Range<usize>) -> Film {
+        self.tile(self.x0..self.x0 + self.width, rows)
+    }
+
+    // Add the sums of a tile of this film.
+    pub fn merge(&mut self, tile: &Film) {
+        for row in 0..tile.height {
+            let y = tile.y0 + row - self.y0;
+            let start = y * self.width + tile.x0 - self.x0;
+            let source = row * tile.width..(row + 1) * tile.width;
+            for (sum, color) in self.colors[start..start + tile.width]
+                .iter_mut()
+                .zip(&tile.colors[source.clone()])
+            {
+                *sum += *color;
+            }
+            for (sum, weight) in self.weights[start..start + tile.width]
+                .iter_mut()
+                .zip(&tile.weights[source])
+            {
+                *sum += *weight;
+            }
+        }
+    }
+
+    // Add a sample at `position`, in pixels from the top left corner of the image.
+    pub fn splat(&mut self, [x, y]: [Float; 2], color: Color) {
+        let radius = self.filter.radius();
+        // Pixels of the film whose centers at (i + 0.5, j + 0.5) are closer than the radius
+        let pixels = |position: Float, start: usize, size: usize| {
+            let first = (position - radius - 0.5).ceil().max(start as Float) as usize;
+            let last = ((position + radius - 0.5).floor() as isize).min((start + size) as isize - 1);
+            first..(last + 1).max(0) as usize
+        };
+        for j in pixels(y, self.y0, self.height) {
+            for i in pixels(x, self.x0, self.width) {
+                let weight = self.filter.evaluate(x - (i as Float + 0.5), y - (j as Float + 0.5));
+                if weight != 0.0 {
+                    let index = (j - self.y0) * self.width + i - self.x0;
+                    self.colors[index] += weight * color;
+                    self.weights[index] += weight;
+                }
+            }
+        }
+    }
+
+    // Filtered color of a pixel of the film. The weights of the Mitchell filter can be negative, a pixel whose weights
+    // don't add up to anything positive is black.
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        let index = y * self.width + x;
+        let weight = self.weights[index];
+        if weight > 0.0 {
+            self.colors[index] / weight
+        } else {
+            Color::BLACK
+        }
+    }
+
+    // Final image of the weighted averages.
+    pub fn resolve(&self) -> Framebuffer {
+        let mut image = Framebuffer::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                image.set(x, y, self.pixel(x, y));
+            }
+        }
+        image
+    }
+
+    // Write the weighted averages into their window of the whole image, leaving the pixels around it as they are.
+    pub fn resolve_into(&self, image: &mut Framebuffer) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                image.set(self.x0 + x, self.y0 + y, self.pixel(x, y));
+            }
+        }
+    }
+
+    // Write the window and the sums of the film in a binary format, for picking up a render where it stopped or sending
+    // a tile rendered elsewhere. Values are stored in double precision regardless of the `f32` feature.
+    #[allow(clippy::unnecessary_cast)] // the cast to `f64` is a no-op without the `f32` feature
+    pub fn write_sums(&self, out: &mut dyn Write) -> io::Result<()> {
+        for value in [self.x0, self.y0, self.width, self.height] {
+            out.write_all(&(value as u64).to_le_bytes())?;
+        }
+        for (color, &weight) in self.colors.iter().zip(&self.weights) {
+            for value in color.to_array().into_iter().chain([weight]) {
+                out.write_all(&(value as f64).to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    // Replace the sums of the film with ones written by `write_sums`, which must be of the same window.
+    pub fn read_sums(&mut self, input: &mut dyn Read) -> io::Result<()> {
+        let mut read_u64 = || -> io::Result<u64> {
+            let mut bytes = [0; 8];
+            input.read_exact(&mut bytes)?;
+            Ok(u64::from_le_bytes(bytes))
+        };
+        let window = [read_u64()?, read_u64()?, read_u64()?, read_u64()?];
+        if window != [self.x0, self.y0, self.width, self.height].map(|value| value as u64) {
+            let [x0, y0, width, height] = window;
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "the sums are of a {width}x{height} window at {x0}, {y0} instead of {}x{} at {}, {}",
+                    self.width, self.height, self.x0, self.y0
+                ),
+            ));
+        }
+        for (color, weight) in self.colors.iter_mut().zip(&mut self.weights) {
+            let [r, g, b, w] = [(); 4].map(|_| read_u64().map(|bits| f64::from_bits(bits) as Float));
+            *color = Color::new(r?, g?, b?);
+            *weight = w?;
+        }
+        Ok(())
+    }
+}
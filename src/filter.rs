@@ -0,0 +1,84 @@
+use std::str::FromStr;
+
+use crate::float::Float;
+
+// Pixel reconstruction filter. Every sample is splatted onto the pixels around it with the filter value at its offset
+// from the pixel center as weight, and each pixel is the weighted average of the samples it received. The box filter
+// only counts the samples inside the pixel, which keeps edges aliased at low sample counts; the wider kernels blend in
+// samples from around the pixel and give smoother edges, the Mitchell-Netravali filter keeps the image sharper than
+// the tent and the Gaussian with its small negative lobes.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Filter {
+    #[default]
+    Box,
+    Tent,
+    Gaussian,
+    Mitchell,
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Filter, String> {
+        match s {
+            "box" => Ok(Filter::Box),
+            "tent" | "triangle" => Ok(Filter::Tent),
+            "gaussian" => Ok(Filter::Gaussian),
+            "mitchell" => Ok(Filter::Mitchell),
+            _ => Err(format!(
+                "unknown filter '{s}', expected 'box', 'tent', 'gaussian' or 'mitchell'"
+            )),
+        }
+    }
+}
+
+const GAUSSIAN_SIGMA: Float = 0.5;
+
+impl Filter {
+    // Half the width of the square the filter is non-zero in, in pixels. The border itself is excluded.
+    pub fn radius(self) -> Float {
+        match self {
+            Filter::Box => 0.5,
+            Filter::Tent => 1.0,
+            Filter::Gaussian => 1.5,
+            Filter::Mitchell => 2.0,
+        }
+    }
+
+    // Weight of a sample at offset (x, y) in pixels from the pixel center. All filters are separable.
+    pub fn evaluate(self, x: Float, y: Float) -> Float {
+        self.evaluate_1d(x) * self.evaluate_1d(y)
+    }
+
+    fn evaluate_1d(self, x: Float) -> Float {
+        let x = x.abs();
+        let radius = self.radius();
+        if x >= radius {
+            return 0.0;
+        }
+        match self {
+            Filter::Box => 1.0,
+            Filter::Tent => radius - x,
+            Filter::Gaussian => {
+                // Shifted down so the kernel reaches zero at its radius instead of being cut off
+                let gaussian = |x: Float| (-x * x / (2.0 * GAUSSIAN_SIGMA * GAUSSIAN_SIGMA)).exp();
+                gaussian(x) - gaussian(radius)
+            }
+            Filter::Mitchell => {
+                // B = C = 1/3 as recommended by Mitchell and Netravali, the cubic spans [-2, 2]
+                const B: Float = 1.0 / 3.0;
+                const C: Float = 1.0 / 3.0;
+                let polynomial = if x < 1.0 {
+                    (12.0 - 9.0 * B - 6.0 * C) * x * x * x + (-18.0 + 12.0 * B + 6.0 * C) * x * x + (6.0 - 2.0 * B)
+                } else {
+                    (-B - 6.0 * C) * x * x * x
+                        + (6.0 * B + 30.0 * C) * x * x
+                        + (-12.0 * B - 48.0 * C) * x
+                        + (8.0 * B + 24.0 * C)
+                };
+                polynomial / 6.0
+            }
+        }
+    }
+}
@@ -0,0 +1,12 @@
+// Precision of all scalar math. Double precision by default, the `f32` feature switches to single precision, which
+// halves the memory and bandwidth taken by vectors, vertices and hierarchies of large scenes and matches the
+// precision GPU renderers work with, at the cost of more self-intersection artifacts.
+#[cfg(not(feature = "f32"))]
+pub type Float = f64;
+#[cfg(feature = "f32")]
+pub type Float = f32;
+
+#[cfg(feature = "f32")]
+pub use std::f32::consts;
+#[cfg(not(feature = "f32"))]
+pub use std::f64::consts;
@@ -0,0 +1,450 @@
+use std::{collections::HashMap, fs, io, path::Path};
+
+use crate::{
+    error::{Error, Result},
+    float::Float,
+};
+
+// Deepest nesting of composite glyphs followed, deeper ones are taken for a loop.
+const MAX_COMPONENT_DEPTH: usize = 8;
+
+// Outlines of a TrueType font, from a TTF or OTF file or the first font of a TTC collection: the characters it maps
+// to glyphs, their advances and kerning, and the quadratic curves of the glyphs. Fonts with PostScript (CFF) outlines
+// aren't supported. Lengths are in font units, `units_per_em` to the size of the font.
+pub struct Font {
+    pub units_per_em: Float,
+    pub line_height: Float, // from one baseline to the next: the ascender, descender and line gap
+    glyphs: HashMap<char, u16>,
+    advances: Vec<u16>, // of the glyphs with their own, the rest have the advance of the last
+    kerning: HashMap<(u16, u16), i16>,
+    locations: Vec<usize>, // start of every glyph in `outlines`, and the end of the last
+    outlines: Vec<u8>,     // the 'glyf' table
+}
+
+impl Font {
+    pub fn load(path: &Path) -> Result<Font> {
+        Font::parse(&fs::read(path)?)
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Font> {
+        let start = match &read::<4>(data, 0)? {
+            [0, 1, 0, 0] | b"true" => 0,
+            b"ttcf" => read_u32(data, 12)? as usize,
+            b"OTTO" => return Err(invalid("fonts with PostScript (CFF) outlines aren't supported")),
+            _ => return Err(invalid("not a TrueType font")),
+        };
+        let version = read::<4>(data, start)?;
+        if version != [0, 1, 0, 0] && &version != b"true" {
+            return Err(invalid("fonts with PostScript (CFF) outlines aren't supported"));
+        }
+        let mut tables = HashMap::new();
+        for record in 0..read_u16(data, start + 4)? as usize {
+            let record = start + 12 + record * 16;
+            let offset = read_u32(data, record + 8)? as usize;
+            let length = read_u32(data, record + 12)? as usize;
+            let table = data
+                .get(offset..offset.saturating_add(length))
+                .ok_or_else(|| invalid("truncated font file"))?;
+            tables.insert(read::<4>(data, record)?, table);
+        }
+        let table = |tag: &[u8; 4]| {
+            tables
+                .get(tag)
+                .copied()
+                .ok_or_else(|| invalid(&format!("font has no '{}' table", String::from_utf8_lossy(tag))))
+        };
+
+        let head = table(b"head")?;
+        let units_per_em = read_u16(head, 18)?;
+        if units_per_em == 0 {
+            return Err(invalid("font has no units per em"));
+        }
+        let long_locations = read_i16(head, 50)? != 0;
+        let glyph_count = read_u16(table(b"maxp")?, 4)? as usize;
+        let hhea = table(b"hhea")?;
+        let line_height = read_i16(hhea, 4)? as Float - read_i16(hhea, 6)? as Float + read_i16(hhea, 8)? as Float;
+        let hmtx = table(b"hmtx")?;
+        let advances = (0..read_u16(hhea, 34)? as usize)
+            .map(|glyph| read_u16(hmtx, glyph * 4))
+            .collect::<Result<Vec<_>>>()?;
+        let loca = table(b"loca")?;
+        let locations = (0..=glyph_count)
+            .map(|glyph| match long_locations {
+                true => read_u32(loca, glyph * 4).map(|offset| offset as usize),
+                false => read_u16(loca, glyph * 2).map(|offset| offset as usize * 2),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let kerning = match tables.get(b"kern") {
+            Some(kern) => parse_kerning(kern)?,
+            None => HashMap::new(),
+        };
+        Ok(Font {
+            units_per_em: units_per_em as Float,
+            line_height,
+            glyphs: parse_character_map(table(b"cmap")?)?,
+            advances,
+            kerning,
+            locations,
+            outlines: table(b"glyf")?.to_vec(),
+        })
+    }
+
+    // Glyph of a character, the missing glyph 0 (usually a box) if the font has none.
+    pub fn glyph(&self, c: char) -> u16 {
+        self.glyphs.get(&c).copied().unwrap_or(0)
+    }
+
+    // How far the pen moves on after a glyph.
+    pub fn advance(&self, glyph: u16) -> Float {
+        let advance = self.advances.get(glyph as usize).or(self.advances.last());
+        advance.copied().unwrap_or(0) as Float
+    }
+
+    // Change of the advance between two glyphs next to each other, from the 'kern' table (not the kerning of the
+    // 'GPOS' table of newer fonts).
+    pub fn kerning(&self, left: u16, right: u16) -> Float {
+        self.kerning.get(&(left, right)).copied().unwrap_or(0) as Float
+    }
+
+    // Closed contours of a glyph, with its curves split into straight segments deviating from them by at most
+    // `tolerance`. The outer contours go around clockwise (with y up) and the holes counter-clockwise, in fonts that
+    // follow the TrueType convention.
+    pub fn outline(&self, glyph: u16, tolerance: Float) -> Result<Vec<Vec<[Float; 2]>>> {
+        let mut contours = vec![];
+        self.add_outline(glyph, [1.0, 0.0, 0.0, 1.0, 0.0, 0.0], tolerance, 0, &mut contours)?;
+        Ok(contours)
+    }
+
+    // Add the contours of a glyph, transformed by the 2x2 matrix and offset of `transform`, to `contours`.
+    fn add_outline(
+        &self,
+        glyph: u16,
+        transform: [Float; 6],
+        tolerance: Float,
+        depth: usize,
+        contours: &mut Vec<Vec<[Float; 2]>>,
+    ) -> Result<()> {
+        if depth > MAX_COMPONENT_DEPTH {
+            return Err(invalid("composite glyphs nested too deeply"));
+        }
+        let (start, end) = match self.locations.get(glyph as usize..glyph as usize + 2) {
+            Some(&[start, end]) => (start, end),
+            _ => return Err(invalid(&format!("font has no glyph {glyph}"))),
+        };
+        if start >= end {
+            // Glyphs without contours, like the space
+            return Ok(());
+        }
+        let data = self
+            .outlines
+            .get(start..end)
+            .ok_or_else(|| invalid("truncated 'glyf' table"))?;
+        let contour_count = read_i16(data, 0)?;
+        if contour_count < 0 {
+            return self.add_composite(data, transform, tolerance, depth, contours);
+        }
+
+        let ends = (0..contour_count as usize)
+            .map(|contour| read_u16(data, 10 + contour * 2).map(|end| end as usize))
+            .collect::<Result<Vec<_>>>()?;
+        let point_count = ends.last().map_or(0, |end| end + 1);
+        let instructions = read_u16(data, 10 + ends.len() * 2)? as usize;
+        let mut offset = 12 + ends.len() * 2 + instructions;
+        let mut flags = Vec::with_capacity(point_count);
+        while flags.len() < point_count {
+            let flag = read::<1>(data, offset)?[0];
+            offset += 1;
+            let repeats = match flag & 8 {
+                0 => 0,
+                _ => {
+                    offset += 1;
+                    read::<1>(data, offset - 1)?[0] as usize
+                }
+            };
+            flags.extend(std::iter::repeat_n(flag, repeats + 1));
+        }
+        flags.truncate(point_count);
+        // The coordinates are changes from the point before, first all x then all y, of one byte (with the sign in
+        // the flag) or two, or no change
+        let mut coordinates = |short: u8, same_or_positive: u8| -> Result<Vec<Float>> {
+            let mut value = 0;
+            flags
+                .iter()
+                .map(|&flag| {
+                    if flag & short != 0 {
+                        let delta = read::<1>(data, offset)?[0] as i32;
+                        offset += 1;
+                        value += if flag & same_or_positive != 0 { delta } else { -delta };
+                    } else if flag & same_or_positive == 0 {
+                        value += read_i16(data, offset)? as i32;
+                        offset += 2;
+                    }
+                    Ok(value as Float)
+                })
+                .collect()
+        };
+        let xs = coordinates(2, 16)?;
+        let ys = coordinates(4, 32)?;
+
+        let mut first = 0;
+        for &end in &ends {
+            if end < first || end >= point_count {
+                return Err(invalid("invalid glyph contour"));
+            }
+            let points: Vec<([Float; 2], bool)> = (first..=end)
+                .map(|point| {
+                    let [a, b, c, d, dx, dy] = transform;
+                    let (x, y) = (xs[point], ys[point]);
+                    ([a * x + c * y + dx, b * x + d * y + dy], flags[point] & 1 != 0)
+                })
+                .collect();
+            first = end + 1;
+            let contour = flatten(&points, tolerance);
+            if contour.len() >= 3 {
+                contours.push(contour);
+            }
+        }
+        Ok(())
+    }
+
+    // Add the contours of a glyph made of other glyphs, each moved and possibly scaled.
+    fn add_composite(
+        &self,
+        data: &[u8],
+        transform: [Float; 6],
+        tolerance: Float,
+        depth: usize,
+        contours: &mut Vec<Vec<[Float; 2]>>,
+    ) -> Result<()> {
+        const ARGS_ARE_WORDS: u16 = 1;
+        const ARGS_ARE_OFFSETS: u16 = 2;
+        const SCALE: u16 = 8;
+        const MORE_COMPONENTS: u16 = 0x20;
+        const X_AND_Y_SCALE: u16 = 0x40;
+        const TWO_BY_TWO: u16 = 0x80;
+        let f2dot14 = |offset: usize| read_i16(data, offset).map(|value| value as Float / 16384.0);
+
+        let mut offset = 10;
+        loop {
+            let flags = read_u16(data, offset)?;
+            let component = read_u16(data, offset + 2)?;
+            offset += 4;
+            let (dx, dy) = match flags & ARGS_ARE_WORDS {
+                0 => {
+                    offset += 2;
+                    let [x, y] = read::<2>(data, offset - 2)?;
+                    (x as i8 as Float, y as i8 as Float)
+                }
+                _ => {
+                    offset += 4;
+                    (
+                        read_i16(data, offset - 4)? as Float,
+                        read_i16(data, offset - 2)? as Float,
+                    )
+                }
+            };
+            // Components placed by matching points instead of offsets are left where they are
+            let (dx, dy) = match flags & ARGS_ARE_OFFSETS {
+                0 => (0.0, 0.0),
+                _ => (dx, dy),
+            };
+            let [a, b, c, d] = if flags & SCALE != 0 {
+                offset += 2;
+                let scale = f2dot14(offset - 2)?;
+                [scale, 0.0, 0.0, scale]
+            } else if flags & X_AND_Y_SCALE != 0 {
+                offset += 4;
+                [f2dot14(offset - 4)?, 0.0, 0.0, f2dot14(offset - 2)?]
+            } else if flags & TWO_BY_TWO != 0 {
+                offset += 8;
+                [
+                    f2dot14(offset - 8)?,
+                    f2dot14(offset - 6)?,
+                    f2dot14(offset - 4)?,
+                    f2dot14(offset - 2)?,
+                ]
+            } else {
+                [1.0, 0.0, 0.0, 1.0]
+            };
+            // The component's transform first, then the one of the glyph it is in
+            let [ta, tb, tc, td, tx, ty] = transform;
+            let combined = [
+                ta * a + tc * b,
+                tb * a + td * b,
+                ta * c + tc * d,
+                tb * c + td * d,
+                ta * dx + tc * dy + tx,
+                tb * dx + td * dy + ty,
+            ];
+            self.add_outline(component, combined, tolerance, depth + 1, contours)?;
+            if flags & MORE_COMPONENTS == 0 {
+                return Ok(());
+            }
+        }
+    }
+}
+
+// Points of a closed contour of on-curve points and the control points of quadratic curves between them, as straight
+// segments. Two control points in a row have an implied on-curve point halfway between them.
+fn flatten(points: &[([Float; 2], bool)], tolerance: Float) -> Vec<[Float; 2]> {
+    let midpoint = |a: [Float; 2], b: [Float; 2]| [(a[0] + b[0]) / 2.0, (a[1] + b[1]) / 2.0];
+    // Start on the curve: at the first on-curve point, or between the last and the first control point if there is
+    // none
+    let Some(start) = points.iter().position(|&(_, on)| on) else {
+        return match (points.first(), points.last()) {
+            (Some(&(first, _)), Some(&(last, _))) => {
+                let mut with_start = vec![(midpoint(last, first), true)];
+                with_start.extend_from_slice(points);
+                flatten(&with_start, tolerance)
+            }
+            _ => vec![],
+        };
+    };
+
+    let mut contour = vec![];
+    let mut current = points[start].0;
+    let mut control = None;
+    for index in 1..=points.len() {
+        let (point, on) = points[(start + index) % points.len()];
+        match (on, control) {
+            (true, None) => {
+                contour.push(current);
+                current = point;
+            }
+            (true, Some(c)) => {
+                curve(&mut contour, current, c, point, tolerance);
+                current = point;
+                control = None;
+            }
+            (false, None) => control = Some(point),
+            (false, Some(c)) => {
+                let end = midpoint(c, point);
+                curve(&mut contour, current, c, end, tolerance);
+                current = end;
+                control = Some(point);
+            }
+        }
+    }
+    // The last segment ended where the contour started
+    contour
+}
+
+// Add the points of a quadratic curve from `a` over `control` to `b` to `contour`, all but `b`. The distance of the
+// curve from a chord of a n-th of it is at most |a - 2 control + b| / (4 n²).
+fn curve(contour: &mut Vec<[Float; 2]>, a: [Float; 2], control: [Float; 2], b: [Float; 2], tolerance: Float) {
+    let bend = (a[0] - 2.0 * control[0] + b[0]).hypot(a[1] - 2.0 * control[1] + b[1]);
+    let segments = ((bend / (4.0 * tolerance)).sqrt().ceil() as usize).clamp(1, 64);
+    for step in 0..segments {
+        let t = step as Float / segments as Float;
+        let (u, v, w) = ((1.0 - t) * (1.0 - t), 2.0 * t * (1.0 - t), t * t);
+        contour.push([
+            u * a[0] + v * control[0] + w * b[0],
+            u * a[1] + v * control[1] + w * b[1],
+        ]);
+    }
+}
+
+// Glyphs of the characters from the best Unicode subtable of the 'cmap' table: the full repertoire of format 12 if
+// there is one, otherwise the basic multilingual plane of format 4.
+fn parse_character_map(cmap: &[u8]) -> Result<HashMap<char, u16>> {
+    let mut best = None;
+    for record in 0..read_u16(cmap, 2)? as usize {
+        let record = 4 + record * 8;
+        let (platform, encoding) = (read_u16(cmap, record)?, read_u16(cmap, record + 2)?);
+        let offset = read_u32(cmap, record + 4)? as usize;
+        let unicode = platform == 0 || (platform == 3 && (encoding == 1 || encoding == 10));
+        if !unicode {
+            continue;
+        }
+        let format = read_u16(cmap, offset)?;
+        if format == 12 || (format == 4 && best.is_none()) {
+            best = Some((format, offset));
+        }
+    }
+    let (format, offset) = best.ok_or_else(|| invalid("font has no Unicode character map"))?;
+    let mut glyphs = HashMap::new();
+    if format == 12 {
+        for group in 0..read_u32(cmap, offset + 12)? as usize {
+            let group = offset + 16 + group * 12;
+            let (first, last) = (read_u32(cmap, group)?, read_u32(cmap, group + 4)?);
+            let glyph = read_u32(cmap, group + 8)?;
+            for code in first..=last.min(0x10ffff) {
+                if let Some(c) = char::from_u32(code) {
+                    glyphs.insert(c, glyph.wrapping_add(code - first) as u16);
+                }
+            }
+        }
+    } else {
+        // Segments of consecutive codes, with the glyphs offset by a delta or looked up in an array
+        let segments = read_u16(cmap, offset + 6)? as usize / 2;
+        let ends = offset + 14;
+        let (starts, deltas, ranges) = (
+            ends + segments * 2 + 2,
+            ends + segments * 4 + 2,
+            ends + segments * 6 + 2,
+        );
+        for segment in 0..segments {
+            let (first, last) = (
+                read_u16(cmap, starts + segment * 2)?,
+                read_u16(cmap, ends + segment * 2)?,
+            );
+            let delta = read_u16(cmap, deltas + segment * 2)?;
+            let range = read_u16(cmap, ranges + segment * 2)? as usize;
+            for code in first..=last.min(0xfffe) {
+                let glyph = match range {
+                    0 => code.wrapping_add(delta),
+                    _ => match read_u16(cmap, ranges + segment * 2 + range + (code - first) as usize * 2)? {
+                        0 => 0,
+                        glyph => glyph.wrapping_add(delta),
+                    },
+                };
+                if let Some(c) = char::from_u32(code as u32) {
+                    glyphs.insert(c, glyph);
+                }
+            }
+        }
+    }
+    Ok(glyphs)
+}
+
+// Pairs of glyphs with the change of their advance from the horizontal subtables of format 0 of a 'kern' table.
+fn parse_kerning(kern: &[u8]) -> Result<HashMap<(u16, u16), i16>> {
+    let mut kerning = HashMap::new();
+    let mut offset = 4;
+    for _ in 0..read_u16(kern, 2)? {
+        let (length, coverage) = (read_u16(kern, offset + 2)? as usize, read_u16(kern, offset + 4)?);
+        // Horizontal kerning values of format 0, not minimums or cross-stream
+        if coverage & 0xff07 == 1 {
+            for pair in 0..read_u16(kern, offset + 6)? as usize {
+                let pair = offset + 14 + pair * 6;
+                let glyphs = (read_u16(kern, pair)?, read_u16(kern, pair + 2)?);
+                kerning.insert(glyphs, read_i16(kern, pair + 4)?);
+            }
+        }
+        offset += length.max(6);
+    }
+    Ok(kerning)
+}
+
+fn invalid(message: &str) -> Error {
+    Error::Io(io::Error::new(io::ErrorKind::InvalidData, message.to_string()))
+}
+
+// Fonts are big-endian.
+fn read<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N]> {
+    data.get(offset..offset.saturating_add(N))
+        .map(|bytes| bytes.try_into().expect("N bytes"))
+        .ok_or_else(|| invalid("truncated font file"))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16> {
+    read(data, offset).map(u16::from_be_bytes)
+}
+
+fn read_i16(data: &[u8], offset: usize) -> Result<i16> {
+    read(data, offset).map(i16::from_be_bytes)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    read(data, offset).map(u32::from_be_bytes)
+}
@@ -0,0 +1,216 @@
+use std::{ops::Range, sync::Arc};
+
+use crate::{
+    aabb::Aabb,
+    color::Color,
+    float::Float,
+    hittable::{Hit, Hittable},
+    material::Material,
+    range::Interval,
+    ray::Ray,
+    vec3::{Point, Vec3},
+};
+
+// Distance from the surface at which a marching ray counts as hitting it, in the units of the fractals, which fit in a
+// box from -2 to 2. Smaller values show finer detail and take more steps.
+const SURFACE_DISTANCE: Float = 1e-4;
+
+// Most steps a ray takes towards the surface, rays grazing it give up after these.
+const MAX_STEPS: usize = 512;
+
+// Fractals whose distance to their surface can be estimated.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Formula {
+    Mandelbulb { power: Float }, // Mandelbrot set in 3D, with the squaring of z generalized to spherical coordinates
+    Menger,                      // cube with the middle of every face and the center cut out, recursively
+    Julia { c: [Float; 4] },     // Julia set of the quaternions: the points for which z² + c stays bounded
+}
+
+// Fractal of a `Formula`, rendered by marching the rays towards its surface in steps of the estimated distance
+// (sphere tracing) instead of intersecting them with geometry. `iterations` of the formula set the depth of the
+// detail. The surface is tinted from `colors[0]` to `colors[1]` by the iteration at which the orbit of a point comes
+// closest to the origin (for the sponge: the level of the hole it is on), which brings out its structure.
+pub struct Fractal {
+    formula: Formula,
+    iterations: u32,
+    colors: Option<[Color; 2]>,
+    material: Arc<dyn Material>,
+}
+
+impl Fractal {
+    pub fn new(formula: Formula, iterations: u32, material: Arc<dyn Material>) -> Fractal {
+        Fractal {
+            formula,
+            iterations: iterations.max(1),
+            colors: None,
+            material,
+        }
+    }
+
+    pub fn with_colors(self, colors: [Color; 2]) -> Fractal {
+        Fractal {
+            colors: Some(colors),
+            ..self
+        }
+    }
+
+    // Lower bound of the distance from `p` to the surface, and where its color is between the two, from 0 to 1.
+    fn estimate(&self, p: Vec3) -> (Float, Float) {
+        match self.formula {
+            Formula::Mandelbulb { power } => mandelbulb(p, power, self.iterations),
+            Formula::Menger => menger(p, self.iterations),
+            Formula::Julia { c } => julia(p, c, self.iterations),
+        }
+    }
+
+    // Normal of the surface near `p`, from the change of the distance around it (with four samples at the corners
+    // of a tetrahedron).
+    fn normal(&self, p: Vec3) -> Vec3 {
+        let h = 5.0 * SURFACE_DISTANCE;
+        [
+            Vec3::new(1.0, -1.0, -1.0),
+            Vec3::new(-1.0, -1.0, 1.0),
+            Vec3::new(-1.0, 1.0, -1.0),
+            Vec3::new(1.0, 1.0, 1.0),
+        ]
+        .into_iter()
+        .map(|k| k * self.estimate(p + k * h).0)
+        .fold(Vec3::ZERO, |sum, term| sum + term)
+        .normalize()
+    }
+}
+
+impl Hittable for Fractal {
+    fn hit(&self, ray: Ray, t_range: Range<Float>) -> Option<Hit<'_>> {
+        let inv_direction = Vec3::new(1.0 / ray.direction.x, 1.0 / ray.direction.y, 1.0 / ray.direction.z);
+        let Range { start, end } = self.bounding_box().clip(ray, inv_direction, &t_range)?;
+        // March in units of distance, the direction of the ray needn't be a unit vector
+        let speed = ray.direction.length();
+        let (origin, direction) = (ray.origin.to_vec(), ray.direction / speed);
+        let mut distance = start * speed;
+        // Rays leaving the surface start close to it, they are moved on until they are clear of it
+        let mut clear = false;
+        for _ in 0..MAX_STEPS {
+            if distance > end * speed {
+                return None;
+            }
+            let (estimate, shade) = self.estimate(origin + direction * distance);
+            if estimate < SURFACE_DISTANCE && !clear {
+                distance += SURFACE_DISTANCE;
+                continue;
+            }
+            clear = true;
+            if estimate < SURFACE_DISTANCE {
+                let t = distance / speed;
+                if !t_range.surrounds(t) {
+                    return None;
+                }
+                let point = origin + direction * distance;
+                let hit = Hit::new(ray, t, self.normal(point), self.material.as_ref());
+                return Some(match self.colors {
+                    Some([few, many]) => hit.with_color(few + (many - few) * shade),
+                    None => hit,
+                });
+            }
+            distance += estimate;
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let extent = match self.formula {
+            Formula::Menger => 1.0 + SURFACE_DISTANCE,
+            _ => 2.0,
+        };
+        Aabb::new(
+            Point::new(-extent, -extent, -extent),
+            Point::new(extent, extent, extent),
+        )
+    }
+}
+
+// Distance estimate of the Mandelbulb of `power` with its axis along y, after Daniel White and Paul Nylander's
+// formula, with the iteration at which its orbit comes closest to the origin.
+fn mandelbulb(p: Vec3, power: Float, iterations: u32) -> (Float, Float) {
+    const BAILOUT: Float = 2.0;
+    let mut z = p;
+    let mut derivative = 1.0;
+    let mut radius = z.length();
+    // Closest the orbit has come to the origin, and at which iteration
+    let (mut closest, mut trap) = (radius, 0);
+    let mut count = 0;
+    while count < iterations && radius <= BAILOUT {
+        // The power of z in spherical coordinates: the radius to the power, the angles times it
+        let theta = (z.y / radius).clamp(-1.0, 1.0).acos() * power;
+        let phi = z.z.atan2(z.x) * power;
+        derivative = radius.powf(power - 1.0) * power * derivative + 1.0;
+        let scaled = radius.powf(power);
+        z = Vec3::new(theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin()) * scaled + p;
+        radius = z.length();
+        count += 1;
+        if radius < closest {
+            (closest, trap) = (radius, count);
+        }
+    }
+    // Points that don't escape are inside, or too close to tell at this depth of detail. The estimate goes on
+    // smoothly for them, down to below zero, so that the normals still follow it.
+    (
+        0.5 * radius.ln() * radius / derivative,
+        trap as Float / iterations as Float,
+    )
+}
+
+// Exact distance to the Menger sponge in a box from -1 to 1, after Inigo Quilez: the distance to the box, cut by the
+// crosses of holes of every level. The color is by the level of the holes of the closest surface.
+fn menger(p: Vec3, iterations: u32) -> (Float, Float) {
+    let q = p.abs() - Vec3::new(1.0, 1.0, 1.0);
+    let mut distance = q.x.max(q.y.max(q.z)).min(0.0) + q.max(Vec3::ZERO).length();
+    let mut level = 0;
+    let mut scale = 1.0;
+    for iteration in 0..iterations {
+        // Position within the cell of this level, from -1 to 1
+        let wrap = |x: Float| x * scale - 2.0 * (0.5 * x * scale).floor() - 1.0;
+        let cell = Vec3::new(wrap(p.x), wrap(p.y), wrap(p.z));
+        scale *= 3.0;
+        let r = (Vec3::new(1.0, 1.0, 1.0) - cell.abs() * 3.0).abs();
+        let (a, b, c) = (r.x.max(r.y), r.y.max(r.z), r.z.max(r.x));
+        let hole = (a.min(b.min(c)) - 1.0) / scale;
+        if hole > distance {
+            distance = hole;
+            level = iteration + 1;
+        }
+    }
+    (distance, level as Float / iterations as Float)
+}
+
+// Distance estimate of the quaternion Julia set of `c` through the slice of the quaternions with a zero last
+// component, with the iteration at which its orbit comes closest to the origin.
+fn julia(p: Vec3, c: [Float; 4], iterations: u32) -> (Float, Float) {
+    const BAILOUT: Float = 256.0;
+    let mut z = [p.x, p.y, p.z, 0.0];
+    // Length of the derivative of z, which is doubled and scaled by |z| every iteration
+    let mut derivative: Float = 1.0;
+    let mut radius = p.length();
+    // Closest the orbit has come to the origin, and at which iteration
+    let (mut closest, mut trap) = (radius, 0);
+    let mut count = 0;
+    while count < iterations && radius <= BAILOUT {
+        derivative *= 2.0 * radius;
+        let [r, i, j, k] = z;
+        z = [
+            r * r - i * i - j * j - k * k + c[0],
+            2.0 * r * i + c[1],
+            2.0 * r * j + c[2],
+            2.0 * r * k + c[3],
+        ];
+        radius = z.iter().map(|x| x * x).sum::<Float>().sqrt();
+        count += 1;
+        if radius < closest {
+            (closest, trap) = (radius, count);
+        }
+    }
+    (
+        0.5 * radius * radius.ln() / derivative,
+        trap as Float / iterations as Float,
+    )
+}
@@ -0,0 +1,244 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, ErrorKind, Write},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    process::exit,
+    sync::atomic::Ordering,
+};
+
+use rustracer::{
+    accel::AcceleratedList,
+    arena::Arena,
+    camera::Camera,
+    error,
+    float::Float,
+    hittable::Hittable,
+    info,
+    output::{write_image, ImageFormat},
+    scene::Scene,
+    warn,
+};
+
+#[cfg(feature = "video")]
+use crate::video::{Encoder, DEFAULT_FPS};
+use crate::{
+    cli::Options,
+    interrupt::{INTERRUPTED, PAUSED},
+};
+
+// File name of the frames when --frame-output isn't given.
+pub const DEFAULT_PATTERN: &str = "frame_####.ppm";
+
+// Exit status when frames were left out because other processes are still rendering them, see `claim`. Errors exit
+// with 1 and interrupted renders with 130 like everywhere else, so a render farm scheduler can tell a slice that
+// failed from one that is done but for frames of other nodes.
+const EXIT_CLAIMED_ELSEWHERE: i32 = 3;
+
+// Render the frames of the animation of the scene one after the other, each into a file of its own named after
+// `pattern`, or into the video of --video. The world is built once, its objects move with the time of the rays, and
+// before every frame the acceleration structures are refitted to where the moving objects are during it rather than
+// along their whole path (not with --arena, which flattens them). The camera rays of every frame are spread over the
+// time the shutter is open around it, so whatever moves meanwhile is blurred. With --focus-at or --focus-on the camera
+// focuses again at every frame, following what it focuses on. --frame-step picks every Nth frame of the range, and
+// --skip-existing leaves out the frames that are done already.
+pub fn run(options: &Options, mut scene: Scene, frames: RangeInclusive<i32>, pattern: &str) {
+    let frames: Vec<i32> = frames.step_by(options.frame_step.unwrap_or(1)).collect();
+    let mut world: Box<dyn Hittable> = if options.arena {
+        Box::new(Arena::new(std::mem::take(&mut scene.world), scene.accelerator))
+    } else {
+        Box::new(AcceleratedList::new(
+            std::mem::take(&mut scene.world),
+            scene.accelerator,
+        ))
+    };
+    let cameras: Vec<_> = frames
+        .iter()
+        .map(|&frame| {
+            let frame = frame as Float;
+            let shutter = scene.camera_at(frame).shutter;
+            let (open, close) = (frame - shutter / 2.0, frame + shutter / 2.0);
+            // Frames that don't see what to focus on, e.g. before an object comes into view, keep their focus
+            let focus_dist = options.focus.as_ref().and_then(|focus| {
+                scene
+                    .camera_at(frame)
+                    .focus_distance(world.as_ref(), focus, frame)
+                    .map_err(|err| warn!("frame {frame}: {err}, keeping the focus distance"))
+                    .ok()
+            });
+            let settings_at = |time: Float| match focus_dist {
+                Some(focus_dist) => scene.camera_at(time).with_focus_dist(focus_dist),
+                None => scene.camera_at(time),
+            };
+            let camera = Camera::new(settings_at(open))
+                .with_shutter(open..close, settings_at(close))
+                .with_next_frame(scene.camera_at(frame + 1.0))
+                .with_light_links(scene.light_links.clone())
+                .with_lens(scene.lens.as_deref())
+                .with_aperture_mask(scene.aperture_mask.clone())
+                .with_lut(scene.lut.clone());
+            (camera, open..close)
+        })
+        .collect();
+
+    crate::interrupt::install_handler();
+    #[cfg(feature = "video")]
+    let mut video: Option<Encoder> = None;
+    let mut claimed_elsewhere = vec![];
+    for (frame, (camera, shutter)) in frames.into_iter().zip(cameras) {
+        let path = frame_path(pattern, frame);
+        let part = match options.skip_existing {
+            true => match claim(&path) {
+                Ok(Claim::Done) => {
+                    info!("frame {frame}: {} exists, skipping", path.display());
+                    continue;
+                }
+                Ok(Claim::Elsewhere(part)) => {
+                    info!("frame {frame}: claimed by {}, skipping", part.display());
+                    claimed_elsewhere.push(frame);
+                    continue;
+                }
+                Ok(Claim::Ours(part)) => Some(part),
+                Err(err) => {
+                    error!("{}: {err}", path.display());
+                    exit(1);
+                }
+            },
+            false => None,
+        };
+        // Whoever exits early gives up the claim, so another run can take the frame
+        let give_up = |status: i32| -> ! {
+            if let Some(part) = &part {
+                let _ = fs::remove_file(part);
+            }
+            exit(status)
+        };
+        let camera = camera
+            .with_packets(options.packets)
+            .with_wavefront(options.wavefront)
+            .with_ray_sorting(options.sort_rays)
+            .with_crop(options.crop)
+            .with_integrator(options.integrator)
+            .with_layer(options.layer.as_deref())
+            .with_stop(Some(&INTERRUPTED))
+            .with_pause(Some(&PAUSED))
+            .with_time_budget(options.time);
+        if world.fit_to(shutter) {
+            rustracer::debug!("frame {frame}: refitted the acceleration structures");
+        }
+        let progress = crate::scanlines_progress(options.quiet);
+        let (image, info) = camera
+            .render_image(world.as_ref(), progress.as_ref())
+            .unwrap_or_else(|err| {
+                error!("{err}");
+                give_up(1);
+            });
+        progress.finish();
+        // A partial frame would look like a glitch in the sequence
+        if info.stopped || INTERRUPTED.load(Ordering::Relaxed) {
+            warn!("interrupted at frame {frame}");
+            // The video ends with the last whole frame
+            #[cfg(feature = "video")]
+            if let Some(video) = video {
+                finish_video(video, options);
+            }
+            give_up(130);
+        }
+        #[cfg(feature = "video")]
+        if let Some(path) = &options.video {
+            let encoder = match &mut video {
+                Some(encoder) => encoder,
+                None => video.insert(
+                    Encoder::start(path, image.width(), image.height(), options.fps.unwrap_or(DEFAULT_FPS))
+                        .unwrap_or_else(|err| {
+                            error!("{}: {err}", path.display());
+                            exit(1);
+                        }),
+                ),
+            };
+            if let Err(err) = encoder.write_frame(&image) {
+                error!("{}: {err}", path.display());
+                exit(1);
+            }
+            rustracer::debug!("frame {frame}: encoded");
+            continue;
+        }
+        // A claimed frame is written into its claim, which becomes the frame once it's complete
+        let written = File::create(part.as_ref().unwrap_or(&path)).and_then(|file| {
+            let mut out = BufWriter::new(file);
+            write_image(&image, ImageFormat::of_path(&path), &mut out)?;
+            out.flush()?;
+            match &part {
+                Some(part) => fs::rename(part, &path),
+                None => Ok(()),
+            }
+        });
+        if let Err(err) = written {
+            error!("{}: {err}", path.display());
+            give_up(1);
+        }
+        info!("frame {frame}: wrote {}", path.display());
+    }
+    #[cfg(feature = "video")]
+    if let Some(video) = video {
+        finish_video(video, options);
+    }
+    if !claimed_elsewhere.is_empty() {
+        let frames: Vec<String> = claimed_elsewhere.iter().map(i32::to_string).collect();
+        warn!(
+            "left out frames other processes are still rendering: {}",
+            frames.join(", ")
+        );
+        exit(EXIT_CLAIMED_ELSEWHERE);
+    }
+}
+
+enum Claim {
+    Done,               // the frame is written already
+    Elsewhere(PathBuf), // another process claimed the frame with this file
+    Ours(PathBuf),      // this process claimed the frame with this file, for writing it into
+}
+
+// Claim a frame for rendering it with --skip-existing, unless its file exists. The claim is the file of the frame with
+// `.part` appended, created exclusively so that of the processes rendering the same frames, e.g. render farm nodes
+// sharing the output directory, only one gets it. The frame is written into the claim and renamed into place when
+// complete, so a frame that exists is never partial. A process that dies leaves its claims behind, they have to be
+// removed for the frames to be rendered again.
+fn claim(path: &Path) -> io::Result<Claim> {
+    if path.exists() {
+        return Ok(Claim::Done);
+    }
+    let mut part = path.as_os_str().to_owned();
+    part.push(".part");
+    let part = PathBuf::from(part);
+    match OpenOptions::new().write(true).create_new(true).open(&part) {
+        // The frame may have been renamed into place since it was looked for
+        Ok(_) if path.exists() => {
+            fs::remove_file(&part)?;
+            Ok(Claim::Done)
+        }
+        Ok(_) => Ok(Claim::Ours(part)),
+        Err(err) if err.kind() == ErrorKind::AlreadyExists => Ok(Claim::Elsewhere(part)),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(feature = "video")]
+fn finish_video(video: Encoder, options: &Options) {
+    let path = options.video.as_ref().expect("there is a video to finish");
+    if let Err(err) = video.finish() {
+        error!("{}: {err}", path.display());
+        exit(1);
+    }
+    info!("wrote {}", path.display());
+}
+
+// The pattern with its run of `#` replaced by the frame number, padded with zeros to the length of the run.
+fn frame_path(pattern: &str, frame: i32) -> PathBuf {
+    let start = pattern
+        .find('#')
+        .expect("the pattern is checked when parsing the arguments");
+    let width = pattern[start..].chars().take_while(|&c| c == '#').count();
+    let end = start + width;
+    PathBuf::from(format!("{}{frame:0width$}{}", &pattern[..start], &pattern[end..]))
+}
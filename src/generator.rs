@@ -0,0 +1,204 @@
+use std::sync::Arc;
+
+use crate::{
+    accel::Accelerator,
+    animation::Keyframes,
+    bvh::BvhSettings,
+    camera::CameraSettings,
+    color::Color,
+    color_space::ColorSpace,
+    examples::Quads,
+    exposure::DEFAULT_SENSOR_HEIGHT,
+    filter::Filter,
+    float::Float,
+    hittable::HittableList,
+    id::Names,
+    light_linking::LightLinks,
+    material::{Dielectric, Lambertian, Material, Metal},
+    post::{Bloom, FilmGrain, LensFlare, WhiteBalance},
+    projection::Projection,
+    rng::Rng,
+    scene::Scene,
+    sphere::{Sphere, SphereList},
+    stereo::Stereo,
+    transform::Transform,
+    vec3::{Point, Vec3},
+};
+
+// Random scene in the style of the cover of "Ray Tracing in One Weekend": small spheres and boxes of random materials
+// scattered over a ground plane around three big spheres. The objects sit on a grid of cells one unit wide that grows
+// with their number, so any count from a handful to millions renders as the same kind of picture, which makes the
+// scene a stress test of the acceleration structures. The same settings always give the same scene.
+#[derive(Copy, Clone, Debug)]
+pub struct GeneratorSettings {
+    pub seed: u64,
+    pub spheres: usize,
+    pub boxes: usize,
+    pub mix: [Float; 3], // weights of diffuse, metal and glass materials
+}
+
+impl Default for GeneratorSettings {
+    // The cover of the book
+    fn default() -> Self {
+        GeneratorSettings {
+            seed: 0,
+            spheres: 484,
+            boxes: 0,
+            mix: [0.8, 0.15, 0.05],
+        }
+    }
+}
+
+// Width of the grid of the book, the camera moves away from larger grids to keep them in the picture.
+const BOOK_GRID: Float = 22.0;
+
+// Centers of the three big spheres in the middle of the grid, no small object is placed close to them.
+const BIG_SPHERES: [Point; 3] = [
+    Point::new(-4.0, 1.0, 0.0),
+    Point::new(0.0, 1.0, 0.0),
+    Point::new(4.0, 1.0, 0.0),
+];
+
+pub fn generate(settings: &GeneratorSettings, bvh: BvhSettings) -> Scene {
+    let accelerator = Accelerator::Bvh(bvh);
+    let mut rng = Rng::seed_from_u64(settings.seed);
+    let total = settings.spheres + settings.boxes;
+    // The cells around the big spheres stay empty, the grid needs about 30 more of them
+    let grid = (((total + 30) as Float).sqrt().ceil() as i64).max(BOOK_GRID as i64);
+
+    let big_materials: [Arc<dyn Material>; 3] = [
+        Arc::new(Lambertian::new(Color::new(0.4, 0.2, 0.1))),
+        Arc::new(Dielectric::new(1.5)),
+        Arc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0)),
+    ];
+    let mut spheres: Vec<Sphere> = BIG_SPHERES
+        .into_iter()
+        .zip(big_materials)
+        .map(|(center, material)| Sphere::new(center, 1.0, material))
+        .collect();
+    let mut boxes = Quads::default();
+    let mut box_materials: Vec<Arc<dyn Material>> = vec![Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))];
+    // Ground, far enough out to reach the horizon behind the grid
+    let ground = 50.0 * grid as Float;
+    boxes.add(
+        Point::new(-ground, 0.0, -ground),
+        Vec3::new(0.0, 0.0, 2.0 * ground),
+        Vec3::new(2.0 * ground, 0.0, 0.0),
+        0,
+    );
+
+    let (mut spheres_left, mut boxes_left) = (settings.spheres, settings.boxes);
+    let cells = (-grid / 2..grid - grid / 2).flat_map(|a| (-grid / 2..grid - grid / 2).map(move |b| (a, b)));
+    for (a, b) in cells {
+        if spheres_left + boxes_left == 0 {
+            break;
+        }
+        let center = Point::new(
+            a as Float + 0.1 + 0.8 * rng.next_float(),
+            0.0,
+            b as Float + 0.1 + 0.8 * rng.next_float(),
+        );
+        if BIG_SPHERES
+            .iter()
+            .any(|&big| (center - Point::new(big.x, 0.0, big.z)).length() < 1.4)
+        {
+            continue;
+        }
+        let material = random_material(&mut rng, &settings.mix);
+        // Spread the spheres and boxes evenly over the grid
+        let sphere = rng.next_float() * ((spheres_left + boxes_left) as Float) < spheres_left as Float;
+        if sphere {
+            spheres.push(Sphere::new(center + Vec3::new(0.0, 0.2, 0.0), 0.2, material));
+            spheres_left -= 1;
+        } else {
+            let size = Vec3::new(
+                rng.next_float_ranged(0.2, 0.4),
+                rng.next_float_ranged(0.2, 0.4),
+                rng.next_float_ranged(0.2, 0.4),
+            );
+            let transform = Transform::translation(Vec3::new(-size.x / 2.0, 0.0, -size.z / 2.0))
+                .then(Transform::rotation(1, rng.next_float_ranged(0.0, 90.0)))
+                .then(Transform::translation(center.to_vec()));
+            boxes.add_box(size, &transform, box_materials.len());
+            box_materials.push(material);
+            boxes_left -= 1;
+        }
+    }
+
+    // Framed like the cover of the book, from further away for larger grids
+    let distance = grid as Float / BOOK_GRID;
+    let camera = CameraSettings {
+        aspect_ratio: 16.0 / 9.0,
+        image_width: 400,
+        samples_per_pixel: 100,
+        max_depth: 50,
+
+        vfov: 20.0,
+        look_from: Point::new(13.0 * distance, 2.0 * distance, 3.0 * distance),
+        look_at: Point::new(0.0, 0.0, 0.0),
+        vup: Vec3::new(0.0, 1.0, 0.0),
+
+        defocus_angle: 0.6,
+        focus_dist: 10.0 * distance,
+
+        filter: Filter::Box,
+        projection: Projection::Perspective,
+        shutter: 0.5,
+        stereo: Stereo::Mono,
+        interocular: 0.065,
+        exposure: 1.0,
+        sensor_height: DEFAULT_SENSOR_HEIGHT,
+        aperture_blades: 0,
+        aperture_rotation: 0.0,
+        shift_x: 0.0,
+        shift_y: 0.0,
+        tilt: 0.0,
+        swing: 0.0,
+        vignetting: 0.0,
+        chromatic_aberration: 0.0,
+        bloom: Bloom::default(),
+        flare: LensFlare::default(),
+        grain: FilmGrain::default(),
+        white_balance: WhiteBalance::default(),
+        color_space: ColorSpace::default(),
+        background: None,
+    };
+
+    let world: HittableList = vec![
+        Box::new(SphereList::new(spheres, accelerator)),
+        Box::new(boxes.mesh(box_materials, accelerator)),
+    ];
+    Scene {
+        camera,
+        world,
+        accelerator,
+        files: vec![],
+        camera_keyframes: Keyframes::default(),
+        names: Names::default(),
+        light_links: LightLinks::default(),
+        lens: None,
+        aperture_mask: None,
+        lut: None,
+    }
+}
+
+// Diffuse, metal or glass material, picked with the weights of `mix`.
+fn random_material(rng: &mut Rng, mix: &[Float; 3]) -> Arc<dyn Material> {
+    let choice = rng.next_float() * mix.iter().sum::<Float>();
+    let random_color = |rng: &mut Rng, min, max| {
+        Color::new(
+            rng.next_float_ranged(min, max),
+            rng.next_float_ranged(min, max),
+            rng.next_float_ranged(min, max),
+        )
+    };
+    if choice < mix[0] {
+        let albedo = random_color(rng, 0.0, 1.0) * random_color(rng, 0.0, 1.0);
+        Arc::new(Lambertian::new(albedo))
+    } else if choice < mix[0] + mix[1] {
+        let albedo = random_color(rng, 0.5, 1.0);
+        Arc::new(Metal::new(albedo, rng.next_float_ranged(0.0, 0.5)))
+    } else {
+        Arc::new(Dielectric::new(1.5))
+    }
+}
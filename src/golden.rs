@@ -0,0 +1,172 @@
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+use rustracer::{
+    accel::AcceleratedList,
+    bvh::BvhSettings,
+    camera::Camera,
+    error::Result,
+    output::{read_ppm, to_rgb8, write_ppm, Framebuffer},
+    progress::NoProgress,
+    rng::Rng,
+    scene::Scene,
+    util::with_rng,
+};
+
+// Seed of the random sequence of every reference render, so that the same build renders the same image every time.
+const SEED: u64 = 0x601d;
+
+// Highest root mean square error of any channel between a render and its reference, in 8 bit values scaled to 0-1.
+// Other builds (e.g. with the `f32` feature) and other platforms trace slightly different paths, and any change to the
+// order of the random numbers gives the noise of the image a different pattern. The images are compared in blocks of
+// pixels, which averages most of the noise out, while a surface that got darker or changed its color stands out.
+pub const DEFAULT_TOLERANCE: f64 = 0.01;
+
+// Width and height of the blocks of pixels whose averages are compared.
+const BLOCK: usize = 4;
+
+enum Outcome {
+    Passed([f64; 3]), // error of every channel
+    Updated,
+}
+
+// Regression test of the images of the renderer, e.g.
+//
+//   rustracer --golden scenes/golden
+//
+// renders every `.scene` file in the directory with a fixed seed and compares the image with the reference image of
+// the same name with the extension `.ppm`, see `DEFAULT_TOLERANCE`. The image of a scene that fails is written next to
+// the reference with the extension `.failed.ppm`. With `update` the references are written instead, after a change
+// that is meant to change the images. Returns false if any of the scenes failed. `tests/golden.rs` runs the check of
+// `scenes/golden` with `cargo test`.
+pub fn run(dir: &Path, tolerance: f64, update: bool) -> Result<bool> {
+    let mut scenes: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<_>>()?;
+    scenes.retain(|path| path.extension().is_some_and(|extension| extension == "scene"));
+    scenes.sort();
+
+    let results: Vec<_> = scenes.iter().map(|scene| check(scene, tolerance, update)).collect();
+    let failed = results.iter().filter(|result| result.is_err()).count();
+    if update {
+        eprintln!("Golden images: {} updated, {failed} failed", results.len() - failed);
+    } else {
+        eprintln!(
+            "Golden images: {} passed, {failed} failed (tolerance {tolerance})",
+            results.len() - failed
+        );
+    }
+    for (scene, result) in scenes.iter().zip(&results) {
+        match result {
+            Ok(Outcome::Passed([r, g, b])) => {
+                eprintln!("  [ok]      {} (RMSE {r:.4} {g:.4} {b:.4})", scene.display())
+            }
+            Ok(Outcome::Updated) => eprintln!("  [updated] {}", reference(scene).display()),
+            Err(message) => eprintln!("  [failed]  {message}"),
+        }
+    }
+    Ok(failed == 0)
+}
+
+fn reference(scene: &Path) -> PathBuf {
+    scene.with_extension("ppm")
+}
+
+fn check(scene: &Path, tolerance: f64, update: bool) -> std::result::Result<Outcome, String> {
+    let image = render(scene).map_err(|err| format!("{}: {err}", scene.display()))?;
+    let reference = reference(scene);
+    if update {
+        write_image(&image, &reference)?;
+        return Ok(Outcome::Updated);
+    }
+
+    let (width, height, expected) = File::open(&reference)
+        .and_then(|mut file| read_ppm(&mut file))
+        .map_err(|err| format!("{}: {err}, write it with --update-golden", reference.display()))?;
+    if (width, height) != (image.width(), image.height()) {
+        return Err(format!(
+            "{}: the image is {}x{}, the reference {width}x{height}",
+            scene.display(),
+            image.width(),
+            image.height()
+        ));
+    }
+    let error = rmse(&image, &expected);
+    if error.iter().any(|&channel| channel > tolerance) {
+        let failed = scene.with_extension("failed.ppm");
+        write_image(&image, &failed)?;
+        let [r, g, b] = error;
+        return Err(format!(
+            "{}: RMSE {r:.4} {g:.4} {b:.4} above the tolerance, the image is in {}",
+            scene.display(),
+            failed.display()
+        ));
+    }
+    Ok(Outcome::Passed(error))
+}
+
+// Render the scene with its own settings and the default acceleration structure.
+fn render(scene: &Path) -> Result<Framebuffer> {
+    with_rng(|rng| *rng = Rng::seed_from_u64(SEED));
+    let scene = Scene::load(scene, BvhSettings::default())?;
+    let camera = Camera::new(scene.camera)
+        .with_light_links(scene.light_links)
+        .with_lens(scene.lens.as_deref())
+        .with_aperture_mask(scene.aperture_mask)
+        .with_lut(scene.lut);
+    let world = AcceleratedList::new(scene.world, scene.accelerator);
+    let (image, _) = camera.render_image(&world, &NoProgress)?;
+    Ok(image)
+}
+
+fn write_image(image: &Framebuffer, path: &Path) -> std::result::Result<(), String> {
+    File::create(path)
+        .and_then(|mut file| write_ppm(image, &mut file))
+        .map_err(|err| format!("{}: {err}", path.display()))
+}
+
+// Root mean square error of every channel between the block averages of the image and of the expected 8 bit values.
+fn rmse(image: &Framebuffer, expected: &[[u8; 3]]) -> [f64; 3] {
+    let actual: Vec<[u8; 3]> = image
+        .pixels()
+        .iter()
+        .map(|&color| to_rgb8(color, image.color_space()))
+        .collect();
+    let (actual, expected) = (
+        block_averages(&actual, image.width()),
+        block_averages(expected, image.width()),
+    );
+    let mut sums = [0.0; 3];
+    for (actual, expected) in actual.iter().zip(&expected) {
+        for (channel, sum) in sums.iter_mut().enumerate() {
+            let difference = (actual[channel] - expected[channel]) / 255.0;
+            *sum += difference * difference;
+        }
+    }
+    sums.map(|sum| (sum / expected.len().max(1) as f64).sqrt())
+}
+
+// Averages of the blocks of `BLOCK` by `BLOCK` pixels of an image `width` pixels wide, the blocks at the right and
+// bottom edges may be smaller.
+fn block_averages(pixels: &[[u8; 3]], width: usize) -> Vec<[f64; 3]> {
+    let height = pixels.len() / width.max(1);
+    let mut averages = vec![];
+    for y0 in (0..height).step_by(BLOCK) {
+        for x0 in (0..width).step_by(BLOCK) {
+            let mut sum = [0.0; 3];
+            let mut count = 0.0;
+            for y in y0..(y0 + BLOCK).min(height) {
+                for x in x0..(x0 + BLOCK).min(width) {
+                    for (sum, value) in sum.iter_mut().zip(pixels[y * width + x]) {
+                        *sum += value as f64;
+                    }
+                    count += 1.0;
+                }
+            }
+            averages.push(sum.map(|sum| sum / count));
+        }
+    }
+    averages
+}
@@ -0,0 +1,380 @@
+use crate::float::consts::{LN_2, PI};
+
+use crate::{
+    color::Color,
+    float::Float,
+    hittable::Hit,
+    material::{Material, Scatter},
+    ray::Ray,
+    util::{degrees_to_radians, random_double},
+    vec3::Vec3,
+};
+
+// Number of scattering lobes modeled separately: reflection (R), transmission (TT) and transmission after one
+// internal reflection (TRT). Longer paths are summed up into a single last lobe.
+const P_MAX: usize = 3;
+
+// Absorption of the two melanin pigments, per unit of concentration.
+const EUMELANIN: Color = Color::new(0.419, 0.697, 1.37);
+const PHEOMELANIN: Color = Color::new(0.187, 0.4, 1.05);
+
+// Scattering from human hair and animal fur, the model of "A Practical and Controllable Hair and Fur Model for
+// Production Path Tracing" by Chiang et al. as described in PBRT. A fiber is a rough dielectric cylinder tilted by its
+// cuticle scales, light is reflected off its surface or passes through it, getting colored by the pigment inside.
+// It's meant for curves (see `curve.rs`): it needs the direction of the fiber (`Hit::tangent`) and where across the
+// fiber the ray hit (the v surface coordinate).
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Hair {
+    pub sigma_a: Color, // absorption inside the fiber per unit of its diameter
+    pub eta: Float,     // index of refraction of the fiber
+    pub beta_m: Float,  // longitudinal roughness, from 0 to 1
+    pub beta_n: Float,  // azimuthal roughness, from 0 to 1
+    pub alpha: Float,   // tilt of the cuticle scales in degrees
+}
+
+impl Hair {
+    pub fn new(sigma_a: Color) -> Hair {
+        Hair {
+            sigma_a,
+            eta: 1.55,
+            beta_m: 0.3,
+            beta_n: 0.3,
+            alpha: 2.0,
+        }
+    }
+
+    // Hair colored by the concentrations of the dark brown eumelanin and of the reddish pheomelanin, e.g. 0.3 for
+    // blond, 1.3 for brown and 8 for black hair.
+    pub fn melanin(eumelanin: Float, pheomelanin: Float) -> Hair {
+        Hair::new(EUMELANIN * eumelanin.max(0.0) + PHEOMELANIN * pheomelanin.max(0.0))
+    }
+
+    // Hair of about the given color after multiple scattering. Needs the final azimuthal roughness.
+    pub fn from_color(color: Color, beta_n: Float) -> Hair {
+        let beta_n = beta_n.clamp(0.0, 1.0);
+        let scale = 5.969 - 0.215 * beta_n + 2.532 * beta_n.powi(2) - 10.73 * beta_n.powi(3)
+            + 5.574 * beta_n.powi(4)
+            + 0.245 * beta_n.powi(5);
+        let sigma_a = color.clamp(1e-3, 1.0).map(|c| (c.ln() / scale).powi(2));
+        Hair {
+            beta_n,
+            ..Hair::new(sigma_a)
+        }
+    }
+
+    pub fn with_ior(self, eta: Float) -> Hair {
+        Hair { eta, ..self }
+    }
+
+    pub fn with_roughness(self, beta_m: Float, beta_n: Float) -> Hair {
+        Hair {
+            beta_m: beta_m.clamp(0.0, 1.0),
+            beta_n: beta_n.clamp(0.0, 1.0),
+            ..self
+        }
+    }
+
+    pub fn with_scale_angle(self, alpha: Float) -> Hair {
+        Hair { alpha, ..self }
+    }
+}
+
+impl Material for Hair {
+    fn name(&self) -> &'static str {
+        "hair"
+    }
+
+    fn scatter(&self, ray_in: Ray, hit: Hit) -> Option<Scatter> {
+        // Frame of the fiber: x along it, z the normal and y across it, the direction the offset `h` is measured in
+        let x = hit.tangent.normalize();
+        if !x.length_squared().is_finite() || x.near_zero() {
+            return None;
+        }
+        let z = (hit.normal - Vec3::dot(hit.normal, x) * x).normalize();
+        let y = Vec3::cross(z, x);
+        let to_local = |v: Vec3| Vec3::new(Vec3::dot(v, x), Vec3::dot(v, y), Vec3::dot(v, z));
+        let wo = to_local(-ray_in.direction.normalize());
+        let h = (2.0 * hit.uv[1] - 1.0).clamp(-1.0, 1.0);
+
+        let fiber = Fiber::new(self, h, wo);
+        let (wi, weight) = fiber.sample()?;
+        let direction = wi.x * x + wi.y * y + wi.z * z;
+        Some(Scatter {
+            ray: Ray::new(hit.point, direction, ray_in.time),
+            attenuation: weight * hit.color,
+        })
+    }
+}
+
+// The hair model at a hit point, for light leaving in direction `wo` (in the frame of the fiber).
+struct Fiber<'a> {
+    hair: &'a Hair,
+    h: Float, // offset of the hit from the axis of the fiber, from -1 to 1
+    wo: Vec3,
+    v: [Float; P_MAX + 1], // variance of the longitudinal scattering of each lobe
+    s: Float,              // scale of the azimuthal scattering
+    sin_2k_alpha: [Float; 3],
+    cos_2k_alpha: [Float; 3],
+    gamma_o: Float,
+}
+
+impl Fiber<'_> {
+    fn new(hair: &Hair, h: Float, wo: Vec3) -> Fiber<'_> {
+        let beta_m = hair.beta_m;
+        let beta_n = hair.beta_n;
+        let v0 = (0.726 * beta_m + 0.812 * beta_m.powi(2) + 3.7 * beta_m.powi(20)).powi(2);
+        let s = (PI / 8.0).sqrt() * (0.265 * beta_n + 1.194 * beta_n.powi(2) + 5.372 * beta_n.powi(22));
+        // The scales tilt the R lobe by 2 alpha, TT by -alpha and TRT by -4 alpha
+        let mut sin_2k_alpha = [degrees_to_radians(hair.alpha).sin(), 0.0, 0.0];
+        let mut cos_2k_alpha = [safe_sqrt(1.0 - sin_2k_alpha[0].powi(2)), 0.0, 0.0];
+        for i in 1..3 {
+            sin_2k_alpha[i] = 2.0 * cos_2k_alpha[i - 1] * sin_2k_alpha[i - 1];
+            cos_2k_alpha[i] = cos_2k_alpha[i - 1].powi(2) - sin_2k_alpha[i - 1].powi(2);
+        }
+        Fiber {
+            hair,
+            h,
+            wo,
+            v: [v0, 0.25 * v0, 4.0 * v0, 4.0 * v0],
+            s: s.max(1e-4),
+            sin_2k_alpha,
+            cos_2k_alpha,
+            gamma_o: safe_asin(h),
+        }
+    }
+
+    // Sample the direction light arrives from, with the ratio of the scattered light to the probability of it.
+    fn sample(&self) -> Option<(Vec3, Color)> {
+        let sin_theta_o = self.wo.x;
+        let cos_theta_o = safe_sqrt(1.0 - sin_theta_o.powi(2));
+        let phi_o = self.wo.z.atan2(self.wo.y);
+
+        // Pick a lobe by how much light it carries
+        let ap_pdf = self.ap_pdf(cos_theta_o);
+        let mut pick = random_double();
+        let mut p = 0;
+        while p < P_MAX && pick >= ap_pdf[p] {
+            pick -= ap_pdf[p];
+            p += 1;
+        }
+
+        // Longitudinal angle around the direction mirrored by the tilted scales
+        let (sin_theta_op, cos_theta_op) = self.tilted(p, sin_theta_o, cos_theta_o);
+        let u = random_double().max(1e-5);
+        let v = self.v[p];
+        let cos_theta = 1.0 + v * (u + (1.0 - u) * (-2.0 / v).exp()).ln();
+        let sin_theta = safe_sqrt(1.0 - cos_theta.powi(2));
+        let cos_phi = (2.0 * PI * random_double()).cos();
+        let sin_theta_i = (-cos_theta * sin_theta_op + sin_theta * cos_phi * cos_theta_op).clamp(-1.0, 1.0);
+        let cos_theta_i = safe_sqrt(1.0 - sin_theta_i.powi(2));
+
+        // Azimuthal angle around the deflection of the lobe
+        let gamma_t = safe_asin(self.h / self.eta_p(sin_theta_o, cos_theta_o));
+        let dphi = if p < P_MAX {
+            phi(p, self.gamma_o, gamma_t) + sample_trimmed_logistic(random_double(), self.s, -PI, PI)
+        } else {
+            2.0 * PI * random_double()
+        };
+        let phi_i = phi_o + dphi;
+        let wi = Vec3::new(sin_theta_i, cos_theta_i * phi_i.cos(), cos_theta_i * phi_i.sin());
+
+        let mut pdf = 0.0;
+        for (p, ap_pdf) in ap_pdf.iter().enumerate().take(P_MAX) {
+            let (sin_theta_op, cos_theta_op) = self.tilted(p, sin_theta_o, cos_theta_o);
+            pdf += mp(cos_theta_i, cos_theta_op, sin_theta_i, sin_theta_op, self.v[p])
+                * ap_pdf
+                * np(dphi, p, self.s, self.gamma_o, gamma_t);
+        }
+        pdf += mp(cos_theta_i, cos_theta_o, sin_theta_i, sin_theta_o, self.v[P_MAX]) * ap_pdf[P_MAX] / (2.0 * PI);
+        if pdf.is_nan() || pdf <= 0.0 {
+            return None;
+        }
+        let weight = self.f_cos(wi) / pdf;
+        weight.is_finite().then_some((wi, weight))
+    }
+
+    // Scattered light for light arriving from `wi`, times the cosine of it to the normal.
+    fn f_cos(&self, wi: Vec3) -> Color {
+        let sin_theta_o = self.wo.x;
+        let cos_theta_o = safe_sqrt(1.0 - sin_theta_o.powi(2));
+        let phi_o = self.wo.z.atan2(self.wo.y);
+        let sin_theta_i = wi.x;
+        let cos_theta_i = safe_sqrt(1.0 - sin_theta_i.powi(2));
+        let phi_i = wi.z.atan2(wi.y);
+
+        let (gamma_t, transmittance) = self.transmittance(sin_theta_o, cos_theta_o);
+        let ap = self.ap(cos_theta_o, transmittance);
+        let dphi = phi_i - phi_o;
+        let mut f = Color::BLACK;
+        for (p, ap) in ap.iter().enumerate().take(P_MAX) {
+            let (sin_theta_op, cos_theta_op) = self.tilted(p, sin_theta_o, cos_theta_o);
+            let m = mp(cos_theta_i, cos_theta_op, sin_theta_i, sin_theta_op, self.v[p]);
+            f += *ap * m * np(dphi, p, self.s, self.gamma_o, gamma_t);
+        }
+        let m = mp(cos_theta_i, cos_theta_o, sin_theta_i, sin_theta_o, self.v[P_MAX]);
+        f + ap[P_MAX] * m / (2.0 * PI)
+    }
+
+    // Longitudinal angle of the outgoing direction as seen by lobe `p` on the tilted scales.
+    fn tilted(&self, p: usize, sin_theta_o: Float, cos_theta_o: Float) -> (Float, Float) {
+        let (sin, cos) = match p {
+            0 => (
+                sin_theta_o * self.cos_2k_alpha[1] - cos_theta_o * self.sin_2k_alpha[1],
+                cos_theta_o * self.cos_2k_alpha[1] + sin_theta_o * self.sin_2k_alpha[1],
+            ),
+            1 => (
+                sin_theta_o * self.cos_2k_alpha[0] + cos_theta_o * self.sin_2k_alpha[0],
+                cos_theta_o * self.cos_2k_alpha[0] - sin_theta_o * self.sin_2k_alpha[0],
+            ),
+            2 => (
+                sin_theta_o * self.cos_2k_alpha[2] + cos_theta_o * self.sin_2k_alpha[2],
+                cos_theta_o * self.cos_2k_alpha[2] - sin_theta_o * self.sin_2k_alpha[2],
+            ),
+            _ => (sin_theta_o, cos_theta_o),
+        };
+        (sin, cos.abs())
+    }
+
+    // Modified index of refraction for the azimuthal projection of the refracted ray.
+    fn eta_p(&self, sin_theta_o: Float, cos_theta_o: Float) -> Float {
+        (self.hair.eta.powi(2) - sin_theta_o.powi(2)).max(0.0).sqrt() / cos_theta_o.max(1e-6)
+    }
+
+    // Azimuthal angle of the refracted ray, and the light left after crossing the fiber once.
+    fn transmittance(&self, sin_theta_o: Float, cos_theta_o: Float) -> (Float, Color) {
+        let sin_theta_t = sin_theta_o / self.hair.eta;
+        let cos_theta_t = safe_sqrt(1.0 - sin_theta_t.powi(2));
+        let sin_gamma_t = self.h / self.eta_p(sin_theta_o, cos_theta_o);
+        let cos_gamma_t = safe_sqrt(1.0 - sin_gamma_t.powi(2));
+        let length = 2.0 * cos_gamma_t / cos_theta_t.max(1e-6);
+        (
+            safe_asin(sin_gamma_t),
+            self.hair.sigma_a.map(|sigma| (-sigma * length).exp()),
+        )
+    }
+
+    // Attenuation of every lobe by the Fresnel reflections and the absorption along its path.
+    fn ap(&self, cos_theta_o: Float, transmittance: Color) -> [Color; P_MAX + 1] {
+        let cos_gamma_o = safe_sqrt(1.0 - self.h * self.h);
+        let f = fresnel_dielectric(cos_theta_o * cos_gamma_o, self.hair.eta);
+        let mut ap = [Color::BLACK; P_MAX + 1];
+        ap[0] = Color::splat(f);
+        ap[1] = transmittance * (1.0 - f).powi(2);
+        for p in 2..P_MAX {
+            ap[p] = ap[p - 1] * transmittance * f;
+        }
+        let remaining = (Color::WHITE - transmittance * f).map(|c| c.max(1e-6));
+        ap[P_MAX] = ap[P_MAX - 1] * transmittance * f / remaining;
+        ap
+    }
+
+    // Probabilities to pick the lobes, proportional to the light they carry.
+    fn ap_pdf(&self, cos_theta_o: Float) -> [Float; P_MAX + 1] {
+        let sin_theta_o = safe_sqrt(1.0 - cos_theta_o.powi(2));
+        let (_, transmittance) = self.transmittance(sin_theta_o, cos_theta_o);
+        let ap = self.ap(cos_theta_o, transmittance).map(Color::luminance);
+        let sum: Float = ap.iter().sum();
+        if sum > 0.0 {
+            ap.map(|a| a / sum)
+        } else {
+            [1.0, 0.0, 0.0, 0.0]
+        }
+    }
+}
+
+// Longitudinal scattering function with variance `v`.
+fn mp(cos_theta_i: Float, cos_theta_o: Float, sin_theta_i: Float, sin_theta_o: Float, v: Float) -> Float {
+    let a = cos_theta_i * cos_theta_o / v;
+    let b = sin_theta_i * sin_theta_o / v;
+    if v <= 0.1 {
+        // In logarithms, the terms overflow for small variances
+        (log_i0(a) - b - 1.0 / v + LN_2 + (1.0 / (2.0 * v)).ln()).exp()
+    } else {
+        (-b).exp() * i0(a) / ((1.0 / v).sinh() * 2.0 * v)
+    }
+}
+
+// Azimuthal scattering function of lobe `p` for the difference `dphi` of the azimuthal angles.
+fn np(dphi: Float, p: usize, s: Float, gamma_o: Float, gamma_t: Float) -> Float {
+    let mut dphi = dphi - phi(p, gamma_o, gamma_t);
+    // Wrap into [-pi, pi]
+    dphi -= 2.0 * PI * ((dphi + PI) / (2.0 * PI)).floor();
+    trimmed_logistic(dphi, s, -PI, PI)
+}
+
+// Azimuthal angle lobe `p` leaves the fiber at.
+fn phi(p: usize, gamma_o: Float, gamma_t: Float) -> Float {
+    let p = p as Float;
+    2.0 * p * gamma_t - 2.0 * gamma_o + p * PI
+}
+
+// Modified Bessel function of the first kind of order 0.
+fn i0(x: Float) -> Float {
+    let mut value = 0.0;
+    let mut x2i = 1.0;
+    let mut factorial: Float = 1.0;
+    let mut four_i = 1.0;
+    for i in 0..10 {
+        if i > 1 {
+            factorial *= i as Float;
+        }
+        value += x2i / (four_i * factorial * factorial);
+        x2i *= x * x;
+        four_i *= 4.0;
+    }
+    value
+}
+
+fn log_i0(x: Float) -> Float {
+    if x > 12.0 {
+        x + 0.5 * (-(2.0 * PI).ln() + (1.0 / x).ln() + 1.0 / (8.0 * x))
+    } else {
+        i0(x).ln()
+    }
+}
+
+fn logistic(x: Float, s: Float) -> Float {
+    let x = x.abs();
+    (-x / s).exp() / (s * (1.0 + (-x / s).exp()).powi(2))
+}
+
+fn logistic_cdf(x: Float, s: Float) -> Float {
+    1.0 / (1.0 + (-x / s).exp())
+}
+
+// Logistic distribution restricted to [a, b].
+fn trimmed_logistic(x: Float, s: Float, a: Float, b: Float) -> Float {
+    logistic(x, s) / (logistic_cdf(b, s) - logistic_cdf(a, s))
+}
+
+fn sample_trimmed_logistic(u: Float, s: Float, a: Float, b: Float) -> Float {
+    let k = logistic_cdf(b, s) - logistic_cdf(a, s);
+    let x = -s * (1.0 / (u * k + logistic_cdf(a, s)) - 1.0).ln();
+    x.clamp(a, b)
+}
+
+// Fresnel reflectance of unpolarized light arriving from outside at a dielectric with index `eta`.
+fn fresnel_dielectric(cos_theta_i: Float, eta: Float) -> Float {
+    let cos_theta_i = cos_theta_i.clamp(-1.0, 1.0);
+    let (cos_theta_i, eta) = if cos_theta_i < 0.0 {
+        (-cos_theta_i, 1.0 / eta)
+    } else {
+        (cos_theta_i, eta)
+    };
+    let sin2_theta_t = (1.0 - cos_theta_i * cos_theta_i) / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_theta_t = safe_sqrt(1.0 - sin2_theta_t);
+    let parallel = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let perpendicular = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    (parallel * parallel + perpendicular * perpendicular) / 2.0
+}
+
+fn safe_sqrt(x: Float) -> Float {
+    x.max(0.0).sqrt()
+}
+
+fn safe_asin(x: Float) -> Float {
+    x.clamp(-1.0, 1.0).asin()
+}
@@ -0,0 +1,271 @@
+use std::{fs, io, ops::Range, path::Path, sync::Arc};
+
+use crate::{
+    aabb::Aabb,
+    accel::{Accelerator, SpatialIndex},
+    error::{Error, Result},
+    float::Float,
+    hittable::{Hit, Hittable},
+    material::Material,
+    mesh::hit_triangle,
+    ray::Ray,
+    vec3::{Point, Vec3},
+};
+
+// Grayscale image of the heights of a terrain, from a PGM or PPM file (plain text or binary, 8 or 16 bits). The
+// brightness of every pixel is its height, 0 for black and 1 for white; the channels of color images are averaged.
+pub struct HeightMap {
+    pub columns: usize,
+    pub rows: usize,
+    pub heights: Vec<Float>, // row by row from the top of the image
+}
+
+impl HeightMap {
+    pub fn load(path: &Path) -> Result<HeightMap> {
+        HeightMap::parse(&fs::read(path)?)
+    }
+
+    pub fn parse(data: &[u8]) -> Result<HeightMap> {
+        let invalid = |message: &str| Error::Io(io::Error::new(io::ErrorKind::InvalidData, message.to_string()));
+        let (channels, binary) = match data.get(..2) {
+            Some(b"P2") => (1, false),
+            Some(b"P3") => (3, false),
+            Some(b"P5") => (1, true),
+            Some(b"P6") => (3, true),
+            _ => return Err(invalid("not a PGM or PPM image")),
+        };
+
+        // The header is the magic number and three numbers separated by whitespace, comments run from '#' to the end
+        // of the line
+        let mut offset = 2;
+        let mut number = |what: &str| -> Result<usize> {
+            loop {
+                match data.get(offset) {
+                    Some(b'#') => {
+                        while data.get(offset).is_some_and(|&byte| byte != b'\n') {
+                            offset += 1;
+                        }
+                    }
+                    Some(byte) if byte.is_ascii_whitespace() => offset += 1,
+                    _ => break,
+                }
+            }
+            let start = offset;
+            while data.get(offset).is_some_and(u8::is_ascii_digit) {
+                offset += 1;
+            }
+            std::str::from_utf8(&data[start..offset])
+                .ok()
+                .and_then(|digits| digits.parse().ok())
+                .ok_or_else(|| invalid(&format!("invalid {what}")))
+        };
+        let (columns, rows, maximum) = (number("width")?, number("height")?, number("maximum value")?);
+        if columns < 2 || rows < 2 {
+            return Err(invalid("a height map needs at least 2 by 2 pixels"));
+        }
+        if !(1..=65535).contains(&maximum) {
+            return Err(invalid("the maximum value must be 1 to 65535"));
+        }
+        let count = columns
+            .checked_mul(rows)
+            .and_then(|pixels| pixels.checked_mul(channels))
+            .ok_or_else(|| invalid("image too large"))?;
+
+        let values: Vec<usize> = if binary {
+            // A single whitespace byte separates the header from the samples, which are big-endian if 2 bytes wide
+            let size = if maximum < 256 { 1 } else { 2 };
+            let samples = count
+                .checked_mul(size)
+                .and_then(|length| offset.checked_add(1)?.checked_add(length))
+                .and_then(|end| data.get(offset + 1..end))
+                .ok_or_else(|| invalid("truncated image"))?;
+            match size {
+                1 => samples.iter().map(|&sample| sample as usize).collect(),
+                _ => samples
+                    .chunks_exact(2)
+                    .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]) as usize)
+                    .collect(),
+            }
+        } else {
+            let text = std::str::from_utf8(&data[offset..]).map_err(|_| invalid("invalid samples"))?;
+            let values: Vec<usize> = text
+                .lines()
+                .flat_map(|line| line.split('#').next().unwrap_or("").split_whitespace())
+                .take(count)
+                .map(|token| token.parse().map_err(|_| invalid(&format!("invalid sample '{token}'"))))
+                .collect::<Result<_>>()?;
+            if values.len() < count {
+                return Err(invalid("truncated image"));
+            }
+            values
+        };
+
+        let scale = 1.0 / (channels * maximum) as Float;
+        let heights = values
+            .chunks_exact(channels)
+            .map(|pixel| pixel.iter().sum::<usize>() as Float * scale)
+            .collect();
+        Ok(HeightMap { columns, rows, heights })
+    }
+}
+
+// Terrain over a grid of heights, centered on the origin with the x axis across the columns and the z axis down the
+// rows, so that the top of an image is in the -z direction when seen from above. The cells of the grid are split into
+// two triangles each and shaded smoothly with normals from the slope of the heights, and kept in an acceleration
+// structure of their own instead of a mesh of vertices and faces.
+pub struct Heightfield {
+    grid: Grid,
+    material: Arc<dyn Material>,
+    index: SpatialIndex, // acceleration structure over the cells
+}
+
+struct Grid {
+    columns: usize,
+    rows: usize,
+    heights: Vec<Float>, // row by row, from -z to +z
+    size: Vec3,          // extent of the grid along x and z, and the height of a value of 1
+}
+
+impl Grid {
+    fn cells(&self) -> usize {
+        (self.columns - 1) * (self.rows - 1)
+    }
+
+    // Column and row of the first corner of a cell.
+    fn cell(&self, cell: usize) -> (usize, usize) {
+        (cell % (self.columns - 1), cell / (self.columns - 1))
+    }
+
+    fn height(&self, column: usize, row: usize) -> Float {
+        self.heights[row * self.columns + column] * self.size.y
+    }
+
+    fn point(&self, column: usize, row: usize) -> Point {
+        Point::new(
+            (column as Float / (self.columns - 1) as Float - 0.5) * self.size.x,
+            self.height(column, row),
+            (row as Float / (self.rows - 1) as Float - 0.5) * self.size.z,
+        )
+    }
+
+    // Corners of a cell: at its column and row, one column further, one row further and both.
+    fn corners(&self, cell: usize) -> [Point; 4] {
+        let (column, row) = self.cell(cell);
+        [
+            self.point(column, row),
+            self.point(column + 1, row),
+            self.point(column, row + 1),
+            self.point(column + 1, row + 1),
+        ]
+    }
+
+    // The two triangles of a cell, split along the diagonal from its first to its last corner.
+    fn triangles(&self, cell: usize) -> [[Point; 3]; 2] {
+        let [a, b, c, d] = self.corners(cell);
+        [[a, d, b], [a, c, d]]
+    }
+
+    // Normal of the surface at a point of the grid, from the differences of the heights around it.
+    fn normal(&self, column: usize, row: usize) -> Vec3 {
+        let (left, right) = (column.saturating_sub(1), (column + 1).min(self.columns - 1));
+        let (top, bottom) = (row.saturating_sub(1), (row + 1).min(self.rows - 1));
+        let dx = (right - left) as Float * self.size.x / (self.columns - 1) as Float;
+        let dz = (bottom - top) as Float * self.size.z / (self.rows - 1) as Float;
+        let slope_x = (self.height(right, row) - self.height(left, row)) / dx;
+        let slope_z = (self.height(column, bottom) - self.height(column, top)) / dz;
+        Vec3::new(-slope_x, 1.0, -slope_z).normalize()
+    }
+}
+
+impl Heightfield {
+    // A grid of `columns` by `rows` heights, `size` x and z wide and deep and with heights of 1 `size.y` high.
+    pub fn new(
+        columns: usize,
+        rows: usize,
+        heights: Vec<Float>,
+        size: Vec3,
+        material: Arc<dyn Material>,
+        accelerator: Accelerator,
+    ) -> Heightfield {
+        assert!(columns >= 2 && rows >= 2, "a heightfield needs at least 2 by 2 heights");
+        assert_eq!(heights.len(), columns * rows, "every point of the grid needs a height");
+        let grid = Grid {
+            columns,
+            rows,
+            heights,
+            size,
+        };
+        let bounds: Vec<Aabb> = (0..grid.cells())
+            .map(|cell| Aabb::from_points(grid.corners(cell)))
+            .collect();
+        Heightfield {
+            index: SpatialIndex::new(&bounds, accelerator),
+            grid,
+            material,
+        }
+    }
+
+    fn hit_cell(&self, cell: usize, ray: Ray, t_range: &Range<Float>) -> Option<Float> {
+        let [first, second] = self.grid.triangles(cell);
+        match hit_triangle(first, ray, t_range) {
+            Some(t) => Some(hit_triangle(second, ray, &(t_range.start..t)).unwrap_or(t)),
+            None => hit_triangle(second, ray, t_range),
+        }
+    }
+
+    fn cell_hit(&self, cell: usize, ray: Ray, t: Float) -> Hit<'_> {
+        let grid = &self.grid;
+        let (column, row) = grid.cell(cell);
+        let point = ray.at(t);
+        let [a, _, _, d] = grid.corners(cell);
+        // Position of the point within the cell
+        let s = ((point.x - a.x) / (d.x - a.x)).clamp(0.0, 1.0);
+        let r = ((point.z - a.z) / (d.z - a.z)).clamp(0.0, 1.0);
+        let [first, second] = grid.triangles(cell);
+        let triangle = if s >= r { first } else { second };
+        let [a, b, c] = triangle;
+        let normal = Vec3::cross(b - a, c - a).normalize();
+        let mut hit = Hit::new(ray, t, normal, self.material.as_ref()).with_uv([
+            (column as Float + s) / (grid.columns - 1) as Float,
+            1.0 - (row as Float + r) / (grid.rows - 1) as Float,
+        ]);
+
+        // The normals of the corners interpolated across the cell, turned to the side the ray hits
+        let shading = (grid.normal(column, row) * (1.0 - s) * (1.0 - r)
+            + grid.normal(column + 1, row) * s * (1.0 - r)
+            + grid.normal(column, row + 1) * (1.0 - s) * r
+            + grid.normal(column + 1, row + 1) * s * r)
+            .normalize();
+        if shading.length_squared().is_finite() {
+            hit.normal = if Vec3::dot(shading, hit.normal) < 0.0 {
+                -shading
+            } else {
+                shading
+            };
+        }
+        hit
+    }
+}
+
+impl Hittable for Heightfield {
+    fn hit(&self, ray: Ray, t_range: Range<Float>) -> Option<Hit<'_>> {
+        let mut closest: Option<(usize, Float)> = None;
+        self.index.traverse(ray, t_range, |cell, t_range| {
+            let t = self.hit_cell(cell, ray, &t_range)?;
+            closest = Some((cell, t));
+            Some(t)
+        });
+        let (cell, t) = closest?;
+        Some(self.cell_hit(cell, ray, t))
+    }
+
+    fn hit_any(&self, ray: Ray, t_range: Range<Float>) -> bool {
+        self.index.traverse_any(ray, t_range, |cell, t_range| {
+            self.hit_cell(cell, ray, &t_range).is_some()
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.index.bounds()
+    }
+}
@@ -1,21 +1,37 @@
 use std::ops::Range;
 
+use crate::aabb::Aabb;
+use crate::arena::Arena;
+use crate::color::Color;
+use crate::float::Float;
 use crate::material::Material;
 use crate::ray::Ray;
+use crate::transform::Transform;
 use crate::vec3::{Point, Vec3};
 
 #[derive(Copy, Clone)]
 pub struct Hit<'a> {
     pub point: Point,               // hit point coordinates
     pub normal: Vec3,               // surface normal at hit point
-    pub t: f64,                     // distance along the ray from ray's origin to hit point
+    pub t: Float,                   // distance along the ray from ray's origin to hit point
     pub front_face: bool,           // if true, hit ocurred from the front face side
     pub material: &'a dyn Material, // material of the hit surface
+    pub uv: [Float; 2],             // surface coordinates of the hit point, from 0 to 1
+    pub tangent: Vec3,              // direction the u coordinate grows in, e.g. along a curve, zero if unknown
+    pub color: Color,               // tint of diffuse reflection from vertex colors, white elsewhere
+    pub motion: Vec3,               // how far the hit point moves until the next frame, zero on objects standing still
+    pub object: u32,                // ID of the named object hit, see `id.rs`, 0 for unnamed ones
+    pub group: u32,                 // ID of the group of the object hit, 0 for objects without one
 }
 
 impl<'a> Hit<'a> {
+    // IDs of the name and of the group of the object hit.
+    pub fn ids(&self) -> [u32; 2] {
+        [self.object, self.group]
+    }
+
     // Assume that outward_normal is normalized
-    pub fn new(ray: Ray, t: f64, outward_normal: Vec3, material: &dyn Material) -> Hit {
+    pub fn new(ray: Ray, t: Float, outward_normal: Vec3, material: &dyn Material) -> Hit<'_> {
         let point = ray.at(t);
         let front_face = Vec3::dot(ray.direction, outward_normal) < 0.0;
         let normal = if front_face { outward_normal } else { -outward_normal };
@@ -25,18 +41,70 @@ impl<'a> Hit<'a> {
             t,
             front_face,
             material,
+            uv: [0.0, 0.0],
+            tangent: Vec3::ZERO,
+            color: Color::WHITE,
+            motion: Vec3::ZERO,
+            object: 0,
+            group: 0,
         }
     }
+
+    pub fn with_uv(self, uv: [Float; 2]) -> Hit<'a> {
+        Hit { uv, ..self }
+    }
+
+    pub fn with_tangent(self, tangent: Vec3) -> Hit<'a> {
+        Hit { tangent, ..self }
+    }
+
+    pub fn with_color(self, color: Color) -> Hit<'a> {
+        Hit { color, ..self }
+    }
 }
 
-pub trait Hittable {
-    fn hit(&self, ray: Ray, t_range: Range<f64>) -> Option<Hit>;
+pub trait Hittable: Send + Sync {
+    fn hit(&self, ray: Ray, t_range: Range<Float>) -> Option<Hit<'_>>;
+    fn bounding_box(&self) -> Aabb;
+
+    // Whether the ray hits anything within `t_range`, for occlusion tests like shadow rays. Objects with an
+    // acceleration structure override this to stop at the first hit they find instead of searching for the closest.
+    fn hit_any(&self, ray: Ray, t_range: Range<Float>) -> bool {
+        self.hit(ray, t_range).is_some()
+    }
+
+    // Add the geometry of the object, moved by `transform`, to the flat storage of `arena`. Returns false if the
+    // object can't be stored that way, it then stays an object of its own.
+    fn flatten(&self, transform: &Transform, arena: &mut Arena) -> bool {
+        let _ = (transform, arena);
+        false
+    }
+
+    // Fit the bounds of moving objects to where they are while the shutter is open, e.g. during one frame of an
+    // animation, instead of their whole path. Rays at other times may miss them afterwards. Returns whether any bounds
+    // changed, for the acceleration structures around the object to refit.
+    fn fit_to(&mut self, shutter: Range<Float>) -> bool {
+        let _ = shutter;
+        false
+    }
+
+    // Trace several rays at once. `hits` holds the closest hit of every ray found so far (e.g. in other objects), a
+    // ray only looks for hits closer than it and its entry is replaced when one is found. Objects with an acceleration
+    // structure override this to trace the rays as packets.
+    fn hit_packet<'a>(&'a self, rays: &[Ray], t_range: Range<Float>, hits: &mut [Option<Hit<'a>>]) {
+        for (ray, closest) in rays.iter().zip(hits) {
+            let t_end = closest.map_or(t_range.end, |hit| hit.t);
+            if let Some(hit) = self.hit(*ray, t_range.start..t_end) {
+                *closest = Some(hit);
+            }
+        }
+    }
 }
 
 pub type HittableList = Vec<Box<dyn Hittable>>;
 
 impl Hittable for HittableList {
-    fn hit(&self, ray: Ray, t_range: Range<f64>) -> Option<Hit> {
+    fn hit(&self, ray: Ray, t_range: Range<Float>) -> Option<Hit<'_>> {
         let mut hit_anything = None;
         let mut closest_so_far = t_range.end;
 
@@ -48,4 +116,22 @@ impl Hittable for HittableList {
         }
         hit_anything
     }
+
+    fn hit_any(&self, ray: Ray, t_range: Range<Float>) -> bool {
+        self.iter().any(|object| object.hit_any(ray, t_range.clone()))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.iter()
+            .fold(Aabb::EMPTY, |acc, object| acc.union(object.bounding_box()))
+    }
+
+    fn fit_to(&mut self, shutter: Range<Float>) -> bool {
+        // All objects are fitted, not only up to the first one that changed
+        let mut changed = false;
+        for object in self.iter_mut() {
+            changed |= object.fit_to(shutter.clone());
+        }
+        changed
+    }
 }
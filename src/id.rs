@@ -0,0 +1,173 @@
+use std::{collections::BTreeSet, ops::Range, sync::Arc};
+
+use crate::{
+    aabb::Aabb,
+    color::Color,
+    float::Float,
+    hittable::{Hit, Hittable},
+    material::{Material, Scatter},
+    medium::Medium,
+    ray::Ray,
+};
+
+// IDs of the named objects and materials of a scene, for picking them out of the image in compositing. The ID of a
+// name is its MurmurHash3, the way Cryptomatte computes it, so the IDs stay the same from render to render and
+// between scenes. 0 is left for everything unnamed.
+pub fn name_id(name: &str) -> u32 {
+    let id = murmur3(name.as_bytes());
+    // Cryptomatte stores the IDs as the bits of 32 bit floats, which mustn't be infinite, NaN or denormal
+    let exponent = (id >> 23) & 0xff;
+    if exponent == 0 || exponent == 0xff {
+        id ^ (1 << 23)
+    } else {
+        id
+    }
+}
+
+// MurmurHash3 of 32 bits with seed 0.
+pub(crate) fn murmur3(data: &[u8]) -> u32 {
+    let mix = |k: u32| k.wrapping_mul(0xcc9e2d51).rotate_left(15).wrapping_mul(0x1b873593);
+    let mut hash = 0_u32;
+    let chunks = data.chunks_exact(4);
+    let tail = chunks.remainder();
+    for chunk in chunks {
+        hash ^= mix(u32::from_le_bytes(chunk.try_into().expect("chunks of 4 bytes")));
+        hash = hash.rotate_left(13).wrapping_mul(5).wrapping_add(0xe6546b64);
+    }
+    if !tail.is_empty() {
+        let k = tail.iter().rev().fold(0, |k, &byte| (k << 8) | byte as u32);
+        hash ^= mix(k);
+    }
+    hash ^= data.len() as u32;
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85ebca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2ae35);
+    hash ^ (hash >> 16)
+}
+
+// Name of the render layer of the objects without a group, and of what the camera sees of the background.
+pub const DEFAULT_GROUP: &str = "default";
+
+// ID of a group of objects, 0 for the default group.
+pub fn group_id(name: &str) -> u32 {
+    if name == DEFAULT_GROUP {
+        0
+    } else {
+        name_id(name)
+    }
+}
+
+// Color of an ID in false-color images, made of the bits of the ID so that neighbors are told apart. Black for 0.
+pub fn id_color(id: u32) -> Color {
+    if id == 0 {
+        return Color::BLACK;
+    }
+    let channel = |shift: u32| 0.2 + 0.8 * ((id >> shift) & 0xff) as Float / 255.0;
+    Color::new(channel(0), channel(8), channel(16))
+}
+
+// Names the IDs of a scene come from.
+#[derive(Clone, Default)]
+pub struct Names {
+    pub objects: BTreeSet<String>,
+    pub materials: BTreeSet<String>,
+    pub groups: BTreeSet<String>,
+}
+
+// Named object or object of a group, its hits carry the IDs of the name and of the group.
+pub struct Tagged {
+    object: Box<dyn Hittable>,
+    id: u32,
+    group: u32,
+}
+
+impl Tagged {
+    pub fn new(object: Box<dyn Hittable>, name: Option<&str>, group: Option<&str>) -> Tagged {
+        Tagged {
+            object,
+            id: name.map_or(0, name_id),
+            group: group.map_or(0, group_id),
+        }
+    }
+}
+
+impl Hittable for Tagged {
+    fn hit(&self, ray: Ray, t_range: Range<Float>) -> Option<Hit<'_>> {
+        let hit = self.object.hit(ray, t_range)?;
+        Some(Hit {
+            object: self.id,
+            group: self.group,
+            ..hit
+        })
+    }
+
+    fn hit_any(&self, ray: Ray, t_range: Range<Float>) -> bool {
+        self.object.hit_any(ray, t_range)
+    }
+
+    fn fit_to(&mut self, shutter: Range<Float>) -> bool {
+        self.object.fit_to(shutter)
+    }
+
+    fn hit_packet<'a>(&'a self, rays: &[Ray], t_range: Range<Float>, hits: &mut [Option<Hit<'a>>]) {
+        // Only the hits the object replaces are its own
+        let before: Vec<Option<Float>> = hits.iter().map(|hit| hit.map(|hit| hit.t)).collect();
+        self.object.hit_packet(rays, t_range, hits);
+        for (hit, before) in hits.iter_mut().zip(before) {
+            if let Some(hit) = hit.as_mut().filter(|hit| Some(hit.t) != before) {
+                hit.object = self.id;
+                hit.group = self.group;
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.object.bounding_box()
+    }
+}
+
+// Material of a scene file, which has the ID of its name.
+pub struct NamedMaterial {
+    material: Arc<dyn Material>,
+    id: u32,
+}
+
+impl NamedMaterial {
+    pub fn new(material: Arc<dyn Material>, name: &str) -> NamedMaterial {
+        NamedMaterial {
+            material,
+            id: name_id(name),
+        }
+    }
+}
+
+impl Material for NamedMaterial {
+    fn scatter(&self, ray_in: Ray, hit: Hit) -> Option<Scatter> {
+        self.material.scatter(ray_in, hit)
+    }
+
+    fn name(&self) -> &'static str {
+        self.material.name()
+    }
+
+    fn emitted(&self, hit: &Hit) -> Color {
+        self.material.emitted(hit)
+    }
+
+    fn is_shadow_catcher(&self) -> bool {
+        self.material.is_shadow_catcher()
+    }
+
+    fn medium(&self) -> Option<Medium> {
+        self.material.medium()
+    }
+
+    fn scatter_between(&self, ray_in: Ray, hit: Hit, eta_from: Float, eta_to: Float) -> Option<Scatter> {
+        self.material.scatter_between(ray_in, hit, eta_from, eta_to)
+    }
+
+    fn id(&self) -> u32 {
+        self.id
+    }
+}
@@ -0,0 +1,102 @@
+use std::{ops::Range, sync::Arc};
+
+use crate::{
+    aabb::Aabb,
+    arena::Arena,
+    float::Float,
+    hittable::{Hit, Hittable},
+    packet::PACKET_SIZE,
+    ray::Ray,
+    transform::Transform,
+};
+
+// Placement of a shared object, usually a mesh, with its own transformation. Rays are moved into the space of the
+// object instead of the object into the scene, so the geometry and its acceleration structure (the bottom level) are
+// stored once however many instances use it. The acceleration structure the scene builds over its objects is the top
+// level, it only sees the instance bounds.
+pub struct Instance {
+    object: Arc<dyn Hittable>,
+    transform: Transform, // object to world space
+    bounds: Aabb,         // world space bounds
+}
+
+impl Instance {
+    pub fn new(object: Arc<dyn Hittable>, transform: Transform) -> Instance {
+        Instance {
+            bounds: transform.bounds(object.bounding_box()),
+            object,
+            transform,
+        }
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, ray: Ray, t_range: Range<Float>) -> Option<Hit<'_>> {
+        hit_placed(self.object.as_ref(), &self.transform, ray, t_range)
+    }
+
+    fn hit_any(&self, ray: Ray, t_range: Range<Float>) -> bool {
+        self.object.hit_any(object_ray(&self.transform.inverse(), ray), t_range)
+    }
+
+    fn flatten(&self, transform: &Transform, arena: &mut Arena) -> bool {
+        self.object.flatten(&self.transform.then(*transform), arena)
+    }
+
+    fn hit_packet<'a>(&'a self, rays: &[Ray], t_range: Range<Float>, hits: &mut [Option<Hit<'a>>]) {
+        let to_object = self.transform.inverse();
+        for (rays, hits) in rays.chunks(PACKET_SIZE).zip(hits.chunks_mut(PACKET_SIZE)) {
+            let mut object_rays = [rays[0]; PACKET_SIZE];
+            for (object, ray) in object_rays.iter_mut().zip(rays) {
+                *object = object_ray(&to_object, *ray);
+            }
+            // Only the distances of the hits found so far matter to the object
+            let mut object_hits = [None; PACKET_SIZE];
+            object_hits[..hits.len()].copy_from_slice(hits);
+            self.object.hit_packet(
+                &object_rays[..rays.len()],
+                t_range.clone(),
+                &mut object_hits[..rays.len()],
+            );
+            for ((ray, hit), object_hit) in rays.iter().zip(hits).zip(object_hits) {
+                if let Some(object_hit) = object_hit.filter(|object_hit| hit.is_none_or(|hit| object_hit.t < hit.t)) {
+                    *hit = Some(Hit {
+                        point: ray.at(object_hit.t),
+                        normal: self.transform.normal(object_hit.normal).normalize(),
+                        tangent: self.transform.vector(object_hit.tangent),
+                        motion: self.transform.vector(object_hit.motion),
+                        ..object_hit
+                    });
+                }
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounds
+    }
+}
+
+// Closest hit of the ray with `object` placed by `transform`, in world space.
+pub(crate) fn hit_placed<'a>(
+    object: &'a dyn Hittable,
+    transform: &Transform,
+    ray: Ray,
+    t_range: Range<Float>,
+) -> Option<Hit<'a>> {
+    let hit = object.hit(object_ray(&transform.inverse(), ray), t_range)?;
+    // The transformed normal still faces the ray, the sign of its dot product with the direction is preserved
+    Some(Hit {
+        point: ray.at(hit.t),
+        normal: transform.normal(hit.normal).normalize(),
+        tangent: transform.vector(hit.tangent),
+        motion: transform.vector(hit.motion),
+        ..hit
+    })
+}
+
+// The ray in the space of an object, `to_object` is the inverse of its placement.
+pub(crate) fn object_ray(to_object: &Transform, ray: Ray) -> Ray {
+    // The direction isn't normalized afterwards so that distances along both rays are the same
+    Ray::new(to_object.point(ray.origin), to_object.vector(ray.direction), ray.time)
+}
@@ -0,0 +1,157 @@
+use std::str::FromStr;
+
+use crate::{color::Color, float::Float, info, output::Framebuffer};
+
+// What the camera computes for every sample. Besides the path tracer there are false-color integrators for looking
+// into problems of the scene or the tracer rather than rendering a picture: the shading normals, the distances and the
+// surface coordinates of the surfaces the camera sees show problems with the geometry and its mapping, the number of
+// bounces of the paths shows where the path tracer spends its samples and where it converges slowly. The motion
+// vectors show how far the surfaces move on the image until the next frame, for temporal denoisers and compositing.
+// The IDs of named objects and materials (see `id.rs`) show them in colors of their own.
+//
+// The heatmaps of the work done color every pixel by the work done for its camera rays: the nodes of all acceleration
+// structures that were visited, or the triangles and spheres that were intersected. Bright spots show where rays
+// descend into many nodes or test many primitives, e.g. long thin triangles or overlapping instances. The counts come
+// from the counters of the `stats` feature, without it these heatmaps are black.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Integrator {
+    #[default]
+    Path,
+    Normals,        // shading normals, the components from -1 to 1 as colors from 0 to 1
+    Depth,          // distance to the closest hit, from white close by to black at the farthest hit in the image
+    Uv,             // surface coordinates as red and green
+    Motion,         // motion vectors, how far the surfaces move on the image until the next frame
+    Object,         // false colors of the IDs of the named objects, black for unnamed ones
+    Material,       // false colors of the IDs of the named materials
+    Bounces,        // heatmap of the number of rays along the path of every sample, up to the maximum depth
+    NodeVisits,     // heatmap of the acceleration structure nodes visited per camera ray
+    PrimitiveTests, // heatmap of the triangle and sphere intersection tests per camera ray
+}
+
+impl FromStr for Integrator {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Integrator, String> {
+        match s {
+            "path" => Ok(Integrator::Path),
+            "normals" => Ok(Integrator::Normals),
+            "depth" => Ok(Integrator::Depth),
+            "uv" => Ok(Integrator::Uv),
+            "motion" => Ok(Integrator::Motion),
+            "object" => Ok(Integrator::Object),
+            "material" => Ok(Integrator::Material),
+            "bounces" => Ok(Integrator::Bounces),
+            "nodes" => Ok(Integrator::NodeVisits),
+            "tests" => Ok(Integrator::PrimitiveTests),
+            _ => Err(format!(
+                "unknown integrator '{s}', expected 'path', 'normals', 'depth', 'uv', 'motion', 'object', 'material', \
+                 'bounces', 'nodes' or 'tests'"
+            )),
+        }
+    }
+}
+
+impl Integrator {
+    // Name of the integrator on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Integrator::Path => "path",
+            Integrator::Normals => "normals",
+            Integrator::Depth => "depth",
+            Integrator::Uv => "uv",
+            Integrator::Motion => "motion",
+            Integrator::Object => "object",
+            Integrator::Material => "material",
+            Integrator::Bounces => "bounces",
+            Integrator::NodeVisits => "nodes",
+            Integrator::PrimitiveTests => "tests",
+        }
+    }
+
+    // Whether the integrator needs the counters of the `stats` feature.
+    pub fn counts(self) -> bool {
+        matches!(self, Integrator::NodeVisits | Integrator::PrimitiveTests)
+    }
+
+    // Turn the values the integrator splatted into the colors of its image. The heatmaps and the depth are splatted as
+    // gray and scaled to the highest value in the image, which is logged, the bounces to the maximum depth of paths.
+    pub fn finish(self, image: &mut Framebuffer, max_depth: i32) {
+        let unit = match self {
+            Integrator::Depth => {
+                let max = scale(image, None, |depth| {
+                    if depth > 0.0 {
+                        displayed(Color::splat(1.0 - depth))
+                    } else {
+                        Color::BLACK
+                    }
+                });
+                info!("depth from white close by to black at a distance of {max}");
+                return;
+            }
+            Integrator::Bounces => {
+                scale(image, Some(max_depth as Float), heat_color);
+                return;
+            }
+            Integrator::Motion => {
+                let max = image
+                    .pixels()
+                    .iter()
+                    .map(|pixel| pixel.r.abs().max(pixel.g.abs()))
+                    .fold(0.0, Float::max);
+                let height = image.height();
+                for pixel in image.rows_mut(0, height) {
+                    let [x, y] = if max > 0.0 {
+                        [pixel.r / max, pixel.g / max]
+                    } else {
+                        [0.0, 0.0]
+                    };
+                    *pixel = displayed(Color::new(0.5 + 0.5 * x, 0.5 + 0.5 * y, 0.5));
+                }
+                info!("motion vectors from -{max:.1} to {max:.1} pixels per frame as red (right) and green (down)");
+                return;
+            }
+            Integrator::NodeVisits => "node visits per camera ray",
+            Integrator::PrimitiveTests => "primitive tests per camera ray",
+            Integrator::Path | Integrator::Normals | Integrator::Uv | Integrator::Object | Integrator::Material => {
+                return
+            }
+        };
+        let max = scale(image, None, heat_color);
+        info!("heatmap from black for none to red for {max:.1} {unit}");
+    }
+}
+
+// Colors the integrator computes, before the gamma encoding of the image when it's written.
+pub(crate) fn displayed(color: Color) -> Color {
+    color * color
+}
+
+// Colors of the heatmap from no work at all to the most work in the image, evenly spaced.
+const HEAT_COLORS: [Color; 6] = [
+    Color::new(0.0, 0.0, 0.0),
+    Color::new(0.0, 0.0, 1.0),
+    Color::new(0.0, 1.0, 1.0),
+    Color::new(0.0, 1.0, 0.0),
+    Color::new(1.0, 1.0, 0.0),
+    Color::new(1.0, 0.0, 0.0),
+];
+
+// Replace the gray values of the pixels of `image` by `color` of the values scaled to `max`, or to the highest value
+// in the image without it. Returns the value that was scaled to 1.
+fn scale(image: &mut Framebuffer, max: Option<Float>, color: impl Fn(Float) -> Color) -> Float {
+    let max = max.unwrap_or_else(|| image.pixels().iter().map(|pixel| pixel.r).fold(0.0, Float::max));
+    let height = image.height();
+    for pixel in image.rows_mut(0, height) {
+        *pixel = color(if max > 0.0 { pixel.r / max } else { 0.0 });
+    }
+    max
+}
+
+// Color of the heatmap at `t` between 0 and 1.
+fn heat_color(t: Float) -> Color {
+    let position = t.clamp(0.0, 1.0) * (HEAT_COLORS.len() - 1) as Float;
+    let index = (position as usize).min(HEAT_COLORS.len() - 2);
+    let fraction = position - index as Float;
+    displayed((1.0 - fraction) * HEAT_COLORS[index] + fraction * HEAT_COLORS[index + 1])
+}
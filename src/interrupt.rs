@@ -0,0 +1,57 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Set by the Ctrl-C handler, the camera stops rendering once it sees it.
+pub static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// Toggled by SIGUSR1 (`kill -USR1 <pid>`), and by Enter in watch mode, the camera waits while it's set.
+pub static PAUSED: AtomicBool = AtomicBool::new(false);
+
+// Pause the render if it's running and resume it if it's paused.
+pub fn toggle_pause() {
+    PAUSED.fetch_xor(true, Ordering::Relaxed);
+}
+
+// Handle Ctrl-C by setting `INTERRUPTED` instead of terminating the process, so the render can stop cleanly and write
+// what it has got so far. A second Ctrl-C terminates right away, for when stopping takes too long. On Unix SIGUSR1
+// toggles `PAUSED`. Implemented with the C runtime directly to keep the tracer free of dependencies.
+#[cfg(unix)]
+pub fn install_handler() {
+    const SIGINT: i32 = 2;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const SIGUSR1: i32 = 10;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    const SIGUSR1: i32 = 30; // macOS and the BSDs
+    extern "C" {
+        fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+        fn _exit(status: i32) -> !;
+    }
+    extern "C" fn handle(_: i32) {
+        if INTERRUPTED.swap(true, Ordering::Relaxed) {
+            // Only async-signal-safe functions may be called here
+            unsafe { _exit(130) };
+        }
+    }
+    // Atomic operations are async-signal-safe
+    extern "C" fn handle_pause(_: i32) {
+        toggle_pause();
+    }
+    unsafe {
+        signal(SIGINT, handle);
+        signal(SIGUSR1, handle_pause);
+    }
+}
+
+#[cfg(windows)]
+pub fn install_handler() {
+    extern "system" {
+        fn SetConsoleCtrlHandler(handler: extern "system" fn(u32) -> i32, add: i32) -> i32;
+    }
+    extern "system" fn handle(_: u32) -> i32 {
+        // Returning false passes the event on to the default handler, which terminates the process
+        !INTERRUPTED.swap(true, Ordering::Relaxed) as i32
+    }
+    unsafe { SetConsoleCtrlHandler(handle, 1) };
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn install_handler() {}
@@ -0,0 +1,255 @@
+use std::ops::Range;
+
+use crate::{
+    aabb::Aabb,
+    float::Float,
+    ray::Ray,
+    stats::{self, Counter},
+    vec3::Vec3,
+};
+
+// Costs used by the surface area heuristic, relative to stepping through a node.
+const TRAVERSAL_COST: Float = 1.0;
+const INTERSECTION_COST: Float = 80.0;
+const EMPTY_BONUS: Float = 0.5; // preference for splits that cut off empty space
+const MAX_PRIMITIVES: usize = 1; // nodes with more primitives are split if the heuristic allows it
+const MAX_DEPTH: usize = 64; // bounds the traversal stack
+
+// Kd-tree over primitives identified by their index. Unlike a BVH it partitions space rather than the primitives,
+// primitives straddling a split plane are referenced from both sides. The cells are disjoint and visited in order
+// along the ray, so the search can stop at the first cell that contains a hit, which pays off in scenes with very
+// uneven geometry, e.g. a detailed model in a large empty room.
+//
+// Split planes are placed at primitive bounds using the surface area heuristic, see "On building fast kd-trees for
+// ray tracing, and on doing that in O(N log N)" by Ingo Wald and Vlastimil Havran and the kd-tree of PBRT.
+pub struct KdTree {
+    nodes: Vec<KdNode>,
+    indices: Vec<usize>, // primitive indices, every leaf refers to a contiguous range
+    bounds: Aabb,
+}
+
+#[derive(Copy, Clone)]
+enum KdNode {
+    Interior { axis: usize, split: Float, above: usize }, // the child below the split is the next node
+    Leaf { start: usize, count: usize },
+}
+
+// Start or end of the bounds of a primitive along an axis, the candidate split positions.
+#[derive(Copy, Clone)]
+struct Edge {
+    position: Float,
+    primitive: usize,
+    start: bool,
+}
+
+impl KdTree {
+    pub fn new(bounds: &[Aabb]) -> KdTree {
+        let tree_bounds = bounds.iter().fold(Aabb::EMPTY, |acc, b| acc.union(*b));
+        let mut tree = KdTree {
+            nodes: vec![],
+            indices: vec![],
+            bounds: tree_bounds,
+        };
+        if !bounds.is_empty() {
+            let max_depth = (8.0 + 1.3 * (bounds.len() as Float).log2()).round() as usize;
+            tree.build(
+                tree_bounds,
+                (0..bounds.len()).collect(),
+                max_depth.min(MAX_DEPTH),
+                bounds,
+                0,
+            );
+        }
+        tree
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.bounds
+    }
+
+    fn build(&mut self, node_bounds: Aabb, primitives: Vec<usize>, depth: usize, bounds: &[Aabb], bad_refines: u32) {
+        let index = self.nodes.len();
+        let leaf = KdNode::Leaf {
+            start: self.indices.len(),
+            count: primitives.len(),
+        };
+        self.nodes.push(leaf);
+        if primitives.len() <= MAX_PRIMITIVES || depth == 0 {
+            self.indices.extend(primitives);
+            return;
+        }
+
+        // Try the longest axis first, the others only if it has no usable split
+        let extent = node_bounds.extent();
+        let longest = (0..3).max_by(|&a, &b| extent[a].total_cmp(&extent[b])).unwrap_or(0);
+        let mut best = None;
+        for axis in [longest, (longest + 1) % 3, (longest + 2) % 3] {
+            let mut edges: Vec<Edge> = primitives
+                .iter()
+                .flat_map(|&primitive| {
+                    let b = &bounds[primitive];
+                    [
+                        Edge {
+                            position: b.min[axis],
+                            primitive,
+                            start: true,
+                        },
+                        Edge {
+                            position: b.max[axis],
+                            primitive,
+                            start: false,
+                        },
+                    ]
+                })
+                .collect();
+            // Starts go before ends at the same position, the sweep in `best_split` relies on it
+            edges.sort_by(|a, b| a.position.total_cmp(&b.position).then(b.start.cmp(&a.start)));
+            if let Some((offset, cost)) = best_split(&edges, &node_bounds, axis) {
+                best = Some((axis, edges, offset, cost));
+                break;
+            }
+        }
+
+        let leaf_cost = INTERSECTION_COST * primitives.len() as Float;
+        let Some((axis, edges, offset, cost)) = best else {
+            self.indices.extend(primitives);
+            return;
+        };
+        // Allow a few splits that don't pay off immediately, they often lead to good ones further down
+        let bad_refines = if cost > leaf_cost { bad_refines + 1 } else { bad_refines };
+        if (cost > 4.0 * leaf_cost && primitives.len() < 16) || bad_refines == 3 {
+            self.indices.extend(primitives);
+            return;
+        }
+
+        let split = edges[offset].position;
+        let below = edges[..offset]
+            .iter()
+            .filter(|e| e.start)
+            .map(|e| e.primitive)
+            .collect();
+        let above = edges[offset + 1..]
+            .iter()
+            .filter(|e| !e.start)
+            .map(|e| e.primitive)
+            .collect();
+        let mut below_bounds = node_bounds;
+        let mut above_bounds = node_bounds;
+        below_bounds.max[axis] = split;
+        above_bounds.min[axis] = split;
+
+        self.build(below_bounds, below, depth - 1, bounds, bad_refines);
+        let above_index = self.nodes.len();
+        self.nodes[index] = KdNode::Interior {
+            axis,
+            split,
+            above: above_index,
+        };
+        self.build(above_bounds, above, depth - 1, bounds, bad_refines);
+    }
+
+    // Visit the leaves the ray passes through in order. `hit_primitive` intersects a primitive within the given range
+    // and returns the distance of the hit, which limits the rest of the search to closer primitives.
+    pub fn traverse(
+        &self,
+        ray: Ray,
+        t_range: Range<Float>,
+        mut hit_primitive: impl FnMut(usize, Range<Float>) -> Option<Float>,
+    ) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let inv_direction = Vec3::new(1.0 / ray.direction.x, 1.0 / ray.direction.y, 1.0 / ray.direction.z);
+        let mut t_range = t_range;
+        let Some(Range {
+            start: mut t_min,
+            end: mut t_max,
+        }) = self.bounds.clip(ray, inv_direction, &t_range)
+        else {
+            return;
+        };
+
+        // Far children still to visit with the part of the ray inside them
+        let mut stack = [(0, 0.0, 0.0); MAX_DEPTH];
+        let mut len = 0;
+        let mut index = 0;
+        loop {
+            // Cells are visited in order, so a hit before the current one ends the search
+            if t_range.end < t_min {
+                break;
+            }
+            stats::count(Counter::NodeVisits, 1);
+            match self.nodes[index] {
+                KdNode::Interior { axis, split, above } => {
+                    let origin = ray.origin[axis];
+                    let direction = ray.direction[axis];
+                    let t_split = (split - origin) * inv_direction[axis];
+                    let below_first = origin < split || (origin == split && direction <= 0.0);
+                    let (first, second) = if below_first {
+                        (index + 1, above)
+                    } else {
+                        (above, index + 1)
+                    };
+                    if t_split > t_max || t_split <= 0.0 {
+                        index = first;
+                    } else if t_split < t_min {
+                        index = second;
+                    } else {
+                        stack[len] = (second, t_split, t_max);
+                        len += 1;
+                        index = first;
+                        t_max = t_split;
+                    }
+                }
+                KdNode::Leaf { start, count } => {
+                    for &primitive in &self.indices[start..start + count] {
+                        if let Some(t) = hit_primitive(primitive, t_range.clone()) {
+                            t_range.end = t;
+                        }
+                    }
+                    if len == 0 {
+                        break;
+                    }
+                    len -= 1;
+                    (index, t_min, t_max) = stack[len];
+                }
+            }
+        }
+    }
+}
+
+// Cheapest split among the edges along one axis as the edge offset and its cost, None if no edge lies inside the
+// node.
+fn best_split(edges: &[Edge], node_bounds: &Aabb, axis: usize) -> Option<(usize, Float)> {
+    let extent = node_bounds.extent();
+    let (other0, other1) = ((axis + 1) % 3, (axis + 2) % 3);
+    let (d0, d1) = (extent[other0], extent[other1]);
+    let inv_area = 1.0 / node_bounds.surface_area();
+    let (min, max) = (node_bounds.min[axis], node_bounds.max[axis]);
+
+    let mut best: Option<(usize, Float)> = None;
+    let mut below = 0;
+    let mut above = edges.len() / 2;
+    for (offset, edge) in edges.iter().enumerate() {
+        if !edge.start {
+            above -= 1;
+        }
+        let t = edge.position;
+        if t > min && t < max {
+            let below_area = 2.0 * (d0 * d1 + (t - min) * (d0 + d1));
+            let above_area = 2.0 * (d0 * d1 + (max - t) * (d0 + d1));
+            let bonus = if below == 0 || above == 0 { EMPTY_BONUS } else { 0.0 };
+            let cost = TRAVERSAL_COST
+                + INTERSECTION_COST
+                    * (1.0 - bonus)
+                    * (below_area * inv_area * below as Float + above_area * inv_area * above as Float);
+            if best.is_none_or(|(_, best_cost)| cost < best_cost) {
+                best = Some((offset, cost));
+            }
+        }
+        if edge.start {
+            below += 1;
+        }
+    }
+    best
+}
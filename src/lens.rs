@@ -0,0 +1,346 @@
+use std::{fs, path::Path};
+
+use crate::{
+    error::{Error, Result},
+    float::Float,
+    vec3::Vec3,
+};
+
+// Camera rays a film point tries before it counts as blocked by the lens.
+const TRIES: usize = 64;
+
+// Film radii the vignetting is measured at, and rays per radius.
+const VIGNETTING_RADII: usize = 64;
+const VIGNETTING_RAYS: usize = 32;
+
+// One surface of a lens system, in meters: a sphere cap, or the aperture stop if the radius is 0.
+#[derive(Copy, Clone, Debug)]
+struct Element {
+    radius: Float,          // curvature radius, positive if the center lies towards the film
+    thickness: Float,       // distance along the axis to the next surface towards the film
+    eta: Float,             // index of refraction between this surface and the next, 0 for air at the stop
+    aperture_radius: Float, // half the diameter of the surface
+}
+
+// The spherical lens elements of a real camera lens, from the front to the film. Rays from the film are traced
+// through all surfaces, refracted by the glass between them, and stopped where they miss one, so the image gets the
+// distortion, the vignetting and the depth of field of the lens.
+//
+// Lens systems are read from the tables of lens design books, e.g. (a double-Gauss 50 mm lens, see `scenes/lenses`)
+//
+//   # radius  thickness  eta    aperture
+//   29.475    3.76       1.67   25.2
+//   84.83     0.12       1      25.2
+//   ...
+//   0         4.5        0      17.1
+//
+// one surface per line from the front: its curvature radius, the distance to the next surface, the index of
+// refraction of the glass behind it and its diameter, all lengths in millimeters. A radius of 0 marks the aperture
+// stop. The thickness of the last surface is replaced by the distance to the film that focuses the lens.
+#[derive(Clone, Debug)]
+pub struct LensSystem {
+    elements: Vec<Element>,
+}
+
+// Ray in the space of the lens: the film at z = 0, the lens towards +z with its axis along z, y up.
+#[derive(Copy, Clone)]
+struct LensRay {
+    origin: Vec3,
+    direction: Vec3,
+}
+
+impl LensRay {
+    fn at(&self, t: Float) -> Vec3 {
+        self.origin + t * self.direction
+    }
+}
+
+pub fn load(path: &Path) -> Result<LensSystem> {
+    parse(&fs::read_to_string(path)?)
+}
+
+pub fn parse(text: &str) -> Result<LensSystem> {
+    let mut elements = vec![];
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let numbers = line
+            .split_whitespace()
+            .map(|number| number.parse::<Float>())
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|_| Error::parse(line_number, format!("invalid lens surface '{line}'")))?;
+        let &[radius, thickness, eta, aperture] = &numbers[..] else {
+            return Err(Error::parse(
+                line_number,
+                "a lens surface needs a radius, a thickness, an index of refraction and an aperture",
+            ));
+        };
+        if thickness < 0.0 || aperture <= 0.0 || (eta != 0.0 && eta < 1.0) {
+            return Err(Error::parse(line_number, format!("invalid lens surface '{line}'")));
+        }
+        elements.push(Element {
+            radius: radius * 0.001,
+            thickness: thickness * 0.001,
+            eta,
+            aperture_radius: aperture * 0.001 / 2.0,
+        });
+    }
+    if elements.is_empty() {
+        return Err(Error::parse(1, "the lens has no surfaces"));
+    }
+    Ok(LensSystem { elements })
+}
+
+impl LensSystem {
+    // Focal length of the lens, in meters.
+    pub fn focal_length(&self) -> Float {
+        let [(principal, focus), _] = self.cardinal_points();
+        principal - focus
+    }
+
+    // Narrow the aperture stop to the focal length over `f_number` across, at most as wide as it goes. That's the
+    // f-number of the lens for stops close to the middle of symmetric designs like the double-Gauss.
+    pub fn stopped_down(&self, f_number: Float) -> LensSystem {
+        let radius = self.focal_length() / f_number / 2.0;
+        let mut elements = self.elements.clone();
+        for element in elements.iter_mut().filter(|element| element.radius == 0.0) {
+            element.aperture_radius = element.aperture_radius.min(radius);
+        }
+        LensSystem { elements }
+    }
+
+    // Distance of the rear surface from the film.
+    fn rear_z(&self) -> Float {
+        self.elements.last().map_or(0.0, |element| element.thickness)
+    }
+
+    // Index of refraction of the medium behind surface `i` towards the film, air at the stop.
+    fn eta(&self, i: usize) -> Float {
+        match self.elements[i].eta {
+            0.0 => 1.0,
+            eta => eta,
+        }
+    }
+
+    // Trace a ray from the film through all surfaces out of the front of the lens, None if a surface blocks it.
+    fn trace_from_film(&self, ray: LensRay) -> Option<LensRay> {
+        let mut ray = ray;
+        let mut z = 0.0;
+        for i in (0..self.elements.len()).rev() {
+            let element = self.elements[i];
+            z += element.thickness;
+            let eta_out = if i > 0 { self.eta(i - 1) } else { 1.0 };
+            ray = refract_at(element, z, ray, self.eta(i) / eta_out)?;
+        }
+        Some(ray)
+    }
+
+    // Trace a ray from the scene through all surfaces out of the back of the lens, None if a surface blocks it.
+    fn trace_from_scene(&self, ray: LensRay) -> Option<LensRay> {
+        let mut ray = ray;
+        let mut z = self.elements.iter().map(|element| element.thickness).sum::<Float>();
+        for (i, element) in self.elements.iter().enumerate() {
+            let eta_in = if i > 0 { self.eta(i - 1) } else { 1.0 };
+            ray = refract_at(*element, z, ray, eta_in / self.eta(i))?;
+            z -= element.thickness;
+        }
+        Some(ray)
+    }
+
+    // Where the principal plane and the focal point of the lens are along the axis, for rays entering parallel to the
+    // axis from the scene and from the film, as seen from the film.
+    fn cardinal_points(&self) -> [(Float, Float); 2] {
+        let height = 0.001 * self.elements[0].aperture_radius;
+        let front = self.elements.iter().map(|element| element.thickness).sum::<Float>();
+        let from_scene = LensRay {
+            origin: Vec3::new(height, 0.0, front + 1.0),
+            direction: Vec3::new(0.0, 0.0, -1.0),
+        };
+        let from_film = LensRay {
+            origin: Vec3::new(height, 0.0, self.rear_z() - 1.0),
+            direction: Vec3::new(0.0, 0.0, 1.0),
+        };
+        let points = |ray_in: LensRay, ray_out: Option<LensRay>| {
+            let Some(ray_out) = ray_out.filter(|ray| ray.direction.x != 0.0) else {
+                return (Float::NAN, Float::NAN);
+            };
+            let focus = ray_out.at(-ray_out.origin.x / ray_out.direction.x).z;
+            let principal = ray_out.at((ray_in.origin.x - ray_out.origin.x) / ray_out.direction.x).z;
+            (principal, focus)
+        };
+        [
+            points(from_scene, self.trace_from_scene(from_scene)),
+            points(from_film, self.trace_from_film(from_film)),
+        ]
+    }
+
+    // Closest distance from the film the lens focuses at, where the film is twice the focal length behind it.
+    pub fn closest_focus(&self) -> Float {
+        let [(rear_principal, _), (front_principal, _)] = self.cardinal_points();
+        4.0 * self.focal_length() + front_principal - rear_principal
+    }
+
+    // Distance from the rear surface to the film that focuses the lens at `focus_dist` from the film, treating it
+    // as a thick lens, or as close as it focuses.
+    fn focus(&self, focus_dist: Float) -> Float {
+        let [(rear_principal, rear_focus), (front_principal, _)] = self.cardinal_points();
+        let focal_length = rear_principal - rear_focus;
+        // Moving the lens by `delta` moves both principal planes, the image is at the film when
+        // 1 / (focus_dist - front) + 1 / rear = 1 / focal_length for the principal planes moved
+        let span = focus_dist - front_principal + rear_principal;
+        let discriminant = (span * (span - 4.0 * focal_length)).max(0.0);
+        let delta = (span - discriminant.sqrt()) / 2.0 - rear_principal;
+        self.rear_z() + delta
+    }
+}
+
+// Refract `ray` at `element`, whose vertex is at `z` along the axis, with the ratio `eta` of the indices of
+// refraction before and after it. None if the ray misses the surface or is reflected.
+fn refract_at(element: Element, z: Float, ray: LensRay, eta: Float) -> Option<LensRay> {
+    let (t, normal) = if element.radius == 0.0 {
+        ((z - ray.origin.z) / ray.direction.z, None)
+    } else {
+        let center = Vec3::new(0.0, 0.0, z - element.radius);
+        let offset = ray.origin - center;
+        let a = ray.direction.length_squared();
+        let b = Vec3::dot(offset, ray.direction);
+        let c = offset.length_squared() - element.radius * element.radius;
+        let discriminant = b * b - a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let roots = [(-b - discriminant.sqrt()) / a, (-b + discriminant.sqrt()) / a];
+        // The cap of the surface is the side of the sphere towards its vertex
+        let closer = (ray.direction.z < 0.0) != (element.radius < 0.0);
+        let t = if closer { roots[0] } else { roots[1] };
+        (t, Some((offset + t * ray.direction).normalize()))
+    };
+    if t.is_nan() || t <= 0.0 {
+        return None;
+    }
+    let point = ray.at(t);
+    if point.x * point.x + point.y * point.y > element.aperture_radius * element.aperture_radius {
+        return None;
+    }
+    let Some(normal) = normal else {
+        return Some(LensRay { origin: point, ..ray });
+    };
+    // Snell's law, with the normal facing the incoming ray
+    let incoming = -ray.direction.normalize();
+    let normal = if Vec3::dot(normal, incoming) < 0.0 {
+        -normal
+    } else {
+        normal
+    };
+    let cos_in = Vec3::dot(normal, incoming);
+    let sin2_out = eta * eta * (1.0 - cos_in * cos_in).max(0.0);
+    if sin2_out >= 1.0 {
+        return None;
+    }
+    let cos_out = (1.0 - sin2_out).sqrt();
+    let direction = eta * -incoming + (eta * cos_in - cos_out) * normal;
+    Some(LensRay {
+        origin: point,
+        direction,
+    })
+}
+
+// A lens system focused in front of a film, with how much light its parts receive.
+#[derive(Clone, Debug)]
+pub struct Lens {
+    system: LensSystem,
+    film_size: [Float; 2],  // width and height of the film in meters
+    vignetting: Vec<Float>, // light reaching the film relative to its center, from the center out to a corner
+}
+
+impl Lens {
+    // Focus `system` at `focus_dist` in front of a film `film_size` wide and high, or as close as it focuses.
+    pub fn new(system: &LensSystem, focus_dist: Float, film_size: [Float; 2]) -> Lens {
+        let mut system = system.clone();
+        let film_distance = system.focus(focus_dist);
+        if let Some(rear) = system.elements.last_mut() {
+            rear.thickness = film_distance;
+        }
+        let mut lens = Lens {
+            system,
+            film_size,
+            vignetting: vec![],
+        };
+        lens.vignetting = lens.measure_vignetting();
+        lens
+    }
+
+    // Light reaching points of the film from the center out to a corner, relative to the center: the part of the rear
+    // surface the lens lets through from there, weighted by the fourth power of the cosine of the angle of the rays.
+    fn measure_vignetting(&self) -> Vec<Float> {
+        let corner = Float::hypot(self.film_size[0], self.film_size[1]) / 2.0;
+        let rear = self.system.elements.last().copied();
+        let light = |radius: Float| {
+            let Some(rear) = rear else {
+                return 0.0;
+            };
+            let mut sum = 0.0;
+            // Stratified points over the disk of the rear surface
+            for i in 0..VIGNETTING_RAYS {
+                for j in 0..VIGNETTING_RAYS {
+                    let u = 2.0 * (i as Float + 0.5) / VIGNETTING_RAYS as Float - 1.0;
+                    let v = 2.0 * (j as Float + 0.5) / VIGNETTING_RAYS as Float - 1.0;
+                    if u * u + v * v > 1.0 {
+                        continue;
+                    }
+                    let target = Vec3::new(u * rear.aperture_radius, v * rear.aperture_radius, rear.thickness);
+                    let ray = LensRay {
+                        origin: Vec3::new(radius, 0.0, 0.0),
+                        direction: target - Vec3::new(radius, 0.0, 0.0),
+                    };
+                    if self.system.trace_from_film(ray).is_some() {
+                        sum += ray.direction.normalize().z.powi(4);
+                    }
+                }
+            }
+            sum
+        };
+        let center = light(0.0);
+        (0..=VIGNETTING_RADII)
+            .map(|i| match center {
+                center if center > 0.0 => light(corner * i as Float / VIGNETTING_RADII as Float) / center,
+                _ => 0.0,
+            })
+            .collect()
+    }
+
+    // Light reaching the point `[x, y]` of the film relative to its center, in fractions of the film size from its
+    // center.
+    pub fn vignetting(&self, [x, y]: [Float; 2]) -> Float {
+        let corner = Float::hypot(self.film_size[0], self.film_size[1]) / 2.0;
+        let radius = Float::hypot(x * self.film_size[0], y * self.film_size[1]) / corner;
+        let position = (radius * VIGNETTING_RADII as Float).min(VIGNETTING_RADII as Float);
+        let i = (position as usize).min(VIGNETTING_RADII - 1);
+        let t = position - i as Float;
+        (1.0 - t) * self.vignetting[i] + t * self.vignetting[i + 1]
+    }
+
+    // Ray leaving the front of the lens for light reaching the point `[x, y]` of the film, in fractions of the film
+    // size to the right and down from its center: its origin and direction to the right, up and forward from the
+    // center of the film, in meters. None if the lens blocks all rays tried.
+    pub fn ray(&self, [x, y]: [Float; 2]) -> Option<(Vec3, Vec3)> {
+        let rear = *self.system.elements.last()?;
+        // The lens turns the image upside down
+        let film_point = Vec3::new(-x * self.film_size[0], y * self.film_size[1], 0.0);
+        for _ in 0..TRIES {
+            let p = Vec3::random_in_unit_disk();
+            let target = Vec3::new(p.x * rear.aperture_radius, p.y * rear.aperture_radius, rear.thickness);
+            let ray = LensRay {
+                origin: film_point,
+                direction: target - film_point,
+            };
+            if let Some(ray) = self.system.trace_from_film(ray) {
+                return Some((ray.origin, ray.direction));
+            }
+        }
+        None
+    }
+}
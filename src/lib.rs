@@ -0,0 +1,92 @@
+// Conversions to single precision for SIMD and Embree are no-ops with the `f32` feature
+#![cfg_attr(feature = "f32", allow(clippy::unnecessary_cast))]
+
+pub mod aabb;
+pub mod accel;
+pub mod animation;
+pub mod aperture;
+pub mod arena;
+pub mod bvh;
+pub mod camera;
+pub mod camera_path;
+pub mod checkpoint;
+pub mod color;
+pub mod color_space;
+#[cfg(feature = "formats")]
+pub mod cryptomatte;
+pub mod curve;
+pub mod cyhair;
+#[cfg(feature = "formats")]
+pub mod deep;
+#[cfg(feature = "embree")]
+pub mod embree;
+pub mod error;
+pub mod examples;
+pub mod exposure;
+#[cfg(feature = "formats")]
+mod exr;
+#[allow(clippy::missing_safety_doc)] // the safety requirements are in the comments of the functions
+pub mod ffi;
+pub mod film;
+pub mod filter;
+pub mod float;
+pub mod font;
+pub mod fractal;
+pub mod generator;
+pub mod hair;
+pub mod heightfield;
+pub mod hittable;
+pub mod id;
+pub mod instance;
+pub mod integrator;
+pub mod kdtree;
+pub mod lens;
+pub mod light_linking;
+pub mod log;
+pub mod lut;
+pub mod material;
+pub mod medium;
+pub mod mesh;
+pub mod microfacet;
+#[cfg(feature = "importers")]
+pub mod mitsuba;
+pub mod obj;
+pub mod onb;
+pub mod output;
+pub mod packet;
+pub mod parser;
+#[cfg(feature = "importers")]
+pub mod pbrt;
+pub mod ply;
+#[cfg(feature = "formats")]
+mod png;
+pub mod point_cloud;
+pub mod post;
+pub mod presets;
+#[cfg(feature = "network")]
+pub mod preview;
+pub mod progress;
+pub mod projection;
+pub mod range;
+pub mod ray;
+pub mod rng;
+pub mod sampling;
+pub mod scene;
+pub mod simd;
+pub mod sphere;
+pub mod stats;
+pub mod stereo;
+pub mod stl;
+pub mod subdivision;
+pub mod terrain;
+pub mod text;
+#[cfg(feature = "formats")]
+mod tiff;
+pub mod transform;
+#[cfg(feature = "importers")]
+pub mod usd;
+pub mod util;
+pub mod vec3;
+pub mod volume;
+pub mod wasm;
+pub mod water;
@@ -0,0 +1,71 @@
+use std::collections::HashSet;
+
+use crate::id::name_id;
+
+// Which lights light which objects, for art-directing a shot: a key light that only lights the hero, a rim light that
+// leaves the ground alone. Lights and objects are referred to by their names or their groups (see `id.rs`), so a
+// group of lights can be linked at once. Linking only changes the light a surface receives straight from a light (for
+// mirrors and glass the reflection of the light), the light still shows to the camera and still casts shadows.
+#[derive(Clone, Default)]
+pub struct LightLinks {
+    rules: Vec<Rule>,
+}
+
+#[derive(Clone)]
+enum Rule {
+    // The light with the ID only lights the objects listed if `include`, or all others if not
+    Light {
+        light: u32,
+        include: bool,
+        objects: HashSet<u32>,
+    },
+    // The object with the ID is only lit by the lights listed if `include`, or by all others if not
+    Object {
+        object: u32,
+        include: bool,
+        lights: HashSet<u32>,
+    },
+}
+
+impl LightLinks {
+    // Let the light of the given name only light the objects and groups named (`include`), or all others.
+    pub fn link_light(&mut self, light: &str, include: bool, objects: &[String]) {
+        self.rules.push(Rule::Light {
+            light: name_id(light),
+            include,
+            objects: ids(objects),
+        });
+    }
+
+    // Let the object of the given name only be lit by the lights and groups of lights named (`include`), or by all
+    // others.
+    pub fn link_object(&mut self, object: &str, include: bool, lights: &[String]) {
+        self.rules.push(Rule::Object {
+            object: name_id(object),
+            include,
+            lights: ids(lights),
+        });
+    }
+
+    // Whether the light lights the object, both given by the IDs of their name and their group.
+    pub fn lights(&self, light: [u32; 2], object: [u32; 2]) -> bool {
+        let listed = |ids: [u32; 2], set: &HashSet<u32>| ids.iter().any(|id| *id != 0 && set.contains(id));
+        self.rules.iter().all(|rule| match rule {
+            Rule::Light {
+                light: id,
+                include,
+                objects,
+            } => light[0] != *id || listed(object, objects) == *include,
+            Rule::Object {
+                object: id,
+                include,
+                lights,
+            } => object[0] != *id || listed(light, lights) == *include,
+        })
+    }
+}
+
+// IDs of names of objects or groups, the two are the same for the same name.
+fn ids(names: &[String]) -> HashSet<u32> {
+    names.iter().map(|name| name_id(name)).collect()
+}
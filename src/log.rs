@@ -0,0 +1,137 @@
+use std::{
+    fmt::Arguments,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+// Logging for the library and the command line tool. With the `tracing` feature messages are `tracing` events and
+// the stages of a render (scene loading, acceleration structure builds, the render and its scanlines) are spans, so
+// long renders produce logs with timings that can be filtered with `RUST_LOG`, e.g. `RUST_LOG=rustracer::bvh=debug`.
+// Without it messages above the level set with `init` are printed to stderr as plain text and spans are left out.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+
+// Print messages up to `level`. With the `tracing` feature this installs a subscriber writing to stderr, colored only
+// if it is a terminal, which `RUST_LOG` overrides, and which reports the time spent in a span when it closes.
+pub fn init(level: Level) {
+    MAX_LEVEL.store(level as u8, Ordering::Relaxed);
+    #[cfg(feature = "tracing")]
+    {
+        let filter = tracing_subscriber::EnvFilter::builder()
+            .with_default_directive(tracing_level(level).into())
+            .from_env_lossy();
+        let _ = tracing_subscriber::fmt()
+            .with_env_filter(filter)
+            .with_writer(std::io::stderr)
+            .with_ansi(std::io::IsTerminal::is_terminal(&std::io::stderr()))
+            .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
+            .try_init();
+    }
+}
+
+// Whether messages at `level` are printed anywhere. With the `tracing` feature that is up to the subscriber, so a
+// `RUST_LOG` asking for more than the level given to `init` counts too.
+pub fn enabled(level: Level) -> bool {
+    #[cfg(feature = "tracing")]
+    return tracing_level(level) <= tracing::level_filters::LevelFilter::current();
+    #[cfg(not(feature = "tracing"))]
+    return level as u8 <= MAX_LEVEL.load(Ordering::Relaxed);
+}
+
+#[cfg(feature = "tracing")]
+fn tracing_level(level: Level) -> tracing::Level {
+    match level {
+        Level::Error => tracing::Level::ERROR,
+        Level::Warn => tracing::Level::WARN,
+        Level::Info => tracing::Level::INFO,
+        Level::Debug => tracing::Level::DEBUG,
+        Level::Trace => tracing::Level::TRACE,
+    }
+}
+
+#[cfg(feature = "tracing")]
+#[doc(hidden)]
+pub use tracing;
+
+// Print a message without `tracing`, use the `error!`, `warn!`, `info!`, `debug!` and `trace!` macros instead.
+#[doc(hidden)]
+pub fn event(level: Level, message: Arguments) {
+    if enabled(level) {
+        match level {
+            Level::Error => eprintln!("error: {message}"),
+            Level::Warn => eprintln!("warning: {message}"),
+            Level::Info => eprintln!("{message}"),
+            Level::Debug => eprintln!("debug: {message}"),
+            Level::Trace => eprintln!("trace: {message}"),
+        }
+    }
+}
+
+// The macros take a format string and its arguments. They forward to the macros of `tracing`, which makes the module
+// of the caller the target of the event, or to `event` without it.
+#[cfg(feature = "tracing")]
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)+) => { $crate::log::tracing::error!($($arg)+) };
+}
+
+#[cfg(not(feature = "tracing"))]
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)+) => { $crate::log::event($crate::log::Level::Error, format_args!($($arg)+)) };
+}
+
+#[cfg(feature = "tracing")]
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)+) => { $crate::log::tracing::warn!($($arg)+) };
+}
+
+#[cfg(not(feature = "tracing"))]
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)+) => { $crate::log::event($crate::log::Level::Warn, format_args!($($arg)+)) };
+}
+
+#[cfg(feature = "tracing")]
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)+) => { $crate::log::tracing::info!($($arg)+) };
+}
+
+#[cfg(not(feature = "tracing"))]
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)+) => { $crate::log::event($crate::log::Level::Info, format_args!($($arg)+)) };
+}
+
+#[cfg(feature = "tracing")]
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)+) => { $crate::log::tracing::debug!($($arg)+) };
+}
+
+#[cfg(not(feature = "tracing"))]
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)+) => { $crate::log::event($crate::log::Level::Debug, format_args!($($arg)+)) };
+}
+
+#[cfg(feature = "tracing")]
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)+) => { $crate::log::tracing::trace!($($arg)+) };
+}
+
+#[cfg(not(feature = "tracing"))]
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)+) => { $crate::log::event($crate::log::Level::Trace, format_args!($($arg)+)) };
+}
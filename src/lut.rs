@@ -0,0 +1,140 @@
+use std::{fs, path::Path};
+
+use crate::{
+    color::Color,
+    error::{Error, Result},
+    float::Float,
+    output::Framebuffer,
+};
+
+// Color lookup table of a grade, as exported by grading tools in the .cube format of Adobe and Resolve, e.g.
+//
+//   TITLE "Show grade"
+//   LUT_3D_SIZE 33
+//   DOMAIN_MIN 0 0 0
+//   DOMAIN_MAX 1 1 1
+//   0.0 0.0 0.0
+//   0.03 0.0 0.0
+//   ...
+//
+// A 3D table holds the output colors of a cube of input colors, red changing fastest, a 1D table (LUT_1D_SIZE) a
+// curve for every channel. Colors in between are interpolated linearly. The table applies to the image as it is
+// displayed, gamma corrected and clamped.
+#[derive(Clone, Debug)]
+pub struct Lut {
+    size: usize,
+    cube: bool, // a 3D table, a 1D one otherwise
+    domain: [[Float; 3]; 2],
+    table: Vec<[Float; 3]>,
+}
+
+pub fn load(path: &Path) -> Result<Lut> {
+    parse(&fs::read_to_string(path)?)
+}
+
+pub fn parse(text: &str) -> Result<Lut> {
+    let mut size = None;
+    let mut domain = [[0.0; 3], [1.0; 3]];
+    let mut table = vec![];
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut words = line.split_whitespace();
+        let Some(keyword) = words.next() else {
+            continue;
+        };
+        // The three numbers of the line after `skip` words
+        let numbers = |skip: usize| {
+            line.split_whitespace()
+                .skip(skip)
+                .map(|number| number.parse::<Float>().ok())
+                .collect::<Option<Vec<_>>>()
+                .and_then(|numbers| <[Float; 3]>::try_from(numbers).ok())
+                .ok_or_else(|| Error::parse(line_number, format!("invalid '{line}', expected three numbers")))
+        };
+        match keyword {
+            "TITLE" => {}
+            "LUT_3D_SIZE" | "LUT_1D_SIZE" => {
+                let count = words
+                    .next()
+                    .and_then(|count| count.parse::<usize>().ok())
+                    .filter(|count| *count >= 2)
+                    .ok_or_else(|| Error::parse(line_number, format!("invalid table size in '{line}'")))?;
+                if size.is_some() {
+                    return Err(Error::parse(line_number, "duplicate table size"));
+                }
+                size = Some((count, keyword == "LUT_3D_SIZE"));
+            }
+            "DOMAIN_MIN" => domain[0] = numbers(1)?,
+            "DOMAIN_MAX" => domain[1] = numbers(1)?,
+            _ if keyword.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.') => table.push(numbers(0)?),
+            _ => return Err(Error::parse(line_number, format!("unknown keyword '{keyword}'"))),
+        }
+    }
+    let Some((size, cube)) = size else {
+        return Err(Error::parse(0, "the table has no LUT_3D_SIZE or LUT_1D_SIZE"));
+    };
+    let expected = if cube { size * size * size } else { size };
+    if table.len() != expected {
+        return Err(Error::parse(
+            0,
+            format!("the table has {} entries, its size needs {expected}", table.len()),
+        ));
+    }
+    if (0..3).any(|channel| domain[1][channel] <= domain[0][channel]) {
+        return Err(Error::parse(0, "DOMAIN_MAX must be above DOMAIN_MIN"));
+    }
+    Ok(Lut {
+        size,
+        cube,
+        domain,
+        table,
+    })
+}
+
+impl Lut {
+    // Output color of a displayed color.
+    pub fn apply(&self, color: [Float; 3]) -> [Float; 3] {
+        let last = (self.size - 1) as Float;
+        // Position in the table of every channel, the entry below it and the fraction to the next one
+        let position = [0, 1, 2].map(|channel| {
+            let [min, max] = [self.domain[0][channel], self.domain[1][channel]];
+            let position = ((color[channel] - min) / (max - min)).clamp(0.0, 1.0) * last;
+            let below = (position as usize).min(self.size - 2);
+            (below, position - below as Float)
+        });
+        if !self.cube {
+            return [0, 1, 2].map(|channel| {
+                let (below, fraction) = position[channel];
+                let [a, b] = [self.table[below][channel], self.table[below + 1][channel]];
+                a + fraction * (b - a)
+            });
+        }
+        let [(r, fr), (g, fg), (b, fb)] = position;
+        let entry = |dr: usize, dg: usize, db: usize| self.table[((b + db) * self.size + g + dg) * self.size + r + dr];
+        let mut output = [0.0; 3];
+        for (db, wb) in [(0, 1.0 - fb), (1, fb)] {
+            for (dg, wg) in [(0, 1.0 - fg), (1, fg)] {
+                for (dr, wr) in [(0, 1.0 - fr), (1, fr)] {
+                    let value = entry(dr, dg, db);
+                    for channel in 0..3 {
+                        output[channel] += wb * wg * wr * value[channel];
+                    }
+                }
+            }
+        }
+        output
+    }
+
+    // Grade the image: every pixel as displayed goes through the table, the result is kept linear so it displays
+    // as the table says.
+    pub fn grade(&self, image: &mut Framebuffer) {
+        for y in 0..image.height() {
+            for x in 0..image.width() {
+                let displayed = image.get(x, y).map(|value| value.clamp(0.0, 1.0).sqrt());
+                let [r, g, b] = self.apply(displayed.to_array()).map(|value| value.max(0.0).powi(2));
+                image.set(x, y, Color::new(r, g, b));
+            }
+        }
+    }
+}
@@ -1,90 +1,364 @@
-mod camera;
-mod color;
-mod hittable;
-mod material;
-mod range;
-mod ray;
-mod sphere;
-mod util;
-mod vec3;
-use camera::Camera;
-use color::Color;
-use hittable::HittableList;
-use material::{Dielectric, Lambertian, Material, Metal};
-use sphere::Sphere;
-use util::{random_double, random_double_ranged};
-use vec3::{Point, Vec3};
+mod batch;
+mod cli;
+#[cfg(feature = "network")]
+mod distributed;
+mod frames;
+mod golden;
+mod interrupt;
+mod metadata;
+#[cfg(feature = "video")]
+mod video;
+mod watch;
+#[cfg(feature = "formats")]
+use std::io::BufWriter;
+use std::{
+    fs::File,
+    io::{self, IsTerminal},
+    process::exit,
+    time::{Duration, Instant},
+};
+
+use metadata::Metadata;
+#[cfg(feature = "network")]
+use rustracer::preview::PreviewServer;
+use rustracer::{
+    accel::AcceleratedList,
+    arena::Arena,
+    camera::{Camera, Focus},
+    checkpoint::{self, CheckpointSettings},
+    error, examples, generator,
+    hittable::Hittable,
+    info,
+    output::{write_hdr, write_image, ImageFormat},
+    presets,
+    progress::{NoProgress, Progress},
+    scene::Scene,
+    stereo::Stereo,
+    warn,
+};
+#[cfg(feature = "formats")]
+use rustracer::{cryptomatte::write_cryptomatte_exr, deep::write_deep_exr};
 
 fn main() {
-    let mut world: HittableList = vec![];
+    let options = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("error: {message}\n\n{}", cli::USAGE);
+            exit(2);
+        }
+    };
+    if options.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+    if options.list_presets {
+        println!("Materials:");
+        for name in presets::material_names() {
+            println!("  {name}");
+        }
+        println!("Indices of refraction:");
+        for (name, ior) in presets::ior_names() {
+            println!("  {name:<16}{ior}");
+        }
+        return;
+    }
+    if options.list_scenes {
+        println!("Built-in scenes:");
+        for (name, description) in examples::names() {
+            println!("  {name:<16}{description}");
+        }
+        return;
+    }
+    rustracer::log::init(options.log_level());
 
-    let ground_material = Box::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
-    world.push(Box::new(Sphere::new(
-        Point::new(0.0, -1000.0, 0.0),
-        1000.0,
-        ground_material,
-    )));
+    #[cfg(feature = "network")]
+    if let Some(address) = &options.listen {
+        if let Err(err) = distributed::serve(address) {
+            error!("{address}: {err}");
+            exit(1);
+        }
+        return;
+    }
 
-    let base_point = Point::new(4.0, 0.2, 0.0);
-    for a in -11..11 {
-        for b in -11..11 {
-            let choose_mat = random_double();
-            let center = Point::new(a as f64 + 0.9 * random_double(), 0.2, b as f64 + 0.9 * random_double());
+    if let Some(manifest) = &options.batch {
+        match batch::run(manifest, options.bvh, options.parallel.unwrap_or(1)) {
+            Ok(true) => return,
+            Ok(false) => exit(1),
+            Err(err) => {
+                error!("{}: {err}", manifest.display());
+                exit(1);
+            }
+        }
+    }
 
-            if (center - base_point).length() > 0.9 {
-                let material: Box<dyn Material> = if choose_mat < 0.8 {
-                    // diffuse
-                    let albedo = Color::random() * Color::random();
-                    Box::new(Lambertian::new(albedo))
-                } else if choose_mat < 0.95 {
-                    // metal
-                    let albedo = Color::random_ranged(0.5, 1.0);
-                    let fuzz = random_double_ranged(0.0, 0.5);
-                    Box::new(Metal::new(albedo, fuzz))
-                } else {
-                    // glass
-                    Box::new(Dielectric::new(1.5))
-                };
-                world.push(Box::new(Sphere::new(center, 0.2, material)));
+    if let Some(dir) = &options.golden {
+        let tolerance = options.tolerance.unwrap_or(golden::DEFAULT_TOLERANCE);
+        match golden::run(dir, tolerance, options.update_golden) {
+            Ok(true) => return,
+            Ok(false) => exit(1),
+            Err(err) => {
+                error!("{}: {err}", dir.display());
+                exit(1);
             }
         }
     }
 
-    let material1 = Box::new(Dielectric::new(1.5));
-    world.push(Box::new(Sphere::new(Point::new(0.0, 1.0, 0.0), 1.0, material1)));
+    if let (Some(output), Some(path)) = (&options.watch, &options.scene) {
+        watch::run(&options, path, output);
+        return;
+    }
 
-    let material2 = Box::new(Lambertian::new(Color::new(0.4, 0.2, 0.1)));
-    world.push(Box::new(Sphere::new(Point::new(-4.0, 1.0, 0.0), 1.0, material2)));
+    // A whole image of text would flood the terminal
+    if options.frames.is_none() && options.output.is_none() && io::stdout().is_terminal() {
+        error!("no --output file given to write the image into, '-o -' writes it to the terminal anyway");
+        exit(2);
+    }
 
-    let material3 = Box::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0));
-    world.push(Box::new(Sphere::new(Point::new(4.0, 1.0, 0.0), 1.0, material3)));
+    let start = Instant::now();
+    let scene = match (&options.scene, &options.generator) {
+        (Some(path), _) => Scene::load_with_overrides(path, options.bvh, &options.overrides).unwrap_or_else(|err| {
+            error!("{}: {err}", path.display());
+            exit(1);
+        }),
+        (None, generator) => {
+            let scene = match generator {
+                Some(settings) => generator::generate(settings, options.bvh),
+                None => {
+                    let name = options.example.as_deref().unwrap_or("demo");
+                    examples::load(name, options.bvh).expect("the scene names are checked when parsing the arguments")
+                }
+            };
+            // Built-in and generated scenes have no names to refer to, only their camera can be changed
+            scene.with_overrides(&options.overrides).unwrap_or_else(|err| {
+                error!("{err}");
+                exit(1);
+            })
+        }
+    };
+    let mut scene = match &options.camera_path {
+        Some(path) => scene.with_camera_path(path).unwrap_or_else(|err| {
+            error!("{}: {err}", path.display());
+            exit(1);
+        }),
+        None => scene,
+    };
+    if let Err(err) = options
+        .layer
+        .as_deref()
+        .map_or(Ok(()), |layer| scene.check_group(layer))
+    {
+        error!("{err}");
+        exit(1);
+    }
+    if let Some(Focus::Object(name)) = &options.focus {
+        if let Err(err) = scene.check_object(name) {
+            error!("{err}");
+            exit(1);
+        }
+    }
+    let scene_load = start.elapsed();
 
-    //Camera
-    let aspect_ratio = 16.0 / 9.0;
-    let image_width = 1200;
-    let samples_per_pixel = 500;
-    let max_depth = 50;
+    if let Some(frames) = &options.frames {
+        let pattern = options.frame_output.as_deref().unwrap_or(frames::DEFAULT_PATTERN);
+        frames::run(&options, scene, frames.clone(), pattern);
+        return;
+    }
 
-    let vfov = 20.0;
-    let look_from = Point::new(13.0, 2.0, 3.0);
-    let look_at = Point::new(0.0, 0.0, 0.0);
-    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let start = Instant::now();
+    let world: Box<dyn Hittable> = if options.arena {
+        Box::new(Arena::new(std::mem::take(&mut scene.world), scene.accelerator))
+    } else {
+        Box::new(AcceleratedList::new(
+            std::mem::take(&mut scene.world),
+            scene.accelerator,
+        ))
+    };
+    let acceleration_build = start.elapsed();
 
-    let defocus_angle = 0.6;
-    let focus_dist = 10.0;
-    let camera = Camera::new(
-        aspect_ratio,
-        image_width,
-        samples_per_pixel,
-        max_depth,
-        vfov,
-        look_from,
-        look_at,
-        vup,
-        defocus_angle,
-        focus_dist,
-    );
+    let settings = match &options.focus {
+        Some(focus) => {
+            let focus_dist = scene
+                .camera
+                .focus_distance(world.as_ref(), focus, 0.0)
+                .unwrap_or_else(|err| {
+                    error!("{err}");
+                    exit(1);
+                });
+            info!("focusing at a distance of {focus_dist:.3}");
+            scene.camera.with_focus_dist(focus_dist)
+        }
+        None => scene.camera,
+    };
+    #[cfg(feature = "network")]
+    let preview = options.serve.as_ref().map(|address| {
+        PreviewServer::start(address).unwrap_or_else(|err| {
+            error!("{address}: {err}");
+            exit(1);
+        })
+    });
+    // The progress bar would get in the way of the messages about the pixel being debugged
+    let progress = scanlines_progress(options.quiet || options.debug_pixel.is_some());
+    let camera = Camera::new(settings)
+        .with_next_frame(scene.camera_at(1.0))
+        .with_light_links(scene.light_links.clone())
+        .with_lens(scene.lens.as_deref())
+        .with_aperture_mask(scene.aperture_mask.clone())
+        .with_lut(scene.lut.clone())
+        .with_packets(options.packets)
+        .with_wavefront(options.wavefront)
+        .with_ray_sorting(options.sort_rays)
+        .with_crop(options.crop)
+        .with_debug_pixel(options.debug_pixel)
+        .with_integrator(options.integrator)
+        .with_layer(options.layer.as_deref())
+        .with_checkpoints(options.checkpoint.clone().map(|path| {
+            CheckpointSettings {
+                path,
+                interval: options
+                    .checkpoint_interval
+                    .map_or(checkpoint::DEFAULT_INTERVAL, Duration::from_secs),
+                resume: options.resume,
+            }
+        }))
+        .with_live_exr(options.exr.clone())
+        .with_stop(Some(&interrupt::INTERRUPTED))
+        .with_pause(Some(&interrupt::PAUSED))
+        .with_time_budget(options.time);
+    #[cfg(feature = "network")]
+    let camera = camera.with_preview(preview);
+    if let Some([x, y]) = options.debug_pixel {
+        if !(0..camera.image_width()).contains(&x) || !(0..camera.image_height()).contains(&y) {
+            error!(
+                "pixel {x} {y} is outside of the {}x{} image",
+                camera.image_width(),
+                camera.image_height()
+            );
+            exit(1);
+        }
+    }
+    // The eyes of an anaglyph are developed apart before they are combined, workers only render films
+    if !options.workers.is_empty() && settings.stereo == Stereo::Anaglyph {
+        error!("--workers can't render anaglyph stereo images");
+        exit(1);
+    }
+    interrupt::install_handler();
+    let start = Instant::now();
+    // The deep image flattens into the image, with a box filter
+    let (image, info) = match &options.deep {
+        #[cfg(feature = "formats")]
+        Some(path) => {
+            let (deep, info) = camera.render_deep(world.as_ref(), progress.as_ref());
+            let written = File::create(path).and_then(|file| write_deep_exr(&deep, &mut BufWriter::new(file)));
+            if let Err(err) = written {
+                error!("{}: {err}", path.display());
+                exit(1);
+            }
+            (deep.flatten(), info)
+        }
+        #[cfg(feature = "network")]
+        _ if !options.workers.is_empty() => {
+            let (image, info) = distributed::render(&options.workers, &camera, world.as_ref(), progress.as_ref())
+                .unwrap_or_else(|err| {
+                    error!("{err}");
+                    exit(1);
+                });
+            (image, info)
+        }
+        _ => {
+            let (image, info) = camera
+                .render_image(world.as_ref(), progress.as_ref())
+                .unwrap_or_else(|err| {
+                    error!("{err}");
+                    exit(1);
+                });
+            (image, info)
+        }
+    };
+    progress.finish();
+    // The mattes take a pass of their own over the first hits
+    #[cfg(feature = "formats")]
+    let mattes = options.cryptomatte.as_ref().map(|_| {
+        let progress = scanlines_progress(options.quiet);
+        let mattes = camera.render_cryptomatte(world.as_ref(), progress.as_ref());
+        progress.finish();
+        mattes
+    });
+    let render = start.elapsed();
+    let start = Instant::now();
+    let written = match &options.output {
+        Some(path) if path.as_os_str() != "-" => File::create(path)
+            .and_then(|mut file| write_image(&image, ImageFormat::of_path(path), &mut file))
+            .map_err(|err| format!("{}: {err}", path.display())),
+        _ => write_image(&image, options.format, &mut io::stdout().lock()).map_err(|err| err.to_string()),
+    };
+    if let Err(err) = written {
+        error!("{err}");
+        exit(1);
+    }
+    #[cfg(feature = "formats")]
+    if let (Some(path), Some(mattes)) = (&options.cryptomatte, &mattes) {
+        let written = File::create(path)
+            .and_then(|file| write_cryptomatte_exr(&image, mattes, &scene.names, &mut BufWriter::new(file)));
+        if let Err(err) = written {
+            error!("{}: {err}", path.display());
+            exit(1);
+        }
+    }
+    if let Some(path) = &options.hdr {
+        if let Err(err) = File::create(path).and_then(|mut file| write_hdr(&image, &mut file)) {
+            error!("{}: {err}", path.display());
+            exit(1);
+        }
+    }
+    let output = start.elapsed();
 
-    // TODO: Execution time
-    camera.render(&world)
+    if let Some(path) = &options.stats_json {
+        let metadata = Metadata {
+            options: &options,
+            camera: settings,
+            image_size: [camera.image_width(), camera.image_height()],
+            accelerator: scene.accelerator,
+            scene_load,
+            acceleration_build,
+            render,
+            output,
+            info,
+        };
+        if let Err(err) = metadata.write(path) {
+            error!("{}: {err}", path.display());
+            exit(1);
+        }
+    }
+    if cfg!(feature = "stats") {
+        info!("{}", info.stats);
+    }
+    if options.time.is_some() && !info.stopped {
+        info!("rendered {:.1} samples per pixel", info.samples_per_pixel);
+    }
+    if info.stopped {
+        warn!(
+            "interrupted after {} samples ({:.1} per pixel), wrote the partial image",
+            info.samples, info.samples_per_pixel,
+        );
+        exit(130);
+    }
+}
+
+#[cfg(feature = "progress")]
+fn scanlines_progress(quiet: bool) -> Box<dyn Progress> {
+    if quiet {
+        return Box::new(NoProgress);
+    }
+    Box::new(rustracer::progress::BarProgress::new(
+        indicatif::ProgressBar::new(0),
+        "Rendering",
+    ))
+}
+
+#[cfg(not(feature = "progress"))]
+fn scanlines_progress(quiet: bool) -> Box<dyn Progress> {
+    if quiet {
+        return Box::new(NoProgress);
+    }
+    Box::new(rustracer::progress::TextProgress::new("Rendering"))
 }
@@ -1,16 +1,203 @@
-use crate::{color::Color, hittable::Hit, ray::Ray, util::random_double, vec3::Vec3};
+use crate::float::consts::PI;
+
+use crate::{
+    color::Color, float::Float, hittable::Hit, medium::Medium, microfacet::Ggx, ray::Ray, util::random_double,
+    vec3::Vec3,
+};
 
 pub struct Scatter {
     pub ray: Ray,
     pub attenuation: Color,
 }
 
-pub trait Material {
+pub trait Material: Send + Sync {
     fn scatter(&self, ray_in: Ray, hit: Hit) -> Option<Scatter>;
+
+    // Kind of material for messages, e.g. "metal".
+    fn name(&self) -> &'static str {
+        "material"
+    }
+
+    // Light the surface gives off at `hit` by itself, on top of the light it scatters.
+    fn emitted(&self, hit: &Hit) -> Color {
+        let _ = hit;
+        Color::BLACK
+    }
+
+    // Shadow catchers are invisible to camera rays, see `ShadowCatcher`.
+    fn is_shadow_catcher(&self) -> bool {
+        false
+    }
+
+    // Transparent medium enclosed by the surface, if any.
+    fn medium(&self) -> Option<Medium> {
+        None
+    }
+
+    // ID of the material in ID images, see `id.rs`, 0 unless it has a name.
+    fn id(&self) -> u32 {
+        0
+    }
+
+    // Scatter at the boundary between two media with known indices of refraction, the ray travels from the medium
+    // with index `eta_from` towards the one with `eta_to`.
+    fn scatter_between(&self, ray_in: Ray, hit: Hit, eta_from: Float, eta_to: Float) -> Option<Scatter> {
+        let _ = (eta_from, eta_to);
+        self.scatter(ray_in, hit)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+// Representative wavelengths (in nanometers) used to evaluate wavelength-dependent effects for R, G and B channels.
+const RGB_WAVELENGTHS: [Float; 3] = [650.0, 532.0, 450.0];
+
+// Thin transparent layer on top of a surface (soap film, oil slick, anti-reflective coating). Light reflected from
+// the top and the bottom of the layer interferes, producing reflectance that depends on wavelength and angle.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ThinFilm {
+    pub thickness: Float, // layer thickness in nanometers
+    pub ir: Float,        // index of refraction of the layer
+}
+
+// Medium below the film.
+enum Substrate {
+    Dielectric(Float),            // index of refraction
+    Conductor(Color),             // normal incidence reflectance per channel
+    ComplexConductor(ComplexIor), // measured optical constants
+}
+
+impl ThinFilm {
+    pub fn new(thickness: Float, ir: Float) -> ThinFilm {
+        ThinFilm {
+            thickness: thickness.max(0.0),
+            ir,
+        }
+    }
+
+    // Reflectance of the film for light arriving from a medium with index `eta_outside` at angle `cos_theta`
+    // to the normal, computed with the Airy summation over all internal reflections and averaged over polarizations.
+    fn reflectance(&self, cos_theta: Float, eta_outside: Float, substrate: Substrate) -> Color {
+        let sin2_outside = 1.0 - cos_theta * cos_theta;
+        let sin2_film = sin2_outside * (eta_outside / self.ir).powi(2);
+        if sin2_film >= 1.0 {
+            // Total internal reflection at the top of the film
+            return Color::WHITE;
+        }
+        let cos_film = (1.0 - sin2_film).sqrt();
+        let (r12_s, r12_p) = fresnel_amplitudes(eta_outside, cos_theta, self.ir, cos_film);
+
+        let (r23_s, r23_p) = match substrate {
+            Substrate::Dielectric(ir) => {
+                let sin2_substrate = sin2_film * (self.ir / ir).powi(2);
+                if sin2_substrate >= 1.0 {
+                    // Total internal reflection at the bottom of the film, nothing is lost
+                    return Color::WHITE;
+                }
+                let cos_substrate = (1.0 - sin2_substrate).sqrt();
+                let (s, p) = fresnel_amplitudes(self.ir, cos_film, ir, cos_substrate);
+                ([s; 3], [p; 3])
+            }
+            Substrate::Conductor(f0) => {
+                // Conductors flip the phase of the reflected wave
+                let r = f0.to_array().map(|c| -c.sqrt());
+                (r, r)
+            }
+            Substrate::ComplexConductor(ior) => {
+                let f = ior.reflectance(cos_film, self.ir);
+                let r = f.to_array().map(|c| -c.sqrt());
+                (r, r)
+            }
+        };
+
+        let mut reflectance = [0.0; 3];
+        for (i, wavelength) in RGB_WAVELENGTHS.iter().enumerate() {
+            // Phase difference between light reflected from the top and the bottom of the film
+            let delta = 4.0 * PI * self.ir * self.thickness * cos_film / wavelength;
+            let airy = |r12: Float, r23: Float| {
+                let cross = 2.0 * r12 * r23 * delta.cos();
+                (r12 * r12 + r23 * r23 + cross) / (1.0 + r12 * r12 * r23 * r23 + cross)
+            };
+            reflectance[i] = 0.5 * (airy(r12_s, r23_s[i]) + airy(r12_p, r23_p[i]));
+        }
+        Color::new(reflectance[0], reflectance[1], reflectance[2])
+    }
+}
+
+// Fresnel amplitude coefficients (s and p polarized) for the interface between two dielectrics.
+fn fresnel_amplitudes(n1: Float, cos1: Float, n2: Float, cos2: Float) -> (Float, Float) {
+    let s = (n1 * cos1 - n2 * cos2) / (n1 * cos1 + n2 * cos2);
+    let p = (n2 * cos1 - n1 * cos2) / (n2 * cos1 + n1 * cos2);
+    (s, p)
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+// Complex index of refraction `eta + i * k` of a conductor, sampled at the wavelengths of the R, G and B channels.
+// The imaginary part describes how quickly light is absorbed inside the material.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ComplexIor {
+    pub eta: Color,
+    pub k: Color,
+}
+
+impl ComplexIor {
+    pub const GOLD: ComplexIor = ComplexIor::new(Color::new(0.143, 0.374, 1.442), Color::new(3.983, 2.385, 1.603));
+    pub const SILVER: ComplexIor = ComplexIor::new(Color::new(0.155, 0.117, 0.138), Color::new(4.828, 3.122, 2.147));
+    pub const COPPER: ComplexIor = ComplexIor::new(Color::new(0.200, 0.924, 1.102), Color::new(3.912, 2.452, 2.142));
+    pub const ALUMINUM: ComplexIor = ComplexIor::new(Color::new(1.657, 0.880, 0.521), Color::new(9.224, 6.269, 4.837));
+
+    pub const fn new(eta: Color, k: Color) -> ComplexIor {
+        ComplexIor { eta, k }
+    }
+
+    // Look up the optical constants of a common metal by name.
+    pub fn preset(name: &str) -> Option<ComplexIor> {
+        match name {
+            "gold" => Some(ComplexIor::GOLD),
+            "silver" => Some(ComplexIor::SILVER),
+            "copper" => Some(ComplexIor::COPPER),
+            "aluminum" | "aluminium" => Some(ComplexIor::ALUMINUM),
+            _ => None,
+        }
+    }
+
+    // Unpolarized Fresnel reflectance of the conductor for light arriving from a dielectric with index
+    // `eta_outside` at angle `cos_theta` to the normal.
+    fn reflectance(&self, cos_theta: Float, eta_outside: Float) -> Color {
+        let channel = |eta: Float, k: Float| {
+            let eta = eta / eta_outside;
+            let k = k / eta_outside;
+            let cos2 = cos_theta * cos_theta;
+            let sin2 = 1.0 - cos2;
+            let eta2 = eta * eta;
+            let k2 = k * k;
+
+            let t0 = eta2 - k2 - sin2;
+            let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+            let t1 = a2_plus_b2 + cos2;
+            let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+            let t2 = 2.0 * cos_theta * a;
+            let rs = (t1 - t2) / (t1 + t2);
+
+            let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+            let t4 = t2 * sin2;
+            let rp = rs * (t3 - t4) / (t3 + t4);
+            0.5 * (rs + rp)
+        };
+        Color::new(
+            channel(self.eta.r, self.k.r),
+            channel(self.eta.g, self.k.g),
+            channel(self.eta.b, self.k.b),
+        )
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Lambertian {
     pub albedo: Color,
 }
@@ -22,7 +209,11 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _: Ray, hit: Hit) -> Option<Scatter> {
+    fn name(&self) -> &'static str {
+        "lambertian"
+    }
+
+    fn scatter(&self, ray_in: Ray, hit: Hit) -> Option<Scatter> {
         // Using Lambertian distribution for diffuse reflection. The reflection direction is a
         // random vector on the unit sphere centered at P + N where P is the hit point and N
         // is the surface normal vector.
@@ -32,8 +223,8 @@ impl Material for Lambertian {
             scatter_direction = hit.normal;
         }
 
-        let scattered = Ray::new(hit.point, scatter_direction);
-        let attenuation = self.albedo;
+        let scattered = Ray::new(hit.point, scatter_direction, ray_in.time);
+        let attenuation = self.albedo * hit.color;
         Some(Scatter {
             ray: scattered,
             attenuation,
@@ -43,25 +234,61 @@ impl Material for Lambertian {
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Metal {
-    pub albedo: Color,
-    pub fuzz: f64,
+    pub albedo: Color, // reflectance, or a tint applied on top of it if `ior` is known
+    pub fuzz: Float,
+    pub ior: Option<ComplexIor>, // optical constants for angle dependent reflectance
+    pub film: Option<ThinFilm>,  // optional interference coating
 }
 
 impl Metal {
-    pub fn new(albedo: Color, fuzz: f64) -> Metal {
+    pub fn new(albedo: Color, fuzz: Float) -> Metal {
         Metal {
             albedo,
             fuzz: fuzz.clamp(0.0, 1.0),
+            ior: None,
+            film: None,
+        }
+    }
+
+    // Metal with reflectance derived from its optical constants, see `ComplexIor::preset` for common ones.
+    pub fn conductor(ior: ComplexIor, fuzz: Float) -> Metal {
+        Metal {
+            albedo: Color::WHITE,
+            ior: Some(ior),
+            ..Metal::new(Color::BLACK, fuzz)
+        }
+    }
+
+    pub fn with_film(self, film: ThinFilm) -> Metal {
+        Metal {
+            film: Some(film),
+            ..self
         }
     }
 }
 
 impl Material for Metal {
+    fn name(&self) -> &'static str {
+        "metal"
+    }
+
     fn scatter(&self, ray_in: Ray, hit: Hit) -> Option<Scatter> {
-        let reflected = Vec3::reflect(ray_in.direction.normalize(), hit.normal);
-        let scattered = Ray::new(hit.point, reflected + self.fuzz * Vec3::random_unit_vector());
-        let attenuation = self.albedo;
+        let unit_direction = ray_in.direction.normalize();
+        let reflected = Vec3::reflect(unit_direction, hit.normal);
+        let scattered = Ray::new(
+            hit.point,
+            reflected + self.fuzz * Vec3::random_unit_vector(),
+            ray_in.time,
+        );
+        let cos_theta = Float::min(Vec3::dot(-unit_direction, hit.normal), 1.0);
+        let attenuation = match (self.film, self.ior) {
+            (Some(film), Some(ior)) => self.albedo * film.reflectance(cos_theta, 1.0, Substrate::ComplexConductor(ior)),
+            (Some(film), None) => film.reflectance(cos_theta, 1.0, Substrate::Conductor(self.albedo)),
+            (None, Some(ior)) => self.albedo * ior.reflectance(cos_theta, 1.0),
+            (None, None) => self.albedo,
+        };
         if Vec3::dot(scattered.direction, hit.normal) > 0.0 {
             Some(Scatter {
                 ray: scattered,
@@ -75,41 +302,273 @@ impl Material for Metal {
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Dielectric {
-    pub ir: f64, // index of refraction
+    pub ir: Float,              // index of refraction
+    pub roughness: Float,       // 0 for perfectly smooth glass, up to 1 for heavily frosted one
+    pub priority: i32,          // precedence of the enclosed medium where it overlaps with another one
+    pub film: Option<ThinFilm>, // optional interference coating
+    pub absorption: Color,      // light absorbed inside per unit of distance by channel, tinting thick glass or water
 }
 
 impl Dielectric {
-    pub fn new(ir: f64) -> Dielectric {
-        Dielectric { ir }
+    pub fn new(ir: Float) -> Dielectric {
+        Dielectric {
+            ir,
+            roughness: 0.0,
+            priority: 0,
+            film: None,
+            absorption: Color::BLACK,
+        }
+    }
+
+    pub fn with_priority(self, priority: i32) -> Dielectric {
+        Dielectric { priority, ..self }
+    }
+
+    pub fn with_roughness(self, roughness: Float) -> Dielectric {
+        Dielectric {
+            roughness: roughness.clamp(0.0, 1.0),
+            ..self
+        }
+    }
+
+    pub fn with_film(self, film: ThinFilm) -> Dielectric {
+        Dielectric {
+            film: Some(film),
+            ..self
+        }
+    }
+
+    pub fn with_absorption(self, absorption: Color) -> Dielectric {
+        Dielectric { absorption, ..self }
     }
 
     // Use Schlick's approximation for reflectance.
-    fn reflectance(cosine: f64, refraction_ratio: f64) -> f64 {
+    fn reflectance(cosine: Float, refraction_ratio: Float) -> Float {
         let r0 = ((1.0 - refraction_ratio) / (1.0 + refraction_ratio)).powf(2.0);
         r0 + (1.0 - r0) * (1.0 - cosine).powf(5.0)
     }
 }
 
 impl Material for Dielectric {
+    fn name(&self) -> &'static str {
+        "dielectric"
+    }
+
     fn scatter(&self, ray_in: Ray, hit: Hit) -> Option<Scatter> {
-        let attenuation = Color::new(1.0, 1.0, 1.0);
-        let refraction_ratio = if hit.front_face { 1.0 / self.ir } else { self.ir };
+        // Assume the object is surrounded by air
+        if hit.front_face {
+            self.scatter_between(ray_in, hit, 1.0, self.ir)
+        } else {
+            self.scatter_between(ray_in, hit, self.ir, 1.0)
+        }
+    }
+
+    fn medium(&self) -> Option<Medium> {
+        Some(Medium {
+            ir: self.ir,
+            priority: self.priority,
+            absorption: self.absorption,
+        })
+    }
+
+    fn scatter_between(&self, ray_in: Ray, hit: Hit, eta_from: Float, eta_to: Float) -> Option<Scatter> {
+        let refraction_ratio = eta_from / eta_to;
 
         let unit_direction = ray_in.direction.normalize();
-        let cos_theta = f64::min(Vec3::dot(-unit_direction, hit.normal), 1.0);
+        // Rough glass is treated as a surface made of tiny smooth facets oriented according to the GGX distribution,
+        // the ray interacts with one of them chosen at random.
+        let microfacets = (self.roughness > 0.0).then(|| Ggx::from_roughness(self.roughness));
+        let normal = match microfacets {
+            Some(ggx) => ggx.sample_visible_normal(hit.normal, -unit_direction),
+            None => hit.normal,
+        };
+
+        let cos_theta = Float::min(Vec3::dot(-unit_direction, normal), 1.0);
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
-        let direction = if cannot_refract || Dielectric::reflectance(cos_theta, refraction_ratio) > random_double() {
-            Vec3::reflect(unit_direction, hit.normal)
-        } else {
-            Vec3::refract(unit_direction, hit.normal, refraction_ratio)
+        let (direction, mut attenuation) = match self.film {
+            Some(film) if !cannot_refract => {
+                // The film reflects every channel differently, so pick reflection with the average probability and
+                // reweight the channels to keep the estimate unbiased.
+                let reflectance = film.reflectance(cos_theta, eta_from, Substrate::Dielectric(eta_to));
+                let probability = reflectance.average();
+                if probability > random_double() {
+                    (Vec3::reflect(unit_direction, normal), reflectance / probability)
+                } else {
+                    let transmittance = Color::WHITE - reflectance;
+                    let direction = Vec3::refract(unit_direction, normal, refraction_ratio);
+                    (direction, transmittance / (1.0 - probability))
+                }
+            }
+            _ => {
+                let direction =
+                    if cannot_refract || Dielectric::reflectance(cos_theta, refraction_ratio) > random_double() {
+                        Vec3::reflect(unit_direction, normal)
+                    } else {
+                        Vec3::refract(unit_direction, normal, refraction_ratio)
+                    };
+                (direction, Color::WHITE)
+            }
         };
-        let scattered = Ray::new(hit.point, direction);
+        if let Some(ggx) = microfacets {
+            // The facet may send the ray to the wrong side of the surface, such paths are discarded.
+            let reflected = Vec3::dot(direction, normal) > 0.0;
+            if reflected != (Vec3::dot(direction, hit.normal) > 0.0) {
+                return None;
+            }
+            // Account for the outgoing ray being blocked by neighbouring facets
+            attenuation = ggx.g1(hit.normal, direction) * attenuation;
+        }
+        let scattered = Ray::new(hit.point, direction, ray_in.time);
         Some(Scatter {
             ray: scattered,
             attenuation,
         })
     }
 }
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+// Diffuse base under a clear dielectric coat, like plastic, rubber or skin. Part of the light is reflected by the
+// coat according to the Fresnel term, the rest is scattered diffusely by the base.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Plastic {
+    pub albedo: Color,    // color of the diffuse base
+    pub ir: Float,        // index of refraction of the coat
+    pub roughness: Float, // blur of the coat reflections, 0 for a glossy finish
+}
+
+impl Plastic {
+    pub fn new(albedo: Color, ir: Float, roughness: Float) -> Plastic {
+        Plastic {
+            albedo,
+            ir,
+            roughness: roughness.clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl Material for Plastic {
+    fn name(&self) -> &'static str {
+        "plastic"
+    }
+
+    fn scatter(&self, ray_in: Ray, hit: Hit) -> Option<Scatter> {
+        let unit_direction = ray_in.direction.normalize();
+        let cos_theta = Float::min(Vec3::dot(-unit_direction, hit.normal), 1.0);
+
+        if Dielectric::reflectance(cos_theta, 1.0 / self.ir) > random_double() {
+            let reflected = Vec3::reflect(unit_direction, hit.normal);
+            let scattered = Ray::new(
+                hit.point,
+                reflected + self.roughness * Vec3::random_unit_vector(),
+                ray_in.time,
+            );
+            return (Vec3::dot(scattered.direction, hit.normal) > 0.0).then_some(Scatter {
+                ray: scattered,
+                attenuation: Color::WHITE,
+            });
+        }
+
+        Lambertian::new(self.albedo).scatter(ray_in, hit)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+// Surface for compositing rendered objects onto a photographic backplate. Camera rays see through it, so it shows only
+// the shadows and reflections of other objects on top of the background. For all other rays it is a plain diffuse
+// surface, so objects still receive light bounced from it.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ShadowCatcher {
+    pub albedo: Color,
+}
+
+impl ShadowCatcher {
+    pub fn new(albedo: Color) -> ShadowCatcher {
+        ShadowCatcher { albedo }
+    }
+}
+
+impl Material for ShadowCatcher {
+    fn name(&self) -> &'static str {
+        "shadow_catcher"
+    }
+
+    fn scatter(&self, ray_in: Ray, hit: Hit) -> Option<Scatter> {
+        Lambertian::new(self.albedo).scatter(ray_in, hit)
+    }
+
+    fn is_shadow_catcher(&self) -> bool {
+        true
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+// Light source that gives off the same light in all directions from its front face, or both faces if it's two-sided,
+// and absorbs all light arriving. Paths only find lights by chance, so small lights make for noisy images.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DiffuseLight {
+    pub emit: Color, // radiance, can be above 1
+    pub two_sided: bool,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> DiffuseLight {
+        DiffuseLight { emit, two_sided: false }
+    }
+
+    pub fn with_two_sided(self, two_sided: bool) -> DiffuseLight {
+        DiffuseLight { two_sided, ..self }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn name(&self) -> &'static str {
+        "light"
+    }
+
+    fn scatter(&self, _: Ray, _: Hit) -> Option<Scatter> {
+        None
+    }
+
+    fn emitted(&self, hit: &Hit) -> Color {
+        if hit.front_face || self.two_sided {
+            self.emit
+        } else {
+            Color::BLACK
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+// Phase function of a participating medium like smoke or fog (see `volume::ConstantMedium`), which scatters light
+// into all directions alike.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Isotropic {
+    pub albedo: Color,
+}
+
+impl Isotropic {
+    pub fn new(albedo: Color) -> Isotropic {
+        Isotropic { albedo }
+    }
+}
+
+impl Material for Isotropic {
+    fn name(&self) -> &'static str {
+        "isotropic"
+    }
+
+    fn scatter(&self, ray_in: Ray, hit: Hit) -> Option<Scatter> {
+        Some(Scatter {
+            ray: Ray::new(hit.point, Vec3::random_unit_vector(), ray_in.time),
+            attenuation: self.albedo,
+        })
+    }
+}
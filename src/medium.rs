@@ -0,0 +1,79 @@
+use crate::{color::Color, float::Float, hittable::Hit, material::Material, ray::Ray};
+
+// Transparent medium enclosed by the surface of a dielectric.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Medium {
+    pub ir: Float,         // index of refraction
+    pub priority: i32,     // where media overlap, the one with the highest priority fills the shared volume
+    pub absorption: Color, // light absorbed per unit of distance by channel, black for clear media
+}
+
+// Media the ray currently travels through, used to handle nested dielectrics like liquid in a glass. The liquid is
+// modelled slightly larger than the inner glass wall and gets a lower priority than the glass, so the overlap belongs
+// to the glass and the ray sees a single glass/liquid interface instead of glass/air/liquid.
+// See "Simple Nested Dielectrics in Ray Traced Images" by Charles M. Schmidt and Brian Budge.
+#[derive(Clone, Default)]
+pub struct MediumStack {
+    entries: Vec<(usize, Medium)>, // identity of the material and its medium, in the order they were entered
+}
+
+impl MediumStack {
+    // Index of refraction of the medium the ray travels through, air if the ray is not inside any object.
+    pub fn ir(&self) -> Float {
+        self.top().map_or(1.0, |(_, medium)| medium.ir)
+    }
+
+    // Fraction of the light that gets through the medium along `ray` up to its `hit`, after Beer-Lambert's law. Rays
+    // that never leave an absorbing medium, like the ones going down into water without a bottom, get nothing through.
+    pub fn transmittance(&self, ray: Ray, hit: Option<&Hit>) -> Color {
+        let absorption = self.top().map_or(Color::BLACK, |(_, medium)| medium.absorption);
+        if absorption == Color::BLACK {
+            return Color::WHITE;
+        }
+        match hit {
+            Some(hit) => {
+                let distance = hit.t * ray.direction.length();
+                absorption.map(|absorption| (-absorption * distance).exp())
+            }
+            None => Color::BLACK,
+        }
+    }
+
+    // An interface is false when it is the boundary of a medium that is hidden by another one with higher priority,
+    // the ray should pass it without any interaction.
+    pub fn is_false_interface(&self, material: &dyn Material, medium: Medium, entering: bool) -> bool {
+        let id = material_id(material);
+        match self.top() {
+            Some((_, top)) if entering => medium.priority < top.priority,
+            Some((top, _)) => *top != id && self.entries.iter().any(|(entry, _)| *entry == id),
+            None => false,
+        }
+    }
+
+    pub fn entered(&self, material: &dyn Material, medium: Medium) -> MediumStack {
+        let mut entries = self.entries.clone();
+        entries.push((material_id(material), medium));
+        MediumStack { entries }
+    }
+
+    pub fn exited(&self, material: &dyn Material) -> MediumStack {
+        let id = material_id(material);
+        let mut entries = self.entries.clone();
+        if let Some(index) = entries.iter().rposition(|(entry, _)| *entry == id) {
+            entries.remove(index);
+        }
+        MediumStack { entries }
+    }
+
+    // Medium with the highest priority, the most recently entered one wins a tie.
+    fn top(&self) -> Option<&(usize, Medium)> {
+        // `max_by_key` returns the last of equal elements
+        self.entries.iter().max_by_key(|(_, medium)| medium.priority)
+    }
+}
+
+// Materials are shared by all objects made of them, so the address identifies the medium.
+fn material_id(material: &dyn Material) -> usize {
+    material as *const dyn Material as *const () as usize
+}
@@ -0,0 +1,279 @@
+use std::{ops::Range, sync::Arc};
+
+use crate::{
+    aabb::Aabb,
+    accel::{Accelerator, SpatialIndex},
+    arena::Arena,
+    color::Color,
+    float::Float,
+    hittable::{Hit, Hittable},
+    material::Material,
+    packet::{RayPacket, PACKET_SIZE},
+    range::Interval,
+    ray::Ray,
+    simd::{Floats, Mask, Vec3s},
+    stats::{self, Counter},
+    transform::Transform,
+    vec3::{Point, Vec3},
+};
+
+// Triangles sharing a vertex buffer. Every face picks its material from the mesh material table, so a single imported
+// object can mix several materials like OBJ and glTF models do.
+pub struct TriangleMesh {
+    vertices: Vec<Point>,
+    triangles: Vec<[usize; 3]>, // vertex indices, counter-clockwise when looking at the front face
+    materials: Vec<Arc<dyn Material>>, // material table
+    face_materials: Vec<usize>, // index into the material table for every triangle, empty if all use the first
+    // Normals of the vertices to shade the faces smoothly with, empty for flat faces
+    normals: Vec<Vec3>,
+    // Colors of the vertices tinting the diffuse reflection, empty if the mesh has none
+    colors: Vec<Color>,
+    index: SpatialIndex, // acceleration structure over the triangles
+}
+
+impl TriangleMesh {
+    pub fn new(
+        vertices: Vec<Point>,
+        triangles: Vec<[usize; 3]>,
+        material: Arc<dyn Material>,
+        accelerator: Accelerator,
+    ) -> TriangleMesh {
+        assert!(
+            triangles.iter().flatten().all(|&index| index < vertices.len()),
+            "triangle refers to a missing vertex"
+        );
+        let bounds: Vec<Aabb> = triangles
+            .iter()
+            .map(|triangle| Aabb::from_points(triangle.map(|index| vertices[index])))
+            .collect();
+        TriangleMesh {
+            index: SpatialIndex::new(&bounds, accelerator),
+            vertices,
+            triangles,
+            materials: vec![material],
+            face_materials: vec![],
+            normals: vec![],
+            colors: vec![],
+        }
+    }
+
+    // Assign materials per face, `face_materials` holds an index into `materials` for every triangle.
+    pub fn with_face_materials(self, materials: Vec<Arc<dyn Material>>, face_materials: Vec<usize>) -> TriangleMesh {
+        assert_eq!(
+            face_materials.len(),
+            self.triangles.len(),
+            "every triangle needs a material"
+        );
+        assert!(
+            face_materials.iter().all(|&index| index < materials.len()),
+            "face refers to a missing material"
+        );
+        TriangleMesh {
+            materials,
+            face_materials,
+            ..self
+        }
+    }
+
+    // Shade the faces smoothly by interpolating a normal for every vertex over them.
+    pub fn with_normals(self, normals: Vec<Vec3>) -> TriangleMesh {
+        assert_eq!(normals.len(), self.vertices.len(), "every vertex needs a normal");
+        TriangleMesh { normals, ..self }
+    }
+
+    // Tint the diffuse reflection of the faces with colors interpolated between their vertices.
+    pub fn with_colors(self, colors: Vec<Color>) -> TriangleMesh {
+        assert_eq!(colors.len(), self.vertices.len(), "every vertex needs a color");
+        TriangleMesh { colors, ..self }
+    }
+
+    pub fn len(&self) -> usize {
+        self.triangles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.triangles.is_empty()
+    }
+
+    fn material(&self, face: usize) -> &dyn Material {
+        let index = self.face_materials.get(face).copied().unwrap_or(0);
+        self.materials[index].as_ref()
+    }
+
+    fn hit_triangle(&self, face: usize, ray: Ray, t_range: &Range<Float>) -> Option<Float> {
+        hit_triangle(self.triangles[face].map(|index| self.vertices[index]), ray, t_range)
+    }
+
+    // Lanes of the packet that may hit the triangle, a single precision version of `hit_triangle` with some slack so
+    // that it never rejects a hit. Most rays miss most triangles, the others are tested exactly afterwards.
+    fn triangle_candidates<const N: usize>(&self, face: usize, packet: &RayPacket<N>, lanes: Mask<N>) -> Mask<N> {
+        const SLACK: f32 = 1e-4;
+        stats::count(Counter::TriangleTests, 1);
+        let [a, b, c] = self.triangles[face].map(|index| Vec3s::<N>::splat(self.vertices[index]));
+        let edge1 = b - a;
+        let edge2 = c - a;
+
+        let p = Vec3s::cross(packet.direction, edge2);
+        let determinant = Vec3s::dot(edge1, p);
+        let inv_determinant = determinant.recip();
+        let s = packet.origin - a;
+        let u = Vec3s::dot(s, p) * inv_determinant;
+        let q = Vec3s::cross(s, edge1);
+        let v = Vec3s::dot(packet.direction, q) * inv_determinant;
+        // Parallel rays give infinite or NaN coordinates, which fail the comparisons
+        let low = Floats::splat(-SLACK);
+        let high = Floats::splat(1.0 + SLACK);
+        let inside = low.le(u) & low.le(v) & (u + v).le(high);
+        // The coordinates of rays nearly parallel to the triangle are too inaccurate to reject anything
+        let scale =
+            Vec3s::dot(edge1, edge1) * Vec3s::dot(edge2, edge2) * Vec3s::dot(packet.direction, packet.direction);
+        let grazing = (determinant * determinant).le(Floats::splat(SLACK) * scale);
+        lanes & (inside | grazing)
+    }
+
+    fn face_hit(&self, face: usize, ray: Ray, t: Float) -> Hit<'_> {
+        let triangle = self.triangles[face];
+        let vertices = triangle.map(|index| self.vertices[index]);
+        let uv = triangle_uv(vertices, ray.at(t));
+        let mut hit = Hit::new(ray, t, triangle_normal(vertices), self.material(face)).with_uv(uv);
+        let [a, b, c] = triangle;
+        let [u, v] = uv;
+        if !self.normals.is_empty() {
+            // The interpolated normal is turned to the side of the face the ray hits
+            let normal = (self.normals[a] * (1.0 - u - v) + self.normals[b] * u + self.normals[c] * v).normalize();
+            if normal.length_squared().is_finite() {
+                hit.normal = if Vec3::dot(normal, hit.normal) < 0.0 {
+                    -normal
+                } else {
+                    normal
+                };
+            }
+        }
+        if !self.colors.is_empty() {
+            hit = hit.with_color(self.colors[a] * (1.0 - u - v) + self.colors[b] * u + self.colors[c] * v);
+        }
+        hit
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, ray: Ray, t_range: Range<Float>) -> Option<Hit<'_>> {
+        let mut closest: Option<(usize, Float)> = None;
+        self.index.traverse(ray, t_range, |face, t_range| {
+            let t = self.hit_triangle(face, ray, &t_range)?;
+            closest = Some((face, t));
+            Some(t)
+        });
+
+        let (face, t) = closest?;
+        Some(self.face_hit(face, ray, t))
+    }
+
+    fn hit_any(&self, ray: Ray, t_range: Range<Float>) -> bool {
+        self.index.traverse_any(ray, t_range, |face, t_range| {
+            self.hit_triangle(face, ray, &t_range).is_some()
+        })
+    }
+
+    fn flatten(&self, transform: &Transform, arena: &mut Arena) -> bool {
+        // The arena only stores flat faces of a single color
+        if !self.normals.is_empty() || !self.colors.is_empty() {
+            return false;
+        }
+        // Mirroring turns the faces around, swapping two vertices keeps the front faces in front
+        let flipped: Vec<[usize; 3]>;
+        let triangles = if transform.is_mirroring() {
+            flipped = self.triangles.iter().map(|&[a, b, c]| [a, c, b]).collect();
+            &flipped
+        } else {
+            &self.triangles
+        };
+        let vertices = self.vertices.iter().map(|&vertex| transform.point(vertex));
+        let material = |face: usize| &self.materials[self.face_materials.get(face).copied().unwrap_or(0)];
+        arena.add_mesh(vertices, triangles, material);
+        true
+    }
+
+    fn hit_packet<'a>(&'a self, rays: &[Ray], t_range: Range<Float>, hits: &mut [Option<Hit<'a>>]) {
+        for (rays, hits) in rays.chunks(PACKET_SIZE).zip(hits.chunks_mut(PACKET_SIZE)) {
+            let packet = RayPacket::<PACKET_SIZE>::new(rays);
+            let mut t_ends = [t_range.end; PACKET_SIZE];
+            for (t_end, hit) in t_ends.iter_mut().zip(hits.iter()) {
+                *t_end = hit.map_or(*t_end, |hit| hit.t);
+            }
+            let mut faces = [None; PACKET_SIZE];
+            self.index
+                .traverse_packet(&packet, t_range.start, &mut t_ends, |face, lanes, t_ends| {
+                    for lane in self.triangle_candidates(face, &packet, lanes).lanes() {
+                        if let Some(t) = self.hit_triangle(face, packet.rays[lane], &(t_range.start..t_ends[lane])) {
+                            t_ends[lane] = t;
+                            faces[lane] = Some(face);
+                        }
+                    }
+                });
+            for (lane, hit) in hits.iter_mut().enumerate() {
+                if let Some(face) = faces[lane] {
+                    *hit = Some(self.face_hit(face, rays[lane], t_ends[lane]));
+                }
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.index.bounds()
+    }
+}
+
+// Ray-triangle intersection using the Möller–Trumbore algorithm, returns the distance along the ray.
+pub(crate) fn hit_triangle([a, b, c]: [Point; 3], ray: Ray, t_range: &Range<Float>) -> Option<Float> {
+    stats::count(Counter::TriangleTests, 1);
+    let edge1 = b - a;
+    let edge2 = c - a;
+
+    let p = Vec3::cross(ray.direction, edge2);
+    let determinant = Vec3::dot(edge1, p);
+    // The ray is parallel to the triangle plane
+    if determinant.abs() < 1e-12 {
+        return None;
+    }
+    let inv_determinant = 1.0 / determinant;
+
+    // Barycentric coordinates of the intersection with the triangle plane
+    let s = ray.origin - a;
+    let u = Vec3::dot(s, p) * inv_determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = Vec3::cross(s, edge1);
+    let v = Vec3::dot(ray.direction, q) * inv_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = Vec3::dot(edge2, q) * inv_determinant;
+    t_range.surrounds(t).then_some(t)
+}
+
+// Unit normal on the side the vertices are counter-clockwise from.
+pub(crate) fn triangle_normal([a, b, c]: [Point; 3]) -> Vec3 {
+    Vec3::cross(b - a, c - a).normalize()
+}
+
+// Barycentric coordinates of `point` in the plane of the triangle, the weights of the second and the third vertex.
+// Meshes have no texture coordinates, these are the surface coordinates of their triangles.
+pub(crate) fn triangle_uv([a, b, c]: [Point; 3], point: Point) -> [Float; 2] {
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let offset = point - a;
+    let (d11, d12, d22) = (
+        Vec3::dot(edge1, edge1),
+        Vec3::dot(edge1, edge2),
+        Vec3::dot(edge2, edge2),
+    );
+    let (d1, d2) = (Vec3::dot(offset, edge1), Vec3::dot(offset, edge2));
+    let denominator = d11 * d22 - d12 * d12;
+    if denominator == 0.0 {
+        return [0.0, 0.0];
+    }
+    [(d22 * d1 - d12 * d2) / denominator, (d11 * d2 - d12 * d1) / denominator]
+}
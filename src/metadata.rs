@@ -0,0 +1,182 @@
+use std::{
+    fmt::Write as _,
+    fs,
+    io::{self, Write},
+    path::Path,
+    time::Duration,
+};
+
+use rustracer::{
+    accel::Accelerator,
+    camera::{CameraSettings, RenderInfo},
+};
+
+use crate::cli::Options;
+
+// Settings, timings and counters of a render for `--stats-json`, so a build pipeline can track the cost of scenes
+// over commits. Written as JSON by hand, which keeps the binary free of dependencies in the minimal build.
+pub struct Metadata<'a> {
+    pub options: &'a Options,
+    pub camera: CameraSettings,
+    pub image_size: [i32; 2],
+    pub accelerator: Accelerator,
+    pub scene_load: Duration, // parsing the scene, loading meshes and building their acceleration structures
+    pub acceleration_build: Duration, // acceleration structure over the objects of the scene
+    pub render: Duration,
+    pub output: Duration, // writing the image
+    pub info: RenderInfo,
+}
+
+impl Metadata<'_> {
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut out = io::BufWriter::new(fs::File::create(path)?);
+        out.write_all(self.to_json().as_bytes())?;
+        out.flush()
+    }
+
+    fn to_json(&self) -> String {
+        let options = self.options;
+        let camera = &self.camera;
+        let info = &self.info;
+        let stats = &info.stats;
+        let [width, height] = self.image_size;
+        let total = self.scene_load + self.acceleration_build + self.render + self.output;
+        let optional = |value: Option<String>| value.unwrap_or_else(|| "null".to_string());
+        let integrator = format!(r#"{{"name": {}}}"#, json_string(options.integrator.name()));
+
+        let mut json = String::new();
+        let _ = write!(
+            json,
+            r#"{{
+  "scene": {scene},
+  "settings": {{
+    "image_width": {width},
+    "image_height": {height},
+    "samples_per_pixel": {samples_per_pixel},
+    "max_depth": {max_depth},
+    "filter": {filter},
+    "projection": {projection},
+    "stereo": {stereo},
+    "exposure": {exposure},
+    "color_space": {color_space},
+    "integrator": {integrator},
+    "accelerator": {accelerator},
+    "packets": {packets},
+    "wavefront": {wavefront},
+    "sort_rays": {sort_rays},
+    "arena": {arena},
+    "crop": {crop},
+    "time_budget": {time_budget},
+    "overrides": [{overrides}]
+  }},
+  "timings": {{
+    "scene_load": {scene_load:.6},
+    "acceleration_build": {acceleration_build:.6},
+    "render": {render:.6},
+    "output": {output:.6},
+    "total": {total:.6}
+  }},
+  "samples": {samples},
+  "samples_per_pixel": {average_samples},
+  "stopped": {stopped},
+  "stats": {{
+    "counted": {counted},
+    "primary_rays": {primary_rays},
+    "secondary_rays": {secondary_rays},
+    "shadow_rays": {shadow_rays},
+    "node_visits": {node_visits},
+    "triangle_tests": {triangle_tests},
+    "sphere_tests": {sphere_tests}
+  }},
+  "peak_memory": {peak_memory}
+}}
+"#,
+            scene = optional(
+                options
+                    .scene
+                    .as_ref()
+                    .map(|path| json_string(&path.display().to_string()))
+            ),
+            samples_per_pixel = camera.samples_per_pixel,
+            max_depth = camera.max_depth,
+            filter = json_string(&format!("{:?}", camera.filter).to_lowercase()),
+            projection = json_string(&format!("{:?}", camera.projection).to_lowercase()),
+            stereo = json_string(camera.stereo.name()),
+            exposure = camera.exposure,
+            color_space = json_string(&camera.color_space.to_string()),
+            accelerator = accelerator_json(self.accelerator),
+            packets = options.packets,
+            wavefront = options.wavefront,
+            sort_rays = options.sort_rays,
+            arena = options.arena,
+            crop = optional(options.crop.map(|[x0, y0, x1, y1]| format!("[{x0}, {y0}, {x1}, {y1}]"))),
+            time_budget = optional(options.time.map(|time| time.as_secs_f64().to_string())),
+            overrides = options
+                .overrides
+                .iter()
+                .map(|o| json_string(&o.to_string()))
+                .collect::<Vec<_>>()
+                .join(", "),
+            scene_load = self.scene_load.as_secs_f64(),
+            acceleration_build = self.acceleration_build.as_secs_f64(),
+            render = self.render.as_secs_f64(),
+            output = self.output.as_secs_f64(),
+            total = total.as_secs_f64(),
+            samples = info.samples,
+            average_samples = info.samples_per_pixel,
+            stopped = info.stopped,
+            counted = cfg!(feature = "stats"),
+            primary_rays = stats.primary_rays,
+            secondary_rays = stats.secondary_rays,
+            shadow_rays = stats.shadow_rays,
+            node_visits = stats.node_visits,
+            triangle_tests = stats.triangle_tests,
+            sphere_tests = stats.sphere_tests,
+            peak_memory = optional(peak_memory().map(|bytes| bytes.to_string())),
+        );
+        json
+    }
+}
+
+// Acceleration structure as its name and, for a BVH, how it is built.
+fn accelerator_json(accelerator: Accelerator) -> String {
+    match accelerator {
+        Accelerator::Bvh(bvh) => format!(
+            r#"{{"name": "bvh", "builder": {}, "width": {}, "compact": {}}}"#,
+            json_string(bvh.builder.name()),
+            bvh.width.children(),
+            bvh.compact
+        ),
+        accelerator => format!(r#"{{"name": {}}}"#, json_string(accelerator.name())),
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut json = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+// Peak resident memory of the process in bytes, only known on Linux.
+fn peak_memory() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kilobytes: u64 = line
+        .trim_start_matches("VmHWM:")
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kilobytes * 1024)
+}
@@ -0,0 +1,55 @@
+use crate::float::consts::PI;
+
+use crate::{float::Float, onb::Onb, util::random_double, vec3::Vec3};
+
+// Trowbridge-Reitz (GGX) distribution of microfacet normals for rough surfaces.
+#[derive(Copy, Clone)]
+pub struct Ggx {
+    alpha: Float, // width of the distribution
+}
+
+impl Ggx {
+    // Map the perceptual roughness in [0, 1] to the distribution width, very small widths are numerically unstable.
+    pub fn from_roughness(roughness: Float) -> Ggx {
+        let roughness = roughness.clamp(0.0, 1.0);
+        Ggx {
+            alpha: (roughness * roughness).max(1e-3),
+        }
+    }
+
+    // Sample a microfacet normal visible from direction `v` (pointing away from the surface with normal `normal`)
+    // as described in "Sampling the GGX Distribution of Visible Normals" by Eric Heitz.
+    pub fn sample_visible_normal(&self, normal: Vec3, v: Vec3) -> Vec3 {
+        let onb = Onb::new(normal);
+        let v = onb.world_to_local(v);
+
+        // Transform the view direction to the hemisphere configuration
+        let vh = Vec3::new(self.alpha * v.x, self.alpha * v.y, v.z).normalize();
+        let len_squared = vh.x * vh.x + vh.y * vh.y;
+        let t1 = if len_squared > 0.0 {
+            Vec3::new(-vh.y, vh.x, 0.0) / len_squared.sqrt()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = Vec3::cross(vh, t1);
+
+        // Sample a point on the projected disk
+        let r = random_double().sqrt();
+        let phi = 2.0 * PI * random_double();
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z);
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+
+        // Reproject onto the hemisphere and transform back to the ellipsoid configuration
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+        let m = Vec3::new(self.alpha * nh.x, self.alpha * nh.y, nh.z.max(1e-6)).normalize();
+        onb.local(m)
+    }
+
+    // Smith masking function for direction `w`, the fraction of microfacets visible from it.
+    pub fn g1(&self, normal: Vec3, w: Vec3) -> Float {
+        let cos2 = Vec3::dot(normal, w).powi(2) / w.length_squared();
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        2.0 / (1.0 + (1.0 + self.alpha * self.alpha * tan2).sqrt())
+    }
+}
@@ -0,0 +1,959 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    accel::{AcceleratedList, Accelerator},
+    animation::Keyframes,
+    bvh::BvhSettings,
+    camera::CameraSettings,
+    color::Color,
+    error::{Error, Result},
+    filter::Filter,
+    float::{consts, Float},
+    hittable::{Hittable, HittableList},
+    id::Names,
+    instance::Instance,
+    light_linking::LightLinks,
+    material::{ComplexIor, Dielectric, DiffuseLight, Lambertian, Material, Metal, Plastic},
+    mesh::TriangleMesh,
+    obj::ObjModel,
+    parser::Warnings,
+    ply::PlyModel,
+    presets,
+    scene::Scene,
+    sphere::{Sphere, SphereList},
+    transform::Transform,
+    util::degrees_to_radians,
+    vec3::{Point, Vec3},
+};
+
+// Importer of scenes in the XML format of the Mitsuba 3 renderer, to check images against it on the same input, e.g.
+//
+//   <scene version="3.0.0">
+//       <sensor type="perspective">
+//           <float name="fov" value="40"/>
+//           <transform name="to_world"><lookat origin="0, 1, 4" target="0, 0, 0" up="0, 1, 0"/></transform>
+//           <film type="hdrfilm"><integer name="width" value="640"/><integer name="height" value="480"/></film>
+//       </sensor>
+//       <bsdf type="diffuse" id="gray"><rgb name="reflectance" value="0.5"/></bsdf>
+//       <shape type="sphere"><float name="radius" value="1"/><ref id="gray"/></shape>
+//       <emitter type="constant"><rgb name="radiance" value="1"/></emitter>
+//   </scene>
+//
+// Like the PBRT importer it's an approximation of what the format describes. It reads the perspective sensor with its
+// film, sampler and filter, the depth of the path integrator, `include` and `default` parameters, the shapes `obj`,
+// `ply`, `sphere`, `rectangle`, `cube`, `disk` and instances of `shapegroup`s, the smooth and rough variants of the
+// `diffuse`, `conductor`, `dielectric` and `plastic` BSDFs, area emitters and constant environment emitters. Textures,
+// media, other shapes, BSDFs and emitters are skipped or approximated with a warning.
+pub fn load(path: &Path, bvh: BvhSettings) -> Result<Scene> {
+    let mut files = vec![];
+    let root = read_file(path, &mut files)?;
+    if root.name != "scene" {
+        return Err(Error::parse(root.line, "expected a <scene> element"));
+    }
+    let mut importer = Importer::new(path, Accelerator::Bvh(bvh));
+    importer.files = files;
+    for element in &root.children {
+        importer.scene_element(element)?;
+    }
+    Ok(importer.finish())
+}
+
+// Element of an XML document, without its text, which Mitsuba scenes don't use.
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Element>,
+    line: usize,
+}
+
+impl Element {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn required(&self, name: &str) -> Result<&str> {
+        self.attribute(name)
+            .ok_or_else(|| Error::parse(self.line, format!("<{}> needs the attribute '{name}'", self.name)))
+    }
+
+    // Child property with the given `name` attribute, e.g. `<float name="radius" value="2"/>`.
+    fn property(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.attribute("name") == Some(name))
+    }
+}
+
+// Read a scene file and splice the scenes it includes into it, adding their paths to `files`.
+fn read_file(path: &Path, files: &mut Vec<PathBuf>) -> Result<Element> {
+    files.push(path.to_path_buf());
+    let mut root = parse_xml(&fs::read_to_string(path)?)?;
+    let base = path.parent().unwrap_or(Path::new(""));
+    let mut children = vec![];
+    for child in root.children {
+        if child.name != "include" {
+            children.push(child);
+            continue;
+        }
+        let included = base.join(child.required("filename")?);
+        let scene = read_file(&included, files)
+            .map_err(|err| Error::parse(child.line, format!("{}: {err}", included.display())))?;
+        children.extend(scene.children);
+    }
+    root.children = children;
+    Ok(root)
+}
+
+// Minimal XML parser: elements with attributes, comments, processing instructions and a document type declaration,
+// the five predefined entities and character references. Text between elements is skipped.
+fn parse_xml(source: &str) -> Result<Element> {
+    let mut stack: Vec<Element> = vec![];
+    let mut root = None;
+    let mut rest = source;
+    let line_of = |rest: &str| source[..source.len() - rest.len()].matches('\n').count() + 1;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        let line = line_of(rest);
+        let skip = |rest: &mut &str, end: &str| -> Result<()> {
+            let index = rest
+                .find(end)
+                .ok_or_else(|| Error::parse(line, format!("missing '{end}'")))?;
+            *rest = &rest[index + end.len()..];
+            Ok(())
+        };
+        if rest.starts_with("<!--") {
+            skip(&mut rest, "-->")?;
+        } else if rest.starts_with("<?") {
+            skip(&mut rest, "?>")?;
+        } else if rest.starts_with("<![CDATA[") {
+            skip(&mut rest, "]]>")?;
+        } else if rest.starts_with("<!") {
+            skip(&mut rest, ">")?;
+        } else if let Some(tag) = rest.strip_prefix("</") {
+            let end = tag.find('>').ok_or_else(|| Error::parse(line, "missing '>'"))?;
+            let name = tag[..end].trim();
+            let element = stack
+                .pop()
+                .filter(|element| element.name == name)
+                .ok_or_else(|| Error::parse(line, format!("unexpected </{name}>")))?;
+            rest = &tag[end + 1..];
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(element),
+                None => root = Some(element),
+            }
+        } else {
+            let (element, closed, after) = parse_tag(&rest[1..], line)?;
+            rest = after;
+            match (closed, stack.last_mut()) {
+                (false, _) => stack.push(element),
+                (true, Some(parent)) => parent.children.push(element),
+                (true, None) => root = Some(element),
+            }
+        }
+        if root.is_some() {
+            break;
+        }
+    }
+    if let Some(element) = stack.last() {
+        return Err(Error::parse(
+            element.line,
+            format!("<{}> is never closed", element.name),
+        ));
+    }
+    root.ok_or_else(|| Error::parse(1, "no XML element"))
+}
+
+// Parse the start tag after its '<'. Returns the element, whether the tag closes it as well and the text after it.
+fn parse_tag(text: &str, line: usize) -> Result<(Element, bool, &str)> {
+    let name_end = text
+        .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        .ok_or_else(|| Error::parse(line, "missing '>'"))?;
+    let mut element = Element {
+        name: text[..name_end].to_string(),
+        attributes: vec![],
+        children: vec![],
+        line,
+    };
+    let mut rest = text[name_end..].trim_start();
+    loop {
+        if let Some(after) = rest.strip_prefix("/>") {
+            return Ok((element, true, after));
+        }
+        if let Some(after) = rest.strip_prefix('>') {
+            return Ok((element, false, after));
+        }
+        let invalid = || Error::parse(line, format!("invalid attribute in <{}>", element.name));
+        let equals = rest.find('=').ok_or_else(invalid)?;
+        let key = rest[..equals].trim().to_string();
+        let value = rest[equals + 1..].trim_start();
+        let quote = value
+            .chars()
+            .next()
+            .filter(|&c| c == '"' || c == '\'')
+            .ok_or_else(invalid)?;
+        let end = value[1..].find(quote).ok_or_else(invalid)?;
+        element.attributes.push((key, unescape(&value[1..end + 1])));
+        rest = value[end + 2..].trim_start();
+    }
+}
+
+fn unescape(text: &str) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else { break };
+        let entity = &rest[1..end];
+        let character = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => match entity.strip_prefix("#x") {
+                Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                None => entity
+                    .strip_prefix('#')
+                    .and_then(|decimal| decimal.parse().ok())
+                    .and_then(char::from_u32),
+            },
+        };
+        match character {
+            Some(character) => {
+                result.push(character);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+struct Importer {
+    file: PathBuf, // scene file, for the files it refers to
+    // files read so far, the scene files and meshes
+    files: Vec<PathBuf>,
+    accelerator: Accelerator,
+    warnings: Warnings,
+    defaults: HashMap<String, String>, // parameters of the scene declared with <default>
+    materials: HashMap<String, Option<Arc<dyn Material>>>, // BSDFs with an id, None for the null BSDF
+    groups: HashMap<String, Arc<dyn Hittable>>, // shape groups by id
+    camera: CameraSettings,
+    world: HittableList,
+    spheres: Vec<Sphere>,
+}
+
+impl Importer {
+    fn new(path: &Path, accelerator: Accelerator) -> Importer {
+        Importer {
+            file: path.to_path_buf(),
+            files: vec![],
+            accelerator,
+            warnings: Warnings::new(path),
+            defaults: HashMap::new(),
+            materials: HashMap::new(),
+            groups: HashMap::new(),
+            camera: CameraSettings {
+                aspect_ratio: 768.0 / 576.0,
+                image_width: 768,
+                samples_per_pixel: 4,
+                filter: Filter::Gaussian,
+                background: Some(Color::BLACK),
+                ..CameraSettings::default()
+            },
+            world: vec![],
+            spheres: vec![],
+        }
+    }
+
+    // Attribute with the `$name` references to the defaults of the scene replaced.
+    fn value(&self, element: &Element, name: &str) -> Result<String> {
+        let mut value = element.required(name)?.to_string();
+        if value.contains('$') {
+            // Longer names first, so `$spp` doesn't replace the start of `$spp_max`
+            let mut defaults: Vec<_> = self.defaults.iter().collect();
+            defaults.sort_by_key(|(name, _)| std::cmp::Reverse(name.len()));
+            for (name, default) in defaults {
+                value = value.replace(&format!("${name}"), default);
+            }
+        }
+        Ok(value)
+    }
+
+    fn numbers(&self, element: &Element, name: &str) -> Result<Vec<Float>> {
+        let value = self.value(element, name)?;
+        value
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|number| !number.is_empty())
+            .map(|number| number.parse())
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|_| Error::parse(element.line, format!("invalid numbers '{value}'")))
+    }
+
+    fn number(&self, element: &Element, name: &str) -> Result<Float> {
+        match self.numbers(element, name)?[..] {
+            [number] => Ok(number),
+            _ => Err(Error::parse(element.line, format!("'{name}' must be a single number"))),
+        }
+    }
+
+    // Number property of `element`, e.g. `<float name="radius" value="2"/>`.
+    fn float(&self, element: &Element, name: &str, default: Float) -> Result<Float> {
+        match element.property(name) {
+            Some(property) => self.number(property, "value"),
+            None => Ok(default),
+        }
+    }
+
+    fn string(&self, element: &Element, name: &str) -> Result<Option<String>> {
+        element
+            .property(name)
+            .map(|property| self.value(property, "value"))
+            .transpose()
+    }
+
+    fn bool(&self, element: &Element, name: &str) -> Result<bool> {
+        match self.string(element, name)?.as_deref() {
+            None | Some("false") => Ok(false),
+            Some("true") => Ok(true),
+            Some(value) => Err(Error::parse(element.line, format!("invalid boolean '{value}'"))),
+        }
+    }
+
+    // Point or vector, written as `value="x, y, z"` or with `x`, `y` and `z` attributes.
+    fn vector(&self, element: &Element, default: Float) -> Result<Vec3> {
+        if element.attribute("value").is_some() {
+            return match self.numbers(element, "value")?[..] {
+                [x, y, z] => Ok(Vec3::new(x, y, z)),
+                [value] => Ok(Vec3::new(value, value, value)),
+                _ => Err(Error::parse(element.line, "expected three numbers")),
+            };
+        }
+        let coordinate = |name| match element.attribute(name) {
+            Some(_) => self.number(element, name),
+            None => Ok(default),
+        };
+        Ok(Vec3::new(coordinate("x")?, coordinate("y")?, coordinate("z")?))
+    }
+
+    // Color property, from RGB values, a constant, pairs of wavelength and value (averaged into a gray) or the
+    // temperature of a black body, normalized to a brightest channel of 1 like in the PBRT importer and then scaled.
+    // Textures are skipped, the caller falls back to its default.
+    fn color(&mut self, element: &Element, name: &str, default: Color) -> Result<Color> {
+        let Some(property) = element.property(name) else {
+            return Ok(default);
+        };
+        match property.name.as_str() {
+            "rgb" | "float" => match self.numbers(property, "value")?[..] {
+                [r, g, b] => Ok(Color::new(r, g, b)),
+                [value] => Ok(Color::splat(value)),
+                _ => Err(Error::parse(property.line, "expected one or three numbers")),
+            },
+            "spectrum" if property.attribute("type") == Some("blackbody") => {
+                let scale = self.float(property, "scale", 1.0)?;
+                Ok(Color::blackbody(self.float(property, "temperature", 6504.0)?) * scale)
+            }
+            "spectrum" => {
+                let value = self.value(property, "value")?;
+                let values: Vec<Float> = value
+                    .split(',')
+                    .map(|pair| pair.rsplit(':').next().unwrap_or_default().trim().parse())
+                    .collect::<std::result::Result<_, _>>()
+                    .map_err(|_| Error::parse(property.line, format!("invalid spectrum '{value}'")))?;
+                Ok(Color::splat(
+                    values.iter().sum::<Float>() / values.len().max(1) as Float,
+                ))
+            }
+            "texture" => {
+                self.warnings.at_line(property.line, "textures are not supported");
+                Ok(default)
+            }
+            kind => Err(Error::parse(property.line, format!("<{kind}> is not a color"))),
+        }
+    }
+
+    // Index of refraction, a number or the name of a material.
+    fn ior(&mut self, element: &Element, name: &str, default: Float) -> Result<Float> {
+        let Some(value) = self.string(element, name)? else {
+            return Ok(match element.property(name) {
+                Some(property) => self.number(property, "value")?,
+                None => default,
+            });
+        };
+        if let Ok(ior) = value.parse() {
+            return Ok(ior);
+        }
+        let ior = match value.as_str() {
+            "bk7" => Some(1.5046),
+            "acrylic glass" => Some(1.49),
+            "fused quartz" => Some(1.458),
+            name => presets::ior(name),
+        };
+        Ok(ior.unwrap_or_else(|| {
+            self.warnings.at_line(
+                element.line,
+                format!("using {default} instead of the index of refraction '{value}'"),
+            );
+            default
+        }))
+    }
+
+    // Transformation of a `<transform>` property, the operations applied in order.
+    fn transform(&self, element: &Element, name: &str) -> Result<Transform> {
+        let Some(property) = element.property(name) else {
+            return Ok(Transform::IDENTITY);
+        };
+        let mut transform = Transform::IDENTITY;
+        for operation in &property.children {
+            let line = operation.line;
+            let next = match operation.name.as_str() {
+                "translate" => Transform::translation(self.vector(operation, 0.0)?),
+                "scale" => {
+                    let factors = self.vector(operation, 1.0)?;
+                    if factors.x == 0.0 || factors.y == 0.0 || factors.z == 0.0 {
+                        return Err(Error::parse(line, "scale must not be zero"));
+                    }
+                    Transform::scaling(factors)
+                }
+                "rotate" => {
+                    let axis = self.vector(operation, 0.0)?;
+                    let angle = self.number(operation, "angle")?;
+                    crate::pbrt::rotation(angle, axis)
+                        .ok_or_else(|| Error::parse(line, "rotation around a zero axis"))?
+                }
+                "matrix" => {
+                    let numbers = self.numbers(operation, "value")?;
+                    if numbers.len() != 16 && numbers.len() != 9 {
+                        return Err(Error::parse(line, "a matrix needs 16 or 9 numbers"));
+                    }
+                    let size = if numbers.len() == 16 { 4 } else { 3 };
+                    let row = |r: usize| {
+                        let value = |c: usize| if c < size { numbers[r * size + c] } else { 0.0 };
+                        [value(0), value(1), value(2), value(3)]
+                    };
+                    Transform::from_matrix([row(0), row(1), row(2)])
+                        .ok_or_else(|| Error::parse(line, "singular matrix"))?
+                }
+                "lookat" => {
+                    let point = |name| -> Result<Vec3> {
+                        match self.numbers(operation, name)?[..] {
+                            [x, y, z] => Ok(Vec3::new(x, y, z)),
+                            _ => Err(Error::parse(line, format!("'{name}' needs three numbers"))),
+                        }
+                    };
+                    let up = match operation.attribute("up") {
+                        Some(_) => point("up")?,
+                        None => Vec3::new(0.0, 1.0, 0.0),
+                    };
+                    look_at(point("origin")?.to_point(), point("target")?.to_point(), up)
+                        .ok_or_else(|| Error::parse(line, "the up vector is parallel to the viewing direction"))?
+                }
+                kind => return Err(Error::parse(line, format!("unknown transformation <{kind}>"))),
+            };
+            transform = transform.then(next);
+        }
+        Ok(transform)
+    }
+
+    fn scene_element(&mut self, element: &Element) -> Result<()> {
+        match element.name.as_str() {
+            "default" => {
+                let name = element.required("name")?.to_string();
+                let value = self.value(element, "value")?;
+                self.defaults.insert(name, value);
+            }
+            "integrator" => {
+                if !matches!(element.required("type")?, "path" | "volpath" | "volpathmis" | "prb") {
+                    let message = format!(
+                        "rendering the '{}' integrator with the path tracer",
+                        element.required("type")?
+                    );
+                    self.warnings.at_line(element.line, message);
+                }
+                // Mitsuba counts the segments of a path like the tracer, -1 for no limit
+                let depth = self.float(element, "max_depth", -1.0)? as i32;
+                self.camera.max_depth = if depth < 0 { 50 } else { depth };
+            }
+            "sensor" => self.sensor(element)?,
+            "bsdf" => {
+                let id = element.required("id")?.to_string();
+                let material = self.bsdf(element)?;
+                self.materials.insert(id, material);
+            }
+            "shape" => self.shape(element, None)?,
+            "emitter" => self.emitter(element)?,
+            "texture" | "medium" => {
+                let message = format!("skipping <{}>, textures and media are not supported", element.name);
+                self.warnings.at_line(element.line, message);
+            }
+            name => return Err(Error::parse(element.line, format!("unknown element <{name}>"))),
+        }
+        Ok(())
+    }
+
+    fn sensor(&mut self, element: &Element) -> Result<()> {
+        let kind = element.required("type")?;
+        if kind != "perspective" && kind != "thinlens" {
+            self.warnings.at_line(
+                element.line,
+                format!("rendering the '{kind}' sensor as a perspective camera"),
+            );
+        }
+        let film = element.children.iter().find(|child| child.name == "film");
+        let (width, height) = match film {
+            Some(film) => (self.float(film, "width", 768.0)?, self.float(film, "height", 576.0)?),
+            None => (768.0, 576.0),
+        };
+        if width < 1.0 || height < 1.0 {
+            return Err(Error::parse(element.line, "the film size must be positive"));
+        }
+        self.camera.image_width = width as i32;
+        self.camera.aspect_ratio = width / height;
+        if let Some(filter) = film.and_then(|film| film.children.iter().find(|child| child.name == "rfilter")) {
+            let kind = filter.required("type")?;
+            self.camera.filter = kind.parse().unwrap_or_else(|_| {
+                self.warnings.at_line(
+                    filter.line,
+                    format!("using a Gaussian filter instead of the '{kind}' filter"),
+                );
+                Filter::Gaussian
+            });
+        }
+        if let Some(sampler) = element.children.iter().find(|child| child.name == "sampler") {
+            self.camera.samples_per_pixel = self.float(sampler, "sample_count", 4.0)?.max(1.0) as i32;
+        }
+
+        // Mitsuba measures the field of view along the x axis of the image by default
+        let fov = self.float(element, "fov", 39.3077)?;
+        let half = degrees_to_radians(fov / 2.0).tan();
+        let aspect = width / height;
+        let axis = self.string(element, "fov_axis")?.unwrap_or("x".to_string());
+        let vertical_half = match axis.as_str() {
+            "x" => half / aspect,
+            "y" => half,
+            "smaller" if width < height => half / aspect,
+            "larger" if width > height => half / aspect,
+            "smaller" | "larger" => half,
+            "diagonal" => half / (1.0 + aspect * aspect).sqrt(),
+            _ => return Err(Error::parse(element.line, format!("unknown fov_axis '{axis}'"))),
+        };
+        self.camera.vfov = 2.0 * vertical_half.atan().to_degrees();
+
+        // The camera looks along +z of its space with +y up
+        let to_world = self.transform(element, "to_world")?;
+        if to_world.is_mirroring() {
+            self.warnings
+                .at_line(element.line, "rendering the mirrored sensor without mirroring");
+        }
+        self.camera.look_from = to_world.point(Point::ORIGIN);
+        self.camera.look_at = to_world.point(Point::new(0.0, 0.0, 1.0));
+        self.camera.vup = to_world.vector(Vec3::new(0.0, 1.0, 0.0));
+        if kind == "thinlens" {
+            let radius = self.float(element, "aperture_radius", 0.0)?;
+            let distance = self.float(element, "focus_distance", 0.0)?;
+            if radius > 0.0 && distance > 0.0 {
+                self.camera.defocus_angle = 2.0 * (radius / distance).atan().to_degrees();
+                // Camera rays are as long as the focus distance
+                self.camera.look_at = to_world.point(Point::new(0.0, 0.0, distance));
+                self.camera.focus_dist = distance;
+            }
+        }
+        Ok(())
+    }
+
+    fn emitter(&mut self, element: &Element) -> Result<()> {
+        match element.required("type")? {
+            "constant" => self.camera.background = Some(self.color(element, "radiance", Color::WHITE)?),
+            "envmap" => {
+                self.warnings
+                    .at_line(element.line, "using the sky instead of the environment map");
+                self.camera.background = None;
+            }
+            kind => {
+                let message = format!("skipping the '{kind}' emitter, only area and constant emitters are supported");
+                self.warnings.at_line(element.line, message);
+            }
+        }
+        Ok(())
+    }
+
+    // Material of a BSDF, None for the null BSDF of shapes that only bound a medium.
+    fn bsdf(&mut self, element: &Element) -> Result<Option<Arc<dyn Material>>> {
+        let kind = element.required("type")?;
+        let roughness = |importer: &Importer| -> Result<Float> {
+            let alpha_u = importer.float(element, "alpha_u", 0.1)?;
+            let alpha_v = importer.float(element, "alpha_v", alpha_u)?;
+            importer.float(element, "alpha", (alpha_u + alpha_v) / 2.0)
+        };
+        let rough = kind.starts_with("rough");
+        let material: Arc<dyn Material> = match kind.strip_prefix("rough").unwrap_or(kind) {
+            // Wrappers of another BSDF, the tracer's materials are two-sided anyway
+            "twosided" | "mask" | "bumpmap" | "normalmap" => {
+                if kind != "twosided" {
+                    self.warnings
+                        .at_line(element.line, format!("rendering the '{kind}' BSDF without its effect"));
+                }
+                let inner = element
+                    .children
+                    .iter()
+                    .find(|child| child.name == "bsdf" || child.name == "ref")
+                    .ok_or_else(|| Error::parse(element.line, format!("'{kind}' BSDF needs a BSDF inside")))?;
+                return self.material_of(inner);
+            }
+            "null" => return Ok(None),
+            "diffuse" => Arc::new(Lambertian::new(self.color(
+                element,
+                "reflectance",
+                Color::splat(0.5),
+            )?)),
+            "conductor" => {
+                let fuzz = if rough { roughness(self)? } else { 0.0 };
+                let albedo = self.color(element, "specular_reflectance", Color::WHITE)?;
+                match self.conductor(element)? {
+                    Some(ior) => Arc::new(Metal {
+                        albedo,
+                        ..Metal::conductor(ior, fuzz)
+                    }),
+                    None => Arc::new(Metal::new(albedo, fuzz)),
+                }
+            }
+            "dielectric" => {
+                let ratio = self.ior(element, "int_ior", 1.5046)? / self.ior(element, "ext_ior", 1.000277)?;
+                let roughness = if rough { roughness(self)? } else { 0.0 };
+                Arc::new(Dielectric::new(ratio).with_roughness(roughness))
+            }
+            "thindielectric" => {
+                self.warnings
+                    .at_line(element.line, "approximating thin dielectrics by transparent surfaces");
+                Arc::new(Dielectric::new(1.0))
+            }
+            "plastic" => {
+                let ratio = self.ior(element, "int_ior", 1.49)? / self.ior(element, "ext_ior", 1.000277)?;
+                let roughness = if rough { roughness(self)? } else { 0.0 };
+                Arc::new(Plastic::new(
+                    self.color(element, "diffuse_reflectance", Color::splat(0.5))?,
+                    ratio,
+                    roughness,
+                ))
+            }
+            _ => {
+                self.warnings.at_line(
+                    element.line,
+                    format!("approximating '{kind}' BSDFs by diffuse materials"),
+                );
+                let albedo = match element.property("base_color") {
+                    Some(_) => self.color(element, "base_color", Color::splat(0.5))?,
+                    None => self.color(element, "reflectance", Color::splat(0.5))?,
+                };
+                Arc::new(Lambertian::new(albedo))
+            }
+        };
+        Ok(Some(material))
+    }
+
+    // Material of a `<bsdf>` or a `<ref>` to one.
+    fn material_of(&mut self, element: &Element) -> Result<Option<Arc<dyn Material>>> {
+        if element.name == "bsdf" {
+            return self.bsdf(element);
+        }
+        let id = element.required("id")?;
+        self.materials
+            .get(id)
+            .cloned()
+            .ok_or_else(|| Error::parse(element.line, format!("unknown BSDF '{id}'")))
+    }
+
+    // Optical constants of a conductor, the metals the tracer knows by their chemical symbol or RGB values. None for
+    // the perfect mirror Mitsuba uses by default.
+    fn conductor(&mut self, element: &Element) -> Result<Option<ComplexIor>> {
+        if let Some(name) = self.string(element, "material")? {
+            let preset = match name.as_str() {
+                "none" => return Ok(None),
+                "Au" => "gold",
+                "Ag" => "silver",
+                "Cu" | "Cu2O" => "copper",
+                "Al" | "Al2O3" => "aluminum",
+                _ => {
+                    self.warnings.at_line(
+                        element.line,
+                        format!("using aluminum instead of the conductor '{name}'"),
+                    );
+                    "aluminum"
+                }
+            };
+            return Ok(ComplexIor::preset(preset));
+        }
+        match (element.property("eta"), element.property("k")) {
+            (Some(_), Some(_)) => Ok(Some(ComplexIor::new(
+                self.color(element, "eta", Color::WHITE)?,
+                self.color(element, "k", Color::BLACK)?,
+            ))),
+            (None, None) => Ok(None),
+            _ => Err(Error::parse(element.line, "conductor needs both 'eta' and 'k'")),
+        }
+    }
+
+    // Add a shape to the scene, or to the list of a shape group.
+    fn shape(&mut self, element: &Element, group: Option<&mut HittableList>) -> Result<()> {
+        let line = element.line;
+        let kind = element.required("type")?;
+        if kind == "shapegroup" {
+            let id = element.required("id")?.to_string();
+            let mut list = vec![];
+            for child in element.children.iter().filter(|child| child.name == "shape") {
+                self.shape(child, Some(&mut list))?;
+            }
+            let shapes: Arc<dyn Hittable> = Arc::new(AcceleratedList::new(list, self.accelerator));
+            self.groups.insert(id, shapes);
+            return Ok(());
+        }
+        let transform = self.transform(element, "to_world")?;
+        if kind == "instance" {
+            let reference = element
+                .children
+                .iter()
+                .find(|child| child.name == "ref")
+                .ok_or_else(|| Error::parse(line, "instance needs a <ref> to a shape group"))?;
+            let id = reference.required("id")?;
+            let shapes = self
+                .groups
+                .get(id)
+                .ok_or_else(|| Error::parse(line, format!("unknown shape group '{id}'")))?
+                .clone();
+            self.world.push(Box::new(Instance::new(shapes, transform)));
+            return Ok(());
+        }
+
+        // An area emitter replaces the BSDF, shapes with the null BSDF aren't rendered
+        let emitter = element.children.iter().find(|child| child.name == "emitter");
+        let material = match emitter {
+            Some(emitter) if emitter.required("type")? == "area" => {
+                Some(Arc::new(DiffuseLight::new(self.color(emitter, "radiance", Color::WHITE)?)) as Arc<dyn Material>)
+            }
+            _ => match element
+                .children
+                .iter()
+                .find(|child| child.name == "bsdf" || child.name == "ref")
+            {
+                Some(bsdf) => self.material_of(bsdf)?,
+                None => Some(Arc::new(Lambertian::new(Color::splat(0.5))) as Arc<dyn Material>),
+            },
+        };
+        let Some(material) = material else {
+            return Ok(());
+        };
+
+        // Only PLY files carry vertex normals. Their colors are left out, Mitsuba only uses them through textures.
+        let mut normals = vec![];
+        let (vertices, triangles) = match kind {
+            "sphere" => {
+                let center = match element.property("center") {
+                    Some(center) => self.vector(center, 0.0)?.to_point(),
+                    None => Point::ORIGIN,
+                };
+                let radius = self.float(element, "radius", 1.0)?;
+                let transform = Transform::translation(center.to_vec()).then(transform);
+                match (transform.uniform_scale(), group) {
+                    (Some(scale), None) => {
+                        self.spheres
+                            .push(Sphere::new(transform.point(Point::ORIGIN), radius * scale, material))
+                    }
+                    (_, group) => {
+                        let sphere = Arc::new(Sphere::new(Point::ORIGIN, radius, material));
+                        let instance = Box::new(Instance::new(sphere, transform));
+                        match group {
+                            Some(list) => list.push(instance),
+                            None => self.world.push(instance),
+                        }
+                    }
+                }
+                return Ok(());
+            }
+            "obj" | "ply" => {
+                let path = self.file.parent().unwrap_or(Path::new("")).join(
+                    self.value(
+                        element
+                            .property("filename")
+                            .ok_or_else(|| Error::parse(line, format!("{kind} shape needs a filename")))?,
+                        "value",
+                    )?,
+                );
+                self.files.push(path.clone());
+                let error = |err: Error| Error::parse(line, format!("{}: {err}", path.display()));
+                if kind == "ply" {
+                    let model = PlyModel::load(&path).map_err(error)?;
+                    normals = model.normals;
+                    (model.vertices, model.triangles)
+                } else {
+                    let model = ObjModel::load(&path).map_err(error)?;
+                    (model.vertices, model.triangles)
+                }
+            }
+            "rectangle" => (
+                vec![
+                    Point::new(-1.0, -1.0, 0.0),
+                    Point::new(1.0, -1.0, 0.0),
+                    Point::new(1.0, 1.0, 0.0),
+                    Point::new(-1.0, 1.0, 0.0),
+                ],
+                vec![[0, 1, 2], [0, 2, 3]],
+            ),
+            "cube" => {
+                let vertices = (0..8)
+                    .map(|corner| {
+                        let coordinate = |bit: usize| if corner & bit == 0 { -1.0 } else { 1.0 };
+                        Point::new(coordinate(1), coordinate(2), coordinate(4))
+                    })
+                    .collect();
+                // Two triangles per face, counter-clockwise seen from outside
+                let faces = [
+                    [0, 2, 3, 1],
+                    [4, 5, 7, 6],
+                    [0, 1, 5, 4],
+                    [2, 6, 7, 3],
+                    [0, 4, 6, 2],
+                    [1, 3, 7, 5],
+                ];
+                let triangles = faces.iter().flat_map(|&[a, b, c, d]| [[a, b, c], [a, c, d]]).collect();
+                (vertices, triangles)
+            }
+            "disk" => {
+                const SEGMENTS: usize = 64;
+                let mut vertices = vec![Point::ORIGIN];
+                for segment in 0..SEGMENTS {
+                    let angle = segment as Float / SEGMENTS as Float * 2.0 * consts::PI;
+                    vertices.push(Point::new(angle.cos(), angle.sin(), 0.0));
+                }
+                let triangles = (0..SEGMENTS).map(|i| [0, i + 1, (i + 1) % SEGMENTS + 1]).collect();
+                (vertices, triangles)
+            }
+            _ => {
+                self.warnings
+                    .at_line(line, format!("skipping '{kind}' shapes, they are not supported"));
+                return Ok(());
+            }
+        };
+        if triangles.is_empty() {
+            return Ok(());
+        }
+
+        // Mirroring turns the triangles around, and so does `flip_normals`
+        let flip = transform.is_mirroring() != self.bool(element, "flip_normals")?;
+        let triangles = triangles
+            .into_iter()
+            .map(|[a, b, c]| if flip { [a, c, b] } else { [a, b, c] })
+            .collect();
+        let vertices = vertices.into_iter().map(|vertex| transform.point(vertex)).collect();
+        let mut mesh = TriangleMesh::new(vertices, triangles, material, self.accelerator);
+        if !normals.is_empty() {
+            mesh = mesh.with_normals(normals.into_iter().map(|n| transform.normal(n).normalize()).collect());
+        }
+        let mesh = Box::new(mesh);
+        match group {
+            Some(list) => list.push(mesh),
+            None => self.world.push(mesh),
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Scene {
+        if !self.spheres.is_empty() {
+            self.world
+                .push(Box::new(SphereList::new(self.spheres, self.accelerator)));
+        }
+        Scene {
+            camera: self.camera,
+            world: self.world,
+            accelerator: self.accelerator,
+            files: self.files,
+            camera_keyframes: Keyframes::default(),
+            names: Names::default(),
+            light_links: LightLinks::default(),
+            lens: None,
+            aperture_mask: None,
+            lut: None,
+        }
+    }
+}
+
+// Transformation from the space of a camera at `eye` looking at `target` into the world, with Mitsuba's axes: x to the
+// left, y up and z forward.
+fn look_at(eye: Point, target: Point, up: Vec3) -> Option<Transform> {
+    let direction = (target - eye).normalize();
+    let left = Vec3::cross(up.normalize(), direction);
+    if left.length() == 0.0 || !left.length().is_finite() {
+        return None;
+    }
+    let left = left.normalize();
+    let up = Vec3::cross(direction, left);
+    Transform::from_matrix([
+        [left.x, up.x, direction.x, eye.x],
+        [left.y, up.y, direction.y, eye.y],
+        [left.z, up.z, direction.z, eye.z],
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray::Ray;
+
+    const SCENE: &str = r#"
+        <scene version="3.0.0">
+            <integrator type="path"><integer name="max_depth" value="3"/></integrator>
+            <sensor type="perspective">
+                <float name="fov" value="30"/>
+                <string name="fov_axis" value="y"/>
+                <transform name="to_world"><lookat origin="0,0,5" target="0,0,0" up="0,1,0"/></transform>
+                <sampler type="independent"><integer name="sample_count" value="8"/></sampler>
+                <film type="hdrfilm"><integer name="width" value="200"/><integer name="height" value="100"/></film>
+            </sensor>
+            <bsdf type="diffuse" id="blue"><rgb name="reflectance" value="0.2,0.4,0.6"/></bsdf>
+            <shape type="sphere"><float name="radius" value="1"/><ref id="blue"/></shape>
+        </scene>
+    "#;
+
+    fn import(source: &str) -> Scene {
+        let root = parse_xml(source).expect("valid XML");
+        let mut importer = Importer::new(Path::new("test.xml"), Accelerator::Bvh(BvhSettings::default()));
+        for element in &root.children {
+            importer.scene_element(element).expect("valid scene");
+        }
+        importer.finish()
+    }
+
+    #[test]
+    fn imports_the_camera() {
+        let camera = import(SCENE).camera;
+        assert_eq!((camera.image_width, camera.aspect_ratio), (200, 2.0));
+        assert!((camera.vfov - 30.0).abs() < 1e-3);
+        assert!((camera.look_from - Point::new(0.0, 0.0, 5.0)).length() < 1e-4);
+        assert!((camera.look_at - Point::new(0.0, 0.0, 4.0)).length() < 1e-4);
+        assert_eq!((camera.samples_per_pixel, camera.max_depth), (8, 3));
+    }
+
+    #[test]
+    fn imports_shapes_with_their_material() {
+        let scene = import(SCENE);
+        let ray = Ray::new(Point::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let hit = scene
+            .world
+            .hit(ray, 0.0..Float::INFINITY)
+            .expect("sphere at the origin");
+        assert!((hit.t - 4.0).abs() < 1e-3);
+        assert_eq!(hit.material.name(), "lambertian");
+        let scatter = hit.material.scatter(ray, hit).expect("diffuse reflection");
+        assert_eq!(scatter.attenuation, Color::new(0.2, 0.4, 0.6));
+    }
+}
@@ -0,0 +1,160 @@
+use std::{fs, path::Path};
+
+use crate::{
+    error::{Error, Result},
+    float::Float,
+    vec3::Point,
+};
+
+// Geometry of a Wavefront OBJ file. Only vertex positions, faces and material assignments (`usemtl`) are read,
+// polygons are split into triangle fans and kept whole for subdivision. Cages for subdivision can tag creases and
+// corners the way the shapes of OpenSubdiv do, with 0-based vertex indices: `t crease 2/1/0 4 5 2.0` makes the edge
+// from vertex 4 to 5 sharp for 2 levels (more vertices make a chain of edges, with one sharpness for all of them or
+// one for every edge), `t corner 1/1/0 3 1.5` the vertex 3.
+pub struct ObjModel {
+    pub vertices: Vec<Point>,
+    pub triangles: Vec<[usize; 3]>,
+    pub face_materials: Vec<usize>,     // index into `materials` for every triangle
+    pub materials: Vec<Option<String>>, // names used by `usemtl`, None for faces that come before any `usemtl`
+    pub polygons: Vec<Vec<usize>>,
+    pub polygon_materials: Vec<usize>,     // index into `materials` for every polygon
+    pub creases: Vec<([usize; 2], Float)>, // edges with their sharpness
+    pub corners: Vec<(usize, Float)>,      // vertices with their sharpness
+}
+
+impl ObjModel {
+    pub fn load(path: &Path) -> Result<ObjModel> {
+        ObjModel::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(source: &str) -> Result<ObjModel> {
+        let mut model = ObjModel {
+            vertices: vec![],
+            triangles: vec![],
+            face_materials: vec![],
+            materials: vec![None],
+            polygons: vec![],
+            polygon_materials: vec![],
+            creases: vec![],
+            corners: vec![],
+        };
+        let mut material = 0;
+
+        for (index, text) in source.lines().enumerate() {
+            let line = index + 1;
+            let text = text.split('#').next().unwrap_or_default();
+            let mut tokens = text.split_whitespace();
+            match tokens.next() {
+                Some("v") => {
+                    let coordinates = tokens
+                        .take(3)
+                        .map(|t| t.parse::<Float>())
+                        .collect::<std::result::Result<Vec<_>, _>>();
+                    match coordinates.as_deref() {
+                        Ok([x, y, z]) => model.vertices.push(Point::new(*x, *y, *z)),
+                        _ => return Err(Error::parse(line, "invalid vertex")),
+                    }
+                }
+                Some("f") => {
+                    let indices = tokens
+                        .map(|t| vertex_index(t, model.vertices.len()))
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or_else(|| Error::parse(line, "invalid face"))?;
+                    if indices.len() < 3 {
+                        return Err(Error::parse(line, "face needs at least 3 vertices"));
+                    }
+                    for i in 1..indices.len() - 1 {
+                        model.triangles.push([indices[0], indices[i], indices[i + 1]]);
+                        model.face_materials.push(material);
+                    }
+                    model.polygons.push(indices);
+                    model.polygon_materials.push(material);
+                }
+                Some("usemtl") => {
+                    let name = tokens
+                        .next()
+                        .ok_or_else(|| Error::parse(line, "missing material name"))?;
+                    material = match model.materials.iter().position(|m| m.as_deref() == Some(name)) {
+                        Some(index) => index,
+                        None => {
+                            model.materials.push(Some(name.to_string()));
+                            model.materials.len() - 1
+                        }
+                    };
+                }
+                Some("t") => {
+                    let tag = tokens.next().unwrap_or_default();
+                    let counts = tokens
+                        .next()
+                        .and_then(|counts| {
+                            let counts = counts
+                                .split('/')
+                                .map(|count| count.parse::<usize>().ok())
+                                .collect::<Option<Vec<_>>>()?;
+                            (counts.len() == 3).then_some(counts)
+                        })
+                        .ok_or_else(|| Error::parse(line, "invalid tag, expected counts like 2/1/0"))?;
+                    let integers = tokens
+                        .by_ref()
+                        .take(counts[0])
+                        .map(|t| t.parse::<usize>())
+                        .collect::<std::result::Result<Vec<_>, _>>()
+                        .map_err(|_| Error::parse(line, "invalid vertex index in tag"))?;
+                    let floats = tokens
+                        .take(counts[1])
+                        .map(|t| t.parse::<Float>())
+                        .collect::<std::result::Result<Vec<_>, _>>()
+                        .map_err(|_| Error::parse(line, "invalid sharpness in tag"))?;
+                    if integers.len() != counts[0] || floats.len() != counts[1] {
+                        return Err(Error::parse(line, "tag has fewer values than its counts"));
+                    }
+                    // One sharpness for all, or one for every edge or vertex
+                    let sharpness = |index: usize| match floats[..] {
+                        [all] => Ok(all),
+                        _ => floats
+                            .get(index)
+                            .copied()
+                            .ok_or_else(|| Error::parse(line, "tag needs one sharpness or one for each")),
+                    };
+                    match tag {
+                        "crease" if integers.len() >= 2 => {
+                            for (index, pair) in integers.windows(2).enumerate() {
+                                model.creases.push(([pair[0], pair[1]], sharpness(index)?));
+                            }
+                        }
+                        "corner" => {
+                            for (index, &vertex) in integers.iter().enumerate() {
+                                model.corners.push((vertex, sharpness(index)?));
+                            }
+                        }
+                        "crease" => return Err(Error::parse(line, "crease needs at least 2 vertices")),
+                        // Other tags, like the boundary interpolation, are not used
+                        _ => {}
+                    }
+                }
+                // Texture coordinates, normals, groups, smoothing and material libraries are not used
+                _ => {}
+            }
+        }
+        let vertex_count = model.vertices.len();
+        let tagged = model.creases.iter().flat_map(|(edge, _)| *edge);
+        if tagged
+            .chain(model.corners.iter().map(|(vertex, _)| *vertex))
+            .any(|vertex| vertex >= vertex_count)
+        {
+            return Err(Error::parse(0, "tag refers to a missing vertex"));
+        }
+        Ok(model)
+    }
+}
+
+// Resolve a face vertex reference (`v`, `v/vt`, `v//vn` or `v/vt/vn`), negative indices count from the last vertex.
+fn vertex_index(token: &str, vertex_count: usize) -> Option<usize> {
+    let index: i64 = token.split('/').next()?.parse().ok()?;
+    let index = if index < 0 {
+        vertex_count as i64 + index
+    } else {
+        index - 1
+    };
+    (0..vertex_count as i64).contains(&index).then_some(index as usize)
+}
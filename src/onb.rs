@@ -0,0 +1,34 @@
+use crate::vec3::Vec3;
+
+// Orthonormal basis with `w` aligned to the given direction, used to move between world space and the local
+// frame of a surface point.
+#[derive(Copy, Clone)]
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    // Assume that `w` is normalized
+    pub fn new(w: Vec3) -> Onb {
+        let a = if w.x.abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = Vec3::cross(w, a).normalize();
+        let u = Vec3::cross(w, v);
+        Onb { u, v, w }
+    }
+
+    // Convert local coordinates to world space.
+    pub fn local(&self, a: Vec3) -> Vec3 {
+        a.x * self.u + a.y * self.v + a.z * self.w
+    }
+
+    // Convert a world space vector to local coordinates.
+    pub fn world_to_local(&self, a: Vec3) -> Vec3 {
+        Vec3::new(Vec3::dot(a, self.u), Vec3::dot(a, self.v), Vec3::dot(a, self.w))
+    }
+}
@@ -0,0 +1,291 @@
+use std::{
+    io::{self, BufWriter, Read, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use crate::{color::Color, color_space::ColorSpace, float::Float};
+#[cfg(feature = "formats")]
+use crate::{exr, png, tiff};
+
+// Rendered image kept in memory, the linear color of every pixel in scanline order from the top left.
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+    color_space: ColorSpace, // the pixels are converted to when written
+}
+
+impl Framebuffer {
+    // Black image of the given size.
+    pub fn new(width: usize, height: usize) -> Framebuffer {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![Color::BLACK; width * height],
+            color_space: ColorSpace::default(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: Color) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    pub fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+
+    pub fn set_color_space(&mut self, color_space: ColorSpace) {
+        self.color_space = color_space;
+    }
+
+    // Pixels of `count` scanlines starting at `y`, for filling whole rows at once.
+    pub fn rows_mut(&mut self, y: usize, count: usize) -> &mut [Color] {
+        &mut self.pixels[y * self.width..(y + count) * self.width]
+    }
+
+    // Write the image into `buffer` as opaque 8-bit RGBA, row by row, the layout canvases and most image APIs take.
+    // The buffer holds `4 * width * height` bytes, pixels that don't fit are left out.
+    pub fn write_rgba8(&self, buffer: &mut [u8]) {
+        for (rgba, &color) in buffer.chunks_exact_mut(4).zip(&self.pixels) {
+            let [r, g, b] = to_rgb8(color, self.color_space);
+            rgba.copy_from_slice(&[r, g, b, 255]);
+        }
+    }
+}
+
+// 8 bit value of a linear color in `color_space`, encoded and clamped to the displayable range.
+pub fn to_rgb8(color: Color, color_space: ColorSpace) -> [u8; 3] {
+    color_space
+        .encode(color)
+        .map(|component| (255.0 * component.clamp(0.0, 1.0)) as u8)
+}
+
+// File format of the images written, PPM unless another one is asked for. PNG and TIFF images have 16 bits per
+// channel, for grading without the banding of 8 bits, OpenEXR and Radiance HDR images keep the linear light values.
+// Only the 8 and 16 bit formats are converted to the color space of the image. PNG, TIFF and OpenEXR need the
+// `formats` feature.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum ImageFormat {
+    #[default]
+    Ppm,
+    Qoi,
+    #[cfg(feature = "formats")]
+    Png,
+    #[cfg(feature = "formats")]
+    Tiff,
+    #[cfg(feature = "formats")]
+    Exr,
+    Hdr,
+}
+
+impl FromStr for ImageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<ImageFormat, String> {
+        match s {
+            "ppm" => Ok(ImageFormat::Ppm),
+            "qoi" => Ok(ImageFormat::Qoi),
+            #[cfg(feature = "formats")]
+            "png" => Ok(ImageFormat::Png),
+            #[cfg(feature = "formats")]
+            "tiff" | "tif" => Ok(ImageFormat::Tiff),
+            #[cfg(feature = "formats")]
+            "exr" => Ok(ImageFormat::Exr),
+            #[cfg(not(feature = "formats"))]
+            "png" | "tiff" | "tif" | "exr" => Err(format!("{s} images need the 'formats' feature")),
+            "hdr" => Ok(ImageFormat::Hdr),
+            _ => Err(format!(
+                "unknown image format '{s}', expected 'ppm', 'qoi', 'png', 'tiff', 'exr' or 'hdr'"
+            )),
+        }
+    }
+}
+
+impl ImageFormat {
+    // The format of an image file by its extension, one of the names of the formats or `.tif`, and PPM for anything
+    // else.
+    pub fn of_path(path: &Path) -> ImageFormat {
+        let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or("");
+        extension.to_ascii_lowercase().parse().unwrap_or(ImageFormat::Ppm)
+    }
+}
+
+// Write the image in `format`.
+pub fn write_image(image: &Framebuffer, format: ImageFormat, out: &mut dyn Write) -> io::Result<()> {
+    match format {
+        ImageFormat::Ppm => write_ppm(image, out),
+        ImageFormat::Qoi => write_qoi(image, out),
+        #[cfg(feature = "formats")]
+        ImageFormat::Png => png::write(image.width, image.height, &to_samples16(image), out),
+        #[cfg(feature = "formats")]
+        ImageFormat::Tiff => tiff::write(image.width, image.height, &to_samples16(image), out),
+        #[cfg(feature = "formats")]
+        ImageFormat::Exr => {
+            let channel = |value: fn(&Color) -> Float| image.pixels.iter().map(|color| value(color) as f32).collect();
+            let channels = vec![
+                ("R".to_string(), channel(|color| color.r)),
+                ("G".to_string(), channel(|color| color.g)),
+                ("B".to_string(), channel(|color| color.b)),
+            ];
+            exr::write_image(image.width, image.height, channels, vec![], out)
+        }
+        ImageFormat::Hdr => write_hdr(image, out),
+    }
+}
+
+// 16 bit values of the red, green and blue of every pixel, encoded and clamped like `to_rgb8`.
+#[cfg(feature = "formats")]
+fn to_samples16(image: &Framebuffer) -> Vec<u16> {
+    image
+        .pixels
+        .iter()
+        .flat_map(|&color| image.color_space.encode(color))
+        .map(|component| (65535.0 * component.clamp(0.0, 1.0)).round() as u16)
+        .collect()
+}
+
+// Write the image as a plain text PPM. The text is formatted into a buffer that is flushed to `out` in large chunks
+// instead of one write per pixel.
+pub fn write_ppm(image: &Framebuffer, out: &mut dyn Write) -> io::Result<()> {
+    let mut out = BufWriter::with_capacity(1 << 16, out);
+    writeln!(out, "P3\n{} {}\n255", image.width, image.height)?;
+    for &color in &image.pixels {
+        let [r, g, b] = to_rgb8(color, image.color_space);
+        writeln!(out, "{r} {g} {b}")?;
+    }
+    out.flush()
+}
+
+// Write the image as a QOI ("Quite OK Image format", https://qoiformat.org), lossless 8 bit RGB like the PPM but a
+// fraction of its size and quick to encode: every pixel is stored as a run of the previous one, a reference to a
+// recently seen color, a small difference to the previous one or, failing all of that, in full.
+pub fn write_qoi(image: &Framebuffer, out: &mut dyn Write) -> io::Result<()> {
+    let mut out = BufWriter::with_capacity(1 << 16, out);
+    out.write_all(b"qoif")?;
+    out.write_all(&(image.width as u32).to_be_bytes())?;
+    out.write_all(&(image.height as u32).to_be_bytes())?;
+    // 3 channels, gamma encoded unless the color space is linear
+    let linear = image.color_space == ColorSpace::AcesCg;
+    out.write_all(&[3, linear as u8])?;
+
+    let hash = |[r, g, b]: [u8; 3]| (r as usize * 3 + g as usize * 5 + b as usize * 7 + 255 * 11) % 64;
+    // Colors seen last by their hash, the transparent black they start with never matches an opaque pixel
+    let mut seen = [None; 64];
+    let mut previous = [0u8; 3];
+    let mut run = 0u8;
+    for (index, &color) in image.pixels.iter().enumerate() {
+        let pixel = to_rgb8(color, image.color_space);
+        if pixel == previous {
+            run += 1;
+            if run == 62 || index == image.pixels.len() - 1 {
+                out.write_all(&[0xc0 | (run - 1)])?;
+                run = 0;
+            }
+            continue;
+        }
+        if run > 0 {
+            out.write_all(&[0xc0 | (run - 1)])?;
+            run = 0;
+        }
+        let slot = hash(pixel);
+        if seen[slot] == Some(pixel) {
+            out.write_all(&[slot as u8])?;
+        } else {
+            seen[slot] = Some(pixel);
+            let [dr, dg, db] = [0, 1, 2].map(|channel| pixel[channel].wrapping_sub(previous[channel]) as i8);
+            let (dr_dg, db_dg) = (dr.wrapping_sub(dg), db.wrapping_sub(dg));
+            if [dr, dg, db].iter().all(|d| (-2..2).contains(d)) {
+                out.write_all(&[0x40 | ((dr + 2) as u8) << 4 | ((dg + 2) as u8) << 2 | (db + 2) as u8])?;
+            } else if (-32..32).contains(&dg) && (-8..8).contains(&dr_dg) && (-8..8).contains(&db_dg) {
+                out.write_all(&[0x80 | (dg + 32) as u8, ((dr_dg + 8) as u8) << 4 | (db_dg + 8) as u8])?;
+            } else {
+                out.write_all(&[0xfe, pixel[0], pixel[1], pixel[2]])?;
+            }
+        }
+        previous = pixel;
+    }
+    out.write_all(&[0, 0, 0, 0, 0, 0, 0, 1])?;
+    out.flush()
+}
+
+// Write the image as a Radiance HDR (RGBE) file, keeping the light above 1 that a PPM clamps away, for tools that read
+// HDR renders and environment maps. The values are the linear ones of the renderer, in sRGB primaries whatever color
+// space the image is written in otherwise, and the scanlines are stored flat without run-length encoding.
+pub fn write_hdr(image: &Framebuffer, out: &mut dyn Write) -> io::Result<()> {
+    let mut out = BufWriter::with_capacity(1 << 16, out);
+    write!(
+        out,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        image.height, image.width
+    )?;
+    for &color in &image.pixels {
+        out.write_all(&to_rgbe(color))?;
+    }
+    out.flush()
+}
+
+// Shared exponent encoding of a linear color: the mantissas of the channels in 8 bits each and the exponent of the
+// brightest one. Negative values and NaNs are black.
+fn to_rgbe(color: Color) -> [u8; 4] {
+    let color = color.map(|value| if value > 0.0 { value } else { 0.0 });
+    let brightest = color.r.max(color.g).max(color.b);
+    if brightest < 1e-32 || !brightest.is_finite() {
+        return [0; 4];
+    }
+    // The brightest channel is 0.5 to 1 times 2 to the exponent
+    let exponent = (brightest.log2().floor() as i32 + 1).clamp(-128, 127);
+    let scale = 256.0 / Float::powi(2.0, exponent);
+    let [r, g, b] = color.to_array().map(|value| (value * scale).min(255.0) as u8);
+    [r, g, b, (exponent + 128) as u8]
+}
+
+// Read a plain text PPM with 8 bit values, as written by `write_ppm`. Returns the width, the height and the gamma
+// encoded values of the pixels in scanline order.
+pub fn read_ppm(input: &mut dyn Read) -> io::Result<(usize, usize, Vec<[u8; 3]>)> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, format!("invalid PPM: {message}"));
+    let mut text = String::new();
+    input.read_to_string(&mut text)?;
+    // Comments run from '#' to the end of the line
+    let mut tokens = text
+        .lines()
+        .flat_map(|line| line.split('#').next().unwrap_or("").split_whitespace());
+    if tokens.next() != Some("P3") {
+        return Err(invalid("expected the plain text format 'P3'"));
+    }
+    let mut number = |what: &str| -> io::Result<usize> {
+        let token = tokens.next().ok_or_else(|| invalid(&format!("missing {what}")))?;
+        token.parse().map_err(|_| invalid(&format!("invalid {what} '{token}'")))
+    };
+    let (width, height) = (number("width")?, number("height")?);
+    if number("maximum value")? != 255 {
+        return Err(invalid("only a maximum value of 255 is supported"));
+    }
+    let mut pixels = Vec::with_capacity(width * height);
+    for _ in 0..width * height {
+        let mut pixel = [0; 3];
+        for value in &mut pixel {
+            *value = number("pixel value")?
+                .try_into()
+                .map_err(|_| invalid("pixel value above 255"))?;
+        }
+        pixels.push(pixel);
+    }
+    Ok((width, height, pixels))
+}
@@ -0,0 +1,70 @@
+use crate::{
+    aabb::Aabb,
+    float::Float,
+    ray::Ray,
+    simd::{Floats, Mask, Vec3s},
+};
+
+pub const PACKET_SIZE: usize = 8; // rays objects are traced with at once, a packet of floats fills an AVX register
+const MAX_RELATIVE_ERROR: f32 = 4.0 * f32::EPSILON; // of the single precision slab distances
+
+// Up to N rays traced together. Camera rays through neighbouring samples take nearly the same path through the
+// acceleration structure, so every node box is tested against all of them at once with SIMD and fetched only once.
+// Unused lanes are inactive and never reported as hitting anything.
+pub struct RayPacket<const N: usize> {
+    pub rays: [Ray; N],
+    pub active: Mask<N>,
+    pub origin: Vec3s<N>,
+    pub direction: Vec3s<N>,
+    inv_direction: Vec3s<N>,
+    origin_error: f32, // largest rounding error of the origins in single precision
+}
+
+impl<const N: usize> RayPacket<N> {
+    // Packet of the first N rays, padded with inactive lanes when there are fewer.
+    pub fn new(rays: &[Ray]) -> RayPacket<N> {
+        let len = rays.len().min(N);
+        let ray = |i: usize| rays[i.min(len - 1)];
+        let origin = Vec3s::from_fn(|i| ray(i).origin);
+        let direction = Vec3s::from_fn(|i| ray(i).direction);
+        let origin_error = (0..len)
+            .map(|i| rays[i].origin)
+            .map(|o| o.x.abs().max(o.y.abs()).max(o.z.abs()) as f32 * f32::EPSILON)
+            .fold(0.0, f32::max);
+        RayPacket {
+            rays: std::array::from_fn(ray),
+            active: Mask(std::array::from_fn(|i| i < len)),
+            origin,
+            direction,
+            inv_direction: direction.recip(),
+            origin_error,
+        }
+    }
+
+    // Slab test of all active lanes within their `t_end`. Returns the lanes that hit the box and the distances at
+    // which they enter it. The test runs in single precision and is conservative: the box is grown by the rounding
+    // error of the origins and the exit distances by the relative error, so no ray misses a box it touches in double
+    // precision. A few more rays hit a box than strictly need to, which the primitive test sorts out.
+    pub fn hit(&self, aabb: &Aabb, t_start: f32, t_end: Floats<N>) -> (Mask<N>, Floats<N>) {
+        let margin = self.origin_error;
+        let mut t_near = Floats::splat(t_start);
+        let mut t_far = t_end;
+        for axis in 0..3 {
+            let min = Floats::splat((aabb.min[axis] as f32).next_down() - margin);
+            let max = Floats::splat((aabb.max[axis] as f32).next_up() + margin);
+            let origin = self.origin.axis(axis);
+            let inv = self.inv_direction.axis(axis);
+            let t0 = (min - origin) * inv;
+            let t1 = (max - origin) * inv;
+            // A NaN appears when the ray lies in the plane of a slab, the operand order drops it
+            t_near = t0.min(t1).max(t_near);
+            t_far = (t0.max(t1) * Floats::splat(1.0 + MAX_RELATIVE_ERROR)).min(t_far);
+        }
+        (self.active & t_near.le(t_far), t_near)
+    }
+}
+
+// Single precision bound of the end of a ray's range, so the conservative box test never cuts it short.
+pub fn t_end(t: Float) -> f32 {
+    (t as f32).next_up()
+}
@@ -0,0 +1,212 @@
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{
+    color::Color,
+    error::{Error, Result},
+    float::Float,
+    vec3::{Point, Vec3},
+    warn,
+};
+
+// A single line of the text formats used for scenes and manifests:
+//
+//   keyword [name] key=value key=value ...
+//
+// Everything after `#` is a comment. Values containing spaces can be wrapped in double quotes, vectors are written
+// as comma separated components (`0.5,0.7,1.0`).
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Directive {
+    pub line: usize,          // 1-based line number in the source file
+    pub keyword: String,      // what the line describes (`camera`, `sphere`, ...)
+    pub name: Option<String>, // optional identifier other directives can refer to
+    params: Vec<(String, String)>,
+}
+
+// Split the source into directives skipping empty lines and comments.
+pub fn parse_directives(source: &str) -> Result<Vec<Directive>> {
+    let mut directives = vec![];
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let mut tokens = tokenize(text, line)?.into_iter();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+
+        let mut name = None;
+        let mut params = vec![];
+        for (position, token) in tokens.enumerate() {
+            match token.split_once('=') {
+                Some((key, value)) => params.push((key.to_string(), value.to_string())),
+                None if position == 0 => name = Some(token),
+                None => return Err(Error::parse(line, format!("expected key=value, found '{token}'"))),
+            }
+        }
+        directives.push(Directive {
+            line,
+            keyword,
+            name,
+            params,
+        });
+    }
+    Ok(directives)
+}
+
+fn tokenize(text: &str, line: usize) -> Result<Vec<String>> {
+    let mut tokens = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in text.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => break,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if quoted {
+        return Err(Error::parse(line, "unterminated quote"));
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+impl Directive {
+    // Remove the parameter from the directive, so that `finish` can report the ones nobody asked for.
+    pub fn take(&mut self, key: &str) -> Option<String> {
+        let index = self.params.iter().position(|(k, _)| k == key)?;
+        Some(self.params.remove(index).1)
+    }
+
+    // Value of a parameter, leaving it in place.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    // Add the parameter, replacing the value it has.
+    pub fn set(&mut self, key: &str, value: &str) {
+        self.params.retain(|(k, _)| k != key);
+        self.params.push((key.to_string(), value.to_string()));
+    }
+
+    pub fn take_required(&mut self, key: &str) -> Result<String> {
+        self.take(key)
+            .ok_or_else(|| Error::parse(self.line, format!("{} is missing '{key}'", self.keyword)))
+    }
+
+    pub fn take_parsed<T: FromStr>(&mut self, key: &str) -> Result<Option<T>> {
+        match self.take(key) {
+            Some(value) => value
+                .parse()
+                .map(Some)
+                .map_err(|_| Error::parse(self.line, format!("invalid value '{value}' for '{key}'"))),
+            None => Ok(None),
+        }
+    }
+
+    pub fn take_or<T: FromStr>(&mut self, key: &str, default: T) -> Result<T> {
+        Ok(self.take_parsed(key)?.unwrap_or(default))
+    }
+
+    pub fn take_vec3(&mut self, key: &str) -> Result<Option<Vec3>> {
+        let Some(value) = self.take(key) else {
+            return Ok(None);
+        };
+        let components = value
+            .split(',')
+            .map(|c| c.trim().parse::<Float>())
+            .collect::<std::result::Result<Vec<_>, _>>();
+        match components.as_deref() {
+            Ok([x, y, z]) => Ok(Some(Vec3::new(*x, *y, *z))),
+            // A single number is a shorthand for a vector with equal components, handy for gray colors
+            Ok([v]) => Ok(Some(Vec3::new(*v, *v, *v))),
+            _ => Err(Error::parse(self.line, format!("invalid vector '{value}' for '{key}'"))),
+        }
+    }
+
+    pub fn take_vec3_or(&mut self, key: &str, default: Vec3) -> Result<Vec3> {
+        Ok(self.take_vec3(key)?.unwrap_or(default))
+    }
+
+    pub fn take_point_or(&mut self, key: &str, default: Point) -> Result<Point> {
+        Ok(self.take_vec3(key)?.map_or(default, Vec3::to_point))
+    }
+
+    // Colors are written like vectors, as three channels or a single gray value.
+    pub fn take_color(&mut self, key: &str) -> Result<Option<Color>> {
+        Ok(self.take_vec3(key)?.map(|v| Color::new(v.x, v.y, v.z)))
+    }
+
+    pub fn take_color_or(&mut self, key: &str, default: Color) -> Result<Color> {
+        Ok(self.take_color(key)?.unwrap_or(default))
+    }
+
+    // Add the parameters of `base` that the directive doesn't set itself.
+    pub fn inherit(&mut self, base: Directive) {
+        for (key, value) in base.params {
+            if !self.params.iter().any(|(k, _)| *k == key) {
+                self.params.push((key, value));
+            }
+        }
+    }
+
+    // Drop the parameters of a directive that is ignored on purpose, so `finish` doesn't report them.
+    pub fn skip(&mut self) {
+        self.params.clear();
+    }
+
+    // Fail if there are parameters left that were not consumed, they are most likely typos.
+    pub fn finish(self) -> Result<()> {
+        match self.params.first() {
+            Some((key, _)) => Err(Error::parse(
+                self.line,
+                format!("unknown parameter '{key}' for {}", self.keyword),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+// Warnings of the importers of other renderers' scenes about what they skip or approximate. Every message is only
+// given once, however often it comes up.
+#[derive(Debug, Default)]
+pub struct Warnings {
+    pub file: PathBuf, // file being read
+    given: HashSet<String>,
+}
+
+impl Warnings {
+    pub fn new(file: &Path) -> Warnings {
+        Warnings {
+            file: file.to_path_buf(),
+            given: HashSet::new(),
+        }
+    }
+
+    // Warn about something at `line` of the file.
+    pub fn at_line(&mut self, line: usize, message: impl Into<String>) {
+        self.warn(format_args!("line {line}"), message);
+    }
+
+    // Warn about something at `place` in the file, e.g. the path of a USD prim.
+    pub fn warn(&mut self, place: impl Display, message: impl Into<String>) {
+        let message = message.into();
+        if self.given.insert(message.clone()) {
+            warn!("{}: {place}: {message}", self.file.display());
+        }
+    }
+}
@@ -0,0 +1,1203 @@
+use std::{
+    collections::HashMap,
+    fs, mem,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    accel::{AcceleratedList, Accelerator},
+    animation::Keyframes,
+    bvh::BvhSettings,
+    camera::CameraSettings,
+    color::Color,
+    curve::{CurveShape, Curves, Strand},
+    error::{Error, Result},
+    filter::Filter,
+    float::{consts, Float},
+    hair::Hair,
+    hittable::{Hittable, HittableList},
+    id::Names,
+    instance::Instance,
+    light_linking::LightLinks,
+    material::{ComplexIor, Dielectric, DiffuseLight, Lambertian, Material, Metal, Plastic},
+    mesh::TriangleMesh,
+    parser::Warnings,
+    ply::PlyModel,
+    scene::Scene,
+    sphere::{Sphere, SphereList},
+    transform::Transform,
+    util::degrees_to_radians,
+    vec3::{Point, Vec3},
+    warn,
+};
+
+// Importer of scenes in the format of the PBRT renderer (version 4), so the scenes published for it can be rendered
+// and compared, e.g.
+//
+//   LookAt 0 2 -6  0 0 0  0 1 0
+//   Camera "perspective" "float fov" 40
+//   Film "rgb" "integer xresolution" 640 "integer yresolution" 480
+//   WorldBegin
+//   AttributeBegin
+//     AreaLight "diffuse" "rgb L" [8 8 8]
+//     Shape "sphere" "float radius" 0.5
+//   AttributeEnd
+//   Material "conductor" "spectrum eta" "metal-Au-eta" "spectrum k" "metal-Au-k"
+//   Shape "trianglemesh" "point3 P" [-5 -1 -5  5 -1 -5  5 -1 5  -5 -1 5] "integer indices" [0 1 2  0 2 3]
+//
+// PBRT describes a lot more than this tracer renders, so the import is an approximation. It reads the camera, film,
+// sampler, integrator depth and filter, the transformations, triangle meshes, PLY meshes, bilinear patch meshes,
+// spheres, disks and curves, object instances, the common materials, area lights and constant infinite lights.
+// Textures, media, point and directional lights, environment maps and the other shapes are skipped, and so is every
+// parameter the tracer has no use for, each with a warning.
+pub fn load(path: &Path, bvh: BvhSettings) -> Result<Scene> {
+    let mut importer = Importer::new(Accelerator::Bvh(bvh));
+    importer.include(path)?;
+    if !importer.stack.is_empty() || importer.object.is_some() {
+        warn!(
+            "{}: missing AttributeEnd or ObjectEnd at the end of the file",
+            path.display()
+        );
+    }
+    Ok(importer.finish())
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String), // directive keyword, number or boolean
+    Str(String),  // quoted string, without the quotes
+    Open,
+    Close,
+}
+
+// Split a PBRT file into tokens with the line they start on.
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>> {
+    let mut tokens = vec![];
+    let mut chars = source.chars().peekable();
+    let mut line = 1;
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            '#' => while chars.next_if(|&c| c != '\n').is_some() {},
+            '[' => tokens.push((line, Token::Open)),
+            ']' => tokens.push((line, Token::Close)),
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\n') | None => return Err(Error::parse(line, "unterminated string")),
+                        Some(c) => text.push(c),
+                    }
+                }
+                tokens.push((line, Token::Str(text)));
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|&c| !c.is_whitespace() && !matches!(c, '[' | ']' | '"' | '#')) {
+                    word.push(c);
+                }
+                tokens.push((line, Token::Word(word)));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Clone, Debug)]
+enum Value {
+    Number(Float),
+    Bool(bool),
+    Str(String),
+}
+
+// Parameter of a directive, e.g. `"float radius" 0.5` or `"rgb L" [1 1 1]`.
+struct Param {
+    kind: String,
+    name: String,
+    values: Vec<Value>,
+    used: bool,
+}
+
+// Directive with its positional arguments, e.g. the type of a shape or the numbers of a transformation, and its
+// parameters.
+struct Call {
+    keyword: String,
+    line: usize,
+    args: Vec<Value>,
+    params: Vec<Param>,
+}
+
+// Directives start with a capital letter, numbers and booleans don't.
+fn is_keyword(word: &str) -> bool {
+    word.starts_with(|c: char| c.is_ascii_uppercase())
+}
+
+fn parse_calls(tokens: Vec<(usize, Token)>) -> Result<Vec<Call>> {
+    let mut tokens = tokens.into_iter().peekable();
+    let mut calls = vec![];
+    while let Some((line, token)) = tokens.next() {
+        let keyword = match token {
+            Token::Word(word) if is_keyword(&word) => word,
+            Token::Word(word) | Token::Str(word) => {
+                return Err(Error::parse(line, format!("expected a directive, found '{word}'")))
+            }
+            Token::Open | Token::Close => return Err(Error::parse(line, "expected a directive, found a bracket")),
+        };
+        // The names and types in front of the parameters, a string after them is the declaration of a parameter
+        let names = match keyword.as_str() {
+            "Texture" => 3,
+            "MediumInterface" => 2,
+            "Option" => 0,
+            _ => 1,
+        };
+        let mut call = Call {
+            keyword,
+            line,
+            args: vec![],
+            params: vec![],
+        };
+        while let Some((line, token)) =
+            tokens.next_if(|(_, token)| !matches!(token, Token::Word(word) if is_keyword(word)))
+        {
+            match token {
+                Token::Str(text) if call.params.is_empty() && call.args.len() < names => {
+                    call.args.push(Value::Str(text))
+                }
+                Token::Str(declaration) => {
+                    let Some((kind, name)) = declaration.split_once(char::is_whitespace) else {
+                        return Err(Error::parse(line, format!("invalid parameter '{declaration}'")));
+                    };
+                    let values = match tokens.next() {
+                        Some((line, Token::Open)) => parse_list(&mut tokens, line)?,
+                        Some((line, token)) => vec![parse_value(token, line)?],
+                        None => return Err(Error::parse(line, format!("missing value for '{declaration}'"))),
+                    };
+                    call.params.push(Param {
+                        kind: kind.to_string(),
+                        name: name.trim().to_string(),
+                        values,
+                        used: false,
+                    });
+                }
+                Token::Open if call.params.is_empty() => call.args.extend(parse_list(&mut tokens, line)?),
+                Token::Word(_) if call.params.is_empty() => call.args.push(parse_value(token, line)?),
+                _ => return Err(Error::parse(line, format!("unexpected argument of {}", call.keyword))),
+            }
+        }
+        calls.push(call);
+    }
+    Ok(calls)
+}
+
+// Values up to the closing bracket of a list.
+fn parse_list(tokens: &mut impl Iterator<Item = (usize, Token)>, line: usize) -> Result<Vec<Value>> {
+    let mut values = vec![];
+    loop {
+        match tokens.next() {
+            Some((_, Token::Close)) => return Ok(values),
+            Some((line, token)) => values.push(parse_value(token, line)?),
+            None => return Err(Error::parse(line, "missing ']'")),
+        }
+    }
+}
+
+fn parse_value(token: Token, line: usize) -> Result<Value> {
+    match token {
+        Token::Str(text) => Ok(Value::Str(text)),
+        Token::Word(word) if word == "true" || word == "false" => Ok(Value::Bool(word == "true")),
+        Token::Word(word) => word
+            .parse()
+            .map(Value::Number)
+            .map_err(|_| Error::parse(line, format!("invalid number '{word}'"))),
+        Token::Open | Token::Close => Err(Error::parse(line, "unexpected bracket")),
+    }
+}
+
+// Color or named spectrum of a parameter.
+enum Spectrum {
+    Color(Color),
+    Named(String),
+}
+
+impl Call {
+    fn param(&mut self, name: &str) -> Option<&Param> {
+        let param = self.params.iter_mut().find(|param| param.name == name)?;
+        param.used = true;
+        Some(param)
+    }
+
+    fn floats(&mut self, name: &str) -> Result<Option<Vec<Float>>> {
+        let line = self.line;
+        let Some(param) = self.param(name) else {
+            return Ok(None);
+        };
+        let numbers = param
+            .values
+            .iter()
+            .map(|value| match value {
+                Value::Number(number) => Ok(*number),
+                _ => Err(Error::parse(line, format!("'{name}' must be numbers"))),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Some(numbers))
+    }
+
+    fn float(&mut self, name: &str, default: Float) -> Result<Float> {
+        match self.floats(name)?.as_deref() {
+            None => Ok(default),
+            Some(&[number]) => Ok(number),
+            Some(_) => Err(Error::parse(self.line, format!("'{name}' must be a single number"))),
+        }
+    }
+
+    fn string(&mut self, name: &str) -> Result<Option<String>> {
+        let line = self.line;
+        match self.param(name).map(|param| &param.values[..]) {
+            None => Ok(None),
+            Some([Value::Str(text)]) => Ok(Some(text.clone())),
+            Some(_) => Err(Error::parse(line, format!("'{name}' must be a string"))),
+        }
+    }
+
+    fn bool(&mut self, name: &str, default: bool) -> Result<bool> {
+        let line = self.line;
+        match self.param(name).map(|param| &param.values[..]) {
+            None => Ok(default),
+            Some([Value::Bool(value)]) => Ok(*value),
+            Some([Value::Str(text)]) if text == "true" || text == "false" => Ok(text == "true"),
+            Some(_) => Err(Error::parse(line, format!("'{name}' must be true or false"))),
+        }
+    }
+
+    // Spectrum given as RGB, as a constant, as pairs of wavelength and value (averaged into a gray), as the
+    // temperature of a black body or by name. Textures are not supported, the caller falls back to its default.
+    fn spectrum(&mut self, name: &str) -> Result<Option<Spectrum>> {
+        let line = self.line;
+        let Some(param) = self.param(name) else {
+            return Ok(None);
+        };
+        let numbers: Vec<Float> = param
+            .values
+            .iter()
+            .filter_map(|value| match value {
+                Value::Number(number) => Some(*number),
+                _ => None,
+            })
+            .collect();
+        let spectrum = match (param.kind.as_str(), &param.values[..], &numbers[..]) {
+            ("rgb" | "color", _, &[r, g, b]) => Spectrum::Color(Color::new(r, g, b)),
+            ("float" | "spectrum", _, &[value]) => Spectrum::Color(Color::splat(value)),
+            ("spectrum", [Value::Str(name)], _) => Spectrum::Named(name.clone()),
+            ("spectrum", _, pairs) if pairs.len() >= 2 && pairs.len().is_multiple_of(2) => {
+                let values = pairs.iter().skip(1).step_by(2);
+                Spectrum::Color(Color::splat(values.sum::<Float>() / (pairs.len() / 2) as Float))
+            }
+            ("blackbody", _, &[temperature]) => Spectrum::Color(Color::blackbody(temperature)),
+            ("texture", _, _) => return Ok(None),
+            _ => return Err(Error::parse(line, format!("invalid spectrum for '{name}'"))),
+        };
+        Ok(Some(spectrum))
+    }
+
+    // Color of a spectrum parameter, named spectra are only known for the optical constants of metals.
+    fn color(&mut self, name: &str) -> Result<Option<Color>> {
+        match self.spectrum(name)? {
+            Some(Spectrum::Color(color)) => Ok(Some(color)),
+            Some(Spectrum::Named(_)) | None => Ok(None),
+        }
+    }
+
+    fn color_or(&mut self, name: &str, default: Color) -> Result<Color> {
+        Ok(self.color(name)?.unwrap_or(default))
+    }
+
+    // Mark the parameters of a directive that is skipped as a whole as used, it gets a warning of its own.
+    fn skip(&mut self) {
+        self.params.iter_mut().for_each(|param| param.used = true);
+    }
+
+    // Positional string argument, e.g. the type of a shape.
+    fn name(&self, index: usize) -> Result<&str> {
+        match self.args.get(index) {
+            Some(Value::Str(name)) => Ok(name),
+            _ => Err(Error::parse(self.line, format!("{} needs a name", self.keyword))),
+        }
+    }
+
+    // Exactly `count` positional numbers.
+    fn numbers(&self, count: usize) -> Result<Vec<Float>> {
+        let numbers: Vec<Float> = self
+            .args
+            .iter()
+            .filter_map(|value| match value {
+                Value::Number(number) => Some(*number),
+                _ => None,
+            })
+            .collect();
+        if numbers.len() != count || self.args.len() != count {
+            return Err(Error::parse(
+                self.line,
+                format!("{} needs {count} numbers", self.keyword),
+            ));
+        }
+        Ok(numbers)
+    }
+}
+
+// Attributes of the shapes that follow, saved by AttributeBegin and restored by AttributeEnd.
+#[derive(Clone)]
+struct Attributes {
+    ctm: Transform, // current transformation, from the space of the shapes to the world
+    reverse_orientation: bool,
+    material: Option<Arc<dyn Material>>, // None for an interface between media, which isn't rendered
+    light: Option<Arc<dyn Material>>,    // emitting material of the shapes of an area light
+}
+
+// Shapes of the object being defined between ObjectBegin and ObjectEnd.
+struct Object {
+    name: String,
+    list: HittableList,
+    spheres: Vec<Sphere>,
+}
+
+struct Importer {
+    accelerator: Accelerator,
+    // files read so far, the scene files and meshes
+    files: Vec<PathBuf>,
+    warnings: Warnings, // of the file being read
+    attributes: Attributes,
+    stack: Vec<Attributes>,
+    transforms: Vec<Transform>, // saved by TransformBegin
+    coordinate_systems: HashMap<String, Transform>,
+    // The scene is rendered in the space of the camera, mirrored to turn the left-handed coordinates of PBRT into the
+    // right-handed ones of the tracer without mirroring the image
+    render_from_world: Transform,
+    camera: CameraSettings,
+    fov: Float, // of the shorter side of the image
+    lens_radius: Float,
+    focal_distance: Float,
+    resolution: [i32; 2],
+    max_depth: i32,
+    materials: HashMap<String, Option<Arc<dyn Material>>>,
+    world: HittableList,
+    spheres: Vec<Sphere>,
+    objects: HashMap<String, Arc<dyn Hittable>>,
+    object: Option<Object>,
+}
+
+impl Importer {
+    fn new(accelerator: Accelerator) -> Importer {
+        Importer {
+            accelerator,
+            files: vec![],
+            warnings: Warnings::default(),
+            attributes: Attributes {
+                ctm: Transform::IDENTITY,
+                reverse_orientation: false,
+                material: Some(Arc::new(Lambertian::new(Color::splat(0.5)))),
+                light: None,
+            },
+            stack: vec![],
+            transforms: vec![],
+            coordinate_systems: HashMap::new(),
+            render_from_world: mirror(),
+            camera: CameraSettings {
+                samples_per_pixel: 16,
+                look_from: Point::ORIGIN,
+                look_at: Point::new(0.0, 0.0, 1.0),
+                vup: Vec3::new(0.0, 1.0, 0.0),
+                background: Some(Color::BLACK),
+                ..CameraSettings::default()
+            },
+            fov: 90.0,
+            lens_radius: 0.0,
+            focal_distance: 1e6,
+            resolution: [1280, 720],
+            max_depth: 5,
+            materials: HashMap::new(),
+            world: vec![],
+            spheres: vec![],
+            objects: HashMap::new(),
+            object: None,
+        }
+    }
+
+    fn include(&mut self, path: &Path) -> Result<()> {
+        self.files.push(path.to_path_buf());
+        self.read(&fs::read_to_string(path)?, path)
+    }
+
+    // Execute the directives of `source`, the contents of the file `path`.
+    fn read(&mut self, source: &str, path: &Path) -> Result<()> {
+        let calls = parse_calls(tokenize(source)?)?;
+        let base = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let file = mem::replace(&mut self.warnings.file, path.to_path_buf());
+        for mut call in calls {
+            self.execute(&mut call, &base)?;
+            for param in call.params.iter().filter(|param| !param.used) {
+                let what = call.args.first().map_or(String::new(), |value| match value {
+                    Value::Str(name) => format!(" '{name}'"),
+                    _ => String::new(),
+                });
+                let message = format!("ignoring the parameter '{}' of {}{what}", param.name, call.keyword);
+                self.warnings.at_line(call.line, message);
+            }
+        }
+        self.warnings.file = file;
+        Ok(())
+    }
+
+    fn execute(&mut self, call: &mut Call, base: &Path) -> Result<()> {
+        let line = call.line;
+        match call.keyword.as_str() {
+            "Include" | "Import" => {
+                let path = base.join(call.name(0)?);
+                self.include(&path)
+                    .map_err(|err| Error::parse(line, format!("{}: {err}", path.display())))?;
+            }
+
+            // Transformations
+            "Identity" => self.attributes.ctm = Transform::IDENTITY,
+            "Translate" => {
+                let [x, y, z] = call.numbers(3)?[..] else {
+                    unreachable!()
+                };
+                self.apply(Transform::translation(Vec3::new(x, y, z)));
+            }
+            "Scale" => {
+                let [x, y, z] = call.numbers(3)?[..] else {
+                    unreachable!()
+                };
+                if x == 0.0 || y == 0.0 || z == 0.0 {
+                    return Err(Error::parse(line, "scale must not be zero"));
+                }
+                self.apply(Transform::scaling(Vec3::new(x, y, z)));
+            }
+            "Rotate" => {
+                let [angle, x, y, z] = call.numbers(4)?[..] else {
+                    unreachable!()
+                };
+                let rotation = rotation(angle, Vec3::new(x, y, z))
+                    .ok_or_else(|| Error::parse(line, "rotation around a zero axis"))?;
+                self.apply(rotation);
+            }
+            "LookAt" => {
+                let numbers = call.numbers(9)?;
+                let point = |i: usize| Point::new(numbers[i], numbers[i + 1], numbers[i + 2]);
+                let camera = look_at(point(0), point(3), point(6).to_vec())
+                    .ok_or_else(|| Error::parse(line, "the up vector is parallel to the viewing direction"))?;
+                self.apply(camera);
+            }
+            "ConcatTransform" => {
+                let matrix = matrix(&call.numbers(16)?).ok_or_else(|| Error::parse(line, "singular matrix"))?;
+                self.apply(matrix);
+            }
+            "Transform" => {
+                self.attributes.ctm =
+                    matrix(&call.numbers(16)?).ok_or_else(|| Error::parse(line, "singular matrix"))?;
+            }
+            "CoordinateSystem" => {
+                let name = call.name(0)?.to_string();
+                self.coordinate_systems.insert(name, self.attributes.ctm);
+            }
+            "CoordSysTransform" => match self.coordinate_systems.get(call.name(0)?) {
+                Some(&ctm) => self.attributes.ctm = ctm,
+                None => {
+                    let message = format!("unknown coordinate system '{}'", call.name(0)?);
+                    self.warnings.at_line(line, message);
+                }
+            },
+            "TransformBegin" => self.transforms.push(self.attributes.ctm),
+            "TransformEnd" => {
+                self.attributes.ctm = self
+                    .transforms
+                    .pop()
+                    .ok_or_else(|| Error::parse(line, "TransformEnd without TransformBegin"))?;
+            }
+            "ReverseOrientation" => self.attributes.reverse_orientation = !self.attributes.reverse_orientation,
+
+            // Rendering options
+            "Camera" => self.camera(call)?,
+            "Film" => {
+                self.resolution = [
+                    call.float("xresolution", 1280.0)? as i32,
+                    call.float("yresolution", 720.0)? as i32,
+                ];
+                if self.resolution.iter().any(|&size| size < 1) {
+                    return Err(Error::parse(line, "the resolution must be positive"));
+                }
+            }
+            "Sampler" => self.camera.samples_per_pixel = call.float("pixelsamples", 16.0)?.max(1.0) as i32,
+            "Integrator" => {
+                self.max_depth = call.float("maxdepth", 5.0)?.max(0.0) as i32;
+                if !matches!(call.name(0)?, "path" | "volpath") {
+                    let message = format!("rendering the '{}' integrator with the path tracer", call.name(0)?);
+                    self.warnings.at_line(line, message);
+                }
+            }
+            "PixelFilter" => {
+                let name = call.name(0)?;
+                self.camera.filter = match name.parse() {
+                    Ok(filter) => filter,
+                    Err(_) => {
+                        let message = format!("using a box filter instead of the '{name}' filter");
+                        self.warnings.at_line(line, message);
+                        Filter::Box
+                    }
+                };
+            }
+            "WorldBegin" => {
+                self.attributes.ctm = Transform::IDENTITY;
+                self.coordinate_systems.insert("world".to_string(), Transform::IDENTITY);
+            }
+            "Option" | "ColorSpace" | "Accelerator" | "WorldEnd" => {}
+
+            // Attributes
+            "AttributeBegin" => self.stack.push(self.attributes.clone()),
+            "AttributeEnd" => {
+                self.attributes = self
+                    .stack
+                    .pop()
+                    .ok_or_else(|| Error::parse(line, "AttributeEnd without AttributeBegin"))?;
+            }
+            "Material" => {
+                let kind = call.name(0)?.to_string();
+                self.attributes.material = self.material(&kind, call)?;
+            }
+            "MakeNamedMaterial" => {
+                let name = call.name(0)?.to_string();
+                let kind = call
+                    .string("type")?
+                    .ok_or_else(|| Error::parse(line, format!("material '{name}' needs a type")))?;
+                let material = self.material(&kind, call)?;
+                self.materials.insert(name, material);
+            }
+            "NamedMaterial" => {
+                let name = call.name(0)?;
+                self.attributes.material = self
+                    .materials
+                    .get(name)
+                    .ok_or_else(|| Error::parse(line, format!("unknown material '{name}'")))?
+                    .clone();
+            }
+            "AreaLight" => {
+                if call.name(0)? != "diffuse" {
+                    return Err(Error::parse(line, format!("unknown area light '{}'", call.name(0)?)));
+                }
+                let emit = call.color_or("L", Color::WHITE)? * call.float("scale", 1.0)?;
+                let light = DiffuseLight::new(emit).with_two_sided(call.bool("twosided", false)?);
+                self.attributes.light = Some(Arc::new(light));
+            }
+            "LightSource" => self.light_source(call)?,
+            "Shape" => self.shape(call, base)?,
+
+            // Instancing
+            "ObjectBegin" => {
+                if self.object.is_some() {
+                    return Err(Error::parse(line, "ObjectBegin inside of another object"));
+                }
+                self.stack.push(self.attributes.clone());
+                self.object = Some(Object {
+                    name: call.name(0)?.to_string(),
+                    list: vec![],
+                    spheres: vec![],
+                });
+            }
+            "ObjectEnd" => {
+                let mut object = self
+                    .object
+                    .take()
+                    .ok_or_else(|| Error::parse(line, "ObjectEnd without ObjectBegin"))?;
+                if !object.spheres.is_empty() {
+                    object
+                        .list
+                        .push(Box::new(SphereList::new(object.spheres, self.accelerator)));
+                }
+                let shapes: Arc<dyn Hittable> = match object.list.len() {
+                    1 => Arc::from(object.list.pop().unwrap()),
+                    _ => Arc::new(AcceleratedList::new(object.list, self.accelerator)),
+                };
+                self.objects.insert(object.name, shapes);
+                self.attributes = self
+                    .stack
+                    .pop()
+                    .ok_or_else(|| Error::parse(line, "ObjectEnd without AttributeBegin"))?;
+            }
+            "ObjectInstance" => {
+                let name = call.name(0)?;
+                let object = self
+                    .objects
+                    .get(name)
+                    .ok_or_else(|| Error::parse(line, format!("unknown object '{name}'")))?
+                    .clone();
+                let transform = self.attributes.ctm.then(self.render_from_world);
+                self.world.push(Box::new(Instance::new(object, transform)));
+            }
+
+            "Texture" | "MakeNamedMedium" | "MediumInterface" | "ActiveTransform" | "TransformTimes" | "Attribute" => {
+                let message = match call.keyword.as_str() {
+                    "Texture" => "textures are not supported",
+                    "ActiveTransform" | "TransformTimes" => "motion blur is not supported",
+                    "Attribute" => "Attribute is not supported",
+                    _ => "participating media are not supported",
+                };
+                self.warnings.at_line(line, message);
+                call.skip();
+            }
+            keyword => return Err(Error::parse(line, format!("unknown directive '{keyword}'"))),
+        }
+        Ok(())
+    }
+
+    // Apply `transform` to the shapes before the current transformation.
+    fn apply(&mut self, transform: Transform) {
+        self.attributes.ctm = transform.then(self.attributes.ctm);
+    }
+
+    fn camera(&mut self, call: &mut Call) -> Result<()> {
+        if call.name(0)? != "perspective" {
+            let message = format!("rendering the '{}' camera as a perspective camera", call.name(0)?);
+            self.warnings.at_line(call.line, message);
+        }
+        self.fov = call.float("fov", 90.0)?;
+        self.lens_radius = call.float("lensradius", 0.0)?;
+        self.focal_distance = call.float("focaldistance", 1e6)?;
+        // The current transformation takes the world into the space of the camera
+        self.render_from_world = self.attributes.ctm.then(mirror());
+        self.coordinate_systems
+            .insert("camera".to_string(), self.attributes.ctm.inverse());
+        Ok(())
+    }
+
+    fn light_source(&mut self, call: &mut Call) -> Result<()> {
+        let kind = call.name(0)?.to_string();
+        match kind.as_str() {
+            "infinite" if call.string("filename")?.is_some() => {
+                self.warnings
+                    .at_line(call.line, "using the sky instead of the environment map");
+                self.camera.background = None;
+            }
+            "infinite" => {
+                let radiance = call.color_or("L", Color::WHITE)? * call.float("scale", 1.0)?;
+                self.camera.background = Some(radiance);
+            }
+            kind => {
+                let message = format!("skipping the '{kind}' light, only area and infinite lights are supported");
+                self.warnings.at_line(call.line, message);
+                call.skip();
+            }
+        }
+        Ok(())
+    }
+
+    // Material of the given PBRT type, None for an interface.
+    fn material(&mut self, kind: &str, call: &mut Call) -> Result<Option<Arc<dyn Material>>> {
+        let roughness = |call: &mut Call| -> Result<Float> {
+            let u = call.float("uroughness", 0.0)?;
+            let v = call.float("vroughness", u)?;
+            call.float("roughness", (u + v) / 2.0)
+        };
+        let material: Arc<dyn Material> = match kind {
+            "diffuse" => Arc::new(Lambertian::new(call.color_or("reflectance", Color::splat(0.5))?)),
+            "coateddiffuse" => Arc::new(Plastic::new(
+                call.color_or("reflectance", Color::splat(0.5))?,
+                call.float("eta", 1.5)?,
+                roughness(call)?,
+            )),
+            "conductor" => {
+                let fuzz = roughness(call)?;
+                match call.color("reflectance")? {
+                    Some(reflectance) => Arc::new(Metal::new(reflectance, fuzz)),
+                    None => Arc::new(Metal::conductor(self.conductor(call)?, fuzz)),
+                }
+            }
+            "dielectric" => {
+                let ir = self.ior(call)?;
+                Arc::new(Dielectric::new(ir).with_roughness(roughness(call)?))
+            }
+            "thindielectric" => {
+                self.warnings
+                    .at_line(call.line, "approximating thin dielectrics by transparent surfaces");
+                call.param("eta");
+                Arc::new(Dielectric::new(1.0))
+            }
+            "hair" => {
+                let beta_n = call.float("beta_n", 0.3)?;
+                let hair = match (call.color("sigma_a")?, call.color("reflectance")?) {
+                    (Some(sigma_a), _) => Hair::new(sigma_a),
+                    (None, Some(reflectance)) => Hair::from_color(reflectance, beta_n),
+                    (None, None) => Hair::melanin(call.float("eumelanin", 1.3)?, call.float("pheomelanin", 0.0)?),
+                };
+                Arc::new(
+                    hair.with_ior(call.float("eta", 1.55)?)
+                        .with_roughness(call.float("beta_m", 0.3)?, beta_n)
+                        .with_scale_angle(call.float("alpha", 2.0)?),
+                )
+            }
+            "interface" => return Ok(None),
+            "mix" => {
+                let first = match call.param("materials").map(|param| &param.values[..]) {
+                    Some([Value::Str(first), ..]) => first.clone(),
+                    _ => return Err(Error::parse(call.line, "mix material needs two materials")),
+                };
+                self.warnings
+                    .at_line(call.line, "using the first material of mix materials");
+                return self
+                    .materials
+                    .get(&first)
+                    .cloned()
+                    .ok_or_else(|| Error::parse(call.line, format!("unknown material '{first}'")));
+            }
+            kind => {
+                let message = format!("approximating '{kind}' materials by diffuse materials");
+                self.warnings.at_line(call.line, message);
+                Arc::new(Lambertian::new(call.color_or("reflectance", Color::splat(0.5))?))
+            }
+        };
+        Ok(Some(material))
+    }
+
+    // Optical constants of a conductor, PBRT's named spectra of the metals the tracer knows or RGB values. Copper by
+    // default, like PBRT.
+    fn conductor(&mut self, call: &mut Call) -> Result<ComplexIor> {
+        let copper = ComplexIor::preset("copper").expect("copper is a preset");
+        match (call.spectrum("eta")?, call.spectrum("k")?) {
+            (Some(Spectrum::Color(eta)), Some(Spectrum::Color(k))) => Ok(ComplexIor::new(eta, k)),
+            (Some(Spectrum::Named(name)), _) => {
+                let metal = name.strip_prefix("metal-").and_then(|name| name.strip_suffix("-eta"));
+                let preset = match metal {
+                    Some("Au") => "gold",
+                    Some("Ag") => "silver",
+                    Some("Cu") => "copper",
+                    Some("Al") => "aluminum",
+                    _ => {
+                        self.warnings
+                            .at_line(call.line, format!("using copper instead of the spectrum '{name}'"));
+                        "copper"
+                    }
+                };
+                Ok(ComplexIor::preset(preset).unwrap_or(copper))
+            }
+            (None, None) => Ok(copper),
+            _ => Err(Error::parse(call.line, "conductor needs both 'eta' and 'k'")),
+        }
+    }
+
+    // Index of refraction of a dielectric, a number or PBRT's named spectra of glasses at the middle of the visible
+    // spectrum.
+    fn ior(&mut self, call: &mut Call) -> Result<Float> {
+        const GLASSES: &[(&str, Float)] = &[
+            ("glass-BK7", 1.5168),
+            ("glass-BAF10", 1.6700),
+            ("glass-FK51A", 1.4866),
+            ("glass-LASF9", 1.8503),
+            ("glass-F5", 1.6034),
+            ("glass-F10", 1.6200),
+            ("glass-F11", 1.6209),
+        ];
+        match call.spectrum("eta")? {
+            Some(Spectrum::Color(color)) => Ok(color.average()),
+            Some(Spectrum::Named(name)) => Ok(match GLASSES.iter().find(|(glass, _)| *glass == name) {
+                Some((_, ior)) => *ior,
+                None => {
+                    self.warnings
+                        .at_line(call.line, format!("using 1.5 instead of the spectrum '{name}'"));
+                    1.5
+                }
+            }),
+            None => Ok(1.5),
+        }
+    }
+
+    fn shape(&mut self, call: &mut Call, base: &Path) -> Result<()> {
+        let line = call.line;
+        // Shapes of area lights only emit, and interfaces between media aren't rendered
+        let Some(material) = self
+            .attributes
+            .light
+            .clone()
+            .or_else(|| self.attributes.material.clone())
+        else {
+            return Ok(());
+        };
+        // The shapes of objects stay in their own space, the instances place them
+        let transform = match self.object {
+            Some(_) => self.attributes.ctm,
+            None => self.attributes.ctm.then(self.render_from_world),
+        };
+        match call.name(0)? {
+            "sphere" => {
+                let radius = call.float("radius", 1.0)?;
+                self.add_sphere(radius, material, transform);
+            }
+            "trianglemesh" | "loopsubdiv" => {
+                if call.name(0)? == "loopsubdiv" {
+                    self.warnings
+                        .at_line(line, "rendering subdivision surfaces without subdividing");
+                    call.param("levels");
+                }
+                let vertices = points(call, "P")?;
+                let indices = match call.floats("indices")? {
+                    Some(indices) => indices,
+                    None if vertices.len() == 3 => vec![0.0, 1.0, 2.0],
+                    None => return Err(Error::parse(line, "triangle mesh needs indices")),
+                };
+                let triangles = polygons::<3>(&indices, vertices.len(), line)?;
+                let normals = match call.floats("N")? {
+                    Some(normals) if normals.len() == vertices.len() * 3 => Some(vectors(&normals)),
+                    Some(_) => return Err(Error::parse(line, "there must be a normal for every vertex")),
+                    None => None,
+                };
+                self.add_mesh(vertices, triangles, normals, material, transform);
+            }
+            "plymesh" => {
+                let path = base.join(
+                    call.string("filename")?
+                        .ok_or_else(|| Error::parse(line, "plymesh needs a filename"))?,
+                );
+                self.files.push(path.clone());
+                let model =
+                    PlyModel::load(&path).map_err(|err| Error::parse(line, format!("{}: {err}", path.display())))?;
+                let normals = (!model.normals.is_empty()).then_some(model.normals);
+                self.add_mesh(model.vertices, model.triangles, normals, material, transform);
+            }
+            "bilinearmesh" => {
+                let vertices = points(call, "P")?;
+                let indices = match call.floats("indices")? {
+                    Some(indices) => indices,
+                    None if vertices.len() == 4 => vec![0.0, 1.0, 2.0, 3.0],
+                    None => return Err(Error::parse(line, "bilinear mesh needs indices")),
+                };
+                // The corners of a patch are in the order 00, 10, 01, 11, which are split into two triangles
+                let triangles = polygons::<4>(&indices, vertices.len(), line)?
+                    .into_iter()
+                    .flat_map(|[p00, p10, p01, p11]| [[p00, p10, p11], [p00, p11, p01]])
+                    .collect();
+                self.add_mesh(vertices, triangles, None, material, transform);
+            }
+            "disk" => {
+                const SEGMENTS: usize = 64;
+                let radius = call.float("radius", 1.0)?;
+                let height = call.float("height", 0.0)?;
+                let mut vertices = vec![Point::new(0.0, 0.0, height)];
+                for segment in 0..SEGMENTS {
+                    let angle = segment as Float / SEGMENTS as Float * 2.0 * consts::PI;
+                    vertices.push(Point::new(radius * angle.cos(), radius * angle.sin(), height));
+                }
+                // Counter-clockwise around the +z normal of PBRT disks
+                let triangles = (0..SEGMENTS).map(|i| [0, i + 1, (i + 1) % SEGMENTS + 1]).collect();
+                self.add_mesh(vertices, triangles, None, material, transform);
+            }
+            "curve" => {
+                let mut points = points(call, "P")?;
+                if call.float("degree", 3.0)? != 3.0 {
+                    return Err(Error::parse(line, "only cubic curves are supported"));
+                }
+                if call.string("basis")?.as_deref() == Some("bspline") {
+                    points = bspline_to_bezier(&points);
+                }
+                if points.len() < 4 || (points.len() - 1) % 3 != 0 {
+                    return Err(Error::parse(line, "Bézier curves need 3n + 1 points"));
+                }
+                let shape = match call.string("type")?.as_deref() {
+                    None | Some("flat") => CurveShape::Flat,
+                    Some("cylinder") => CurveShape::Cylinder,
+                    Some("ribbon") => {
+                        self.warnings
+                            .at_line(line, "rendering ribbon curves as flat curves facing the ray");
+                        call.param("N");
+                        CurveShape::Flat
+                    }
+                    Some(kind) => return Err(Error::parse(line, format!("unknown curve type '{kind}'"))),
+                };
+                let width = call.float("width", 1.0)?;
+                let widths = [call.float("width0", width)?, call.float("width1", width)?];
+                call.param("splitdepth");
+                self.add_curve(Strand { points, widths }, shape, material, transform);
+            }
+            kind => {
+                let message = format!("skipping '{kind}' shapes, they are not supported");
+                self.warnings.at_line(line, message);
+                call.skip();
+            }
+        }
+        Ok(())
+    }
+
+    fn add_sphere(&mut self, radius: Float, material: Arc<dyn Material>, transform: Transform) {
+        let spheres = match &mut self.object {
+            Some(object) => &mut object.spheres,
+            None => &mut self.spheres,
+        };
+        match transform.uniform_scale() {
+            Some(scale) => spheres.push(Sphere::new(transform.point(Point::ORIGIN), radius * scale, material)),
+            None => {
+                // Stretched into an ellipsoid
+                let sphere = Arc::new(Sphere::new(Point::ORIGIN, radius, material));
+                self.push(Box::new(Instance::new(sphere, transform)));
+            }
+        }
+    }
+
+    fn add_curve(&mut self, strand: Strand, shape: CurveShape, material: Arc<dyn Material>, transform: Transform) {
+        match transform.uniform_scale() {
+            Some(scale) => {
+                let strand = Strand {
+                    points: strand.points.iter().map(|&point| transform.point(point)).collect(),
+                    widths: strand.widths.map(|width| width * scale),
+                };
+                self.push(Box::new(Curves::new(vec![strand], shape, material, self.accelerator)));
+            }
+            None => {
+                // Stretched along with the width
+                let curves = Arc::new(Curves::new(vec![strand], shape, material, self.accelerator));
+                self.push(Box::new(Instance::new(curves, transform)));
+            }
+        }
+    }
+
+    // Add a triangle mesh in the space of the current transformation. PBRT meshes face the side their normals point
+    // to, or the side their triangles go around counter-clockwise without normals.
+    fn add_mesh(
+        &mut self,
+        vertices: Vec<Point>,
+        mut triangles: Vec<[usize; 3]>,
+        normals: Option<Vec<Vec3>>,
+        material: Arc<dyn Material>,
+        transform: Transform,
+    ) {
+        if triangles.is_empty() {
+            return;
+        }
+        for triangle in &mut triangles {
+            let [a, b, c] = triangle.map(|index| vertices[index]);
+            let flip = match &normals {
+                Some(normals) => {
+                    let normal = normals[triangle[0]] + normals[triangle[1]] + normals[triangle[2]];
+                    Vec3::dot(Vec3::cross(b - a, c - a), normal) < 0.0
+                }
+                None => self.attributes.reverse_orientation,
+            };
+            // Mirroring turns the triangles around as well
+            if flip != transform.is_mirroring() {
+                triangle.swap(1, 2);
+            }
+        }
+        let vertices = vertices.into_iter().map(|vertex| transform.point(vertex)).collect();
+        let mut mesh = TriangleMesh::new(vertices, triangles, material, self.accelerator);
+        if let Some(normals) = normals {
+            mesh = mesh.with_normals(normals.into_iter().map(|n| transform.normal(n).normalize()).collect());
+        }
+        self.push(Box::new(mesh));
+    }
+
+    fn push(&mut self, shape: Box<dyn Hittable>) {
+        match &mut self.object {
+            Some(object) => object.list.push(shape),
+            None => self.world.push(shape),
+        }
+    }
+
+    fn finish(mut self) -> Scene {
+        let [width, height] = self.resolution;
+        let aspect_ratio = width as Float / height as Float;
+        // PBRT's field of view spans the shorter side of the image
+        let half_fov = degrees_to_radians(self.fov / 2.0).tan();
+        let vfov = if width >= height {
+            self.fov
+        } else {
+            2.0 * (half_fov / aspect_ratio).atan().to_degrees()
+        };
+        // Camera rays are as long as the focus distance, which only matters with a lens
+        let focus_dist = if self.lens_radius > 0.0 {
+            self.focal_distance
+        } else {
+            self.camera.focus_dist
+        };
+        let camera = CameraSettings {
+            aspect_ratio,
+            image_width: width,
+            // PBRT counts the bounces of a path, the tracer its rays, one more to reach a light after the last bounce
+            max_depth: self.max_depth + 1,
+            vfov,
+            defocus_angle: 2.0 * (self.lens_radius / focus_dist).atan().to_degrees(),
+            focus_dist,
+            ..self.camera
+        };
+        if !self.spheres.is_empty() {
+            self.world
+                .push(Box::new(SphereList::new(self.spheres, self.accelerator)));
+        }
+        Scene {
+            camera,
+            world: self.world,
+            accelerator: self.accelerator,
+            files: self.files,
+            camera_keyframes: Keyframes::default(),
+            names: Names::default(),
+            light_links: LightLinks::default(),
+            lens: None,
+            aperture_mask: None,
+            lut: None,
+        }
+    }
+}
+
+// Mirror image along the x axis of the camera, see `Importer::render_from_world`.
+fn mirror() -> Transform {
+    Transform::scaling(Vec3::new(-1.0, 1.0, 1.0))
+}
+
+// Rotation by `degrees` around `axis`.
+pub(crate) fn rotation(degrees: Float, axis: Vec3) -> Option<Transform> {
+    if axis.length() == 0.0 {
+        return None;
+    }
+    let a = axis.normalize();
+    let (sin, cos) = degrees_to_radians(degrees).sin_cos();
+    Transform::from_matrix([
+        [
+            a.x * a.x + (1.0 - a.x * a.x) * cos,
+            a.x * a.y * (1.0 - cos) - a.z * sin,
+            a.x * a.z * (1.0 - cos) + a.y * sin,
+            0.0,
+        ],
+        [
+            a.x * a.y * (1.0 - cos) + a.z * sin,
+            a.y * a.y + (1.0 - a.y * a.y) * cos,
+            a.y * a.z * (1.0 - cos) - a.x * sin,
+            0.0,
+        ],
+        [
+            a.x * a.z * (1.0 - cos) - a.y * sin,
+            a.y * a.z * (1.0 - cos) + a.x * sin,
+            a.z * a.z + (1.0 - a.z * a.z) * cos,
+            0.0,
+        ],
+    ])
+}
+
+// Transformation from the world into the space of a camera at `eye` looking at `target`, with the left-handed axes of
+// PBRT: x to the right, y up and z forward.
+fn look_at(eye: Point, target: Point, up: Vec3) -> Option<Transform> {
+    let direction = (target - eye).normalize();
+    let right = Vec3::cross(up.normalize(), direction);
+    if right.length() == 0.0 || !right.length().is_finite() {
+        return None;
+    }
+    let right = right.normalize();
+    let up = Vec3::cross(direction, right);
+    let world_from_camera = Transform::from_matrix([
+        [right.x, up.x, direction.x, eye.x],
+        [right.y, up.y, direction.y, eye.y],
+        [right.z, up.z, direction.z, eye.z],
+    ])?;
+    Some(world_from_camera.inverse())
+}
+
+// Transformation of a matrix written by PBRT, which lists the columns one after the other.
+fn matrix(numbers: &[Float]) -> Option<Transform> {
+    let column = |row: usize| [numbers[row], numbers[4 + row], numbers[8 + row], numbers[12 + row]];
+    Transform::from_matrix([column(0), column(1), column(2)])
+}
+
+fn points(call: &mut Call, name: &str) -> Result<Vec<Point>> {
+    let numbers = call
+        .floats(name)?
+        .ok_or_else(|| Error::parse(call.line, format!("missing '{name}'")))?;
+    if !numbers.len().is_multiple_of(3) {
+        return Err(Error::parse(call.line, format!("'{name}' must be triples of numbers")));
+    }
+    Ok(numbers.chunks(3).map(|p| Point::new(p[0], p[1], p[2])).collect())
+}
+
+// Control points of the Bézier segments of a uniform cubic B-spline.
+fn bspline_to_bezier(points: &[Point]) -> Vec<Point> {
+    let mut bezier = vec![];
+    for window in points.windows(4) {
+        let [p0, p1, p2, p3] = [0, 1, 2, 3].map(|i| window[i].to_vec());
+        if bezier.is_empty() {
+            bezier.push(((p0 + 4.0 * p1 + p2) / 6.0).to_point());
+        }
+        bezier.push(((2.0 * p1 + p2) / 3.0).to_point());
+        bezier.push(((p1 + 2.0 * p2) / 3.0).to_point());
+        bezier.push(((p1 + 4.0 * p2 + p3) / 6.0).to_point());
+    }
+    bezier
+}
+
+fn vectors(numbers: &[Float]) -> Vec<Vec3> {
+    numbers.chunks(3).map(|v| Vec3::new(v[0], v[1], v[2])).collect()
+}
+
+// Groups of `N` vertex indices.
+fn polygons<const N: usize>(indices: &[Float], vertices: usize, line: usize) -> Result<Vec<[usize; N]>> {
+    if !indices.len().is_multiple_of(N) {
+        return Err(Error::parse(
+            line,
+            format!("the number of indices must be a multiple of {N}"),
+        ));
+    }
+    indices
+        .chunks(N)
+        .map(|polygon| {
+            let mut result = [0; N];
+            for (index, &value) in result.iter_mut().zip(polygon) {
+                if value < 0.0 || value as usize >= vertices || value.fract() != 0.0 {
+                    return Err(Error::parse(line, format!("invalid vertex index {value}")));
+                }
+                *index = value as usize;
+            }
+            Ok(result)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray::Ray;
+
+    const SCENE: &str = r#"
+        LookAt 0 0 5  0 0 0  0 1 0
+        Camera "perspective" "float fov" 30
+        Film "rgb" "integer xresolution" 200 "integer yresolution" 100
+        Sampler "halton" "integer pixelsamples" 8
+        Integrator "path" "integer maxdepth" 3
+        WorldBegin
+        Material "diffuse" "rgb reflectance" [0.2 0.4 0.6]
+        Shape "sphere" "float radius" 1
+    "#;
+
+    fn import(source: &str) -> Scene {
+        let mut importer = Importer::new(Accelerator::Bvh(BvhSettings::default()));
+        importer.read(source, Path::new("test.pbrt")).expect("valid scene");
+        importer.finish()
+    }
+
+    #[test]
+    fn imports_the_camera() {
+        let camera = import(SCENE).camera;
+        assert_eq!((camera.image_width, camera.aspect_ratio), (200, 2.0));
+        // The field of view spans the shorter side, here the height
+        assert_eq!(camera.vfov, 30.0);
+        assert_eq!(camera.samples_per_pixel, 8);
+        assert_eq!(camera.max_depth, 4);
+    }
+
+    #[test]
+    fn imports_shapes_with_their_material() {
+        let scene = import(SCENE);
+        let camera = &scene.camera;
+        let ray = Ray::new(camera.look_from, (camera.look_at - camera.look_from).normalize(), 0.0);
+        // The camera is 5 units from the center of the unit sphere
+        let hit = scene.world.hit(ray, 0.0..Float::INFINITY).expect("sphere in view");
+        assert!((hit.t - 4.0).abs() < 1e-3);
+        assert_eq!(hit.material.name(), "lambertian");
+        let scatter = hit.material.scatter(ray, hit).expect("diffuse reflection");
+        assert_eq!(scatter.attenuation, Color::new(0.2, 0.4, 0.6));
+    }
+}
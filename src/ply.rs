@@ -0,0 +1,365 @@
+use std::{fs, path::Path, str::Lines};
+
+use crate::{
+    color::Color,
+    error::{Error, Result},
+    float::Float,
+    vec3::{Point, Vec3},
+};
+
+// Geometry of a PLY file, the format of the Stanford scans, in ASCII or binary. Vertex positions, normals and colors
+// and the faces are read, polygons are split into triangle fans. Files of points without faces are read as well, and
+// the exports of Gaussian splats give every point its base color and a size. Other elements and properties are skipped.
+pub struct PlyModel {
+    pub vertices: Vec<Point>,
+    pub triangles: Vec<[usize; 3]>,
+    pub normals: Vec<Vec3>, // one per vertex, empty if the file has none
+    pub colors: Vec<Color>, // one per vertex in linear RGB, empty if the file has none
+    // One per vertex of Gaussian splats, the largest standard deviation of the splat, empty for other files
+    pub radii: Vec<Float>,
+}
+
+// Factor of the first spherical harmonic, which Gaussian splats store their view independent color in.
+const SH_C0: f64 = 0.28209479177387814;
+
+#[derive(Copy, Clone, PartialEq)]
+enum Type {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Type {
+    fn parse(name: &str) -> Option<Type> {
+        Some(match name {
+            "char" | "int8" => Type::I8,
+            "uchar" | "uint8" => Type::U8,
+            "short" | "int16" => Type::I16,
+            "ushort" | "uint16" => Type::U16,
+            "int" | "int32" => Type::I32,
+            "uint" | "uint32" => Type::U32,
+            "float" | "float32" => Type::F32,
+            "double" | "float64" => Type::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Type::I8 | Type::U8 => 1,
+            Type::I16 | Type::U16 => 2,
+            Type::I32 | Type::U32 | Type::F32 => 4,
+            Type::F64 => 8,
+        }
+    }
+
+    // Largest value of an integer type, which stands for full intensity in colors.
+    fn max(self) -> Option<f64> {
+        match self {
+            Type::I8 => Some(i8::MAX as f64),
+            Type::U8 => Some(u8::MAX as f64),
+            Type::I16 => Some(i16::MAX as f64),
+            Type::U16 => Some(u16::MAX as f64),
+            Type::I32 => Some(i32::MAX as f64),
+            Type::U32 => Some(u32::MAX as f64),
+            Type::F32 | Type::F64 => None,
+        }
+    }
+}
+
+enum Property {
+    Scalar(String, Type),
+    List(String, Type, Type), // types of the count and of the items
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    // Position of a scalar property among the scalars of a record.
+    fn scalar(&self, names: &[&str]) -> Option<(usize, Type)> {
+        self.properties
+            .iter()
+            .filter_map(|property| match property {
+                Property::Scalar(name, kind) => Some((name, *kind)),
+                Property::List(..) => None,
+            })
+            .enumerate()
+            .find(|(_, (name, _))| names.contains(&name.as_str()))
+            .map(|(index, (_, kind))| (index, kind))
+    }
+}
+
+// Records after the header, as lines of numbers or packed binary values.
+enum Body<'a> {
+    Ascii {
+        lines: Lines<'a>,
+        line: usize,
+        values: std::str::SplitAsciiWhitespace<'a>,
+    },
+    Binary {
+        data: &'a [u8],
+        position: usize,
+        big_endian: bool,
+        line: usize, // of the end of the header, for errors
+    },
+}
+
+impl Body<'_> {
+    fn line(&self) -> usize {
+        match self {
+            Body::Ascii { line, .. } | Body::Binary { line, .. } => *line,
+        }
+    }
+
+    // Start reading the next record, a line of its own in ASCII files.
+    fn next_record(&mut self) -> Result<()> {
+        if let Body::Ascii { lines, line, values } = self {
+            loop {
+                *line += 1;
+                let text = lines
+                    .next()
+                    .ok_or_else(|| Error::parse(*line, "unexpected end of file"))?;
+                if !text.trim().is_empty() {
+                    *values = text.split_ascii_whitespace();
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, kind: Type) -> Result<f64> {
+        match self {
+            Body::Ascii { line, values, .. } => {
+                let value = values.next().ok_or_else(|| Error::parse(*line, "missing value"))?;
+                value
+                    .parse()
+                    .map_err(|_| Error::parse(*line, format!("invalid number '{value}'")))
+            }
+            Body::Binary {
+                data,
+                position,
+                big_endian,
+                line,
+            } => {
+                let bytes = data
+                    .get(*position..*position + kind.size())
+                    .ok_or_else(|| Error::parse(*line, "unexpected end of file"))?;
+                *position += kind.size();
+                macro_rules! decode {
+                    ($Type:ty) => {{
+                        let bytes = bytes
+                            .try_into()
+                            .expect("the slice has the size of the type");
+                        (if *big_endian {
+                            <$Type>::from_be_bytes(bytes)
+                        } else {
+                            <$Type>::from_le_bytes(bytes)
+                        }) as f64
+                    }};
+                }
+                Ok(match kind {
+                    Type::I8 => decode!(i8),
+                    Type::U8 => decode!(u8),
+                    Type::I16 => decode!(i16),
+                    Type::U16 => decode!(u16),
+                    Type::I32 => decode!(i32),
+                    Type::U32 => decode!(u32),
+                    Type::F32 => decode!(f32),
+                    Type::F64 => decode!(f64),
+                })
+            }
+        }
+    }
+}
+
+impl PlyModel {
+    pub fn load(path: &Path) -> Result<PlyModel> {
+        PlyModel::parse(&fs::read(path)?)
+    }
+
+    pub fn parse(data: &[u8]) -> Result<PlyModel> {
+        let mut elements: Vec<Element> = vec![];
+        let mut format = None;
+        let mut position = 0;
+        let mut line = 0;
+        loop {
+            line += 1;
+            let length = data[position..]
+                .iter()
+                .position(|&byte| byte == b'\n')
+                .ok_or_else(|| Error::parse(line, "missing end_header"))?;
+            let text = std::str::from_utf8(&data[position..position + length])
+                .map_err(|_| Error::parse(line, "invalid header"))?;
+            position += length + 1;
+            let mut words = text.split_whitespace();
+            let invalid = || Error::parse(line, format!("invalid header line '{}'", text.trim()));
+            match (line, words.next()) {
+                (1, Some("ply")) => {}
+                (1, _) => return Err(Error::parse(line, "not a PLY file")),
+                (_, Some("format")) => {
+                    format = Some(match words.next() {
+                        Some("ascii") => None,
+                        Some("binary_little_endian") => Some(false),
+                        Some("binary_big_endian") => Some(true),
+                        _ => return Err(invalid()),
+                    })
+                }
+                (_, Some("element")) => {
+                    let name = words.next().ok_or_else(invalid)?.to_string();
+                    let count = words.next().and_then(|count| count.parse().ok()).ok_or_else(invalid)?;
+                    elements.push(Element {
+                        name,
+                        count,
+                        properties: vec![],
+                    });
+                }
+                (_, Some("property")) => {
+                    let element = elements
+                        .last_mut()
+                        .ok_or_else(|| Error::parse(line, "property before the first element"))?;
+                    let words: Vec<_> = words.collect();
+                    let property = match words[..] {
+                        ["list", count, item, name] => Property::List(
+                            name.to_string(),
+                            Type::parse(count).ok_or_else(invalid)?,
+                            Type::parse(item).ok_or_else(invalid)?,
+                        ),
+                        [kind, name] => Property::Scalar(name.to_string(), Type::parse(kind).ok_or_else(invalid)?),
+                        _ => return Err(invalid()),
+                    };
+                    element.properties.push(property);
+                }
+                (_, Some("end_header")) => break,
+                (_, Some("comment" | "obj_info") | None) => {}
+                (_, Some(_)) => return Err(invalid()),
+            }
+        }
+        let format = format.ok_or_else(|| Error::parse(line, "missing format"))?;
+        let data = &data[position..];
+        let mut body = match format {
+            None => {
+                let text = std::str::from_utf8(data).map_err(|_| Error::parse(line, "invalid ASCII data"))?;
+                Body::Ascii {
+                    lines: text.lines(),
+                    line,
+                    values: "".split_ascii_whitespace(),
+                }
+            }
+            Some(big_endian) => Body::Binary {
+                data,
+                position: 0,
+                big_endian,
+                line,
+            },
+        };
+
+        let mut model = PlyModel {
+            vertices: vec![],
+            triangles: vec![],
+            normals: vec![],
+            colors: vec![],
+            radii: vec![],
+        };
+        let mut scalars = vec![];
+        let mut indices = vec![];
+        for element in &elements {
+            let position = [["x"], ["y"], ["z"]].map(|name| element.scalar(&name));
+            let normal = [["nx"], ["ny"], ["nz"]].map(|name| element.scalar(&name));
+            let color = [
+                ["red", "r", "diffuse_red"],
+                ["green", "g", "diffuse_green"],
+                ["blue", "b", "diffuse_blue"],
+            ]
+            .map(|names| element.scalar(&names));
+            let splat_color = [["f_dc_0"], ["f_dc_1"], ["f_dc_2"]].map(|name| element.scalar(&name));
+            let splat_scale = [["scale_0"], ["scale_1"], ["scale_2"]].map(|name| element.scalar(&name));
+            let vertex = element.name == "vertex";
+            let face = element.name == "face";
+            if vertex && position.contains(&None) {
+                return Err(Error::parse(line, "vertices need x, y and z"));
+            }
+
+            for _ in 0..element.count {
+                body.next_record()?;
+                scalars.clear();
+                indices.clear();
+                for property in &element.properties {
+                    match property {
+                        Property::Scalar(_, kind) => scalars.push(body.read(*kind)?),
+                        Property::List(name, count, item) => {
+                            let count = body.read(*count)?;
+                            if count < 0.0 || count.fract() != 0.0 {
+                                return Err(Error::parse(body.line(), "invalid list length"));
+                            }
+                            let keep = face && (name == "vertex_indices" || name == "vertex_index");
+                            for _ in 0..count as usize {
+                                let index = body.read(*item)?;
+                                if keep {
+                                    if index < 0.0 || index.fract() != 0.0 {
+                                        return Err(Error::parse(body.line(), "invalid vertex index"));
+                                    }
+                                    indices.push(index as usize);
+                                }
+                            }
+                        }
+                    }
+                }
+
+                if vertex {
+                    let [x, y, z] = position.map(|scalar| scalars[scalar.expect("checked above").0] as Float);
+                    model.vertices.push(Point::new(x, y, z));
+                    if let [Some(x), Some(y), Some(z)] = normal {
+                        model.normals.push(Vec3::new(
+                            scalars[x.0] as Float,
+                            scalars[y.0] as Float,
+                            scalars[z.0] as Float,
+                        ));
+                    }
+                    // Colors are 8-bit sRGB values in most files
+                    if let [Some(r), Some(g), Some(b)] = color {
+                        let channel =
+                            |(index, kind): (usize, Type)| (scalars[index] / kind.max().unwrap_or(1.0)) as Float;
+                        model
+                            .colors
+                            .push(Color::new(channel(r), channel(g), channel(b)).srgb_to_linear());
+                    } else if let [Some(r), Some(g), Some(b)] = splat_color {
+                        let channel =
+                            |(index, _): (usize, Type)| (0.5 + SH_C0 * scalars[index]).clamp(0.0, 1.0) as Float;
+                        model
+                            .colors
+                            .push(Color::new(channel(r), channel(g), channel(b)).srgb_to_linear());
+                    }
+                    // The scales along the axes of the splat are logarithms
+                    if let [Some(x), Some(y), Some(z)] = splat_scale {
+                        let scale = scalars[x.0].max(scalars[y.0]).max(scalars[z.0]);
+                        model.radii.push(scale.exp() as Float);
+                    }
+                }
+                if face && indices.len() >= 3 {
+                    for i in 1..indices.len() - 1 {
+                        model.triangles.push([indices[0], indices[i], indices[i + 1]]);
+                    }
+                }
+            }
+        }
+        if model
+            .triangles
+            .iter()
+            .flatten()
+            .any(|&index| index >= model.vertices.len())
+        {
+            return Err(Error::parse(line, "face refers to a missing vertex"));
+        }
+        Ok(model)
+    }
+}
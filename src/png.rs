@@ -0,0 +1,78 @@
+use std::io::{self, Write};
+
+// PNG files of 16 bit RGB pixels as `output::write_image` writes them: a single IDAT chunk of zlib data in stored
+// (uncompressed) deflate blocks, every scanline without a filter. Larger than a compressed PNG, but every reader
+// takes it and it keeps the full precision of 16 bits.
+
+// Largest block of stored deflate data.
+const BLOCK: usize = 65535;
+
+// Write an image of `width` by `height` pixels, `samples` holding the red, green and blue values of every pixel in
+// scanline order.
+pub(crate) fn write(width: usize, height: usize, samples: &[u16], out: &mut dyn Write) -> io::Result<()> {
+    out.write_all(b"\x89PNG\r\n\x1a\n")?;
+    let mut header = vec![];
+    header.extend((width as u32).to_be_bytes());
+    header.extend((height as u32).to_be_bytes());
+    // 16 bits, RGB, deflate, no filter method other than the standard one, not interlaced
+    header.extend([16, 2, 0, 0, 0]);
+    chunk(b"IHDR", &header, out)?;
+
+    // The scanlines, each after its filter type 0 (none), with the samples big-endian
+    let mut raw = Vec::with_capacity(height * (1 + 6 * width));
+    for row in samples.chunks(3 * width).take(height) {
+        raw.push(0);
+        raw.extend(row.iter().flat_map(|sample| sample.to_be_bytes()));
+    }
+    // zlib header without a preset dictionary, the deflate blocks and the Adler-32 checksum
+    let mut data = vec![0x78, 0x01];
+    let blocks = raw.chunks(BLOCK).count().max(1);
+    for (index, block) in raw.chunks(BLOCK).enumerate() {
+        data.push((index + 1 == blocks) as u8);
+        data.extend((block.len() as u16).to_le_bytes());
+        data.extend((!(block.len() as u16)).to_le_bytes());
+        data.extend(block);
+    }
+    data.extend(adler32(&raw).to_be_bytes());
+    chunk(b"IDAT", &data, out)?;
+    chunk(b"IEND", &[], out)
+}
+
+// Write a chunk: the length of its data, its type, the data and the CRC of type and data.
+fn chunk(kind: &[u8; 4], data: &[u8], out: &mut dyn Write) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    out.write_all(&crc32(kind.iter().chain(data)).to_be_bytes())
+}
+
+// CRC-32 of PNG chunks (and zip and gzip files), bit by bit.
+fn crc32<'a>(bytes: impl Iterator<Item = &'a u8>) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                0xedb88320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+// Adler-32 checksum of zlib data.
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    // The sums stay below 2^32 over 5552 bytes before they need the modulo
+    for block in bytes.chunks(5552) {
+        for &byte in block {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
@@ -0,0 +1,161 @@
+use std::{ops::Range, str::FromStr, sync::Arc};
+
+use crate::{
+    aabb::Aabb,
+    accel::{Accelerator, SpatialIndex},
+    color::Color,
+    float::Float,
+    hittable::{Hit, Hittable},
+    material::Material,
+    range::Interval,
+    ray::Ray,
+    sphere::{hit_sphere, sphere_bounds, sphere_hit},
+    vec3::{Point, Vec3},
+};
+
+// What every point of a cloud is drawn as.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Splat {
+    Sphere,
+    Disk, // facing the direction of the normal of the point, or the ray if the points have none
+}
+
+impl FromStr for Splat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Splat, String> {
+        match s {
+            "sphere" => Ok(Splat::Sphere),
+            "disk" => Ok(Splat::Disk),
+            _ => Err(format!("unknown splat '{s}', expected 'sphere' or 'disk'")),
+        }
+    }
+}
+
+// Large sets of points like LiDAR scans or exports of Gaussian splats, drawn as small spheres or disks of a single
+// material tinted by the color of every point, with an acceleration structure of their own. The points are stored as
+// arrays like the spheres of a `SphereList`, without a material for each.
+pub struct PointCloud {
+    centers: Vec<Point>,
+    radii: Vec<Float>,  // one per point
+    colors: Vec<Color>, // one per point, empty if the points have none
+    normals: Vec<Vec3>, // unit normals the disks face, zero or empty to turn them to the ray
+    splat: Splat,
+    material: Arc<dyn Material>,
+    index: SpatialIndex, // acceleration structure over the points
+}
+
+impl PointCloud {
+    pub fn new(
+        centers: Vec<Point>,
+        radii: Vec<Float>,
+        splat: Splat,
+        material: Arc<dyn Material>,
+        accelerator: Accelerator,
+    ) -> PointCloud {
+        assert_eq!(radii.len(), centers.len(), "every point needs a radius");
+        let bounds: Vec<Aabb> = centers
+            .iter()
+            .zip(&radii)
+            .map(|(&center, &radius)| sphere_bounds(center, radius))
+            .collect();
+        PointCloud {
+            index: SpatialIndex::new(&bounds, accelerator),
+            centers,
+            radii,
+            colors: vec![],
+            normals: vec![],
+            splat,
+            material,
+        }
+    }
+
+    // Tint the material of every point with its own color.
+    pub fn with_colors(self, colors: Vec<Color>) -> PointCloud {
+        assert_eq!(colors.len(), self.centers.len(), "every point needs a color");
+        PointCloud { colors, ..self }
+    }
+
+    // Turn the disks of the points to face these normals instead of the ray, except where they are zero.
+    pub fn with_normals(self, normals: Vec<Vec3>) -> PointCloud {
+        assert_eq!(normals.len(), self.centers.len(), "every point needs a normal");
+        let normals = normals
+            .into_iter()
+            .map(|normal| match normal.near_zero() {
+                true => Vec3::ZERO,
+                false => normal.normalize(),
+            })
+            .collect();
+        PointCloud { normals, ..self }
+    }
+
+    pub fn len(&self) -> usize {
+        self.centers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.centers.is_empty()
+    }
+
+    // Normal of the disk of point `i`, if it doesn't face the ray.
+    fn disk_normal(&self, i: usize) -> Option<Vec3> {
+        self.normals
+            .get(i)
+            .copied()
+            .filter(|normal| normal.length_squared() > 0.0)
+    }
+
+    // Distance along the ray to the splat of point `i` within `t_range`.
+    fn hit_point(&self, i: usize, ray: Ray, t_range: &Range<Float>) -> Option<Float> {
+        let (center, radius) = (self.centers[i], self.radii[i]);
+        match self.splat {
+            Splat::Sphere => hit_sphere(center, radius, ray, t_range),
+            Splat::Disk => {
+                let normal = self.disk_normal(i).unwrap_or(ray.direction);
+                let denominator = Vec3::dot(normal, ray.direction);
+                if denominator.abs() < 1e-12 {
+                    return None;
+                }
+                let t = Vec3::dot(center - ray.origin, normal) / denominator;
+                let inside = (ray.at(t) - center).length_squared() <= radius * radius;
+                (inside && t_range.surrounds(t)).then_some(t)
+            }
+        }
+    }
+
+    fn point_hit(&self, i: usize, ray: Ray, t: Float) -> Hit<'_> {
+        let hit = match self.splat {
+            Splat::Sphere => sphere_hit(self.centers[i], self.radii[i], self.material.as_ref(), ray, t),
+            Splat::Disk => {
+                let normal = self.disk_normal(i).unwrap_or(-ray.direction.normalize());
+                Hit::new(ray, t, normal, self.material.as_ref())
+            }
+        };
+        match self.colors.get(i) {
+            Some(&color) => hit.with_color(color),
+            None => hit,
+        }
+    }
+}
+
+impl Hittable for PointCloud {
+    fn hit(&self, ray: Ray, t_range: Range<Float>) -> Option<Hit<'_>> {
+        let mut closest: Option<(usize, Float)> = None;
+        self.index.traverse(ray, t_range, |i, t_range| {
+            let t = self.hit_point(i, ray, &t_range)?;
+            closest = Some((i, t));
+            Some(t)
+        });
+        let (i, t) = closest?;
+        Some(self.point_hit(i, ray, t))
+    }
+
+    fn hit_any(&self, ray: Ray, t_range: Range<Float>) -> bool {
+        self.index
+            .traverse_any(ray, t_range, |i, t_range| self.hit_point(i, ray, &t_range).is_some())
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.index.bounds()
+    }
+}
@@ -0,0 +1,405 @@
+use crate::{
+    color::Color,
+    float::{consts::PI, Float},
+    output::Framebuffer,
+    rng::Rng,
+};
+
+// Glow around the bright parts of the image, bright emitters and highlights, like the light a real lens and the eye
+// scatter around them. The light above the `threshold` is blurred over a `radius` in image heights and `strength`
+// times of it is added back onto the image, 0 leaves it as is.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bloom {
+    pub strength: Float,
+    pub threshold: Float,
+    pub radius: Float,
+}
+
+impl Default for Bloom {
+    fn default() -> Bloom {
+        Bloom {
+            strength: 0.0,
+            threshold: 1.0,
+            radius: 0.02,
+        }
+    }
+}
+
+// Add the glow of `bloom` to every view of `size` pixels the image is made of.
+pub fn bloom(image: &mut Framebuffer, size: [usize; 2], bloom: Bloom) {
+    let [width, height] = size;
+    let bright: Vec<_> = image
+        .pixels()
+        .iter()
+        .map(|color| {
+            let luminance = color.luminance();
+            if luminance > bloom.threshold {
+                (luminance - bloom.threshold) / luminance * *color
+            } else {
+                Color::BLACK
+            }
+        })
+        .collect();
+    // A separable Gaussian blur, cut off at 3 standard deviations and within the view
+    let sigma = (bloom.radius * height as Float).max(0.5);
+    let reach = (3.0 * sigma).ceil() as isize;
+    let weights: Vec<_> = (-reach..=reach)
+        .map(|offset| (-(offset * offset) as Float / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let total: Float = weights.iter().sum();
+    let image_width = image.width();
+    let blur = |source: &[Color], horizontal: bool| {
+        let mut blurred = vec![Color::BLACK; source.len()];
+        for (index, color) in blurred.iter_mut().enumerate() {
+            let [x, y] = [index % image_width, index / image_width];
+            let (along, extent, stride) = if horizontal {
+                (x % width, width, 1)
+            } else {
+                (y % height, height, image_width)
+            };
+            for (weight, offset) in weights.iter().zip(-reach..=reach) {
+                if (0..extent as isize).contains(&(along as isize + offset)) {
+                    *color += *weight / total * source[(index as isize + offset * stride as isize) as usize];
+                }
+            }
+        }
+        blurred
+    };
+    let glow = blur(&blur(&bright, true), false);
+    for (index, glow) in glow.iter().enumerate() {
+        let [x, y] = [index % image_width, index / image_width];
+        image.set(x, y, image.get(x, y) + bloom.strength * *glow);
+    }
+}
+
+// Lateral chromatic aberration of a cheap lens, which bends red less than blue: the red image is magnified by
+// `1 + amount` around the center of every view of `size` pixels the image is made of and the blue one shrunk by
+// `1 - amount`, which fringes edges towards the corners with red on one side and blue on the other.
+pub fn chromatic_aberration(image: &mut Framebuffer, size: [usize; 2], amount: Float) {
+    let [width, height] = size;
+    let source: Vec<_> = image.pixels().iter().map(|color| color.to_array()).collect();
+    let image_width = image.width();
+    for y in 0..image.height() {
+        for x in 0..image_width {
+            let [left, top] = [x - x % width, y - y % height];
+            // Value of a channel between the pixel centers of the view, clamped to its edges
+            let sample = |channel: usize, [u, v]: [Float; 2]| {
+                let (u, v) = (u.clamp(0.0, (width - 1) as Float), v.clamp(0.0, (height - 1) as Float));
+                let [x0, y0] = [u as usize, v as usize];
+                let [x1, y1] = [(x0 + 1).min(width - 1), (y0 + 1).min(height - 1)];
+                let (s, t) = (u - x0 as Float, v - y0 as Float);
+                let at = |x: usize, y: usize| source[(top + y) * image_width + left + x][channel];
+                (1.0 - t) * ((1.0 - s) * at(x0, y0) + s * at(x1, y0)) + t * ((1.0 - s) * at(x0, y1) + s * at(x1, y1))
+            };
+            let center = [width as Float / 2.0, height as Float / 2.0];
+            let point = [(x % width) as Float + 0.5, (y % height) as Float + 0.5];
+            let scaled = |scale: Float| [0, 1].map(|axis| center[axis] + (point[axis] - center[axis]) / scale - 0.5);
+            let color = Color::new(
+                sample(0, scaled(1.0 + amount)),
+                sample(1, scaled(1.0)),
+                sample(2, scaled(1.0 - amount)),
+            );
+            image.set(x, y, color);
+        }
+    }
+}
+
+// White balance of a camera set to the light of a scene, so the light of that color comes out white: the `temperature`
+// in Kelvin of a black body of its color, 6500 for daylight leaves the image as it is and e.g. 3200 neutralizes
+// tungsten lamps, and the `tint` of green the light has on top of that (negative for magenta), from -1 to 1.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WhiteBalance {
+    pub temperature: Float,
+    pub tint: Float,
+}
+
+impl Default for WhiteBalance {
+    fn default() -> WhiteBalance {
+        WhiteBalance {
+            temperature: DAYLIGHT,
+            tint: 0.0,
+        }
+    }
+}
+
+// Color temperature of the light that is white without white balance.
+const DAYLIGHT: Float = 6500.0;
+
+impl WhiteBalance {
+    // Whether the balance leaves the image as it is.
+    pub fn is_neutral(&self) -> bool {
+        self.temperature == DAYLIGHT && self.tint == 0.0
+    }
+
+    // Scale of each channel that turns the light white, keeping its brightness.
+    fn gains(&self) -> Color {
+        let light = Color::blackbody(self.temperature) / Color::blackbody(DAYLIGHT);
+        let light = Color::new(light.r, light.g * (1.0 + self.tint), light.b);
+        let gains = Color::new(1.0 / light.r, 1.0 / light.g, 1.0 / light.b);
+        gains / gains.luminance()
+    }
+}
+
+// Balance the colors of the image for the light of `balance`.
+pub fn white_balance(image: &mut Framebuffer, balance: WhiteBalance) {
+    let gains = balance.gains();
+    let height = image.height();
+    for color in image.rows_mut(0, height) {
+        *color *= gains;
+    }
+}
+
+// Natural vignetting of a lens: the light falls off with the fourth power of the cosine of the angle off the axis,
+// which darkens the edges of wide angle views. `amount` goes from none at 0 to the whole falloff at 1.
+pub fn vignetting(cos_angle: Float, amount: Float) -> Float {
+    1.0 - amount * (1.0 - cos_angle.powi(4))
+}
+
+// Flare of the reflections between the glass surfaces of a lens around bright lights: ghosts, soft discs mirrored
+// through the center of the image, a halo ring around the light and streaks through it. The lights are the parts of
+// the image above the `threshold`, the flare gets `strength` times their light. Without ghosts, a halo of radius 0 (in
+// image heights) or streaks, the flare leaves them out.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LensFlare {
+    pub strength: Float,
+    pub threshold: Float,
+    pub ghosts: u32,
+    pub halo: Float,
+    pub streaks: u32,
+}
+
+impl Default for LensFlare {
+    fn default() -> LensFlare {
+        LensFlare {
+            strength: 0.0,
+            threshold: 2.0,
+            ghosts: 4,
+            halo: 0.25,
+            streaks: 2,
+        }
+    }
+}
+
+// Ghosts by where they are on the line from the light through the center (1 on the light, -1 mirrored through the
+// center), their radius in image heights and their tint from the coatings of the lens.
+const GHOSTS: [(Float, Float, [Float; 3]); 8] = [
+    (-0.6, 0.06, [1.0, 0.8, 0.5]),
+    (-1.2, 0.12, [0.5, 0.7, 1.0]),
+    (0.4, 0.03, [0.7, 1.0, 0.6]),
+    (-0.2, 0.09, [1.0, 0.6, 0.9]),
+    (-1.6, 0.05, [0.6, 1.0, 1.0]),
+    (0.7, 0.02, [1.0, 1.0, 0.6]),
+    (-0.9, 0.15, [0.6, 0.6, 1.0]),
+    (0.1, 0.04, [1.0, 0.7, 0.7]),
+];
+
+// Most lights that get a flare, the brightest ones.
+const MAX_FLARE_LIGHTS: usize = 32;
+
+// Add the flares of `flare` around the lights of every view of `size` pixels the image is made of.
+pub fn lens_flare(image: &mut Framebuffer, size: [usize; 2], flare: LensFlare) {
+    let [width, height] = size;
+    for top in (0..image.height()).step_by(height) {
+        for left in (0..image.width()).step_by(width) {
+            let lights = flare_lights(image, [left, top], size, flare.threshold);
+            let mut view = View {
+                image,
+                left,
+                top,
+                width,
+                height,
+            };
+            for (position, light) in lights {
+                view.flare(position, flare.strength * light, flare);
+            }
+        }
+    }
+}
+
+// Lights of the view at `corner` with their positions. The light above `threshold` is gathered over cells of the
+// view, neighboring cells with light belong to the same light.
+fn flare_lights(
+    image: &Framebuffer,
+    corner: [usize; 2],
+    size: [usize; 2],
+    threshold: Float,
+) -> Vec<([Float; 2], Color)> {
+    let [width, height] = size;
+    let cell = (height / 64).max(1);
+    let [columns, rows] = [width.div_ceil(cell), height.div_ceil(cell)];
+    // Light of every cell, its weight and the sum of the weighted positions of its pixels
+    let mut cells = vec![(Color::BLACK, 0.0, [0.0; 2]); columns * rows];
+    for y in 0..height {
+        for x in 0..width {
+            let color = image.get(corner[0] + x, corner[1] + y);
+            let luminance = color.luminance();
+            if luminance > threshold {
+                let excess = luminance - threshold;
+                let (light, weight, position) = &mut cells[y / cell * columns + x / cell];
+                *light += excess / luminance * color;
+                *weight += excess;
+                *position = [
+                    position[0] + excess * (x as Float + 0.5),
+                    position[1] + excess * (y as Float + 0.5),
+                ];
+            }
+        }
+    }
+    let mut lights = vec![];
+    let mut visited = vec![false; cells.len()];
+    for start in 0..cells.len() {
+        if visited[start] || cells[start].1 == 0.0 {
+            continue;
+        }
+        visited[start] = true;
+        let (mut light, mut weight, mut position) = (Color::BLACK, 0.0, [0.0; 2]);
+        let mut stack = vec![start];
+        while let Some(index) = stack.pop() {
+            let (cell_light, cell_weight, cell_position) = cells[index];
+            light += cell_light;
+            weight += cell_weight;
+            position = [position[0] + cell_position[0], position[1] + cell_position[1]];
+            let [column, row] = [index % columns, index / columns];
+            for neighbor_row in row.saturating_sub(1)..(row + 2).min(rows) {
+                for neighbor_column in column.saturating_sub(1)..(column + 2).min(columns) {
+                    let neighbor = neighbor_row * columns + neighbor_column;
+                    if !visited[neighbor] && cells[neighbor].1 > 0.0 {
+                        visited[neighbor] = true;
+                        stack.push(neighbor);
+                    }
+                }
+            }
+        }
+        lights.push((position.map(|coordinate| coordinate / weight), light));
+    }
+    lights.sort_by(|(_, a), (_, b)| b.luminance().total_cmp(&a.luminance()));
+    lights.truncate(MAX_FLARE_LIGHTS);
+    lights
+}
+
+// The part of the image a flare is drawn into.
+struct View<'a> {
+    image: &'a mut Framebuffer,
+    left: usize,
+    top: usize,
+    width: usize,
+    height: usize,
+}
+
+impl View<'_> {
+    // Draw the flare of a light at `position` in pixels, its elements share `light` out over their areas.
+    fn flare(&mut self, [x, y]: [Float; 2], light: Color, flare: LensFlare) {
+        let height = self.height as Float;
+        let center = [self.width as Float / 2.0, height / 2.0];
+        for &(along, radius, tint) in GHOSTS.iter().take(flare.ghosts as usize) {
+            let ghost = [center[0] + along * (x - center[0]), center[1] + along * (y - center[1])];
+            let radius = radius * height;
+            let [r, g, b] = tint;
+            let color = 0.05 / (PI * radius * radius) * Color::new(r, g, b) * light;
+            self.splat(ghost, radius + 1.0, |distance| {
+                (radius - distance + 0.5).clamp(0.0, 1.0) * color
+            });
+        }
+        if flare.halo > 0.0 {
+            let radius = flare.halo * height;
+            let thickness = 0.05 * radius + 1.0;
+            let color = 0.1 / (2.0 * PI * radius * thickness) * light;
+            self.splat([x, y], radius + 3.0 * thickness, |distance| {
+                let offset = (distance - radius) / thickness;
+                (-offset * offset).exp() * color
+            });
+        }
+        // Streaks fade away over a tenth of the image height on either side of the light
+        let fade = 0.1 * height;
+        for streak in 0..flare.streaks {
+            let angle = PI * streak as Float / flare.streaks as Float;
+            let (dy, dx) = angle.sin_cos();
+            let color = 0.1 / (2.0 * fade) / flare.streaks as Float * light;
+            for step in -(5.0 * fade) as i32..=(5.0 * fade) as i32 {
+                let distance = step as Float;
+                self.add(
+                    [x + distance * dx, y + distance * dy],
+                    (-distance.abs() / fade).exp() * color,
+                );
+            }
+        }
+    }
+
+    // Add `color` of the distance from `center` to the pixels within `reach` of it.
+    fn splat(&mut self, center: [Float; 2], reach: Float, color: impl Fn(Float) -> Color) {
+        let [x0, y0] = [center[0] - reach, center[1] - reach].map(|coordinate| coordinate.floor().max(0.0) as usize);
+        let x1 = ((center[0] + reach).ceil().max(0.0) as usize).min(self.width);
+        let y1 = ((center[1] + reach).ceil().max(0.0) as usize).min(self.height);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let distance = Float::hypot(x as Float + 0.5 - center[0], y as Float + 0.5 - center[1]);
+                if distance <= reach {
+                    let (x, y) = (self.left + x, self.top + y);
+                    self.image.set(x, y, self.image.get(x, y) + color(distance));
+                }
+            }
+        }
+    }
+
+    // Add `color` to the pixel at `point`, if it is within the view.
+    fn add(&mut self, [x, y]: [Float; 2], color: Color) {
+        if (0.0..self.width as Float).contains(&x) && (0.0..self.height as Float).contains(&y) {
+            let (x, y) = (self.left + x as usize, self.top + y as usize);
+            self.image.set(x, y, self.image.get(x, y) + color);
+        }
+    }
+}
+
+// Grain of photographic film, noise in the brightness of the image that is strongest in the midtones and fades in
+// the shadows and the highlights. `amount` is how much it changes the gamma corrected brightness at most, about, and
+// `size` the size of the grains in pixels. The grain depends only on the `seed` and the frame, so a render comes out
+// the same every time and the grain of an animation changes from frame to frame like on film.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FilmGrain {
+    pub amount: Float,
+    pub size: Float,
+    pub seed: u64,
+}
+
+impl Default for FilmGrain {
+    fn default() -> FilmGrain {
+        FilmGrain {
+            amount: 0.0,
+            size: 1.0,
+            seed: 0,
+        }
+    }
+}
+
+// Add the grain of `grain` at `frame` to the image.
+pub fn film_grain(image: &mut Framebuffer, grain: FilmGrain, frame: i64) {
+    // Noise of a grain between -1 and 1, bell shaped, the same for the same grain, seed and frame
+    let noise = |x: i64, y: i64| {
+        let seed = (grain.seed ^ (frame as u64).wrapping_mul(0x9e3779b97f4a7c15))
+            .wrapping_add((x as u64).wrapping_mul(0xbf58476d1ce4e5b9))
+            .wrapping_add((y as u64).wrapping_mul(0x94d049bb133111eb));
+        let mut rng = Rng::seed_from_u64(seed);
+        (rng.next_float() + rng.next_float() + rng.next_float()) / 1.5 - 1.0
+    };
+    for y in 0..image.height() {
+        for x in 0..image.width() {
+            // Grains bigger than a pixel are blended between their centers
+            let [u, v] = [x, y].map(|coordinate| (coordinate as Float + 0.5) / grain.size - 0.5);
+            let [x0, y0] = [u.floor(), v.floor()];
+            let (s, t) = (u - x0, v - y0);
+            let [x0, y0] = [x0 as i64, y0 as i64];
+            let value = (1.0 - t) * ((1.0 - s) * noise(x0, y0) + s * noise(x0 + 1, y0))
+                + t * ((1.0 - s) * noise(x0, y0 + 1) + s * noise(x0 + 1, y0 + 1));
+            let color = image.get(x, y);
+            let brightness = color.luminance().clamp(0.0, 1.0).sqrt();
+            if brightness > 0.0 {
+                let grainy = (brightness + grain.amount * value * 4.0 * brightness * (1.0 - brightness)).max(0.0);
+                image.set(x, y, (grainy / brightness).powi(2) * color);
+            }
+        }
+    }
+}
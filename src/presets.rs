@@ -0,0 +1,65 @@
+use crate::float::Float;
+
+// Named defaults for common materials so scenes don't need to know physical constants. Presets are written in the
+// scene file syntax and can be used directly as a material name (`material=glass`) or as a base for a material whose
+// parameters are partially overridden:
+//
+//   material red_plastic preset=plastic albedo=0.8,0.1,0.1
+const MATERIALS: &[(&str, &str)] = &[
+    // Dielectrics
+    ("glass", "type=dielectric ir=glass"),
+    ("frosted_glass", "type=dielectric ir=glass roughness=0.3"),
+    ("water", "type=dielectric ir=water absorption=0.35,0.045,0.01"),
+    ("ice", "type=dielectric ir=ice roughness=0.05"),
+    ("diamond", "type=dielectric ir=diamond"),
+    ("sapphire", "type=dielectric ir=sapphire"),
+    ("soap_bubble", "type=dielectric ir=air film_thickness=400 film_ir=water"),
+    // Metals
+    ("gold", "type=metal conductor=gold"),
+    ("silver", "type=metal conductor=silver"),
+    ("copper", "type=metal conductor=copper"),
+    ("aluminum", "type=metal conductor=aluminum"),
+    ("brushed_aluminum", "type=metal conductor=aluminum fuzz=0.3"),
+    ("mirror", "type=metal albedo=0.95,0.95,0.95"),
+    // Coated and diffuse surfaces
+    ("plastic", "type=plastic albedo=0.8,0.8,0.8 ir=1.5 roughness=0.05"),
+    ("rubber", "type=plastic albedo=0.05,0.05,0.05 ir=1.5 roughness=0.6"),
+    ("skin", "type=plastic albedo=0.8,0.56,0.46 ir=1.4 roughness=0.4"),
+    ("chalk", "type=lambertian albedo=0.9,0.9,0.9"),
+    ("clay", "type=lambertian albedo=0.65,0.4,0.28"),
+    ("concrete", "type=lambertian albedo=0.5,0.5,0.48"),
+];
+
+// Indices of refraction of common transparent media, usable wherever a scene expects one (`ir=water`).
+const IORS: &[(&str, Float)] = &[
+    ("vacuum", 1.0),
+    ("air", 1.000293),
+    ("ice", 1.31),
+    ("water", 1.333),
+    ("ethanol", 1.361),
+    ("acrylic", 1.49),
+    ("glass", 1.5),
+    ("crown_glass", 1.52),
+    ("quartz", 1.544),
+    ("polycarbonate", 1.585),
+    ("flint_glass", 1.62),
+    ("sapphire", 1.77),
+    ("diamond", 2.418),
+];
+
+// Parameters of the material preset with the given name.
+pub fn material(name: &str) -> Option<&'static str> {
+    MATERIALS.iter().find(|(n, _)| *n == name).map(|(_, params)| *params)
+}
+
+pub fn ior(name: &str) -> Option<Float> {
+    IORS.iter().find(|(n, _)| *n == name).map(|(_, ior)| *ior)
+}
+
+pub fn material_names() -> impl Iterator<Item = &'static str> {
+    MATERIALS.iter().map(|(name, _)| *name)
+}
+
+pub fn ior_names() -> impl Iterator<Item = (&'static str, Float)> {
+    IORS.iter().copied()
+}
@@ -0,0 +1,338 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    color::Color,
+    color_space::ColorSpace,
+    info,
+    output::{to_rgb8, Framebuffer},
+    warn,
+};
+
+// Web page that shows the image while it renders, for keeping an eye on a long render on a headless machine from a
+// phone or any other browser. The server answers plain HTTP: the page at / connects back to /socket over WebSocket
+// (RFC 6455), which streams the image as it fills in. A text message `size W H` starts an image, binary messages of
+// a little-endian u32 row, a u32 row count and the 8-bit RGB pixels of the rows update it, and `done` means the
+// finished image is complete. The rows come at most every `UPDATE_INTERVAL`, each with its latest samples, so the
+// passes of a progressive render refine the image in the browser. There is no authentication.
+pub struct PreviewServer {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,    // 8-bit RGB of the image so far
+    versions: Vec<u64>, // version of every row when it last changed, 0 if it hasn't yet
+    version: u64,       // counts the changes
+    image: u64,         // counts the images, the size may change between them
+    finished: bool,     // the finished image is complete
+    clients: usize,     // browsers connected
+}
+
+// How often the browsers get the rows that changed.
+const UPDATE_INTERVAL: Duration = Duration::from_millis(250);
+
+// How long the finished image waits for the browsers to get it, so the process doesn't exit under them.
+const FINISH_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Most rows of a single message.
+const MESSAGE_ROWS: usize = 64;
+
+const PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>rustracer</title>
+<style>
+body { margin: 0; background: #222; color: #ccc; font: 14px sans-serif; text-align: center; }
+canvas { max-width: 100%; max-height: 90vh; image-rendering: pixelated; background: #000; }
+</style>
+</head>
+<body>
+<canvas></canvas>
+<p>Connecting...</p>
+<script>
+const canvas = document.querySelector("canvas");
+const status = document.querySelector("p");
+const context = canvas.getContext("2d");
+const socket = new WebSocket(`ws://${location.host}/socket`);
+socket.binaryType = "arraybuffer";
+socket.onmessage = (event) => {
+  if (typeof event.data === "string") {
+    const [message, width, height] = event.data.split(" ");
+    if (message === "size") {
+      canvas.width = Number(width);
+      canvas.height = Number(height);
+      status.textContent = `Rendering ${width}x${height}`;
+    } else if (message === "done") {
+      status.textContent = "Finished";
+    }
+    return;
+  }
+  const header = new DataView(event.data, 0, 8);
+  const [row, rows] = [header.getUint32(0, true), header.getUint32(4, true)];
+  const rgb = new Uint8Array(event.data, 8);
+  const image = context.createImageData(canvas.width, rows);
+  for (let i = 0; i < canvas.width * rows; i++) {
+    image.data.set(rgb.subarray(3 * i, 3 * i + 3), 4 * i);
+    image.data[4 * i + 3] = 255;
+  }
+  context.putImageData(image, 0, row);
+};
+socket.onclose = () => {
+  if (status.textContent !== "Finished") status.textContent = "Disconnected";
+};
+</script>
+</body>
+</html>
+"#;
+
+impl PreviewServer {
+    // Serve the preview at `address`, e.g. 0.0.0.0:8080, on threads of its own until the process exits.
+    pub fn start(address: &str) -> io::Result<Arc<PreviewServer>> {
+        let listener = TcpListener::bind(address)?;
+        info!("serving the preview at http://{}/", listener.local_addr()?);
+        let server = Arc::new(PreviewServer {
+            state: Mutex::new(State::default()),
+        });
+        let accepting = server.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let server = accepting.clone();
+                thread::spawn(move || {
+                    if let Err(err) = server.answer(stream) {
+                        warn!("preview: {err}");
+                    }
+                });
+            }
+        });
+        Ok(server)
+    }
+
+    // Start a new image, black until its rows come in.
+    pub fn begin(&self, width: usize, height: usize) {
+        let mut state = self.state.lock().unwrap();
+        let State {
+            version,
+            image,
+            clients,
+            ..
+        } = *state;
+        *state = State {
+            width,
+            height,
+            pixels: vec![0; 3 * width * height],
+            versions: vec![0; height],
+            version,
+            image: image + 1,
+            finished: false,
+            clients,
+        };
+    }
+
+    // Replace a row of the image with linear colors, shown in `color_space`.
+    pub fn scanline(&self, y: usize, colors: &[Color], color_space: ColorSpace) {
+        let mut state = self.state.lock().unwrap();
+        let width = state.width;
+        if y >= state.height || colors.len() != width {
+            return;
+        }
+        for (pixel, &color) in state.pixels[3 * y * width..3 * (y + 1) * width]
+            .chunks_mut(3)
+            .zip(colors)
+        {
+            pixel.copy_from_slice(&to_rgb8(color, color_space));
+        }
+        state.version += 1;
+        state.versions[y] = state.version;
+    }
+
+    // Show the finished image, and give the browsers a moment to get it.
+    pub fn finish(&self, image: &Framebuffer) {
+        self.begin(image.width(), image.height());
+        for y in 0..image.height() {
+            let colors: Vec<Color> = (0..image.width()).map(|x| image.get(x, y)).collect();
+            self.scanline(y, &colors, image.color_space());
+        }
+        self.state.lock().unwrap().finished = true;
+        let deadline = Instant::now() + FINISH_TIMEOUT;
+        while self.state.lock().unwrap().clients > 0 && Instant::now() < deadline {
+            thread::sleep(UPDATE_INTERVAL / 5);
+        }
+    }
+
+    // Answer an HTTP request: the page, or the image over WebSocket.
+    fn answer(&self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut request = String::new();
+        reader.read_line(&mut request)?;
+        let mut key = None;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.trim().eq_ignore_ascii_case("sec-websocket-key") {
+                    key = Some(value.trim().to_string());
+                }
+            }
+        }
+        match (request.split_whitespace().nth(1), key) {
+            (Some("/socket"), Some(key)) => {
+                let accept = base64(&sha1(format!("{key}258EAFA5-E914-47DA-95CA-C5AB0DC85B11").as_bytes()));
+                write!(
+                    stream,
+                    "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+                     Sec-WebSocket-Accept: {accept}\r\n\r\n"
+                )?;
+                self.state.lock().unwrap().clients += 1;
+                let streamed = self.stream(&mut stream);
+                self.state.lock().unwrap().clients -= 1;
+                streamed
+            }
+            (Some("/"), _) => write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\n\
+                 Connection: close\r\n\r\n{PAGE}",
+                PAGE.len()
+            ),
+            _ => write!(
+                stream,
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            ),
+        }
+    }
+
+    // Send the rows that changed to a browser until the finished image is complete.
+    fn stream(&self, stream: &mut TcpStream) -> io::Result<()> {
+        let (mut image, mut seen) = (0, 0);
+        loop {
+            let mut messages = vec![];
+            let finished = {
+                let state = self.state.lock().unwrap();
+                if state.image != image {
+                    image = state.image;
+                    seen = 0;
+                    messages.push((TEXT, format!("size {} {}", state.width, state.height).into_bytes()));
+                }
+                let changed: Vec<usize> = (0..state.height).filter(|&y| state.versions[y] > seen).collect();
+                // Runs of consecutive rows go together
+                for run in changed.chunk_by(|a, b| b - a == 1) {
+                    for rows in run.chunks(MESSAGE_ROWS) {
+                        let (first, count) = (rows[0], rows.len());
+                        let mut message = Vec::with_capacity(8 + 3 * state.width * count);
+                        message.extend((first as u32).to_le_bytes());
+                        message.extend((count as u32).to_le_bytes());
+                        message.extend(&state.pixels[3 * first * state.width..3 * (first + count) * state.width]);
+                        messages.push((BINARY, message));
+                    }
+                }
+                seen = state.version;
+                state.finished
+            };
+            for (opcode, payload) in &messages {
+                write_frame(stream, *opcode, payload)?;
+            }
+            if finished {
+                write_frame(stream, TEXT, b"done")?;
+                return write_frame(stream, CLOSE, &[]);
+            }
+            thread::sleep(UPDATE_INTERVAL);
+        }
+    }
+}
+
+// Opcodes of WebSocket frames.
+const TEXT: u8 = 1;
+const BINARY: u8 = 2;
+const CLOSE: u8 = 8;
+
+// Write a whole message as a single unmasked frame, the way a server sends them.
+fn write_frame(out: &mut impl Write, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        length @ 0..=125 => frame.push(length as u8),
+        length @ 126..=0xffff => {
+            frame.push(126);
+            frame.extend((length as u16).to_be_bytes());
+        }
+        length => {
+            frame.push(127);
+            frame.extend((length as u64).to_be_bytes());
+        }
+    }
+    frame.extend(payload);
+    out.write_all(&frame)
+}
+
+// SHA-1 digest (FIPS 180-4), which the WebSocket handshake takes of the key of the browser.
+fn sha1(message: &[u8]) -> [u8; 20] {
+    let mut data = message.to_vec();
+    data.push(0x80);
+    while data.len() % 64 != 56 {
+        data.push(0);
+    }
+    data.extend((message.len() as u64 * 8).to_be_bytes());
+    let mut hash: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+    for block in data.chunks(64) {
+        let mut words = [0; 80];
+        for (word, bytes) in words.iter_mut().zip(block.chunks(4)) {
+            *word = u32::from_be_bytes(bytes.try_into().expect("4 bytes"));
+        }
+        for i in 16..80 {
+            words[i] = (words[i - 3] ^ words[i - 8] ^ words[i - 14] ^ words[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = hash;
+        for (i, word) in words.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a827999),
+                20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let next = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            (e, d, c, b, a) = (d, c, b.rotate_left(30), a, next);
+        }
+        for (value, add) in hash.iter_mut().zip([a, b, c, d, e]) {
+            *value = value.wrapping_add(add);
+        }
+    }
+    let mut digest = [0; 20];
+    for (bytes, value) in digest.chunks_mut(4).zip(hash) {
+        bytes.copy_from_slice(&value.to_be_bytes());
+    }
+    digest
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut text = String::new();
+    for chunk in bytes.chunks(3) {
+        let bits = chunk
+            .iter()
+            .enumerate()
+            .fold(0, |bits, (i, &byte)| bits | (byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => text.push(ALPHABET[(bits >> (18 - 6 * i) & 63) as usize] as char),
+                false => text.push('='),
+            }
+        }
+    }
+    text
+}
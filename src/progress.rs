@@ -0,0 +1,220 @@
+use std::{
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+// Receives updates about how much of the render is done. Updates can come from many threads at once.
+pub trait Progress: Sync {
+    // Called once before the work starts with the number of units (scanlines) to expect.
+    fn start(&self, total: u64);
+    fn advance(&self, delta: u64);
+    // Rays traced since the last call, for the rate of rays per second.
+    fn traced(&self, _rays: u64) {}
+    // Samples per pixel the image will have once the units in progress are done.
+    fn samples_per_pixel(&self, _samples: u64) {}
+    fn finish(&self) {}
+}
+
+// Ignores all updates.
+pub struct NoProgress;
+
+impl Progress for NoProgress {
+    fn start(&self, _: u64) {}
+    fn advance(&self, _: u64) {}
+}
+
+// Counters behind the progress displays, updated atomically so that all threads can report to the same meter.
+pub struct Meter {
+    started: Mutex<Instant>,
+    total: AtomicU64,
+    done: AtomicU64,
+    rays: AtomicU64,
+    samples_per_pixel: AtomicU64,
+}
+
+impl Default for Meter {
+    fn default() -> Meter {
+        Meter {
+            started: Mutex::new(Instant::now()),
+            total: AtomicU64::new(0),
+            done: AtomicU64::new(0),
+            rays: AtomicU64::new(0),
+            samples_per_pixel: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Progress for Meter {
+    fn start(&self, total: u64) {
+        *self.started.lock().unwrap() = Instant::now();
+        self.total.store(total, Ordering::Relaxed);
+        self.done.store(0, Ordering::Relaxed);
+        self.rays.store(0, Ordering::Relaxed);
+    }
+
+    fn advance(&self, delta: u64) {
+        self.done.fetch_add(delta, Ordering::Relaxed);
+    }
+
+    fn traced(&self, rays: u64) {
+        self.rays.fetch_add(rays, Ordering::Relaxed);
+    }
+
+    fn samples_per_pixel(&self, samples: u64) {
+        self.samples_per_pixel.store(samples, Ordering::Relaxed);
+    }
+}
+
+impl Meter {
+    pub fn elapsed(&self) -> Duration {
+        self.started.lock().unwrap().elapsed()
+    }
+
+    pub fn remaining(&self) -> u64 {
+        self.total
+            .load(Ordering::Relaxed)
+            .saturating_sub(self.done.load(Ordering::Relaxed))
+    }
+
+    pub fn percent(&self) -> u64 {
+        let total = self.total.load(Ordering::Relaxed);
+        (self.done.load(Ordering::Relaxed) * 100)
+            .checked_div(total)
+            .unwrap_or(100)
+    }
+
+    // Estimated time left, assuming the remaining units take as long as the ones so far.
+    pub fn eta(&self) -> Option<Duration> {
+        let done = self.done.load(Ordering::Relaxed);
+        (done > 0).then(|| self.elapsed().mul_f64(self.remaining() as f64 / done as f64))
+    }
+
+    pub fn rays_per_second(&self) -> f64 {
+        self.rays.load(Ordering::Relaxed) as f64 / self.elapsed().as_secs_f64().max(1e-9)
+    }
+
+    // Samples per pixel and ray rate, e.g. "64 spp, 12.3 Mrays/s".
+    pub fn rates(&self) -> String {
+        format!(
+            "{} spp, {:.1} Mrays/s",
+            self.samples_per_pixel.load(Ordering::Relaxed),
+            self.rays_per_second() / 1e6
+        )
+    }
+}
+
+// Minutes and seconds, with hours in front for long renders.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds >= 3600 {
+        format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+    } else {
+        format!("{:02}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+// Plain text status line on stderr for builds without the `progress` feature.
+pub struct TextProgress {
+    label: &'static str,
+    meter: Meter,
+}
+
+impl TextProgress {
+    pub fn new(label: &'static str) -> TextProgress {
+        TextProgress {
+            label,
+            meter: Meter::default(),
+        }
+    }
+}
+
+impl Progress for TextProgress {
+    fn start(&self, total: u64) {
+        self.meter.start(total);
+    }
+
+    fn advance(&self, delta: u64) {
+        self.meter.advance(delta);
+        let meter = &self.meter;
+        let elapsed = format_duration(meter.elapsed());
+        if meter.remaining() == 0 {
+            eprintln!("\r{} done in {elapsed}, {}          ", self.label, meter.rates());
+        } else {
+            let eta = meter.eta().map_or("--:--".to_string(), format_duration);
+            eprint!(
+                "\r{} {:>3}% [{elapsed} / ETA {eta}] {} ",
+                self.label,
+                meter.percent(),
+                meter.rates()
+            );
+            let _ = io::stderr().flush();
+        }
+    }
+
+    fn traced(&self, rays: u64) {
+        self.meter.traced(rays);
+    }
+
+    fn samples_per_pixel(&self, samples: u64) {
+        self.meter.samples_per_pixel(samples);
+    }
+
+    fn finish(&self) {
+        // End the status line of a render that stopped early
+        if self.meter.remaining() > 0 {
+            eprintln!();
+        }
+    }
+}
+
+// Terminal progress bar with the percentage, elapsed time and ETA of indicatif, and the samples per pixel and ray rate
+// of a `Meter`.
+#[cfg(feature = "progress")]
+pub struct BarProgress {
+    bar: indicatif::ProgressBar,
+    meter: Meter,
+}
+
+#[cfg(feature = "progress")]
+impl BarProgress {
+    pub fn new(bar: indicatif::ProgressBar, label: &'static str) -> BarProgress {
+        let template = "{prefix} {wide_bar} {percent:>3}% [{elapsed_precise} / ETA {eta_precise}] {msg}";
+        bar.set_style(indicatif::ProgressStyle::with_template(template).unwrap());
+        bar.set_prefix(label);
+        BarProgress {
+            bar,
+            meter: Meter::default(),
+        }
+    }
+}
+
+#[cfg(feature = "progress")]
+impl Progress for BarProgress {
+    fn start(&self, total: u64) {
+        self.meter.start(total);
+        self.bar.reset();
+        self.bar.set_length(total);
+    }
+
+    fn advance(&self, delta: u64) {
+        self.meter.advance(delta);
+        self.bar.set_message(self.meter.rates());
+        self.bar.inc(delta);
+    }
+
+    fn traced(&self, rays: u64) {
+        self.meter.traced(rays);
+    }
+
+    fn samples_per_pixel(&self, samples: u64) {
+        self.meter.samples_per_pixel(samples);
+    }
+
+    fn finish(&self) {
+        self.bar.finish_and_clear();
+    }
+}
@@ -0,0 +1,108 @@
+use std::str::FromStr;
+
+use crate::{
+    float::{
+        consts::{FRAC_PI_2, PI},
+        Float,
+    },
+    vec3::Vec3,
+};
+
+// How the camera lens maps directions onto the image. The perspective projection keeps straight lines straight and
+// is limited to fields of view below 180°. The fisheye projections map the directions around the viewing direction
+// onto a circle that fits the height of the image, its diameter covers the vertical field of view, which can go past
+// 180°. The equidistant fisheye spaces the angles from the center evenly (the angular fisheye of dome masters), the
+// equisolid one gives equal areas of the image to equal solid angles, like most real fisheye lenses. The
+// equirectangular projection sees all around: longitude runs across the image and latitude down it, the viewing
+// direction in the middle, which is the layout of HDR environment maps and 360° photos.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Projection {
+    #[default]
+    Perspective,
+    Equidistant,
+    Equisolid,
+    Equirectangular,
+}
+
+impl FromStr for Projection {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Projection, String> {
+        match s {
+            "perspective" => Ok(Projection::Perspective),
+            "equidistant" | "fisheye" => Ok(Projection::Equidistant),
+            "equisolid" => Ok(Projection::Equisolid),
+            "equirectangular" | "panorama" => Ok(Projection::Equirectangular),
+            _ => Err(format!(
+                "unknown projection '{s}', expected 'perspective', 'equidistant', 'equisolid' or 'equirectangular'"
+            )),
+        }
+    }
+}
+
+impl Projection {
+    pub fn is_fisheye(self) -> bool {
+        matches!(self, Projection::Equidistant | Projection::Equisolid)
+    }
+
+    // Direction the lens sees through the point `x` to the right and `y` down of the center of its viewport, with
+    // a vertical field of view of `fov` radians, as components to the right, down and forward. The viewport of the
+    // perspective lens is the plane of the pixels at a distance of 1. The other lenses bend the rays of a viewport
+    // 90° high, so the top and the bottom of the image are at 1; the equirectangular one of a viewport 90° wide as
+    // well.
+    pub fn direction(self, [x, y]: [Float; 2], fov: Float) -> Vec3 {
+        match self {
+            Projection::Perspective => Vec3::new(x, y, 1.0),
+            Projection::Equidistant | Projection::Equisolid => {
+                let radius = Float::hypot(x, y);
+                if radius == 0.0 {
+                    return Vec3::new(0.0, 0.0, 1.0);
+                }
+                let angle = match self {
+                    Projection::Equidistant => radius * fov / 2.0,
+                    _ => 2.0 * (radius * (fov / 4.0).sin()).min(1.0).asin(),
+                };
+                // Points past the image circle see at most straight back
+                let angle = angle.min(PI);
+                let sideways = angle.sin() / radius;
+                Vec3::new(sideways * x, sideways * y, angle.cos())
+            }
+            Projection::Equirectangular => {
+                let (longitude, latitude) = (x * PI, -y * FRAC_PI_2);
+                Vec3::new(
+                    latitude.cos() * longitude.sin(),
+                    -latitude.sin(),
+                    latitude.cos() * longitude.cos(),
+                )
+            }
+        }
+    }
+
+    // Point of the viewport through which the lens sees `direction` (to the right, down and forward), the inverse of
+    // `direction`. None if the lens doesn't see it.
+    pub fn viewport_point(self, direction: Vec3, fov: Float) -> Option<[Float; 2]> {
+        let direction = direction.normalize();
+        let [x, y, forward] = [direction.x, direction.y, direction.z];
+        match self {
+            Projection::Perspective if forward <= 0.0 => None,
+            Projection::Perspective => Some([x / forward, y / forward]),
+            Projection::Equidistant | Projection::Equisolid => {
+                let sideways = Float::hypot(x, y);
+                if sideways == 0.0 {
+                    return (forward > 0.0).then_some([0.0, 0.0]);
+                }
+                let angle = forward.clamp(-1.0, 1.0).acos();
+                let radius = match self {
+                    Projection::Equidistant => angle / (fov / 2.0),
+                    _ => (angle / 2.0).sin() / (fov / 4.0).sin(),
+                };
+                Some([radius * x / sideways, radius * y / sideways])
+            }
+            Projection::Equirectangular => {
+                let (longitude, latitude) = (x.atan2(forward), (-y).clamp(-1.0, 1.0).asin());
+                Some([longitude / PI, -latitude / FRAC_PI_2])
+            }
+        }
+    }
+}
@@ -1,16 +1,18 @@
 use std::ops::Range;
 
+use crate::float::Float;
+
 pub trait Interval {
-    fn contains(&self, item: f64) -> bool;
-    fn surrounds(&self, item: f64) -> bool;
+    fn contains(&self, item: Float) -> bool;
+    fn surrounds(&self, item: Float) -> bool;
 }
 
-impl Interval for Range<f64> {
-    fn contains(&self, item: f64) -> bool {
+impl Interval for Range<Float> {
+    fn contains(&self, item: Float) -> bool {
         self.start <= item && item <= self.end
     }
 
-    fn surrounds(&self, item: f64) -> bool {
+    fn surrounds(&self, item: Float) -> bool {
         self.start < item && item < self.end
     }
 }
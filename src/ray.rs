@@ -1,17 +1,26 @@
-use crate::vec3::{Point, Vec3};
+use crate::{
+    float::Float,
+    vec3::{Point, Vec3},
+};
 
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Ray {
     pub origin: Point,
     pub direction: Vec3,
+    pub time: Float, // moment the ray is traced at in frames, what moving objects are hit where depends on it
 }
 
 impl Ray {
-    pub fn new(origin: Point, direction: Vec3) -> Ray {
-        Ray { origin, direction }
+    pub fn new(origin: Point, direction: Vec3, time: Float) -> Ray {
+        Ray {
+            origin,
+            direction,
+            time,
+        }
     }
 
-    pub fn at(&self, t: f64) -> Point {
+    pub fn at(&self, t: Float) -> Point {
         self.origin + t * self.direction
     }
 }
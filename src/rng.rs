@@ -0,0 +1,69 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+use crate::float::Float;
+
+// Small and fast pseudo-random number generator (xoshiro256++). Implemented here to keep the tracer free of external
+// dependencies and to make the random sequence depend only on the seed.
+#[derive(Clone)]
+pub struct Rng {
+    state: [u64; 4],
+}
+
+impl Rng {
+    pub fn seed_from_u64(seed: u64) -> Rng {
+        // Expand the seed with SplitMix64 as recommended by the xoshiro authors, this also avoids the all-zero state
+        let mut seed = seed;
+        let mut next = || {
+            seed = seed.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            z ^ (z >> 31)
+        };
+        Rng {
+            state: [next(), next(), next(), next()],
+        }
+    }
+
+    // Generator continuing where the one `state` was taken from left off, e.g. after resuming a render.
+    pub fn from_state(state: [u64; 4]) -> Rng {
+        Rng { state }
+    }
+
+    pub fn state(&self) -> [u64; 4] {
+        self.state
+    }
+
+    // Seed from the randomness the standard library uses for hash maps.
+    pub fn from_entropy() -> Rng {
+        Rng::seed_from_u64(RandomState::new().build_hasher().finish())
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let result = s[0].wrapping_add(s[3]).rotate_left(23).wrapping_add(s[0]);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+
+    // Returns a random value in [0, 1).
+    pub fn next_float(&mut self) -> Float {
+        // Use as many upper bits as the mantissa holds exactly
+        let bits = Float::MANTISSA_DIGITS;
+        (self.next_u64() >> (64 - bits)) as Float * (1.0 / (1u64 << bits) as Float)
+    }
+
+    // Returns a random value in [min, max).
+    pub fn next_float_ranged(&mut self, min: Float, max: Float) -> Float {
+        min + (max - min) * self.next_float()
+    }
+}
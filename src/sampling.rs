@@ -0,0 +1,67 @@
+use crate::{
+    float::{consts::PI, Float},
+    onb::Onb,
+    rng::Rng,
+    vec3::Vec3,
+};
+
+// Random points and directions for Monte Carlo integration. Everything draws from an explicit generator, so a
+// generator seeded the same way makes the same decisions; `util::with_rng` lends the generator of the thread.
+
+// Uniformly distributed in the unit ball.
+pub fn random_in_unit_sphere(rng: &mut Rng) -> Vec3 {
+    loop {
+        let p = Vec3::new(
+            rng.next_float_ranged(-1.0, 1.0),
+            rng.next_float_ranged(-1.0, 1.0),
+            rng.next_float_ranged(-1.0, 1.0),
+        );
+        if p.length_squared() < 1.0 {
+            return p;
+        }
+    }
+}
+
+// Uniformly distributed on the unit sphere.
+pub fn random_unit_vector(rng: &mut Rng) -> Vec3 {
+    random_in_unit_sphere(rng).normalize()
+}
+
+// Uniformly distributed on the half of the unit sphere that `normal` points into.
+pub fn random_in_hemisphere(rng: &mut Rng, normal: Vec3) -> Vec3 {
+    let on_unit_sphere = random_unit_vector(rng);
+    if Vec3::dot(on_unit_sphere, normal) > 0.0 {
+        on_unit_sphere
+    } else {
+        -on_unit_sphere
+    }
+}
+
+// Uniformly distributed in the unit disk in the xy plane.
+pub fn random_in_unit_disk(rng: &mut Rng) -> Vec3 {
+    loop {
+        let p = Vec3::new(rng.next_float_ranged(-1.0, 1.0), rng.next_float_ranged(-1.0, 1.0), 0.0);
+        if p.length_squared() < 1.0 {
+            return p;
+        }
+    }
+}
+
+// Unit direction around +z with density cos(theta) / pi, the distribution of light reflected by a diffuse surface.
+pub fn random_cosine_direction(rng: &mut Rng) -> Vec3 {
+    let r1 = rng.next_float();
+    let r2 = rng.next_float();
+    let phi = 2.0 * PI * r1;
+    let sin_theta = r2.sqrt();
+    Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, (1.0 - r2).sqrt())
+}
+
+// Cosine weighted direction in the hemisphere around the unit vector `normal`.
+pub fn random_cosine_hemisphere(rng: &mut Rng, normal: Vec3) -> Vec3 {
+    Onb::new(normal).local(random_cosine_direction(rng))
+}
+
+// Density of `random_cosine_hemisphere` for a unit `direction`.
+pub fn cosine_hemisphere_pdf(normal: Vec3, direction: Vec3) -> Float {
+    Vec3::dot(normal, direction).max(0.0) / PI
+}
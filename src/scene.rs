@@ -0,0 +1,1882 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display, Formatter},
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+
+use crate::{
+    accel::Accelerator,
+    animation::{Animated, Interpolate, Keyframes, Placement},
+    aperture::{self, ApertureMask},
+    bvh::BvhSettings,
+    camera::CameraSettings,
+    camera_path,
+    color::Color,
+    curve::{CurveShape, Curves},
+    cyhair::HairModel,
+    error::{Error, Result},
+    exposure::{Exposure, ShutterTime, DEFAULT_FPS},
+    float::Float,
+    font::Font,
+    fractal::{Formula, Fractal},
+    hair::Hair,
+    heightfield::{HeightMap, Heightfield},
+    hittable::{Hittable, HittableList},
+    id::{NamedMaterial, Names, Tagged, DEFAULT_GROUP},
+    instance::Instance,
+    lens::{self, LensSystem},
+    light_linking::LightLinks,
+    lut::{self, Lut},
+    material::{ComplexIor, Dielectric, DiffuseLight, Lambertian, Material, Metal, Plastic, ShadowCatcher, ThinFilm},
+    mesh::TriangleMesh,
+    obj::ObjModel,
+    parser::{parse_directives, Directive},
+    ply::PlyModel,
+    point_cloud::{PointCloud, Splat},
+    post::{Bloom, FilmGrain, LensFlare, WhiteBalance},
+    presets,
+    projection::Projection,
+    sphere::{Sphere, SphereList},
+    stl::StlModel,
+    subdivision::{self, SubdivisionMesh},
+    terrain::{NoiseKind, TerrainNoise, MAX_OCTAVES},
+    text::{TextMesh, TextStyle},
+    transform::Transform,
+    vec3::{Point, Vec3},
+    water::{Water, WindWaves},
+};
+#[cfg(feature = "importers")]
+use crate::{mitsuba, pbrt, usd};
+
+pub struct Scene {
+    pub camera: CameraSettings,
+    pub world: HittableList,
+    pub accelerator: Accelerator, // structure to build over the world and used by the meshes
+    // files the scene was read from, the scene files and the meshes they refer to
+    pub files: Vec<PathBuf>,
+    // settings the camera moves through in animations, empty if it stands still
+    pub camera_keyframes: Keyframes<CameraSettings>,
+    // names of the objects and materials that have IDs, see `id.rs`
+    pub names: Names,
+    // which lights light which objects
+    pub light_links: LightLinks,
+    // lens system of the camera, see `lens.rs`
+    pub lens: Option<Arc<LensSystem>>,
+    // image the aperture of the thin lens camera has the shape of, see `aperture.rs`
+    pub aperture_mask: Option<Arc<ApertureMask>>,
+    // color grade of the image, see `lut.rs`
+    pub lut: Option<Arc<Lut>>,
+}
+
+impl Scene {
+    pub fn load(path: &Path, bvh: BvhSettings) -> Result<Scene> {
+        Scene::load_with_overrides(path, bvh, &[])
+    }
+
+    // Load the scene and change parameters of it with `overrides`. Scenes in the formats of other renderers only take
+    // overrides of the camera.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "scene_load", level = "debug", skip_all, fields(path = %path.display()))
+    )]
+    pub fn load_with_overrides(path: &Path, bvh: BvhSettings, overrides: &[Override]) -> Result<Scene> {
+        let base = path.parent().unwrap_or(Path::new(""));
+        let scene = match path.extension().and_then(|extension| extension.to_str()) {
+            #[cfg(feature = "importers")]
+            Some("pbrt") => pbrt::load(path, bvh)?.with_overrides(overrides)?,
+            #[cfg(feature = "importers")]
+            Some("xml") => mitsuba::load(path, bvh)?.with_overrides(overrides)?,
+            #[cfg(feature = "importers")]
+            Some("usda" | "usd" | "usdz") => usd::load(path, bvh)?.with_overrides(overrides)?,
+            #[cfg(not(feature = "importers"))]
+            Some(extension @ ("pbrt" | "xml" | "usda" | "usd" | "usdz")) => {
+                let message = format!("loading .{extension} scenes needs the 'importers' feature");
+                return Err(Error::parse(0, message));
+            }
+            _ => {
+                let mut scene = Scene::parse_with_overrides(&fs::read_to_string(path)?, base, bvh, overrides)?;
+                scene.files.insert(0, path.to_path_buf());
+                scene
+            }
+        };
+        crate::debug!("loaded {} objects from {}", scene.world.len(), path.display());
+        Ok(scene)
+    }
+
+    // Change the camera of a scene that is loaded already, the objects can't be changed any more.
+    pub fn with_overrides(mut self, overrides: &[Override]) -> Result<Scene> {
+        if let Some(other) = overrides.iter().find(|o| !o.is_camera()) {
+            return Err(Error::parse(
+                0,
+                format!("--set {other}: only the camera of this scene can be changed"),
+            ));
+        }
+        self.camera = override_camera(self.camera, overrides)?;
+        self.camera_keyframes = self
+            .camera_keyframes
+            .try_map(|camera| override_camera(camera, overrides))?;
+        Ok(self)
+    }
+
+    // Move the camera along a path loaded from a file, see `camera_path::load`, instead of its keyframes. The image of
+    // a single frame shows the first frame of the path.
+    pub fn with_camera_path(mut self, path: &Path) -> Result<Scene> {
+        self.camera_keyframes = camera_path::load(path, self.camera)?;
+        self.camera = self.camera_at(0.0);
+        self.files.push(path.to_path_buf());
+        Ok(self)
+    }
+
+    // Check that the scene has the group of a render layer, see `Camera::with_layer`.
+    pub fn check_group(&self, group: &str) -> Result<()> {
+        if group == DEFAULT_GROUP || self.names.groups.contains(group) {
+            Ok(())
+        } else {
+            Err(Error::parse(0, format!("no group '{group}' in the scene")))
+        }
+    }
+
+    // Check that the scene has an object or a group of the name, see `Focus::Object`.
+    pub fn check_object(&self, name: &str) -> Result<()> {
+        if self.names.objects.contains(name) || self.names.groups.contains(name) {
+            Ok(())
+        } else {
+            Err(Error::parse(0, format!("no object or group '{name}' in the scene")))
+        }
+    }
+
+    // Camera settings at a frame of the animation.
+    pub fn camera_at(&self, frame: Float) -> CameraSettings {
+        self.camera_keyframes.at(frame).unwrap_or(self.camera)
+    }
+
+    // Build a scene from its text description, e.g.
+    //
+    //   camera image_width=400 aspect_ratio=1.5 look_from=0,1,4 look_at=0,0,0 vfov=40
+    //   material ground type=lambertian albedo=0.5,0.5,0.5
+    //   material glass type=dielectric ir=1.5
+    //   sphere center=0,-1000,0 radius=1000 material=ground
+    //   sphere center=0,1,0 radius=1 material=glass
+    //   include file=props/table.scene material=wood scale=0.5 translate=2,0,0
+    //   keyframe camera frame=1 look_from=0,1,4
+    //   keyframe camera frame=48 look_from=4,1,0
+    //
+    // Materials can be declared anywhere in the file and shared between objects. Files the scene refers to are
+    // looked up relative to `base`. The scene can pick its acceleration structure with `accelerator type=kdtree`,
+    // a BVH uses the `bvh` settings.
+    pub fn parse(source: &str, base: &Path, bvh: BvhSettings) -> Result<Scene> {
+        Scene::parse_with_overrides(source, base, bvh, &[])
+    }
+
+    // Build a scene from its text description after changing the directives with `overrides`.
+    pub fn parse_with_overrides(source: &str, base: &Path, bvh: BvhSettings, overrides: &[Override]) -> Result<Scene> {
+        Scene::from_description(SceneDescription::parse(source)?, base, bvh, overrides)
+    }
+
+    // Build a scene from the directives of its description, e.g. one read from JSON with the `serde` feature.
+    pub fn from_description(
+        description: SceneDescription,
+        base: &Path,
+        bvh: BvhSettings,
+        overrides: &[Override],
+    ) -> Result<Scene> {
+        let root = Arc::new(Origin {
+            base: base.to_path_buf(),
+            transform: Transform::IDENTITY,
+            file: None,
+            material: None,
+            include: None,
+        });
+        let mut builder = SceneBuilder::default();
+        let mut directives = vec![];
+        expand(description.directives, &root, &mut directives, &mut builder.files)?;
+        let (camera_overrides, overrides): (Vec<_>, Vec<_>) = overrides.iter().partition(|o| o.is_camera());
+        for o in overrides {
+            apply_override(o, &mut directives, &root)?;
+        }
+
+        // Materials and keyframes come first, objects can refer to the ones written after them
+        let mut accelerator = None;
+        let mut objects = vec![];
+        for (mut directive, origin) in directives {
+            let result = match directive.keyword.as_str() {
+                "keyframe" => {
+                    builder.keyframe(directive, origin)?;
+                    continue;
+                }
+                "accelerator" if accelerator.is_some() => Err(Error::parse(directive.line, "duplicate accelerator")),
+                "accelerator" => parse_accelerator(&mut directive, bvh).map(|parsed| accelerator = Some(parsed)),
+                "material" => builder.material(&mut directive, &origin),
+                _ => {
+                    objects.push((directive, origin));
+                    continue;
+                }
+            };
+            result
+                .and_then(|()| directive.finish())
+                .map_err(|err| origin.locate(err))?;
+        }
+        builder.accelerator = accelerator.unwrap_or(Accelerator::Bvh(bvh));
+        for (mut directive, origin) in objects {
+            // Keyframes are read with the object they move, their errors point to their own files
+            let placements = builder.animate(&directive, &origin)?;
+            let result = builder.object(&mut directive, &origin, placements);
+            result
+                .and_then(|()| directive.finish())
+                .map_err(|err| origin.locate(err))?;
+        }
+        builder.finish(camera_overrides)
+    }
+}
+
+// Directives of the objects of a scene, which can have names, be moved by keyframes and be linked to lights.
+const OBJECT_KEYWORDS: &[&str] = &[
+    "sphere",
+    "mesh",
+    "curves",
+    "points",
+    "heightfield",
+    "terrain",
+    "water",
+    "fractal",
+    "text",
+    "instance",
+];
+
+// Where a directive is written, see `Origin::site`.
+type Site = (Option<PathBuf>, usize);
+
+// What the directives of a scene have built so far, see `Scene::from_description`.
+#[derive(Default)]
+struct SceneBuilder {
+    files: Vec<PathBuf>,
+    // Files included more than once define their materials and meshes again, which is no conflict
+    materials: HashMap<String, Arc<dyn Material>>,
+    material_sites: HashMap<String, Site>,
+    accelerator: Accelerator,
+    // keyframes by the name of the object they move, until it is read
+    keyframes: HashMap<String, Vec<(Directive, Arc<Origin>)>>,
+    animated: HashSet<String>, // names of the objects that have keyframes
+    camera: Option<CameraSettings>,
+    camera_keyframes: Keyframes<CameraSettings>,
+    lens: Option<Arc<LensSystem>>,
+    aperture_mask: Option<Arc<ApertureMask>>,
+    lut: Option<Arc<Lut>>,
+    names: Names,
+    light_links: LightLinks,
+    linked: Vec<(String, Error)>, // names the light links refer to, with the error if they are unknown
+    meshes: HashMap<String, (Arc<dyn Hittable>, Site)>, // named meshes for instances
+    loaded: HashMap<(Site, Option<String>), Arc<dyn Hittable>>, // meshes by where they are written and their material
+    world: HittableList,
+    spheres: Vec<Sphere>, // spheres without a name, group or keyframes, kept in a list of their own
+}
+
+impl SceneBuilder {
+    fn keyframe(&mut self, directive: Directive, origin: Arc<Origin>) -> Result<()> {
+        let name = directive
+            .name
+            .clone()
+            .ok_or_else(|| origin.locate(Error::parse(directive.line, "keyframe must name the object it moves")))?;
+        self.keyframes.entry(name).or_default().push((directive, origin));
+        Ok(())
+    }
+
+    fn material(&mut self, directive: &mut Directive, origin: &Origin) -> Result<()> {
+        let name = directive
+            .name
+            .clone()
+            .ok_or_else(|| Error::parse(directive.line, "material must have a name"))?;
+        match self.material_sites.get(&name) {
+            Some(site) if *site == origin.site(directive.line) => {
+                directive.skip();
+                return Ok(());
+            }
+            Some(_) => return Err(Error::parse(directive.line, format!("duplicate material '{name}'"))),
+            None => {}
+        }
+        let material = parse_material(directive)?;
+        self.material_sites.insert(name.clone(), origin.site(directive.line));
+        self.materials
+            .insert(name.clone(), Arc::new(NamedMaterial::new(material, &name)));
+        Ok(())
+    }
+
+    // Placements of an object at the keyframes that move it. The keyframes of the camera go to `camera_keyframes`.
+    fn animate(&mut self, directive: &Directive, origin: &Origin) -> Result<Keyframes<Placement>> {
+        let target = match directive.keyword.as_str() {
+            "camera" => Some("camera"),
+            keyword if OBJECT_KEYWORDS.contains(&keyword) => directive.name.as_deref(),
+            _ => None,
+        };
+        let Some((name, keys)) = target.and_then(|target| self.keyframes.get_key_value(target)) else {
+            return Ok(Keyframes::default());
+        };
+        self.animated.insert(name.clone());
+        match directive.keyword.as_str() {
+            "camera" => {
+                self.camera_keyframes = keyframed(directive, keys, CAMERA_KEYS, |directive| {
+                    Ok(origin.place_camera(parse_camera(directive, CameraSettings::default())?))
+                })?;
+                Ok(Keyframes::default())
+            }
+            "sphere" => keyframed(directive, keys, &["center", "radius"], |directive| {
+                let center = directive.take_point_or("center", Point::ORIGIN)?;
+                let radius = directive.take_or("radius", 1.0)?;
+                Ok(Placement {
+                    scale: Vec3::new(radius, radius, radius),
+                    rotate: Vec3::ZERO,
+                    translate: center.to_vec(),
+                })
+            }),
+            _ => keyframed(directive, keys, &["scale", "rotate", "translate"], parse_placement),
+        }
+    }
+
+    // Add the camera or an object, moving through `placements` if it has keyframes.
+    fn object(&mut self, directive: &mut Directive, origin: &Origin, placements: Keyframes<Placement>) -> Result<()> {
+        // Objects of included files use the material of the include instead of their own
+        let material = match &origin.material {
+            Some(name) => {
+                directive.take("material");
+                Some(
+                    resolve_material(name, directive.line, &mut self.materials)?
+                        .ok_or_else(|| Error::parse(directive.line, format!("unknown material '{name}'")))?,
+                )
+            }
+            None => None,
+        };
+        if OBJECT_KEYWORDS.contains(&directive.keyword.as_str()) {
+            for name in take_links(directive, &mut self.light_links)? {
+                let unknown = Error::parse(directive.line, format!("no object or group '{name}' to link"));
+                self.linked.push((name, origin.locate(unknown)));
+            }
+        }
+        match directive.keyword.as_str() {
+            "camera" => self.camera(directive, origin),
+            "sphere" => self.sphere(directive, origin, material, placements),
+            "instance" => self.instance(directive, origin, placements),
+            keyword if OBJECT_KEYWORDS.contains(&keyword) => self.mesh_like(directive, origin, material, placements),
+            keyword => Err(Error::parse(directive.line, format!("unknown directive '{keyword}'"))),
+        }
+    }
+
+    fn camera(&mut self, directive: &mut Directive, origin: &Origin) -> Result<()> {
+        if self.camera.is_some() {
+            return Err(Error::parse(directive.line, "duplicate camera"));
+        }
+        let path = directive.take("path");
+        let lens_file = directive.take("lens");
+        let mask_file = directive.take("aperture_mask");
+        let lut_file = directive.take("lut");
+        if lens_file.is_some() {
+            if mask_file.is_some() {
+                return Err(Error::parse(
+                    directive.line,
+                    "a camera with a lens gets its aperture from the lens",
+                ));
+            }
+            if let Some(key) = ["vfov", "defocus_angle", "aperture_blades"]
+                .into_iter()
+                .find(|key| directive.get(key).is_some())
+            {
+                return Err(Error::parse(
+                    directive.line,
+                    format!("a camera with a lens gets its {key} from the lens"),
+                ));
+            }
+        }
+        let f_number = directive.get("f_number").and_then(|f_number| f_number.parse().ok());
+        let mut settings = parse_camera(directive, CameraSettings::default())?;
+        if let Some(file) = path {
+            if !self.camera_keyframes.is_empty() {
+                return Err(Error::parse(
+                    directive.line,
+                    "a camera with a path can't have keyframes",
+                ));
+            }
+            let path = origin.base.join(file);
+            self.camera_keyframes = camera_path::load(&path, settings)
+                .map_err(|err| Error::parse(directive.line, format!("{}: {err}", path.display())))?
+                .try_map(|camera| Ok::<_, Error>(origin.place_camera(camera)))?;
+            self.files.push(path);
+        }
+        if let Some(file) = lens_file {
+            let path = origin.base.join(file);
+            let system = load_lens(directive, &path, f_number, &settings)?;
+            settings = through_lens(settings, &system);
+            self.camera_keyframes = std::mem::take(&mut self.camera_keyframes)
+                .try_map(|camera| Ok::<_, Error>(through_lens(camera, &system)))?;
+            self.lens = Some(Arc::new(system));
+            self.files.push(path);
+        }
+        if let Some(file) = mask_file {
+            let path = origin.base.join(file);
+            let mask = aperture::load(&path)
+                .map_err(|err| Error::parse(directive.line, format!("{}: {err}", path.display())))?;
+            self.aperture_mask = Some(Arc::new(mask));
+            self.files.push(path);
+        }
+        if let Some(file) = lut_file {
+            let path = origin.base.join(file);
+            let table =
+                lut::load(&path).map_err(|err| Error::parse(directive.line, format!("{}: {err}", path.display())))?;
+            self.lut = Some(Arc::new(table));
+            self.files.push(path);
+        }
+        self.camera = Some(origin.place_camera(settings));
+        Ok(())
+    }
+
+    fn sphere(
+        &mut self,
+        directive: &mut Directive,
+        origin: &Origin,
+        material: Option<Arc<dyn Material>>,
+        placements: Keyframes<Placement>,
+    ) -> Result<()> {
+        let center = directive.take_point_or("center", Point::ORIGIN)?;
+        let radius = directive.take_or("radius", 1.0)?;
+        let group = take_group(directive)?;
+        let material = match material {
+            Some(material) => material,
+            None => lookup_material(directive, &mut self.materials)?,
+        };
+        let sphere: Box<dyn Hittable> = if !placements.is_empty() {
+            // A unit sphere placed at the center, scaled to the radius
+            let sphere = Arc::new(Sphere::new(Point::ORIGIN, 1.0, material));
+            Box::new(Animated::new(sphere, placements, origin.transform))
+        } else {
+            // Named spheres and spheres of a group are objects of their own, to carry the IDs
+            match origin.transform.uniform_scale() {
+                Some(scale) if directive.name.is_none() && group.is_none() => {
+                    let sphere = Sphere::new(origin.transform.point(center), radius * scale, material);
+                    self.spheres.push(sphere);
+                    return Ok(());
+                }
+                Some(scale) => Box::new(Sphere::new(origin.transform.point(center), radius * scale, material)),
+                None => {
+                    let sphere = Arc::new(Sphere::new(center, radius, material));
+                    Box::new(Instance::new(sphere, origin.transform))
+                }
+            }
+        };
+        let sphere = named(sphere, directive.name.as_deref(), group.as_deref(), &mut self.names);
+        self.world.push(sphere);
+        Ok(())
+    }
+
+    // Objects with geometry of their own, which instances can place again: meshes, curves, point clouds, heightfields,
+    // terrains, water surfaces, fractals and text.
+    fn mesh_like(
+        &mut self,
+        directive: &mut Directive,
+        origin: &Origin,
+        material: Option<Arc<dyn Material>>,
+        placements: Keyframes<Placement>,
+    ) -> Result<()> {
+        // Copies of an included file share the geometry, unless they use different materials
+        let site = origin.site(directive.line);
+        let key = (site.clone(), origin.material.clone());
+        let mesh = match self.loaded.get(&key) {
+            Some(mesh) => {
+                directive.take("file");
+                directive.take("material");
+                Arc::clone(mesh)
+            }
+            None => {
+                if let Some(file) = directive.get("file") {
+                    self.files.push(origin.base.join(file));
+                }
+                let (base, accelerator, materials) = (&origin.base, self.accelerator, &mut self.materials);
+                let mesh: Arc<dyn Hittable> = match directive.keyword.as_str() {
+                    "mesh" => Arc::new(parse_mesh(directive, base, accelerator, materials, material)?),
+                    "curves" => Arc::new(parse_curves(directive, base, accelerator, materials, material)?),
+                    "points" => Arc::new(parse_points(directive, base, accelerator, materials, material)?),
+                    "heightfield" => Arc::new(parse_heightfield(directive, base, accelerator, materials, material)?),
+                    "terrain" => Arc::new(parse_terrain(directive, accelerator, materials, material)?),
+                    "water" => Arc::new(parse_water(directive, materials, material)?),
+                    "fractal" => Arc::new(parse_fractal(directive, materials, material)?),
+                    "text" => Arc::new(parse_text(directive, base, accelerator, materials, material)?),
+                    keyword => return Err(Error::parse(directive.line, format!("unknown directive '{keyword}'"))),
+                };
+                self.loaded.insert(key, mesh.clone());
+                mesh
+            }
+        };
+        if let Some(name) = directive.name.clone() {
+            if self.meshes.get(&name).is_some_and(|(_, defined)| *defined != site) {
+                return Err(Error::parse(
+                    directive.line,
+                    format!("duplicate {} '{name}'", directive.keyword),
+                ));
+            }
+            self.meshes.insert(name, (mesh.clone(), site));
+        }
+        let transform = parse_transform(directive)?.then(origin.transform);
+        let mesh = place(mesh, transform, placements, origin.transform);
+        let group = take_group(directive)?;
+        let mesh = named(mesh, directive.name.as_deref(), group.as_deref(), &mut self.names);
+        self.world.push(mesh);
+        Ok(())
+    }
+
+    fn instance(&mut self, directive: &mut Directive, origin: &Origin, placements: Keyframes<Placement>) -> Result<()> {
+        let name = directive.take_required("mesh")?;
+        let (mesh, _) = self
+            .meshes
+            .get(&name)
+            .ok_or_else(|| Error::parse(directive.line, format!("unknown mesh '{name}'")))?;
+        let transform = parse_transform(directive)?.then(origin.transform);
+        let instance = place(mesh.clone(), transform, placements, origin.transform);
+        let group = take_group(directive)?;
+        // Instances without a name of their own go by the name of the mesh
+        let name = directive.name.as_deref().unwrap_or(&name);
+        self.world
+            .push(named(instance, Some(name), group.as_deref(), &mut self.names));
+        Ok(())
+    }
+
+    // The scene, once every directive is read and the names they refer to are known.
+    fn finish(mut self, camera_overrides: Vec<&Override>) -> Result<Scene> {
+        if let Some((name, keys)) = self
+            .keyframes
+            .iter()
+            .filter(|(name, _)| !self.animated.contains(*name))
+            .min_by_key(|(_, keys)| keys[0].0.line)
+        {
+            let (keyframe, origin) = &keys[0];
+            return Err(origin.locate(Error::parse(keyframe.line, format!("there is no '{name}' to animate"))));
+        }
+        let names = &self.names;
+        if let Some((_, err)) = self
+            .linked
+            .into_iter()
+            .find(|(name, _)| !names.objects.contains(name) && !names.groups.contains(name))
+        {
+            return Err(err);
+        }
+        if !self.spheres.is_empty() {
+            self.world
+                .push(Box::new(SphereList::new(self.spheres, self.accelerator)));
+        }
+
+        // Renders of a single image show frame 0
+        let camera = self.camera.unwrap_or_default();
+        let camera = self.camera_keyframes.at(0.0).unwrap_or(camera);
+        Ok(Scene {
+            camera: override_camera(camera, camera_overrides.iter().copied())?,
+            camera_keyframes: self
+                .camera_keyframes
+                .try_map(|camera| override_camera(camera, camera_overrides.iter().copied()))?,
+            world: self.world,
+            accelerator: self.accelerator,
+            files: self.files,
+            names: Names {
+                materials: self.materials.into_keys().collect(),
+                ..self.names
+            },
+            light_links: self.light_links,
+            lens: self.lens,
+            aperture_mask: self.aperture_mask,
+            lut: self.lut,
+        })
+    }
+}
+
+// Acceleration structure of `accelerator type=kdtree`, a BVH uses the `bvh` settings.
+fn parse_accelerator(directive: &mut Directive, bvh: BvhSettings) -> Result<Accelerator> {
+    Ok(match directive.take_parsed("type")? {
+        Some(Accelerator::Bvh(_)) | None => Accelerator::Bvh(bvh),
+        Some(other) => other,
+    })
+}
+
+// Scene file as its directives, in the order they are written. With the `serde` feature it goes through other
+// formats than the text one, e.g. JSON, and `Scene::from_description` builds the scene from it.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SceneDescription {
+    pub directives: Vec<Directive>,
+}
+
+impl SceneDescription {
+    pub fn parse(source: &str) -> Result<SceneDescription> {
+        Ok(SceneDescription {
+            directives: parse_directives(source)?,
+        })
+    }
+}
+
+// Change of a scene parameter from the command line, e.g.
+//
+//   camera.vfov=35  ball.radius=2  ball.material.roughness=0.2
+//
+// The object is the camera, or the objects and materials of that name in the scene file, and the key one of the
+// parameters of their directives. With `material` in between the key is a parameter of the material the object uses,
+// which changes all the other objects using it too.
+#[derive(Clone, Debug)]
+pub struct Override {
+    pub object: String,
+    pub material: bool,
+    pub key: String,
+    pub value: String,
+}
+
+impl Override {
+    fn is_camera(&self) -> bool {
+        self.object == "camera" && !self.material
+    }
+}
+
+impl FromStr for Override {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Override, String> {
+        let invalid = || format!("invalid override '{s}', expected OBJECT.KEY=VALUE or OBJECT.material.KEY=VALUE");
+        let (target, value) = s.split_once('=').ok_or_else(invalid)?;
+        let parts: Vec<_> = target.split('.').collect();
+        let (object, material, key) = match parts[..] {
+            [object, key] => (object, false, key),
+            [object, "material", key] => (object, true, key),
+            _ => return Err(invalid()),
+        };
+        if object.is_empty() || key.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Override {
+            object: object.to_string(),
+            material,
+            key: key.to_string(),
+            value: value.to_string(),
+        })
+    }
+}
+
+impl Display for Override {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let material = if self.material { ".material" } else { "" };
+        write!(f, "{}{material}.{}={}", self.object, self.key, self.value)
+    }
+}
+
+// Set the parameter of the override on the directives it names, or on the directives of the materials they use.
+// Presets are turned into materials of their own for that.
+fn apply_override(o: &Override, directives: &mut Vec<(Directive, Arc<Origin>)>, root: &Arc<Origin>) -> Result<()> {
+    let named = |directive: &Directive| directive.name.as_deref() == Some(o.object.as_str());
+    if !o.material {
+        let mut found = false;
+        for (directive, _) in directives.iter_mut().filter(|(directive, _)| named(directive)) {
+            // The change holds for the whole animation, keyframes setting the parameter get it as well
+            if directive.keyword == "keyframe" {
+                if directive.get(&o.key).is_some() {
+                    directive.set(&o.key, &o.value);
+                }
+                continue;
+            }
+            directive.set(&o.key, &o.value);
+            found = true;
+        }
+        if !found {
+            return Err(Error::parse(
+                0,
+                format!("--set {o}: there is no '{}' in the scene", o.object),
+            ));
+        }
+        return Ok(());
+    }
+
+    let mut names = vec![];
+    for (directive, origin) in directives
+        .iter()
+        .filter(|(directive, _)| !["material", "keyframe"].contains(&directive.keyword.as_str()) && named(directive))
+    {
+        // Objects of included files may use the material of the include
+        let name = origin
+            .material
+            .as_deref()
+            .or(directive.get("material"))
+            .ok_or_else(|| Error::parse(0, format!("--set {o}: '{}' has no material", o.object)))?;
+        if !names.iter().any(|known| known == name) {
+            names.push(name.to_string());
+        }
+    }
+    if names.is_empty() {
+        return Err(Error::parse(
+            0,
+            format!("--set {o}: there is no object '{}' in the scene", o.object),
+        ));
+    }
+    for name in names {
+        let mut found = false;
+        for (directive, _) in directives.iter_mut().filter(|(directive, _)| {
+            directive.keyword == "material" && directive.name.as_deref() == Some(name.as_str())
+        }) {
+            directive.set(&o.key, &o.value);
+            found = true;
+        }
+        if !found {
+            if presets::material(&name).is_none() {
+                return Err(Error::parse(0, format!("--set {o}: unknown material '{name}'")));
+            }
+            let mut preset = parse_directives(&format!("material {name} preset={name}"))?.remove(0);
+            preset.line = 0;
+            preset.set(&o.key, &o.value);
+            directives.push((preset, root.clone()));
+        }
+    }
+    Ok(())
+}
+
+// Apply the overrides of the camera on top of its settings.
+fn override_camera<'a>(
+    camera: CameraSettings,
+    overrides: impl IntoIterator<Item = &'a Override>,
+) -> Result<CameraSettings> {
+    let mut directive = parse_directives("camera")?.remove(0);
+    directive.line = 0;
+    for o in overrides {
+        directive.set(&o.key, &o.value);
+    }
+    let camera = parse_camera(&mut directive, camera)?;
+    directive.finish()?;
+    Ok(camera)
+}
+
+// Where a directive comes from: the root scene or a file it includes, with the placement of the include.
+struct Origin {
+    base: PathBuf,            // directory of the file, for the files it refers to
+    file: Option<PathBuf>,    // canonical path of an included file
+    transform: Transform,     // placement of everything in the file
+    material: Option<String>, // replaces the materials of all objects in the file
+    include: Option<Include>,
+}
+
+// An `include` directive, in the file of `parent`.
+struct Include {
+    line: usize,
+    path: PathBuf,
+    parent: Arc<Origin>,
+}
+
+impl Origin {
+    // Where a directive is written, to tell a file included twice from two definitions of the same name.
+    fn site(&self, line: usize) -> (Option<PathBuf>, usize) {
+        (self.file.clone(), line)
+    }
+
+    // Point errors in included files to the chain of includes that lead to them.
+    fn locate(&self, err: Error) -> Error {
+        match &self.include {
+            Some(include) => include
+                .parent
+                .locate(Error::parse(include.line, format!("{}: {err}", include.path.display()))),
+            None => err,
+        }
+    }
+
+    // Whether the file is being included already, so including it again would never end.
+    fn includes(&self, file: &Path) -> bool {
+        self.file.as_deref() == Some(file)
+            || self
+                .include
+                .as_ref()
+                .is_some_and(|include| include.parent.includes(file))
+    }
+
+    // Cameras of included files move with them, so a file can hold a camera rig.
+    fn place_camera(&self, camera: CameraSettings) -> CameraSettings {
+        CameraSettings {
+            look_from: self.transform.point(camera.look_from),
+            look_at: self.transform.point(camera.look_at),
+            vup: self.transform.vector(camera.vup),
+            focus_dist: camera.focus_dist * self.transform.uniform_scale().unwrap_or(1.0),
+            ..camera
+        }
+    }
+}
+
+// Replace the `include` directives by the directives of the files they include, recursively:
+//
+//   include file=props/table.scene material=wood scale=0.5 rotate=0,90,0 translate=2,0,0
+//
+// Included files share the materials and named meshes of the scene. Their objects and camera are placed with the
+// transformation of the include, and the optional `material` replaces the materials of all their objects.
+fn expand(
+    directives: Vec<Directive>,
+    origin: &Arc<Origin>,
+    expanded: &mut Vec<(Directive, Arc<Origin>)>,
+    files: &mut Vec<PathBuf>,
+) -> Result<()> {
+    for mut directive in directives {
+        if directive.keyword != "include" {
+            expanded.push((directive, origin.clone()));
+            continue;
+        }
+        let line = directive.line;
+        let included = (|| {
+            let path = origin.base.join(directive.take_required("file")?);
+            let material = directive.take("material");
+            let transform = parse_transform(&mut directive)?.then(origin.transform);
+            directive.finish()?;
+            let error = |err: Error| Error::parse(line, format!("{}: {err}", path.display()));
+            let file = fs::canonicalize(&path).map_err(|err| error(err.into()))?;
+            if origin.includes(&file) {
+                return Err(Error::parse(line, format!("{} includes itself", path.display())));
+            }
+            files.push(path.clone());
+            let source = fs::read_to_string(&path).map_err(|err| error(err.into()))?;
+            let directives = parse_directives(&source).map_err(error)?;
+            let included = Origin {
+                base: path.parent().unwrap_or(Path::new("")).to_path_buf(),
+                file: Some(file),
+                transform,
+                // The outermost include decides
+                material: origin.material.clone().or(material),
+                include: Some(Include {
+                    line,
+                    path,
+                    parent: origin.clone(),
+                }),
+            };
+            Ok((directives, Arc::new(included)))
+        })();
+        let (directives, included) = included.map_err(|err| origin.locate(err))?;
+        expand(directives, &included, expanded, files)?;
+    }
+    Ok(())
+}
+
+// Lens system of the camera directive, stopped down to its `f_number` and checked against its `settings`.
+fn load_lens(
+    directive: &Directive,
+    path: &Path,
+    f_number: Option<Float>,
+    settings: &CameraSettings,
+) -> Result<LensSystem> {
+    let system = lens::load(path).map_err(|err| Error::parse(directive.line, format!("{}: {err}", path.display())))?;
+    let system = match f_number {
+        Some(f_number) => system.stopped_down(f_number),
+        None => system,
+    };
+    let focal_length = system.focal_length();
+    if focal_length.is_nan() || focal_length <= 0.0 {
+        return Err(Error::parse(
+            directive.line,
+            format!("{}: the lens doesn't bring light to a focus", path.display()),
+        ));
+    }
+    if settings.projection != Projection::Perspective {
+        return Err(Error::parse(
+            directive.line,
+            "a camera with a lens has a perspective projection",
+        ));
+    }
+    if [settings.shift_x, settings.shift_y, settings.tilt, settings.swing]
+        .iter()
+        .any(|value| *value != 0.0)
+    {
+        return Err(Error::parse(
+            directive.line,
+            "a camera with a lens system can't tilt or shift it",
+        ));
+    }
+    if settings.focus_dist < system.closest_focus() {
+        return Err(Error::parse(
+            directive.line,
+            format!("the lens can't focus closer than {:.3}", system.closest_focus()),
+        ));
+    }
+    Ok(system)
+}
+
+// The camera settings with the field of view of the lens system over the sensor, without the thin lens.
+fn through_lens(settings: CameraSettings, system: &LensSystem) -> CameraSettings {
+    CameraSettings {
+        vfov: 2.0
+            * (settings.sensor_height / 2.0 / system.focal_length())
+                .atan()
+                .to_degrees(),
+        defocus_angle: 0.0,
+        ..settings
+    }
+}
+
+// Camera settings of the directive, the ones it leaves out keep their `default`.
+fn parse_camera(directive: &mut Directive, default: CameraSettings) -> Result<CameraSettings> {
+    // Photographic settings, the ones given change the exposure, the f-number sets the depth of field and the shutter
+    // time the motion blur
+    let positive = |directive: &mut Directive, key: &str| match directive.take_parsed::<Float>(key)? {
+        Some(value) if value <= 0.0 => Err(Error::parse(directive.line, format!("{key} must be positive"))),
+        value => Ok(value),
+    };
+    let iso = positive(directive, "iso")?;
+    let shutter_time = directive.take_parsed::<ShutterTime>("shutter_time")?;
+    let f_number = positive(directive, "f_number")?;
+    let fps = positive(directive, "fps")?.unwrap_or(DEFAULT_FPS);
+    let reference = Exposure::default();
+    let exposure = Exposure {
+        iso: iso.unwrap_or(reference.iso),
+        shutter_time: shutter_time.map_or(reference.shutter_time, |ShutterTime(time)| time),
+        f_number: f_number.unwrap_or(reference.f_number),
+    };
+    for (key, photographic, given) in [
+        ("defocus_angle", "f_number", f_number.is_some()),
+        ("shutter", "shutter_time", shutter_time.is_some()),
+    ] {
+        if given && directive.get(key).is_some() {
+            return Err(Error::parse(
+                directive.line,
+                format!("give either {key} or {photographic}"),
+            ));
+        }
+    }
+
+    let mut settings = CameraSettings {
+        aspect_ratio: match directive.take_or("aspect_ratio", default.aspect_ratio)? {
+            ratio if ratio.is_finite() && ratio > 0.0 => ratio,
+            _ => return Err(Error::parse(directive.line, "aspect_ratio must be positive")),
+        },
+        image_width: match directive.take_or("image_width", default.image_width)? {
+            width if width > 0 => width,
+            _ => return Err(Error::parse(directive.line, "image_width must be positive")),
+        },
+        samples_per_pixel: match directive.take_or("samples_per_pixel", default.samples_per_pixel)? {
+            samples if samples > 0 => samples,
+            _ => return Err(Error::parse(directive.line, "samples_per_pixel must be positive")),
+        },
+        max_depth: directive.take_or("max_depth", default.max_depth)?,
+        vfov: directive.take_or("vfov", default.vfov)?,
+        look_from: directive.take_point_or("look_from", default.look_from)?,
+        look_at: directive.take_point_or("look_at", default.look_at)?,
+        vup: directive.take_vec3_or("vup", default.vup)?,
+        defocus_angle: directive.take_or("defocus_angle", default.defocus_angle)?,
+        focus_dist: directive.take_or("focus_dist", default.focus_dist)?,
+        filter: directive.take_or("filter", default.filter)?,
+        projection: directive.take_or("projection", default.projection)?,
+        shutter: match directive.take_or("shutter", default.shutter)? {
+            shutter if (0.0..=1.0).contains(&shutter) => shutter,
+            _ => return Err(Error::parse(directive.line, "shutter must be between 0 and 1")),
+        },
+        stereo: directive.take_or("stereo", default.stereo)?,
+        interocular: match directive.take_or("interocular", default.interocular)? {
+            interocular if interocular >= 0.0 => interocular,
+            _ => return Err(Error::parse(directive.line, "interocular must not be negative")),
+        },
+        exposure: default.exposure,
+        sensor_height: match directive.take_or("sensor_height", default.sensor_height)? {
+            height if height > 0.0 => height,
+            _ => return Err(Error::parse(directive.line, "sensor_height must be positive")),
+        },
+        aperture_blades: match directive.take_or("aperture_blades", default.aperture_blades)? {
+            1 | 2 => return Err(Error::parse(directive.line, "an aperture needs at least 3 blades")),
+            blades => blades,
+        },
+        aperture_rotation: directive.take_or("aperture_rotation", default.aperture_rotation)?,
+        shift_x: directive.take_or("shift_x", default.shift_x)?,
+        shift_y: directive.take_or("shift_y", default.shift_y)?,
+        tilt: directive.take_or("tilt", default.tilt)?,
+        swing: directive.take_or("swing", default.swing)?,
+        vignetting: match directive.take_or("vignetting", default.vignetting)? {
+            amount if (0.0..=1.0).contains(&amount) => amount,
+            _ => return Err(Error::parse(directive.line, "vignetting must be between 0 and 1")),
+        },
+        chromatic_aberration: match directive.take_or("chromatic_aberration", default.chromatic_aberration)? {
+            amount if amount.abs() < 1.0 => amount,
+            _ => {
+                return Err(Error::parse(
+                    directive.line,
+                    "chromatic_aberration must be between -1 and 1",
+                ))
+            }
+        },
+        bloom: Bloom {
+            strength: match directive.take_or("bloom", default.bloom.strength)? {
+                strength if strength >= 0.0 => strength,
+                _ => return Err(Error::parse(directive.line, "bloom must not be negative")),
+            },
+            threshold: directive.take_or("bloom_threshold", default.bloom.threshold)?,
+            radius: match directive.take_or("bloom_radius", default.bloom.radius)? {
+                radius if radius > 0.0 => radius,
+                _ => return Err(Error::parse(directive.line, "bloom_radius must be positive")),
+            },
+        },
+        flare: LensFlare {
+            strength: match directive.take_or("flare", default.flare.strength)? {
+                strength if strength >= 0.0 => strength,
+                _ => return Err(Error::parse(directive.line, "flare must not be negative")),
+            },
+            threshold: directive.take_or("flare_threshold", default.flare.threshold)?,
+            ghosts: match directive.take_or("flare_ghosts", default.flare.ghosts)? {
+                ghosts if ghosts <= 8 => ghosts,
+                _ => return Err(Error::parse(directive.line, "a flare has at most 8 ghosts")),
+            },
+            halo: match directive.take_or("flare_halo", default.flare.halo)? {
+                halo if halo >= 0.0 => halo,
+                _ => return Err(Error::parse(directive.line, "flare_halo must not be negative")),
+            },
+            streaks: directive.take_or("flare_streaks", default.flare.streaks)?,
+        },
+        grain: FilmGrain {
+            amount: match directive.take_or("grain", default.grain.amount)? {
+                amount if amount >= 0.0 => amount,
+                _ => return Err(Error::parse(directive.line, "grain must not be negative")),
+            },
+            size: match directive.take_or("grain_size", default.grain.size)? {
+                size if size > 0.0 => size,
+                _ => return Err(Error::parse(directive.line, "grain_size must be positive")),
+            },
+            seed: directive.take_or("grain_seed", default.grain.seed)?,
+        },
+        white_balance: WhiteBalance {
+            temperature: match directive.take_or("white_balance", default.white_balance.temperature)? {
+                temperature if (1000.0..=40000.0).contains(&temperature) => temperature,
+                _ => {
+                    return Err(Error::parse(
+                        directive.line,
+                        "white_balance must be between 1000 and 40000 Kelvin",
+                    ))
+                }
+            },
+            tint: match directive.take_or("tint", default.white_balance.tint)? {
+                tint if tint > -1.0 && tint < 1.0 => tint,
+                _ => return Err(Error::parse(directive.line, "tint must be between -1 and 1")),
+            },
+        },
+        color_space: directive.take_or("color_space", default.color_space)?,
+        background: directive.take_color("background")?.or(default.background),
+    };
+    if settings.projection.is_fisheye() && (settings.vfov <= 0.0 || settings.vfov > 360.0) {
+        return Err(Error::parse(
+            directive.line,
+            "the field of view of a fisheye lens must be between 0 and 360 degrees",
+        ));
+    }
+    if [settings.tilt, settings.swing].iter().any(|angle| angle.abs() >= 90.0) {
+        return Err(Error::parse(
+            directive.line,
+            "tilt and swing must be between -90 and 90 degrees",
+        ));
+    }
+    if [settings.shift_x, settings.shift_y, settings.tilt, settings.swing]
+        .iter()
+        .any(|value| *value != 0.0)
+        && settings.projection != Projection::Perspective
+    {
+        return Err(Error::parse(
+            directive.line,
+            "a camera with a tilted or shifted lens has a perspective projection",
+        ));
+    }
+    if iso.is_some() || shutter_time.is_some() || f_number.is_some() {
+        settings.exposure = exposure.scale();
+    }
+    if f_number.is_some() {
+        settings.defocus_angle = exposure.defocus_angle(settings.vfov, settings.sensor_height, settings.focus_dist);
+    }
+    if shutter_time.is_some() {
+        settings.shutter = exposure.shutter(fps);
+    }
+    Ok(settings)
+}
+
+fn parse_material(directive: &mut Directive) -> Result<Arc<dyn Material>> {
+    if let Some(name) = directive.take("preset") {
+        let preset = material_preset(&name, directive.line)?;
+        directive.inherit(preset);
+    }
+
+    let kind = directive.take_required("type")?;
+    let film = match directive.take_parsed("film_thickness")? {
+        Some(thickness) => Some(ThinFilm::new(thickness, take_ior(directive, "film_ir", 1.33)?)),
+        None => None,
+    };
+    let material: Arc<dyn Material> = match kind.as_str() {
+        "lambertian" if film.is_some() => {
+            return Err(Error::parse(
+                directive.line,
+                "lambertian material does not support a film",
+            ))
+        }
+        "lambertian" => Arc::new(Lambertian::new(
+            directive.take_color_or("albedo", Color::new(0.5, 0.5, 0.5))?,
+        )),
+        "metal" => {
+            let fuzz = directive.take_or("fuzz", 0.0)?;
+            let metal = match parse_conductor(directive)? {
+                Some(ior) => Metal {
+                    albedo: directive.take_color_or("albedo", Color::WHITE)?,
+                    ..Metal::conductor(ior, fuzz)
+                },
+                None => Metal::new(directive.take_color_or("albedo", Color::new(0.8, 0.8, 0.8))?, fuzz),
+            };
+            match film {
+                Some(film) => Arc::new(metal.with_film(film)),
+                None => Arc::new(metal),
+            }
+        }
+        "dielectric" => {
+            let absorption = directive.take_color_or("absorption", Color::BLACK)?;
+            if absorption
+                .to_array()
+                .iter()
+                .any(|channel| channel.is_nan() || *channel < 0.0)
+            {
+                return Err(Error::parse(directive.line, "absorption must not be negative"));
+            }
+            let dielectric = Dielectric::new(take_ior(directive, "ir", 1.5)?)
+                .with_roughness(directive.take_or("roughness", 0.0)?)
+                .with_priority(directive.take_or("priority", 0)?)
+                .with_absorption(absorption);
+            match film {
+                Some(film) => Arc::new(dielectric.with_film(film)),
+                None => Arc::new(dielectric),
+            }
+        }
+        "shadow_catcher" if film.is_some() => {
+            return Err(Error::parse(directive.line, "shadow catcher does not support a film"))
+        }
+        "shadow_catcher" => Arc::new(ShadowCatcher::new(
+            directive.take_color_or("albedo", Color::new(0.5, 0.5, 0.5))?,
+        )),
+        "plastic" if film.is_some() => {
+            return Err(Error::parse(directive.line, "plastic material does not support a film"))
+        }
+        "plastic" => Arc::new(Plastic::new(
+            directive.take_color_or("albedo", Color::new(0.8, 0.8, 0.8))?,
+            take_ior(directive, "ir", 1.5)?,
+            directive.take_or("roughness", 0.0)?,
+        )),
+        "hair" if film.is_some() => return Err(Error::parse(directive.line, "hair does not support a film")),
+        "hair" => {
+            let roughness = directive.take_or("roughness", 0.3)?;
+            let azimuthal_roughness = directive.take_or("azimuthal_roughness", 0.3)?;
+            let hair = match (directive.take_color("absorption")?, directive.take_color("color")?) {
+                (Some(_), Some(_)) => {
+                    return Err(Error::parse(
+                        directive.line,
+                        "hair takes either an 'absorption' or a 'color'",
+                    ))
+                }
+                (Some(absorption), None) => Hair::new(absorption),
+                (None, Some(color)) => Hair::from_color(color, azimuthal_roughness),
+                (None, None) => Hair::melanin(
+                    directive.take_or("eumelanin", 1.3)?,
+                    directive.take_or("pheomelanin", 0.0)?,
+                ),
+            };
+            Arc::new(
+                hair.with_ior(take_ior(directive, "ir", 1.55)?)
+                    .with_roughness(roughness, azimuthal_roughness)
+                    .with_scale_angle(directive.take_or("scale_angle", 2.0)?),
+            )
+        }
+        "light" if film.is_some() => return Err(Error::parse(directive.line, "light does not support a film")),
+        "light" => Arc::new(
+            DiffuseLight::new(directive.take_color_or("emit", Color::WHITE)?)
+                .with_two_sided(directive.take_or("two_sided", false)?),
+        ),
+        kind => return Err(Error::parse(directive.line, format!("unknown material type '{kind}'"))),
+    };
+    Ok(material)
+}
+
+// Strands of hair or fur loaded from a file in the HAIR format, see `cyhair.rs`. Their widths come from the file unless
+// `width` (and `tip_width` if they narrow) are given.
+fn parse_curves(
+    directive: &mut Directive,
+    base: &Path,
+    accelerator: Accelerator,
+    materials: &mut HashMap<String, Arc<dyn Material>>,
+    replacement: Option<Arc<dyn Material>>,
+) -> Result<Curves> {
+    let line = directive.line;
+    let path = base.join(directive.take_required("file")?);
+    let material = take_material(directive, materials, replacement)?;
+    let shape = directive.take_or("shape", CurveShape::Flat)?;
+    let width: Option<Float> = directive.take_parsed("width")?;
+    let tip_width = directive.take_parsed("tip_width")?.or(width);
+    if tip_width.is_some() && width.is_none() {
+        return Err(Error::parse(line, "'tip_width' needs a 'width' at the root"));
+    }
+
+    let model = HairModel::load(&path).map_err(|err| Error::parse(line, format!("{}: {err}", path.display())))?;
+    let mut strands = model.strands;
+    if strands.is_empty() {
+        return Err(Error::parse(line, format!("{} has no strands", path.display())));
+    }
+    if let (Some(width), Some(tip_width)) = (width, tip_width) {
+        for strand in &mut strands {
+            strand.widths = [width, tip_width];
+        }
+    }
+    Ok(Curves::new(strands, shape, material, accelerator))
+}
+
+// Points of a PLY file drawn as small spheres or disks, see `point_cloud.rs`. They are as large as `radius`, or as the
+// splats of the file for Gaussian splats, and tinted by the colors of the file.
+fn parse_points(
+    directive: &mut Directive,
+    base: &Path,
+    accelerator: Accelerator,
+    materials: &mut HashMap<String, Arc<dyn Material>>,
+    replacement: Option<Arc<dyn Material>>,
+) -> Result<PointCloud> {
+    let line = directive.line;
+    let path = base.join(directive.take_required("file")?);
+    let material = take_material(directive, materials, replacement)?;
+    let splat = directive.take_or("shape", Splat::Sphere)?;
+    let radius: Option<Float> = directive.take_parsed("radius")?;
+    if path.extension().and_then(|extension| extension.to_str()) != Some("ply") {
+        return Err(Error::parse(line, "point clouds are read from PLY files"));
+    }
+
+    let model = PlyModel::load(&path).map_err(|err| Error::parse(line, format!("{}: {err}", path.display())))?;
+    if model.vertices.is_empty() {
+        return Err(Error::parse(line, format!("{} has no points", path.display())));
+    }
+    let radii = match radius {
+        Some(radius) => vec![radius; model.vertices.len()],
+        None if !model.radii.is_empty() => model.radii,
+        None => {
+            return Err(Error::parse(
+                line,
+                format!("the points of {} need a 'radius'", path.display()),
+            ))
+        }
+    };
+    let mut cloud = PointCloud::new(model.vertices, radii, splat, material, accelerator);
+    if !model.colors.is_empty() {
+        cloud = cloud.with_colors(model.colors);
+    }
+    if !model.normals.is_empty() {
+        cloud = cloud.with_normals(model.normals);
+    }
+    Ok(cloud)
+}
+
+// Terrain from the heights of a grayscale image, see `heightfield.rs`. It is `width` wide along x, `depth` deep along z
+// (in the proportions of the image by default) and white pixels are `height` high.
+fn parse_heightfield(
+    directive: &mut Directive,
+    base: &Path,
+    accelerator: Accelerator,
+    materials: &mut HashMap<String, Arc<dyn Material>>,
+    replacement: Option<Arc<dyn Material>>,
+) -> Result<Heightfield> {
+    let line = directive.line;
+    let path = base.join(directive.take_required("file")?);
+    let material = take_material(directive, materials, replacement)?;
+    let width: Float = directive.take_or("width", 1.0)?;
+    let depth: Option<Float> = directive.take_parsed("depth")?;
+    let height: Float = directive.take_or("height", 1.0)?;
+
+    let map = HeightMap::load(&path).map_err(|err| Error::parse(line, format!("{}: {err}", path.display())))?;
+    let depth = depth.unwrap_or(width * (map.rows - 1) as Float / (map.columns - 1) as Float);
+    if [width, depth, height].iter().any(|size| size.is_nan() || *size <= 0.0) {
+        return Err(Error::parse(line, "'width', 'depth' and 'height' must be positive"));
+    }
+    Ok(Heightfield::new(
+        map.columns,
+        map.rows,
+        map.heights,
+        Vec3::new(width, height, depth),
+        material,
+        accelerator,
+    ))
+}
+
+// Terrain of procedural noise, see `terrain.rs`, generated as a heightfield `width` wide and `depth` deep with
+// `resolution` cells along its longer side. The noise is sampled at the points of the grid moved by `offset`, so a
+// terrain placed next to another with the same noise continues it when `offset` moves the same way as `translate`.
+fn parse_terrain(
+    directive: &mut Directive,
+    accelerator: Accelerator,
+    materials: &mut HashMap<String, Arc<dyn Material>>,
+    replacement: Option<Arc<dyn Material>>,
+) -> Result<Heightfield> {
+    let line = directive.line;
+    let material = take_material(directive, materials, replacement)?;
+    let width: Float = directive.take_or("width", 1.0)?;
+    let depth: Float = directive.take_or("depth", width)?;
+    let height: Float = directive.take_or("height", 1.0)?;
+    let resolution: usize = directive.take_or("resolution", 256)?;
+    let offset = directive.take_vec3_or("offset", Vec3::ZERO)?;
+    let noise = TerrainNoise {
+        kind: directive.take_or("noise", NoiseKind::Fbm)?,
+        octaves: directive.take_or("octaves", 6)?,
+        // A few hills across the terrain
+        frequency: directive.take_or("frequency", 4.0 / width.max(depth))?,
+        lacunarity: directive.take_or("lacunarity", 2.0)?,
+        gain: directive.take_or("gain", 0.5)?,
+        seed: directive.take_or("seed", 0)?,
+    };
+    if [width, depth, height].iter().any(|size| size.is_nan() || *size <= 0.0) {
+        return Err(Error::parse(line, "'width', 'depth' and 'height' must be positive"));
+    }
+    if resolution == 0 {
+        return Err(Error::parse(line, "'resolution' must be at least 1"));
+    }
+    if noise.octaves > MAX_OCTAVES {
+        return Err(Error::parse(line, format!("'octaves' must be at most {MAX_OCTAVES}")));
+    }
+    if [noise.frequency, noise.lacunarity, noise.gain]
+        .iter()
+        .any(|value| !value.is_finite() || *value <= 0.0)
+    {
+        return Err(Error::parse(
+            line,
+            "'frequency', 'lacunarity' and 'gain' must be positive and finite",
+        ));
+    }
+
+    // Square cells, `resolution` of them along the longer side
+    let spacing = width.max(depth) / resolution as Float;
+    let [columns, rows] = [width, depth].map(|extent| (extent / spacing).round().max(1.0) as usize + 1);
+    let mut heights = Vec::with_capacity(columns * rows);
+    for row in 0..rows {
+        let z = (row as Float / (rows - 1) as Float - 0.5) * depth;
+        for column in 0..columns {
+            let x = (column as Float / (columns - 1) as Float - 0.5) * width;
+            heights.push(noise.height(x + offset.x, z + offset.z));
+        }
+    }
+    Ok(Heightfield::new(
+        columns,
+        rows,
+        heights,
+        Vec3::new(width, height, depth),
+        material,
+        accelerator,
+    ))
+}
+
+// Surface of water with waves raised by the wind, see `water.rs`, `width` wide and `depth` deep. The waves roll on
+// with the frames of an animation at `speed` times their real speed, in seconds of `fps` frames.
+fn parse_water(
+    directive: &mut Directive,
+    materials: &mut HashMap<String, Arc<dyn Material>>,
+    replacement: Option<Arc<dyn Material>>,
+) -> Result<Water> {
+    let line = directive.line;
+    let material = take_material(directive, materials, replacement)?;
+    let width: Float = directive.take_or("width", 1.0)?;
+    let depth: Float = directive.take_or("depth", width)?;
+    let default = WindWaves::default();
+    let wavelength: Float = directive.take_or("wavelength", default.wavelength)?;
+    let wind = WindWaves {
+        count: directive.take_or("waves", default.count)?,
+        wavelength,
+        amplitude: directive.take_or("amplitude", default.amplitude * wavelength / default.wavelength)?,
+        steepness: directive.take_or("steepness", default.steepness)?,
+        direction: directive.take_or("direction", default.direction)?,
+        spread: directive.take_or("spread", default.spread)?,
+        seed: directive.take_or("seed", default.seed)?,
+    };
+    let speed: Float = directive.take_or("speed", 1.0)?;
+    let fps: Float = directive.take_or("fps", DEFAULT_FPS)?;
+    if [width, depth, wavelength, fps]
+        .iter()
+        .any(|value| value.is_nan() || *value <= 0.0)
+    {
+        return Err(Error::parse(
+            line,
+            "'width', 'depth', 'wavelength' and 'fps' must be positive",
+        ));
+    }
+    if !(0.0..=1.0).contains(&wind.steepness) {
+        return Err(Error::parse(line, "'steepness' must be between 0 and 1"));
+    }
+    if wind.amplitude.is_nan() || wind.amplitude < 0.0 {
+        return Err(Error::parse(line, "'amplitude' must not be negative"));
+    }
+    Ok(Water::new(width, depth, wind.waves(), material).with_speed(speed, fps))
+}
+
+// Fractal of distance estimates, see `fractal.rs`, of the formula `type` to `iterations` levels of detail. Its surface
+// is tinted from `color` to `color2` by the iterations points take to escape, if either is given.
+fn parse_fractal(
+    directive: &mut Directive,
+    materials: &mut HashMap<String, Arc<dyn Material>>,
+    replacement: Option<Arc<dyn Material>>,
+) -> Result<Fractal> {
+    let line = directive.line;
+    let material = take_material(directive, materials, replacement)?;
+    let (formula, iterations) = match directive.take_required("type")?.as_str() {
+        "mandelbulb" => (
+            Formula::Mandelbulb {
+                power: directive.take_or("power", 8.0)?,
+            },
+            12,
+        ),
+        "menger" => (Formula::Menger, 4),
+        "julia" => {
+            let c = match directive.take("c") {
+                Some(value) => value
+                    .split(',')
+                    .map(|component| component.trim().parse::<Float>().ok())
+                    .collect::<Option<Vec<_>>>()
+                    .and_then(|components| <[Float; 4]>::try_from(components).ok())
+                    .ok_or_else(|| Error::parse(line, format!("invalid quaternion '{value}' for 'c'")))?,
+                None => [-0.2, 0.8, 0.0, 0.0],
+            };
+            (Formula::Julia { c }, 10)
+        }
+        other => {
+            return Err(Error::parse(
+                line,
+                format!("unknown fractal '{other}', expected 'mandelbulb', 'menger' or 'julia'"),
+            ))
+        }
+    };
+    let iterations = directive.take_or("iterations", iterations)?;
+    if let Formula::Mandelbulb { power } = formula {
+        if power.is_nan() || power <= 1.0 {
+            return Err(Error::parse(line, "the power of a mandelbulb must be above 1"));
+        }
+    }
+    let fractal = Fractal::new(formula, iterations, material);
+    Ok(
+        match (directive.take_color("color")?, directive.take_color("color2")?) {
+            (None, None) => fractal,
+            (color, color2) => {
+                let color = color.unwrap_or(Color::WHITE);
+                fractal.with_colors([color, color2.unwrap_or(color)])
+            }
+        },
+    )
+}
+
+fn parse_text(
+    directive: &mut Directive,
+    base: &Path,
+    accelerator: Accelerator,
+    materials: &mut HashMap<String, Arc<dyn Material>>,
+    replacement: Option<Arc<dyn Material>>,
+) -> Result<TriangleMesh> {
+    let line = directive.line;
+    let path = base.join(directive.take_required("file")?);
+    // `\n` starts a new line
+    let string = directive.take_required("string")?.replace("\\n", "\n");
+    let material = take_material(directive, materials, replacement)?;
+    let defaults = TextStyle::default();
+    let style = TextStyle {
+        size: directive.take_or("size", defaults.size)?,
+        depth: directive.take_or("depth", defaults.depth)?,
+        align: directive.take_or("align", defaults.align)?,
+        line_spacing: directive.take_or("line_spacing", defaults.line_spacing)?,
+    };
+    if style.size.is_nan() || style.size <= 0.0 {
+        return Err(Error::parse(line, "'size' must be positive"));
+    }
+    if style.depth.is_nan() || style.depth < 0.0 {
+        return Err(Error::parse(line, "'depth' must not be negative"));
+    }
+
+    let located = |err: Error| Error::parse(line, format!("{}: {err}", path.display()));
+    let font = Font::load(&path).map_err(located)?;
+    let text = TextMesh::new(&font, &string, &style).map_err(located)?;
+    if text.triangles.is_empty() {
+        return Err(Error::parse(line, format!("no glyphs to show for '{string}'")));
+    }
+    Ok(TriangleMesh::new(text.vertices, text.triangles, material, accelerator).with_normals(text.normals))
+}
+
+// Material of an object, the one of the include it is in or its own `material`.
+fn take_material(
+    directive: &mut Directive,
+    materials: &mut HashMap<String, Arc<dyn Material>>,
+    replacement: Option<Arc<dyn Material>>,
+) -> Result<Arc<dyn Material>> {
+    match replacement {
+        Some(material) => {
+            directive.take("material");
+            Ok(material)
+        }
+        None => lookup_material(directive, materials),
+    }
+}
+
+// Camera settings of the parameters of a camera directive, e.g. `look_from=0,1,5 vfov=40`, for scenes built in code.
+// Files like a lens or a LUT can't be given.
+pub fn parse_camera_parameters(parameters: &str) -> Result<CameraSettings> {
+    let mut directive = parse_parameters("camera", parameters)?;
+    let settings = parse_camera(&mut directive, CameraSettings::default())?;
+    directive.finish()?;
+    Ok(settings)
+}
+
+// Material of the parameters of a material directive, e.g. `type=metal albedo=0.8,0.6,0.2`, for scenes built in
+// code.
+pub fn parse_material_parameters(parameters: &str) -> Result<Arc<dyn Material>> {
+    let mut directive = parse_parameters("material", parameters)?;
+    let material = parse_material(&mut directive)?;
+    directive.finish()?;
+    Ok(material)
+}
+
+fn parse_parameters(keyword: &str, parameters: &str) -> Result<Directive> {
+    let mut directives = parse_directives(&format!("{keyword} {parameters}"))?;
+    match (directives.pop(), directives.is_empty()) {
+        (Some(directive), true) if directive.name.is_none() => Ok(directive),
+        (Some(directive), true) => Err(Error::parse(
+            1,
+            format!("expected key=value, found '{}'", directive.name.unwrap_or_default()),
+        )),
+        _ => Err(Error::parse(1, "expected the parameters on a single line")),
+    }
+}
+
+// Index of refraction given either as a number or as a name of a known medium (`ir=water`).
+fn take_ior(directive: &mut Directive, key: &str, default: Float) -> Result<Float> {
+    let Some(value) = directive.take(key) else {
+        return Ok(default);
+    };
+    value.parse().ok().or_else(|| presets::ior(&value)).ok_or_else(|| {
+        Error::parse(
+            directive.line,
+            format!("invalid index of refraction '{value}' for '{key}'"),
+        )
+    })
+}
+
+fn material_preset(name: &str, line: usize) -> Result<Directive> {
+    let params = presets::material(name).ok_or_else(|| Error::parse(line, format!("unknown preset '{name}'")))?;
+    let mut directives = parse_directives(&format!("material {name} {params}"))?;
+    let mut preset = directives.remove(0);
+    preset.line = line;
+    Ok(preset)
+}
+
+// Optical constants of a metal, either a named preset (`conductor=gold`) or explicit `eta` and `k`.
+fn parse_conductor(directive: &mut Directive) -> Result<Option<ComplexIor>> {
+    if let Some(name) = directive.take("conductor") {
+        return ComplexIor::preset(&name)
+            .map(Some)
+            .ok_or_else(|| Error::parse(directive.line, format!("unknown conductor '{name}'")));
+    }
+    match (directive.take_color("eta")?, directive.take_color("k")?) {
+        (Some(eta), Some(k)) => Ok(Some(ComplexIor::new(eta, k))),
+        (None, None) => Ok(None),
+        _ => Err(Error::parse(directive.line, "both 'eta' and 'k' are required")),
+    }
+}
+
+// Find the material the directive refers to, presets can be used directly when the scene doesn't define a material
+// with the same name.
+fn lookup_material(
+    directive: &mut Directive,
+    materials: &mut HashMap<String, Arc<dyn Material>>,
+) -> Result<Arc<dyn Material>> {
+    let name = directive.take_required("material")?;
+    resolve_material(&name, directive.line, materials)?
+        .ok_or_else(|| Error::parse(directive.line, format!("unknown material '{name}'")))
+}
+
+fn resolve_material(
+    name: &str,
+    line: usize,
+    materials: &mut HashMap<String, Arc<dyn Material>>,
+) -> Result<Option<Arc<dyn Material>>> {
+    if let Some(material) = materials.get(name) {
+        return Ok(Some(material.clone()));
+    }
+    if presets::material(name).is_none() {
+        return Ok(None);
+    }
+    let mut preset = material_preset(name, line)?;
+    let material: Arc<dyn Material> = Arc::new(NamedMaterial::new(parse_material(&mut preset)?, name));
+    materials.insert(name.to_string(), material.clone());
+    Ok(Some(material))
+}
+
+// The object with the ID of its name, if it has one, see `id.rs`.
+fn named(object: Box<dyn Hittable>, name: Option<&str>, group: Option<&str>, names: &mut Names) -> Box<dyn Hittable> {
+    if name.is_none() && group.is_none() {
+        return object;
+    }
+    names.objects.extend(name.map(str::to_string));
+    names.groups.extend(group.map(str::to_string));
+    Box::new(Tagged::new(object, name, group))
+}
+
+// Light linking of a named object, see `light_linking.rs`: `light_include` or `light_exclude` list the objects and
+// groups a light lights only or leaves out, `lit_by` or `not_lit_by` the lights and groups of lights an object is lit
+// by only or not lit by. Returns the names listed, which may come later in the file.
+fn take_links(directive: &mut Directive, links: &mut LightLinks) -> Result<Vec<String>> {
+    let mut listed = vec![];
+    for (key, light, include) in [
+        ("light_include", true, true),
+        ("light_exclude", true, false),
+        ("lit_by", false, true),
+        ("not_lit_by", false, false),
+    ] {
+        let Some(value) = directive.take(key) else {
+            continue;
+        };
+        let name = directive
+            .name
+            .as_deref()
+            .ok_or_else(|| Error::parse(directive.line, format!("{key} needs the object to have a name")))?;
+        let names: Vec<String> = value
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect();
+        if light {
+            links.link_light(name, include, &names);
+        } else {
+            links.link_object(name, include, &names);
+        }
+        listed.extend(names);
+    }
+    Ok(listed)
+}
+
+// The render layer the object is in, see `Camera::with_layer`.
+fn take_group(directive: &mut Directive) -> Result<Option<String>> {
+    let group = directive.take("group");
+    if group.as_deref() == Some(DEFAULT_GROUP) {
+        return Err(Error::parse(
+            directive.line,
+            format!("the group '{DEFAULT_GROUP}' is the one of the objects without a group"),
+        ));
+    }
+    Ok(group)
+}
+
+// Placement of an object, applied in this order:
+//
+//   scale=2 (or 1,2,1)  rotate=0,90,0 (degrees around X, then Y, then Z)  translate=0,1,0
+fn parse_transform(directive: &mut Directive) -> Result<Transform> {
+    Ok(parse_placement(directive)?.transform())
+}
+
+fn parse_placement(directive: &mut Directive) -> Result<Placement> {
+    let scale = directive.take_vec3_or("scale", Vec3::new(1.0, 1.0, 1.0))?;
+    let rotate = directive.take_vec3_or("rotate", Vec3::ZERO)?;
+    let translate = directive.take_vec3_or("translate", Vec3::ZERO)?;
+    if scale.x == 0.0 || scale.y == 0.0 || scale.z == 0.0 {
+        return Err(Error::parse(directive.line, "scale must not be zero"));
+    }
+    Ok(Placement {
+        scale,
+        rotate,
+        translate,
+    })
+}
+
+// Camera settings keyframes can change, the ones of the image stay.
+const CAMERA_KEYS: &[&str] = &["look_from", "look_at", "vup", "vfov", "defocus_angle", "focus_dist"];
+
+// Values of an object at its keyframes, e.g.
+//
+//   sphere ball center=0,1,0 radius=1 material=red
+//   keyframe ball frame=1
+//   keyframe ball frame=24 center=0,3,0
+//   keyframe ball frame=48
+//
+// A keyframe sets some of the `keys` of the object directive at a frame, the ones it leaves out keep the values of
+// the object. `parse` reads the value from the object directive changed that way.
+fn keyframed<T: Interpolate>(
+    object: &Directive,
+    keyframes: &[(Directive, Arc<Origin>)],
+    keys: &[&str],
+    parse: impl Fn(&mut Directive) -> Result<T>,
+) -> Result<Keyframes<T>> {
+    let mut values: Vec<(Float, T)> = vec![];
+    for (keyframe, origin) in keyframes {
+        let value = (|| {
+            let mut keyframe = keyframe.clone();
+            let frame: Float = keyframe
+                .take_parsed("frame")?
+                .ok_or_else(|| Error::parse(keyframe.line, "keyframe is missing 'frame'"))?;
+            if values.iter().any(|(other, _)| *other == frame) {
+                return Err(Error::parse(
+                    keyframe.line,
+                    format!("duplicate keyframe at frame {frame}"),
+                ));
+            }
+            let mut changed = object.clone();
+            changed.line = keyframe.line;
+            for key in keys {
+                if let Some(value) = keyframe.take(key) {
+                    changed.set(key, &value);
+                }
+            }
+            keyframe.finish()?;
+            Ok((frame, parse(&mut changed)?))
+        })();
+        values.push(value.map_err(|err| origin.locate(err))?);
+    }
+    Ok(Keyframes::new(values))
+}
+
+// Place a shared object with `transform`, or move it through the `placements` if there are keyframes for it. They
+// replace the placement of the directive, the one of the file it is in (`origin`) still applies.
+fn place(
+    object: Arc<dyn Hittable>,
+    transform: Transform,
+    placements: Keyframes<Placement>,
+    origin: Transform,
+) -> Box<dyn Hittable> {
+    if placements.is_empty() {
+        Box::new(Instance::new(object, transform))
+    } else {
+        Box::new(Animated::new(object, placements, origin))
+    }
+}
+
+// Triangle mesh loaded from an OBJ, PLY or STL file, e.g.
+//
+//   mesh lamp file=models/lamp.obj material=plastic scale=0.5 translate=0,1,0
+//
+// Faces of OBJ files assigned to a material with `usemtl` use the scene material (or preset) of the same name, the
+// others use the `material` of the directive. PLY and STL meshes use the `material` throughout, PLY meshes are shaded
+// smoothly if the file has vertex normals and tinted by its vertex colors. The mesh is placed with the transformation
+// of the directive, named meshes can be placed again with `instance mesh=lamp ...`.
+// Most levels of subdivision of a mesh, 4096 times the faces of the cage.
+const MAX_SUBDIVISION: u32 = 6;
+
+fn parse_mesh(
+    directive: &mut Directive,
+    base: &Path,
+    accelerator: Accelerator,
+    materials: &mut HashMap<String, Arc<dyn Material>>,
+    replacement: Option<Arc<dyn Material>>,
+) -> Result<TriangleMesh> {
+    let line = directive.line;
+    let path = base.join(directive.take_required("file")?);
+    // The material of an include replaces the ones of all faces
+    let replaced = replacement.is_some();
+    let default = match (replacement, directive.take("material")) {
+        (Some(material), _) => Some(material),
+        (None, Some(name)) => Some(
+            resolve_material(&name, line, materials)?
+                .ok_or_else(|| Error::parse(line, format!("unknown material '{name}'")))?,
+        ),
+        (None, None) => None,
+    };
+
+    // Levels of Catmull-Clark subdivision of the polygons of OBJ cages
+    let levels: u32 = directive.take_or("subdivide", 0)?;
+    if levels > MAX_SUBDIVISION {
+        return Err(Error::parse(
+            line,
+            format!("at most {MAX_SUBDIVISION} levels of subdivision, each has 4 times the faces"),
+        ));
+    }
+
+    let load_error = |err: Error| Error::parse(line, format!("{}: {err}", path.display()));
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    if let Some(format @ ("ply" | "stl")) = extension {
+        if levels > 0 {
+            return Err(Error::parse(line, "only OBJ meshes can be subdivided"));
+        }
+        let material =
+            default.ok_or_else(|| Error::parse(line, format!("{} meshes need a material", format.to_uppercase())))?;
+        let (vertices, triangles, normals, colors) = if format == "ply" {
+            let model = PlyModel::load(&path).map_err(load_error)?;
+            (model.vertices, model.triangles, model.normals, model.colors)
+        } else {
+            let model = StlModel::load(&path).map_err(load_error)?;
+            (model.vertices, model.triangles, vec![], vec![])
+        };
+        if triangles.is_empty() {
+            return Err(Error::parse(line, format!("{} has no faces", path.display())));
+        }
+        let mut mesh = TriangleMesh::new(vertices, triangles, material, accelerator);
+        if !normals.is_empty() {
+            mesh = mesh.with_normals(normals);
+        }
+        if !colors.is_empty() {
+            mesh = mesh.with_colors(colors);
+        }
+        return Ok(mesh);
+    }
+
+    let model = ObjModel::load(&path).map_err(load_error)?;
+    // Build the material table from the groups that are actually used
+    let mut table: Vec<Arc<dyn Material>> = vec![];
+    let mut group_to_table = vec![None; model.materials.len()];
+    let mut face_materials = Vec::with_capacity(model.face_materials.len());
+    let groups = match levels {
+        0 => &model.face_materials,
+        _ => &model.polygon_materials,
+    };
+    for &group in groups {
+        let index = match group_to_table[group] {
+            Some(index) => index,
+            None => {
+                let name = model.materials[group].as_deref().filter(|_| !replaced);
+                let material = match name {
+                    Some(name) => resolve_material(name, line, materials)?.or_else(|| default.clone()),
+                    None => default.clone(),
+                };
+                let material = material.ok_or_else(|| {
+                    Error::parse(
+                        line,
+                        format!("no material for faces of '{}'", name.unwrap_or("default group")),
+                    )
+                })?;
+                table.push(material);
+                group_to_table[group] = Some(table.len() - 1);
+                table.len() - 1
+            }
+        };
+        face_materials.push(index);
+    }
+    if table.is_empty() {
+        return Err(Error::parse(line, format!("{} has no faces", path.display())));
+    }
+
+    if levels == 0 {
+        let mesh = TriangleMesh::new(model.vertices, model.triangles, table[0].clone(), accelerator);
+        return Ok(mesh.with_face_materials(table, face_materials));
+    }
+    let cage = SubdivisionMesh {
+        face_groups: face_materials,
+        creases: model
+            .creases
+            .iter()
+            .map(|&([a, b], sharpness)| (subdivision::edge(a, b), sharpness))
+            .collect(),
+        corners: model.corners.into_iter().collect(),
+        ..SubdivisionMesh::new(model.vertices, model.polygons)
+    };
+    let surface = cage.refine(levels).tessellate();
+    let mesh = TriangleMesh::new(surface.vertices, surface.triangles, table[0].clone(), accelerator);
+    Ok(mesh
+        .with_normals(surface.normals)
+        .with_face_materials(table, surface.triangle_groups))
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn descriptions_round_trip_through_json() {
+        let source = "camera image_width=64 aspect_ratio=2 look_from=0,1,4 look_at=0,0,0\n\
+                      material ground type=lambertian albedo=0.5\n\
+                      material \"glass ball\" type=dielectric ir=1.5 # a comment\n\
+                      sphere center=0,-1000,0 radius=1000 material=ground\n\
+                      sphere ball center=0,1,0 radius=1 material=\"glass ball\"\n";
+        let description = SceneDescription::parse(source).unwrap();
+        let json = serde_json::to_string(&description).unwrap();
+        let read: SceneDescription = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, description);
+
+        let scene = Scene::from_description(read, Path::new(""), BvhSettings::default(), &[]).unwrap();
+        assert_eq!(scene.world.len(), 2);
+        assert_eq!(scene.camera.image_width, 64);
+        assert!(scene.names.objects.contains("ball"));
+
+        // Render settings go along with the description
+        let camera: CameraSettings = serde_json::from_str(&serde_json::to_string(&scene.camera).unwrap()).unwrap();
+        assert_eq!((camera.image_width, camera.aspect_ratio), (64, 2.0));
+    }
+}
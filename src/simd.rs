@@ -0,0 +1,207 @@
+use std::ops::{Add, BitAnd, BitOr, Mul, Sub};
+
+use crate::float::Float;
+
+// Portable SIMD math on N lanes of single precision floats. The operations are plain loops over fixed size arrays,
+// which the compiler turns into SSE/AVX instructions, so this needs neither nightly nor target specific code.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Floats<const N: usize>(pub [f32; N]);
+
+pub type F32x4 = Floats<4>;
+pub type F32x8 = Floats<8>;
+
+// Per-lane result of a comparison.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Mask<const N: usize>(pub [bool; N]);
+
+impl<const N: usize> Floats<N> {
+    pub fn splat(value: f32) -> Floats<N> {
+        Floats([value; N])
+    }
+
+    pub fn from_fn(f: impl FnMut(usize) -> f32) -> Floats<N> {
+        Floats(std::array::from_fn(f))
+    }
+
+    fn zip(self, other: Floats<N>, f: impl Fn(f32, f32) -> f32) -> Floats<N> {
+        Floats(std::array::from_fn(|i| f(self.0[i], other.0[i])))
+    }
+
+    // Lanes where either operand is NaN take the value of `other`, which maps to a single instruction (unlike
+    // `f32::min`) and lets callers decide which side wins
+    pub fn min(self, other: Floats<N>) -> Floats<N> {
+        self.zip(other, |a, b| if a < b { a } else { b })
+    }
+
+    pub fn max(self, other: Floats<N>) -> Floats<N> {
+        self.zip(other, |a, b| if a > b { a } else { b })
+    }
+
+    pub fn recip(self) -> Floats<N> {
+        Floats(self.0.map(|x| 1.0 / x))
+    }
+
+    pub fn le(self, other: Floats<N>) -> Mask<N> {
+        Mask(std::array::from_fn(|i| self.0[i] <= other.0[i]))
+    }
+
+    // Smallest lane among the ones where `mask` is set, infinity if there are none
+    pub fn min_where(self, mask: Mask<N>) -> f32 {
+        (0..N)
+            .filter(|&i| mask.0[i])
+            .fold(f32::INFINITY, |acc, i| acc.min(self.0[i]))
+    }
+}
+
+impl<const N: usize> Mask<N> {
+    // Mask with only the given lane set
+    pub fn single(lane: usize) -> Mask<N> {
+        Mask(std::array::from_fn(|i| i == lane))
+    }
+
+    pub fn any(self) -> bool {
+        self.0.iter().any(|&lane| lane)
+    }
+
+    pub fn all(self) -> bool {
+        self.0.iter().all(|&lane| lane)
+    }
+
+    // Indices of the set lanes
+    pub fn lanes(self) -> impl Iterator<Item = usize> {
+        (0..N).filter(move |&i| self.0[i])
+    }
+}
+
+impl<const N: usize> Add for Floats<N> {
+    type Output = Floats<N>;
+
+    fn add(self, other: Floats<N>) -> Floats<N> {
+        self.zip(other, |a, b| a + b)
+    }
+}
+
+impl<const N: usize> Sub for Floats<N> {
+    type Output = Floats<N>;
+
+    fn sub(self, other: Floats<N>) -> Floats<N> {
+        self.zip(other, |a, b| a - b)
+    }
+}
+
+impl<const N: usize> Mul for Floats<N> {
+    type Output = Floats<N>;
+
+    fn mul(self, other: Floats<N>) -> Floats<N> {
+        self.zip(other, |a, b| a * b)
+    }
+}
+
+impl<const N: usize> BitAnd for Mask<N> {
+    type Output = Mask<N>;
+
+    fn bitand(self, other: Mask<N>) -> Mask<N> {
+        Mask(std::array::from_fn(|i| self.0[i] & other.0[i]))
+    }
+}
+
+impl<const N: usize> BitOr for Mask<N> {
+    type Output = Mask<N>;
+
+    fn bitor(self, other: Mask<N>) -> Mask<N> {
+        Mask(std::array::from_fn(|i| self.0[i] | other.0[i]))
+    }
+}
+
+// N vectors stored as one lane array per component (structure of arrays).
+#[derive(Copy, Clone, Debug)]
+pub struct Vec3s<const N: usize> {
+    pub x: Floats<N>,
+    pub y: Floats<N>,
+    pub z: Floats<N>,
+}
+
+impl<const N: usize> Vec3s<N> {
+    // Same point or vector in all lanes
+    pub fn splat(v: impl Into<[Float; 3]>) -> Vec3s<N> {
+        let [x, y, z] = v.into();
+        Vec3s {
+            x: Floats::splat(x as f32),
+            y: Floats::splat(y as f32),
+            z: Floats::splat(z as f32),
+        }
+    }
+
+    pub fn from_fn<V: Into<[Float; 3]>>(mut f: impl FnMut(usize) -> V) -> Vec3s<N> {
+        let vectors: [[Float; 3]; N] = std::array::from_fn(|i| f(i).into());
+        Vec3s {
+            x: Floats::from_fn(|i| vectors[i][0] as f32),
+            y: Floats::from_fn(|i| vectors[i][1] as f32),
+            z: Floats::from_fn(|i| vectors[i][2] as f32),
+        }
+    }
+
+    pub fn axis(&self, axis: usize) -> Floats<N> {
+        match axis {
+            0 => self.x,
+            1 => self.y,
+            _ => self.z,
+        }
+    }
+
+    pub fn dot(a: Vec3s<N>, b: Vec3s<N>) -> Floats<N> {
+        a.x * b.x + a.y * b.y + a.z * b.z
+    }
+
+    pub fn cross(a: Vec3s<N>, b: Vec3s<N>) -> Vec3s<N> {
+        Vec3s {
+            x: a.y * b.z - a.z * b.y,
+            y: a.z * b.x - a.x * b.z,
+            z: a.x * b.y - a.y * b.x,
+        }
+    }
+
+    pub fn recip(self) -> Vec3s<N> {
+        Vec3s {
+            x: self.x.recip(),
+            y: self.y.recip(),
+            z: self.z.recip(),
+        }
+    }
+}
+
+impl<const N: usize> Add for Vec3s<N> {
+    type Output = Vec3s<N>;
+
+    fn add(self, other: Vec3s<N>) -> Vec3s<N> {
+        Vec3s {
+            x: self.x + other.x,
+            y: self.y + other.y,
+            z: self.z + other.z,
+        }
+    }
+}
+
+impl<const N: usize> Sub for Vec3s<N> {
+    type Output = Vec3s<N>;
+
+    fn sub(self, other: Vec3s<N>) -> Vec3s<N> {
+        Vec3s {
+            x: self.x - other.x,
+            y: self.y - other.y,
+            z: self.z - other.z,
+        }
+    }
+}
+
+impl<const N: usize> Mul<Floats<N>> for Vec3s<N> {
+    type Output = Vec3s<N>;
+
+    fn mul(self, scale: Floats<N>) -> Vec3s<N> {
+        Vec3s {
+            x: self.x * scale,
+            y: self.y * scale,
+            z: self.z * scale,
+        }
+    }
+}
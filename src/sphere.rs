@@ -1,21 +1,29 @@
-use std::ops::Range;
+use std::{ops::Range, sync::Arc};
+
+use crate::float::consts::PI;
 
 use crate::{
+    aabb::Aabb,
+    accel::{Accelerator, SpatialIndex},
+    arena::Arena,
+    float::Float,
     hittable::{Hit, Hittable},
     material::Material,
     range::Interval,
     ray::Ray,
+    stats::{self, Counter},
+    transform::Transform,
     vec3::{Point, Vec3},
 };
 
 pub struct Sphere {
     center: Point,
-    radius: f64,
-    material: Box<dyn Material>,
+    radius: Float,
+    material: Arc<dyn Material>,
 }
 
 impl Sphere {
-    pub fn new(center: Point, radius: f64, material: Box<dyn Material>) -> Sphere {
+    pub fn new(center: Point, radius: Float, material: Arc<dyn Material>) -> Sphere {
         Sphere {
             center,
             radius,
@@ -25,32 +33,137 @@ impl Sphere {
 }
 
 impl Hittable for Sphere {
-    fn hit(&self, ray: Ray, t_range: Range<f64>) -> Option<Hit> {
-        // Define coefficients of a quadratic equation for `t` in order to
-        // determine ray-sphere intersection points
-        let oc = ray.origin - self.center;
-        let a = ray.direction.length_squared();
-        let half_b = Vec3::dot(oc, ray.direction);
-        let c = oc.length_squared() - self.radius * self.radius;
-
-        let discriminant = half_b * half_b - a * c;
-        if discriminant < 0.0 {
-            return None;
-        };
-        let sqrtd = discriminant.sqrt();
+    fn hit(&self, ray: Ray, t_range: Range<Float>) -> Option<Hit<'_>> {
+        let t = hit_sphere(self.center, self.radius, ray, &t_range)?;
+        Some(sphere_hit(self.center, self.radius, self.material.as_ref(), ray, t))
+    }
 
-        // Find the nearest root that lies in the acceptable range
-        let mut root = (-half_b - sqrtd) / a;
-        if !t_range.surrounds(root) {
-            root = (-half_b + sqrtd) / a;
-            if !t_range.surrounds(root) {
-                return None;
-            }
+    fn bounding_box(&self) -> Aabb {
+        sphere_bounds(self.center, self.radius)
+    }
+
+    fn flatten(&self, transform: &Transform, arena: &mut Arena) -> bool {
+        // A transformed sphere is an ellipsoid in general, spheres aren't instanced anyway
+        if !transform.is_identity() {
+            return false;
+        }
+        arena.add_sphere(self.center, self.radius, &self.material);
+        true
+    }
+}
+
+// Spheres stored as arrays of their centers, radii and materials (structure of arrays) with an acceleration structure
+// of their own. The spheres are intersected directly instead of through `dyn Hittable` like the objects of a
+// `HittableList`, so scenes made of spheres pay no virtual call per object.
+pub struct SphereList {
+    centers: Vec<Point>,
+    radii: Vec<Float>,
+    materials: Vec<Arc<dyn Material>>,
+    index: SpatialIndex,
+}
+
+impl SphereList {
+    pub fn new(spheres: Vec<Sphere>, accelerator: Accelerator) -> SphereList {
+        let bounds: Vec<Aabb> = spheres.iter().map(Sphere::bounding_box).collect();
+        SphereList {
+            centers: spheres.iter().map(|sphere| sphere.center).collect(),
+            radii: spheres.iter().map(|sphere| sphere.radius).collect(),
+            materials: spheres.into_iter().map(|sphere| sphere.material).collect(),
+            index: SpatialIndex::new(&bounds, accelerator),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.centers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.centers.is_empty()
+    }
+}
+
+impl Hittable for SphereList {
+    fn hit(&self, ray: Ray, t_range: Range<Float>) -> Option<Hit<'_>> {
+        let mut closest: Option<(usize, Float)> = None;
+        self.index.traverse(ray, t_range, |i, t_range| {
+            let t = hit_sphere(self.centers[i], self.radii[i], ray, &t_range)?;
+            closest = Some((i, t));
+            Some(t)
+        });
+        let (i, t) = closest?;
+        Some(sphere_hit(
+            self.centers[i],
+            self.radii[i],
+            self.materials[i].as_ref(),
+            ray,
+            t,
+        ))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.index.bounds()
+    }
+
+    fn flatten(&self, transform: &Transform, arena: &mut Arena) -> bool {
+        if !transform.is_identity() {
+            return false;
+        }
+        for (i, material) in self.materials.iter().enumerate() {
+            arena.add_sphere(self.centers[i], self.radii[i], material);
         }
+        true
+    }
+
+    fn hit_any(&self, ray: Ray, t_range: Range<Float>) -> bool {
+        self.index.traverse_any(ray, t_range, |i, t_range| {
+            hit_sphere(self.centers[i], self.radii[i], ray, &t_range).is_some()
+        })
+    }
+}
+
+// Distance along the ray to its nearest intersection with the sphere within `t_range`.
+pub(crate) fn hit_sphere(center: Point, radius: Float, ray: Ray, t_range: &Range<Float>) -> Option<Float> {
+    stats::count(Counter::SphereTests, 1);
+    // Define coefficients of a quadratic equation for `t` in order to
+    // determine ray-sphere intersection points
+    let oc = ray.origin - center;
+    let a = ray.direction.length_squared();
+    let half_b = Vec3::dot(oc, ray.direction);
+    let c = oc.length_squared() - radius * radius;
 
-        let t = root;
-        let hit_point = ray.at(t);
-        let outward_normal = (hit_point - self.center) / self.radius;
-        Some(Hit::new(ray, t, outward_normal, self.material.as_ref()))
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    };
+    let sqrtd = discriminant.sqrt();
+
+    // Find the nearest root that lies in the acceptable range
+    let mut root = (-half_b - sqrtd) / a;
+    if !t_range.surrounds(root) {
+        root = (-half_b + sqrtd) / a;
+        if !t_range.surrounds(root) {
+            return None;
+        }
     }
+    Some(root)
+}
+
+pub(crate) fn sphere_hit(center: Point, radius: Float, material: &dyn Material, ray: Ray, t: Float) -> Hit<'_> {
+    let hit_point = ray.at(t);
+    let outward_normal = (hit_point - center) / radius;
+    Hit::new(ray, t, outward_normal, material).with_uv(sphere_uv((hit_point - center) / radius.abs()))
+}
+
+// Longitude and latitude of a point on the unit sphere around the origin, u from 0 to 1 around the y axis starting at
+// -x, v from 0 at the bottom to 1 at the top.
+fn sphere_uv(point: Vec3) -> [Float; 2] {
+    let theta = (-point.y).clamp(-1.0, 1.0).acos();
+    let phi = (-point.z).atan2(point.x) + PI;
+    [phi / (2.0 * PI), theta / PI]
+}
+
+pub(crate) fn sphere_bounds(center: Point, radius: Float) -> Aabb {
+    // Hollow spheres have a negative radius
+    let r = Vec3::new(radius.abs(), radius.abs(), radius.abs());
+    Aabb::new(center - r, center + r)
 }
@@ -0,0 +1,121 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    ops::AddAssign,
+};
+
+// Work done by a render: rays traced, acceleration structure nodes visited and primitives intersected. The numbers
+// only depend on the scene, the settings and the random sequence, not on the machine, which makes them useful for
+// telling whether a change made the tracer do more work or just do it slower.
+//
+// Counting costs time in the innermost loops, so the counters are only kept in builds with the `stats` feature and
+// stay zero otherwise. Every thread counts for itself, the counts of worker threads are added up.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Stats {
+    pub primary_rays: u64,   // camera rays
+    pub secondary_rays: u64, // rays of bounces after the first hit
+    pub shadow_rays: u64,    // occlusion queries, which only ask whether anything is hit
+    pub node_visits: u64,    // nodes of BVHs and kd-trees visited, a node visited by a ray packet counts once
+    pub triangle_tests: u64,
+    pub sphere_tests: u64,
+}
+
+#[derive(Copy, Clone)]
+pub enum Counter {
+    PrimaryRays,
+    SecondaryRays,
+    ShadowRays,
+    NodeVisits,
+    TriangleTests,
+    SphereTests,
+}
+
+#[cfg(feature = "stats")]
+thread_local! {
+    static COUNTERS: [std::cell::Cell<u64>; 6] = const { [const { std::cell::Cell::new(0) }; 6] };
+}
+
+// Add `n` to a counter of the current thread.
+#[inline(always)]
+pub fn count(counter: Counter, n: u64) {
+    #[cfg(feature = "stats")]
+    COUNTERS.with(|counters| {
+        let counter = &counters[counter as usize];
+        counter.set(counter.get() + n);
+    });
+    #[cfg(not(feature = "stats"))]
+    let _ = (counter, n);
+}
+
+// Counts of the current thread since the last call.
+pub fn take() -> Stats {
+    #[cfg(feature = "stats")]
+    return COUNTERS.with(|counters| {
+        let [primary_rays, secondary_rays, shadow_rays, node_visits, triangle_tests, sphere_tests] =
+            [0, 1, 2, 3, 4, 5].map(|index| counters[index].replace(0));
+        Stats {
+            primary_rays,
+            secondary_rays,
+            shadow_rays,
+            node_visits,
+            triangle_tests,
+            sphere_tests,
+        }
+    });
+    #[cfg(not(feature = "stats"))]
+    Stats::default()
+}
+
+// Counts of the current thread since the last call to `take`, without resetting them.
+pub fn current() -> Stats {
+    #[cfg(feature = "stats")]
+    return COUNTERS.with(|counters| {
+        let [primary_rays, secondary_rays, shadow_rays, node_visits, triangle_tests, sphere_tests] =
+            [0, 1, 2, 3, 4, 5].map(|index| counters[index].get());
+        Stats {
+            primary_rays,
+            secondary_rays,
+            shadow_rays,
+            node_visits,
+            triangle_tests,
+            sphere_tests,
+        }
+    });
+    #[cfg(not(feature = "stats"))]
+    Stats::default()
+}
+
+impl Stats {
+    pub fn rays(&self) -> u64 {
+        self.primary_rays + self.secondary_rays + self.shadow_rays
+    }
+}
+
+impl AddAssign for Stats {
+    fn add_assign(&mut self, other: Stats) {
+        self.primary_rays += other.primary_rays;
+        self.secondary_rays += other.secondary_rays;
+        self.shadow_rays += other.shadow_rays;
+        self.node_visits += other.node_visits;
+        self.triangle_tests += other.triangle_tests;
+        self.sphere_tests += other.sphere_tests;
+    }
+}
+
+// Table of the counts, with the averages per ray for the traversal counts.
+impl Display for Stats {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let per_ray = |count: u64| count as f64 / self.rays().max(1) as f64;
+        writeln!(f, "Render statistics:")?;
+        writeln!(f, "  Primary rays    {:>16}", self.primary_rays)?;
+        writeln!(f, "  Secondary rays  {:>16}", self.secondary_rays)?;
+        writeln!(f, "  Shadow rays     {:>16}", self.shadow_rays)?;
+        for (label, count) in [
+            ("Node visits", self.node_visits),
+            ("Triangle tests", self.triangle_tests),
+            ("Sphere tests", self.sphere_tests),
+        ] {
+            writeln!(f, "  {label:<16}{count:>16} ({:.1} per ray)", per_ray(count))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,69 @@
+use std::str::FromStr;
+
+use crate::float::Float;
+
+// Stereoscopic rendering for VR headsets: the image holds a view for each eye, the left one on the left or on top.
+// Every camera ray starts from a point a half interocular distance to the side of the camera center, to the left or
+// to the right of the direction it goes in. That makes an omni-directional stereo (ODS) panorama with the
+// equirectangular projection, whose rays all around see with the offset they need, and parallel eyes with the
+// perspective one. The offset shrinks towards straight up and down, where the eyes can't tell left from right. An
+// anaglyph needs no headset, only red and cyan glasses: the image has the size of one view and takes its red from the
+// left eye and its green and blue from the right one.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Stereo {
+    #[default]
+    Mono,
+    SideBySide,
+    TopBottom,
+    Anaglyph,
+}
+
+impl FromStr for Stereo {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Stereo, String> {
+        match s {
+            "mono" | "none" => Ok(Stereo::Mono),
+            "side_by_side" | "sbs" => Ok(Stereo::SideBySide),
+            "top_bottom" | "over_under" => Ok(Stereo::TopBottom),
+            "anaglyph" => Ok(Stereo::Anaglyph),
+            _ => Err(format!(
+                "unknown stereo layout '{s}', expected 'mono', 'side_by_side', 'top_bottom' or 'anaglyph'"
+            )),
+        }
+    }
+}
+
+impl Stereo {
+    pub fn name(self) -> &'static str {
+        match self {
+            Stereo::Mono => "mono",
+            Stereo::SideBySide => "side_by_side",
+            Stereo::TopBottom => "top_bottom",
+            Stereo::Anaglyph => "anaglyph",
+        }
+    }
+
+    // Size of the whole image holding the views of both eyes, each `width` by `height` pixels.
+    pub fn image_size(self, width: i32, height: i32) -> [i32; 2] {
+        match self {
+            Stereo::Mono | Stereo::Anaglyph => [width, height],
+            Stereo::SideBySide => [2 * width, height],
+            Stereo::TopBottom => [width, 2 * height],
+        }
+    }
+
+    // The eye that sees the pixel at x, y of the whole image, -1 for the left one, 1 for the right one and 0 without
+    // stereo, and the position of the pixel in the view of that eye, `width` by `height` pixels. The views of an
+    // anaglyph are rendered apart, as a side-by-side image.
+    pub fn eye(self, [x, y]: [i32; 2], width: i32, height: i32) -> (Float, [i32; 2]) {
+        match self {
+            Stereo::Mono | Stereo::Anaglyph => (0.0, [x, y]),
+            Stereo::SideBySide if x < width => (-1.0, [x, y]),
+            Stereo::SideBySide => (1.0, [x - width, y]),
+            Stereo::TopBottom if y < height => (-1.0, [x, y]),
+            Stereo::TopBottom => (1.0, [x, y - height]),
+        }
+    }
+}
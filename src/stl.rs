@@ -0,0 +1,108 @@
+use std::{collections::HashMap, fs, io, path::Path};
+
+use crate::{
+    error::{Error, Result},
+    float::Float,
+    vec3::Point,
+};
+
+// Geometry of an STL file, the format of 3D printing, in ASCII or binary. STL stores every triangle with its own
+// corners, which are welded where they share a position. The facet normals of the file are ignored: exporters often
+// leave them zero or point them the wrong way, so the triangles face the side their corners go around
+// counter-clockwise, as the format prescribes.
+pub struct StlModel {
+    pub vertices: Vec<Point>,
+    pub triangles: Vec<[usize; 3]>,
+}
+
+impl StlModel {
+    pub fn load(path: &Path) -> Result<StlModel> {
+        StlModel::parse(&fs::read(path)?)
+    }
+
+    pub fn parse(data: &[u8]) -> Result<StlModel> {
+        // Binary files may start with "solid" as well, their size tells them apart
+        let binary = match data.get(80..84) {
+            Some(count) => {
+                let count = u32::from_le_bytes(count.try_into().expect("4 bytes")) as usize;
+                data.len() - 84 == count.saturating_mul(50) || !data.starts_with(b"solid")
+            }
+            None => !data.starts_with(b"solid"),
+        };
+        let mut builder = Builder::default();
+        if binary {
+            let invalid = |message: &str| Error::Io(io::Error::new(io::ErrorKind::InvalidData, message.to_string()));
+            let count = data.get(80..84).ok_or_else(|| invalid("truncated STL header"))?;
+            let count = u32::from_le_bytes(count.try_into().expect("4 bytes")) as usize;
+            let facets = data
+                .get(84..)
+                .and_then(|facets| facets.get(..count.saturating_mul(50)))
+                .ok_or_else(|| invalid("truncated STL file"))?;
+            for facet in facets.chunks_exact(50) {
+                // A normal, three corners and two bytes of attributes
+                let value = |index: usize| {
+                    let bytes = &facet[12 + index * 4..16 + index * 4];
+                    f32::from_le_bytes(bytes.try_into().expect("4 bytes"))
+                };
+                builder.triangle(
+                    [0, 1, 2].map(|corner| [value(corner * 3), value(corner * 3 + 1), value(corner * 3 + 2)]),
+                );
+            }
+        } else {
+            let text = std::str::from_utf8(data).map_err(|_| Error::parse(1, "invalid ASCII STL file"))?;
+            let mut corners = vec![];
+            for (index, text) in text.lines().enumerate() {
+                let line = index + 1;
+                let mut tokens = text.split_whitespace();
+                match tokens.next() {
+                    Some("vertex") => {
+                        let coordinates = tokens
+                            .map(|t| t.parse::<f32>())
+                            .collect::<std::result::Result<Vec<_>, _>>();
+                        match coordinates.as_deref() {
+                            Ok(&[x, y, z]) => corners.push([x, y, z]),
+                            _ => return Err(Error::parse(line, "invalid vertex")),
+                        }
+                    }
+                    Some("endloop") => {
+                        if corners.len() != 3 {
+                            return Err(Error::parse(line, "facets need 3 vertices"));
+                        }
+                        builder.triangle([corners[0], corners[1], corners[2]]);
+                        corners.clear();
+                    }
+                    Some("solid" | "facet" | "outer" | "endfacet" | "endsolid") | None => {}
+                    Some(keyword) => return Err(Error::parse(line, format!("unknown keyword '{keyword}'"))),
+                }
+            }
+        }
+        Ok(StlModel {
+            vertices: builder.vertices,
+            triangles: builder.triangles,
+        })
+    }
+}
+
+#[derive(Default)]
+struct Builder {
+    vertices: Vec<Point>,
+    triangles: Vec<[usize; 3]>,
+    indices: HashMap<[u32; 3], usize>, // bits of the coordinates of every vertex
+}
+
+impl Builder {
+    fn triangle(&mut self, corners: [[f32; 3]; 3]) {
+        let triangle = corners.map(|[x, y, z]| {
+            // Adding zero turns -0.0 into 0.0, so both weld
+            let key = [x + 0.0, y + 0.0, z + 0.0].map(f32::to_bits);
+            *self.indices.entry(key).or_insert_with(|| {
+                self.vertices.push(Point::new(x as Float, y as Float, z as Float));
+                self.vertices.len() - 1
+            })
+        });
+        // Slivers left over from the export have no area to hit
+        if triangle[0] != triangle[1] && triangle[1] != triangle[2] && triangle[2] != triangle[0] {
+            self.triangles.push(triangle);
+        }
+    }
+}
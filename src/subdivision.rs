@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+
+use crate::{
+    float::Float,
+    vec3::{Point, Vec3},
+};
+
+// Polygon mesh refined by Catmull-Clark subdivision, for cages modelled to be rendered smooth. Every level splits each
+// face into a quad for every corner and moves the vertices towards the smooth limit surface. Edges tagged with a
+// sharpness stay creased for that many levels, softening into the surface after them (fractional sharpness blends
+// the sharp and smooth rules), and vertices tagged as corners stay pointed the same way. Boundary edges are kept as
+// creases that don't soften.
+pub struct SubdivisionMesh {
+    pub vertices: Vec<Point>,
+    pub faces: Vec<Vec<usize>>, // vertex indices of polygons, counter-clockwise when looking at the front
+    pub face_groups: Vec<usize>, // of every face, passed on to the faces it is split into, e.g. its material
+    pub creases: HashMap<[usize; 2], Float>, // sharpness of edges by their vertices, the lower index first
+    pub corners: HashMap<usize, Float>, // sharpness of vertices
+}
+
+// Triangles of a subdivided mesh with vertex normals for smooth shading, split along creases so that they stay sharp.
+pub struct Tessellation {
+    pub vertices: Vec<Point>,
+    pub triangles: Vec<[usize; 3]>,
+    pub normals: Vec<Vec3>,
+    pub triangle_groups: Vec<usize>, // the group of the face of every triangle
+}
+
+// Key of the edge between two vertices.
+pub fn edge(a: usize, b: usize) -> [usize; 2] {
+    [a.min(b), a.max(b)]
+}
+
+impl SubdivisionMesh {
+    pub fn new(vertices: Vec<Point>, faces: Vec<Vec<usize>>) -> SubdivisionMesh {
+        assert!(
+            faces.iter().flatten().all(|&index| index < vertices.len()),
+            "face refers to a missing vertex"
+        );
+        SubdivisionMesh {
+            face_groups: vec![0; faces.len()],
+            vertices,
+            faces,
+            creases: HashMap::new(),
+            corners: HashMap::new(),
+        }
+    }
+
+    // Subdivide `levels` times, each multiplying the number of faces by about 4.
+    pub fn refine(self, levels: u32) -> SubdivisionMesh {
+        (0..levels).fold(self, |mesh, _| mesh.subdivide())
+    }
+
+    // One level of subdivision. The new vertices are the moved old ones, then one on every edge, then one in the
+    // middle of every face.
+    pub fn subdivide(&self) -> SubdivisionMesh {
+        let vertices: Vec<Vec3> = self.vertices.iter().map(|vertex| vertex.to_vec()).collect();
+        let topology = Topology::new(self);
+        let face_points: Vec<Vec3> = self
+            .faces
+            .iter()
+            .map(|face| face.iter().fold(Vec3::ZERO, |sum, &index| sum + vertices[index]) / face.len() as Float)
+            .collect();
+
+        let sharpness = |edge: usize| match topology.edge_faces[edge].len() {
+            2 => self.creases.get(&topology.edges[edge]).copied().unwrap_or(0.0),
+            _ => Float::INFINITY,
+        };
+        let edge_points = topology.edges.iter().enumerate().map(|(index, &[a, b])| {
+            let middle = (vertices[a] + vertices[b]) / 2.0;
+            match topology.edge_faces[index][..] {
+                [left, right] => {
+                    let smooth = (vertices[a] + vertices[b] + face_points[left] + face_points[right]) / 4.0;
+                    Vec3::lerp(smooth, middle, sharpness(index).min(1.0))
+                }
+                _ => middle,
+            }
+        });
+
+        let vertex_points = (0..vertices.len()).map(|vertex| {
+            let point = vertices[vertex];
+            let edges = &topology.vertex_edges[vertex];
+            let faces = &topology.vertex_faces[vertex];
+            let valence = edges.len();
+            if faces.is_empty() {
+                // Loose vertices stay where they are
+                return point;
+            }
+            let smooth = match valence {
+                0..=2 => point,
+                _ => {
+                    let face_average =
+                        faces.iter().fold(Vec3::ZERO, |sum, &face| sum + face_points[face]) / faces.len() as Float;
+                    let edge_average = edges.iter().fold(Vec3::ZERO, |sum, &edge| {
+                        let [a, b] = topology.edges[edge];
+                        sum + (vertices[a] + vertices[b]) / 2.0
+                    }) / valence as Float;
+                    (face_average + edge_average * 2.0 + point * (valence as Float - 3.0)) / valence as Float
+                }
+            };
+            let sharp: Vec<usize> = edges.iter().copied().filter(|&edge| sharpness(edge) > 0.0).collect();
+            let corner = self.corners.get(&vertex).copied().unwrap_or(0.0);
+            // Sharp vertices are blended with the smooth rule by how sharp they are, up to 1
+            let blend = sharp.iter().map(|&edge| sharpness(edge).min(1.0)).sum::<Float>() / sharp.len().max(1) as Float;
+            if corner > 0.0 {
+                Vec3::lerp(smooth, point, corner.min(1.0))
+            } else {
+                match sharp[..] {
+                    [] | [_] => smooth,
+                    [first, second] => {
+                        let other = |edge: usize| {
+                            let [a, b] = topology.edges[edge];
+                            vertices[if a == vertex { b } else { a }]
+                        };
+                        let crease = (point * 6.0 + other(first) + other(second)) / 8.0;
+                        Vec3::lerp(smooth, crease, blend)
+                    }
+                    _ => Vec3::lerp(smooth, point, blend),
+                }
+            }
+        });
+
+        let edge_start = vertices.len();
+        let face_start = edge_start + topology.edges.len();
+        let points: Vec<Point> = vertex_points
+            .chain(edge_points)
+            .chain(face_points.iter().copied())
+            .map(|point| point.to_point())
+            .collect();
+        let mut faces = Vec::with_capacity(self.faces.iter().map(Vec::len).sum());
+        let mut face_groups = Vec::with_capacity(faces.capacity());
+        for (index, face) in self.faces.iter().enumerate() {
+            let count = face.len();
+            for corner in 0..count {
+                let (before, vertex, after) = (
+                    face[(corner + count - 1) % count],
+                    face[corner],
+                    face[(corner + 1) % count],
+                );
+                faces.push(vec![
+                    vertex,
+                    edge_start + topology.edge_index[&edge(vertex, after)],
+                    face_start + index,
+                    edge_start + topology.edge_index[&edge(before, vertex)],
+                ]);
+                face_groups.push(self.face_groups[index]);
+            }
+        }
+        // Creases lose a level of sharpness on the halves of their edges
+        let mut creases = HashMap::new();
+        for (&[a, b], &sharpness) in &self.creases {
+            if let Some(&index) = topology.edge_index.get(&[a, b]) {
+                if sharpness > 1.0 {
+                    let middle = edge_start + index;
+                    creases.insert(edge(a, middle), sharpness - 1.0);
+                    creases.insert(edge(middle, b), sharpness - 1.0);
+                }
+            }
+        }
+        let corners = self
+            .corners
+            .iter()
+            .filter(|(_, &sharpness)| sharpness > 1.0)
+            .map(|(&vertex, &sharpness)| (vertex, sharpness - 1.0))
+            .collect();
+        SubdivisionMesh {
+            vertices: points,
+            faces,
+            face_groups,
+            creases,
+            corners,
+        }
+    }
+
+    // Triangles of the faces, with normals averaged over the faces around every vertex that aren't parted from each
+    // other by creases and boundaries.
+    pub fn tessellate(&self) -> Tessellation {
+        let topology = Topology::new(self);
+        // Corners of the faces around a vertex that are shaded together share their normal, found by joining the
+        // corners across every smooth edge
+        let starts: Vec<usize> = self
+            .faces
+            .iter()
+            .scan(0, |start, face| {
+                *start += face.len();
+                Some(*start - face.len())
+            })
+            .collect();
+        let corner = |face: usize, vertex: usize| {
+            starts[face]
+                + self.faces[face]
+                    .iter()
+                    .position(|&v| v == vertex)
+                    .expect("vertex of the face")
+        };
+        let mut parents: Vec<usize> = (0..self.faces.iter().map(Vec::len).sum()).collect();
+        fn root(parents: &mut [usize], mut corner: usize) -> usize {
+            while parents[corner] != corner {
+                parents[corner] = parents[parents[corner]];
+                corner = parents[corner];
+            }
+            corner
+        }
+        for (index, &[a, b]) in topology.edges.iter().enumerate() {
+            let smooth = !self.creases.get(&[a, b]).is_some_and(|&sharpness| sharpness > 0.0);
+            if let (&[left, right], true) = (&topology.edge_faces[index][..], smooth) {
+                for vertex in [a, b] {
+                    let (first, second) = (
+                        root(&mut parents, corner(left, vertex)),
+                        root(&mut parents, corner(right, vertex)),
+                    );
+                    parents[first] = second;
+                }
+            }
+        }
+
+        let mut tessellation = Tessellation {
+            vertices: vec![],
+            triangles: vec![],
+            normals: vec![],
+            triangle_groups: vec![],
+        };
+        let mut shared: HashMap<usize, usize> = HashMap::new();
+        for (index, face) in self.faces.iter().enumerate() {
+            // Newell's normal, as long as twice the area of the face
+            let normal = (0..face.len()).fold(Vec3::ZERO, |sum, corner| {
+                let (a, b) = (
+                    self.vertices[face[corner]],
+                    self.vertices[face[(corner + 1) % face.len()]],
+                );
+                sum + Vec3::cross(a.to_vec(), b.to_vec())
+            });
+            let output: Vec<usize> = face
+                .iter()
+                .enumerate()
+                .map(|(position, &vertex)| {
+                    let group = root(&mut parents, starts[index] + position);
+                    let output = *shared.entry(group).or_insert_with(|| {
+                        tessellation.vertices.push(self.vertices[vertex]);
+                        tessellation.normals.push(Vec3::ZERO);
+                        tessellation.vertices.len() - 1
+                    });
+                    tessellation.normals[output] += normal;
+                    output
+                })
+                .collect();
+            for corner in 1..face.len().saturating_sub(1) {
+                tessellation
+                    .triangles
+                    .push([output[0], output[corner], output[corner + 1]]);
+                tessellation.triangle_groups.push(self.face_groups[index]);
+            }
+        }
+        for normal in &mut tessellation.normals {
+            *normal = match normal.near_zero() {
+                true => Vec3::new(0.0, 1.0, 0.0),
+                false => normal.normalize(),
+            };
+        }
+        tessellation
+    }
+}
+
+// Edges of a mesh and what is around them and the vertices.
+struct Topology {
+    edges: Vec<[usize; 2]>,
+    edge_index: HashMap<[usize; 2], usize>,
+    edge_faces: Vec<Vec<usize>>,
+    vertex_edges: Vec<Vec<usize>>,
+    vertex_faces: Vec<Vec<usize>>,
+}
+
+impl Topology {
+    fn new(mesh: &SubdivisionMesh) -> Topology {
+        let mut topology = Topology {
+            edges: vec![],
+            edge_index: HashMap::new(),
+            edge_faces: vec![],
+            vertex_edges: vec![vec![]; mesh.vertices.len()],
+            vertex_faces: vec![vec![]; mesh.vertices.len()],
+        };
+        for (index, face) in mesh.faces.iter().enumerate() {
+            for (corner, &vertex) in face.iter().enumerate() {
+                topology.vertex_faces[vertex].push(index);
+                let key = edge(vertex, face[(corner + 1) % face.len()]);
+                let edge = match topology.edge_index.get(&key) {
+                    Some(&edge) => edge,
+                    None => {
+                        topology.edges.push(key);
+                        topology.edge_faces.push(vec![]);
+                        topology.vertex_edges[key[0]].push(topology.edges.len() - 1);
+                        topology.vertex_edges[key[1]].push(topology.edges.len() - 1);
+                        topology.edge_index.insert(key, topology.edges.len() - 1);
+                        topology.edges.len() - 1
+                    }
+                };
+                topology.edge_faces[edge].push(index);
+            }
+        }
+        topology
+    }
+}
@@ -0,0 +1,126 @@
+use std::str::FromStr;
+
+use crate::float::{
+    consts::{FRAC_1_SQRT_2, SQRT_2},
+    Float,
+};
+
+// How the octaves of noise of a terrain add up.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NoiseKind {
+    Fbm,    // fractional Brownian motion: rolling hills
+    Ridged, // ridged multifractal: sharp crests and smooth valleys, like eroded mountains
+}
+
+impl FromStr for NoiseKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<NoiseKind, String> {
+        match s {
+            "fbm" => Ok(NoiseKind::Fbm),
+            "ridged" => Ok(NoiseKind::Ridged),
+            _ => Err(format!("unknown noise '{s}', expected 'fbm' or 'ridged'")),
+        }
+    }
+}
+
+// Most octaves of a terrain. Every octave costs a noise evaluation per grid point, and after a few dozen doublings the
+// detail is finer than any grid anyway.
+pub const MAX_OCTAVES: u32 = 32;
+
+// Heights of a procedural terrain, sums of octaves of gradient noise, each `lacunarity` times the frequency and `gain`
+// times the amplitude of the one before. They are between about 0 and 1 and depend only on the position and the
+// seed, so terrains of the same noise next to each other join without a seam.
+#[derive(Copy, Clone, Debug)]
+pub struct TerrainNoise {
+    pub kind: NoiseKind,
+    pub octaves: u32,
+    pub frequency: Float, // of the first octave, in cycles per unit
+    pub lacunarity: Float,
+    pub gain: Float,
+    pub seed: u64,
+}
+
+impl Default for TerrainNoise {
+    fn default() -> TerrainNoise {
+        TerrainNoise {
+            kind: NoiseKind::Fbm,
+            octaves: 6,
+            frequency: 1.0,
+            lacunarity: 2.0,
+            gain: 0.5,
+            seed: 0,
+        }
+    }
+}
+
+impl TerrainNoise {
+    pub fn height(&self, x: Float, z: Float) -> Float {
+        let (mut sum, mut total) = (0.0, 0.0);
+        let (mut frequency, mut amplitude) = (self.frequency, 1.0);
+        // How much of the detail of an octave of ridged noise is kept, little in the valleys of the octave before
+        let mut weight: Float = 1.0;
+        for octave in 0..self.octaves {
+            // Every octave has noise of its own, so that their lattices don't line up
+            let seed = self.seed.wrapping_add(octave as u64);
+            let noise = gradient_noise(x * frequency, z * frequency, seed);
+            sum += amplitude
+                * match self.kind {
+                    NoiseKind::Fbm => 0.5 + 0.5 * noise,
+                    NoiseKind::Ridged => {
+                        let ridge = (1.0 - noise.abs()).powi(2) * weight;
+                        weight = (2.0 * ridge).clamp(0.0, 1.0);
+                        ridge
+                    }
+                };
+            total += amplitude;
+            frequency *= self.lacunarity;
+            amplitude *= self.gain;
+        }
+        if total > 0.0 {
+            sum / total
+        } else {
+            0.0
+        }
+    }
+}
+
+// Perlin's gradient noise in the plane, between about -1 and 1 and 0 at the points of the integer lattice, with
+// gradients of the lattice points picked by a hash of their coordinates and the seed.
+fn gradient_noise(x: Float, z: Float, seed: u64) -> Float {
+    let (x0, z0) = (x.floor(), z.floor());
+    let (fx, fz) = (x - x0, z - z0);
+    let (ix, iz) = (x0 as i64, z0 as i64);
+    let corner = |dx: i64, dz: i64| {
+        let hash = lattice_hash(ix + dx, iz + dz, seed);
+        // One of 8 directions, evenly around the circle
+        let (gx, gz) = match hash & 7 {
+            0 => (1.0, 0.0),
+            1 => (-1.0, 0.0),
+            2 => (0.0, 1.0),
+            3 => (0.0, -1.0),
+            4 => (FRAC_1_SQRT_2, FRAC_1_SQRT_2),
+            5 => (-FRAC_1_SQRT_2, FRAC_1_SQRT_2),
+            6 => (FRAC_1_SQRT_2, -FRAC_1_SQRT_2),
+            _ => (-FRAC_1_SQRT_2, -FRAC_1_SQRT_2),
+        };
+        gx * (fx - dx as Float) + gz * (fz - dz as Float)
+    };
+    let fade = |t: Float| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    let (u, v) = (fade(fx), fade(fz));
+    let top = corner(0, 0) + u * (corner(1, 0) - corner(0, 0));
+    let bottom = corner(0, 1) + u * (corner(1, 1) - corner(0, 1));
+    // The largest values of noise with unit gradients are about ±1/√2
+    (top + v * (bottom - top)) * SQRT_2
+}
+
+// Mix the coordinates of a lattice point and the seed into well distributed bits, with the finalizer of SplitMix64.
+fn lattice_hash(x: i64, z: i64, seed: u64) -> u64 {
+    let mut hash = seed
+        .wrapping_mul(0x9e3779b97f4a7c15)
+        .wrapping_add((x as u64).wrapping_mul(0xbf58476d1ce4e5b9))
+        .wrapping_add((z as u64).wrapping_mul(0x94d049bb133111eb));
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d049bb133111eb);
+    hash ^ (hash >> 31)
+}
@@ -0,0 +1,368 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    str::FromStr,
+};
+
+use crate::{
+    error::Result,
+    float::Float,
+    font::Font,
+    vec3::{Point, Vec3},
+};
+
+// Most the curves of the glyphs deviate from their straight segments, in parts of the size of the text.
+const TOLERANCE: Float = 1e-3;
+
+// Largest angle between neighbouring faces of the sides of the letters that is shaded smoothly, in degrees: the
+// segments of the curves are, the corners of the glyphs stay sharp.
+const SMOOTH_ANGLE: Float = 30.0;
+
+// Where the lines of a text are placed relative to the origin.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Align {
+    Left,   // start at the origin
+    Center, // centered on it
+    Right,  // end at it
+}
+
+impl FromStr for Align {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Align, String> {
+        match s {
+            "left" => Ok(Align::Left),
+            "center" => Ok(Align::Center),
+            "right" => Ok(Align::Right),
+            _ => Err(format!("unknown alignment '{s}', expected 'left', 'center' or 'right'")),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct TextStyle {
+    pub size: Float,  // of the em square of the font, capitals are about 0.7 of it high
+    pub depth: Float, // of the extrusion, 0 for flat letters
+    pub align: Align,
+    pub line_spacing: Float, // times the distance of the lines of the font
+}
+
+impl Default for TextStyle {
+    fn default() -> TextStyle {
+        TextStyle {
+            size: 1.0,
+            depth: 0.2,
+            align: Align::Left,
+            line_spacing: 1.0,
+        }
+    }
+}
+
+// Text in a font as geometry: the outlines of the glyphs triangulated into front faces at z 0 facing +z, extruded
+// back to the back faces at -depth and joined by the sides. The baseline of the first line is on the x axis and the
+// next lines go down from it. The glyphs follow each other by their advances and the kerning of the font, without
+// ligatures or the shaping of complex scripts.
+pub struct TextMesh {
+    pub vertices: Vec<Point>,
+    pub triangles: Vec<[usize; 3]>,
+    pub normals: Vec<Vec3>, // of the vertices, the sides shaded smoothly around the curves
+}
+
+impl TextMesh {
+    pub fn new(font: &Font, text: &str, style: &TextStyle) -> Result<TextMesh> {
+        let mut mesh = TextMesh {
+            vertices: vec![],
+            triangles: vec![],
+            normals: vec![],
+        };
+        let scale = style.size / font.units_per_em;
+        let mut shapes: HashMap<u16, Shape> = HashMap::new();
+        for (row, line) in text.split('\n').enumerate() {
+            let glyphs: Vec<u16> = line
+                .chars()
+                .filter(|c| !c.is_control())
+                .map(|c| font.glyph(c))
+                .collect();
+            let mut pens = Vec::with_capacity(glyphs.len());
+            let mut pen = 0.0;
+            for (index, &glyph) in glyphs.iter().enumerate() {
+                if index > 0 {
+                    pen += font.kerning(glyphs[index - 1], glyph);
+                }
+                pens.push(pen);
+                pen += font.advance(glyph);
+            }
+            let start = match style.align {
+                Align::Left => 0.0,
+                Align::Center => -pen / 2.0,
+                Align::Right => -pen,
+            };
+            let baseline = -(row as Float) * font.line_height * style.line_spacing;
+            for (&glyph, pen) in glyphs.iter().zip(pens) {
+                let shape = match shapes.entry(glyph) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => {
+                        entry.insert(Shape::new(font.outline(glyph, TOLERANCE * font.units_per_em)?))
+                    }
+                };
+                let place = |[x, y]: [Float; 2]| [(x + start + pen) * scale, (y + baseline) * scale];
+                mesh.add(shape, place, style.depth);
+            }
+        }
+        Ok(mesh)
+    }
+
+    // Add the faces and sides of a glyph, placed by `place`.
+    fn add(&mut self, shape: &Shape, place: impl Fn([Float; 2]) -> [Float; 2], depth: Float) {
+        let at = |point: [Float; 2], z: Float| {
+            let [x, y] = place(point);
+            Point::new(x, y, z)
+        };
+        for Face { polygon, triangles } in &shape.faces {
+            let front = self.vertices.len();
+            self.vertices.extend(polygon.iter().map(|&point| at(point, 0.0)));
+            self.normals.extend(polygon.iter().map(|_| Vec3::new(0.0, 0.0, 1.0)));
+            self.triangles
+                .extend(triangles.iter().map(|triangle| triangle.map(|index| front + index)));
+            if depth > 0.0 {
+                let back = self.vertices.len();
+                self.vertices.extend(polygon.iter().map(|&point| at(point, -depth)));
+                self.normals.extend(polygon.iter().map(|_| Vec3::new(0.0, 0.0, -1.0)));
+                self.triangles
+                    .extend(triangles.iter().map(|&[a, b, c]| [back + a, back + c, back + b]));
+            }
+        }
+        if depth <= 0.0 {
+            return;
+        }
+
+        let smooth = SMOOTH_ANGLE.to_radians().cos();
+        for contour in &shape.contours {
+            let points: Vec<Point> = contour.iter().map(|&point| at(point, 0.0)).collect();
+            // Outward normal of every edge: to the right of outer contours going around counter-clockwise and of
+            // holes going around clockwise
+            let normals: Vec<Vec3> = (0..points.len())
+                .map(|edge| {
+                    let along = points[(edge + 1) % points.len()] - points[edge];
+                    Vec3::new(along.y, -along.x, 0.0).normalize()
+                })
+                .collect();
+            let blend = |normal: Vec3, neighbour: Vec3| match Vec3::dot(normal, neighbour) > smooth {
+                true => (normal + neighbour).normalize(),
+                false => normal,
+            };
+            for edge in 0..points.len() {
+                let next = (edge + 1) % points.len();
+                let normal = normals[edge];
+                let (start, end) = (
+                    blend(normal, normals[(edge + points.len() - 1) % points.len()]),
+                    blend(normal, normals[next]),
+                );
+                let first = self.vertices.len();
+                let back = Vec3::new(0.0, 0.0, -depth);
+                self.vertices
+                    .extend([points[edge], points[edge] + back, points[next] + back, points[next]]);
+                self.normals.extend([start, start, end, end]);
+                self.triangles
+                    .extend([[first, first + 1, first + 2], [first, first + 2, first + 3]]);
+            }
+        }
+    }
+}
+
+// Outline of a glyph ready to be extruded: its contours with the outer ones going around counter-clockwise and the
+// holes clockwise, and its faces.
+struct Shape {
+    contours: Vec<Vec<[Float; 2]>>,
+    faces: Vec<Face>,
+}
+
+// Outer contour of a glyph with its holes joined in, and its triangles.
+struct Face {
+    polygon: Vec<[Float; 2]>,
+    triangles: Vec<[usize; 3]>,
+}
+
+impl Shape {
+    fn new(contours: Vec<Vec<[Float; 2]>>) -> Shape {
+        let mut contours: Vec<Vec<[Float; 2]>> = contours
+            .into_iter()
+            .map(|mut contour| {
+                contour.dedup();
+                while contour.len() > 1 && contour.first() == contour.last() {
+                    contour.pop();
+                }
+                contour
+            })
+            .filter(|contour| contour.len() >= 3 && area(contour) != 0.0)
+            .collect();
+
+        // Contours inside an odd number of others are holes, of the smallest of them one level up
+        let depths: Vec<usize> = (0..contours.len())
+            .map(|index| {
+                (0..contours.len())
+                    .filter(|&other| other != index && contains(&contours[other], contours[index][0]))
+                    .count()
+            })
+            .collect();
+        let hole: Vec<bool> = depths.iter().map(|depth| depth % 2 == 1).collect();
+        let parents: Vec<Option<usize>> = (0..contours.len())
+            .map(|index| match hole[index] {
+                true => (0..contours.len())
+                    .filter(|&other| {
+                        depths[other] + 1 == depths[index] && contains(&contours[other], contours[index][0])
+                    })
+                    .min_by(|&a, &b| area(&contours[a]).abs().total_cmp(&area(&contours[b]).abs())),
+                false => None,
+            })
+            .collect();
+        for (index, contour) in contours.iter_mut().enumerate() {
+            if (area(contour) > 0.0) == hole[index] {
+                contour.reverse();
+            }
+        }
+
+        let faces = (0..contours.len())
+            .filter(|&index| !hole[index])
+            .map(|outer| {
+                let mut holes: Vec<&Vec<[Float; 2]>> = (0..contours.len())
+                    .filter(|&index| parents[index] == Some(outer))
+                    .map(|index| &contours[index])
+                    .collect();
+                // Join the holes furthest right first, the ones left of them can join to their bridges
+                holes.sort_by(|a, b| rightmost(b).1[0].total_cmp(&rightmost(a).1[0]));
+                let mut polygon = contours[outer].clone();
+                for (index, hole) in holes.iter().enumerate() {
+                    polygon = bridge(&polygon, hole, &holes[index + 1..]);
+                }
+                let triangles = triangulate(&polygon);
+                Face { polygon, triangles }
+            })
+            .collect();
+        Shape { contours, faces }
+    }
+}
+
+// Twice the signed area of a polygon, positive when it goes around counter-clockwise.
+fn area(polygon: &[[Float; 2]]) -> Float {
+    (0..polygon.len())
+        .map(|index| {
+            let ([x0, y0], [x1, y1]) = (polygon[index], polygon[(index + 1) % polygon.len()]);
+            x0 * y1 - x1 * y0
+        })
+        .sum()
+}
+
+// Whether `point` is inside a polygon, by the number of its edges a ray to the right of it crosses.
+fn contains(polygon: &[[Float; 2]], [x, y]: [Float; 2]) -> bool {
+    let mut inside = false;
+    for index in 0..polygon.len() {
+        let ([x0, y0], [x1, y1]) = (polygon[index], polygon[(index + 1) % polygon.len()]);
+        if (y0 > y) != (y1 > y) && x < x0 + (y - y0) / (y1 - y0) * (x1 - x0) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+// Index and position of the vertex of a polygon furthest right.
+fn rightmost(polygon: &[[Float; 2]]) -> (usize, [Float; 2]) {
+    polygon
+        .iter()
+        .copied()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a[0].total_cmp(&b[0]))
+        .expect("contours have points")
+}
+
+// Twice the signed area of the triangle abc, positive when it goes around counter-clockwise.
+fn cross(a: [Float; 2], b: [Float; 2], c: [Float; 2]) -> Float {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+// Whether the segments ab and cd cross at a point inside both, not just touching.
+fn crosses(a: [Float; 2], b: [Float; 2], c: [Float; 2], d: [Float; 2]) -> bool {
+    cross(a, b, c) * cross(a, b, d) < 0.0 && cross(c, d, a) * cross(c, d, b) < 0.0
+}
+
+// Join a hole into a polygon around it by a bridge from its rightmost vertex to the closest vertex of the polygon
+// the bridge doesn't cross an edge from, going in along it, around the hole and back out. `others` are holes that
+// are still to be joined.
+fn bridge(polygon: &[[Float; 2]], hole: &[[Float; 2]], others: &[&Vec<[Float; 2]>]) -> Vec<[Float; 2]> {
+    let (start, point) = rightmost(hole);
+    let edges = |contour: &[[Float; 2]]| -> Vec<([Float; 2], [Float; 2])> {
+        (0..contour.len())
+            .map(|index| (contour[index], contour[(index + 1) % contour.len()]))
+            .collect()
+    };
+    let mut obstacles = edges(polygon);
+    obstacles.extend(edges(hole));
+    for other in others {
+        obstacles.extend(edges(other));
+    }
+    let distance = |[x, y]: [Float; 2]| (x - point[0]).powi(2) + (y - point[1]).powi(2);
+    let mut candidates: Vec<usize> = (0..polygon.len()).collect();
+    candidates.sort_by(|&a, &b| distance(polygon[a]).total_cmp(&distance(polygon[b])));
+    let visible = |&index: &usize| {
+        // The bridge has to leave the vertex on the inside of its corner, which tells the copies of a vertex on
+        // bridges already made apart
+        let (before, vertex, after) = (
+            polygon[(index + polygon.len() - 1) % polygon.len()],
+            polygon[index],
+            polygon[(index + 1) % polygon.len()],
+        );
+        let inside = match cross(before, vertex, after) >= 0.0 {
+            true => cross(before, vertex, point) > 0.0 && cross(vertex, after, point) > 0.0,
+            false => cross(before, vertex, point) > 0.0 || cross(vertex, after, point) > 0.0,
+        };
+        inside && !obstacles.iter().any(|&(a, b)| crosses(point, vertex, a, b))
+    };
+    let end = candidates.iter().copied().find(visible).unwrap_or(candidates[0]);
+
+    let mut joined = Vec::with_capacity(polygon.len() + hole.len() + 2);
+    joined.extend_from_slice(&polygon[..=end]);
+    joined.extend_from_slice(&hole[start..]);
+    joined.extend_from_slice(&hole[..=start]);
+    joined.extend_from_slice(&polygon[end..]);
+    joined
+}
+
+// Triangles of a simple polygon going around counter-clockwise, by clipping off its ears: corners whose triangle
+// has no other vertex in it.
+fn triangulate(polygon: &[[Float; 2]]) -> Vec<[usize; 3]> {
+    let mut remaining: Vec<usize> = (0..polygon.len()).collect();
+    let mut triangles = Vec::with_capacity(polygon.len().saturating_sub(2));
+    let mut corner = 0;
+    let mut misses = 0;
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        corner %= count;
+        let [a, b, c] = [(corner + count - 1) % count, corner, (corner + 1) % count].map(|index| remaining[index]);
+        let (pa, pb, pc) = (polygon[a], polygon[b], polygon[c]);
+        // Polygons that aren't simple, from fonts with overlapping contours, can run out of ears; corners that
+        // aren't reflex are clipped then, and the ones that are dropped
+        let stuck = misses >= count;
+        let ear = cross(pa, pb, pc) > 0.0
+            && remaining.iter().all(|&index| {
+                let point = polygon[index];
+                point == pa
+                    || point == pb
+                    || point == pc
+                    || !(cross(pa, pb, point) >= 0.0 && cross(pb, pc, point) >= 0.0 && cross(pc, pa, point) >= 0.0)
+            });
+        if ear || (stuck && cross(pa, pb, pc) >= 0.0) {
+            triangles.push([a, b, c]);
+            remaining.remove(corner);
+            misses = 0;
+        } else if stuck && misses >= 2 * count {
+            remaining.remove(corner);
+            misses = 0;
+        } else {
+            corner += 1;
+            misses += 1;
+        }
+    }
+    if let [a, b, c] = remaining[..] {
+        triangles.push([a, b, c]);
+    }
+    triangles
+}
@@ -0,0 +1,53 @@
+use std::io::{self, Write};
+
+// Baseline TIFF files of 16 bit RGB pixels as `output::write_image` writes them: little-endian, uncompressed, with
+// all pixels in a single strip.
+
+// Field types of the entries of the image file directory.
+const SHORT: u16 = 3;
+const LONG: u16 = 4;
+
+// Count of entries of the directory.
+const ENTRIES: usize = 10;
+
+// Write an image of `width` by `height` pixels, `samples` holding the red, green and blue values of every pixel in
+// scanline order.
+pub(crate) fn write(width: usize, height: usize, samples: &[u16], out: &mut dyn Write) -> io::Result<()> {
+    // The header, then the directory, the bits per sample it points to and the pixels
+    let directory = 8;
+    let bits = directory + 2 + 12 * ENTRIES + 4;
+    let strip = bits + 6;
+    let size = 6 * width * height;
+
+    let mut file = Vec::with_capacity(strip + size);
+    file.extend(b"II*\0");
+    file.extend((directory as u32).to_le_bytes());
+    file.extend((ENTRIES as u16).to_le_bytes());
+    // Tag, type, count and the value, or where the values are if they don't fit into 4 bytes, sorted by tag
+    let entries: [(u16, u16, u32, u32); ENTRIES] = [
+        (256, LONG, 1, width as u32),  // image width
+        (257, LONG, 1, height as u32), // image length
+        (258, SHORT, 3, bits as u32),  // bits per sample
+        (259, SHORT, 1, 1),            // no compression
+        (262, SHORT, 1, 2),            // RGB
+        (273, LONG, 1, strip as u32),  // strip offsets
+        (277, SHORT, 1, 3),            // samples per pixel
+        (278, LONG, 1, height as u32), // rows per strip
+        (279, LONG, 1, size as u32),   // strip byte counts
+        (284, SHORT, 1, 1),            // samples of a pixel together
+    ];
+    for (tag, kind, count, value) in entries {
+        file.extend(tag.to_le_bytes());
+        file.extend(kind.to_le_bytes());
+        file.extend(count.to_le_bytes());
+        // Single shorts are at the start of the 4 bytes of the value
+        match (kind, count) {
+            (SHORT, 1) => file.extend([(value as u16).to_le_bytes(), [0; 2]].concat()),
+            _ => file.extend(value.to_le_bytes()),
+        }
+    }
+    file.extend(0u32.to_le_bytes()); // no further directory
+    file.extend([16u16; 3].iter().flat_map(|bits| bits.to_le_bytes()));
+    file.extend(samples.iter().flat_map(|sample| sample.to_le_bytes()));
+    out.write_all(&file)
+}
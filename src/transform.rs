@@ -0,0 +1,176 @@
+use crate::{
+    aabb::Aabb,
+    float::Float,
+    util::degrees_to_radians,
+    vec3::{Point, Vec3},
+};
+
+type Matrix = [[Float; 4]; 3]; // rows of an affine 4x4 matrix, the last row is always 0,0,0,1
+
+// Affine transformation together with its inverse. Transformations built from simple parts compose the inverse
+// alongside, only `from_matrix` has to compute it.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transform {
+    matrix: Matrix,
+    inverse: Matrix,
+}
+
+const IDENTITY: Matrix = [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0]];
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        matrix: IDENTITY,
+        inverse: IDENTITY,
+    };
+
+    pub fn translation(offset: Vec3) -> Transform {
+        let matrix = |v: Vec3| [[1.0, 0.0, 0.0, v.x], [0.0, 1.0, 0.0, v.y], [0.0, 0.0, 1.0, v.z]];
+        Transform {
+            matrix: matrix(offset),
+            inverse: matrix(-offset),
+        }
+    }
+
+    pub fn scaling(factors: Vec3) -> Transform {
+        let matrix = |v: Vec3| [[v.x, 0.0, 0.0, 0.0], [0.0, v.y, 0.0, 0.0], [0.0, 0.0, v.z, 0.0]];
+        Transform {
+            matrix: matrix(factors),
+            inverse: matrix(Vec3::new(1.0 / factors.x, 1.0 / factors.y, 1.0 / factors.z)),
+        }
+    }
+
+    // Rotation by `degrees` around the X, Y or Z axis (0, 1 or 2), counter-clockwise when looking down the axis.
+    pub fn rotation(axis: usize, degrees: Float) -> Transform {
+        let (sin, cos) = degrees_to_radians(degrees).sin_cos();
+        let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+        let mut matrix = IDENTITY;
+        matrix[a][a] = cos;
+        matrix[a][b] = -sin;
+        matrix[b][a] = sin;
+        matrix[b][b] = cos;
+        // The inverse of a rotation is its transpose
+        let mut inverse = matrix;
+        (inverse[a][b], inverse[b][a]) = (matrix[b][a], matrix[a][b]);
+        Transform { matrix, inverse }
+    }
+
+    // Transformation with the given rows of an affine matrix, e.g. from a file. None if the matrix can't be inverted.
+    pub fn from_matrix(matrix: Matrix) -> Option<Transform> {
+        let m = &matrix;
+        let cofactor = |r0: usize, r1: usize, c0: usize, c1: usize| m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0];
+        // Inverse of the linear part from its adjugate, the translation is undone after it
+        let adjugate = [
+            [cofactor(1, 2, 1, 2), -cofactor(0, 2, 1, 2), cofactor(0, 1, 1, 2)],
+            [-cofactor(1, 2, 0, 2), cofactor(0, 2, 0, 2), -cofactor(0, 1, 0, 2)],
+            [cofactor(1, 2, 0, 1), -cofactor(0, 2, 0, 1), cofactor(0, 1, 0, 1)],
+        ];
+        let determinant = m[0][0] * adjugate[0][0] + m[0][1] * adjugate[1][0] + m[0][2] * adjugate[2][0];
+        if determinant == 0.0 || !determinant.is_finite() {
+            return None;
+        }
+        let mut inverse = [[0.0; 4]; 3];
+        for (row, inverse_row) in inverse.iter_mut().enumerate() {
+            for column in 0..3 {
+                inverse_row[column] = adjugate[row][column] / determinant;
+            }
+            inverse_row[3] = -(0..3).map(|k| inverse_row[k] * m[k][3]).sum::<Float>();
+        }
+        Some(Transform { matrix, inverse })
+    }
+
+    // Apply `self` first and `next` after it.
+    pub fn then(self, next: Transform) -> Transform {
+        Transform {
+            matrix: multiply(&next.matrix, &self.matrix),
+            inverse: multiply(&self.inverse, &next.inverse),
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.matrix == IDENTITY
+    }
+
+    // Whether the transformation mirrors space, which turns counter-clockwise triangles clockwise.
+    pub fn is_mirroring(&self) -> bool {
+        let m = &self.matrix;
+        let determinant = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+        determinant < 0.0
+    }
+
+    // Factor of the transformation if it scales all directions the same, e.g. for spheres that stay spheres.
+    pub fn uniform_scale(&self) -> Option<Float> {
+        let axes = [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        ]
+        .map(|axis| self.vector(axis));
+        let scale = axes[0].length();
+        let perpendicular = |a: Vec3, b: Vec3| Vec3::dot(a, b).abs() <= 1e-4 * scale * scale;
+        let uniform = axes.iter().all(|axis| (axis.length() - scale).abs() <= 1e-4 * scale);
+        (uniform
+            && perpendicular(axes[0], axes[1])
+            && perpendicular(axes[1], axes[2])
+            && perpendicular(axes[0], axes[2]))
+        .then_some(scale)
+    }
+
+    pub fn inverse(self) -> Transform {
+        Transform {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn point(&self, p: Point) -> Point {
+        let m = &self.matrix;
+        let row = |r: &[Float; 4]| r[0] * p.x + r[1] * p.y + r[2] * p.z + r[3];
+        Point::new(row(&m[0]), row(&m[1]), row(&m[2]))
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.matrix;
+        let row = |r: &[Float; 4]| r[0] * v.x + r[1] * v.y + r[2] * v.z;
+        Vec3::new(row(&m[0]), row(&m[1]), row(&m[2]))
+    }
+
+    // Normals are transformed by the inverse transpose to stay perpendicular to the transformed surface. The result
+    // is not normalized.
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        let m = &self.inverse;
+        Vec3::new(
+            m[0][0] * n.x + m[1][0] * n.y + m[2][0] * n.z,
+            m[0][1] * n.x + m[1][1] * n.y + m[2][1] * n.z,
+            m[0][2] * n.x + m[1][2] * n.y + m[2][2] * n.z,
+        )
+    }
+
+    // Box around the transformed corners of `bounds`.
+    pub fn bounds(&self, bounds: Aabb) -> Aabb {
+        if bounds.is_empty() {
+            return bounds;
+        }
+        let (min, max) = (bounds.min, bounds.max);
+        Aabb::from_points((0..8).map(|corner| {
+            let x = if corner & 1 == 0 { min.x } else { max.x };
+            let y = if corner & 2 == 0 { min.y } else { max.y };
+            let z = if corner & 4 == 0 { min.z } else { max.z };
+            self.point(Point::new(x, y, z))
+        }))
+    }
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut result = [[0.0; 4]; 3];
+    for (row, result_row) in result.iter_mut().enumerate() {
+        for (column, value) in result_row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[row][k] * b[k][column]).sum();
+        }
+        // Translation column picks up the implicit 1 in the last row of `b`
+        result_row[3] += a[row][3];
+    }
+    result
+}
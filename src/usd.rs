@@ -0,0 +1,1584 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    fs, io,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    accel::Accelerator,
+    animation::Keyframes,
+    bvh::BvhSettings,
+    camera::CameraSettings,
+    color::Color,
+    error::{Error, Result},
+    float::{consts, Float},
+    hittable::HittableList,
+    id::Names,
+    instance::Instance,
+    light_linking::LightLinks,
+    material::{Dielectric, DiffuseLight, Lambertian, Material, Metal, Plastic},
+    mesh::TriangleMesh,
+    parser::Warnings,
+    scene::Scene,
+    sphere::{Sphere, SphereList},
+    transform::Transform,
+    vec3::{Point, Vec3},
+    warn,
+};
+
+// Importer of scenes in the text format of Pixar's Universal Scene Description, to render what content creation tools
+// export without a round trip through another format, e.g.
+//
+//   #usda 1.0
+//   def Xform "World"
+//   {
+//       def Camera "Camera"
+//       {
+//           float focalLength = 35
+//           double3 xformOp:translate = (0, 1, 5)
+//           uniform token[] xformOpOrder = ["xformOp:translate"]
+//       }
+//       def Sphere "Ball"
+//       {
+//           double radius = 1
+//       }
+//       def DomeLight "Sky"
+//       {
+//           float inputs:intensity = 1
+//       }
+//   }
+//
+// It reads `.usda` files and `.usdz` packages with a text root layer, binary layers need converting to text with
+// `usdcat` first. The stage is composed from sublayers, references, payloads and the selected variants, and rendered
+// at its first time sample. Meshes with their geometry subsets, spheres, cubes, UsdPreviewSurface materials, the camera
+// with the render settings, and sphere, rectangle, disk and dome lights are imported. Textures, other prims, and
+// inherits and specializes arcs are skipped or approximated with a warning.
+pub fn load(path: &Path, bvh: BvhSettings) -> Result<Scene> {
+    let mut stage = Stage::open(path)?;
+    let root = stage.compose()?;
+    // The layers of a package all come from its file
+    let mut files: Vec<PathBuf> = match stage.package {
+        Some(_) => vec![path.to_path_buf()],
+        None => stage.layers.keys().map(PathBuf::from).collect(),
+    };
+    files.sort();
+    let mut importer = Importer::new(Accelerator::Bvh(bvh), stage.warnings);
+    importer.import(&root)?;
+    Ok(Scene {
+        files,
+        ..importer.finish()?
+    })
+}
+
+// Composition arcs nested deeper than this are taken to be a cycle.
+const MAX_DEPTH: usize = 32;
+
+// Prims and values nested deeper than this are taken to be a broken file, parsing them could overflow the stack.
+const MAX_NESTING: usize = 256;
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+// Parsing
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Punct(char),
+    Number(Float),
+    Str(String),
+    Ident(String),
+    Path(String),  // `</World/Prim>`
+    Asset(String), // `@file.usda@`
+}
+
+#[derive(Clone, Debug)]
+enum Value {
+    None,
+    Number(Float),
+    Str(String), // strings and tokens
+    Path(String),
+    Asset(String, Option<String>), // file and the prim in it
+    List(Vec<Value>),              // arrays and tuples
+    Dict(Vec<(String, Value)>),
+}
+
+impl Value {
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Str(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    // Target of a relationship or a connection, the first one if there are several.
+    fn as_path(&self) -> Option<&str> {
+        match self {
+            Value::Path(path) => Some(path),
+            Value::List(items) => items.first().and_then(Value::as_path),
+            _ => None,
+        }
+    }
+
+    // All numbers of a number, tuple or array, flattened.
+    fn numbers(&self) -> Option<Vec<Float>> {
+        fn flatten(value: &Value, numbers: &mut Vec<Float>) -> bool {
+            match value {
+                Value::Number(number) => numbers.push(*number),
+                Value::List(items) => return items.iter().all(|item| flatten(item, numbers)),
+                _ => return false,
+            }
+            true
+        }
+        let mut numbers = vec![];
+        flatten(self, &mut numbers).then_some(numbers)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Property {
+    name: String,
+    value: Value,
+    sampled: bool, // the value is the first of its time samples
+}
+
+#[derive(Clone, Debug, Default)]
+struct Prim {
+    specifier: String, // def, over or class
+    kind: String,      // type name, empty for typeless prims
+    name: String,
+    path: String, // set by the composition
+    metadata: Vec<(String, Value)>,
+    properties: Vec<Property>, // in the order of increasing strength
+    children: Vec<Prim>,
+    variant_sets: Vec<(String, Vec<(String, Prim)>)>, // variants by set, each with the opinions it adds
+    line: usize,
+}
+
+impl Prim {
+    // Strongest authored value of an attribute, time samples win over defaults.
+    fn attribute(&self, name: &str) -> Option<&Value> {
+        self.properties
+            .iter()
+            .rev()
+            .filter(|property| property.name == name && !matches!(property.value, Value::None))
+            .min_by_key(|property| !property.sampled)
+            .map(|property| &property.value)
+    }
+
+    fn metadata(&self, key: &str) -> Option<&Value> {
+        self.metadata
+            .iter()
+            .rev()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value)
+    }
+
+    fn invalid(&self, message: impl Display) -> Error {
+        Error::parse(self.line, format!("{}: {message}", self.path))
+    }
+
+    fn float(&self, name: &str, default: Float) -> Result<Float> {
+        match self.attribute(name) {
+            None => Ok(default),
+            Some(Value::Number(number)) => Ok(*number),
+            Some(Value::Str(value)) if value == "true" => Ok(1.0),
+            Some(Value::Str(value)) if value == "false" => Ok(0.0),
+            Some(_) => Err(self.invalid(format!("'{name}' must be a number"))),
+        }
+    }
+
+    // Color from three numbers, a single number for gray, or the first color of an array.
+    fn color(&self, name: &str, default: Color) -> Result<Color> {
+        let Some(value) = self.attribute(name) else {
+            return Ok(default);
+        };
+        match value.numbers().as_deref() {
+            Some([value]) => Ok(Color::splat(*value)),
+            Some([r, g, b, ..]) => Ok(Color::new(*r, *g, *b)),
+            _ => Err(self.invalid(format!("'{name}' must be a color"))),
+        }
+    }
+
+    fn integers(&self, name: &str) -> Result<Vec<usize>> {
+        let Some(value) = self.attribute(name) else {
+            return Ok(vec![]);
+        };
+        let numbers = value.numbers().unwrap_or_default();
+        if numbers.iter().any(|&number| number < 0.0 || number.fract() != 0.0) {
+            return Err(self.invalid(format!("'{name}' must be an array of indices")));
+        }
+        Ok(numbers.into_iter().map(|number| number as usize).collect())
+    }
+
+    fn token(&self, name: &str) -> Option<&str> {
+        self.attribute(name).and_then(Value::as_str)
+    }
+
+    fn target(&self, name: &str) -> Option<&str> {
+        self.attribute(name).and_then(Value::as_path)
+    }
+}
+
+// Prims and metadata of a layer.
+#[derive(Debug, Default)]
+struct Layer {
+    metadata: Vec<(String, Value)>,
+    prims: Vec<Prim>,
+}
+
+impl Layer {
+    fn find(&self, path: &str) -> Option<&Prim> {
+        let mut names = path.trim_start_matches('/').split('/');
+        let first = names.next()?;
+        let mut prim = self.prims.iter().find(|prim| prim.name == first)?;
+        for name in names {
+            prim = prim.children.iter().find(|child| child.name == name)?;
+        }
+        Some(prim)
+    }
+
+    // Path of the prim references to the layer point to if they don't name one.
+    fn default_prim(&self) -> Option<String> {
+        let metadata = self.metadata.iter().rev().find(|(key, _)| key == "defaultPrim");
+        match metadata.and_then(|(_, value)| value.as_str()) {
+            Some(name) => Some(format!("/{}", name.trim_start_matches('/'))),
+            None => self.prims.first().map(|prim| format!("/{}", prim.name)),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>> {
+    let mut tokens = vec![];
+    let mut line = 1;
+    let mut rest = source;
+    loop {
+        let trimmed = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ';');
+        line += rest[..rest.len() - trimmed.len()].matches('\n').count();
+        rest = trimmed;
+        let Some(c) = rest.chars().next() else {
+            break;
+        };
+        // Comments run to the end of the line, the `#usda 1.0` header is one as well
+        if c == '#' {
+            rest = rest.find('\n').map_or("", |end| &rest[end..]);
+            continue;
+        }
+        let (token, length) =
+            if let Some(quote) = ["\"\"\"", "'''", "\"", "'"].into_iter().find(|q| rest.starts_with(q)) {
+                let body = &rest[quote.len()..];
+                let end = string_end(body, quote).ok_or_else(|| Error::parse(line, "unterminated string"))?;
+                (Token::Str(unescape(&body[..end])), end + 2 * quote.len())
+            } else if c == '@' {
+                let quote = if rest.starts_with("@@@") { "@@@" } else { "@" };
+                let body = &rest[quote.len()..];
+                let end = body
+                    .find(quote)
+                    .ok_or_else(|| Error::parse(line, "unterminated asset path"))?;
+                (Token::Asset(body[..end].to_string()), end + 2 * quote.len())
+            } else if c == '<' {
+                let end = rest.find('>').ok_or_else(|| Error::parse(line, "unterminated path"))?;
+                (Token::Path(rest[1..end].to_string()), end + 1)
+            } else if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' {
+                // Numbers include exponents and infinities, `-inf`
+                let length = rest[1..]
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '+' | '-')))
+                    .map_or(rest.len(), |end| end + 1);
+                let text = &rest[..length];
+                let number = text
+                    .parse()
+                    .map_err(|_| Error::parse(line, format!("invalid number '{text}'")))?;
+                (Token::Number(number), length)
+            } else if c.is_alphabetic() || c == '_' {
+                // Property names are namespaced, `inputs:diffuseColor.connect`
+                let length = rest
+                    .find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | ':' | '.')))
+                    .unwrap_or(rest.len());
+                (Token::Ident(rest[..length].to_string()), length)
+            } else if "()[]{}=,:".contains(c) {
+                (Token::Punct(c), 1)
+            } else {
+                return Err(Error::parse(line, format!("unexpected character '{c}'")));
+            };
+        tokens.push((token, line));
+        line += rest[..length].matches('\n').count();
+        rest = &rest[length..];
+    }
+    Ok(tokens)
+}
+
+// Index of the closing quote of a string, skipping escaped characters.
+fn string_end(body: &str, quote: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (index, c) in body.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if body[index..].starts_with(quote) {
+            return Some(index);
+        }
+    }
+    None
+}
+
+fn unescape(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some(c) => result.push(c),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    depth: usize, // prims and values the parser is inside of
+}
+
+impl Parser {
+    fn new(source: &str) -> Result<Parser> {
+        Ok(Parser {
+            tokens: tokenize(source)?,
+            position: 0,
+            depth: 0,
+        })
+    }
+
+    fn line(&self) -> usize {
+        match self.tokens.get(self.position).or(self.tokens.last()) {
+            Some((_, line)) => *line,
+            None => 1,
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        let token = self
+            .peek()
+            .cloned()
+            .ok_or_else(|| Error::parse(self.line(), "unexpected end of file"))?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    // Skip the punctuation `c` if it comes next.
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(&Token::Punct(c));
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        if !self.eat(c) {
+            return Err(Error::parse(self.line(), format!("expected '{c}'")));
+        }
+        Ok(())
+    }
+
+    // Go one level deeper into the prims or values, which `body` and `value` leave again.
+    fn enter(&mut self) -> Result<()> {
+        if self.depth == MAX_NESTING {
+            let message = format!("prims and values nested deeper than {MAX_NESTING}");
+            return Err(Error::parse(self.line(), message));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        match self.next()? {
+            Token::Ident(ident) => Ok(ident),
+            _ => Err(Error::parse(self.line(), "expected a name")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        match self.next()? {
+            Token::Str(string) => Ok(string),
+            _ => Err(Error::parse(self.line(), "expected a string")),
+        }
+    }
+
+    fn layer(&mut self) -> Result<Layer> {
+        let mut layer = Layer::default();
+        if self.eat('(') {
+            layer.metadata = self.metadata()?;
+        }
+        while self.peek().is_some() {
+            let line = self.line();
+            match self.next()? {
+                Token::Ident(specifier) if matches!(specifier.as_str(), "def" | "over" | "class") => {
+                    layer.prims.push(self.prim(specifier, line)?);
+                }
+                _ => return Err(Error::parse(line, "expected a prim")),
+            }
+        }
+        Ok(layer)
+    }
+
+    // Metadata after its opening parenthesis, up to and including the closing one. Deleted list items are dropped.
+    fn metadata(&mut self) -> Result<Vec<(String, Value)>> {
+        let mut entries = vec![];
+        while !self.eat(')') {
+            let key = match self.next()? {
+                // Documentation
+                Token::Str(_) => continue,
+                Token::Ident(operation) if matches!(operation.as_str(), "prepend" | "append" | "add" | "delete") => {
+                    let key = self.ident()?;
+                    self.expect('=')?;
+                    let value = self.value()?;
+                    if operation != "delete" {
+                        entries.push((key, value));
+                    }
+                    continue;
+                }
+                Token::Ident(key) => key,
+                _ => return Err(Error::parse(self.line(), "invalid metadata")),
+            };
+            self.expect('=')?;
+            entries.push((key, self.value()?));
+        }
+        Ok(entries)
+    }
+
+    fn prim(&mut self, specifier: String, line: usize) -> Result<Prim> {
+        let kind = match self.peek() {
+            Some(Token::Ident(_)) => self.ident()?,
+            _ => String::new(),
+        };
+        let mut prim = Prim {
+            specifier,
+            kind,
+            name: self.string()?,
+            line,
+            ..Prim::default()
+        };
+        if self.eat('(') {
+            prim.metadata = self.metadata()?;
+        }
+        self.expect('{')?;
+        self.body(&mut prim)?;
+        Ok(prim)
+    }
+
+    // Properties, children and variant sets of a prim or a variant, up to and including the closing brace.
+    fn body(&mut self, prim: &mut Prim) -> Result<()> {
+        self.enter()?;
+        loop {
+            let line = self.line();
+            let word = match self.next()? {
+                Token::Punct('}') => {
+                    self.depth -= 1;
+                    return Ok(());
+                }
+                Token::Ident(word) => word,
+                _ => return Err(Error::parse(line, "expected a property or a prim")),
+            };
+            match word.as_str() {
+                "def" | "over" | "class" => prim.children.push(self.prim(word, line)?),
+                "variantSet" => {
+                    let name = self.string()?;
+                    self.expect('=')?;
+                    self.expect('{')?;
+                    let mut variants = vec![];
+                    while !self.eat('}') {
+                        let line = self.line();
+                        let mut variant = Prim {
+                            specifier: "over".to_string(),
+                            name: prim.name.clone(),
+                            line,
+                            ..Prim::default()
+                        };
+                        let name = self.string()?;
+                        if self.eat('(') {
+                            variant.metadata = self.metadata()?;
+                        }
+                        self.expect('{')?;
+                        self.body(&mut variant)?;
+                        variants.push((name, variant));
+                    }
+                    prim.variant_sets.push((name, variants));
+                }
+                "reorder" => {
+                    self.ident()?;
+                    self.expect('=')?;
+                    self.value()?;
+                }
+                _ => self.property(word, prim)?,
+            }
+        }
+    }
+
+    // Attribute or relationship declaration after its first word, e.g.
+    // `uniform color3f[] primvars:displayColor = [(1, 0, 0)] (interpolation = "constant")`.
+    fn property(&mut self, first: String, prim: &mut Prim) -> Result<()> {
+        let mut word = first;
+        while matches!(
+            word.as_str(),
+            "custom" | "uniform" | "varying" | "config" | "prepend" | "append" | "add" | "delete"
+        ) {
+            word = self.ident()?;
+        }
+        // The type of an attribute, possibly an array of it
+        if word != "rel" && self.eat('[') {
+            self.expect(']')?;
+        }
+        let name = self.ident()?;
+        let value = if self.eat('=') { self.value()? } else { Value::None };
+        if self.eat('(') {
+            self.metadata()?;
+        }
+        let (name, sampled) = match name.strip_suffix(".timeSamples") {
+            Some(name) => (name.to_string(), true),
+            None => (name, false),
+        };
+        prim.properties.push(Property { name, value, sampled });
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        self.enter()?;
+        let line = self.line();
+        let value = match self.next()? {
+            Token::Punct(open @ ('(' | '[')) => {
+                let close = if open == '(' { ')' } else { ']' };
+                let mut items = vec![];
+                while !self.eat(close) {
+                    items.push(self.value()?);
+                    if !self.eat(',') {
+                        self.expect(close)?;
+                        break;
+                    }
+                }
+                Value::List(items)
+            }
+            Token::Punct('{') => self.dictionary()?,
+            Token::Number(number) => Value::Number(number),
+            Token::Str(string) => Value::Str(string),
+            Token::Ident(word) if word == "None" => Value::None,
+            Token::Ident(word) => Value::Str(word),
+            Token::Path(path) => Value::Path(path),
+            Token::Asset(file) => {
+                let prim = match self.peek() {
+                    Some(Token::Path(_)) => Some(self.next()?).and_then(|token| match token {
+                        Token::Path(path) => Some(path),
+                        _ => None,
+                    }),
+                    _ => None,
+                };
+                // Layer offsets of references
+                if self.eat('(') {
+                    self.metadata()?;
+                }
+                Value::Asset(file, prim)
+            }
+            Token::Punct(c) => return Err(Error::parse(line, format!("unexpected '{c}'"))),
+        };
+        self.depth -= 1;
+        Ok(value)
+    }
+
+    // Time samples or a dictionary after the opening brace. Of time samples only the earliest is kept, it's the one
+    // the scene is rendered at.
+    fn dictionary(&mut self) -> Result<Value> {
+        let samples = matches!(self.peek(), Some(Token::Number(_)))
+            && matches!(self.tokens.get(self.position + 1), Some((Token::Punct(':'), _)));
+        if samples {
+            let mut first: Option<(Float, Value)> = None;
+            while !self.eat('}') {
+                let Token::Number(time) = self.next()? else {
+                    return Err(Error::parse(self.line(), "expected a time code"));
+                };
+                self.expect(':')?;
+                let value = self.value()?;
+                if first.as_ref().is_none_or(|(earliest, _)| time < *earliest) {
+                    first = Some((time, value));
+                }
+                self.eat(',');
+            }
+            return Ok(first.map_or(Value::None, |(_, value)| value));
+        }
+        let mut entries = vec![];
+        while !self.eat('}') {
+            // Entries are typed, `string name = "value"`, with names that may be quoted
+            let mut key = None;
+            while !self.eat('=') {
+                key = match self.next()? {
+                    Token::Ident(word) | Token::Str(word) => Some(word),
+                    Token::Punct('[' | ']') => key,
+                    _ => return Err(Error::parse(self.line(), "invalid dictionary")),
+                };
+            }
+            let key = key.ok_or_else(|| Error::parse(self.line(), "dictionary entry without a name"))?;
+            entries.push((key, self.value()?));
+            self.eat(',');
+        }
+        Ok(Value::Dict(entries))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+// Composition
+
+// Layers of a stage, from the file system or from a USDZ package.
+struct Stage {
+    root: String,                              // key of the root layer
+    package: Option<HashMap<String, Vec<u8>>>, // files of a USDZ package by their path in it
+    layers: HashMap<String, Arc<Layer>>,       // layers read so far by their path, with their sublayers
+    warnings: Warnings,                        // of the file of the stage
+}
+
+impl Stage {
+    fn open(path: &Path) -> Result<Stage> {
+        let mut stage = Stage {
+            root: path.to_string_lossy().into_owned(),
+            package: None,
+            layers: HashMap::new(),
+            warnings: Warnings::new(path),
+        };
+        if path.extension().and_then(|extension| extension.to_str()) == Some("usdz") {
+            // The first file of the package is its root layer
+            let files = read_package(&fs::read(path)?)?;
+            stage.root = files
+                .first()
+                .map(|(name, _)| name.clone())
+                .ok_or_else(|| invalid_data("empty USDZ package"))?;
+            stage.package = Some(files.into_iter().collect());
+        }
+        Ok(stage)
+    }
+
+    // Key of the layer an asset path in the layer `layer` refers to.
+    fn resolve(&self, layer: &str, asset: &str) -> String {
+        let mut path = PathBuf::new();
+        for component in Path::new(layer)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(asset)
+            .components()
+        {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir if matches!(path.components().next_back(), Some(Component::Normal(_))) => {
+                    path.pop();
+                }
+                component => path.push(component),
+            }
+        }
+        path.to_string_lossy().into_owned()
+    }
+
+    fn layer(&mut self, key: &str, depth: usize) -> Result<Arc<Layer>> {
+        if let Some(layer) = self.layers.get(key) {
+            return Ok(layer.clone());
+        }
+        if depth > MAX_DEPTH {
+            return Err(Error::parse(1, "sublayers nested too deeply"));
+        }
+        let bytes = match &self.package {
+            Some(files) => files
+                .get(key)
+                .cloned()
+                .ok_or_else(|| invalid_data(format!("no {key} in the package")))?,
+            None => fs::read(key)?,
+        };
+        if bytes.starts_with(b"PXR-USDC") {
+            let message = format!("{key} is a binary USD layer, convert it to text with usdcat");
+            return Err(invalid_data(message).into());
+        }
+        let source = String::from_utf8(bytes).map_err(|_| invalid_data(format!("{key} is not UTF-8 text")))?;
+        let mut layer = Parser::new(&source)?.layer()?;
+
+        // Sublayers are listed from the strongest, the layer itself is stronger than all of them
+        let sublayers = layer.metadata.iter().rev().find(|(key, _)| key == "subLayers");
+        if let Some((_, Value::List(sublayers))) = sublayers {
+            let mut prims = vec![];
+            for sublayer in sublayers.clone().iter().rev() {
+                let Value::Asset(file, _) = sublayer else {
+                    return Err(Error::parse(1, "sublayers must be asset paths"));
+                };
+                let sublayer = self.resolve(key, file);
+                let stack = self
+                    .layer(&sublayer, depth + 1)
+                    .map_err(|err| Error::parse(1, format!("{sublayer}: {err}")))?;
+                merge_children(&mut prims, stack.prims.clone());
+            }
+            merge_children(&mut prims, std::mem::take(&mut layer.prims));
+            layer.prims = prims;
+        }
+        let layer = Arc::new(layer);
+        self.layers.insert(key.to_string(), layer.clone());
+        Ok(layer)
+    }
+
+    // Root layer with the composition arcs of all prims resolved.
+    fn compose(&mut self) -> Result<Layer> {
+        let root = self.root.clone();
+        let layer = self.layer(&root, 0)?;
+        let mut prims = vec![];
+        for prim in &layer.prims {
+            let path = format!("/{}", prim.name);
+            prims.push(self.prim(prim.clone(), &root, &path, 0)?);
+        }
+        Ok(Layer {
+            metadata: layer.metadata.clone(),
+            prims,
+        })
+    }
+
+    // Prim of the layer `layer` with its selected variants and references merged in, and its children composed.
+    fn prim(&mut self, mut prim: Prim, layer: &str, path: &str, depth: usize) -> Result<Prim> {
+        prim.path = path.to_string();
+        if depth > MAX_DEPTH {
+            return Err(prim.invalid("composition arcs nested too deeply"));
+        }
+
+        // The selected variants are weaker than the prim itself and stronger than its references
+        let selections = match prim.metadata("variants") {
+            Some(Value::Dict(entries)) => entries.clone(),
+            _ => vec![],
+        };
+        let mut combined = Prim {
+            specifier: "over".to_string(),
+            path: path.to_string(),
+            ..Prim::default()
+        };
+        for (set, variants) in std::mem::take(&mut prim.variant_sets) {
+            let selection = selections.iter().rev().find(|(name, _)| *name == set);
+            match selection.and_then(|(_, variant)| variant.as_str()) {
+                Some(selection) => {
+                    if let Some((_, variant)) = variants.into_iter().find(|(name, _)| name == selection) {
+                        merge(&mut combined, variant);
+                    }
+                }
+                None => self.warnings.warn(
+                    &prim.path,
+                    format!("leaving out the variant set '{set}' without a selection"),
+                ),
+            }
+        }
+        merge(&mut combined, prim);
+
+        // References are stronger than payloads, both are listed from the strongest
+        let mut arcs = vec![];
+        for key in ["references", "payload"] {
+            for (name, value) in &combined.metadata {
+                if name == key {
+                    match value {
+                        Value::List(items) => arcs.extend(items.iter().cloned()),
+                        Value::None => {}
+                        value => arcs.push(value.clone()),
+                    }
+                }
+            }
+        }
+        combined
+            .metadata
+            .retain(|(name, _)| name != "references" && name != "payload");
+        if combined.metadata("inherits").is_some() || combined.metadata("specializes").is_some() {
+            self.warnings
+                .warn(&combined.path, "ignoring inherits and specializes arcs");
+        }
+        let mut composed: Option<Prim> = None;
+        for arc in arcs.iter().rev() {
+            let referenced = self.reference(arc, layer, &combined, depth)?;
+            match &mut composed {
+                Some(base) => merge(base, referenced),
+                None => composed = Some(referenced),
+            }
+        }
+        let mut composed = match composed {
+            Some(mut base) => {
+                merge(&mut base, combined);
+                base
+            }
+            None => combined,
+        };
+        composed.path = path.to_string();
+
+        let mut children = vec![];
+        for child in std::mem::take(&mut composed.children) {
+            let path = format!("{path}/{}", child.name);
+            children.push(self.prim(child, layer, &path, depth)?);
+        }
+        composed.children = children;
+        Ok(composed)
+    }
+
+    // Prim a reference or payload of `prim` brings in, moved to the path of `prim`.
+    fn reference(&mut self, arc: &Value, layer: &str, prim: &Prim, depth: usize) -> Result<Prim> {
+        let (key, source) = match arc {
+            Value::Asset(file, source) => (self.resolve(layer, file), source.clone()),
+            Value::Path(source) => (layer.to_string(), Some(source.clone())),
+            _ => return Err(prim.invalid("invalid reference")),
+        };
+        let stack = self
+            .layer(&key, depth + 1)
+            .map_err(|err| prim.invalid(format!("{key}: {err}")))?;
+        let source = source
+            .or_else(|| stack.default_prim())
+            .ok_or_else(|| prim.invalid(format!("{key} has no prims")))?;
+        let referenced = stack
+            .find(&source)
+            .cloned()
+            .ok_or_else(|| prim.invalid(format!("no prim {source} in {key}")))?;
+        let mut referenced = self.prim(referenced, &key, &source, depth + 1)?;
+        remap(&mut referenced, &source, &prim.path);
+        Ok(referenced)
+    }
+}
+
+// Merge the opinions of `stronger` into `base`.
+fn merge(base: &mut Prim, stronger: Prim) {
+    if stronger.specifier != "over" {
+        base.specifier = stronger.specifier;
+    }
+    if !stronger.kind.is_empty() {
+        base.kind = stronger.kind;
+    }
+    if !stronger.name.is_empty() {
+        base.name = stronger.name;
+        base.line = stronger.line;
+    }
+    base.metadata.extend(stronger.metadata);
+    base.properties.extend(stronger.properties);
+    base.variant_sets.extend(stronger.variant_sets);
+    merge_children(&mut base.children, stronger.children);
+}
+
+fn merge_children(base: &mut Vec<Prim>, stronger: Vec<Prim>) {
+    for child in stronger {
+        match base.iter_mut().find(|prim| prim.name == child.name) {
+            Some(prim) => merge(prim, child),
+            None => base.push(child),
+        }
+    }
+}
+
+// Point the paths inside a referenced prim at its new place in the stage.
+fn remap(prim: &mut Prim, from: &str, to: &str) {
+    fn remap_value(value: &mut Value, from: &str, to: &str) {
+        match value {
+            Value::Path(path) => {
+                if let Some(rest) = path.strip_prefix(from) {
+                    if rest.is_empty() || rest.starts_with('/') || rest.starts_with('.') {
+                        *path = format!("{to}{rest}");
+                    }
+                }
+            }
+            Value::List(items) => items.iter_mut().for_each(|item| remap_value(item, from, to)),
+            _ => {}
+        }
+    }
+    if let Some(rest) = prim.path.strip_prefix(from) {
+        prim.path = format!("{to}{rest}");
+    }
+    for property in &mut prim.properties {
+        remap_value(&mut property.value, from, to);
+    }
+    for child in &mut prim.children {
+        remap(child, from, to);
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+// Files of a USDZ package, a zip archive that stores its files uncompressed.
+fn read_package(data: &[u8]) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let u16_at = |offset: usize| {
+        data.get(offset..offset + 2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+    };
+    let u32_at = |offset: usize| {
+        data.get(offset..offset + 4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+    };
+    let invalid = || invalid_data("invalid USDZ package");
+
+    // The central directory lists the files, the record at the end of the archive points to it
+    let end = (0..data.len().saturating_sub(21))
+        .rev()
+        .find(|&offset| u32_at(offset) == Some(0x06054b50))
+        .ok_or_else(invalid)?;
+    let count = u16_at(end + 10).ok_or_else(invalid)?;
+    let mut entry = u32_at(end + 16).ok_or_else(invalid)?;
+    let mut files = vec![];
+    for _ in 0..count {
+        if u32_at(entry) != Some(0x02014b50) {
+            return Err(invalid());
+        }
+        let method = u16_at(entry + 10).ok_or_else(invalid)?;
+        let size = u32_at(entry + 20).ok_or_else(invalid)?;
+        let name_length = u16_at(entry + 28).ok_or_else(invalid)?;
+        let extra_length = u16_at(entry + 30).ok_or_else(invalid)?;
+        let comment_length = u16_at(entry + 32).ok_or_else(invalid)?;
+        let header = u32_at(entry + 42).ok_or_else(invalid)?;
+        let name = data.get(entry + 46..entry + 46 + name_length).ok_or_else(invalid)?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        entry += 46 + name_length + extra_length + comment_length;
+        if method != 0 {
+            return Err(invalid_data(format!("{name} is compressed in the USDZ package")));
+        }
+        if u32_at(header) != Some(0x04034b50) {
+            return Err(invalid());
+        }
+        let start = header + 30 + u16_at(header + 26).ok_or_else(invalid)? + u16_at(header + 28).ok_or_else(invalid)?;
+        let contents = data.get(start..start + size).ok_or_else(invalid)?;
+        files.push((name, contents.to_vec()));
+    }
+    Ok(files)
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+// Import
+
+struct Importer<'a> {
+    accelerator: Accelerator,
+    warnings: Warnings,
+    prims: HashMap<&'a str, &'a Prim>, // all prims of the composed stage by path
+    materials: HashMap<String, Arc<dyn Material>>, // bound materials by path
+    cameras: Vec<(&'a Prim, Transform)>, // with their transformation into the world
+    settings: Option<&'a Prim>,        // render settings
+    camera: CameraSettings,
+    world: HittableList,
+    spheres: Vec<Sphere>,
+}
+
+impl<'a> Importer<'a> {
+    fn new(accelerator: Accelerator, warnings: Warnings) -> Importer<'a> {
+        Importer {
+            accelerator,
+            warnings,
+            prims: HashMap::new(),
+            materials: HashMap::new(),
+            cameras: vec![],
+            settings: None,
+            // USD has no sampling settings, the command line overrides them
+            camera: CameraSettings {
+                image_width: 640,
+                samples_per_pixel: 16,
+                background: Some(Color::BLACK),
+                ..CameraSettings::default()
+            },
+            world: vec![],
+            spheres: vec![],
+        }
+    }
+
+    fn import(&mut self, stage: &'a Layer) -> Result<()> {
+        fn index<'a>(prims: &mut HashMap<&'a str, &'a Prim>, prim: &'a Prim) {
+            prims.insert(&prim.path, prim);
+            for child in &prim.children {
+                index(prims, child);
+            }
+        }
+        for prim in &stage.prims {
+            index(&mut self.prims, prim);
+        }
+
+        // The tracer's up axis is y
+        let up_axis = stage.metadata.iter().rev().find(|(key, _)| key == "upAxis");
+        let up = match up_axis.and_then(|(_, value)| value.as_str()) {
+            Some("Z") => Transform::rotation(0, -90.0),
+            _ => Transform::IDENTITY,
+        };
+        for prim in &stage.prims {
+            self.prim(prim, up, None)?;
+        }
+        self.setup_camera()
+    }
+
+    // Import a prim and its descendants, `parent` takes the parent's space into the world and `binding` is the material
+    // the parent binds.
+    fn prim(&mut self, prim: &'a Prim, parent: Transform, binding: Option<&'a str>) -> Result<()> {
+        // Classes and overrides of prims that are never defined aren't rendered, nor are guides and proxies
+        let inactive = matches!(prim.metadata("active"), Some(Value::Str(active)) if active == "false");
+        if prim.specifier != "def"
+            || inactive
+            || prim.token("visibility") == Some("invisible")
+            || matches!(prim.token("purpose"), Some("guide" | "proxy"))
+        {
+            return Ok(());
+        }
+        let (local, reset) = self.transform(prim)?;
+        let transform = if reset { local } else { local.then(parent) };
+        let binding = material_binding(prim).or(binding);
+        match prim.kind.as_str() {
+            "Mesh" => self.mesh(prim, transform, binding)?,
+            "Sphere" => {
+                let radius = prim.float("radius", 1.0)?;
+                let material = self.material(prim, binding)?;
+                self.sphere(radius, material, transform);
+            }
+            "Cube" => {
+                let (vertices, triangles) = cube(prim.float("size", 2.0)?);
+                let material = self.material(prim, binding)?;
+                self.triangles(vertices, triangles, transform, material);
+            }
+            "Camera" => self.cameras.push((prim, transform)),
+            "SphereLight" | "RectLight" | "DiskLight" | "DomeLight" => self.light(prim, transform)?,
+            "RenderSettings" => {
+                self.settings.get_or_insert(prim);
+            }
+            // Materials are found through the bindings and the render products through the settings
+            "Material" | "NodeGraph" | "Shader" | "GeomSubset" | "RenderProduct" | "RenderVar" => return Ok(()),
+            "" | "Xform" | "Scope" | "SkelRoot" | "RenderSettingsBase" => {}
+            kind => self
+                .warnings
+                .warn(&prim.path, format!("skipping '{kind}' prims, they are not supported")),
+        }
+        for child in &prim.children {
+            self.prim(child, transform, binding)?;
+        }
+        Ok(())
+    }
+
+    // Transformation of a prim into its parent's space, and whether it ignores the parent's transformation.
+    fn transform(&self, prim: &Prim) -> Result<(Transform, bool)> {
+        let Some(order) = prim.attribute("xformOpOrder") else {
+            return Ok((Transform::IDENTITY, false));
+        };
+        let Value::List(operations) = order else {
+            return Err(prim.invalid("'xformOpOrder' must be a list of operations"));
+        };
+        // The first operation is the outermost, points go through the list from the end
+        let mut transform = Transform::IDENTITY;
+        let mut reset = false;
+        for operation in operations.iter().rev() {
+            let name = operation
+                .as_str()
+                .ok_or_else(|| prim.invalid("'xformOpOrder' must be a list of operations"))?;
+            if name == "!resetXformStack!" {
+                reset = true;
+                break;
+            }
+            let (name, invert) = match name.strip_prefix("!invert!") {
+                Some(name) => (name, true),
+                None => (name, false),
+            };
+            let value = prim
+                .attribute(name)
+                .ok_or_else(|| prim.invalid(format!("missing transformation '{name}'")))?;
+            let numbers = value
+                .numbers()
+                .ok_or_else(|| prim.invalid(format!("'{name}' must be numbers")))?;
+            let kind = name.strip_prefix("xformOp:").and_then(|name| name.split(':').next());
+            let next = match (kind.unwrap_or_default(), &numbers[..]) {
+                ("translate", &[x, y, z]) => Transform::translation(Vec3::new(x, y, z)),
+                ("scale", &[x, y, z]) if x != 0.0 && y != 0.0 && z != 0.0 => Transform::scaling(Vec3::new(x, y, z)),
+                ("scale", &[factor]) if factor != 0.0 => Transform::scaling(Vec3::new(factor, factor, factor)),
+                ("rotateX", &[angle]) => Transform::rotation(0, angle),
+                ("rotateY", &[angle]) => Transform::rotation(1, angle),
+                ("rotateZ", &[angle]) => Transform::rotation(2, angle),
+                // Rotations around several axes in the order of their names, `rotateXYZ` turns around x first
+                (kind, &[x, y, z]) if kind.len() == 9 && kind.starts_with("rotate") => {
+                    let angles = [x, y, z];
+                    let mut rotation = Transform::IDENTITY;
+                    for axis in kind[6..].bytes() {
+                        let axis = (axis - b'X') as usize;
+                        rotation = rotation.then(Transform::rotation(axis, angles[axis]));
+                    }
+                    rotation
+                }
+                ("orient", &[w, x, y, z]) => quaternion(w, x, y, z).ok_or_else(|| prim.invalid("zero quaternion"))?,
+                // USD matrices transform row vectors, the translation is in the last row
+                ("transform", matrix) if matrix.len() == 16 => {
+                    let row = |r: usize| [matrix[r], matrix[4 + r], matrix[8 + r], matrix[12 + r]];
+                    Transform::from_matrix([row(0), row(1), row(2)])
+                        .ok_or_else(|| prim.invalid(format!("'{name}' is singular")))?
+                }
+                _ => return Err(prim.invalid(format!("invalid transformation '{name}'"))),
+            };
+            transform = transform.then(if invert { next.inverse() } else { next });
+        }
+        Ok((transform, reset))
+    }
+
+    fn mesh(&mut self, prim: &'a Prim, transform: Transform, binding: Option<&'a str>) -> Result<()> {
+        let Some(points) = prim.attribute("points") else {
+            return Ok(());
+        };
+        let points: Vec<Point> = match points.numbers() {
+            Some(numbers) if numbers.len().is_multiple_of(3) => numbers
+                .chunks_exact(3)
+                .map(|point| Point::new(point[0], point[1], point[2]))
+                .collect(),
+            _ => return Err(prim.invalid("'points' must be a list of points")),
+        };
+        let counts = prim.integers("faceVertexCounts")?;
+        let indices = prim.integers("faceVertexIndices")?;
+        let holes: HashSet<usize> = prim.integers("holeIndices")?.into_iter().collect();
+
+        // Polygons are split into fans of triangles, remembering the face each came from
+        let flip = (prim.token("orientation") == Some("leftHanded")) != transform.is_mirroring();
+        let mut triangles = vec![];
+        let mut faces = vec![];
+        let mut start = 0;
+        for (face, &count) in counts.iter().enumerate() {
+            let corners = indices
+                .get(start..start + count)
+                .ok_or_else(|| prim.invalid("'faceVertexIndices' is shorter than the faces need"))?;
+            start += count;
+            if corners.iter().any(|&index| index >= points.len()) {
+                return Err(prim.invalid("face refers to a missing point"));
+            }
+            if holes.contains(&face) {
+                continue;
+            }
+            for i in 1..count.saturating_sub(1) {
+                let [a, b, c] = [corners[0], corners[i], corners[i + 1]];
+                triangles.push(if flip { [a, c, b] } else { [a, b, c] });
+                faces.push(face);
+            }
+        }
+        if triangles.is_empty() {
+            return Ok(());
+        }
+
+        // Faces of geometry subsets with a material of their own use it instead of the one of the mesh
+        let mut materials = vec![self.material(prim, binding)?];
+        let mut face_materials = vec![0; counts.len()];
+        for subset in &prim.children {
+            if subset.kind != "GeomSubset" || subset.token("elementType").is_some_and(|kind| kind != "face") {
+                continue;
+            }
+            let Some(binding) = material_binding(subset) else {
+                continue;
+            };
+            materials.push(self.material(subset, Some(binding))?);
+            for face in subset.integers("indices")? {
+                if let Some(material) = face_materials.get_mut(face) {
+                    *material = materials.len() - 1;
+                }
+            }
+        }
+        let vertices = points.into_iter().map(|point| transform.point(point)).collect();
+        let mut mesh = TriangleMesh::new(vertices, triangles, materials[0].clone(), self.accelerator);
+        if materials.len() > 1 {
+            let face_materials = faces.into_iter().map(|face| face_materials[face]).collect();
+            mesh = mesh.with_face_materials(materials, face_materials);
+        }
+        self.world.push(Box::new(mesh));
+        Ok(())
+    }
+
+    // Add a sphere around the origin moved into the world by `transform`.
+    fn sphere(&mut self, radius: Float, material: Arc<dyn Material>, transform: Transform) {
+        match transform.uniform_scale() {
+            Some(scale) => self
+                .spheres
+                .push(Sphere::new(transform.point(Point::ORIGIN), radius * scale, material)),
+            None => {
+                let sphere = Arc::new(Sphere::new(Point::ORIGIN, radius, material));
+                self.world.push(Box::new(Instance::new(sphere, transform)));
+            }
+        }
+    }
+
+    // Add triangles counter-clockwise around their front face, moved into the world by `transform`.
+    fn triangles(
+        &mut self,
+        vertices: Vec<Point>,
+        triangles: Vec<[usize; 3]>,
+        transform: Transform,
+        material: Arc<dyn Material>,
+    ) {
+        let flip = transform.is_mirroring();
+        let triangles = triangles
+            .into_iter()
+            .map(|[a, b, c]| if flip { [a, c, b] } else { [a, b, c] })
+            .collect();
+        let vertices = vertices.into_iter().map(|vertex| transform.point(vertex)).collect();
+        let mesh = TriangleMesh::new(vertices, triangles, material, self.accelerator);
+        self.world.push(Box::new(mesh));
+    }
+
+    // Material bound to a geometry prim, or a diffuse one of its display color without a binding.
+    fn material(&mut self, prim: &Prim, binding: Option<&'a str>) -> Result<Arc<dyn Material>> {
+        let Some(path) = binding else {
+            let color = prim.color("primvars:displayColor", Color::splat(0.5))?;
+            return Ok(Arc::new(Lambertian::new(color)));
+        };
+        if let Some(material) = self.materials.get(path) {
+            return Ok(material.clone());
+        }
+        let material = match self.prims.get(path).copied() {
+            Some(material) => self.surface(material)?,
+            None => {
+                self.warnings.warn(
+                    &prim.path,
+                    format!("using a diffuse material instead of the missing material {path}"),
+                );
+                Arc::new(Lambertian::new(Color::splat(0.5)))
+            }
+        };
+        self.materials.insert(path.to_string(), material.clone());
+        Ok(material)
+    }
+
+    // Prim a connection points to, e.g. the shader of `</Looks/Red/Surface.outputs:surface>`.
+    fn source(&self, target: &str) -> Option<&'a Prim> {
+        let path = target.rsplit_once('.').map_or(target, |(path, _)| path);
+        self.prims.get(path).copied()
+    }
+
+    // Material of the UsdPreviewSurface shader connected to the surface output of a material, following the
+    // connections through node graphs.
+    fn surface(&mut self, material: &'a Prim) -> Result<Arc<dyn Material>> {
+        let mut shader = Some(material);
+        let mut output = "outputs:surface.connect".to_string();
+        for _ in 0..MAX_DEPTH {
+            match shader {
+                Some(prim) if prim.kind != "Shader" => {
+                    let target = prim.target(&output);
+                    if let Some((_, name)) = target.and_then(|target| target.rsplit_once('.')) {
+                        output = format!("{name}.connect");
+                    }
+                    shader = target.and_then(|target| self.source(target));
+                }
+                _ => break,
+            }
+        }
+        let Some(shader) = shader.filter(|shader| shader.token("info:id") == Some("UsdPreviewSurface")) else {
+            let message = "approximating materials without a UsdPreviewSurface shader by diffuse materials";
+            self.warnings.warn(&material.path, message);
+            return Ok(Arc::new(Lambertian::new(Color::splat(0.5))));
+        };
+
+        let diffuse = self.input(shader, "diffuseColor", Color::splat(0.18))?;
+        let emissive = self.input(shader, "emissiveColor", Color::BLACK)?;
+        let metallic = self.input(shader, "metallic", Color::BLACK)?.r;
+        let roughness = self.input(shader, "roughness", Color::splat(0.5))?.r;
+        let opacity = self.input(shader, "opacity", Color::WHITE)?.r;
+        let ior = self.input(shader, "ior", Color::splat(1.5))?.r;
+        let material: Arc<dyn Material> = if emissive.to_array().iter().any(|&channel| channel > 0.0) {
+            Arc::new(DiffuseLight::new(emissive))
+        } else if opacity < 1.0 && shader.float("inputs:opacityThreshold", 0.0)? == 0.0 {
+            // Without a threshold the opacity makes the surface refract
+            Arc::new(Dielectric::new(ior).with_roughness(roughness))
+        } else if metallic >= 0.5 {
+            Arc::new(Metal::new(diffuse, roughness))
+        } else {
+            Arc::new(Plastic::new(diffuse, ior, roughness))
+        };
+        Ok(material)
+    }
+
+    // Input of a shader, a single number for gray. Textures are not supported, a connected texture gives the value it
+    // falls back to without a file.
+    fn input(&mut self, shader: &Prim, name: &str, default: Color) -> Result<Color> {
+        if let Some(target) = shader.target(&format!("inputs:{name}.connect")) {
+            self.warnings
+                .warn(&shader.path, "using constant colors instead of textures");
+            return match self.source(target) {
+                Some(texture) => texture.color("inputs:fallback", default),
+                None => Ok(default),
+            };
+        }
+        shader.color(&format!("inputs:{name}"), default)
+    }
+
+    fn light(&mut self, prim: &Prim, transform: Transform) -> Result<()> {
+        // Older files name the attributes without the `inputs:` namespace
+        let float = |name: &str, default| prim.float(&format!("inputs:{name}"), prim.float(name, default)?);
+        let color = prim.color("inputs:color", prim.color("color", Color::WHITE)?)?;
+        let mut radiance = color * float("intensity", 1.0)? * float("exposure", 0.0)?.exp2();
+        if float("enableColorTemperature", 0.0)? != 0.0 {
+            radiance *= Color::blackbody(float("colorTemperature", 6500.0)?);
+        }
+        // Normalized lights keep their power whatever their size
+        let normalize = float("normalize", 0.0)? != 0.0;
+        let scale = transform.uniform_scale().unwrap_or(1.0);
+        let emit = |area: Float| -> Arc<dyn Material> {
+            let area = if normalize { area * scale * scale } else { 1.0 };
+            Arc::new(DiffuseLight::new(radiance / area.max(Float::EPSILON)))
+        };
+        if prim
+            .attribute("inputs:texture:file")
+            .or(prim.attribute("texture:file"))
+            .is_some()
+        {
+            self.warnings
+                .warn(&prim.path, "using the color of lights instead of their textures");
+        }
+        match prim.kind.as_str() {
+            "DomeLight" => self.camera.background = Some(radiance),
+            "SphereLight" => {
+                let radius = float("radius", 0.5)?;
+                let material = emit(4.0 * consts::PI * radius * radius);
+                self.sphere(radius, material, transform);
+            }
+            // Rectangle and disk lights shine down their -z axis
+            "RectLight" => {
+                let (width, height) = (float("width", 1.0)?, float("height", 1.0)?);
+                let vertices = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+                    .map(|(x, y)| Point::new(x * width / 2.0, y * height / 2.0, 0.0))
+                    .to_vec();
+                let material = emit(width * height);
+                self.triangles(vertices, vec![[0, 2, 1], [0, 3, 2]], transform, material);
+            }
+            _ => {
+                let radius = float("radius", 0.5)?;
+                let (vertices, triangles) = disk(radius);
+                let triangles = triangles.into_iter().map(|[a, b, c]| [a, c, b]).collect();
+                let material = emit(consts::PI * radius * radius);
+                self.triangles(vertices, triangles, transform, material);
+            }
+        }
+        Ok(())
+    }
+
+    // Camera from the render settings, or the first one of the stage. The settings also give the image size, either
+    // themselves or through their first render product.
+    fn setup_camera(&mut self) -> Result<()> {
+        let mut sources: Vec<&Prim> = vec![];
+        if let Some(settings) = self.settings {
+            sources.push(settings);
+            let products = settings
+                .attribute("products")
+                .into_iter()
+                .flat_map(|products| match products {
+                    Value::List(items) => items.iter().filter_map(Value::as_path).collect(),
+                    value => value.as_path().into_iter().collect::<Vec<_>>(),
+                });
+            sources.extend(products.filter_map(|product| self.prims.get(product).copied()));
+        }
+        let camera_path = sources.iter().find_map(|source| source.target("camera"));
+        let resolution = sources.iter().find_map(|source| source.attribute("resolution"));
+        let selected = camera_path.and_then(|path| self.cameras.iter().find(|(camera, _)| camera.path == path));
+        let Some(&(camera, transform)) = selected.or(self.cameras.first()) else {
+            warn!(
+                "{}: the stage has no camera, looking down -z from the origin",
+                self.warnings.file.display()
+            );
+            return Ok(());
+        };
+
+        if camera.token("projection") == Some("orthographic") {
+            self.warnings.warn(
+                &camera.path,
+                "rendering orthographic cameras with a perspective projection",
+            );
+        }
+        let focal_length = camera.float("focalLength", 50.0)?;
+        let horizontal = camera.float("horizontalAperture", 20.955)?;
+        let vertical = camera.float("verticalAperture", 15.2908)?;
+        if focal_length <= 0.0 || horizontal <= 0.0 || vertical <= 0.0 {
+            return Err(camera.invalid("the focal length and the apertures must be positive"));
+        }
+        let (width, height) = match resolution.and_then(Value::numbers).as_deref() {
+            Some(&[width, height]) if width >= 1.0 && height >= 1.0 => (width, height),
+            Some(_) => return Err(camera.invalid("the resolution must be two positive numbers")),
+            None => (640.0, (640.0 * vertical / horizontal).round().max(1.0)),
+        };
+        self.camera.image_width = width as i32;
+        self.camera.aspect_ratio = width / height;
+        // The aperture grows to the shape of the image
+        let half_height = (vertical / 2.0).max(horizontal / 2.0 / self.camera.aspect_ratio);
+        self.camera.vfov = 2.0 * (half_height / focal_length).atan().to_degrees();
+
+        // The camera looks down its -z axis with +y up
+        if transform.is_mirroring() {
+            self.warnings
+                .warn(&camera.path, "rendering the mirrored camera without mirroring");
+        }
+        self.camera.look_from = transform.point(Point::ORIGIN);
+        self.camera.look_at = transform.point(Point::new(0.0, 0.0, -1.0));
+        self.camera.vup = transform.vector(Vec3::new(0.0, 1.0, 0.0));
+        let f_stop = camera.float("fStop", 0.0)?;
+        let distance = camera.float("focusDistance", 0.0)?;
+        if f_stop > 0.0 && distance > 0.0 {
+            // Lens lengths are in tenths of a scene unit
+            let radius = focal_length / f_stop / 2.0 / 10.0;
+            self.camera.defocus_angle = 2.0 * (radius / distance).atan().to_degrees();
+            // Camera rays are as long as the focus distance
+            self.camera.look_at = transform.point(Point::new(0.0, 0.0, -distance));
+            self.camera.focus_dist = distance;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Scene> {
+        if !self.spheres.is_empty() {
+            self.world
+                .push(Box::new(SphereList::new(self.spheres, self.accelerator)));
+        }
+        Ok(Scene {
+            camera: self.camera,
+            world: self.world,
+            accelerator: self.accelerator,
+            files: vec![],
+            camera_keyframes: Keyframes::default(),
+            names: Names::default(),
+            light_links: LightLinks::default(),
+            lens: None,
+            aperture_mask: None,
+            lut: None,
+        })
+    }
+}
+
+// Path of the material a prim binds, for all purposes or for full renders.
+fn material_binding(prim: &Prim) -> Option<&str> {
+    ["material:binding", "material:binding:full", "material:binding:preview"]
+        .into_iter()
+        .find_map(|name| prim.target(name))
+}
+
+// Rotation of the unit quaternion `w + xi + yj + zk`, normalized first.
+fn quaternion(w: Float, x: Float, y: Float, z: Float) -> Option<Transform> {
+    let length = (w * w + x * x + y * y + z * z).sqrt();
+    if length == 0.0 {
+        return None;
+    }
+    let [w, x, y, z] = [w, x, y, z].map(|component| component / length);
+    Transform::from_matrix([
+        [
+            1.0 - 2.0 * (y * y + z * z),
+            2.0 * (x * y - w * z),
+            2.0 * (x * z + w * y),
+            0.0,
+        ],
+        [
+            2.0 * (x * y + w * z),
+            1.0 - 2.0 * (x * x + z * z),
+            2.0 * (y * z - w * x),
+            0.0,
+        ],
+        [
+            2.0 * (x * z - w * y),
+            2.0 * (y * z + w * x),
+            1.0 - 2.0 * (x * x + y * y),
+            0.0,
+        ],
+    ])
+}
+
+// Cube of edge `size` around the origin, with its faces pointing out.
+fn cube(size: Float) -> (Vec<Point>, Vec<[usize; 3]>) {
+    let half = size / 2.0;
+    let vertices = (0..8)
+        .map(|corner| {
+            let coordinate = |bit: usize| if corner & bit == 0 { -half } else { half };
+            Point::new(coordinate(1), coordinate(2), coordinate(4))
+        })
+        .collect();
+    let faces = [
+        [0, 2, 3, 1],
+        [4, 5, 7, 6],
+        [0, 1, 5, 4],
+        [2, 6, 7, 3],
+        [0, 4, 6, 2],
+        [1, 3, 7, 5],
+    ];
+    let triangles = faces.iter().flat_map(|&[a, b, c, d]| [[a, b, c], [a, c, d]]).collect();
+    (vertices, triangles)
+}
+
+// Disk of `radius` around the origin in the xy plane, facing +z.
+fn disk(radius: Float) -> (Vec<Point>, Vec<[usize; 3]>) {
+    const SEGMENTS: usize = 64;
+    let mut vertices = vec![Point::ORIGIN];
+    for segment in 0..SEGMENTS {
+        let angle = segment as Float / SEGMENTS as Float * 2.0 * consts::PI;
+        vertices.push(Point::new(radius * angle.cos(), radius * angle.sin(), 0.0));
+    }
+    let triangles = (0..SEGMENTS).map(|i| [0, i + 1, (i + 1) % SEGMENTS + 1]).collect();
+    (vertices, triangles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hittable::Hittable, ray::Ray};
+
+    const STAGE: &str = r#"#usda 1.0
+        def Xform "World"
+        {
+            def Camera "Camera"
+            {
+                float focalLength = 50
+                float horizontalAperture = 40
+                float verticalAperture = 20
+                double3 xformOp:translate = (0, 0, 5)
+                uniform token[] xformOpOrder = ["xformOp:translate"]
+            }
+            def Sphere "Ball"
+            {
+                double radius = 1
+                rel material:binding = </World/Looks/Blue>
+            }
+            def Scope "Looks"
+            {
+                def Material "Blue"
+                {
+                    token outputs:surface.connect = </World/Looks/Blue/Surface.outputs:surface>
+                    def Shader "Surface"
+                    {
+                        uniform token info:id = "UsdPreviewSurface"
+                        color3f inputs:diffuseColor = (0.2, 0.4, 0.6)
+                        float inputs:metallic = 1
+                        float inputs:roughness = 0
+                        token outputs:surface
+                    }
+                }
+            }
+        }
+    "#;
+
+    // Scene of a stage with the single layer `source`, read like a package so that no file is needed.
+    fn import(source: &str) -> Scene {
+        let file = "test.usda".to_string();
+        let mut stage = Stage {
+            root: file.clone(),
+            package: Some(HashMap::from([(file.clone(), source.as_bytes().to_vec())])),
+            layers: HashMap::new(),
+            warnings: Warnings::new(Path::new(&file)),
+        };
+        let root = stage.compose().expect("valid stage");
+        let mut importer = Importer::new(Accelerator::Bvh(BvhSettings::default()), stage.warnings);
+        importer.import(&root).expect("supported stage");
+        importer.finish().expect("valid scene")
+    }
+
+    #[test]
+    fn imports_the_camera() {
+        let camera = import(STAGE).camera;
+        // The image takes the shape of the apertures
+        assert_eq!((camera.image_width, camera.aspect_ratio), (640, 2.0));
+        assert!((camera.vfov - 2.0 * (10.0 as Float / 50.0).atan().to_degrees()).abs() < 1e-3);
+        assert!((camera.look_from - Point::new(0.0, 0.0, 5.0)).length() < 1e-4);
+        assert!((camera.look_at - Point::new(0.0, 0.0, 4.0)).length() < 1e-4);
+    }
+
+    #[test]
+    fn imports_shapes_with_their_material() {
+        let scene = import(STAGE);
+        let ray = Ray::new(Point::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let hit = scene
+            .world
+            .hit(ray, 0.0..Float::INFINITY)
+            .expect("sphere at the origin");
+        assert!((hit.t - 4.0).abs() < 1e-3);
+        assert_eq!(hit.material.name(), "metal");
+        let scatter = hit.material.scatter(ray, hit).expect("mirror reflection");
+        assert_eq!(scatter.attenuation, Color::new(0.2, 0.4, 0.6));
+    }
+}
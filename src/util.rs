@@ -1,20 +1,33 @@
-use std::f64::consts::PI;
+use std::cell::RefCell;
 
-use rand::Rng;
+use crate::{
+    float::{consts::PI, Float},
+    rng::Rng,
+};
+
+thread_local! {
+    static RNG: RefCell<Rng> = RefCell::new(Rng::from_entropy());
+}
 
 #[inline]
-pub fn degrees_to_radians(degrees: f64) -> f64 {
+pub fn degrees_to_radians(degrees: Float) -> Float {
     degrees * PI / 180.0
 }
 
+// Run `f` with the random number generator of the current thread.
+#[inline]
+pub fn with_rng<T>(f: impl FnOnce(&mut Rng) -> T) -> T {
+    RNG.with(|rng| f(&mut rng.borrow_mut()))
+}
+
 // Returns a random double value in [0, 1).
 #[inline]
-pub fn random_double() -> f64 {
-    rand::thread_rng().gen_range(0.0..1.0)
+pub fn random_double() -> Float {
+    with_rng(Rng::next_float)
 }
 
 // Returns a random double value in [min, max).
 #[inline]
-pub fn random_double_ranged(min: f64, max: f64) -> f64 {
-    rand::thread_rng().gen_range(min..max)
+pub fn random_double_ranged(min: Float, max: Float) -> Float {
+    min + (max - min) * random_double()
 }
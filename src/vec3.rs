@@ -1,18 +1,31 @@
 use std::{
     fmt::{Display, Formatter, Result},
-    ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign},
+    ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub, SubAssign},
 };
 
-use crate::util::{random_double, random_double_ranged};
+use crate::{
+    float::Float,
+    sampling,
+    util::{random_double, random_double_ranged, with_rng},
+};
 
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vec3 {
-    pub x: f64,
-    pub y: f64,
-    pub z: f64,
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
 }
 
-pub type Point = Vec3;
+// Position in space. Unlike a `Vec3` it has no length or direction: the difference of two points is a vector, a point
+// moved by a vector is a point, and points can't be added to each other or scaled.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Point {
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
+}
 
 impl Display for Vec3 {
     fn fmt(&self, f: &mut Formatter) -> Result {
@@ -20,18 +33,24 @@ impl Display for Vec3 {
     }
 }
 
+impl Display for Point {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{} {} {}", self.x, self.y, self.z)
+    }
+}
+
 impl Vec3 {
     pub const ZERO: Vec3 = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
 
-    pub fn new(x: f64, y: f64, z: f64) -> Vec3 {
+    pub const fn new(x: Float, y: Float, z: Float) -> Vec3 {
         Vec3 { x, y, z }
     }
 
-    pub fn length_squared(&self) -> f64 {
+    pub fn length_squared(&self) -> Float {
         self.x * self.x + self.y * self.y + self.z * self.z
     }
 
-    pub fn length(&self) -> f64 {
+    pub fn length(&self) -> Float {
         self.length_squared().sqrt()
     }
 
@@ -39,7 +58,7 @@ impl Vec3 {
         self / self.length()
     }
 
-    pub fn dot(a: Vec3, b: Vec3) -> f64 {
+    pub fn dot(a: Vec3, b: Vec3) -> Float {
         a.x * b.x + a.y * b.y + a.z * b.z
     }
 
@@ -55,7 +74,7 @@ impl Vec3 {
         Vec3::new(random_double(), random_double(), random_double())
     }
 
-    pub fn random_ranged(min: f64, max: f64) -> Vec3 {
+    pub fn random_ranged(min: Float, max: Float) -> Vec3 {
         Vec3::new(
             random_double_ranged(min, max),
             random_double_ranged(min, max),
@@ -63,36 +82,54 @@ impl Vec3 {
         )
     }
 
+    fn map(self, f: impl Fn(Float) -> Float) -> Vec3 {
+        Vec3::new(f(self.x), f(self.y), f(self.z))
+    }
+
+    // Component-wise minimum.
+    pub fn min(self, other: Vec3) -> Vec3 {
+        Vec3::new(self.x.min(other.x), self.y.min(other.y), self.z.min(other.z))
+    }
+
+    // Component-wise maximum.
+    pub fn max(self, other: Vec3) -> Vec3 {
+        Vec3::new(self.x.max(other.x), self.y.max(other.y), self.z.max(other.z))
+    }
+
+    // Every component clamped to [min, max].
+    pub fn clamp(self, min: Float, max: Float) -> Vec3 {
+        self.map(|c| c.clamp(min, max))
+    }
+
+    pub fn abs(self) -> Vec3 {
+        self.map(Float::abs)
+    }
+
+    // Component-wise square root.
+    pub fn sqrt(self) -> Vec3 {
+        self.map(Float::sqrt)
+    }
+
+    // Linear interpolation, `a` at t = 0 and `b` at t = 1.
+    pub fn lerp(a: Vec3, b: Vec3, t: Float) -> Vec3 {
+        (1.0 - t) * a + t * b
+    }
+
+    // Samples drawn from the generator of the current thread, see `sampling` for the versions taking a generator.
     pub fn random_in_unit_sphere() -> Vec3 {
-        loop {
-            let p = Vec3::random_ranged(-1.0, 1.0);
-            if p.length_squared() < 1.0 {
-                return p;
-            }
-        }
+        with_rng(sampling::random_in_unit_sphere)
     }
 
     pub fn random_in_unit_disk() -> Vec3 {
-        loop {
-            let p = Vec3::new(random_double_ranged(-1.0, 1.0), random_double_ranged(-1.0, 1.0), 0.0);
-            if p.length_squared() < 1.0 {
-                return p;
-            }
-        }
+        with_rng(sampling::random_in_unit_disk)
     }
 
     pub fn random_unit_vector() -> Vec3 {
-        Vec3::random_in_unit_sphere().normalize()
+        with_rng(sampling::random_unit_vector)
     }
 
     pub fn random_on_hemisphere(normal: Vec3) -> Vec3 {
-        let on_unit_sphere = Vec3::random_unit_vector();
-        if Vec3::dot(on_unit_sphere, normal) > 0.0 {
-            // In the same hemisphere as the normal
-            on_unit_sphere
-        } else {
-            -on_unit_sphere
-        }
+        with_rng(|rng| sampling::random_in_hemisphere(rng, normal))
     }
 
     // Return true if the vector is close to zero in all dimensions.
@@ -101,12 +138,16 @@ impl Vec3 {
         self.x.abs() < eps && self.y.abs() < eps && self.z.abs() < eps
     }
 
+    // Mirror `v` about the surface with unit normal `n`.
     pub fn reflect(v: Vec3, n: Vec3) -> Vec3 {
         v - 2.0 * Vec3::dot(v, n) * n
     }
 
-    pub fn refract(uv: Vec3, n: Vec3, refraction_ratio: f64) -> Vec3 {
-        let cos_theta = f64::min(Vec3::dot(-uv, n), 1.0);
+    // Bend the unit vector `uv` through the surface with unit normal `n` by Snell's law, `refraction_ratio` is the
+    // index of refraction of the side `uv` comes from over the one it enters. Callers check for total internal
+    // reflection themselves.
+    pub fn refract(uv: Vec3, n: Vec3, refraction_ratio: Float) -> Vec3 {
+        let cos_theta = Float::min(Vec3::dot(-uv, n), 1.0);
         let r_out_perpendicular = refraction_ratio * (uv + cos_theta * n);
         let r_out_parallel = -(1.0 - r_out_perpendicular.length_squared()).abs().sqrt() * n;
         r_out_perpendicular + r_out_parallel
@@ -115,7 +156,7 @@ impl Vec3 {
 
 // This macro helps us implement math operators on Vector3
 // in such a way that it handles binary operators on any
-// combination of Vec3, &Vec3 and f64.
+// combination of Vec3, &Vec3 and Float.
 macro_rules! impl_binary_operations {
   // $VectorType is something like `Vec3`
   // $Operation is something like `Add`
@@ -170,11 +211,11 @@ macro_rules! impl_binary_operations {
       }
     }
 
-    // Implement a + b where a is type &$VectorType and b is type f64
-    impl<'a> $Operation<f64> for &'a $VectorType {
+    // Implement a + b where a is type &$VectorType and b is type Float
+    impl<'a> $Operation<Float> for &'a $VectorType {
       type Output = $VectorType;
 
-      fn $op_fn(self, other: f64) -> $VectorType {
+      fn $op_fn(self, other: Float) -> $VectorType {
         $VectorType {
           x: self.x $op_symbol other,
           y: self.y $op_symbol other,
@@ -185,22 +226,22 @@ macro_rules! impl_binary_operations {
 
     // Implement a + b where...
     //
-    // a is $VectorType and b is f64
-    // a is f64 and b is $VectorType
-    // a is f64 and b is &$VectorType
+    // a is $VectorType and b is Float
+    // a is Float and b is $VectorType
+    // a is Float and b is &$VectorType
     //
     // In each case we forward the logic to the implementation
     // above.
-    impl $Operation<f64> for $VectorType {
+    impl $Operation<Float> for $VectorType {
       type Output = $VectorType;
 
       #[inline]
-      fn $op_fn(self, other: f64) -> $VectorType {
+      fn $op_fn(self, other: Float) -> $VectorType {
         &self $op_symbol other
       }
     }
 
-    impl $Operation<$VectorType> for f64 {
+    impl $Operation<$VectorType> for Float {
       type Output = $VectorType;
 
       #[inline]
@@ -209,7 +250,7 @@ macro_rules! impl_binary_operations {
       }
     }
 
-    impl<'a> $Operation<&'a $VectorType> for f64 {
+    impl<'a> $Operation<&'a $VectorType> for Float {
       type Output = $VectorType;
 
       #[inline]
@@ -298,3 +339,203 @@ impl_op_assign!(Vec3 MulAssign mul_assign *);
 
 impl_binary_operations!(Vec3 Div div /);
 impl_op_assign!(Vec3 DivAssign div_assign /);
+
+impl Point {
+    pub const ORIGIN: Point = Point::new(0.0, 0.0, 0.0);
+
+    pub const fn new(x: Float, y: Float, z: Float) -> Point {
+        Point { x, y, z }
+    }
+
+    // Vector from the origin to the point.
+    pub fn to_vec(self) -> Vec3 {
+        Vec3::new(self.x, self.y, self.z)
+    }
+
+    // Point halfway between `a` and `b`.
+    pub fn midpoint(a: Point, b: Point) -> Point {
+        Point::new(0.5 * (a.x + b.x), 0.5 * (a.y + b.y), 0.5 * (a.z + b.z))
+    }
+
+    // Component-wise minimum, the lower corner of the box around both points.
+    pub fn min(self, other: Point) -> Point {
+        Point::new(self.x.min(other.x), self.y.min(other.y), self.z.min(other.z))
+    }
+
+    // Component-wise maximum, the upper corner of the box around both points.
+    pub fn max(self, other: Point) -> Point {
+        Point::new(self.x.max(other.x), self.y.max(other.y), self.z.max(other.z))
+    }
+}
+
+impl Vec3 {
+    // Point the vector leads to from the origin.
+    pub fn to_point(self) -> Point {
+        Point::new(self.x, self.y, self.z)
+    }
+}
+
+impl Sub for Point {
+    type Output = Vec3;
+
+    #[inline]
+    fn sub(self, other: Point) -> Vec3 {
+        Vec3::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl Add<Vec3> for Point {
+    type Output = Point;
+
+    #[inline]
+    fn add(self, v: Vec3) -> Point {
+        Point::new(self.x + v.x, self.y + v.y, self.z + v.z)
+    }
+}
+
+impl Sub<Vec3> for Point {
+    type Output = Point;
+
+    #[inline]
+    fn sub(self, v: Vec3) -> Point {
+        Point::new(self.x - v.x, self.y - v.y, self.z - v.z)
+    }
+}
+
+impl AddAssign<Vec3> for Point {
+    #[inline]
+    fn add_assign(&mut self, v: Vec3) {
+        *self = *self + v;
+    }
+}
+
+impl SubAssign<Vec3> for Point {
+    #[inline]
+    fn sub_assign(&mut self, v: Vec3) {
+        *self = *self - v;
+    }
+}
+
+// Access to the coordinates by axis (0 is x, 1 is y, 2 is z) and conversions from and to arrays, for code that works
+// the same along every axis.
+macro_rules! impl_axes {
+    ($Type:ident) => {
+        impl Index<usize> for $Type {
+            type Output = Float;
+
+            #[inline]
+            fn index(&self, axis: usize) -> &Float {
+                match axis {
+                    0 => &self.x,
+                    1 => &self.y,
+                    2 => &self.z,
+                    _ => panic!("axis {axis} out of range"),
+                }
+            }
+        }
+
+        impl IndexMut<usize> for $Type {
+            #[inline]
+            fn index_mut(&mut self, axis: usize) -> &mut Float {
+                match axis {
+                    0 => &mut self.x,
+                    1 => &mut self.y,
+                    2 => &mut self.z,
+                    _ => panic!("axis {axis} out of range"),
+                }
+            }
+        }
+
+        impl From<[Float; 3]> for $Type {
+            fn from([x, y, z]: [Float; 3]) -> $Type {
+                $Type { x, y, z }
+            }
+        }
+
+        impl From<$Type> for [Float; 3] {
+            fn from(v: $Type) -> [Float; 3] {
+                [v.x, v.y, v.z]
+            }
+        }
+
+        impl IntoIterator for $Type {
+            type Item = Float;
+            type IntoIter = std::array::IntoIter<Float, 3>;
+
+            fn into_iter(self) -> Self::IntoIter {
+                <[Float; 3]>::from(self).into_iter()
+            }
+        }
+    };
+}
+
+impl_axes!(Vec3);
+impl_axes!(Point);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        hittable::Hit,
+        material::{Dielectric, Material},
+        ray::Ray,
+    };
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-6, "{a} is not {b}");
+    }
+
+    #[test]
+    fn reflect_mirrors_about_the_normal() {
+        let normal = Vec3::new(0.0, 1.0, 0.0);
+        assert_close(
+            Vec3::reflect(Vec3::new(1.0, -1.0, 0.0), normal),
+            Vec3::new(1.0, 1.0, 0.0),
+        );
+        // Straight on it comes back, parallel to the surface it passes by
+        assert_close(Vec3::reflect(-normal, normal), normal);
+        assert_close(
+            Vec3::reflect(Vec3::new(0.0, 0.0, 1.0), normal),
+            Vec3::new(0.0, 0.0, 1.0),
+        );
+    }
+
+    #[test]
+    fn refract_follows_snells_law() {
+        let normal = Vec3::new(0.0, 1.0, 0.0);
+        // Straight on it passes unbent
+        assert_close(Vec3::refract(-normal, normal, 1.0 / 1.5), -normal);
+        // Into glass at 45 degrees it bends towards the normal
+        let incoming = Vec3::new(1.0, -1.0, 0.0).normalize();
+        let refracted = Vec3::refract(incoming, normal, 1.0 / 1.5);
+        let sin = |v: Vec3| Vec3::cross(v, normal).length();
+        assert!((refracted.length() - 1.0).abs() < 1e-6);
+        assert!(refracted.y < 0.0 && refracted.x > 0.0);
+        assert!((sin(refracted) - sin(incoming) / 1.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn total_internal_reflection_reflects() {
+        // Leaving glass at 60 degrees is past the critical angle of about 42 degrees, all of the light is reflected
+        let glass = Dielectric::new(1.5);
+        let normal = Vec3::new(0.0, 1.0, 0.0);
+        let direction = Vec3::new(Float::sqrt(3.0), 1.0, 0.0).normalize();
+        let ray = Ray::new(Point::new(0.0, -1.0, 0.0), direction, 0.0);
+        let sin_theta = Vec3::cross(direction, normal).length();
+        assert!(1.5 * sin_theta > 1.0);
+        for _ in 0..100 {
+            let hit = Hit::new(ray, 1.0, normal, &glass);
+            assert!(!hit.front_face);
+            let scatter = glass.scatter(ray, hit).expect("glass scatters");
+            assert_close(scatter.ray.direction, Vec3::reflect(direction, -normal));
+        }
+    }
+
+    #[test]
+    fn near_zero_is_within_epsilon_on_every_axis() {
+        assert!(Vec3::new(0.0, 0.0, 0.0).near_zero());
+        assert!(Vec3::new(1e-9, -1e-9, 5e-9).near_zero());
+        assert!(!Vec3::new(1e-9, 1e-3, 0.0).near_zero());
+        assert!(!Vec3::new(0.0, 0.0, -1.0).near_zero());
+    }
+}
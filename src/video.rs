@@ -0,0 +1,54 @@
+use std::{
+    io::{self, BufWriter, Write},
+    path::Path,
+    process::{Child, ChildStdin, Command, Stdio},
+};
+
+// Frame rate of videos when --fps isn't given, the one shutter times of scenes assume unless they set another
+pub use rustracer::exposure::DEFAULT_FPS;
+use rustracer::{
+    float::Float,
+    output::{to_rgb8, Framebuffer},
+};
+
+// Video file encoded by ffmpeg, which reads the frames as raw RGB from a pipe. The codec is the default one of the
+// container the file name asks for, e.g. H.264 for .mp4 and VP9 for .webm, with 4:2:0 chroma so players can show it.
+pub struct Encoder {
+    ffmpeg: Child,
+    input: BufWriter<ChildStdin>,
+}
+
+impl Encoder {
+    // Start ffmpeg writing the video of `width` by `height` frames to `path`.
+    pub fn start(path: &Path, width: usize, height: usize, fps: Float) -> io::Result<Encoder> {
+        let mut ffmpeg = Command::new("ffmpeg")
+            .args(["-y", "-loglevel", "error", "-f", "rawvideo", "-pix_fmt", "rgb24"])
+            .args(["-s", &format!("{width}x{height}"), "-r", &fps.to_string(), "-i", "-"])
+            // 4:2:0 needs even sizes, odd ones get a black line
+            .args(["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2", "-pix_fmt", "yuv420p"])
+            .arg(path)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|err| io::Error::new(err.kind(), format!("can't run ffmpeg: {err}")))?;
+        let input = BufWriter::with_capacity(1 << 16, ffmpeg.stdin.take().expect("stdin is piped"));
+        Ok(Encoder { ffmpeg, input })
+    }
+
+    pub fn write_frame(&mut self, image: &Framebuffer) -> io::Result<()> {
+        for &color in image.pixels() {
+            self.input.write_all(&to_rgb8(color, image.color_space()))?;
+        }
+        Ok(())
+    }
+
+    // Close the pipe and wait for ffmpeg to finish the file.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.input.flush()?;
+        drop(self.input);
+        let status = self.ffmpeg.wait()?;
+        if !status.success() {
+            return Err(io::Error::other(format!("ffmpeg failed with {status}")));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,72 @@
+use std::{ops::Range, sync::Arc};
+
+use crate::{
+    aabb::Aabb,
+    color::Color,
+    float::Float,
+    hittable::{Hit, Hittable},
+    material::{Isotropic, Material},
+    ray::Ray,
+    util::random_double,
+    vec3::Vec3,
+};
+
+// Participating medium of the same density everywhere inside a closed boundary, like smoke or fog. Instead of hitting
+// a surface, a ray going through the medium scatters at a random distance, with the probability of getting through a
+// stretch falling exponentially with its length. The boundary must be closed and convex: the medium is taken to be
+// between the first two surfaces the ray meets.
+pub struct ConstantMedium {
+    boundary: Box<dyn Hittable>,
+    neg_inv_density: Float,
+    phase: Arc<dyn Material>, // how light scatters inside, usually `Isotropic`
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: Box<dyn Hittable>, density: Float, albedo: Color) -> ConstantMedium {
+        ConstantMedium {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase: Arc::new(Isotropic::new(albedo)),
+        }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, ray: Ray, t_range: Range<Float>) -> Option<Hit<'_>> {
+        // The ray may start inside the medium, the entry can be behind its origin
+        let entry = self.boundary.hit(ray, Float::NEG_INFINITY..Float::INFINITY)?;
+        let exit = self.boundary.hit(ray, entry.t + 0.0001..Float::INFINITY)?;
+        let t_entry = entry.t.max(t_range.start).max(0.0);
+        let t_exit = exit.t.min(t_range.end);
+        if t_entry >= t_exit {
+            return None;
+        }
+
+        let ray_length = ray.direction.length();
+        let distance_inside = (t_exit - t_entry) * ray_length;
+        let hit_distance = self.neg_inv_density * random_double().ln();
+        if hit_distance > distance_inside {
+            return None;
+        }
+
+        let t = t_entry + hit_distance / ray_length;
+        // There is no surface, the normal and the face are arbitrary
+        Some(Hit {
+            point: ray.at(t),
+            normal: Vec3::new(1.0, 0.0, 0.0),
+            t,
+            front_face: true,
+            material: self.phase.as_ref(),
+            uv: [0.0, 0.0],
+            tangent: Vec3::ZERO,
+            color: Color::WHITE,
+            motion: Vec3::ZERO,
+            object: 0,
+            group: 0,
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+}
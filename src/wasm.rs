@@ -0,0 +1,231 @@
+use std::path::Path;
+
+use crate::{
+    accel::AcceleratedList, bvh::BvhSettings, camera::Camera, error::Result, examples, film::Film, hittable::Hittable,
+    progress::Progress, scene::Scene,
+};
+
+// Rendering in a web page. The tracer compiles to WebAssembly (`wasm32-unknown-unknown`) with the default features
+// off, and `Renderer` works without a file system, a terminal or threads: the scene comes as text, the image is
+// rendered a few scanlines per call so the page stays responsive in between, and it's written into a buffer of the
+// caller. Built for wasm32, the functions of `exports` make it callable from JavaScript, see `web/index.html`.
+pub struct Renderer {
+    world: Box<dyn Hittable>,
+    camera: Camera,
+    film: Film,
+    next_row: i32, // first scanline not rendered yet
+}
+
+impl Renderer {
+    // Renderer of a scene file (see `scene.rs`) given as text. Files it refers to, e.g. meshes, can't be read.
+    pub fn parse(source: &str) -> Result<Renderer> {
+        Scene::parse(source, Path::new(""), BvhSettings::default()).map(Renderer::new)
+    }
+
+    // Renderer of the built-in scene `name` (see `examples.rs`).
+    pub fn example(name: &str) -> Option<Renderer> {
+        examples::load(name, BvhSettings::default()).map(Renderer::new)
+    }
+
+    fn new(mut scene: Scene) -> Renderer {
+        let world = Box::new(AcceleratedList::new(
+            std::mem::take(&mut scene.world),
+            scene.accelerator,
+        ));
+        let camera = Camera::new(scene.camera)
+            .with_next_frame(scene.camera_at(1.0))
+            .with_light_links(scene.light_links.clone())
+            .with_lens(scene.lens.as_deref())
+            .with_aperture_mask(scene.aperture_mask.clone())
+            .with_lut(scene.lut.clone());
+        Renderer {
+            world,
+            film: camera.film(),
+            next_row: camera.rows().start,
+            camera,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.camera.image_width() as usize
+    }
+
+    pub fn height(&self) -> usize {
+        self.camera.image_height() as usize
+    }
+
+    // Render the next `rows` scanlines with all their samples, returns whether the image is done. `progress` starts
+    // with the first call and advances by the scanlines.
+    pub fn render_rows(&mut self, rows: usize, progress: &dyn Progress) -> bool {
+        let all = self.camera.rows();
+        if self.next_row == all.start {
+            progress.start(all.len() as u64);
+        }
+        let end = (self.next_row + rows as i32).min(all.end);
+        if self.next_row < end {
+            let mut tile = self.film.band(self.next_row as usize..end as usize);
+            self.camera
+                .render_rows(self.world.as_ref(), &mut tile, self.next_row..end);
+            self.film.merge(&tile);
+            progress.advance((end - self.next_row) as u64);
+            self.next_row = end;
+            if end == all.end {
+                progress.finish();
+            }
+        }
+        self.next_row == all.end
+    }
+
+    // Write the image as far as it's rendered into `buffer` as 8-bit RGBA, see `Framebuffer::write_rgba8`.
+    pub fn write_rgba(&self, buffer: &mut [u8]) {
+        self.camera.develop(&self.film).write_rgba8(buffer);
+    }
+}
+
+// The renderer as functions of the WebAssembly module, e.g. from JavaScript:
+//
+//   const { instance } = await WebAssembly.instantiateStreaming(fetch("rustracer.wasm"), {
+//     env: { rustracer_progress: (done, total) => { ... } },
+//   });
+//   const { memory, rustracer_alloc, rustracer_example, rustracer_render, ... } = instance.exports;
+//
+// Strings and images are passed as a pointer and a length into the memory of the module, allocated with
+// `rustracer_alloc` and released with `rustracer_free`. The module holds a single renderer at a time.
+#[cfg(target_arch = "wasm32")]
+mod exports {
+    use std::{
+        cell::RefCell,
+        ptr, slice,
+        sync::atomic::{AtomicU64, Ordering},
+    };
+
+    use super::Renderer;
+    use crate::progress::Progress;
+
+    thread_local! {
+        static RENDERER: RefCell<Option<Renderer>> = const { RefCell::new(None) };
+        static ERROR: RefCell<String> = const { RefCell::new(String::new()) };
+    }
+
+    #[link(wasm_import_module = "env")]
+    extern "C" {
+        // Called by the module with the scanlines done and the scanlines of the whole render.
+        fn rustracer_progress(done: u32, total: u32);
+    }
+
+    // Progress reported to the page.
+    #[derive(Default)]
+    struct PageProgress {
+        done: AtomicU64,
+        total: AtomicU64,
+    }
+
+    impl Progress for PageProgress {
+        fn start(&self, total: u64) {
+            self.total.store(total, Ordering::Relaxed);
+        }
+
+        fn advance(&self, delta: u64) {
+            let done = self.done.fetch_add(delta, Ordering::Relaxed) + delta;
+            unsafe { rustracer_progress(done as u32, self.total.load(Ordering::Relaxed) as u32) };
+        }
+    }
+
+    thread_local! {
+        static PROGRESS: PageProgress = PageProgress::default();
+    }
+
+    #[no_mangle]
+    pub extern "C" fn rustracer_alloc(length: usize) -> *mut u8 {
+        Box::into_raw(vec![0u8; length].into_boxed_slice()).cast()
+    }
+
+    // # Safety
+    // `pointer` and `length` must come from `rustracer_alloc`, and the memory must not be used afterwards.
+    #[no_mangle]
+    pub unsafe extern "C" fn rustracer_free(pointer: *mut u8, length: usize) {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(pointer, length)));
+    }
+
+    // Load the scene file of `length` bytes of UTF-8 text at `source`. Returns false if it's invalid, see
+    // `rustracer_error`.
+    //
+    // # Safety
+    // `source` must point to `length` readable bytes.
+    #[no_mangle]
+    pub unsafe extern "C" fn rustracer_load(source: *const u8, length: usize) -> bool {
+        let loaded = std::str::from_utf8(slice::from_raw_parts(source, length))
+            .map_err(|err| err.to_string())
+            .and_then(|source| Renderer::parse(source).map_err(|err| err.to_string()));
+        replace(loaded)
+    }
+
+    // Load the built-in scene of the name of `length` bytes at `name`.
+    //
+    // # Safety
+    // `name` must point to `length` readable bytes.
+    #[no_mangle]
+    pub unsafe extern "C" fn rustracer_example(name: *const u8, length: usize) -> bool {
+        let name = String::from_utf8_lossy(slice::from_raw_parts(name, length));
+        replace(Renderer::example(&name).ok_or_else(|| format!("unknown scene '{name}'")))
+    }
+
+    fn replace(loaded: Result<Renderer, String>) -> bool {
+        PROGRESS.with(|progress| progress.done.store(0, Ordering::Relaxed));
+        match loaded {
+            Ok(renderer) => {
+                RENDERER.with(|slot| *slot.borrow_mut() = Some(renderer));
+                true
+            }
+            Err(message) => {
+                ERROR.with(|error| *error.borrow_mut() = message);
+                false
+            }
+        }
+    }
+
+    // Pointer to the message of the last error, of `rustracer_error_length` bytes.
+    #[no_mangle]
+    pub extern "C" fn rustracer_error() -> *const u8 {
+        ERROR.with(|error| error.borrow().as_ptr())
+    }
+
+    #[no_mangle]
+    pub extern "C" fn rustracer_error_length() -> usize {
+        ERROR.with(|error| error.borrow().len())
+    }
+
+    // Width of the image of the loaded scene, 0 without one.
+    #[no_mangle]
+    pub extern "C" fn rustracer_width() -> u32 {
+        with_renderer(|renderer| renderer.width() as u32).unwrap_or(0)
+    }
+
+    #[no_mangle]
+    pub extern "C" fn rustracer_height() -> u32 {
+        with_renderer(|renderer| renderer.height() as u32).unwrap_or(0)
+    }
+
+    // Render the next `rows` scanlines, returns whether the image is done.
+    #[no_mangle]
+    pub extern "C" fn rustracer_render(rows: u32) -> bool {
+        PROGRESS
+            .with(|progress| with_renderer(|renderer| renderer.render_rows(rows as usize, progress)))
+            .unwrap_or(true)
+    }
+
+    // Write the image as far as it's rendered into the `length` bytes at `buffer` as RGBA, see
+    // `Renderer::write_rgba`.
+    //
+    // # Safety
+    // `buffer` must point to `length` writable bytes.
+    #[no_mangle]
+    pub unsafe extern "C" fn rustracer_write_rgba(buffer: *mut u8, length: usize) {
+        let buffer = slice::from_raw_parts_mut(buffer, length);
+        with_renderer(|renderer| renderer.write_rgba(buffer));
+    }
+
+    fn with_renderer<T>(f: impl FnOnce(&mut Renderer) -> T) -> Option<T> {
+        RENDERER.with(|slot| slot.borrow_mut().as_mut().map(f))
+    }
+}
@@ -0,0 +1,194 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, IsTerminal, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use rustracer::{
+    accel::AcceleratedList,
+    arena::Arena,
+    camera::{Camera, CameraSettings, Focus},
+    debug, error,
+    float::Float,
+    hittable::Hittable,
+    info,
+    output::{write_image, Framebuffer, ImageFormat},
+    progress::NoProgress,
+    scene::Scene,
+};
+
+use crate::{
+    cli::Options,
+    interrupt::{self, INTERRUPTED, PAUSED},
+};
+
+// Set when a watched file changes or on Ctrl-C, it stops the render in progress.
+static CHANGED: AtomicBool = AtomicBool::new(false);
+
+// How often the files are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// Render the scene into `output` again and again as it's edited. The render is progressive, it starts with a single
+// sample per pixel and rewrites the image after every pass with more samples, until the samples per pixel of the
+// scene are done. Whenever the scene file or a file it refers to changes, the scene is loaded again and the render
+// starts over. Scenes that fail to load are reported and waited on. Enter in the terminal pauses the render and resumes
+// it. Runs until Ctrl-C.
+pub fn run(options: &Options, path: &Path, output: &Path) {
+    interrupt::install_handler();
+    if io::stdin().is_terminal() {
+        thread::spawn(|| {
+            for _ in io::stdin().lines() {
+                interrupt::toggle_pause();
+            }
+        });
+        info!("press Enter to pause or resume the render");
+    }
+    let mut files = vec![path.to_path_buf()];
+    loop {
+        CHANGED.store(false, Ordering::Relaxed);
+        let scene = Scene::load_with_overrides(path, options.bvh, &options.overrides).and_then(|scene| match &options
+            .camera_path
+        {
+            Some(camera_path) => scene.with_camera_path(camera_path),
+            None => Ok(scene),
+        });
+        let scene = scene.and_then(|scene| match &options.layer {
+            Some(layer) => scene.check_group(layer).map(|_| scene),
+            None => Ok(scene),
+        });
+        let scene = scene.and_then(|scene| match &options.focus {
+            Some(Focus::Object(name)) => scene.check_object(name).map(|_| scene),
+            _ => Ok(scene),
+        });
+        // Files of a scene that doesn't load are unknown, the ones of the last scene that did are watched instead
+        if let Ok(scene) = &scene {
+            files.clone_from(&scene.files);
+        }
+        let stamps = modification_times(&files);
+        let changed = thread::scope(|scope| {
+            let watcher = scope.spawn(|| wait_for_change(&files, &stamps));
+            match scene {
+                Ok(scene) => render(options, scene, output),
+                Err(err) => error!("{}: {err}", path.display()),
+            }
+            watcher.join().expect("watching files doesn't panic")
+        });
+        let Some(changed) = changed else {
+            return;
+        };
+        info!("{} changed, rendering again", changed.display());
+    }
+}
+
+// Render passes of growing sample counts, each one written out averaged with the ones before, until the samples of
+// the scene are done or the render is stopped.
+fn render(options: &Options, scene: Scene, output: &Path) {
+    let next_frame = scene.camera_at(1.0);
+    let world: Box<dyn Hittable> = if options.arena {
+        Box::new(Arena::new(scene.world, scene.accelerator))
+    } else {
+        Box::new(AcceleratedList::new(scene.world, scene.accelerator))
+    };
+    let settings = match &options.focus {
+        Some(focus) => match scene.camera.focus_distance(world.as_ref(), focus, 0.0) {
+            Ok(focus_dist) => scene.camera.with_focus_dist(focus_dist),
+            Err(err) => {
+                error!("{err}");
+                return;
+            }
+        },
+        None => scene.camera,
+    };
+    let total = settings.samples_per_pixel.max(1);
+    let mut done = 0;
+    let mut image: Option<Framebuffer> = None;
+    while done < total && !CHANGED.load(Ordering::Relaxed) {
+        // 1, 1, 2, 4, ... samples per pixel, so the first image comes quickly and every pass doubles the samples
+        let count = done.max(1).min(total - done);
+        let camera = Camera::new(CameraSettings {
+            samples_per_pixel: count,
+            ..settings
+        })
+        .with_next_frame(next_frame)
+        .with_light_links(scene.light_links.clone())
+        .with_lens(scene.lens.as_deref())
+        .with_aperture_mask(scene.aperture_mask.clone())
+        .with_lut(scene.lut.clone())
+        .with_packets(options.packets)
+        .with_wavefront(options.wavefront)
+        .with_ray_sorting(options.sort_rays)
+        .with_crop(options.crop)
+        .with_integrator(options.integrator)
+        .with_layer(options.layer.as_deref())
+        .with_stop(Some(&CHANGED))
+        .with_pause(Some(&PAUSED));
+        let (pass, info) = match camera.render_image(world.as_ref(), &NoProgress) {
+            Ok(result) => result,
+            Err(err) => {
+                error!("{err}");
+                return;
+            }
+        };
+        if info.stopped {
+            return;
+        }
+        // Average the passes weighted by their samples
+        let image = image.get_or_insert_with(|| Framebuffer::new(pass.width(), pass.height()));
+        let weight = count as Float / (done + count) as Float;
+        for (pixel, &sample) in image.rows_mut(0, pass.height()).iter_mut().zip(pass.pixels()) {
+            *pixel = *pixel * (1.0 - weight) + sample * weight;
+        }
+        done += count;
+        if let Err(err) = write(image, output) {
+            error!("{}: {err}", output.display());
+            return;
+        }
+        debug!("wrote {} with {done} samples per pixel", output.display());
+    }
+    info!(
+        "finished {} with {done} samples per pixel, watching for changes",
+        output.display()
+    );
+}
+
+// Write the image next to `output` first and then move it over, so viewers never see a partial image.
+fn write(image: &Framebuffer, output: &Path) -> io::Result<()> {
+    let mut temporary = output.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+    let mut out = BufWriter::new(File::create(&temporary)?);
+    write_image(image, ImageFormat::of_path(output), &mut out)?;
+    out.flush()?;
+    drop(out);
+    fs::rename(&temporary, output)
+}
+
+// Poll the files until one of them changes and return it, or None on Ctrl-C. Either way `CHANGED` is set.
+fn wait_for_change(files: &[PathBuf], stamps: &[Option<SystemTime>]) -> Option<PathBuf> {
+    let changed = loop {
+        if INTERRUPTED.load(Ordering::Relaxed) {
+            break None;
+        }
+        let changed = modification_times(files)
+            .iter()
+            .zip(stamps)
+            .position(|(now, then)| now != then);
+        if let Some(index) = changed {
+            break Some(files[index].clone());
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+    CHANGED.store(true, Ordering::Relaxed);
+    changed
+}
+
+// Times the files were last modified, None for files that are missing.
+fn modification_times(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|file| fs::metadata(file).and_then(|metadata| metadata.modified()).ok())
+        .collect()
+}
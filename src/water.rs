@@ -0,0 +1,264 @@
+use std::{ops::Range, sync::Arc};
+
+use crate::{
+    aabb::Aabb,
+    float::{consts::PI, Float},
+    hittable::{Hit, Hittable},
+    material::Material,
+    range::Interval,
+    ray::Ray,
+    rng::Rng,
+    vec3::{Point, Vec3},
+};
+
+// Acceleration of gravity in units per second squared, for scenes in meters. Deep water waves of a wavenumber k
+// travel with the angular frequency √(g k).
+const GRAVITY: Float = 9.81;
+
+// Most times the crossing of a ray with the surface is refined, it's usually found to the precision of the floats in
+// far fewer.
+const REFINEMENTS: usize = 16;
+
+// Most steps a ray takes over the surface, grazing rays over large water give up after these.
+const MAX_STEPS: usize = 2000;
+
+// Single Gerstner wave: the points of the surface go around in circles, which sharpens the crests and flattens the
+// troughs of the sine wave they make.
+#[derive(Copy, Clone, Debug)]
+pub struct Wave {
+    pub direction: [Float; 2], // unit direction it travels in, in x and z
+    pub wavelength: Float,
+    pub amplitude: Float,
+    pub steepness: Float, // 0 for a sine wave, up to 1 for crests coming to a point
+    pub phase: Float,     // in radians
+}
+
+impl Wave {
+    fn wavenumber(&self) -> Float {
+        2.0 * PI / self.wavelength
+    }
+}
+
+// Waves raised by a steady wind: `count` waves of decreasing wavelength from `wavelength` down, with amplitudes in
+// proportion to their wavelengths and directions spread around the wind. The same seed gives the same waves.
+#[derive(Copy, Clone, Debug)]
+pub struct WindWaves {
+    pub count: usize,
+    pub wavelength: Float, // of the longest wave
+    pub amplitude: Float,  // of the longest wave
+    pub steepness: Float,  // of all waves together, 0 to 1
+    pub direction: Float,  // of the wind in degrees, 0 along +x and 90 along -z
+    pub spread: Float,     // of the directions of the waves around the wind in degrees
+    pub seed: u64,
+}
+
+impl Default for WindWaves {
+    fn default() -> WindWaves {
+        WindWaves {
+            count: 8,
+            wavelength: 1.0,
+            amplitude: 0.02,
+            steepness: 0.5,
+            direction: 0.0,
+            spread: 60.0,
+            seed: 0,
+        }
+    }
+}
+
+impl WindWaves {
+    pub fn waves(&self) -> Vec<Wave> {
+        let mut rng = Rng::seed_from_u64(self.seed);
+        let mut wavelength = self.wavelength;
+        (0..self.count)
+            .map(|_| {
+                let angle = (self.direction + self.spread * (rng.next_float() - 0.5)).to_radians();
+                let wave = Wave {
+                    direction: [angle.cos(), -angle.sin()],
+                    wavelength,
+                    amplitude: self.amplitude * wavelength / self.wavelength,
+                    steepness: self.steepness.clamp(0.0, 1.0) / self.count as Float,
+                    phase: 2.0 * PI * rng.next_float(),
+                };
+                // Irregular steps keep the waves from adding up to a visible pattern
+                wavelength *= rng.next_float_ranged(0.55, 0.8);
+                wave
+            })
+            .collect()
+    }
+}
+
+// Surface of water moved by Gerstner waves, a rectangle `width` along x and `depth` along z centered on the origin at
+// rest. The waves move with the time of the rays, so animations and motion blur show them rolling. A ray finds the
+// surface by marching over it in steps no larger than the steepest slope of the waves allows, then refining the
+// crossing, instead of through a mesh of the displaced surface.
+pub struct Water {
+    waves: Vec<Wave>,
+    width: Float,
+    depth: Float,
+    seconds_per_frame: Float, // how far the waves move on from one frame to the next
+    slope: Float,             // steepest the surface can be
+    material: Arc<dyn Material>,
+    bounds: Aabb,
+}
+
+impl Water {
+    pub fn new(width: Float, depth: Float, waves: Vec<Wave>, material: Arc<dyn Material>) -> Water {
+        let height = waves.iter().map(|wave| wave.amplitude).sum::<Float>().max(1e-4);
+        // The circles of the points squeeze the surface together at the crests by up to the total steepness
+        let squeeze = waves.iter().map(|wave| wave.steepness).sum::<Float>().min(0.9);
+        let slope = waves
+            .iter()
+            .map(|wave| wave.wavenumber() * wave.amplitude)
+            .sum::<Float>()
+            / (1.0 - squeeze);
+        Water {
+            waves,
+            width,
+            depth,
+            seconds_per_frame: 1.0 / 24.0,
+            slope,
+            material,
+            bounds: Aabb::new(
+                Point::new(-width / 2.0, -height, -depth / 2.0),
+                Point::new(width / 2.0, height, depth / 2.0),
+            ),
+        }
+    }
+
+    // Play the waves at `speed` times their real speed in an animation of `fps` frames per second.
+    pub fn with_speed(self, speed: Float, fps: Float) -> Water {
+        Water {
+            seconds_per_frame: speed / fps,
+            ..self
+        }
+    }
+
+    // Phase of every wave at a point of the surface at rest at `seconds`.
+    fn phases(&self, x: Float, z: Float, seconds: Float) -> impl Iterator<Item = (&Wave, Float)> {
+        self.waves.iter().map(move |wave| {
+            let k = wave.wavenumber();
+            let omega = (GRAVITY * k).sqrt();
+            let [dx, dz] = wave.direction;
+            (wave, k * (dx * x + dz * z) - omega * seconds + wave.phase)
+        })
+    }
+
+    // Where a point of the surface at rest at x and z is moved by the waves.
+    fn displaced(&self, x: Float, z: Float, seconds: Float) -> Point {
+        let mut point = Point::new(x, 0.0, z);
+        for (wave, phase) in self.phases(x, z, seconds) {
+            let circle = wave.steepness / wave.wavenumber() * phase.cos();
+            point.x += circle * wave.direction[0];
+            point.z += circle * wave.direction[1];
+            point.y += wave.amplitude * phase.sin();
+        }
+        point
+    }
+
+    // Height of the surface above x and z, and the point at rest that moved there. The waves move the points
+    // sideways as well, which is undone by a few fixed-point iterations.
+    fn surface(&self, x: Float, z: Float, seconds: Float) -> (Float, [Float; 2]) {
+        let mut rest = [x, z];
+        for _ in 0..4 {
+            let moved = self.displaced(rest[0], rest[1], seconds);
+            rest = [rest[0] + x - moved.x, rest[1] + z - moved.z];
+        }
+        (self.displaced(rest[0], rest[1], seconds).y, rest)
+    }
+
+    // Unit normal of the surface at the point that was at rest at x and z.
+    fn normal(&self, [x, z]: [Float; 2], seconds: Float) -> Vec3 {
+        let mut normal = Vec3::new(0.0, 1.0, 0.0);
+        for (wave, phase) in self.phases(x, z, seconds) {
+            let ka = wave.wavenumber() * wave.amplitude;
+            normal.x -= wave.direction[0] * ka * phase.cos();
+            normal.z -= wave.direction[1] * ka * phase.cos();
+            normal.y -= wave.steepness * phase.sin();
+        }
+        normal.normalize()
+    }
+
+    // How far the ray is above the surface at `t`, negative below it.
+    fn above(&self, ray: Ray, t: Float, seconds: Float) -> Float {
+        let point = ray.at(t);
+        point.y - self.surface(point.x, point.z, seconds).0
+    }
+}
+
+impl Hittable for Water {
+    fn hit(&self, ray: Ray, t_range: Range<Float>) -> Option<Hit<'_>> {
+        let inv_direction = Vec3::new(1.0 / ray.direction.x, 1.0 / ray.direction.y, 1.0 / ray.direction.z);
+        let Range { start, end } = self.bounds.clip(ray, inv_direction, &t_range)?;
+        let seconds = ray.time * self.seconds_per_frame;
+
+        // The height above the surface changes along the ray by at most this much per unit of t, so a step of the
+        // height divided by it can't pass the surface
+        let horizontal = (ray.direction.x.powi(2) + ray.direction.z.powi(2)).sqrt();
+        let rate = ray.direction.y.abs() + self.slope * horizontal;
+        // Rays close to the surface take steps of at least a small part of the shortest wave
+        let shortest = self
+            .waves
+            .iter()
+            .map(|wave| wave.wavelength)
+            .fold(Float::INFINITY, Float::min);
+        let min_step = match shortest.is_finite() {
+            true => 0.01 * shortest / ray.direction.length(),
+            false => 1e-6 * (end - start),
+        };
+
+        let (mut t0, mut height0) = (start, self.above(ray, start, seconds));
+        let side = height0 > 0.0;
+        let mut crossing = None;
+        for _ in 0..MAX_STEPS {
+            if t0 >= end {
+                break;
+            }
+            let t1 = (t0 + (height0.abs() / rate).max(min_step)).min(end);
+            let height1 = self.above(ray, t1, seconds);
+            if (height1 > 0.0) != side {
+                crossing = Some((t0, height0, t1, height1));
+                break;
+            }
+            (t0, height0) = (t1, height1);
+        }
+
+        // Refine the crossing by false position, halving the height at an end that stays twice in a row (the
+        // Illinois method) so that it closes in from both sides
+        let (mut t0, mut height0, mut t1, mut height1) = crossing?;
+        let tolerance = 1e-9 * self.bounds.extent().y;
+        let mut kept_first = None;
+        for _ in 0..REFINEMENTS {
+            let t = (t0 * height1 - t1 * height0) / (height1 - height0);
+            let height = self.above(ray, t, seconds);
+            if (height > 0.0) == side {
+                (t0, height0) = (t, height);
+                if kept_first == Some(false) {
+                    height1 *= 0.5;
+                }
+                kept_first = Some(false);
+            } else {
+                (t1, height1) = (t, height);
+                if kept_first == Some(true) {
+                    height0 *= 0.5;
+                }
+                kept_first = Some(true);
+            }
+            if height.abs() < tolerance {
+                break;
+            }
+        }
+        let t = (t0 * height1 - t1 * height0) / (height1 - height0);
+        if !t_range.surrounds(t) {
+            return None;
+        }
+        let point = ray.at(t);
+        let (_, rest) = self.surface(point.x, point.z, seconds);
+        let uv = [point.x / self.width + 0.5, 0.5 - point.z / self.depth];
+        Some(Hit::new(ray, t, self.normal(rest, seconds), self.material.as_ref()).with_uv(uv))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounds
+    }
+}
@@ -0,0 +1,13 @@
+use std::process::Command;
+
+// Render the scenes of `scenes/golden` and compare them with their reference images, see `src/golden.rs`. The
+// images of the scenes that fail are written next to their references.
+#[test]
+fn golden_images_match_their_references() {
+    let output = Command::new(env!("CARGO_BIN_EXE_rustracer"))
+        .arg("--golden")
+        .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/scenes/golden"))
+        .output()
+        .expect("the renderer runs");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}